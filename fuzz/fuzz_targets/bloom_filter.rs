@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lsmer::BloomFilter;
+
+// A filter rebuilt from stored parts must answer lookups without reading
+// past its bits, whatever sizes it was given
+fuzz_target!(|input: (Vec<u8>, u32, u8, Vec<String>)| {
+    let (bits, size_bits, num_hashes, keys) = input;
+    let filter = BloomFilter::<String>::from_parts(bits, size_bits as usize, num_hashes as usize);
+    for key in keys.iter().take(64) {
+        let _ = filter.may_contain(key);
+    }
+});
@@ -0,0 +1,35 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lsmer::sstable::SSTableReader;
+use std::io::Write;
+
+// Opening and reading an SSTable must fail with an error rather than panic
+// or allocate without bound, whatever the file holds
+fuzz_target!(|data: &[u8]| {
+    let Ok(mut file) = tempfile::NamedTempFile::new() else {
+        return;
+    };
+    if file.write_all(data).and_then(|_| file.flush()).is_err() {
+        return;
+    }
+    let Some(path) = file.path().to_str() else {
+        return;
+    };
+
+    let Ok(mut reader) = SSTableReader::open(path) else {
+        return;
+    };
+    let _ = reader.get("");
+    let _ = reader.get("key");
+    if let Ok(entries) = reader.iter() {
+        for entry in entries.take(1024) {
+            let _ = entry;
+        }
+    }
+    if let Ok(entries) = reader.into_entries() {
+        for entry in entries.take(1024) {
+            let _ = entry;
+        }
+    }
+});
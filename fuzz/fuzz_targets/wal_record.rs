@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lsmer::{Operation, WalRecord};
+
+// Decoding a WAL record, and the operation it carries, must fail with an
+// error rather than panic on any input
+fuzz_target!(|data: &[u8]| {
+    if let Ok(record) = WalRecord::deserialize(data) {
+        let _ = Operation::from_record(record);
+    }
+});
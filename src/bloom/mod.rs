@@ -1,3 +1,4 @@
+use rayon::prelude::*;
 use siphasher::sip::SipHasher;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
@@ -5,7 +6,28 @@ use std::marker::PhantomData;
 // Create the partitioned module
 mod partitioned;
 // Re-export the PartitionedBloomFilter
-pub use partitioned::PartitionedBloomFilter;
+pub use partitioned::{PartitionedBloomBuilder, PartitionedBloomFilter};
+
+/// Compute the two hashes an item's bits are derived from with double
+/// hashing. Borrowed forms hash alike, so a `&str` finds a `String`'s bits.
+fn hash_pair<Q: Hash + ?Sized>(item: &Q) -> (u64, u64) {
+    // Use SipHasher with different keys for the two hash functions
+    // SipHasher takes two u64 values as keys (k0 and k1)
+    let mut hasher1 = SipHasher::new_with_keys(0x0123456789ABCDEF, 0xFEDCBA9876543210);
+    let mut hasher2 = SipHasher::new_with_keys(0xABCDEF0123456789, 0x0123456789ABCDEF);
+
+    // Hash the item with each hasher
+    item.hash(&mut hasher1);
+    let h1 = hasher1.finish();
+
+    item.hash(&mut hasher2);
+    let h2 = hasher2.finish();
+
+    // Ensure h2 is odd to ensure we hit all positions when using double hashing
+    let h2 = if h2.is_multiple_of(2) { h2 + 1 } else { h2 };
+
+    (h1, h2)
+}
 
 /// A Bloom filter implementation using double hashing technique
 /// to reduce the number of required hash functions.
@@ -80,9 +102,8 @@ impl<T: Hash> BloomFilter<T> {
         // Calculate optimal size in bits
         // m = -n * ln(p) / (ln(2)^2)
         let ln2_squared = std::f64::consts::LN_2.powi(2);
-        let mut size_bits = (-1.0 * (expected_elements as f64) * false_positive_rate.ln()
-            / ln2_squared)
-            .ceil() as usize;
+        let mut size_bits =
+            (-(expected_elements as f64) * false_positive_rate.ln() / ln2_squared).ceil() as usize;
 
         // Safety cap on maximum bit size
         const MAX_BLOOM_FILTER_BITS: usize = 100_000_000; // 100 million bits (12.5MB)
@@ -101,7 +122,7 @@ impl<T: Hash> BloomFilter<T> {
         num_hashes = num_hashes.clamp(1, MAX_HASH_FUNCTIONS);
 
         // Size in bytes (rounded up)
-        let size_bytes = (size_bits + 7) / 8;
+        let size_bytes = size_bits.div_ceil(8);
 
         BloomFilter {
             bits: vec![0; size_bytes],
@@ -127,14 +148,49 @@ impl<T: Hash> BloomFilter<T> {
     /// assert!(filter.may_contain(&"test"));
     /// ```
     pub fn insert(&mut self, item: &T) {
-        let (h1, h2) = self.get_hash_values(item);
+        self.insert_hash_pair(self.get_hash_values(item));
+    }
 
+    /// Set the bits for an item hashed with `hash_pair`
+    fn insert_hash_pair(&mut self, (h1, h2): (u64, u64)) {
         for i in 0..self.num_hashes {
             let index = self.get_bit_index(h1, h2, i);
             self.set_bit(index);
         }
     }
 
+    /// Inserts elements in bulk, hashing them in parallel.
+    ///
+    /// Hashing dominates the cost of an insert, so the hashes are computed
+    /// across threads and the bits set afterwards on the calling thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - Slice of elements to insert
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsmer::bloom::BloomFilter;
+    ///
+    /// let mut filter: BloomFilter<&str> = BloomFilter::new(100, 0.01);
+    /// filter.insert_bulk(&["apple", "banana", "cherry"]);
+    /// assert!(filter.may_contain(&"banana"));
+    /// ```
+    pub fn insert_bulk(&mut self, items: &[T])
+    where
+        T: Sync,
+    {
+        let hashes: Vec<(u64, u64)> = items
+            .par_iter()
+            .map(|item| self.get_hash_values(item))
+            .collect();
+
+        for hash_pair in hashes {
+            self.insert_hash_pair(hash_pair);
+        }
+    }
+
     /// Checks if an element might be in the Bloom filter.
     ///
     /// Returns `true` if the element might be in the set, `false` if it definitely is not.
@@ -169,22 +225,7 @@ impl<T: Hash> BloomFilter<T> {
 
     /// Compute two different hash values for the item, to be used with the double hashing technique
     fn get_hash_values(&self, item: &T) -> (u64, u64) {
-        // Use SipHasher with different keys for the two hash functions
-        // SipHasher takes two u64 values as keys (k0 and k1)
-        let mut hasher1 = SipHasher::new_with_keys(0x0123456789ABCDEF, 0xFEDCBA9876543210);
-        let mut hasher2 = SipHasher::new_with_keys(0xABCDEF0123456789, 0x0123456789ABCDEF);
-
-        // Hash the item with each hasher
-        item.hash(&mut hasher1);
-        let h1 = hasher1.finish();
-
-        item.hash(&mut hasher2);
-        let h2 = hasher2.finish();
-
-        // Ensure h2 is odd to ensure we hit all positions when using double hashing
-        let h2 = if h2 % 2 == 0 { h2 + 1 } else { h2 };
-
-        (h1, h2)
+        hash_pair(item)
     }
 
     /// Calculate bit index using double hashing formula: (h1 + i * h2) % size
@@ -391,10 +432,18 @@ impl<T: Hash> BloomFilter<T> {
     /// ```
     pub fn from_parts(bits: Vec<u8>, size_bits: usize, num_hashes: usize) -> Self {
         // Safety checks
+        // Never address bits past the end of the array
+        let available_bits = bits.len() * 8;
         let size_bits = if size_bits == 0 {
-            bits.len() * 8 // Use actual bit array size if size_bits is invalid
+            available_bits // Use actual bit array size if size_bits is invalid
+        } else {
+            size_bits.min(100_000_000).min(available_bits) // Cap at 100 million bits
+        };
+        // An empty array rules nothing out rather than dividing by zero
+        let (bits, size_bits) = if size_bits == 0 {
+            (vec![0xff], 8)
         } else {
-            std::cmp::min(size_bits, 100_000_000) // Cap at 100 million bits
+            (bits, size_bits)
         };
 
         let num_hashes = num_hashes.clamp(1, 20); // 1-20 hash functions
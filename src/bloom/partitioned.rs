@@ -1,10 +1,126 @@
 use rayon::prelude::*;
 use siphasher::sip::SipHasher;
+use std::borrow::Borrow;
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 use std::sync::Arc;
 
-use super::BloomFilter;
+use super::{BloomFilter, hash_pair};
+
+/// Number of partitions a filter asked for `num_partitions` gets: one per
+/// CPU core for 0, and at most 64
+fn partition_count(num_partitions: usize) -> usize {
+    let num_partitions = if num_partitions == 0 {
+        num_cpus::get()
+    } else {
+        num_partitions
+    };
+    std::cmp::min(num_partitions, 64)
+}
+
+/// Which of `num_partitions` partitions an item belongs to
+fn partition_of<Q: Hash + ?Sized>(item: &Q, num_partitions: usize) -> usize {
+    let mut hasher = SipHasher::new_with_keys(0xDEADBEEF, 0xCAFEBABE);
+    item.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    // Use modulo to determine partition
+    (hash as usize) % num_partitions
+}
+
+/// Collects items for a partitioned Bloom filter whose size is not known
+/// until every item has been seen.
+///
+/// Only each item's two 64-bit hashes are kept, grouped by partition, so
+/// the items themselves need not stay in memory. `finish` sizes the filter
+/// for the items collected and fills its partitions in parallel.
+///
+/// # Examples
+///
+/// ```
+/// use lsmer::bloom::PartitionedBloomBuilder;
+///
+/// let mut builder = PartitionedBloomBuilder::<String>::new(4);
+/// builder.insert("apple");
+/// builder.insert_bulk(&["banana".to_string(), "cherry".to_string()]);
+/// let filter = builder.finish(0.01);
+/// assert!(filter.may_contain(&"banana".to_string()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct PartitionedBloomBuilder<T> {
+    /// Hash pairs of the items collected, by partition
+    hashes: Vec<Vec<(u64, u64)>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Hash + Send + Sync> PartitionedBloomBuilder<T> {
+    /// Create a builder for a filter with `num_partitions` partitions, or
+    /// one per CPU core if 0
+    pub fn new(num_partitions: usize) -> Self {
+        Self {
+            hashes: vec![Vec::new(); partition_count(num_partitions)],
+            _marker: PhantomData,
+        }
+    }
+
+    /// Add an item, or a borrowed form of one
+    pub fn insert<Q>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        let partition = partition_of(item, self.hashes.len());
+        self.hashes[partition].push(hash_pair(item));
+    }
+
+    /// Add items in bulk, hashing them in parallel
+    pub fn insert_bulk<Q>(&mut self, items: &[Q])
+    where
+        T: Borrow<Q>,
+        Q: Hash + Sync,
+    {
+        let num_partitions = self.hashes.len();
+        let hashed: Vec<(usize, (u64, u64))> = items
+            .par_iter()
+            .map(|item| (partition_of(item, num_partitions), hash_pair(item)))
+            .collect();
+        for (partition, hash_pair) in hashed {
+            self.hashes[partition].push(hash_pair);
+        }
+    }
+
+    /// Number of items collected
+    pub fn len(&self) -> usize {
+        self.hashes.iter().map(Vec::len).sum()
+    }
+
+    /// Whether no items have been collected
+    pub fn is_empty(&self) -> bool {
+        self.hashes.iter().all(Vec::is_empty)
+    }
+
+    /// Forget every item collected
+    pub fn clear(&mut self) {
+        self.hashes.iter_mut().for_each(Vec::clear);
+    }
+
+    /// Build a filter sized for the items collected, filling its partitions
+    /// in parallel
+    pub fn finish(self, false_positive_rate: f64) -> PartitionedBloomFilter<T> {
+        let mut filter =
+            PartitionedBloomFilter::new(self.len(), false_positive_rate, self.hashes.len());
+        filter
+            .partitions
+            .par_iter_mut()
+            .zip(self.hashes)
+            .for_each(|(partition, hashes)| {
+                for hash_pair in hashes {
+                    partition.insert_hash_pair(hash_pair);
+                }
+            });
+        filter
+    }
+}
 
 /// A partitioned Bloom filter that enables parallel lookups
 ///
@@ -45,15 +161,9 @@ impl<T: Hash + Send + Sync> PartitionedBloomFilter<T> {
     /// let filter: PartitionedBloomFilter<&str> = PartitionedBloomFilter::new(1000, 0.01, 4);
     /// ```
     pub fn new(expected_elements: usize, false_positive_rate: f64, num_partitions: usize) -> Self {
-        // Use at least 1 partition, default to # of CPUs if 0
-        let num_partitions = if num_partitions == 0 {
-            num_cpus::get()
-        } else {
-            num_partitions
-        };
-
-        // Cap number of partitions to a reasonable max
-        let num_partitions = std::cmp::min(num_partitions, 64);
+        // Use at least 1 partition, default to # of CPUs if 0, and cap the
+        // number of partitions to a reasonable max
+        let num_partitions = partition_count(num_partitions);
 
         // Calculate elements per partition
         let elements_per_partition = expected_elements.div_ceil(num_partitions);
@@ -74,12 +184,7 @@ impl<T: Hash + Send + Sync> PartitionedBloomFilter<T> {
 
     /// Determines which partition an item belongs to
     fn get_partition_index(&self, item: &T) -> usize {
-        let mut hasher = SipHasher::new_with_keys(0xDEADBEEF, 0xCAFEBABE);
-        item.hash(&mut hasher);
-        let hash = hasher.finish();
-
-        // Use modulo to determine partition
-        (hash as usize) % self.num_partitions
+        partition_of(item, self.num_partitions)
     }
 
     /// Inserts an item into the appropriate partition
@@ -117,20 +222,54 @@ impl<T: Hash + Send + Sync> PartitionedBloomFilter<T> {
     /// filter.insert_bulk(&["apple", "banana", "cherry"]);
     /// ```
     pub fn insert_bulk(&mut self, items: &[T]) {
-        // Group items by partition to minimize lock contention
-        let mut partition_items: Vec<Vec<&T>> = vec![Vec::new(); self.num_partitions];
+        // Route each item to its partition in parallel, then group by partition
+        let indices: Vec<usize> = items
+            .par_iter()
+            .map(|item| self.get_partition_index(item))
+            .collect();
 
-        for item in items {
-            let idx = self.get_partition_index(item);
+        let mut partition_items: Vec<Vec<&T>> = vec![Vec::new(); self.num_partitions];
+        for (item, idx) in items.iter().zip(indices) {
             partition_items[idx].push(item);
         }
 
-        // Insert items into their respective partitions
-        for (idx, items) in partition_items.into_iter().enumerate() {
-            for item in items {
-                self.partitions[idx].insert(item);
-            }
-        }
+        // Partitions are independent, so each one is filled on its own thread
+        self.partitions
+            .par_iter_mut()
+            .zip(partition_items)
+            .for_each(|(partition, items)| {
+                for item in items {
+                    partition.insert(item);
+                }
+            });
+    }
+
+    /// Builds a filter sized for a complete set of keys, filling the
+    /// partitions in parallel
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - All items the filter should contain
+    /// * `false_positive_rate` - Target false positive rate
+    /// * `num_partitions` - Number of partitions (0 means one per CPU core)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsmer::bloom::PartitionedBloomFilter;
+    ///
+    /// let keys = ["apple", "banana", "cherry"];
+    /// let filter = PartitionedBloomFilter::from_keys_parallel(&keys, 0.01, 4);
+    /// assert!(keys.iter().all(|key| filter.may_contain(key)));
+    /// ```
+    pub fn from_keys_parallel(
+        items: &[T],
+        false_positive_rate: f64,
+        num_partitions: usize,
+    ) -> Self {
+        let mut builder = PartitionedBloomBuilder::new(num_partitions);
+        builder.insert_bulk(items);
+        builder.finish(false_positive_rate)
     }
 
     /// Checks if an item might be in the filter
@@ -274,6 +413,47 @@ impl<T: Hash + Send + Sync> PartitionedBloomFilter<T> {
         self.partitions = partitions;
     }
 
+    /// Merges another partitioned Bloom filter into this one, partition by partition.
+    ///
+    /// Both filters must have the same number of partitions, and each pair of
+    /// partitions the same size and number of hash functions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use lsmer::bloom::PartitionedBloomFilter;
+    ///
+    /// let mut filter1 = PartitionedBloomFilter::<&str>::new(1000, 0.01, 4);
+    /// let mut filter2 = PartitionedBloomFilter::<&str>::new(1000, 0.01, 4);
+    ///
+    /// filter1.insert(&"apple");
+    /// filter2.insert(&"banana");
+    ///
+    /// filter1.merge(&filter2).unwrap();
+    /// assert!(filter1.may_contain(&"apple"));
+    /// assert!(filter1.may_contain(&"banana"));
+    /// ```
+    pub fn merge(&mut self, other: &Self) -> Result<(), &'static str> {
+        if self.num_partitions != other.num_partitions {
+            return Err("Cannot merge partitioned Bloom filters with different partition counts");
+        }
+
+        let compatible = self
+            .partitions
+            .iter()
+            .zip(&other.partitions)
+            .all(|(a, b)| a.size_bits() == b.size_bits() && a.num_hashes() == b.num_hashes());
+        if !compatible {
+            return Err("Cannot merge Bloom filters of different sizes or hash counts");
+        }
+
+        for (partition, other_partition) in self.partitions.iter_mut().zip(&other.partitions) {
+            partition.merge(other_partition)?;
+        }
+
+        Ok(())
+    }
+
     /// Calculates the estimated false positive rate based on current occupancy
     ///
     /// # Arguments
@@ -348,6 +528,46 @@ mod tests {
         assert!(!filter.may_contain(&"fig"));
     }
 
+    #[test]
+    fn test_from_keys_parallel_matches_sequential() {
+        let keys: Vec<String> = (0..5000).map(|i| format!("key{}", i)).collect();
+
+        let parallel = PartitionedBloomFilter::from_keys_parallel(&keys, 0.01, 8);
+        let mut sequential = PartitionedBloomFilter::new(keys.len(), 0.01, 8);
+        for key in &keys {
+            sequential.insert(key);
+        }
+
+        // Both construction paths must set exactly the same bits
+        for i in 0..parallel.num_partitions() {
+            assert_eq!(
+                parallel.get_partition(i).unwrap().get_bits(),
+                sequential.get_partition(i).unwrap().get_bits()
+            );
+        }
+        assert!(keys.iter().all(|key| parallel.may_contain(key)));
+    }
+
+    #[test]
+    fn test_builder_takes_borrowed_keys() {
+        let keys: Vec<String> = (0..5000).map(|i| format!("key{}", i)).collect();
+
+        let mut builder = PartitionedBloomBuilder::<String>::new(8);
+        for key in &keys {
+            builder.insert(key.as_str());
+        }
+        assert_eq!(builder.len(), keys.len());
+        let built = builder.finish(0.01);
+        let owned = PartitionedBloomFilter::from_keys_parallel(&keys, 0.01, 8);
+
+        for i in 0..built.num_partitions() {
+            assert_eq!(
+                built.get_partition(i).unwrap().get_bits(),
+                owned.get_partition(i).unwrap().get_bits()
+            );
+        }
+    }
+
     #[test]
     fn test_clear() {
         let mut filter = PartitionedBloomFilter::<&str>::new(1000, 0.01, 4);
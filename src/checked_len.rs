@@ -0,0 +1,66 @@
+//! Checked decoding of the length and count fields stored in WAL and SSTable
+//! files.
+//!
+//! A damaged file can claim any length, so each length is checked against a
+//! hard limit and against the bytes actually left to read before anything is
+//! allocated for it. Failures are `ErrorKind::InvalidData`.
+
+use std::io::{self, Read, Seek};
+
+/// Check a decoded length before allocating for it: no more than `max`,
+/// and no more than the `remaining` bytes that follow the field
+pub(crate) fn check_len(len: u64, max: usize, remaining: u64, what: &str) -> io::Result<usize> {
+    if len > max as u64 {
+        return Err(invalid(format!("{} length too large: {}", what, len)));
+    }
+    if len > remaining {
+        return Err(invalid(format!(
+            "{} length {} would read past end of file",
+            what, len
+        )));
+    }
+    Ok(len as usize)
+}
+
+/// Check a decoded item count before reserving room for it: no more than
+/// `max`, and few enough that items of at least `min_item_size` bytes fit
+/// in the `remaining` bytes
+pub(crate) fn check_count(
+    count: u64,
+    max: usize,
+    min_item_size: usize,
+    remaining: u64,
+    what: &str,
+) -> io::Result<usize> {
+    if count > max as u64 {
+        return Err(invalid(format!(
+            "Unreasonable number of {}: {}",
+            what, count
+        )));
+    }
+    if count.saturating_mul(min_item_size as u64) > remaining {
+        return Err(invalid(format!(
+            "{} {} cannot fit in the {} bytes that follow",
+            count, what, remaining
+        )));
+    }
+    Ok(count as usize)
+}
+
+/// Read a little-endian u32 length at the reader's position and check it,
+/// `end` being the offset where the readable data stops
+pub(crate) fn read_len<R: Read + Seek>(
+    reader: &mut R,
+    end: u64,
+    max: usize,
+    what: &str,
+) -> io::Result<usize> {
+    let mut len_buf = [0u8; 4];
+    reader.read_exact(&mut len_buf)?;
+    let remaining = end.saturating_sub(reader.stream_position()?);
+    check_len(u32::from_le_bytes(len_buf) as u64, max, remaining, what)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
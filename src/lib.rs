@@ -2,15 +2,24 @@
 // First comment out and then uncomment to reset any conflict
 pub mod bloom;
 pub mod bptree;
+mod checked_len;
+pub mod clock;
+mod locks;
+#[macro_use]
+pub mod logging;
 pub mod lsm_index;
 pub mod memtable;
 pub mod sstable;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
 pub mod wal;
 
 pub use bloom::BloomFilter;
 pub use bptree::{BPlusTree, IndexKeyValue, StorageReference, TreeOps};
-pub use lsm_index::{LsmIndex, LsmIndexError, SkipListIndex};
+pub use clock::{Clock, MockClock, SystemClock};
+pub use lsm_index::{diff, LsmIndex, LsmIndexError, LsmIndexOptions, SkipListIndex};
 pub use memtable::{AsyncStringMemtable, ByteSize, Memtable, MemtableError, StringMemtable};
 pub use sstable::SSTableInfo;
+pub use wal::change_feed::{Change, ChangeFeed, OperationId};
 pub use wal::durability::{DurabilityError, DurabilityManager, KeyValuePair, Operation};
 pub use wal::{RecordType, WalError, WalRecord, WriteAheadLog};
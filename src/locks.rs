@@ -0,0 +1,16 @@
+//! Handling of locks left poisoned by a thread that panicked while holding
+//! them.
+
+use std::io;
+use std::sync::{LockResult, PoisonError};
+
+/// The guard of a lock whether or not it was poisoned, for accessors that
+/// cannot fail and only read the state behind it
+pub(crate) fn recover<G>(result: LockResult<G>) -> G {
+    result.unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The guard of the lock on `what`, or an `io::Error` if a panic poisoned it
+pub(crate) fn checked<G>(result: LockResult<G>, what: &str) -> io::Result<G> {
+    result.map_err(|_| io::Error::other(format!("The {} lock was poisoned by a panic", what)))
+}
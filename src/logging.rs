@@ -0,0 +1,66 @@
+//! Diagnostics emitted through the `log` facade.
+//!
+//! The library never prints. Messages go to whatever logger the application
+//! installs, under targets named after the module that emits them, such as
+//! `lsmer::sstable`, and nothing is emitted without one. `set_verbosity`
+//! additionally caps how much detail the library produces, independently of
+//! the logger's own filtering: opening an SSTable traces its header and
+//! Bloom filter layout, which is rarely wanted outside debugging.
+
+pub use log::LevelFilter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static VERBOSITY: AtomicUsize = AtomicUsize::new(LevelFilter::Trace as usize);
+
+/// Emit only messages at `level` or more severe; `LevelFilter::Off`
+/// silences the library. Defaults to `Trace`, leaving filtering to the
+/// logger.
+pub fn set_verbosity(level: LevelFilter) {
+    VERBOSITY.store(level as usize, Ordering::Relaxed);
+}
+
+/// Most detailed level the library currently emits
+pub fn verbosity() -> LevelFilter {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Whether a message at `level` passes `set_verbosity`
+pub(crate) fn enabled(level: log::Level) -> bool {
+    level <= verbosity()
+}
+
+/// Log through the `log` facade, subject to `set_verbosity`
+macro_rules! lsm_log {
+    ($level:expr, $($arg:tt)+) => {
+        if $crate::logging::enabled($level) {
+            log::log!($level, $($arg)+);
+        }
+    };
+}
+
+/// Log detail useful only when debugging a file format or code path
+macro_rules! trace {
+    ($($arg:tt)+) => { lsm_log!(log::Level::Trace, $($arg)+) };
+}
+
+/// Log a step of an operation
+macro_rules! debug {
+    ($($arg:tt)+) => { lsm_log!(log::Level::Debug, $($arg)+) };
+}
+
+/// Log a milestone such as the end of recovery
+macro_rules! info {
+    ($($arg:tt)+) => { lsm_log!(log::Level::Info, $($arg)+) };
+}
+
+/// Log a failure the library works around
+macro_rules! warn {
+    ($($arg:tt)+) => { lsm_log!(log::Level::Warn, $($arg)+) };
+}
@@ -0,0 +1,287 @@
+use super::manifest::{FileMetadata, MANIFEST_FILE_NAME};
+use super::value_log::segment_files;
+use super::{LsmIndex, LsmIndexError, Manifest, Result};
+use crate::memtable::Memtable;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Name of the archive member describing the snapshot; always the first
+pub const SNAPSHOT_METADATA_NAME: &str = "SNAPSHOT";
+
+/// Version of the snapshot archive layout written by `export_snapshot`
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Size of a tar header and of the blocks member data is padded to
+const BLOCK_SIZE: usize = 512;
+
+/// Largest member the 11 octal digits of a tar size field can describe
+const MAX_MEMBER_SIZE: u64 = 0o77_777_777_777;
+
+impl LsmIndex {
+    /// Stream a consistent snapshot of the index to `writer` as a tar
+    /// archive, returning the number of bytes written.
+    ///
+    /// The memtable is flushed first, then the live SSTables and the
+    /// manifest naming them are captured together, so compactions finishing
+    /// while the archive is written do not tear it. The archive holds a
+    /// `SNAPSHOT` metadata member, the manifest, the SSTables and any value
+    /// log segments, all at its top level, and can be unpacked with
+    /// `import_snapshot` or any tar tool.
+    /// Writes made to this index while the snapshot is taken may or may not
+    /// be included.
+    pub fn export_snapshot<W: Write>(&self, mut writer: W) -> Result<u64> {
+        if !self.memtable.is_empty()? || !self.deleted.is_empty() || !self.removed.is_empty() {
+            self.flush()?;
+        }
+
+        // Open every file under the manifest lock: a compaction retiring one
+        // afterwards unlinks it, but the open handle keeps its contents
+        let (manifest_bytes, files, segments) = {
+            let manifest = self.lock_manifest()?;
+            let manifest_bytes = fs::read(manifest.path())?;
+            let files = manifest
+                .files()
+                .map(|file| Ok((member_name(&file.path)?, File::open(&file.path)?)))
+                .collect::<Result<Vec<(String, File)>>>()?;
+            // Value log garbage collection deletes segments under the same lock
+            let segments = segment_files(&self.base_path)?
+                .into_iter()
+                .map(|(_, path)| {
+                    let path = path.to_string_lossy();
+                    Ok((member_name(&path)?, File::open(&*path)?))
+                })
+                .collect::<Result<Vec<(String, File)>>>()?;
+            (manifest_bytes, files, segments)
+        };
+
+        let mtime = self.options().clock.now_secs();
+        let metadata = format!(
+            "version={}\ncreated_at_ms={}\nsstables={}\n",
+            SNAPSHOT_VERSION,
+            self.now_ms(),
+            files.len()
+        );
+        let mut written = 0;
+        written += write_member(
+            &mut writer,
+            SNAPSHOT_METADATA_NAME,
+            metadata.as_bytes(),
+            mtime,
+        )?;
+        written += write_member(&mut writer, MANIFEST_FILE_NAME, &manifest_bytes, mtime)?;
+        for (name, file) in files.into_iter().chain(segments) {
+            let size = file.metadata()?.len();
+            written += write_header(&mut writer, &name, size, mtime)?;
+            let copied = io::copy(&mut file.take(size), &mut writer)?;
+            if copied != size {
+                return Err(LsmIndexError::InvalidOperation(format!(
+                    "File {} shrank while being archived",
+                    name
+                )));
+            }
+            written += copied + write_padding(&mut writer, size)?;
+        }
+        // A tar archive ends with two empty blocks
+        writer.write_all(&[0u8; 2 * BLOCK_SIZE])?;
+        writer.flush()?;
+        Ok(written + 2 * BLOCK_SIZE as u64)
+    }
+
+    /// Unpack a snapshot written by `export_snapshot` into `dir`, ready to be
+    /// opened and recovered there.
+    ///
+    /// Every SSTable is placed in `dir` itself, whichever data directory it
+    /// came from, and the manifest is rewritten to match. Fails with
+    /// `InvalidOperation` if `dir` already holds a database, and with
+    /// `IoError` of kind `InvalidData` if the archive is not a snapshot or
+    /// names a file outside `dir`.
+    pub fn import_snapshot<R: Read>(mut reader: R, dir: &str) -> Result<()> {
+        let target = Path::new(dir);
+        if target.join(MANIFEST_FILE_NAME).exists() || target.join("wal").exists() {
+            return Err(LsmIndexError::InvalidOperation(format!(
+                "{} already holds a database",
+                dir
+            )));
+        }
+        fs::create_dir_all(target)?;
+
+        let mut members = 0;
+        while let Some((name, size)) = read_header(&mut reader)? {
+            if members == 0 {
+                if name != SNAPSHOT_METADATA_NAME {
+                    return Err(invalid_data(
+                        "Archive does not start with snapshot metadata",
+                    ));
+                }
+                let mut metadata = Vec::new();
+                (&mut reader).take(size).read_to_end(&mut metadata)?;
+                check_metadata(&metadata)?;
+            } else {
+                let mut file = File::create(target.join(&name))?;
+                let copied = io::copy(&mut (&mut reader).take(size), &mut file)?;
+                if copied != size {
+                    return Err(invalid_data(&format!(
+                        "Archive member {} is truncated",
+                        name
+                    )));
+                }
+                file.sync_all()?;
+            }
+            skip_padding(&mut reader, size)?;
+            members += 1;
+        }
+        if members == 0 {
+            return Err(invalid_data("Archive is empty"));
+        }
+
+        // The manifest names files where the exporting index kept them
+        let mut manifest = Manifest::open(dir)?;
+        let files: Vec<FileMetadata> = manifest.files().cloned().collect();
+        for file in files {
+            let path = target
+                .join(member_name(&file.path)?)
+                .to_string_lossy()
+                .to_string();
+            if path != file.path {
+                manifest.remove_file(&file.path)?;
+                manifest.add_file(FileMetadata { path, ..file })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Name an SSTable takes in the archive: its file name
+fn member_name(path: &str) -> Result<String> {
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| LsmIndexError::InvalidOperation(format!("Invalid SSTable path {}", path)))
+}
+
+/// Check the snapshot metadata member describes a layout this build reads
+fn check_metadata(metadata: &[u8]) -> Result<()> {
+    let metadata = std::str::from_utf8(metadata)
+        .map_err(|_| invalid_data("Snapshot metadata is not UTF-8"))?;
+    let version = metadata
+        .lines()
+        .find_map(|line| line.strip_prefix("version="))
+        .and_then(|version| version.parse::<u32>().ok())
+        .ok_or_else(|| invalid_data("Snapshot metadata has no version"))?;
+    if version > SNAPSHOT_VERSION {
+        return Err(invalid_data(&format!(
+            "Unsupported snapshot version: {}",
+            version
+        )));
+    }
+    Ok(())
+}
+
+/// Write a whole member held in memory, returning the bytes written
+fn write_member<W: Write>(writer: &mut W, name: &str, data: &[u8], mtime: u64) -> Result<u64> {
+    let header = write_header(writer, name, data.len() as u64, mtime)?;
+    writer.write_all(data)?;
+    Ok(header + data.len() as u64 + write_padding(writer, data.len() as u64)?)
+}
+
+/// Write a ustar header for a regular file
+fn write_header<W: Write>(writer: &mut W, name: &str, size: u64, mtime: u64) -> Result<u64> {
+    if name.len() >= 100 {
+        return Err(LsmIndexError::InvalidOperation(format!(
+            "File name {} is too long for the archive",
+            name
+        )));
+    }
+    if size > MAX_MEMBER_SIZE {
+        return Err(LsmIndexError::InvalidOperation(format!(
+            "File {} is too large for the archive",
+            name
+        )));
+    }
+
+    let mut header = [0u8; BLOCK_SIZE];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+    header[136..148].copy_from_slice(format!("{:011o}\0", mtime.min(MAX_MEMBER_SIZE)).as_bytes());
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is taken with its own field filled with spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+    writer.write_all(&header)?;
+    Ok(BLOCK_SIZE as u64)
+}
+
+/// Pad a member of `size` bytes out to a whole block
+fn write_padding<W: Write>(writer: &mut W, size: u64) -> Result<u64> {
+    let padding = padding_for(size);
+    writer.write_all(&[0u8; BLOCK_SIZE][..padding])?;
+    Ok(padding as u64)
+}
+
+/// Read the next member's header, returning its name and size, or `None` at
+/// the end of the archive
+fn read_header<R: Read>(reader: &mut R) -> Result<Option<(String, u64)>> {
+    let mut header = [0u8; BLOCK_SIZE];
+    reader.read_exact(&mut header)?;
+    if header.iter().all(|&byte| byte == 0) {
+        return Ok(None);
+    }
+
+    let stored = parse_octal(&header[148..156])?;
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u64 = header.iter().map(|&byte| u64::from(byte)).sum();
+    if stored != checksum {
+        return Err(invalid_data("Archive header checksum verification failed"));
+    }
+    if header[156] != b'0' && header[156] != 0 {
+        return Err(invalid_data("Archive holds something other than files"));
+    }
+
+    let name_len = header[..100]
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(100);
+    let name = std::str::from_utf8(&header[..name_len])
+        .map_err(|_| invalid_data("Archive member name is not UTF-8"))?;
+    // Members are unpacked straight into the target directory
+    if name.is_empty() || name.contains('/') || name.contains('\\') || name == "." || name == ".." {
+        return Err(invalid_data(&format!(
+            "Invalid archive member name {:?}",
+            name
+        )));
+    }
+    Ok(Some((name.to_string(), parse_octal(&header[124..136])?)))
+}
+
+/// Skip the padding after a member of `size` bytes
+fn skip_padding<R: Read>(reader: &mut R, size: u64) -> Result<()> {
+    let mut padding = [0u8; BLOCK_SIZE];
+    reader.read_exact(&mut padding[..padding_for(size)])?;
+    Ok(())
+}
+
+fn padding_for(size: u64) -> usize {
+    (BLOCK_SIZE - (size % BLOCK_SIZE as u64) as usize) % BLOCK_SIZE
+}
+
+/// Parse a NUL- or space-terminated octal header field
+fn parse_octal(field: &[u8]) -> Result<u64> {
+    let digits = std::str::from_utf8(field)
+        .map_err(|_| invalid_data("Archive header field is not ASCII"))?
+        .trim_matches(|c: char| c == '\0' || c == ' ');
+    u64::from_str_radix(digits, 8).map_err(|_| invalid_data("Invalid archive header field"))
+}
+
+fn invalid_data(message: &str) -> LsmIndexError {
+    LsmIndexError::IoError(io::Error::new(io::ErrorKind::InvalidData, message))
+}
@@ -0,0 +1,154 @@
+use super::{LsmIndex, LsmIndexError, Result};
+use std::fs;
+use std::future::Future;
+use std::ops::RangeBounds;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// What a flush or compaction did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkSummary {
+    /// SSTables written
+    pub files_written: Vec<String>,
+    /// SSTables retired
+    pub files_removed: Vec<String>,
+    /// Entries in the files written
+    pub entries_written: u64,
+    /// Size of the files written in bytes
+    pub bytes_written: u64,
+    /// How long the work took
+    pub duration: Duration,
+    /// Trace IDs of the writes the work persisted or merged, as given in
+    /// `WriteOptions::trace_id`; at most `MAX_TRACE_IDS`
+    pub trace_ids: Vec<String>,
+}
+
+/// Flushes and compactions started with `flush_async` or
+/// `compact_range_async` that have not finished
+#[derive(Debug, Default)]
+pub(super) struct PendingJobs {
+    running: AtomicUsize,
+    idle: Notify,
+}
+
+/// Counts a job as pending until dropped, waking waiters when none remain
+struct PendingJobGuard(Arc<PendingJobs>);
+
+impl PendingJobGuard {
+    fn new(jobs: Arc<PendingJobs>) -> Self {
+        jobs.running.fetch_add(1, Ordering::SeqCst);
+        PendingJobGuard(jobs)
+    }
+}
+
+impl Drop for PendingJobGuard {
+    fn drop(&mut self) {
+        if self.0.running.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+/// A flush or compaction running on Tokio's blocking pool.
+///
+/// The work starts when the job is created, whether or not it is awaited;
+/// awaiting it yields the work's summary.
+#[derive(Debug)]
+pub struct BackgroundJob {
+    task: JoinHandle<Result<WorkSummary>>,
+}
+
+impl BackgroundJob {
+    /// Whether the work has finished, successfully or not
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Future for BackgroundJob {
+    type Output = Result<WorkSummary>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.task).poll(cx).map(|joined| {
+            joined.unwrap_or_else(|e| {
+                Err(LsmIndexError::InvalidOperation(format!(
+                    "Background job failed: {}",
+                    e
+                )))
+            })
+        })
+    }
+}
+
+impl LsmIndex {
+    /// Flush the memtable, summarising the file written
+    pub fn flush_with_summary(&self) -> Result<WorkSummary> {
+        let started = Instant::now();
+        let path = self.flush_to_sstable()?;
+        let entries_written = self
+            .lock_manifest()?
+            .get(&path)
+            .map_or(0, |file| file.entry_count);
+        let trace_ids = self.traces.of_file(&path);
+        Ok(WorkSummary {
+            bytes_written: fs::metadata(&path)?.len(),
+            files_written: vec![path],
+            files_removed: Vec::new(),
+            entries_written,
+            duration: started.elapsed(),
+            trace_ids,
+        })
+    }
+
+    /// Start flushing the memtable on the current Tokio runtime
+    pub fn flush_async(self: &Arc<Self>) -> BackgroundJob {
+        let index = self.clone();
+        self.spawn_job(move || index.flush_with_summary())
+    }
+
+    /// Start `compact_range` on the current Tokio runtime
+    pub fn compact_range_async<R>(self: &Arc<Self>, range: R) -> BackgroundJob
+    where
+        R: RangeBounds<String> + Send + 'static,
+    {
+        let index = self.clone();
+        self.spawn_job(move || index.compact_range(range))
+    }
+
+    /// Wait until every flush and compaction started with `flush_async` or
+    /// `compact_range_async` has finished, awaited or not
+    pub async fn wait_for_pending_work(&self) {
+        loop {
+            // Register before checking, so a job ending in between still
+            // wakes us
+            let idle = self.pending_jobs.idle.notified();
+            if self.pending_jobs.running.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    /// Number of flushes and compactions started asynchronously that have
+    /// not finished
+    pub fn pending_jobs(&self) -> usize {
+        self.pending_jobs.running.load(Ordering::SeqCst)
+    }
+
+    fn spawn_job(
+        &self,
+        work: impl FnOnce() -> Result<WorkSummary> + Send + 'static,
+    ) -> BackgroundJob {
+        let pending = PendingJobGuard::new(self.pending_jobs.clone());
+        let task = tokio::task::spawn_blocking(move || {
+            let _pending = pending;
+            work()
+        });
+        BackgroundJob { task }
+    }
+}
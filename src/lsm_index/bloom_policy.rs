@@ -0,0 +1,90 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// What is known about an SSTable when its Bloom filter is sized
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterContext {
+    /// Level the file is written to; flushes write level 0
+    pub level: u32,
+    /// Number of keys the filter will hold
+    pub expected_entries: usize,
+    /// Observed SSTable reads per second against files on this level
+    pub level_reads_per_sec: f64,
+    /// Average time files on this level have lived before being replaced,
+    /// if any have been replaced yet
+    pub average_lifetime: Option<Duration>,
+}
+
+/// Chooses the Bloom filter false positive rate for a new SSTable.
+///
+/// Implementations can trade memory for fewer wasted disk reads per file:
+/// files that are read often and live long deserve tighter filters than
+/// short-lived files that will soon be compacted away.
+pub trait BloomFprPolicy: Debug + Send + Sync {
+    /// Return the false positive rate to use, in the open interval (0, 1)
+    fn false_positive_rate(&self, context: &FilterContext) -> f64;
+}
+
+/// Uses the same false positive rate for every file
+#[derive(Debug, Clone, Copy)]
+pub struct FixedFprPolicy(pub f64);
+
+impl BloomFprPolicy for FixedFprPolicy {
+    fn false_positive_rate(&self, _context: &FilterContext) -> f64 {
+        self.0
+    }
+}
+
+/// Scales a base false positive rate by level, read load and file lifetime.
+///
+/// Each level below 0 tightens the rate by `level_factor`; levels with more
+/// than `hot_reads_per_sec` reads tighten it by a further `level_factor`, and
+/// files expected to live less than `short_lifetime` loosen it by the same
+/// factor. The result is clamped to `[min_fpr, max_fpr]`.
+#[derive(Debug, Clone)]
+pub struct AdaptiveFprPolicy {
+    /// Rate used for a level 0 file with no read history
+    pub base_fpr: f64,
+    /// Tightest rate the policy will choose
+    pub min_fpr: f64,
+    /// Loosest rate the policy will choose
+    pub max_fpr: f64,
+    /// Multiplier applied per adjustment step
+    pub level_factor: f64,
+    /// Read rate above which a level counts as hot
+    pub hot_reads_per_sec: f64,
+    /// Lifetime below which files count as short-lived
+    pub short_lifetime: Duration,
+}
+
+impl Default for AdaptiveFprPolicy {
+    fn default() -> Self {
+        AdaptiveFprPolicy {
+            base_fpr: 0.01,
+            min_fpr: 0.0001,
+            max_fpr: 0.1,
+            level_factor: 2.0,
+            hot_reads_per_sec: 100.0,
+            short_lifetime: Duration::from_secs(60),
+        }
+    }
+}
+
+impl BloomFprPolicy for AdaptiveFprPolicy {
+    fn false_positive_rate(&self, context: &FilterContext) -> f64 {
+        let mut fpr = self.base_fpr / self.level_factor.powi(context.level as i32);
+
+        if context.level_reads_per_sec > self.hot_reads_per_sec {
+            fpr /= self.level_factor;
+        }
+
+        if context
+            .average_lifetime
+            .is_some_and(|lifetime| lifetime < self.short_lifetime)
+        {
+            fpr *= self.level_factor;
+        }
+
+        fpr.clamp(self.min_fpr, self.max_fpr)
+    }
+}
@@ -0,0 +1,190 @@
+use super::{LsmIndex, LsmIndexError, Result, WriteOptions};
+use crate::checked_len;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+/// Magic number at the start of an encoded wide-column value ("LWC1")
+const COLUMNS_MAGIC: u32 = 0x4C57_4331;
+
+/// Encode a set of columns as a single value.
+///
+/// Columns are written in name order, so the same columns always encode to
+/// the same bytes regardless of how the map was built.
+pub fn encode_columns<'a, I>(columns: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = (&'a String, &'a Vec<u8>)>,
+{
+    let sorted: BTreeMap<&String, &Vec<u8>> = columns.into_iter().collect();
+
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&COLUMNS_MAGIC.to_le_bytes());
+    buf.extend_from_slice(&(sorted.len() as u32).to_le_bytes());
+    for (name, value) in sorted {
+        buf.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        buf.extend_from_slice(value);
+    }
+    buf
+}
+
+/// Decode a value written by `encode_columns`
+pub fn decode_columns(buf: &[u8]) -> io::Result<HashMap<String, Vec<u8>>> {
+    let mut cursor = buf;
+    if get_u32(&mut cursor)? != COLUMNS_MAGIC {
+        return Err(invalid("Value is not a wide-column value"));
+    }
+
+    // Each column takes at least its name and value lengths
+    let count = checked_len::check_count(
+        get_u32(&mut cursor)? as u64,
+        u32::MAX as usize,
+        8,
+        cursor.len() as u64,
+        "columns",
+    )?;
+    let mut columns = HashMap::with_capacity(count);
+    for _ in 0..count {
+        let name_len = get_u32(&mut cursor)? as usize;
+        let name = String::from_utf8(take(&mut cursor, name_len)?.to_vec())
+            .map_err(|_| invalid("Column name is not valid UTF-8"))?;
+        let value_len = get_u32(&mut cursor)? as usize;
+        columns.insert(name, take(&mut cursor, value_len)?.to_vec());
+    }
+
+    if !cursor.is_empty() {
+        return Err(invalid("Trailing bytes after wide-column value"));
+    }
+    Ok(columns)
+}
+
+fn take<'a>(cursor: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if cursor.len() < len {
+        return Err(invalid("Truncated wide-column value"));
+    }
+    let (bytes, rest) = cursor.split_at(len);
+    *cursor = rest;
+    Ok(bytes)
+}
+
+fn get_u32(cursor: &mut &[u8]) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(take(cursor, 4)?.try_into().unwrap()))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+impl LsmIndex {
+    /// Store a set of named columns under one key, replacing any previous value
+    pub fn put_columns(&self, key: String, columns: HashMap<String, Vec<u8>>) -> Result<()> {
+        self.put_columns_with_options(key, columns, &self.default_write_options())?;
+        Ok(())
+    }
+
+    /// Store a set of named columns under one key with explicit durability
+    /// settings and preconditions, returning the write's commit sequence
+    pub fn put_columns_with_options(
+        &self,
+        key: String,
+        columns: HashMap<String, Vec<u8>>,
+        write_options: &WriteOptions,
+    ) -> Result<u64> {
+        self.insert_with_options(key, encode_columns(&columns), write_options)
+    }
+
+    /// Get every column stored under a key.
+    ///
+    /// Fails with `InvalidOperation` if the key holds a plain value.
+    pub fn get_columns(&self, key: &str) -> Result<Option<HashMap<String, Vec<u8>>>> {
+        match self.get(key)? {
+            Some(value) => decode_columns(&value)
+                .map(Some)
+                .map_err(|e| LsmIndexError::InvalidOperation(format!("Key {:?}: {}", key, e))),
+            None => Ok(None),
+        }
+    }
+
+    /// Get a single column stored under a key
+    pub fn get_column(&self, key: &str, column: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .get_columns(key)?
+            .and_then(|mut columns| columns.remove(column)))
+    }
+
+    /// Update some columns of a key, keeping the others.
+    ///
+    /// Columns mapped to `None` are removed. A missing key starts out with no
+    /// columns. The index has no merge operator yet, so this reads the current
+    /// columns and writes the merged set back; concurrent merges of the same
+    /// key may lose updates.
+    pub fn merge_columns(
+        &self,
+        key: String,
+        updates: HashMap<String, Option<Vec<u8>>>,
+    ) -> Result<()> {
+        self.merge_columns_with_options(key, updates, &self.default_write_options())?;
+        Ok(())
+    }
+
+    /// Update some columns of a key, as `merge_columns` does, with explicit
+    /// durability settings and preconditions, returning the write's commit
+    /// sequence. Conditioning the merge on the sequence the current columns
+    /// were read at makes it safe against concurrent merges.
+    pub fn merge_columns_with_options(
+        &self,
+        key: String,
+        updates: HashMap<String, Option<Vec<u8>>>,
+        write_options: &WriteOptions,
+    ) -> Result<u64> {
+        let mut columns = self.get_columns(&key)?.unwrap_or_default();
+        for (name, value) in updates {
+            match value {
+                Some(value) => {
+                    columns.insert(name, value);
+                }
+                None => {
+                    columns.remove(&name);
+                }
+            }
+        }
+        self.put_columns_with_options(key, columns, write_options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut columns = HashMap::new();
+        columns.insert("name".to_string(), b"alice".to_vec());
+        columns.insert("empty".to_string(), Vec::new());
+
+        let encoded = encode_columns(&columns);
+        assert_eq!(decode_columns(&encoded).unwrap(), columns);
+    }
+
+    #[test]
+    fn test_encoding_is_deterministic() {
+        let a: HashMap<_, _> = (0..32)
+            .map(|i| (format!("col{}", i), vec![i as u8]))
+            .collect();
+        let b: HashMap<_, _> = (0..32)
+            .rev()
+            .map(|i| (format!("col{}", i), vec![i as u8]))
+            .collect();
+        assert_eq!(encode_columns(&a), encode_columns(&b));
+    }
+
+    #[test]
+    fn test_rejects_plain_and_truncated_values() {
+        assert!(decode_columns(b"plain").is_err());
+
+        let mut columns = HashMap::new();
+        columns.insert("name".to_string(), b"alice".to_vec());
+        let encoded = encode_columns(&columns);
+        assert!(decode_columns(&encoded[..encoded.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,355 @@
+use super::compaction_scheduler::{CompactionContext, CompactionDecision, CompactionReason};
+use super::locks::recover;
+use super::placement::ColdStorageContext;
+use super::trace;
+use super::{FileMetadata, LsmIndex, Result, WorkSummary};
+use crate::sstable::{CompactionOptions, SSTableCompaction};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::ops::{Bound, RangeBounds};
+use std::time::{Duration, Instant};
+
+/// A range queued with `queue_compaction`
+pub(super) type QueuedRange = (Bound<String>, Bound<String>);
+
+impl LsmIndex {
+    /// Merge the live SSTables holding keys in `range` into one file.
+    ///
+    /// Every file at least as new as the oldest one overlapping the range
+    /// is merged too, so no file left out holds newer versions of the keys
+    /// the output carries. The output goes on the deepest level of its
+    /// inputs, and at least level 1. Writes and flushes wait while the
+    /// compaction runs.
+    ///
+    /// Tombstones are carried into the output: the inputs are retired, and
+    /// only deleted once no index entry points into them, so one still on
+    /// disk at a crash is adopted again by recovery and its values must
+    /// stay hidden.
+    ///
+    /// A compaction scheduler, if configured, may defer the compaction, in
+    /// which case nothing is done and an empty summary is returned.
+    pub fn compact_range<R: RangeBounds<String>>(&self, range: R) -> Result<WorkSummary> {
+        self.compact_range_scheduled(range, true)
+    }
+
+    /// Queue a compaction of `range` for the next `run_queued_compactions`,
+    /// which runs queued ranges in the order the scheduler prioritises them
+    pub fn queue_compaction<R: RangeBounds<String>>(&self, range: R) {
+        recover(self.compaction_queue.lock())
+            .push((range.start_bound().cloned(), range.end_bound().cloned()));
+    }
+
+    /// Number of ranges queued with `queue_compaction` and not yet run
+    pub fn queued_compactions(&self) -> usize {
+        recover(self.compaction_queue.lock()).len()
+    }
+
+    /// Run the queued compactions, highest priority first, returning what
+    /// each did.
+    ///
+    /// Ranges the scheduler defers stay queued for the next call; ranges
+    /// without any live files are dropped. Without a scheduler, ranges run
+    /// in the order they were queued. If a compaction fails, it and the
+    /// ranges not yet run are queued again.
+    pub fn run_queued_compactions(&self) -> Result<Vec<WorkSummary>> {
+        let mut queue = self.checked_lock(self.compaction_queue.lock(), "compaction queue")?;
+        let queued = std::mem::take(&mut *queue);
+        drop(queue);
+        let mut runnable = Vec::new();
+        let mut deferred = Vec::new();
+        for range in queued {
+            let Some((inputs, level)) = self.range_inputs(&range) else {
+                continue;
+            };
+            match self.schedule_compaction(&range_reason(&range), &inputs, level) {
+                CompactionDecision::Run { priority } => runnable.push((priority, range)),
+                CompactionDecision::Defer => deferred.push(range),
+            }
+        }
+        // The sort is stable, so equal priorities keep their queue order
+        runnable.sort_by_key(|(priority, _)| Reverse(*priority));
+
+        let mut summaries = Vec::with_capacity(runnable.len());
+        let mut runnable = runnable.into_iter().map(|(_, range)| range);
+        while let Some(range) = runnable.next() {
+            match self.compact_range_scheduled(range.clone(), false) {
+                Ok(summary) => summaries.push(summary),
+                Err(e) => {
+                    deferred.push(range);
+                    deferred.extend(runnable);
+                    self.requeue(deferred);
+                    return Err(e);
+                }
+            }
+        }
+        self.requeue(deferred);
+        Ok(summaries)
+    }
+
+    /// Put ranges back at the front of the queue, ahead of any queued since
+    fn requeue(&self, ranges: Vec<QueuedRange>) {
+        let mut queue = recover(self.compaction_queue.lock());
+        queue.splice(0..0, ranges);
+    }
+
+    /// Compact `range`, asking the scheduler first unless it already agreed
+    fn compact_range_scheduled<R: RangeBounds<String>>(
+        &self,
+        range: R,
+        consult_scheduler: bool,
+    ) -> Result<WorkSummary> {
+        let started = Instant::now();
+        let _running = self.track_background_task();
+        let _durability_manager = self.lock_wal()?;
+
+        let Some((inputs, level)) = self.range_inputs(&range) else {
+            return Ok(WorkSummary::default());
+        };
+        if consult_scheduler
+            && self.schedule_compaction(&range_reason(&range), &inputs, level)
+                == CompactionDecision::Defer
+        {
+            return Ok(WorkSummary::default());
+        }
+
+        self.compact_files(inputs, level, started)
+    }
+
+    /// The files compacting `range` merges and the level of the output, or
+    /// `None` if no live file holds keys in it
+    fn range_inputs<R: RangeBounds<String>>(&self, range: &R) -> Option<(Vec<FileMetadata>, u32)> {
+        // Live files from oldest to newest, as recovery indexes them
+        let mut files: Vec<_> = recover(self.manifest.lock()).files().cloned().collect();
+        files.sort_by(|a, b| Self::recency(a).cmp(&Self::recency(b)));
+        let first = files
+            .iter()
+            .position(|file| overlaps(range, &file.min_key, &file.max_key))?;
+        let inputs = files.split_off(first);
+        let level = inputs
+            .iter()
+            .map(|file| file.level)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        Some((inputs, level))
+    }
+
+    /// Ask the compaction scheduler, if any, whether to run a compaction
+    fn schedule_compaction(
+        &self,
+        reason: &CompactionReason,
+        inputs: &[FileMetadata],
+        output_level: u32,
+    ) -> CompactionDecision {
+        let Some(scheduler) = self.options().compaction_scheduler.clone() else {
+            return CompactionDecision::RUN;
+        };
+        scheduler.schedule(&CompactionContext {
+            reason,
+            inputs,
+            output_level,
+            level_storage: &self.level_storage_stats(),
+            level_reads: &self.level_read_stats(),
+            now_ms: self.now_ms(),
+        })
+    }
+
+    /// Merge the run of SSTables smaller than `max_file_bytes` at the newest
+    /// end of the index into one file, if it holds at least two.
+    ///
+    /// Frequent small flushes, such as one per restart in a crash loop,
+    /// leave a swarm of tiny files every lookup has to consult. Only the
+    /// newest run is merged, since the output orders after every live file
+    /// and a tiny file older than a larger one would otherwise overtake it.
+    /// The output stays on the deepest level of its inputs. A compaction
+    /// scheduler may defer the merge, as with `compact_range`.
+    pub fn compact_tiny_files(&self, max_file_bytes: u64) -> Result<WorkSummary> {
+        let started = Instant::now();
+        let _running = self.track_background_task();
+        let _durability_manager = self.lock_wal()?;
+
+        let mut files: Vec<_> = self.lock_manifest()?.files().cloned().collect();
+        files.sort_by(|a, b| Self::recency(a).cmp(&Self::recency(b)));
+        let tiny = files
+            .iter()
+            .rev()
+            .take_while(|file| file.size_bytes < max_file_bytes)
+            .count();
+        if tiny < 2 {
+            return Ok(WorkSummary::default());
+        }
+        let inputs = files.split_off(files.len() - tiny);
+        let level = inputs.iter().map(|file| file.level).max().unwrap_or(0);
+        if self.schedule_compaction(&CompactionReason::TinyFiles, &inputs, level)
+            == CompactionDecision::Defer
+        {
+            return Ok(WorkSummary::default());
+        }
+
+        self.compact_files(inputs, level, started)
+    }
+
+    /// The cold path, if the cold storage policy sends the output of
+    /// compacting `inputs` onto `level` there
+    fn cold_path_for(
+        &self,
+        inputs: &[FileMetadata],
+        level: u32,
+        expected_entries: u64,
+    ) -> Option<String> {
+        let options = self.options();
+        let (Some(cold_path), Some(policy)) = (&options.cold_path, &options.cold_storage_policy)
+        else {
+            return None;
+        };
+        let context = ColdStorageContext {
+            level,
+            expected_entries: expected_entries as usize,
+            newest_input_secs: inputs
+                .iter()
+                .map(|file| file.created_at_secs)
+                .max()
+                .unwrap_or(0),
+            now_secs: options.clock.now_secs(),
+        };
+        policy.is_cold(&context).then(|| cold_path.clone())
+    }
+
+    /// Merge `inputs`, live files from oldest to newest, into one file on
+    /// `level` and retire them. The caller holds the WAL lock.
+    fn compact_files(
+        &self,
+        inputs: Vec<FileMetadata>,
+        level: u32,
+        started: Instant,
+    ) -> Result<WorkSummary> {
+        let input_paths: Vec<String> = inputs.iter().map(|file| file.path.clone()).collect();
+        let expected_entries: u64 = inputs.iter().map(|file| file.entry_count).sum();
+        let created_at_secs = self.options().clock.now_secs();
+        let (output_path, file_number) = match self.cold_path_for(&inputs, level, expected_entries)
+        {
+            Some(cold_path) => self.new_sstable_path_in(&cold_path, level, created_at_secs)?,
+            None => self.new_sstable_path(level, expected_entries as usize, created_at_secs)?,
+        };
+        let input_bytes: u64 = inputs.iter().map(|file| file.size_bytes).sum();
+        self.ensure_disk_space(&output_path, input_bytes)?;
+
+        let mut options = CompactionOptions::default()
+            .with_bloom_filter(self.use_bloom_filters)
+            .with_false_positive_rate(self.bloom_fpr_for(level, expected_entries as usize))
+            .with_compression(self.options().compression)
+            .with_block_compression(self.options().block_compression)
+            .with_hash_index(self.options().uses_hash_index(level))
+            .with_block_size(self.options().block_size)
+            .with_checksum_kind(self.options().checksum_kind)
+            .with_key_prefix_compression(self.options().key_prefix_compression)
+            .with_clock(self.options().clock.clone())
+            .with_level(level);
+        for (name, value) in &self.options().user_properties {
+            options = options.with_user_property(name, value);
+        }
+        if let Some(extractor) = &self.options().prefix_extractor {
+            options = options.with_prefix_extractor(extractor.clone());
+        }
+        if let Some(provider) = &self.options().key_provider {
+            options = options.with_encryption(provider.clone());
+        }
+        if let Some(false_positive_rate) = self.options().block_filter_fpr {
+            options = options.with_block_filters(false_positive_rate);
+        }
+        if let Some(index_block_size) = self.options().index_block_size {
+            options = options.with_index_block_size(index_block_size);
+        }
+        if let Some(restart_interval) = self.options().restart_interval {
+            options = options.with_restart_interval(restart_interval);
+        }
+        self.options().retry_policy.run("compaction", || {
+            SSTableCompaction::compact_sstables_with_options(&input_paths, &output_path, &options)
+        })?;
+
+        // Point the keys the inputs served at the output, and drop entries
+        // the compaction found expired
+        let replaced: HashSet<String> = input_paths.iter().cloned().collect();
+        let summary = self.index_sstable(&output_path, Some(&replaced))?;
+        let stale: Vec<String> = self
+            .index
+            .iter()
+            .filter(|entry| {
+                entry
+                    .value()
+                    .storage_ref()
+                    .is_some_and(|storage_ref| replaced.contains(&storage_ref.file_path))
+            })
+            .map(|entry| entry.key().clone())
+            .collect();
+        for key in stale {
+            self.index.remove(&key);
+        }
+
+        let metadata =
+            Self::file_metadata(&output_path, level, (created_at_secs, file_number), summary)?;
+        let written = metadata.clone();
+        {
+            let mut manifest = self.lock_manifest()?;
+            self.options()
+                .retry_policy
+                .run("manifest update", || manifest.add_file(metadata.clone()))?;
+        }
+        let reader = self.open_reader(&output_path, level)?;
+        self.sstable_readers.insert(output_path.clone(), reader);
+
+        let trace_ids = self.traces.compacted(&input_paths, &output_path);
+        for input in &inputs {
+            self.retire_sstable(&input.path)?;
+            let lifetime = created_at_secs.saturating_sub(input.created_at_secs);
+            self.level_stats
+                .record_retirement(input.level, Duration::from_secs(lifetime));
+        }
+
+        debug!(
+            "Compacted {} files into {}{}",
+            input_paths.len(),
+            output_path,
+            trace::describe(&trace_ids)
+        );
+        self.log_if_slow("compaction", started.elapsed(), &trace_ids);
+        Ok(WorkSummary {
+            files_written: vec![output_path],
+            files_removed: input_paths,
+            entries_written: written.entry_count,
+            bytes_written: written.size_bytes,
+            duration: started.elapsed(),
+            trace_ids,
+        })
+    }
+}
+
+/// How a compaction of `range` is described to the scheduler
+fn range_reason<R: RangeBounds<String>>(range: &R) -> CompactionReason {
+    CompactionReason::Range {
+        start: range.start_bound().cloned(),
+        end: range.end_bound().cloned(),
+    }
+}
+
+/// Whether a file holding keys from `min_key` to `max_key` has any in `range`
+fn overlaps<R: RangeBounds<String>>(
+    range: &R,
+    min_key: &Option<String>,
+    max_key: &Option<String>,
+) -> bool {
+    let (Some(min_key), Some(max_key)) = (min_key, max_key) else {
+        return false;
+    };
+    let starts_before_max = match range.start_bound() {
+        Bound::Included(start) => start <= max_key,
+        Bound::Excluded(start) => start < max_key,
+        Bound::Unbounded => true,
+    };
+    let ends_after_min = match range.end_bound() {
+        Bound::Included(end) => end >= min_key,
+        Bound::Excluded(end) => end > min_key,
+        Bound::Unbounded => true,
+    };
+    starts_before_max && ends_after_min
+}
@@ -0,0 +1,91 @@
+use super::manifest::FileMetadata;
+use super::stats::{LevelReadStats, LevelStorageStats};
+use std::fmt::Debug;
+use std::ops::Bound;
+
+/// Why a compaction is about to run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompactionReason {
+    /// `compact_range`, or a range queued with `queue_compaction`, asked
+    /// for the keys between these bounds
+    Range {
+        /// Lower bound of the requested range
+        start: Bound<String>,
+        /// Upper bound of the requested range
+        end: Bound<String>,
+    },
+    /// The newest run of small files is being merged, as at recovery
+    TinyFiles,
+}
+
+/// What is known about a compaction before it runs
+#[derive(Debug, Clone)]
+pub struct CompactionContext<'a> {
+    /// Why the compaction was asked for
+    pub reason: &'a CompactionReason,
+    /// Live files that would be merged, oldest first
+    pub inputs: &'a [FileMetadata],
+    /// Level the output would be written to
+    pub output_level: u32,
+    /// Raw and on-disk bytes of the live files, per level
+    pub level_storage: &'a [LevelStorageStats],
+    /// Read-path counters, per level
+    pub level_reads: &'a [LevelReadStats],
+    /// Current time on the index's clock, in milliseconds since the Unix
+    /// epoch
+    pub now_ms: u64,
+}
+
+impl CompactionContext<'_> {
+    /// Estimated number of live keys among the inputs, taking each
+    /// tombstone to hide one older entry, as
+    /// `SSTableCompaction::estimate_num_keys` does
+    pub fn estimated_live_entries(&self) -> u64 {
+        let entries: u64 = self.inputs.iter().map(|file| file.entry_count).sum();
+        let tombstones: u64 = self.inputs.iter().map(|file| file.tombstone_count).sum();
+        entries.saturating_sub(tombstones)
+    }
+
+    /// Estimated fraction of the inputs' entries and tombstones that are not
+    /// live keys, for scoring how much the compaction would reclaim
+    pub fn estimated_benefit(&self) -> f64 {
+        let records: u64 = self
+            .inputs
+            .iter()
+            .map(|file| file.entry_count + file.tombstone_count)
+            .sum();
+        if records == 0 {
+            return 0.0;
+        }
+        1.0 - self.estimated_live_entries() as f64 / records as f64
+    }
+}
+
+/// A scheduler's verdict on a compaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactionDecision {
+    /// Run the compaction. Among queued compactions, higher priorities run
+    /// first; a direct call ignores the priority.
+    Run {
+        /// Order among queued compactions, highest first
+        priority: i32,
+    },
+    /// Skip the compaction for now. A direct call returns an empty summary;
+    /// a queued compaction stays queued for the next run.
+    Defer,
+}
+
+impl CompactionDecision {
+    /// Run at the default priority
+    pub const RUN: CompactionDecision = CompactionDecision::Run { priority: 0 };
+}
+
+/// Consulted before each compaction, to veto it or set its priority.
+///
+/// Implementations might defer compactions during business hours, or boost
+/// ranges that serve latency-sensitive reads. The scheduler may be called
+/// with the WAL lock held, so it must not write to the index.
+pub trait CompactionScheduler: Debug + Send + Sync {
+    /// Decide whether and how urgently the compaction runs
+    fn schedule(&self, context: &CompactionContext<'_>) -> CompactionDecision;
+}
@@ -0,0 +1,223 @@
+use super::{LsmIndex, LsmIndexError, Result};
+use crate::sstable::TableFile;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Suffix given to files set aside by a repair, so recovery no longer
+/// picks them up
+pub const SET_ASIDE_SUFFIX: &str = ".corrupt";
+
+/// What an `LsmIndex` does about its files when it is opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsistencyCheck {
+    /// Open without checking
+    #[default]
+    Off,
+    /// Fail to open if the manifest, data directories and WAL disagree
+    Verify,
+    /// Repair whatever can be repaired and open
+    Repair,
+}
+
+/// A checkpoint in the WAL that does not match the SSTables on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckpointMismatch {
+    /// A flush logged the start of the checkpoint but never its end, so the
+    /// SSTable it was writing may be partial
+    Unfinished {
+        /// The checkpoint's ID
+        checkpoint_id: u64,
+    },
+    /// The SSTable written for the checkpoint fails its integrity check
+    CorruptSSTable {
+        /// The checkpoint's ID
+        checkpoint_id: u64,
+        /// The checkpoint's SSTable
+        path: String,
+    },
+}
+
+/// What a consistency check found, and whether it was repaired
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    /// Files the manifest records that are not on disk; a repair removes
+    /// them from the manifest
+    pub missing_files: Vec<String>,
+    /// Readable SSTables in the data directories that the manifest does not
+    /// record; a repair adopts them on level 0
+    pub unrecorded_files: Vec<String>,
+    /// SSTables in the data directories that the manifest does not record
+    /// and that cannot be read; a repair sets them aside
+    pub unreadable_files: Vec<String>,
+    /// Checkpoints that do not match the SSTables on disk; a repair closes
+    /// unfinished checkpoints and sets corrupt checkpoint SSTables aside
+    pub checkpoint_mismatches: Vec<CheckpointMismatch>,
+    /// Whether the problems found were repaired
+    pub repaired: bool,
+}
+
+impl ConsistencyReport {
+    /// Whether the check found nothing wrong
+    pub fn is_consistent(&self) -> bool {
+        self.missing_files.is_empty()
+            && self.unrecorded_files.is_empty()
+            && self.unreadable_files.is_empty()
+            && self.checkpoint_mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for ConsistencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_consistent() {
+            return write!(f, "consistent");
+        }
+        let mut problems = Vec::new();
+        for path in &self.missing_files {
+            problems.push(format!("missing file {}", path));
+        }
+        for path in &self.unrecorded_files {
+            problems.push(format!("unrecorded file {}", path));
+        }
+        for path in &self.unreadable_files {
+            problems.push(format!("unreadable file {}", path));
+        }
+        for mismatch in &self.checkpoint_mismatches {
+            problems.push(match mismatch {
+                CheckpointMismatch::Unfinished { checkpoint_id } => {
+                    format!("unfinished checkpoint {}", checkpoint_id)
+                }
+                CheckpointMismatch::CorruptSSTable {
+                    checkpoint_id,
+                    path,
+                } => format!("corrupt SSTable {} for checkpoint {}", path, checkpoint_id),
+            });
+        }
+        write!(f, "{}", problems.join(", "))
+    }
+}
+
+impl LsmIndex {
+    /// Cross-check the manifest against the SSTables in the data directories
+    /// and the checkpoints in the WAL.
+    ///
+    /// With `repair`, files the manifest lost track of are removed from it,
+    /// readable unrecorded SSTables are adopted on level 0, unreadable ones
+    /// and corrupt checkpoint SSTables are renamed with `SET_ASIDE_SUFFIX`,
+    /// and unfinished checkpoints are closed in the WAL. Nothing is deleted.
+    pub fn check_consistency(&self, repair: bool) -> Result<ConsistencyReport> {
+        let mut report = ConsistencyReport::default();
+
+        // Files the manifest records but the directories do not hold
+        let recorded: HashSet<String> = self
+            .lock_manifest()?
+            .files()
+            .map(|file| file.path.clone())
+            .collect();
+        report.missing_files = recorded
+            .iter()
+            .filter(|path| !Path::new(path).exists())
+            .cloned()
+            .collect();
+        report.missing_files.sort();
+
+        // SSTables the directories hold but the manifest does not record
+        for directory in self.sstable_directories() {
+            for entry in fs::read_dir(&directory)? {
+                let path = entry?.path();
+                if !path.is_file() || path.extension().unwrap_or_default() != "db" {
+                    continue;
+                }
+                let path = path.to_string_lossy().to_string();
+                if recorded.contains(&path) {
+                    continue;
+                }
+                let readable = TableFile::open(&path, self.options().key_provider.as_deref())
+                    .map_err(LsmIndexError::from)
+                    .and_then(|file| Self::read_sstable_layout(&mut BufReader::new(file)))
+                    .is_ok();
+                if readable {
+                    report.unrecorded_files.push(path);
+                } else {
+                    report.unreadable_files.push(path);
+                }
+            }
+        }
+        report.unrecorded_files.sort();
+        report.unreadable_files.sort();
+
+        // Checkpoints the WAL and checkpoint SSTables disagree about
+        let mut durability_manager = self.lock_wal()?;
+        for checkpoint_id in durability_manager.unfinished_checkpoints()? {
+            report
+                .checkpoint_mismatches
+                .push(CheckpointMismatch::Unfinished { checkpoint_id });
+        }
+        for path in durability_manager.find_sstables()? {
+            let Ok(checkpoint_id) = durability_manager.extract_checkpoint_id(&path) else {
+                continue;
+            };
+            let path = path.to_string_lossy().to_string();
+            if !matches!(durability_manager.verify_sstable_integrity(&path), Ok(true)) {
+                report
+                    .checkpoint_mismatches
+                    .push(CheckpointMismatch::CorruptSSTable {
+                        checkpoint_id,
+                        path,
+                    });
+            }
+        }
+
+        if !repair || report.is_consistent() {
+            return Ok(report);
+        }
+
+        for mismatch in &report.checkpoint_mismatches {
+            match mismatch {
+                CheckpointMismatch::Unfinished { checkpoint_id } => {
+                    durability_manager.end_checkpoint(*checkpoint_id)?;
+                }
+                CheckpointMismatch::CorruptSSTable { path, .. } => set_aside(path)?,
+            }
+        }
+        drop(durability_manager);
+
+        for path in &report.missing_files {
+            self.lock_manifest()?.remove_file(path)?;
+            self.sstable_readers.remove(path);
+        }
+        for path in &report.unreadable_files {
+            set_aside(path)?;
+        }
+
+        // Adopt from oldest to newest, as recovery does
+        let mut unrecorded = report
+            .unrecorded_files
+            .iter()
+            .map(|path| Ok((self.sstable_age(path)?, path)))
+            .collect::<Result<Vec<_>>>()?;
+        unrecorded.sort();
+        for (age, path) in unrecorded {
+            let summary = self.update_index_from_sstable(path)?;
+            let mut manifest = self.lock_manifest()?;
+            manifest.mark_file_number_used(age.file_number)?;
+            manifest.add_file(Self::file_metadata(
+                path,
+                0,
+                (age.created_at_secs, age.file_number),
+                summary,
+            )?)?;
+        }
+
+        report.repaired = true;
+        Ok(report)
+    }
+}
+
+/// Rename a file with `SET_ASIDE_SUFFIX`
+fn set_aside(path: &str) -> Result<()> {
+    fs::rename(path, format!("{}{}", path, SET_ASIDE_SUFFIX))?;
+    Ok(())
+}
@@ -0,0 +1,184 @@
+use super::{GenIndexEntry, LsmIndex, Result, Snapshot};
+use crossbeam_skiplist::map::Entry;
+use std::ops::Bound;
+
+/// A cursor over the live keys of an `LsmIndex`, in key order.
+///
+/// The cursor is positioned with `seek`, `seek_after`, `seek_to_first` or
+/// `seek_to_last` and then moved with `next` and `prev`. It reads the lock-free
+/// index directly, so writes made while the cursor is open may or may not be
+/// observed; each positioning step only ever lands on a live key. An open
+/// cursor counts as an active snapshot taken when it was created.
+///
+/// An optional limit caps how many entries the cursor will visit after a seek,
+/// which together with `seek_after` gives keyset pagination:
+///
+/// ```no_run
+/// # use lsmer::LsmIndex;
+/// # let index = LsmIndex::new(1024, "data".to_string(), None, false, 0.01).unwrap();
+/// let mut cursor = index.cursor().with_limit(100);
+/// cursor.seek_to_first().unwrap();
+/// let page = cursor.collect_page().unwrap();
+///
+/// // Resume after the last key of the previous page
+/// if let Some((last_key, _)) = page.last() {
+///     cursor.seek_after(last_key).unwrap();
+///     let next_page = cursor.collect_page().unwrap();
+/// #   let _ = next_page;
+/// }
+/// ```
+pub struct LsmCursor<'a> {
+    /// The index being iterated
+    index: &'a LsmIndex,
+    /// Current key and value, if the cursor is valid
+    current: Option<(String, Vec<u8>)>,
+    /// Maximum number of entries to visit after a seek
+    limit: Option<usize>,
+    /// Number of entries visited since the last seek
+    visited: usize,
+    /// Registers the cursor as an active snapshot until it is dropped
+    _snapshot: Snapshot,
+}
+
+/// Direction in which the cursor looks for the next live entry
+#[derive(Clone, Copy)]
+enum Direction {
+    Forward,
+    Backward,
+}
+
+impl<'a> LsmCursor<'a> {
+    /// Create an unpositioned cursor over the given index
+    pub(crate) fn new(index: &'a LsmIndex) -> Self {
+        LsmCursor {
+            index,
+            current: None,
+            limit: None,
+            visited: 0,
+            _snapshot: index.acquire_snapshot(),
+        }
+    }
+
+    /// Limit the number of entries visited after each seek
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Returns true if the cursor is positioned on an entry
+    pub fn valid(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Returns the key at the current position
+    pub fn key(&self) -> Option<&str> {
+        self.current.as_ref().map(|(key, _)| key.as_str())
+    }
+
+    /// Returns the value at the current position
+    pub fn value(&self) -> Option<&[u8]> {
+        self.current.as_ref().map(|(_, value)| value.as_slice())
+    }
+
+    /// Position the cursor on the first key
+    pub fn seek_to_first(&mut self) -> Result<()> {
+        self.visited = 0;
+        self.settle(Bound::Unbounded, Direction::Forward)
+    }
+
+    /// Position the cursor on the last key
+    pub fn seek_to_last(&mut self) -> Result<()> {
+        self.visited = 0;
+        self.settle(Bound::Unbounded, Direction::Backward)
+    }
+
+    /// Position the cursor on the first key greater than or equal to `key`
+    pub fn seek(&mut self, key: &str) -> Result<()> {
+        self.visited = 0;
+        self.settle(Bound::Included(key), Direction::Forward)
+    }
+
+    /// Position the cursor on the first key strictly greater than `key`
+    pub fn seek_after(&mut self, key: &str) -> Result<()> {
+        self.visited = 0;
+        self.settle(Bound::Excluded(key), Direction::Forward)
+    }
+
+    /// Position the cursor on the last key less than or equal to `key`
+    pub fn seek_for_prev(&mut self, key: &str) -> Result<()> {
+        self.visited = 0;
+        self.settle(Bound::Included(key), Direction::Backward)
+    }
+
+    /// Move to the next key; the cursor becomes invalid past the end
+    #[allow(clippy::should_implement_trait)] // Fallible cursor step, not an iterator
+    pub fn next(&mut self) -> Result<()> {
+        match self.current.take() {
+            Some((key, _)) => self.settle(Bound::Excluded(&key), Direction::Forward),
+            None => Ok(()),
+        }
+    }
+
+    /// Move to the previous key; the cursor becomes invalid before the start
+    pub fn prev(&mut self) -> Result<()> {
+        match self.current.take() {
+            Some((key, _)) => self.settle(Bound::Excluded(&key), Direction::Backward),
+            None => Ok(()),
+        }
+    }
+
+    /// Collect entries from the current position forwards until the limit is
+    /// reached or the keys run out.
+    ///
+    /// The cursor is left past the last collected entry.
+    pub fn collect_page(&mut self) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut page = Vec::new();
+        while let Some(entry) = self.current.clone() {
+            page.push(entry);
+            self.next()?;
+        }
+        Ok(page)
+    }
+
+    /// Find the nearest live entry from `bound` in the given direction
+    fn settle(&mut self, bound: Bound<&str>, direction: Direction) -> Result<()> {
+        self.current = None;
+
+        if self.limit.is_some_and(|limit| self.visited >= limit) {
+            return Ok(());
+        }
+
+        let found = match direction {
+            Direction::Forward => {
+                self.first_live(self.index.index.range::<str, _>((bound, Bound::Unbounded)))?
+            }
+            Direction::Backward => self.first_live(
+                self.index
+                    .index
+                    .range::<str, _>((Bound::Unbounded, bound))
+                    .rev(),
+            )?,
+        };
+
+        if found.is_some() {
+            self.visited += 1;
+        }
+        self.current = found;
+
+        Ok(())
+    }
+
+    /// Return the first entry that resolves to a live value, skipping
+    /// tombstones and entries whose value can no longer be found
+    fn first_live<'e>(
+        &self,
+        entries: impl Iterator<Item = Entry<'e, String, GenIndexEntry>>,
+    ) -> Result<Option<(String, Vec<u8>)>> {
+        for entry in entries {
+            if let Some(value) = self.index.resolve_entry_value(entry.key(), entry.value())? {
+                return Ok(Some((entry.key().clone(), value)));
+            }
+        }
+        Ok(None)
+    }
+}
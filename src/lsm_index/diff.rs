@@ -0,0 +1,202 @@
+use super::{LsmIndex, Result};
+use crate::sstable::digest::{entry_digest, Digest, MerkleHasher};
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+/// Ranges holding at most this many entries on both sides are compared key by key
+const LEAF_RANGE_ENTRIES: u64 = 64;
+
+/// Digest and size of the live entries in a key range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeSummary {
+    /// Merkle root over the entries in the range, in key order
+    pub digest: Digest,
+    /// Number of live entries in the range
+    pub entry_count: u64,
+}
+
+/// A store that can summarize key ranges by digest, so it can take part in `diff`.
+///
+/// Implementations must hash entries with `sstable::digest` in key order so that
+/// two stores holding the same data agree on every range.
+pub trait RangeDigests {
+    /// Summarize the live entries between the given bounds
+    fn range_summary(&self, lower: Bound<&str>, upper: Bound<&str>) -> Result<RangeSummary>;
+
+    /// Per-entry digests for the live entries between the given bounds
+    fn range_entry_digests(
+        &self,
+        lower: Bound<&str>,
+        upper: Bound<&str>,
+    ) -> Result<Vec<(String, Digest)>>;
+
+    /// A key splitting the range into two roughly equal halves, if it holds
+    /// at least two entries
+    fn range_split_key(&self, lower: Bound<&str>, upper: Bound<&str>) -> Result<Option<String>>;
+}
+
+/// How a key differs between two stores
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    /// The key is only present in the first store
+    OnlyInLeft,
+    /// The key is only present in the second store
+    OnlyInRight,
+    /// The key is present in both stores with different values
+    ValueMismatch,
+}
+
+/// A key that differs between two stores
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyDifference {
+    /// The differing key
+    pub key: String,
+    /// How the key differs
+    pub kind: DiffKind,
+}
+
+/// Compare two stores and return the keys that differ, in key order.
+///
+/// Ranges are compared by digest first and only split further where the
+/// digests disagree, so identical stores cost one summary per side and small
+/// divergences are located without comparing every key.
+pub fn diff<A, B>(left: &A, right: &B) -> Result<Vec<KeyDifference>>
+where
+    A: RangeDigests + ?Sized,
+    B: RangeDigests + ?Sized,
+{
+    let mut differences = Vec::new();
+    diff_range(
+        left,
+        right,
+        Bound::Unbounded,
+        Bound::Unbounded,
+        &mut differences,
+    )?;
+    Ok(differences)
+}
+
+/// Compare one range, recursing into halves whose digests disagree
+fn diff_range<A, B>(
+    left: &A,
+    right: &B,
+    lower: Bound<&str>,
+    upper: Bound<&str>,
+    differences: &mut Vec<KeyDifference>,
+) -> Result<()>
+where
+    A: RangeDigests + ?Sized,
+    B: RangeDigests + ?Sized,
+{
+    let left_summary = left.range_summary(lower, upper)?;
+    let right_summary = right.range_summary(lower, upper)?;
+    if left_summary == right_summary {
+        return Ok(());
+    }
+
+    let small = left_summary.entry_count <= LEAF_RANGE_ENTRIES
+        && right_summary.entry_count <= LEAF_RANGE_ENTRIES;
+
+    // Split on the larger side, which always makes progress on that side
+    let split = if small {
+        None
+    } else if left_summary.entry_count >= right_summary.entry_count {
+        left.range_split_key(lower, upper)?
+    } else {
+        right.range_split_key(lower, upper)?
+    };
+
+    match split {
+        Some(split) => {
+            diff_range(left, right, lower, Bound::Excluded(&split), differences)?;
+            diff_range(left, right, Bound::Included(&split), upper, differences)
+        }
+        None => {
+            compare_entries(
+                left.range_entry_digests(lower, upper)?,
+                right.range_entry_digests(lower, upper)?,
+                differences,
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Compare per-key digests of a small range
+fn compare_entries(
+    left: Vec<(String, Digest)>,
+    right: Vec<(String, Digest)>,
+    differences: &mut Vec<KeyDifference>,
+) {
+    let mut right: BTreeMap<String, Digest> = right.into_iter().collect();
+    let mut found = BTreeMap::new();
+
+    for (key, digest) in left {
+        match right.remove(&key) {
+            Some(other) if other == digest => {}
+            Some(_) => {
+                found.insert(key, DiffKind::ValueMismatch);
+            }
+            None => {
+                found.insert(key, DiffKind::OnlyInLeft);
+            }
+        }
+    }
+    for key in right.into_keys() {
+        found.insert(key, DiffKind::OnlyInRight);
+    }
+
+    differences.extend(
+        found
+            .into_iter()
+            .map(|(key, kind)| KeyDifference { key, kind }),
+    );
+}
+
+impl RangeDigests for LsmIndex {
+    fn range_summary(&self, lower: Bound<&str>, upper: Bound<&str>) -> Result<RangeSummary> {
+        let mut hasher = MerkleHasher::new();
+        for entry in self.index.range::<str, _>((lower, upper)) {
+            if let Some(value) = self.resolve_entry_value(entry.key(), entry.value())? {
+                hasher.update(entry.key(), &value);
+            }
+        }
+
+        Ok(RangeSummary {
+            digest: hasher.finish(),
+            entry_count: hasher.entry_count(),
+        })
+    }
+
+    fn range_entry_digests(
+        &self,
+        lower: Bound<&str>,
+        upper: Bound<&str>,
+    ) -> Result<Vec<(String, Digest)>> {
+        let mut digests = Vec::new();
+        for entry in self.index.range::<str, _>((lower, upper)) {
+            if let Some(value) = self.resolve_entry_value(entry.key(), entry.value())? {
+                digests.push((entry.key().clone(), entry_digest(entry.key(), &value)));
+            }
+        }
+        Ok(digests)
+    }
+
+    fn range_split_key(&self, lower: Bound<&str>, upper: Bound<&str>) -> Result<Option<String>> {
+        let mut keys = Vec::new();
+        for entry in self.index.range::<str, _>((lower, upper)) {
+            if self
+                .resolve_entry_value(entry.key(), entry.value())?
+                .is_some()
+            {
+                keys.push(entry.key().clone());
+            }
+        }
+
+        if keys.len() < 2 {
+            return Ok(None);
+        }
+        let middle = keys.len() / 2;
+        Ok(Some(keys.swap_remove(middle)))
+    }
+}
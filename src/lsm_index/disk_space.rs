@@ -0,0 +1,63 @@
+use super::{LsmIndex, LsmIndexError, Result};
+use std::fmt::Debug;
+use std::io;
+
+/// Reports how much space is free on the file system holding a path
+pub trait DiskSpaceProbe: Debug + Send + Sync {
+    /// Bytes available to unprivileged writers on the file system holding
+    /// `path`
+    fn available_bytes(&self, path: &str) -> io::Result<u64>;
+}
+
+/// Asks the operating system with `statvfs`. Unsupported on other
+/// platforms, where writes are never refused for lack of space.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileSystemProbe;
+
+impl DiskSpaceProbe for FileSystemProbe {
+    #[cfg(unix)]
+    fn available_bytes(&self, path: &str) -> io::Result<u64> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = CString::new(std::path::Path::new(path).as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+        // SAFETY: `path` is NUL-terminated and `stats` is only read after
+        // statvfs reports that it filled it in
+        let stats = unsafe {
+            if libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            stats.assume_init()
+        };
+        #[allow(clippy::unnecessary_cast)]
+        Ok(stats.f_bavail as u64 * stats.f_frsize as u64)
+    }
+
+    #[cfg(not(unix))]
+    fn available_bytes(&self, _path: &str) -> io::Result<u64> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+impl LsmIndex {
+    /// Refuse a write of `bytes` to the file system holding `path` if it
+    /// would eat into the reserved headroom. Space that cannot be measured
+    /// is assumed to be there.
+    pub(super) fn ensure_disk_space(&self, path: &str, bytes: u64) -> Result<()> {
+        let headroom = self.options().reserved_headroom_bytes;
+        if headroom == 0 {
+            return Ok(());
+        }
+        let required = headroom.saturating_add(bytes);
+        match self.options().disk_space_probe.available_bytes(path) {
+            Ok(available) if available < required => Err(LsmIndexError::DiskFull {
+                path: path.to_string(),
+                available,
+                required,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
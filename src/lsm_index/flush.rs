@@ -0,0 +1,112 @@
+use super::locks::recover;
+use super::manifest::FileMetadata;
+use super::{LsmIndex, LsmIndexError, Result};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+impl LsmIndex {
+    /// Fraction of the memtable's capacity in use. Writes that would take it
+    /// past 1.0 fail until it is flushed.
+    pub fn memtable_fill(&self) -> Result<f64> {
+        let capacity = self.memtable.max_capacity().max(1);
+        Ok(self.memtable.current_size()? as f64 / capacity as f64)
+    }
+
+    /// Flush every index in `indexes` whose memtable is at least `min_fill`
+    /// full, running at most `max_concurrent` flushes at a time, and return
+    /// how many were flushed.
+    ///
+    /// Meant for shards or tenants kept in separate indexes, where a burst of
+    /// writes can fill several memtables at once; flushing them side by side
+    /// shortens the time writers stall. The flushes run on one pool shared by
+    /// every call, so there are never more of them than it has threads.
+    ///
+    /// The new files are recorded in one manifest commit once every flush
+    /// has finished: all the manifests are locked together and each gains
+    /// its index's file, so none shows a file from the batch before the
+    /// whole batch is written. A crash before the commit leaves the files
+    /// to be adopted when their indexes are reopened. Every flush is
+    /// attempted even if another fails, and the first error is returned.
+    pub fn flush_full(
+        indexes: &[&LsmIndex],
+        min_fill: f64,
+        max_concurrent: usize,
+    ) -> Result<usize> {
+        let mut full: Vec<&LsmIndex> = Vec::new();
+        for &index in indexes {
+            let fill = index.memtable_fill()?;
+            if fill > 0.0 && fill >= min_fill && !full.iter().any(|&f| std::ptr::eq(f, index)) {
+                full.push(index);
+            }
+        }
+        if full.is_empty() {
+            return Ok(0);
+        }
+        // Manifests are locked in address order, so concurrent calls cannot
+        // deadlock on them
+        full.sort_by_key(|&index| index as *const LsmIndex as usize);
+
+        // Each worker takes the next index until none are left
+        let next = AtomicUsize::new(0);
+        let flushed: Vec<Mutex<Option<Result<FileMetadata>>>> =
+            full.iter().map(|_| Mutex::new(None)).collect();
+        flush_pool()?.scope(|scope| {
+            for _ in 0..max_concurrent.clamp(1, full.len()) {
+                scope.spawn(|_| {
+                    loop {
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        let Some(index) = full.get(i) else { break };
+                        let result = index.flush_memtable(false).map(|(_, metadata)| metadata);
+                        *recover(flushed[i].lock()) = Some(result);
+                    }
+                });
+            }
+        });
+        let results: Vec<Result<FileMetadata>> = flushed
+            .into_iter()
+            .map(|slot| {
+                recover(slot.into_inner()).unwrap_or_else(|| {
+                    Err(LsmIndexError::InvalidOperation(
+                        "Flush did not run".to_string(),
+                    ))
+                })
+            })
+            .collect();
+
+        // Record the new files together
+        let mut manifests = full
+            .iter()
+            .map(|index| index.lock_manifest())
+            .collect::<Result<Vec<_>>>()?;
+        let mut first_error = None;
+        for ((index, manifest), result) in full.iter().zip(&mut manifests).zip(results) {
+            let recorded = result.and_then(|metadata| {
+                index
+                    .options()
+                    .retry_policy
+                    .run("manifest update", || manifest.add_file(metadata.clone()))
+            });
+            if let Err(e) = recorded {
+                first_error.get_or_insert(e);
+            }
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(full.len()),
+        }
+    }
+}
+
+/// Pool shared by every `flush_full` call, with a thread per CPU
+fn flush_pool() -> Result<&'static rayon::ThreadPool> {
+    static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+    if let Some(pool) = POOL.get() {
+        return Ok(pool);
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(std::thread::available_parallelism().map_or(1, |n| n.get()))
+        .thread_name(|i| format!("lsm-flush-{}", i))
+        .build()
+        .map_err(|e| LsmIndexError::InvalidOperation(format!("Flush pool failed: {}", e)))?;
+    Ok(POOL.get_or_init(|| pool))
+}
@@ -0,0 +1,76 @@
+use super::manifest::{FileMetadata, MANIFEST_FILE_NAME};
+use super::value_log::segment_files;
+use super::{LsmIndex, LsmIndexError, Manifest, Result};
+use crate::memtable::Memtable;
+use std::fs;
+use std::path::Path;
+
+impl LsmIndex {
+    /// Create a new database in `target_dir` holding the same data as this
+    /// one, and open it.
+    ///
+    /// The memtable is flushed first, then every live SSTable and value log
+    /// segment is hard linked into the target, so the two databases share
+    /// file contents until compaction replaces them; files are copied
+    /// instead if they cannot be linked, e.g. across filesystems. The fork
+    /// gets its own WAL and manifest. Writes made to this index while the
+    /// fork is taken may or may not be included.
+    ///
+    /// Fails with `InvalidOperation` if `target_dir` already holds a database.
+    pub fn fork(&self, target_dir: &str) -> Result<LsmIndex> {
+        let target = Path::new(target_dir);
+        if target.join(MANIFEST_FILE_NAME).exists() || target.join("wal").exists() {
+            return Err(LsmIndexError::InvalidOperation(format!(
+                "{} already holds a database",
+                target_dir
+            )));
+        }
+        fs::create_dir_all(target)?;
+
+        // Only immutable files are shared, so get everything still in
+        // memory into an SSTable first
+        if !self.memtable.is_empty()? || !self.deleted.is_empty() || !self.removed.is_empty() {
+            self.flush()?;
+        }
+
+        {
+            // Compaction retires SSTables and value log garbage collection
+            // deletes segments under the same lock, so every file is linked
+            // before it can go
+            let manifest = self.lock_manifest()?;
+            for (_, path) in segment_files(&self.base_path)? {
+                let link = target.join(path.file_name().unwrap_or_default());
+                if fs::hard_link(&path, &link).is_err() {
+                    fs::copy(&path, &link)?;
+                }
+            }
+
+            let mut forked = Manifest::open(target_dir)?;
+            for file in manifest.files() {
+                let name = Path::new(&file.path).file_name().ok_or_else(|| {
+                    LsmIndexError::InvalidOperation(format!("Invalid SSTable path {}", file.path))
+                })?;
+                let link = target.join(name);
+                if fs::hard_link(&file.path, &link).is_err() {
+                    fs::copy(&file.path, &link)?;
+                }
+
+                forked.add_file(FileMetadata {
+                    path: link.to_string_lossy().to_string(),
+                    ..file.clone()
+                })?;
+            }
+        }
+
+        let mut fork = LsmIndex::new_with_options(
+            self.memtable.max_capacity(),
+            target_dir.to_string(),
+            None,
+            self.use_bloom_filters,
+            self.bloom_filter_fpr,
+            (*self.options()).clone(),
+        )?;
+        fork.recover()?;
+        Ok(fork)
+    }
+}
@@ -1,5 +1,6 @@
 use crate::bptree::StorageReference;
 use crate::lsm_index::gen_ref::{make_gen_ref, GenRefHandle};
+use crate::lsm_index::sstable_file::SSTableFileRef;
 
 /// A generationally reference-counted index entry
 ///
@@ -12,6 +13,16 @@ pub struct GenIndexEntry {
     value: Option<GenRefHandle<Vec<u8>>>,
     /// Reference to storage on disk (SSTables), if applicable
     storage_ref: Option<StorageReference>,
+    /// Handle keeping the SSTable behind `storage_ref` from being deleted
+    file: Option<SSTableFileRef>,
+    /// When the entry was written, in milliseconds since the Unix epoch
+    written_at_ms: Option<u64>,
+    /// When the entry expires, in milliseconds since the Unix epoch
+    expires_at_ms: Option<u64>,
+    /// Sequence of the write behind the entry, if its SSTable recorded one
+    sequence: Option<u64>,
+    /// Length of the value left on disk, for entries indexed from an SSTable
+    disk_len: Option<u64>,
 }
 
 impl GenIndexEntry {
@@ -23,6 +34,20 @@ impl GenIndexEntry {
         GenIndexEntry {
             value: gen_value,
             storage_ref,
+            file: None,
+            written_at_ms: None,
+            expires_at_ms: None,
+            sequence: None,
+            disk_len: None,
+        }
+    }
+
+    /// Create an entry for a value of `value_len` bytes held only on disk,
+    /// read through `storage_ref` when the key is looked up
+    pub fn on_disk(storage_ref: StorageReference, value_len: u64) -> Self {
+        GenIndexEntry {
+            disk_len: Some(value_len),
+            ..Self::new(None, Some(storage_ref))
         }
     }
 
@@ -31,6 +56,22 @@ impl GenIndexEntry {
         self.value.as_ref().map(|handle| handle.clone_data())
     }
 
+    /// Length of the value held in memory, without cloning it
+    pub fn value_len(&self) -> Option<usize> {
+        self.value.as_ref().map(|handle| handle.get().len())
+    }
+
+    /// Length of the value as written, whether held in memory or left on
+    /// disk, if known
+    pub fn written_len(&self) -> Option<u64> {
+        self.value_len().map(|len| len as u64).or(self.disk_len)
+    }
+
+    /// Check whether a value is held in memory, without cloning it
+    pub fn has_value(&self) -> bool {
+        self.value.is_some()
+    }
+
     /// Get a reference to the storage reference, if present
     pub fn storage_ref(&self) -> Option<&StorageReference> {
         self.storage_ref.as_ref()
@@ -41,6 +82,11 @@ impl GenIndexEntry {
         GenIndexEntry {
             value: Some(make_gen_ref(value)),
             storage_ref: self.storage_ref,
+            file: self.file,
+            written_at_ms: self.written_at_ms,
+            expires_at_ms: self.expires_at_ms,
+            sequence: self.sequence,
+            disk_len: self.disk_len,
         }
     }
 
@@ -49,9 +95,88 @@ impl GenIndexEntry {
         GenIndexEntry {
             value: self.value,
             storage_ref: Some(storage_ref),
+            file: self.file,
+            written_at_ms: self.written_at_ms,
+            expires_at_ms: self.expires_at_ms,
+            sequence: self.sequence,
+            disk_len: self.disk_len,
+        }
+    }
+
+    /// Pin the SSTable the storage reference points into, returning a new entry
+    pub fn with_file(self, file: SSTableFileRef) -> Self {
+        GenIndexEntry {
+            value: self.value,
+            storage_ref: self.storage_ref,
+            file: Some(file),
+            written_at_ms: self.written_at_ms,
+            expires_at_ms: self.expires_at_ms,
+            sequence: self.sequence,
+            disk_len: self.disk_len,
+        }
+    }
+
+    /// Record when the entry was written, returning a new entry
+    pub fn with_written_at_ms(self, written_at_ms: u64) -> Self {
+        GenIndexEntry {
+            written_at_ms: Some(written_at_ms),
+            ..self
+        }
+    }
+
+    /// When the entry was written, in milliseconds since the Unix epoch, if
+    /// write times are tracked
+    pub fn written_at_ms(&self) -> Option<u64> {
+        self.written_at_ms
+    }
+
+    /// Make the entry expire at the given time, returning a new entry
+    pub fn with_expires_at_ms(self, expires_at_ms: u64) -> Self {
+        GenIndexEntry {
+            expires_at_ms: Some(expires_at_ms),
+            ..self
+        }
+    }
+
+    /// When the entry expires, in milliseconds since the Unix epoch, if it
+    /// was written with a time to live
+    pub fn expires_at_ms(&self) -> Option<u64> {
+        self.expires_at_ms
+    }
+
+    /// Record the sequence of the write behind the entry, returning a new
+    /// entry
+    pub fn with_sequence(self, sequence: u64) -> Self {
+        GenIndexEntry {
+            sequence: Some(sequence),
+            ..self
         }
     }
 
+    /// Sequence of the write behind the entry, if its SSTable recorded one
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    /// Check if the entry has expired as of `now_ms`
+    pub fn is_expired_at(&self, now_ms: u64) -> bool {
+        self.expires_at_ms
+            .is_some_and(|expires_at_ms| expires_at_ms <= now_ms)
+    }
+
+    /// Get the handle to the SSTable this entry points into, if pinned
+    pub fn file(&self) -> Option<&SSTableFileRef> {
+        self.file.as_ref()
+    }
+
+    /// Check if the SSTable this entry points into has been retired. The
+    /// file stays readable until the entry is dropped or re-pointed.
+    pub fn is_file_retired(&self) -> bool {
+        self.file
+            .as_ref()
+            .is_some_and(|file| file.is_stale() || file.get().is_retired())
+    }
+
     /// Check if the value is a tombstone
     pub fn is_tombstone(&self) -> bool {
         if let Some(ref_storage) = &self.storage_ref {
@@ -86,6 +211,7 @@ mod tests {
         let entry = GenIndexEntry::new(Some(vec![1, 2, 3]), None);
 
         // Check value
+        assert!(entry.has_value());
         assert_eq!(entry.value(), Some(vec![1, 2, 3]));
         assert_eq!(entry.storage_ref(), None);
         assert!(!entry.is_tombstone());
@@ -104,12 +230,33 @@ mod tests {
         let entry = GenIndexEntry::new(None, Some(storage_ref.clone()));
 
         // Check storage reference
+        assert!(!entry.has_value());
         assert_eq!(entry.value(), None);
         assert_eq!(entry.storage_ref().unwrap().file_path, "test.sst");
         assert_eq!(entry.storage_ref().unwrap().offset, 123);
         assert!(!entry.is_tombstone());
     }
 
+    #[test]
+    fn test_gen_index_entry_on_disk() {
+        let storage_ref = StorageReference {
+            file_path: "test.sst".to_string(),
+            offset: 64,
+            is_tombstone: false,
+        };
+
+        // The value stays on disk, but its length is known
+        let entry = GenIndexEntry::on_disk(storage_ref, 4096);
+        assert!(!entry.has_value());
+        assert_eq!(entry.value_len(), None);
+        assert_eq!(entry.written_len(), Some(4096));
+        assert!(!entry.is_tombstone());
+
+        // A value held in memory takes precedence
+        let entry = entry.with_value(vec![1, 2, 3]);
+        assert_eq!(entry.written_len(), Some(3));
+    }
+
     #[test]
     fn test_gen_index_entry_tombstone() {
         // Create storage reference with tombstone
@@ -138,6 +285,24 @@ mod tests {
         assert_eq!(updated.value(), Some(vec![4, 5, 6]));
     }
 
+    #[test]
+    fn test_gen_index_entry_file_pin() {
+        let file = crate::lsm_index::SSTableFile::new_ref("pinned.sst");
+        let entry = GenIndexEntry::new(None, None);
+        assert!(entry.file().is_none());
+        assert!(!entry.is_file_retired());
+
+        let entry = entry.with_file(file.clone());
+        assert_eq!(entry.file().unwrap().get().path(), "pinned.sst");
+        assert_eq!(file.ref_count(), 2);
+
+        file.invalidate();
+        assert!(entry.is_file_retired());
+
+        drop(entry);
+        assert_eq!(file.ref_count(), 1);
+    }
+
     #[test]
     fn test_gen_index_entry_clone() {
         // Create a new entry with a value
@@ -1,4 +1,4 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
 
 /// A reference-counted pointer with generational counting to ensure memory safety.
 ///
@@ -42,6 +42,19 @@ impl<T> GenRef<T> {
         self.generation.load(Ordering::Acquire)
     }
 
+    /// Get the current reference count.
+    pub fn ref_count(&self) -> usize {
+        self.ref_count.load(Ordering::Acquire)
+    }
+
+    /// Mark every existing handle as stale without replacing the data.
+    ///
+    /// This increments the generation number, so holders can notice that the
+    /// object has been superseded while it stays valid until they let go.
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
     /// Increment the reference count.
     pub fn inc_ref(&self) {
         self.ref_count.fetch_add(1, Ordering::Relaxed);
@@ -129,6 +142,16 @@ impl<T> GenRefHandle<T> {
     pub fn generation(&self) -> usize {
         self.generation
     }
+
+    /// Number of live handles to the shared object, including this one.
+    pub fn ref_count(&self) -> usize {
+        unsafe { (*self.gen_ref).ref_count() }
+    }
+
+    /// Mark every handle to the shared object, including this one, as stale.
+    pub fn invalidate(&self) {
+        unsafe { (*self.gen_ref).invalidate() }
+    }
 }
 
 impl<T: Clone> GenRefHandle<T> {
@@ -151,7 +174,9 @@ impl<T> Drop for GenRefHandle<T> {
         unsafe {
             // If this returns true, we were the last reference
             if (*self.gen_ref).dec_ref() {
-                // This was the last reference, so deallocate the GenRef
+                // Synchronize with the releases of every other handle before
+                // deallocating the GenRef
+                fence(Ordering::Acquire);
                 let _ = Box::from_raw(self.gen_ref as *mut GenRef<T>);
             }
         }
@@ -165,7 +190,13 @@ pub fn make_gen_ref<T>(data: T) -> GenRefHandle<T> {
     // Convert the Box to a raw pointer to avoid double-free
     let raw_ptr = Box::into_raw(gen_ref);
     // Create a handle to it - safe to call unsafe function here as we just created the pointer
-    unsafe { GenRefHandle::new(raw_ptr) }
+    let handle = unsafe { GenRefHandle::new(raw_ptr) };
+    // Hand the initial reference over to the handle, so the GenRef is freed
+    // when the last handle is dropped
+    unsafe {
+        (*raw_ptr).dec_ref();
+    }
+    handle
 }
 
 #[cfg(test)]
@@ -182,6 +213,40 @@ mod tests {
         assert!(!handle.is_stale());
     }
 
+    #[test]
+    fn test_gen_ref_drop_frees_data() {
+        struct DropFlag(Arc<std::sync::atomic::AtomicBool>);
+        impl Drop for DropFlag {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let handle1 = make_gen_ref(DropFlag(dropped.clone()));
+        let handle2 = handle1.clone();
+        assert_eq!(handle1.ref_count(), 2);
+
+        drop(handle1);
+        assert_eq!(handle2.ref_count(), 1);
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        drop(handle2);
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_gen_ref_invalidate() {
+        let handle1 = make_gen_ref(7);
+        let handle2 = handle1.clone();
+
+        handle1.invalidate();
+        assert!(handle1.is_stale());
+        assert!(handle2.is_stale());
+        assert_eq!(*handle2.get(), 7);
+        assert!(!handle2.clone().is_stale());
+    }
+
     #[test]
     fn test_gen_ref_clone() {
         let handle1 = make_gen_ref(42);
@@ -0,0 +1,166 @@
+use super::{LsmIndex, LsmIndexError, Result};
+use crossbeam_skiplist::SkipMap;
+
+/// A point in an index's history, as a commit sequence or a time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsOf {
+    /// Just after the write with this commit sequence, as returned by
+    /// `LsmIndex::last_sequence` or `Snapshot::sequence`
+    Sequence(u64),
+    /// At this time, in milliseconds since the Unix epoch
+    Time(u64),
+}
+
+impl AsOf {
+    /// Whether `version` had been written by this point
+    fn sees(&self, version: &Version) -> bool {
+        match *self {
+            AsOf::Sequence(sequence) => version.sequence <= sequence,
+            AsOf::Time(at_ms) => version.written_at_ms <= at_ms,
+        }
+    }
+}
+
+/// A value a key held, retained by `LsmIndexOptions::with_version_retention`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    /// Commit sequence of the write
+    pub sequence: u64,
+    /// Time of the write in milliseconds since the Unix epoch
+    pub written_at_ms: u64,
+    /// Value written, or `None` if the write removed the key
+    pub value: Option<Vec<u8>>,
+}
+
+/// Versions written since the index was opened, keyed by key and commit
+/// sequence so each key's versions are contiguous and in write order
+#[derive(Debug, Default)]
+pub(super) struct VersionHistory {
+    versions: SkipMap<(String, u64), Version>,
+}
+
+impl VersionHistory {
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    fn of(&self, key: &str) -> Vec<Version> {
+        self.versions
+            .range((key.to_string(), 0)..=(key.to_string(), u64::MAX))
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    /// Drop the versions of `key` superseded at or before `horizon_ms`,
+    /// returning how many went. The newest version is always kept.
+    fn prune(&self, key: &str, horizon_ms: u64) -> usize {
+        let versions: Vec<_> = self
+            .versions
+            .range((key.to_string(), 0)..=(key.to_string(), u64::MAX))
+            .collect();
+        let mut pruned = 0;
+        for pair in versions.windows(2) {
+            if pair[1].value().written_at_ms <= horizon_ms {
+                pair[0].remove();
+                pruned += 1;
+            }
+        }
+        pruned
+    }
+}
+
+impl LsmIndex {
+    /// The value `key` held as of `as_of`, read from the versions kept by
+    /// `LsmIndexOptions::with_version_retention`.
+    ///
+    /// Versions are kept from the time the index was opened. Fails with
+    /// `InvalidOperation` if retention is not enabled, or if `as_of` falls
+    /// before the oldest version still retained for the key.
+    pub fn get_at(&self, key: &str, as_of: AsOf) -> Result<Option<Vec<u8>>> {
+        let versions = self.history(key)?;
+        if let Some(version) = versions.iter().rev().find(|version| as_of.sees(version)) {
+            return Ok(version.value.clone());
+        }
+
+        // A key not written since the index was opened holds the value it
+        // was opened with
+        let opened_at = self.opened_sequence();
+        let since_open = match as_of {
+            AsOf::Sequence(sequence) => sequence >= opened_at,
+            AsOf::Time(at_ms) => at_ms.saturating_mul(1000) >= opened_at,
+        };
+        if versions.is_empty() && since_open {
+            return self.get(key);
+        }
+        Err(LsmIndexError::InvalidOperation(format!(
+            "No version of {} is retained as of {:?}",
+            key, as_of
+        )))
+    }
+
+    /// Retained versions of `key`, oldest first. Fails with
+    /// `InvalidOperation` unless the index was created with
+    /// `LsmIndexOptions::with_version_retention`.
+    pub fn history(&self, key: &str) -> Result<Vec<Version>> {
+        if self.version_retention_ms().is_none() {
+            return Err(LsmIndexError::InvalidOperation(
+                "Version retention is not enabled".to_string(),
+            ));
+        }
+        Ok(self.history.of(key))
+    }
+
+    /// Forget versions superseded longer ago than the retention period,
+    /// returning how many were dropped. Flushes call this.
+    ///
+    /// Versions an open snapshot may still read are kept.
+    pub fn purge_expired_versions(&self) -> usize {
+        let Some(horizon_ms) = self.version_horizon_ms() else {
+            return 0;
+        };
+        let mut keys: Vec<String> = self
+            .history
+            .versions
+            .iter()
+            .map(|entry| entry.key().0.clone())
+            .collect();
+        keys.dedup();
+        keys.iter()
+            .map(|key| self.history.prune(key, horizon_ms))
+            .sum()
+    }
+
+    /// Keep the version `key` was given at `sequence`, if versions are
+    /// retained. Called with the WAL lock held.
+    pub(super) fn record_version(&self, key: &str, sequence: u64, value: Option<&[u8]>) {
+        let Some(horizon_ms) = self.version_horizon_ms() else {
+            return;
+        };
+        self.history.versions.insert(
+            (key.to_string(), sequence),
+            Version {
+                sequence,
+                written_at_ms: self.now_ms(),
+                value: value.map(<[u8]>::to_vec),
+            },
+        );
+        self.history.prune(key, horizon_ms);
+    }
+
+    /// Time before which superseded versions may be dropped: the start of
+    /// the retention period, or the oldest open snapshot if earlier
+    fn version_horizon_ms(&self) -> Option<u64> {
+        let retention_ms = self.version_retention_ms()?;
+        let horizon_ms = self.now_ms().saturating_sub(retention_ms);
+        Some(
+            self.min_active_snapshot()
+                .map_or(horizon_ms, |oldest| horizon_ms.min(oldest)),
+        )
+    }
+
+    fn version_retention_ms(&self) -> Option<u64> {
+        self.options()
+            .version_retention
+            .map(|retention| retention.as_millis() as u64)
+    }
+}
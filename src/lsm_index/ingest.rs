@@ -0,0 +1,69 @@
+use super::{LsmIndex, Result};
+use crate::memtable::Memtable;
+use crate::sstable::ingest::{rewrite_sstable, IngestOptions};
+use std::fs;
+
+impl LsmIndex {
+    /// Ingest an SSTable written elsewhere, such as by another index or a
+    /// bulk loader, or restored from a backup, returning the global sequence
+    /// assigned to it.
+    ///
+    /// The file is copied into the index's data directories and stamped
+    /// with a new sequence, recorded in its properties, so every entry and
+    /// tombstone in it orders after the data already in the index, across
+    /// restarts too. Its entries are given the ingest time as their write
+    /// time, so snapshots taken before the ingest do not see them. The
+    /// memtable is flushed first if it holds anything; writes made after
+    /// that and before the ingest completes are superseded by the file.
+    /// The source is left in place.
+    pub fn ingest_sstable(&self, path: &str) -> Result<u64> {
+        if !self.memtable.is_empty()? {
+            self.flush()?;
+        }
+
+        let _running = self.track_background_task();
+        let _durability_manager = self.lock_wal()?;
+
+        let entry_count = crate::sstable::SSTableReader::open(path)?.entry_count() as usize;
+        let global_sequence = self.next_sequence();
+        let created_at_secs = self.options().clock.now_secs();
+        let (target, file_number) = self.new_sstable_path(0, entry_count, created_at_secs)?;
+        self.ensure_disk_space(&target, fs::metadata(path)?.len())?;
+
+        let mut options = IngestOptions::new(global_sequence)
+            .with_file_number(file_number)
+            .with_written_at_ms(self.now_ms())
+            .with_bloom_filter(self.use_bloom_filters)
+            .with_false_positive_rate(self.bloom_fpr_for(0, entry_count))
+            .with_compression(self.options().compression);
+        if let Some(extractor) = &self.options().prefix_extractor {
+            options = options.with_prefix_extractor(extractor.clone());
+        }
+        // A failed attempt is rewritten from the start, since creating the
+        // writer truncates the file
+        let keys = self
+            .options()
+            .retry_policy
+            .run("ingest", || rewrite_sstable(path, &target, &options))?;
+
+        // The file supersedes whatever the memtable picked up meanwhile
+        for key in &keys {
+            self.memtable.remove(key)?;
+            self.removed.remove(key);
+            self.record_sequence(key, global_sequence);
+        }
+
+        let summary = self.update_index_from_sstable(&target)?;
+        let metadata = Self::file_metadata(&target, 0, (created_at_secs, file_number), summary)?;
+        {
+            let mut manifest = self.lock_manifest()?;
+            self.options()
+                .retry_policy
+                .run("manifest update", || manifest.add_file(metadata.clone()))?;
+        }
+        let reader = self.open_reader(&target, 0)?;
+        self.sstable_readers.insert(target, reader);
+
+        Ok(global_sequence)
+    }
+}
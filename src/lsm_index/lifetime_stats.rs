@@ -0,0 +1,172 @@
+use super::{LsmIndex, Result};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Name of the file lifetime statistics are saved to, inside the index's
+/// base directory
+pub const STATS_FILE_NAME: &str = "STATS";
+/// Magic number at the start of a statistics file ("LSMS")
+const STATS_MAGIC: u32 = 0x4C53_4D53;
+/// Current statistics file format version
+const STATS_VERSION: u32 = 1;
+
+/// Cumulative counters that survive restarts.
+///
+/// They are saved on `LsmIndex::shutdown` and reloaded when the index is
+/// opened, so they only lose what happened since the last clean shutdown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LifetimeStats {
+    /// Key and value bytes inserted
+    pub bytes_written: u64,
+    /// Keys inserted, counting each overwrite
+    pub keys_written: u64,
+    /// Keys removed
+    pub keys_removed: u64,
+    /// Memtable flushes completed
+    pub flushes: u64,
+    /// SSTables retired, such as inputs replaced by a compaction's output
+    pub files_retired: u64,
+}
+
+impl LifetimeStats {
+    /// The counters in the order they are stored
+    fn fields(&self) -> [u64; 5] {
+        [
+            self.bytes_written,
+            self.keys_written,
+            self.keys_removed,
+            self.flushes,
+            self.files_retired,
+        ]
+    }
+}
+
+/// Live counters behind `LifetimeStats`, starting from the saved totals
+#[derive(Debug, Default)]
+pub(super) struct LifetimeCounters {
+    bytes_written: AtomicU64,
+    keys_written: AtomicU64,
+    keys_removed: AtomicU64,
+    flushes: AtomicU64,
+    files_retired: AtomicU64,
+}
+
+impl LifetimeCounters {
+    /// Continue from the totals saved in `dir`. A missing or damaged file
+    /// starts the counters from zero rather than failing the open.
+    pub(super) fn load(dir: &str) -> io::Result<Self> {
+        let saved = match fs::read(Path::new(dir).join(STATS_FILE_NAME)) {
+            Ok(buf) => decode(&buf).unwrap_or_default(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => LifetimeStats::default(),
+            Err(e) => return Err(e),
+        };
+        Ok(LifetimeCounters {
+            bytes_written: AtomicU64::new(saved.bytes_written),
+            keys_written: AtomicU64::new(saved.keys_written),
+            keys_removed: AtomicU64::new(saved.keys_removed),
+            flushes: AtomicU64::new(saved.flushes),
+            files_retired: AtomicU64::new(saved.files_retired),
+        })
+    }
+
+    /// Count an inserted key and its bytes
+    pub(super) fn record_write(&self, bytes: usize) {
+        self.keys_written.fetch_add(1, Ordering::Relaxed);
+        self.bytes_written
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Count a removed key
+    pub(super) fn record_remove(&self) {
+        self.keys_removed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a completed flush
+    pub(super) fn record_flush(&self) {
+        self.flushes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a retired SSTable
+    pub(super) fn record_retirement(&self) {
+        self.files_retired.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn totals(&self) -> LifetimeStats {
+        LifetimeStats {
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            keys_written: self.keys_written.load(Ordering::Relaxed),
+            keys_removed: self.keys_removed.load(Ordering::Relaxed),
+            flushes: self.flushes.load(Ordering::Relaxed),
+            files_retired: self.files_retired.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl LsmIndex {
+    /// Cumulative counters, including those saved before the last restart
+    pub fn lifetime_stats(&self) -> LifetimeStats {
+        self.lifetime.totals()
+    }
+
+    /// Save the lifetime statistics so the next open continues from them.
+    /// `shutdown` calls this; call it directly to checkpoint them sooner.
+    pub fn save_lifetime_stats(&self) -> Result<()> {
+        let dir = Path::new(&self.base_path);
+        let tmp_path = dir.join(format!("{}.tmp", STATS_FILE_NAME));
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&encode(&self.lifetime.totals()))?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, dir.join(STATS_FILE_NAME))?;
+        Ok(())
+    }
+}
+
+/// Encode the counters after a count of them, so later versions can add
+/// counters that older readers skip
+fn encode(stats: &LifetimeStats) -> Vec<u8> {
+    let fields = stats.fields();
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&STATS_MAGIC.to_le_bytes());
+    buf.extend_from_slice(&STATS_VERSION.to_le_bytes());
+    buf.extend_from_slice(&(fields.len() as u32).to_le_bytes());
+    for value in fields {
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+    let checksum = crc32fast::hash(&buf);
+    buf.extend_from_slice(&checksum.to_le_bytes());
+    buf
+}
+
+/// Decode counters written by `encode`, or `None` if the file is damaged
+fn decode(buf: &[u8]) -> Option<LifetimeStats> {
+    let (body, checksum) = buf.split_at_checked(buf.len().checked_sub(4)?)?;
+    if crc32fast::hash(body) != u32::from_le_bytes(checksum.try_into().ok()?) {
+        return None;
+    }
+
+    let header = |at: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(body.get(at..at + 4)?.try_into().ok()?))
+    };
+    if header(0)? != STATS_MAGIC || header(4)? > STATS_VERSION {
+        return None;
+    }
+    let count = header(8)? as usize;
+    let values = body.get(12..)?;
+    if values.len() != count * 8 {
+        return None;
+    }
+    let mut fields = values
+        .chunks_exact(8)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()));
+    let mut next = || fields.next().unwrap_or(0);
+
+    Some(LifetimeStats {
+        bytes_written: next(),
+        keys_written: next(),
+        keys_removed: next(),
+        flushes: next(),
+        files_retired: next(),
+    })
+}
@@ -0,0 +1,51 @@
+use super::{LsmIndex, LsmIndexError, Manifest, Result};
+use crate::wal::durability::DurabilityManager;
+use std::sync::{LockResult, MutexGuard};
+
+pub(super) use crate::locks::recover;
+
+/// What an `LsmIndex` does with a lock left poisoned by a thread that
+/// panicked while holding it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoisonPolicy {
+    /// Fail the operation with `LsmIndexError::Internal`, leaving the
+    /// caller to decide whether to reopen the index
+    #[default]
+    Fail,
+    /// Take the lock anyway and carry on with whatever state the panicking
+    /// thread left behind
+    Recover,
+    /// Panic, propagating the first panic to every later caller
+    Panic,
+}
+
+impl LsmIndex {
+    /// The guard of the lock on `what`, or, if a panic poisoned it, what
+    /// `LsmIndexOptions::poison_policy` calls for
+    pub(super) fn checked_lock<G>(&self, result: LockResult<G>, what: &str) -> Result<G> {
+        match result {
+            Ok(guard) => Ok(guard),
+            Err(poisoned) => match self.options().poison_policy {
+                PoisonPolicy::Fail => Err(LsmIndexError::Internal(format!(
+                    "The {} lock was poisoned by a panic",
+                    what
+                ))),
+                PoisonPolicy::Recover => {
+                    warn!("Recovering the {} lock after a panic poisoned it", what);
+                    Ok(poisoned.into_inner())
+                }
+                PoisonPolicy::Panic => panic!("The {} lock was poisoned by a panic", what),
+            },
+        }
+    }
+
+    /// Lock the WAL, which every write holds while it is logged and applied
+    pub(super) fn lock_wal(&self) -> Result<MutexGuard<'_, DurabilityManager>> {
+        self.checked_lock(self.durability_manager.lock(), "WAL")
+    }
+
+    /// Lock the manifest
+    pub(super) fn lock_manifest(&self) -> Result<MutexGuard<'_, Manifest>> {
+        self.checked_lock(self.manifest.lock(), "manifest")
+    }
+}
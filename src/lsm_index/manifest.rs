@@ -0,0 +1,485 @@
+use crate::sstable::SSTableInfo;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest file inside the index's base directory
+pub const MANIFEST_FILE_NAME: &str = "MANIFEST";
+/// Magic number at the start of a manifest file ("LSMF")
+const MANIFEST_MAGIC: u32 = 0x4C53_4D46;
+/// Current manifest format version; version 2 adds tombstone counts,
+/// version 3 file numbers, version 4 applied WAL LSNs, version 5 maximum
+/// entry sequences and version 6 flush numbers
+const MANIFEST_VERSION: u32 = 6;
+
+/// What the manifest records about one live SSTable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// Path to the SSTable file
+    pub path: String,
+    /// Level the SSTable belongs to
+    pub level: u32,
+    /// Number of entries in the SSTable
+    pub entry_count: u64,
+    /// Size of the whole file in bytes
+    pub size_bytes: u64,
+    /// When the SSTable was written, in seconds since the Unix epoch
+    pub created_at_secs: u64,
+    /// Size of the Bloom filter section in bytes, 0 if there is none
+    pub bloom_bytes: u64,
+    /// Total size of the keys and values stored in the file
+    pub raw_bytes: u64,
+    /// On-disk size of the data section holding the entries
+    pub data_bytes: u64,
+    /// Number of tombstones recorded in the SSTable
+    pub tombstone_count: u64,
+    /// Number allocated to the file by the manifest, 0 if it has none
+    pub file_number: u64,
+    /// File number of the newest flush whose writes the file holds, which
+    /// orders files with overlapping keys; the file number in manifests
+    /// older than version 6
+    pub flush_number: u64,
+    /// WAL LSN up to which logged writes are in the file, 0 if unknown
+    pub applied_lsn: u64,
+    /// Largest sequence of any entry in the file, 0 if its entries carry
+    /// no sequences
+    pub max_sequence: u64,
+    /// Smallest key in the SSTable, if it has any entries
+    pub min_key: Option<String>,
+    /// Largest key in the SSTable, if it has any entries
+    pub max_key: Option<String>,
+}
+
+impl FileMetadata {
+    /// Describe the file as an `SSTableInfo`
+    pub fn to_info(&self) -> SSTableInfo {
+        let compression_ratio = if self.data_bytes == 0 {
+            1.0
+        } else {
+            self.raw_bytes as f64 / self.data_bytes as f64
+        };
+
+        SSTableInfo {
+            path: self.path.clone(),
+            size_bytes: self.size_bytes,
+            entry_count: self.entry_count,
+            has_bloom_filter: self.bloom_bytes > 0,
+            level: self.level,
+            min_key: self.min_key.clone(),
+            max_key: self.max_key.clone(),
+            created_at_secs: self.created_at_secs,
+            bloom_bytes: self.bloom_bytes,
+            raw_bytes: self.raw_bytes,
+            data_bytes: self.data_bytes,
+            tombstone_count: self.tombstone_count,
+            file_number: self.file_number,
+            flush_number: self.flush_number,
+            max_sequence: self.max_sequence,
+            compression_ratio,
+        }
+    }
+}
+
+/// The set of live SSTables, persisted next to them so the file inventory
+/// survives restarts without scraping the directory.
+///
+/// Every change rewrites the whole manifest to a temporary file and renames
+/// it over the old one, so a crash leaves either the old or the new version.
+///
+/// The manifest also hands out file numbers. The next number is persisted
+/// before it is returned, so numbers only increase and are never reused,
+/// even across crashes.
+#[derive(Debug)]
+pub struct Manifest {
+    /// Directory holding the manifest and the SSTables it lists
+    dir: PathBuf,
+    /// Live files keyed by path
+    files: BTreeMap<String, FileMetadata>,
+    /// Number the next allocated file receives; numbering starts at 1
+    next_file_number: u64,
+}
+
+impl Manifest {
+    /// Load the manifest in `dir`, or start an empty one if there is none yet
+    pub fn open(dir: &str) -> io::Result<Self> {
+        let dir = PathBuf::from(dir);
+        let path = dir.join(MANIFEST_FILE_NAME);
+
+        let (files, next_file_number) = match fs::read(&path) {
+            Ok(buf) => decode(&buf, &dir)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (BTreeMap::new(), 1),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Manifest {
+            dir,
+            files,
+            next_file_number,
+        })
+    }
+
+    /// Path of the manifest file
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(MANIFEST_FILE_NAME)
+    }
+
+    /// Live files in path order
+    pub fn files(&self) -> impl Iterator<Item = &FileMetadata> {
+        self.files.values()
+    }
+
+    /// Metadata for the file at `path`, if it is live
+    pub fn get(&self, path: &str) -> Option<&FileMetadata> {
+        self.files.get(path)
+    }
+
+    /// Number of live files
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns true if no files are live
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Number the next allocated file will receive
+    pub fn next_file_number(&self) -> u64 {
+        self.next_file_number
+    }
+
+    /// Allocate a file number, persisting the allocation before returning it
+    pub fn allocate_file_number(&mut self) -> io::Result<u64> {
+        let file_number = self.next_file_number;
+        self.next_file_number += 1;
+        self.persist()?;
+        Ok(file_number)
+    }
+
+    /// Make sure numbers allocated from now on are above `file_number`, for
+    /// files found on disk that the manifest did not number
+    pub fn mark_file_number_used(&mut self, file_number: u64) -> io::Result<()> {
+        if file_number >= self.next_file_number {
+            self.next_file_number = file_number + 1;
+            self.persist()?;
+        }
+        Ok(())
+    }
+
+    /// Record a new live file, replacing any previous record for its path
+    pub fn add_file(&mut self, file: FileMetadata) -> io::Result<()> {
+        self.files.insert(file.path.clone(), file);
+        self.persist()
+    }
+
+    /// Drop a file from the live set
+    pub fn remove_file(&mut self, path: &str) -> io::Result<Option<FileMetadata>> {
+        let removed = self.files.remove(path);
+        if removed.is_some() {
+            self.persist()?;
+        }
+        Ok(removed)
+    }
+
+    /// Atomically replace the manifest file with the current state
+    fn persist(&self) -> io::Result<()> {
+        let tmp_path = self.dir.join(format!("{}.tmp", MANIFEST_FILE_NAME));
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&encode(&self.files, self.next_file_number, &self.dir))?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, self.path())
+    }
+}
+
+/// Store paths inside the manifest's directory relative to it, so the
+/// directory can be moved as a whole
+fn relative_path<'a>(path: &'a str, dir: &Path) -> &'a str {
+    Path::new(path)
+        .strip_prefix(dir)
+        .ok()
+        .and_then(Path::to_str)
+        .unwrap_or(path)
+}
+
+fn encode(files: &BTreeMap<String, FileMetadata>, next_file_number: u64, dir: &Path) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&MANIFEST_MAGIC.to_le_bytes());
+    buf.extend_from_slice(&MANIFEST_VERSION.to_le_bytes());
+    buf.extend_from_slice(&(files.len() as u32).to_le_bytes());
+
+    for file in files.values() {
+        put_string(&mut buf, relative_path(&file.path, dir));
+        buf.extend_from_slice(&file.level.to_le_bytes());
+        for value in [
+            file.entry_count,
+            file.size_bytes,
+            file.created_at_secs,
+            file.bloom_bytes,
+            file.raw_bytes,
+            file.data_bytes,
+        ] {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+        put_optional_string(&mut buf, file.min_key.as_deref());
+        put_optional_string(&mut buf, file.max_key.as_deref());
+        buf.extend_from_slice(&file.tombstone_count.to_le_bytes());
+        buf.extend_from_slice(&file.file_number.to_le_bytes());
+        buf.extend_from_slice(&file.applied_lsn.to_le_bytes());
+        buf.extend_from_slice(&file.max_sequence.to_le_bytes());
+        buf.extend_from_slice(&file.flush_number.to_le_bytes());
+    }
+    buf.extend_from_slice(&next_file_number.to_le_bytes());
+
+    let checksum = crc32fast::hash(&buf);
+    buf.extend_from_slice(&checksum.to_le_bytes());
+    buf
+}
+
+type Decoded = (BTreeMap<String, FileMetadata>, u64);
+
+fn decode(buf: &[u8], dir: &Path) -> io::Result<Decoded> {
+    if buf.len() < 4 {
+        return Err(corrupt("Manifest is too short"));
+    }
+    let (body, checksum) = buf.split_at(buf.len() - 4);
+    if crc32fast::hash(body) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+        return Err(corrupt("Manifest checksum verification failed"));
+    }
+
+    let mut cursor = body;
+    if get_u32(&mut cursor)? != MANIFEST_MAGIC {
+        return Err(corrupt("Invalid manifest magic number"));
+    }
+    let version = get_u32(&mut cursor)?;
+    if version > MANIFEST_VERSION {
+        return Err(corrupt(&format!(
+            "Unsupported manifest version: {}",
+            version
+        )));
+    }
+
+    let count = get_u32(&mut cursor)?;
+    let mut files = BTreeMap::new();
+    for _ in 0..count {
+        let relative = get_string(&mut cursor)?;
+        let path = dir.join(&relative).to_string_lossy().to_string();
+        let mut file = FileMetadata {
+            path: path.clone(),
+            level: get_u32(&mut cursor)?,
+            entry_count: get_u64(&mut cursor)?,
+            size_bytes: get_u64(&mut cursor)?,
+            created_at_secs: get_u64(&mut cursor)?,
+            bloom_bytes: get_u64(&mut cursor)?,
+            raw_bytes: get_u64(&mut cursor)?,
+            data_bytes: get_u64(&mut cursor)?,
+            tombstone_count: 0,
+            file_number: 0,
+            flush_number: 0,
+            applied_lsn: 0,
+            max_sequence: 0,
+            min_key: get_optional_string(&mut cursor)?,
+            max_key: get_optional_string(&mut cursor)?,
+        };
+        if version >= 2 {
+            file.tombstone_count = get_u64(&mut cursor)?;
+        }
+        if version >= 3 {
+            file.file_number = get_u64(&mut cursor)?;
+        }
+        if version >= 4 {
+            file.applied_lsn = get_u64(&mut cursor)?;
+        }
+        if version >= 5 {
+            file.max_sequence = get_u64(&mut cursor)?;
+        }
+        file.flush_number = if version >= 6 {
+            get_u64(&mut cursor)?
+        } else {
+            file.file_number
+        };
+        files.insert(path, file);
+    }
+
+    // Older manifests did not number files; continue past any numbers seen
+    let next_file_number = if version >= 3 {
+        get_u64(&mut cursor)?
+    } else {
+        files
+            .values()
+            .map(|file| file.file_number)
+            .max()
+            .unwrap_or(0)
+            + 1
+    };
+
+    Ok((files, next_file_number))
+}
+
+fn put_string(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+fn put_optional_string(buf: &mut Vec<u8>, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            buf.push(1);
+            put_string(buf, value);
+        }
+        None => buf.push(0),
+    }
+}
+
+fn take<'a>(cursor: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if cursor.len() < len {
+        return Err(corrupt("Truncated manifest"));
+    }
+    let (bytes, rest) = cursor.split_at(len);
+    *cursor = rest;
+    Ok(bytes)
+}
+
+fn get_u32(cursor: &mut &[u8]) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(take(cursor, 4)?.try_into().unwrap()))
+}
+
+fn get_u64(cursor: &mut &[u8]) -> io::Result<u64> {
+    Ok(u64::from_le_bytes(take(cursor, 8)?.try_into().unwrap()))
+}
+
+fn get_string(cursor: &mut &[u8]) -> io::Result<String> {
+    let len = get_u32(cursor)? as usize;
+    String::from_utf8(take(cursor, len)?.to_vec())
+        .map_err(|_| corrupt("Manifest string is not valid UTF-8"))
+}
+
+fn get_optional_string(cursor: &mut &[u8]) -> io::Result<Option<String>> {
+    match take(cursor, 1)?[0] {
+        0 => Ok(None),
+        _ => get_string(cursor).map(Some),
+    }
+}
+
+fn corrupt(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn sample(dir: &Path, name: &str) -> FileMetadata {
+        FileMetadata {
+            path: dir.join(name).to_string_lossy().to_string(),
+            level: 1,
+            entry_count: 10,
+            size_bytes: 4096,
+            created_at_secs: 1_700_000_000,
+            bloom_bytes: 64,
+            raw_bytes: 300,
+            data_bytes: 380,
+            tombstone_count: 2,
+            file_number: 7,
+            flush_number: 5,
+            applied_lsn: 1234,
+            max_sequence: 99,
+            min_key: Some("a".to_string()),
+            max_key: None,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+
+        let mut manifest = Manifest::open(path).unwrap();
+        manifest.add_file(sample(dir.path(), "one.db")).unwrap();
+        manifest.add_file(sample(dir.path(), "two.db")).unwrap();
+        manifest
+            .remove_file(&sample(dir.path(), "one.db").path)
+            .unwrap();
+
+        let reopened = Manifest::open(path).unwrap();
+        let files: Vec<_> = reopened.files().cloned().collect();
+        assert_eq!(files, vec![sample(dir.path(), "two.db")]);
+    }
+
+    #[test]
+    fn test_version_1_has_no_tombstone_counts() {
+        let dir = tempdir().unwrap();
+        let file = sample(dir.path(), "one.db");
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&MANIFEST_MAGIC.to_le_bytes());
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        put_string(&mut buf, "one.db");
+        buf.extend_from_slice(&file.level.to_le_bytes());
+        for value in [
+            file.entry_count,
+            file.size_bytes,
+            file.created_at_secs,
+            file.bloom_bytes,
+            file.raw_bytes,
+            file.data_bytes,
+        ] {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+        put_optional_string(&mut buf, file.min_key.as_deref());
+        put_optional_string(&mut buf, file.max_key.as_deref());
+        let checksum = crc32fast::hash(&buf);
+        buf.extend_from_slice(&checksum.to_le_bytes());
+
+        let (files, next_file_number) = decode(&buf, dir.path()).unwrap();
+        let decoded = files.values().next().unwrap();
+        assert_eq!(decoded.tombstone_count, 0);
+        assert_eq!(
+            decoded,
+            &FileMetadata {
+                tombstone_count: 0,
+                file_number: 0,
+                flush_number: 0,
+                applied_lsn: 0,
+                max_sequence: 0,
+                ..file
+            }
+        );
+        assert_eq!(next_file_number, 1);
+    }
+
+    #[test]
+    fn test_file_numbers_survive_reopen() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+
+        let mut manifest = Manifest::open(path).unwrap();
+        assert_eq!(manifest.allocate_file_number().unwrap(), 1);
+        assert_eq!(manifest.allocate_file_number().unwrap(), 2);
+        manifest.mark_file_number_used(1).unwrap();
+        assert_eq!(manifest.next_file_number(), 3);
+
+        let mut reopened = Manifest::open(path).unwrap();
+        assert_eq!(reopened.allocate_file_number().unwrap(), 3);
+        reopened.mark_file_number_used(10).unwrap();
+        assert_eq!(Manifest::open(path).unwrap().next_file_number(), 11);
+    }
+
+    #[test]
+    fn test_corruption_is_detected() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+
+        let mut manifest = Manifest::open(path).unwrap();
+        manifest.add_file(sample(dir.path(), "one.db")).unwrap();
+
+        let mut bytes = fs::read(manifest.path()).unwrap();
+        bytes[12] ^= 0xFF;
+        fs::write(manifest.path(), bytes).unwrap();
+
+        let err = Manifest::open(path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
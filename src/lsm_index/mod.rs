@@ -1,12 +1,22 @@
 use crate::bptree::StorageReference;
-use crate::memtable::{Memtable, MemtableError, SSTableWriter, StringMemtable};
-use crate::wal::durability::{DurabilityManager, Operation};
+use crate::checked_len;
+use crate::memtable::{Memtable, MemtableError, StringMemtable};
+use crate::sstable::digest::Digest;
+use crate::sstable::{
+    FragmentedRangeTombstones, KeyExpander, KeyProvider, SSTableCompaction, SSTableInfo,
+    SSTableRecord, TableFile, Tombstone,
+};
+use crate::wal::durability::{CheckpointFile, DurabilityManager, Operation};
 use crossbeam_skiplist::SkipMap;
-use std::collections::HashSet;
-use std::fs::{self, File};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
 use std::io::{self, BufReader, Read, Seek, SeekFrom};
-use std::ops::RangeBounds;
-use std::sync::{Arc, Mutex};
+use std::ops::{Bound, RangeBounds};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 // Export the skip_list module
 pub mod skip_list;
@@ -16,12 +26,80 @@ pub mod skip_list_index;
 pub mod gen_index_entry;
 pub mod gen_ref;
 
+mod archive;
+mod background;
+pub mod bloom_policy;
+pub mod columns;
+mod compaction;
+pub mod compaction_scheduler;
+mod consistency;
+pub mod cursor;
+pub mod diff;
+mod disk_space;
+mod flush;
+mod fork;
+mod history;
+mod ingest;
+mod lifetime_stats;
+mod locks;
+pub mod manifest;
+pub mod options;
+pub mod placement;
+mod range_delete;
+mod retry;
+mod runtime_options;
+mod sequence;
+mod snapshots;
+mod soft_delete;
+pub mod sstable_file;
+mod stats;
+mod table_cache;
+mod trace;
+mod ttl;
+mod value_log;
+mod write_batch;
+
 // Re-export the SkipListIndex
 pub use skip_list_index::SkipListIndex;
 // Re-export the generational reference counting types for external use
 pub use gen_index_entry::GenIndexEntry;
 pub use gen_ref::{make_gen_ref, GenRefHandle};
 
+pub use crate::sstable::{DelimiterPrefixExtractor, FixedPrefixExtractor, PrefixExtractor};
+pub use archive::SNAPSHOT_METADATA_NAME;
+pub use background::{BackgroundJob, WorkSummary};
+pub use bloom_policy::{AdaptiveFprPolicy, BloomFprPolicy, FilterContext, FixedFprPolicy};
+pub use columns::{decode_columns, encode_columns};
+pub use compaction_scheduler::{
+    CompactionContext, CompactionDecision, CompactionReason, CompactionScheduler,
+};
+pub use consistency::{CheckpointMismatch, ConsistencyCheck, ConsistencyReport, SET_ASIDE_SUFFIX};
+pub use cursor::LsmCursor;
+pub use diff::{diff, DiffKind, KeyDifference, RangeDigests, RangeSummary};
+pub use disk_space::{DiskSpaceProbe, FileSystemProbe};
+pub use history::{AsOf, Version};
+pub use lifetime_stats::{LifetimeStats, STATS_FILE_NAME};
+pub use locks::PoisonPolicy;
+pub use manifest::{FileMetadata, Manifest};
+pub use options::{LsmIndexOptions, ReadOptions, WriteOptions};
+pub use placement::{
+    ColdByAge, ColdByLevel, ColdStorageContext, ColdStoragePolicy, DirectoryUsage, FileNameContext,
+    FileNamer, LeastUsedPlacement, LevelPlacement, NumberedFileNamer, PlacementContext,
+    PlacementPolicy, PrefixedFileNamer, RoundRobinPlacement, TimestampFileNamer,
+};
+pub use retry::{ErrorClass, RetryEvent, RetryObserver, RetryPolicy};
+pub use runtime_options::{OptionChange, OptionChangeEvent, OptionsObserver};
+pub use snapshots::Snapshot;
+pub use sstable_file::{SSTableFile, SSTableFileRef};
+pub use stats::{
+    FileHotness, LevelReadStats, LevelStorageStats, ResourceUsage, SSTableAccessStats,
+};
+pub use trace::MAX_TRACE_IDS;
+pub use ttl::TtlSweeper;
+pub use value_log::{ValueLogGcSummary, VALUE_LOG_EXTENSION};
+use value_log::{SegmentWriter, ValuePointer};
+pub use write_batch::{BatchEntry, WriteBatchWithIndex};
+
 /// Error type for LSM index operations
 #[derive(Debug)]
 pub enum LsmIndexError {
@@ -37,6 +115,52 @@ pub enum LsmIndexError {
     KeyNotFound,
     /// Invalid operation
     InvalidOperation(String),
+    /// Key exceeds the configured maximum key size
+    KeyTooLarge {
+        /// Size of the rejected key in bytes
+        size: usize,
+        /// Configured maximum key size in bytes
+        max: usize,
+    },
+    /// Key was rejected by write-time validation
+    InvalidKey(String),
+    /// Value exceeds the configured maximum value size
+    ValueTooLarge {
+        /// Size of the rejected value in bytes
+        size: usize,
+        /// Configured maximum value size in bytes
+        max: usize,
+    },
+    /// A write was refused because it would leave less free space than
+    /// the reserved headroom
+    DiskFull {
+        /// Path on the file system that is short of space
+        path: String,
+        /// Bytes available on that file system
+        available: u64,
+        /// Bytes the write and the headroom need together
+        required: u64,
+    },
+    /// A conditional write found the key changed after the sequence it was
+    /// conditioned on
+    PreconditionFailed {
+        /// The key written
+        key: String,
+        /// Sequence of the key's last write
+        sequence: u64,
+    },
+    /// A file operation kept failing after the retry policy gave up
+    Unrecoverable {
+        /// The operation that failed, such as `"flush"`
+        operation: String,
+        /// Number of attempts made
+        attempts: u32,
+        /// The error the last attempt failed with
+        error: io::Error,
+    },
+    /// The index could not do its part of an operation, such as taking a
+    /// lock a panicking thread poisoned; the message says what went wrong
+    Internal(String),
 }
 
 impl From<io::Error> for LsmIndexError {
@@ -76,23 +200,78 @@ pub struct SSTableReader {
     entry_count: u64,
     /// Whether the SSTable has a Bloom filter
     has_bloom_filter: bool,
+    /// Merkle root of the SSTable's entries, from its properties
+    content_digest: Option<Digest>,
+    /// Level the SSTable belongs to
+    level: u32,
+    /// Restores the SSTable's values if they are stored compressed
+    decoder: crate::sstable::ValueDecoder,
+    /// Rebuilds the SSTable's keys if they are stored prefix-compressed
+    key_expander: Option<KeyExpander>,
+    /// Earliest expiry time of any entry in the SSTable, if any entry expires
+    min_expiry_ms: Option<u64>,
+    /// Value log segment of each key whose value was separated from the
+    /// SSTable
+    value_log_segments: Arc<HashMap<String, u64>>,
+    /// When the table cache last looked the reader up
+    last_used: AtomicU64,
 }
 
 impl SSTableReader {
     /// Open an SSTable reader for the given path
     pub fn open(path: &str) -> io::Result<Self> {
+        Self::open_at_level(path, 0)
+    }
+
+    /// Open an SSTable reader for a file on the given level
+    pub fn open_at_level(path: &str, level: u32) -> io::Result<Self> {
+        Self::open_with_filter_cache(path, level, None)
+    }
+
+    /// Open an SSTable reader for a file on the given level, leaving its
+    /// Bloom filter to `filter_cache` if one is given
+    pub fn open_with_filter_cache(
+        path: &str,
+        level: u32,
+        filter_cache: Option<&Arc<crate::sstable::FilterCache>>,
+    ) -> io::Result<Self> {
+        Self::open_with(path, level, filter_cache, None)
+    }
+
+    /// Open an SSTable reader for a file on the given level, leaving its
+    /// Bloom filter to `filter_cache` and getting its key from
+    /// `key_provider` if either is given
+    pub(crate) fn open_with(
+        path: &str,
+        level: u32,
+        filter_cache: Option<&Arc<crate::sstable::FilterCache>>,
+        key_provider: Option<&dyn KeyProvider>,
+    ) -> io::Result<Self> {
         // Open the actual reader from the sstable module
-        let reader = crate::sstable::SSTableReader::open(path)?;
+        let reader =
+            crate::sstable::SSTableReader::open_with(path, filter_cache.cloned(), key_provider)?;
 
         // Extract information from the reader
         let entry_count = reader.entry_count();
         let has_bloom_filter = reader.has_bloom_filter();
+        let content_digest = reader.content_digest();
+        let decoder = reader.value_decoder().clone();
+        let key_expander = reader.key_expander();
+        let min_expiry_ms = reader.min_expiry_ms();
+        let value_log_segments = reader.value_log_segments().clone();
 
         Ok(Self {
             file_path: path.to_string(),
             reader: Some(reader),
             entry_count,
             has_bloom_filter,
+            content_digest,
+            level,
+            decoder,
+            key_expander,
+            min_expiry_ms,
+            value_log_segments,
+            last_used: AtomicU64::new(0),
         })
     }
 
@@ -132,6 +311,201 @@ impl SSTableReader {
     pub fn has_bloom_filter(&self) -> bool {
         self.has_bloom_filter
     }
+
+    /// Level the SSTable belongs to
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// Whether the reader holds the file open
+    pub fn holds_file(&self) -> bool {
+        self.reader.is_some()
+    }
+
+    /// Estimated heap memory held by the reader
+    pub fn memory_usage(&self) -> usize {
+        self.file_path.len()
+            + self
+                .reader
+                .as_ref()
+                .map_or(0, |reader| reader.memory_usage())
+    }
+
+    /// Merkle root of the SSTable's entries, if the file records one
+    pub fn content_digest(&self) -> Option<Digest> {
+        self.content_digest
+    }
+
+    /// Decoder for values read directly from the SSTable file
+    pub fn value_decoder(&self) -> &crate::sstable::ValueDecoder {
+        &self.decoder
+    }
+
+    /// Rebuilds keys read directly from the SSTable file, if they are
+    /// stored prefix-compressed
+    pub(crate) fn key_expander(&self) -> Option<KeyExpander> {
+        self.key_expander.clone()
+    }
+
+    /// Check whether the SSTable records a tombstone for a key, or for a
+    /// range holding it
+    pub fn has_tombstone(&self, key: &str) -> bool {
+        self.reader.as_ref().is_some_and(|reader| {
+            reader.tombstones().contains_key(key) || reader.range_tombstones().covers(key)
+        })
+    }
+
+    /// Earliest expiry time of any entry in the SSTable, if any entry expires
+    pub fn min_expiry_ms(&self) -> Option<u64> {
+        self.min_expiry_ms
+    }
+
+    /// Value log segment of each key whose value was separated from the
+    /// SSTable
+    pub fn value_log_segments(&self) -> &Arc<HashMap<String, u64>> {
+        &self.value_log_segments
+    }
+
+    /// Keys in the SSTable that had expired by `now_ms`. Files whose earliest
+    /// expiry is later are skipped without looking at their keys.
+    pub fn expired_keys(&self, now_ms: u64) -> Vec<String> {
+        match &self.reader {
+            Some(reader) if reader.min_expiry_ms().is_some_and(|min| min <= now_ms) => reader
+                .expiries()
+                .iter()
+                .filter(|(_, expires_at_ms)| **expires_at_ms <= now_ms)
+                .map(|(key, _)| key.clone())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A value returned together with its entry checksum
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksummedValue {
+    /// The stored value
+    pub value: Vec<u8>,
+    /// Checksum of the encoded entry, computed with the `ChecksumKind` of
+    /// the file holding it, or of the index for entries not yet flushed
+    pub checksum: u32,
+    /// True if the checksum was read from disk and matched the data
+    pub verified: bool,
+}
+
+/// A value returned together with what is known about when it was written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueWithMetadata {
+    /// The stored value
+    pub value: Vec<u8>,
+    /// Wall-clock time the entry was last written, if write times are tracked
+    pub written_at: Option<SystemTime>,
+}
+
+/// What `LsmIndex::get_if_cached` could tell without reading from disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CachedValue {
+    /// The value, held in memory
+    Found(Vec<u8>),
+    /// The key is known to have no value
+    Absent,
+    /// Answering would mean reading an SSTable
+    NotCached,
+}
+
+/// Where entries start in an SSTable file and how they are encoded
+struct SSTableLayout {
+    /// Number of entries in the data section
+    entry_count: u64,
+    /// Whether each entry is followed by a checksum
+    has_entry_checksums: bool,
+    /// Function entry checksums were computed with
+    checksum_kind: crate::sstable::ChecksumKind,
+    /// Bytes the data section takes in the file, fewer than its entries
+    /// when its blocks are compressed
+    data_bytes: u64,
+    /// Size of the Bloom filter section in bytes
+    bloom_bytes: u64,
+}
+
+/// What indexing an SSTable learned about its contents
+struct SSTableSummary {
+    entry_count: u64,
+    min_key: Option<String>,
+    max_key: Option<String>,
+    /// Total size of the keys and values
+    raw_bytes: u64,
+    /// On-disk size of the data section
+    data_bytes: u64,
+    bloom_bytes: u64,
+    tombstone_count: u64,
+    /// WAL LSN up to which logged writes are in the file, 0 if unknown
+    applied_lsn: u64,
+    /// Largest sequence of any entry in the file, 0 if none carry one
+    max_sequence: u64,
+    /// File number of the newest flush whose writes the file holds, if it
+    /// records one
+    flush_number: Option<u64>,
+}
+
+/// How new an SSTable's data is, ordering files from oldest to newest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct SSTableAge {
+    /// File number of the newest flush whose writes the file holds
+    flush_number: u64,
+    /// Number the manifest allocated to the file, 0 if it has none
+    file_number: u64,
+    /// When the file was written, in seconds since the Unix epoch
+    created_at_secs: u64,
+}
+
+/// Everything indexing an SSTable reads from it, gathered before the index
+/// is touched so files can be read side by side
+struct LoadedSSTable {
+    layout: SSTableLayout,
+    write_times: HashMap<String, u64>,
+    expiries: HashMap<String, u64>,
+    tombstones: HashMap<String, Tombstone>,
+    range_tombstones: FragmentedRangeTombstones,
+    applied_lsn: u64,
+    sequences: HashMap<String, u64>,
+    max_sequence: Option<u64>,
+    flush_number: Option<u64>,
+    /// Key, offset and decoded value length of each entry in file order;
+    /// values stay on disk
+    entries: Vec<(String, u64, u64)>,
+}
+
+/// What reading an entry back from an SSTable needs from the file: its value
+/// decoder, key expander and the keys whose values are in the value log
+type EntryDecoders = (
+    crate::sstable::ValueDecoder,
+    Option<KeyExpander>,
+    Arc<HashMap<String, u64>>,
+);
+
+/// An entry read back from an SSTable through a storage reference
+struct StoredEntry {
+    key: String,
+    /// The value as stored, which may be compressed
+    value: Vec<u8>,
+    stored_checksum: Option<u32>,
+    checksum_kind: crate::sstable::ChecksumKind,
+    decoder: crate::sstable::ValueDecoder,
+    /// Keys of the file whose stored value points into the value log
+    value_log_segments: Arc<HashMap<String, u64>>,
+}
+
+impl StoredEntry {
+    /// Check the stored checksum against the data that was read
+    fn checksum_matches(&self) -> bool {
+        self.stored_checksum == Some(self.checksum_kind.entry_checksum(&self.key, &self.value))
+    }
+
+    /// The value as it was written
+    fn into_value(self) -> Result<Vec<u8>> {
+        Ok(self.decoder.decode(self.value)?)
+    }
 }
 
 /// Convert from legacy IndexEntry to generational GenIndexEntry
@@ -157,16 +531,55 @@ pub struct LsmIndex {
     index: Arc<SkipMap<String, GenIndexEntry>>,
     /// Durability manager for crash recovery
     durability_manager: Arc<Mutex<DurabilityManager>>,
-    /// Cache of SSTable readers for quick access
-    sstable_readers: Arc<SkipMap<String, SSTableReader>>,
+    /// Cache of SSTable readers for quick access, holding at most
+    /// `max_open_files` of them open
+    sstable_readers: Arc<table_cache::TableCache>,
     /// Base directory for SSTables
     base_path: String,
     /// Bloom filter false positive rate
-    #[allow(dead_code)]
     bloom_filter_fpr: f64,
     /// Whether to use Bloom filters
-    #[allow(dead_code)]
     use_bloom_filters: bool,
+    /// Limits and behaviour, configured at creation time and partly
+    /// adjustable with `set_options`
+    options: RwLock<Arc<LsmIndexOptions>>,
+    /// Per-level read and lifetime statistics
+    level_stats: Arc<stats::LevelStats>,
+    /// Persistent record of the live SSTables
+    manifest: Arc<Mutex<Manifest>>,
+    /// Per-SSTable probe counters used to plan lookups
+    file_access: Arc<stats::FileAccessStats>,
+    /// Sampled reads per SSTable, if read sampling is enabled
+    read_sampler: Option<Arc<stats::ReadSampler>>,
+    /// One handle per live SSTable; index entries pin files through clones
+    live_files: Arc<SkipMap<String, SSTableFileRef>>,
+    /// Values of soft-deleted keys, kept until their retention period ends
+    deleted: Arc<SkipMap<String, Tombstone>>,
+    /// Superseded versions of keys, kept while version retention asks for them
+    history: Arc<history::VersionHistory>,
+    /// Keys removed since the last flush with their deletion times, written
+    /// as tombstones so the removals reach older SSTables
+    removed: Arc<SkipMap<String, u64>>,
+    /// Key ranges deleted since the last flush, hiding the flushed entries
+    /// in them until the flush writing them drops those entries
+    range_tombstones: Arc<RwLock<FragmentedRangeTombstones>>,
+    /// Number of background tasks running against the index
+    background_tasks: Arc<AtomicUsize>,
+    /// Commit sequences of writes made since the index was opened
+    sequences: Arc<sequence::Sequences>,
+    /// Snapshots and cursors currently open, which compaction must respect
+    snapshots: Arc<snapshots::SnapshotList>,
+    /// Cumulative counters saved on shutdown and reloaded at open
+    lifetime: Arc<lifetime_stats::LifetimeCounters>,
+    /// Flushes and compactions started asynchronously and not yet finished
+    pending_jobs: Arc<background::PendingJobs>,
+    /// Ranges queued with `queue_compaction`, oldest first
+    compaction_queue: Mutex<Vec<compaction::QueuedRange>>,
+    /// Keys written with a TTL since the index was opened, ordered by
+    /// expiry, so a sweep only looks at keys that are due
+    ttl_queue: Mutex<BTreeSet<(u64, String)>>,
+    /// Trace IDs of writes in the memtable and of the files they reached
+    traces: trace::Traces,
 }
 
 impl LsmIndex {
@@ -178,77 +591,455 @@ impl LsmIndex {
         use_bloom_filters: bool,
         bloom_filter_fpr: f64,
     ) -> io::Result<Self> {
+        Self::new_with_options(
+            capacity,
+            base_path,
+            _compaction_interval_secs,
+            use_bloom_filters,
+            bloom_filter_fpr,
+            LsmIndexOptions::default(),
+        )
+    }
+
+    /// Create a new LSM index with explicit options
+    pub fn new_with_options(
+        capacity: usize,
+        base_path: String,
+        _compaction_interval_secs: Option<u64>,
+        use_bloom_filters: bool,
+        bloom_filter_fpr: f64,
+        options: LsmIndexOptions,
+    ) -> io::Result<Self> {
+        // Reject limits the on-disk format cannot honour
+        options.validate()?;
+
         // Create the directories if they don't exist
         fs::create_dir_all(&base_path)?;
         let wal_path = format!("{}/wal", base_path);
         fs::create_dir_all(&wal_path)?;
+        for directory in options.data_directories.iter().chain(&options.cold_path) {
+            fs::create_dir_all(directory)?;
+        }
 
-        // Create the memtable - StringMemtable only takes capacity
-        let memtable = StringMemtable::new(capacity);
+        let read_sampler = options
+            .read_sample_interval
+            .map(|interval| Arc::new(stats::ReadSampler::new(interval)));
+
+        // Create the memtable, counting keys by prefix if an extractor is set
+        let mut memtable = match &options.prefix_extractor {
+            Some(extractor) => StringMemtable::with_prefix_extractor(capacity, extractor.clone()),
+            None => StringMemtable::new(capacity),
+        };
+        if let Some(filter) = options.memtable_filter {
+            memtable = memtable.with_key_filter(filter);
+        }
 
         // Create the durability manager
-        let durability_manager =
-            DurabilityManager::new(&format!("{}/wal/wal.log", base_path), &base_path)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+        let mut durability_manager =
+            DurabilityManager::new_with_checksum_kind(
+                &format!("{}/wal/wal.log", base_path),
+                &base_path,
+                options.checksum_kind,
+            )
+            .map_err(|e| io::Error::other(format!("{:?}", e)))?
+            .with_clock(options.clock.clone());
+        if let Some(provider) = &options.key_provider {
+            durability_manager = durability_manager.with_key_provider(provider.clone());
+        }
 
         // Create the lock-free skip map index
         let index = SkipMap::new();
 
-        Ok(LsmIndex {
+        // Load the inventory of live SSTables
+        let manifest = Manifest::open(&base_path)?;
+        let lifetime = lifetime_stats::LifetimeCounters::load(&base_path)?;
+        let sequences = sequence::Sequences::new(options.clock.now_ms());
+
+        let lsm_index = LsmIndex {
             memtable,
             index: Arc::new(index),
             durability_manager: Arc::new(Mutex::new(durability_manager)),
-            sstable_readers: Arc::new(SkipMap::new()),
+            sstable_readers: Arc::new(table_cache::TableCache::new(
+                options.max_open_files,
+                options.filter_cache.clone(),
+                options.key_provider.clone(),
+            )),
             base_path,
             bloom_filter_fpr,
             use_bloom_filters,
-        })
+            options: RwLock::new(Arc::new(options)),
+            level_stats: Arc::new(stats::LevelStats::new()),
+            manifest: Arc::new(Mutex::new(manifest)),
+            file_access: Arc::new(stats::FileAccessStats::new()),
+            read_sampler,
+            live_files: Arc::new(SkipMap::new()),
+            deleted: Arc::new(SkipMap::new()),
+            history: Arc::new(history::VersionHistory::new()),
+            removed: Arc::new(SkipMap::new()),
+            range_tombstones: Arc::new(RwLock::new(FragmentedRangeTombstones::new())),
+            background_tasks: Arc::new(AtomicUsize::new(0)),
+            sequences: Arc::new(sequences),
+            snapshots: Arc::new(snapshots::SnapshotList::new()),
+            lifetime: Arc::new(lifetime),
+            pending_jobs: Arc::new(background::PendingJobs::default()),
+            compaction_queue: Mutex::new(Vec::new()),
+            ttl_queue: Mutex::new(BTreeSet::new()),
+            traces: trace::Traces::default(),
+        };
+
+        // Check the files before serving anything from them
+        let check = lsm_index.options().consistency_check;
+        if check != ConsistencyCheck::Off {
+            let report = lsm_index
+                .check_consistency(check == ConsistencyCheck::Repair)
+                .map_err(|e| io::Error::other(format!("{:?}", e)))?;
+            if check == ConsistencyCheck::Verify && !report.is_consistent() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Inconsistent database at {}: {}",
+                        lsm_index.base_path, report
+                    ),
+                ));
+            }
+        }
+
+        Ok(lsm_index)
+    }
+
+    /// Returns the options in effect: those the index was created with, as
+    /// adjusted by `set_options` since
+    pub fn options(&self) -> Arc<LsmIndexOptions> {
+        locks::recover(self.options.read()).clone()
+    }
+
+    /// Describe a file about to be written on `level`, for Bloom filter sizing
+    pub fn filter_context(&self, level: u32, expected_entries: usize) -> FilterContext {
+        FilterContext {
+            level,
+            expected_entries,
+            level_reads_per_sec: self.level_stats.reads_per_sec(level),
+            average_lifetime: self.level_stats.average_lifetime(level),
+        }
+    }
+
+    /// Choose the Bloom filter false positive rate for a new file on `level`
+    fn bloom_fpr_for(&self, level: u32, expected_entries: usize) -> f64 {
+        match &self.options().bloom_fpr_policy {
+            Some(policy) => {
+                policy.false_positive_rate(&self.filter_context(level, expected_entries))
+            }
+            None => self.bloom_filter_fpr,
+        }
+    }
+
+    /// Total SSTable reads served by files on the given level
+    pub fn level_read_count(&self, level: u32) -> u64 {
+        self.level_stats.reads(level)
+    }
+
+    /// Check that a key can be safely logged and read back.
+    ///
+    /// Keys are UTF-8 by construction. NUL bytes are still refused, as they
+    /// were when WAL insert records separated key and value with one.
+    fn validate_key(&self, key: &str) -> Result<()> {
+        if let Some(position) = key.bytes().position(|b| b == 0) {
+            return Err(LsmIndexError::InvalidKey(format!(
+                "key contains a NUL byte at position {}",
+                position
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Write options for writes made without explicit ones, following the
+    /// index's `sync_writes` setting
+    fn default_write_options(&self) -> WriteOptions {
+        WriteOptions::default().with_sync(self.options().sync_writes)
+    }
+
+    /// Check a key-value pair against the configured size limits
+    fn check_entry_size(&self, key: &str, value: &[u8]) -> Result<()> {
+        if key.len() > self.options().max_key_size {
+            return Err(LsmIndexError::KeyTooLarge {
+                size: key.len(),
+                max: self.options().max_key_size,
+            });
+        }
+
+        if value.len() > self.options().max_value_size {
+            return Err(LsmIndexError::ValueTooLarge {
+                size: value.len(),
+                max: self.options().max_value_size,
+            });
+        }
+
+        Ok(())
     }
 
     /// Insert a key-value pair
     pub fn insert(&self, key: String, value: Vec<u8>) -> Result<()> {
-        // Log the operation for durability
-        let mut durability_manager = self.durability_manager.lock().unwrap();
-        durability_manager.log_operation(Operation::Insert {
-            key: key.clone(),
-            value: value.clone(),
-        })?;
+        self.insert_entry(key, value, None, &self.default_write_options())?;
+        Ok(())
+    }
+
+    /// Insert a key-value pair with explicit durability settings and
+    /// preconditions, returning the write's commit sequence
+    pub fn insert_with_options(
+        &self,
+        key: String,
+        value: Vec<u8>,
+        write_options: &WriteOptions,
+    ) -> Result<u64> {
+        self.insert_entry(key, value, None, write_options)
+    }
+
+    /// Insert a key-value pair, expiring it at `expires_at_ms` if given, and
+    /// return its commit sequence
+    fn insert_entry(
+        &self,
+        key: String,
+        value: Vec<u8>,
+        expires_at_ms: Option<u64>,
+        write_options: &WriteOptions,
+    ) -> Result<u64> {
+        let started = Instant::now();
+        // Reject bad or oversized entries, and entries there is no room
+        // for, before they reach the WAL
+        self.validate_key(&key)?;
+        self.check_entry_size(&key, &value)?;
+        self.ensure_disk_space(&self.base_path, (key.len() + value.len()) as u64)?;
+
+        // Log the operation for durability; the lock is held either way so
+        // writes stay ordered with flushes and with each other's preconditions
+        let mut durability_manager = self.lock_wal()?;
+        self.check_unchanged(&key, write_options)?;
+        Self::log_write(
+            &mut durability_manager,
+            Operation::Insert {
+                key: key.clone(),
+                value: value.clone(),
+            },
+            write_options,
+        )?;
+
+        let sequence = self.apply_insert(key, value, expires_at_ms)?;
+        self.finish_write(write_options, started);
+        Ok(sequence)
+    }
+
+    /// Log `operation` to the WAL unless `write_options` disables it, syncing
+    /// it if they ask to. Called with the WAL lock held.
+    fn log_write(
+        durability_manager: &mut DurabilityManager,
+        operation: Operation,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        if !write_options.disable_wal {
+            durability_manager.log_operation_with_sync(operation, write_options.sync)?;
+        }
+        Ok(())
+    }
 
+    /// Remember a successful write's trace ID for the flush that persists
+    /// it, and log the write if it was slow. Called with the WAL lock held,
+    /// so no flush runs between applying the write and recording its ID.
+    fn finish_write(&self, write_options: &WriteOptions, started: Instant) {
+        if let Some(trace_id) = &write_options.trace_id {
+            self.traces.record_write(trace_id);
+        }
+        self.log_if_slow(
+            "write",
+            started.elapsed(),
+            write_options.trace_id.as_slice(),
+        );
+    }
+
+    /// Apply a logged insert to the memtable and index, returning its
+    /// commit sequence. Called with the WAL lock held.
+    fn apply_insert(&self, key: String, value: Vec<u8>, expires_at_ms: Option<u64>) -> Result<u64> {
         // Insert into the memtable
+        let value_len = value.len();
         match self.memtable.insert(key.clone(), value.clone()) {
             Ok(_) => {
+                let sequence = self.commit_sequence(&key);
+                self.record_version(&key, sequence, Some(&value));
+
                 // Update the index with the in-memory value
-                self.index
-                    .insert(key, GenIndexEntry::new(Some(value), None));
-                Ok(())
+                let mut entry = GenIndexEntry::new(Some(value), None);
+                if self.options().track_write_times {
+                    entry = entry.with_written_at_ms(self.now_ms());
+                }
+                if let Some(expires_at_ms) = expires_at_ms {
+                    entry = entry.with_expires_at_ms(expires_at_ms);
+                    locks::recover(self.ttl_queue.lock()).insert((expires_at_ms, key.clone()));
+                }
+                // A new value supersedes any soft-deleted one
+                self.deleted.remove(&key);
+                self.removed.remove(&key);
+                self.lifetime.record_write(key.len() + value_len);
+                self.index.insert(key, entry);
+                Ok(sequence)
             }
             Err(e) => Err(LsmIndexError::MemtableError(e)),
         }
     }
 
+    /// Insert a key-value pair where the key is given as raw bytes.
+    ///
+    /// Keys are stored as UTF-8 strings, so byte keys that are not valid
+    /// UTF-8 are rejected with `InvalidKey` instead of being written.
+    pub fn insert_bytes(&self, key: &[u8], value: Vec<u8>) -> Result<()> {
+        self.insert_bytes_with_options(key, value, &self.default_write_options())?;
+        Ok(())
+    }
+
+    /// Insert a key-value pair where the key is given as raw bytes, with
+    /// explicit durability settings and preconditions
+    pub fn insert_bytes_with_options(
+        &self,
+        key: &[u8],
+        value: Vec<u8>,
+        write_options: &WriteOptions,
+    ) -> Result<u64> {
+        let key = std::str::from_utf8(key)
+            .map_err(|e| LsmIndexError::InvalidKey(format!("key is not valid UTF-8: {}", e)))?;
+        self.insert_with_options(key.to_string(), value, write_options)
+    }
+
     /// Remove a key
     pub fn remove(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.remove_with_options(key, &self.default_write_options())
+    }
+
+    /// Remove a key with explicit durability settings and preconditions
+    pub fn remove_with_options(
+        &self,
+        key: &str,
+        write_options: &WriteOptions,
+    ) -> Result<Option<Vec<u8>>> {
+        self.remove_versioned(key, write_options)
+            .map(|(value, _)| value)
+    }
+
+    /// Remove a key, returning its previous value and the removal's commit
+    /// sequence
+    pub fn remove_versioned(
+        &self,
+        key: &str,
+        write_options: &WriteOptions,
+    ) -> Result<(Option<Vec<u8>>, u64)> {
+        let started = Instant::now();
         // First, retrieve the current value so we can return it
         let current_value = self.get(key)?;
 
         // Log the operation for durability
-        let mut durability_manager = self.durability_manager.lock().unwrap();
-        durability_manager.log_operation(Operation::Remove {
-            key: key.to_string(),
-        })?;
+        let mut durability_manager = self.lock_wal()?;
+        self.check_unchanged(key, write_options)?;
+        Self::log_write(
+            &mut durability_manager,
+            Operation::Remove {
+                key: key.to_string(),
+            },
+            write_options,
+        )?;
+
+        let sequence = self.apply_remove(key, current_value.as_deref())?;
+        self.finish_write(write_options, started);
+
+        // Return the previous value
+        Ok((current_value, sequence))
+    }
 
+    /// Apply a logged removal of `key`, which held `current_value`, to the
+    /// memtable and index, returning its commit sequence. Called with the
+    /// WAL lock held.
+    fn apply_remove(&self, key: &str, current_value: Option<&[u8]>) -> Result<u64> {
         // Remove from the memtable
         self.memtable.remove(&key.to_string())?;
 
         // Update the index - in a lock-free structure, we can just remove the entry
         self.index.remove(key);
 
-        // Return the previous value
-        Ok(current_value)
+        // Keep the value around for undelete if soft deletes are enabled,
+        // and remember the removal so the next flush writes a tombstone
+        if let Some(value) = current_value {
+            let deleted_at_ms = self.now_ms();
+            if self.options().soft_delete_retention.is_some() {
+                self.deleted.insert(
+                    key.to_string(),
+                    Tombstone {
+                        deleted_at_ms,
+                        value: Some(value.to_vec()),
+                    },
+                );
+            }
+            self.removed.insert(key.to_string(), deleted_at_ms);
+        }
+
+        self.lifetime.record_remove();
+        let sequence = self.commit_sequence(key);
+        self.record_version(key, sequence, None);
+        Ok(sequence)
+    }
+
+    /// Current time in milliseconds, for use as `ReadOptions::snapshot`.
+    ///
+    /// Reads at the snapshot skip entries written after it, which relies on
+    /// write times being tracked (`LsmIndexOptions::with_write_times`).
+    /// The snapshot is not registered; use `acquire_snapshot` for one that
+    /// compaction respects.
+    pub fn snapshot(&self) -> u64 {
+        self.now_ms()
+    }
+
+    /// Sync WAL records written with `sync: false` to disk
+    pub fn sync_wal(&self) -> Result<()> {
+        self.lock_wal()?.sync()?;
+        Ok(())
     }
 
     /// Get a value by key
     pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.get_with_options(key, &ReadOptions::default())
+    }
+
+    /// Get a value by key with explicit read settings
+    pub fn get_with_options(
+        &self,
+        key: &str,
+        read_options: &ReadOptions,
+    ) -> Result<Option<Vec<u8>>> {
+        let started = Instant::now();
+        let result = self.lookup(key, read_options);
+        self.log_if_slow("get", started.elapsed(), read_options.trace_id.as_slice());
+        result
+    }
+
+    /// Answer a get, from memory if possible and otherwise from SSTables
+    fn lookup(&self, key: &str, read_options: &ReadOptions) -> Result<Option<Vec<u8>>> {
+        self.sample_read(key);
+        let started = Instant::now();
+
+        // Expired entries read as missing until they are swept
+        if self.is_expired(key) {
+            return Ok(None);
+        }
+
+        if read_options.snapshot.is_some()
+            && let Some(entry) = self.index.get(key)
+            && !read_options.sees_write(entry.value().written_at_ms())
+        {
+            return Ok(None);
+        }
+
+        if let Some(entry) = self.index.get(key)
+            && self.is_range_deleted(key, entry.value())
+        {
+            return Ok(None);
+        }
+
         // Try to get from the memtable first
         match self.memtable.get(&key.to_string()) {
             Ok(Some(value)) => Ok(Some(value)),
@@ -256,31 +1047,14 @@ impl LsmIndex {
                 // If not in memtable, use the index to find it in SSTables
                 if let Some(entry) = self.index.get(key) {
                     let index_entry = entry.value();
-
-                    if let Some(value) = index_entry.value() {
-                        // Return the in-memory value
-                        return Ok(Some(value));
-                    }
-
+                    let result = self.get_from_index_entry(key, index_entry, read_options);
                     if let Some(storage_ref) = index_entry.storage_ref() {
-                        // If we have a tombstone, return None
-                        if storage_ref.is_tombstone {
-                            return Ok(None);
-                        }
-
-                        // Check if the key might be in the SSTable using the Bloom filter
-                        if let Some(reader_entry) = self.sstable_readers.get(&storage_ref.file_path)
-                        {
-                            let reader = reader_entry.value();
-                            if !reader.may_contain(key) {
-                                // Definitely not in the SSTable
-                                return Ok(None);
-                            }
-                        }
-
-                        // Load the value from the SSTable
-                        return self.load_value_from_sstable(storage_ref);
+                        self.level_stats.record_get(
+                            self.sstable_level(&storage_ref.file_path),
+                            started.elapsed(),
+                        );
                     }
+                    return result;
                 }
 
                 // Key not found
@@ -290,15 +1064,216 @@ impl LsmIndex {
         }
     }
 
+    /// Answer a get from the key's index entry once the memtable missed
+    fn get_from_index_entry(
+        &self,
+        key: &str,
+        index_entry: &GenIndexEntry,
+        read_options: &ReadOptions,
+    ) -> Result<Option<Vec<u8>>> {
+        if let Some(value) = index_entry.value() {
+            // Return the in-memory value
+            return Ok(Some(value));
+        }
+
+        if let Some(storage_ref) = index_entry.storage_ref() {
+            // If we have a tombstone, return None
+            if storage_ref.is_tombstone {
+                return Ok(None);
+            }
+
+            // Check if the key might be in the SSTable using the Bloom filter
+            if let Some(reader_entry) = self.sstable_readers.get(&storage_ref.file_path) {
+                let reader = reader_entry.value();
+                if !reader.may_contain(key) {
+                    // Definitely not in the SSTable
+                    self.record_probe(&storage_ref.file_path, stats::ProbeOutcome::BloomNegative);
+                    return Ok(None);
+                }
+            }
+
+            // Load the value from the SSTable
+            return self.load_value_with_options(storage_ref, read_options);
+        }
+
+        Ok(None)
+    }
+
+    /// Level of the open SSTable at `path`, 0 if it has no open reader
+    fn sstable_level(&self, path: &str) -> u32 {
+        self.sstable_readers
+            .peek(path)
+            .map_or(0, |reader| reader.value().level())
+    }
+
+    /// Count one probe of the SSTable at `path` against the file and its level
+    fn record_probe(&self, path: &str, outcome: stats::ProbeOutcome) {
+        self.file_access.record(path, outcome);
+        self.level_stats
+            .record_probe(self.sstable_level(path), outcome);
+    }
+
+    /// Get a value only if it can be answered from memory: the memtable, the
+    /// values held by the index, tombstones, and the Bloom filters of open
+    /// SSTables. Anything else returns `NotCached` instead of blocking on
+    /// disk, so latency-critical callers can fetch it some other way, such
+    /// as with `get` on a blocking thread.
+    pub fn get_if_cached(&self, key: &str) -> Result<CachedValue> {
+        self.sample_read(key);
+
+        if self.is_expired(key) {
+            return Ok(CachedValue::Absent);
+        }
+        if let Some(value) = self.memtable.get(&key.to_string())? {
+            return Ok(CachedValue::Found(value));
+        }
+
+        let Some(entry) = self.index.get(key) else {
+            return Ok(CachedValue::Absent);
+        };
+        let index_entry = entry.value();
+        if self.is_range_deleted(key, index_entry) {
+            return Ok(CachedValue::Absent);
+        }
+        if let Some(value) = index_entry.value() {
+            return Ok(CachedValue::Found(value));
+        }
+        match index_entry.storage_ref() {
+            Some(storage_ref) if storage_ref.is_tombstone => Ok(CachedValue::Absent),
+            Some(storage_ref) => {
+                let filtered_out = self
+                    .sstable_readers
+                    .get(&storage_ref.file_path)
+                    .is_some_and(|reader| !reader.value().may_contain(key));
+                if filtered_out {
+                    self.record_probe(&storage_ref.file_path, stats::ProbeOutcome::BloomNegative);
+                    Ok(CachedValue::Absent)
+                } else {
+                    Ok(CachedValue::NotCached)
+                }
+            }
+            None => Ok(CachedValue::Absent),
+        }
+    }
+
+    /// Get several values at once, in the order the keys were given
+    pub fn multi_get(&self, keys: &[&str]) -> Result<Vec<Option<Vec<u8>>>> {
+        self.multi_get_with_options(keys, &ReadOptions::default())
+    }
+
+    /// Get several values at once with explicit read settings
+    pub fn multi_get_with_options(
+        &self,
+        keys: &[&str],
+        read_options: &ReadOptions,
+    ) -> Result<Vec<Option<Vec<u8>>>> {
+        keys.iter()
+            .map(|key| self.get_with_options(key, read_options))
+            .collect()
+    }
+
+    /// Check whether a key is present without materializing its value.
+    ///
+    /// The index tracks every live key, including those still in the memtable,
+    /// so most lookups are answered in memory. Only when an entry lives solely
+    /// on disk and its Bloom filter cannot rule it out is the stored key read
+    /// back from the SSTable, and even then the value is skipped.
+    pub fn contains_key(&self, key: &str) -> Result<bool> {
+        let entry = match self.index.get(key) {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+        let index_entry = entry.value();
+
+        if index_entry.is_expired_at(self.now_ms()) || self.is_range_deleted(key, index_entry) {
+            return Ok(false);
+        }
+        if index_entry.has_value() {
+            return Ok(true);
+        }
+
+        match index_entry.storage_ref() {
+            Some(storage_ref) if !storage_ref.is_tombstone => {
+                if let Some(reader_entry) = self.sstable_readers.get(&storage_ref.file_path)
+                    && !reader_entry.value().may_contain(key)
+                {
+                    self.record_probe(&storage_ref.file_path, stats::ProbeOutcome::BloomNegative);
+                    return Ok(false);
+                }
+
+                let matches = self.sstable_key_matches(storage_ref, key)?;
+                let outcome = if matches {
+                    stats::ProbeOutcome::Hit
+                } else {
+                    stats::ProbeOutcome::FalsePositive
+                };
+                self.record_probe(&storage_ref.file_path, outcome);
+                Ok(matches)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Cheap, purely in-memory membership check.
+    ///
+    /// Never touches disk and never fails: `false` is definitive, while `true`
+    /// may be a false positive for an on-disk entry that `contains_key` would
+    /// go on to reject. Intended for dedup checks on the hot path.
+    pub fn may_exist(&self, key: &str) -> bool {
+        self.index.get(key).is_some_and(|entry| {
+            !entry.value().is_tombstone() && !self.is_range_deleted(key, entry.value())
+        })
+    }
+
+    /// Compute a Merkle root over every live key-value pair in key order.
+    ///
+    /// Two indexes holding the same live data produce the same digest no
+    /// matter how that data is spread across the memtable and SSTables, so
+    /// replicas can be compared by exchanging 16 bytes.
+    pub fn compute_content_hash(&self) -> Result<Digest> {
+        let summary = self.range_summary(Bound::Unbounded, Bound::Unbounded)?;
+        Ok(summary.digest)
+    }
+
+    /// Per-SSTable content digests recorded in each open file's properties.
+    ///
+    /// Files are identified by path; files written before digests were
+    /// recorded report `None`.
+    pub fn sstable_digests(&self) -> Vec<(String, Option<Digest>)> {
+        self.sstable_readers
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().content_digest()))
+            .collect()
+    }
+
     /// Get a range of key-value pairs
     pub fn range<R>(&self, range: R) -> Result<Vec<(String, Vec<u8>)>>
     where
         R: RangeBounds<String> + Clone,
     {
+        self.range_with_options(range, &ReadOptions::default())
+    }
+
+    /// Get a range of key-value pairs with explicit read settings.
+    ///
+    /// The options' bounds narrow `range` before the index is walked, so the
+    /// result covers the intersection of the two and keys outside it are
+    /// never visited.
+    pub fn range_with_options<R>(
+        &self,
+        range: R,
+        read_options: &ReadOptions,
+    ) -> Result<Vec<(String, Vec<u8>)>>
+    where
+        R: RangeBounds<String> + Clone,
+    {
+        let started = Instant::now();
         // Use the SkipMap's range capability to get entries within the range
         let index_entries: Vec<_> = self
             .index
-            .range(range.clone())
+            .range(Self::bounded_range(&range, read_options))
+            .filter(|entry| read_options.sees_write(entry.value().written_at_ms()))
+            .filter(|entry| !self.is_range_deleted(entry.key(), entry.value()))
             .map(|entry| (entry.key().clone(), entry.value().clone()))
             .collect();
 
@@ -324,7 +1299,7 @@ impl LsmIndex {
                 }
 
                 // Load the value from the SSTable
-                if let Ok(Some(value)) = self.load_value_from_sstable(storage_ref) {
+                if let Ok(Some(value)) = self.load_value_with_options(storage_ref, read_options) {
                     keys_seen.insert(key.clone());
                     result.push((key, value));
                 }
@@ -347,261 +1322,1275 @@ impl LsmIndex {
             }
         }
 
+        self.log_if_slow(
+            "range read",
+            started.elapsed(),
+            read_options.trace_id.as_slice(),
+        );
         Ok(result)
     }
 
+    /// Intersect a range with the lower and upper bounds of read options
+    fn bounded_range<R>(range: &R, read_options: &ReadOptions) -> (Bound<String>, Bound<String>)
+    where
+        R: RangeBounds<String>,
+    {
+        let start = match (range.start_bound(), read_options.lower_bound.as_ref()) {
+            (Bound::Included(start) | Bound::Excluded(start), Some(lower)) if start < lower => {
+                Bound::Included(lower.clone())
+            }
+            (Bound::Unbounded, Some(lower)) => Bound::Included(lower.clone()),
+            (start, _) => start.cloned(),
+        };
+        let end = match (range.end_bound(), read_options.upper_bound.as_ref()) {
+            (Bound::Included(end) | Bound::Excluded(end), Some(upper)) if end < upper => {
+                range.end_bound().cloned()
+            }
+            (_, Some(upper)) => Bound::Excluded(upper.clone()),
+            (end, None) => end.cloned(),
+        };
+        (start, end)
+    }
+
+    /// Get every live entry sharing `key`'s prefix under the configured
+    /// prefix extractor, in key order.
+    ///
+    /// Fails with `InvalidOperation` if no extractor is configured or the key
+    /// has no prefix under it.
+    pub fn prefix_iter(&self, key: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let extractor = self.options().prefix_extractor.clone().ok_or_else(|| {
+            LsmIndexError::InvalidOperation("No prefix extractor configured".to_string())
+        })?;
+        let prefix = extractor.prefix_of(key).ok_or_else(|| {
+            LsmIndexError::InvalidOperation(format!(
+                "Key {:?} has no prefix under extractor {}",
+                key,
+                extractor.name()
+            ))
+        })?;
+
+        let mut result = Vec::new();
+        for entry in self
+            .index
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+        {
+            // Keys sharing the prefix sort together right after it
+            if !entry.key().starts_with(prefix) {
+                break;
+            }
+            if extractor.prefix_of(entry.key()) != Some(prefix) {
+                continue;
+            }
+            if let Some(value) = self.resolve_entry_value(entry.key(), entry.value())? {
+                result.push((entry.key().clone(), value));
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Create a cursor over the live keys of the index
+    pub fn cursor(&self) -> LsmCursor<'_> {
+        LsmCursor::new(self)
+    }
+
+    /// Resolve the value of `key`'s index entry, loading it from its SSTable
+    /// if it is not held in memory. Tombstones resolve to `None`.
+    fn resolve_entry_value(&self, key: &str, entry: &GenIndexEntry) -> Result<Option<Vec<u8>>> {
+        if entry.is_expired_at(self.now_ms()) || self.is_range_deleted(key, entry) {
+            return Ok(None);
+        }
+        if let Some(value) = entry.value() {
+            return Ok(Some(value));
+        }
+
+        match entry.storage_ref() {
+            Some(storage_ref) if !storage_ref.is_tombstone => {
+                self.load_value_from_sstable(storage_ref)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Check that the entry at a storage reference carries the given key,
+    /// reading only the key bytes
+    fn sstable_key_matches(&self, storage_ref: &StorageReference, key: &str) -> Result<bool> {
+        let mut reader = BufReader::new(TableFile::open(
+            &storage_ref.file_path,
+            self.options().key_provider.as_deref(),
+        )?);
+        reader.seek(SeekFrom::Start(storage_ref.offset as u64))?;
+
+        let mut key_len_buf = [0u8; 4];
+        reader.read_exact(&mut key_len_buf)?;
+        let key_len = u32::from_le_bytes(key_len_buf) as usize;
+        if key_len != key.len() {
+            return Ok(false);
+        }
+
+        let mut key_buf = vec![0u8; key_len];
+        reader.read_exact(&mut key_buf)?;
+        Ok(key_buf == key.as_bytes())
+    }
+
     /// Load a value from an SSTable using a storage reference
     fn load_value_from_sstable(&self, storage_ref: &StorageReference) -> Result<Option<Vec<u8>>> {
-        println!(
-            "load_value_from_sstable - Loading from {} at offset {}",
-            storage_ref.file_path, storage_ref.offset
-        );
+        self.load_value_with_options(storage_ref, &ReadOptions::default())
+    }
 
-        // Open the file directly and seek to the reference's position
-        match File::open(&storage_ref.file_path) {
-            Ok(file) => {
-                let mut reader = BufReader::new(file);
+    /// Load a value from an SSTable, verifying its checksum and caching the
+    /// file's reader as the read options ask
+    fn load_value_with_options(
+        &self,
+        storage_ref: &StorageReference,
+        read_options: &ReadOptions,
+    ) -> Result<Option<Vec<u8>>> {
+        if storage_ref.is_tombstone {
+            return Ok(None);
+        }
 
-                // Seek to the position stored in the reference
-                reader.seek(SeekFrom::Start(storage_ref.offset as u64))?;
+        let entry = self.read_sstable_entry(storage_ref, read_options.fill_cache)?;
+        self.level_stats
+            .record_read(self.sstable_level(&storage_ref.file_path));
+        self.record_probe(&storage_ref.file_path, stats::ProbeOutcome::Hit);
 
-                // Read the key length and skip the key (we already know what key we want)
-                let mut key_len_buf = [0u8; 4];
-                reader.read_exact(&mut key_len_buf)?;
-                let key_len = u32::from_le_bytes(key_len_buf) as usize;
+        if read_options.verify_checksums
+            && entry.stored_checksum.is_some()
+            && !entry.checksum_matches()
+        {
+            return Err(LsmIndexError::InvalidOperation(format!(
+                "Checksum mismatch for entry at offset {} in {}",
+                storage_ref.offset, storage_ref.file_path
+            )));
+        }
 
-                // Skip over the key
-                reader.seek(SeekFrom::Current(key_len as i64))?;
+        Ok(Some(self.resolve_stored_value(entry)?))
+    }
 
-                // Read the value length
-                let mut value_len_buf = [0u8; 4];
-                reader.read_exact(&mut value_len_buf)?;
-                let value_len = u32::from_le_bytes(value_len_buf) as usize;
+    /// Read the entry a storage reference points at, including its stored
+    /// checksum when the file format carries one
+    fn read_sstable_entry(
+        &self,
+        storage_ref: &StorageReference,
+        fill_cache: bool,
+    ) -> Result<StoredEntry> {
+        let mut reader = BufReader::new(TableFile::open(
+            &storage_ref.file_path,
+            self.options().key_provider.as_deref(),
+        )?);
+        let layout = Self::read_sstable_layout(&mut reader)?;
+        let has_checksums = layout.has_entry_checksums;
+        // Only files with entry checksums can be compressed
+        let (decoder, key_expander, value_log_segments) = if has_checksums {
+            self.entry_decoders(&storage_ref.file_path, fill_cache)?
+        } else {
+            Default::default()
+        };
+
+        // Seek to the position stored in the reference
+        let offset = storage_ref.offset as u64;
+        reader.seek(SeekFrom::Start(offset))?;
+
+        let file_size = reader.get_ref().len()?;
+        let key_len =
+            checked_len::read_len(&mut reader, file_size, crate::sstable::MAX_KEY_SIZE, "Key")?;
+        let mut key = vec![0u8; key_len];
+        reader.read_exact(&mut key)?;
+        if let Some(mut expander) = key_expander {
+            expander.prime(&mut reader, file_size, offset)?;
+            key = expander.expand(offset, &key)?;
+        }
 
-                // Read the value
-                let mut value_buf = vec![0u8; value_len];
-                reader.read_exact(&mut value_buf)?;
+        let value_len = checked_len::read_len(
+            &mut reader,
+            file_size,
+            crate::sstable::MAX_VALUE_SIZE,
+            "Value",
+        )?;
+        let mut value = vec![0u8; value_len];
+        reader.read_exact(&mut value)?;
+
+        let stored_checksum = if has_checksums {
+            let mut checksum_buf = [0u8; 4];
+            reader.read_exact(&mut checksum_buf)?;
+            Some(u32::from_le_bytes(checksum_buf))
+        } else {
+            None
+        };
+
+        Ok(StoredEntry {
+            key: String::from_utf8_lossy(&key).to_string(),
+            value,
+            stored_checksum,
+            checksum_kind: layout.checksum_kind,
+            decoder,
+            value_log_segments,
+        })
+    }
 
-                println!(
-                    "load_value_from_sstable - Successfully read value of length {}",
-                    value_len
-                );
+    /// Open an SSTable's reader for the index, in the cache it will be kept
+    /// in
+    fn open_reader(&self, path: &str, level: u32) -> io::Result<SSTableReader> {
+        SSTableReader::open_with(
+            path,
+            level,
+            self.options().filter_cache.as_ref(),
+            self.options().key_provider.as_deref(),
+        )
+    }
 
-                if storage_ref.is_tombstone {
-                    println!("load_value_from_sstable - Entry is a tombstone, returning None");
-                    Ok(None)
+    /// Open an SSTable for a single use, leaving its Bloom filter unread
+    /// until needed if the index has a filter cache
+    fn open_sstable(&self, path: &str) -> io::Result<crate::sstable::SSTableReader> {
+        crate::sstable::SSTableReader::open_with(
+            path,
+            self.options().filter_cache.clone(),
+            self.options().key_provider.as_deref(),
+        )
+    }
+
+    /// Decoder for values stored in an SSTable, the expander for its keys if
+    /// they are prefix-compressed and the keys whose values it left in the
+    /// value log, from the cached reader if there is one. Otherwise the file is opened, and its reader cached when
+    /// `fill_cache` is set.
+    fn entry_decoders(
+        &self,
+        path: &str,
+        fill_cache: bool,
+    ) -> Result<EntryDecoders> {
+        let decoders = |reader: &SSTableReader| {
+            (
+                reader.value_decoder().clone(),
+                reader.key_expander(),
+                reader.value_log_segments().clone(),
+            )
+        };
+        if let Some(reader) = self.sstable_readers.peek(path) {
+            return Ok(decoders(reader.value()));
+        }
+        if !fill_cache {
+            let reader = self.open_sstable(path)?;
+            return Ok((
+                reader.value_decoder().clone(),
+                reader.key_expander(),
+                reader.value_log_segments().clone(),
+            ));
+        }
+
+        // Only live files are cached, at the level the manifest records
+        let level = self
+            .lock_manifest()?
+            .get(path)
+            .map(|file| file.level);
+        let reader = self.open_reader(path, level.unwrap_or(0))?;
+        let entry_decoders = decoders(&reader);
+        if level.is_some() {
+            self.sstable_readers.insert(path.to_string(), reader);
+        }
+        Ok(entry_decoders)
+    }
+
+    /// Work out where entries start in an SSTable and whether they carry
+    /// checksums, leaving the reader positioned at the first entry.
+    ///
+    /// Version 3 and later files are recognised by their header checksum; anything else
+    /// with the SSTable magic is treated as the legacy memtable layout. Version 8 and
+    /// later files keep their entry count and offsets in the footer.
+    fn read_sstable_layout(reader: &mut BufReader<TableFile>) -> Result<SSTableLayout> {
+        let file_size = reader.get_ref().len()?;
+
+        let header_size = crate::sstable::MAX_HEADER_SIZE;
+        let mut header = vec![0u8; header_size.min(file_size as usize)];
+        reader.read_exact(&mut header)?;
+
+        if crate::sstable::is_valid_header(&header) {
+            let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+            let (entry_count, index_offset, bloom_bytes) =
+                if version >= crate::sstable::FOOTER_VERSION {
+                    let footer = crate::sstable::Footer::read_from(reader)?;
+                    (footer.entry_count, footer.index_offset, footer.bloom_size)
                 } else {
-                    Ok(Some(value_buf))
-                }
+                    (
+                        u64::from_le_bytes(header[12..20].try_into().unwrap()),
+                        u64::from_le_bytes(header[20..28].try_into().unwrap()),
+                        u64::from_le_bytes(header[36..44].try_into().unwrap()),
+                    )
+                };
+            let data_start = crate::sstable::header_size(version) as u64;
+            reader.seek(SeekFrom::Start(data_start))?;
+            return Ok(SSTableLayout {
+                entry_count,
+                has_entry_checksums: true,
+                checksum_kind: crate::sstable::header_checksum_kind(&header)?,
+                data_bytes: reader.get_ref().stored_bytes(data_start, index_offset),
+                bloom_bytes,
+            });
+        }
+
+        // Legacy layout: Magic(8) + Version(4) + Count(8) + IndexOffset(8)
+        if header.len() < crate::sstable::LEGACY_HEADER_SIZE {
+            return Err(LsmIndexError::InvalidOperation(format!(
+                "SSTable of {} bytes is too small to hold a header",
+                file_size
+            )));
+        }
+        let entry_count = u64::from_le_bytes(header[12..20].try_into().unwrap());
+        let index_offset = u64::from_le_bytes(header[20..28].try_into().unwrap());
+
+        // Validate the format
+        if index_offset > file_size {
+            return Err(LsmIndexError::InvalidOperation(format!(
+                "Invalid index offset {} exceeds file size {}",
+                index_offset, file_size
+            )));
+        }
+
+        reader.seek(SeekFrom::Start(crate::sstable::LEGACY_HEADER_SIZE as u64))?;
+        Ok(SSTableLayout {
+            entry_count,
+            has_entry_checksums: false,
+            checksum_kind: crate::sstable::ChecksumKind::Crc32,
+            data_bytes: index_offset.saturating_sub(crate::sstable::LEGACY_HEADER_SIZE as u64),
+            bloom_bytes: 0,
+        })
+    }
+
+    /// Get a value together with its entry checksum.
+    ///
+    /// For flushed entries the checksum is the one stored in the SSTable and
+    /// `verified` reports whether it matched the data read back. Entries that
+    /// have not been flushed yet have no stored checksum, so one is computed
+    /// with `LsmIndexOptions::checksum_kind` and `verified` is false.
+    pub fn get_with_checksum(&self, key: &str) -> Result<Option<ChecksummedValue>> {
+        let storage_ref = match self.index.get(key) {
+            Some(entry)
+                if entry.value().is_expired_at(self.now_ms())
+                    || self.is_range_deleted(key, entry.value()) =>
+            {
+                return Ok(None)
             }
-            Err(e) => {
-                eprintln!(
-                    "load_value_from_sstable - Error opening file {}: {}",
-                    storage_ref.file_path, e
-                );
-                Err(LsmIndexError::IoError(e))
+            Some(entry) => entry.value().storage_ref().cloned(),
+            None => return Ok(None),
+        };
+
+        if let Some(storage_ref) = storage_ref {
+            if storage_ref.is_tombstone {
+                return Ok(None);
+            }
+
+            let entry = self.read_sstable_entry(&storage_ref, true)?;
+            if entry.key == key
+                && let Some(checksum) = entry.stored_checksum
+            {
+                let verified = entry.checksum_matches();
+                return Ok(Some(ChecksummedValue {
+                    value: self.resolve_stored_value(entry)?,
+                    checksum,
+                    verified,
+                }));
             }
         }
+
+        Ok(self.get(key)?.map(|value| ChecksummedValue {
+            checksum: self.options().checksum_kind.entry_checksum(key, &value),
+            value,
+            verified: false,
+        }))
+    }
+
+    /// Get a value together with the time it was last written.
+    ///
+    /// `written_at` is only set when the index was created with
+    /// `LsmIndexOptions::with_write_times` and the entry was written while
+    /// tracking was on.
+    pub fn get_with_metadata(&self, key: &str) -> Result<Option<ValueWithMetadata>> {
+        let written_at_ms = match self.index.get(key) {
+            Some(entry) => entry.value().written_at_ms(),
+            None => return Ok(None),
+        };
+
+        Ok(self.get(key)?.map(|value| ValueWithMetadata {
+            value,
+            written_at: written_at_ms.map(|ms| UNIX_EPOCH + Duration::from_millis(ms)),
+        }))
+    }
+
+    /// Current time in milliseconds since the Unix epoch, from the
+    /// configured clock
+    fn now_ms(&self) -> u64 {
+        self.options().clock.now_ms()
     }
 
     /// Flush the memtable to an SSTable and update the index
     pub fn flush(&self) -> Result<()> {
-        // Begin checkpoint
-        let mut durability_manager = self.durability_manager.lock().unwrap();
-        let checkpoint_id = durability_manager.begin_checkpoint()?;
+        self.flush_to_sstable().map(|_| ())
+    }
 
-        // Create an SSTable path
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        let _sstable_path = format!("{}/sstable_{}.sst", self.base_path, timestamp);
-
-        // CRITICAL: Before flushing, capture keys from the index for reindexing
-        // Get all keys currently in the index
-        let keys_to_reindex: Vec<String> =
-            self.index.iter().map(|entry| entry.key().clone()).collect();
-
-        // In a real implementation, we would use our SSTableWriter with Bloom filters
-        // For now, we just use the existing flush_to_sstable method
-        let sstable_path = self.memtable.flush_to_sstable(&self.base_path)?;
-
-        // End checkpoint
-        durability_manager.end_checkpoint(checkpoint_id)?;
-
-        // Update the index with the new SSTable entries
-        self.update_index_from_sstable(&sstable_path)?;
-
-        // IMPORTANT: Reindex any entries we just flushed, using their storage references
-        // For each key that was in our index, we need to make sure it has a storage reference
-        for key in keys_to_reindex {
-            // Check if the key still exists in the index
-            if let Some(entry) = self.index.get(&key) {
-                let index_entry = entry.value();
-
-                // Only add storage reference if it doesn't already have one
-                if index_entry.storage_ref().is_none() && index_entry.value().is_some() {
-                    // Create a storage reference for this entry
-                    let storage_ref = StorageReference {
-                        file_path: sstable_path.clone(),
-                        offset: 0, // We don't have the exact offset, but we know the file
-                        is_tombstone: false,
-                    };
+    /// Flush the memtable, returning the path of the SSTable written
+    fn flush_to_sstable(&self) -> Result<String> {
+        let (sstable_path, _) = self.flush_memtable(true)?;
+        Ok(sstable_path)
+    }
 
-                    // Create a new entry with the updated storage reference
-                    let new_entry = GenIndexEntry::new(index_entry.value(), Some(storage_ref));
+    /// Flush the memtable, returning the path of the SSTable written and
+    /// the manifest record for it. With `record` unset the caller adds the
+    /// record to the manifest; until it does, the file is live but only
+    /// found again on restart by being adopted as an unrecorded SSTable.
+    fn flush_memtable(&self, record: bool) -> Result<(String, FileMetadata)> {
+        let started = Instant::now();
+        let mut durability_manager = self.lock_wal()?;
+        let entries = self.memtable.iter()?;
+        let timestamp = self.options().clock.now_secs();
+        let (sstable_path, file_number) = self.new_sstable_path(0, entries.len(), timestamp)?;
+        // Refuse before the checkpoint starts, so a full disk does not leave
+        // an unfinished checkpoint in the WAL
+        self.ensure_disk_space(&sstable_path, self.memtable.current_size()? as u64)?;
+
+        // Begin checkpoint; the memtable holds every write logged before it
+        let checkpoint_id = durability_manager.begin_checkpoint()?;
+        let applied_lsn = durability_manager.checkpoint_lsn(checkpoint_id);
+
+        // Write the memtable contents with per-entry checksums and, if enabled,
+        // a Bloom filter
+        self.purge_expired_deletions();
+        self.purge_expired_versions();
+        let range_tombstones = self.pending_range_tombstones();
+        // A failed attempt is rewritten from the start, since creating the
+        // writer truncates the file
+        self.options().retry_policy.run("flush", || {
+            // Values at or over the separation threshold go to a value log
+            // segment numbered after the SSTable, which keeps a pointer to
+            // each. The segment is synced before the SSTable is written.
+            let separated: Vec<_> = match self.options().value_separation_threshold {
+                Some(threshold) => entries
+                    .iter()
+                    .filter(|(_, value)| value.len() >= threshold)
+                    .collect(),
+                None => Vec::new(),
+            };
+            let mut pointers = HashMap::new();
+            if !separated.is_empty() {
+                let mut segment = SegmentWriter::create(&self.base_path, file_number)?;
+                for (key, value) in separated {
+                    pointers.insert(key.as_str(), segment.append(key, value)?);
+                }
+                segment.finish()?;
+            }
 
-                    // In a lock-free structure, we insert the updated entry
-                    self.index.insert(key, new_entry);
+            let mut writer = crate::sstable::SSTableWriter::new(
+                &sstable_path,
+                entries.len(),
+                self.use_bloom_filters,
+                self.bloom_fpr_for(0, entries.len()),
+            )?;
+            writer.set_file_number(file_number);
+            writer.set_flush_number(file_number);
+            writer.set_created_at(timestamp);
+            writer.set_level(0);
+            writer.set_checkpoint_id(checkpoint_id);
+            for (name, value) in &self.options().user_properties {
+                writer.set_user_property(name, value)?;
+            }
+            writer.set_strict_key_order()?;
+            for key in pointers.keys() {
+                writer.mark_value_pointer(key, file_number);
+            }
+            if let Some(lsn) = applied_lsn {
+                writer.set_applied_lsn(lsn);
+            }
+            if let Some(extractor) = &self.options().prefix_extractor {
+                writer.set_prefix_extractor(extractor.clone());
+            }
+            let dictionary = match &self.options().compression {
+                crate::sstable::Compression::Zstd(zstd) => {
+                    zstd.train_dictionary(entries.iter().map(|(_, value)| value.as_slice()))
                 }
+                _ => None,
+            };
+            writer.set_compression(self.options().compression, dictionary)?;
+            writer.set_compression_type(self.options().block_compression)?;
+            if let Some(provider) = &self.options().key_provider {
+                writer.set_encryption(provider.as_ref())?;
+            }
+            writer.set_checksum_kind(self.options().checksum_kind)?;
+            if let Some(false_positive_rate) = self.options().block_filter_fpr {
+                writer.set_block_filters(false_positive_rate)?;
+            }
+            writer.set_block_size(self.options().block_size)?;
+            if let Some(restart_interval) = self.options().restart_interval {
+                writer.set_restart_interval(restart_interval)?;
             }
+            if self.options().key_prefix_compression {
+                writer.set_key_prefix_compression()?;
+            }
+            if self.options().uses_hash_index(0) {
+                writer.set_hash_index()?;
+            } else if let Some(index_block_size) = self
+                .options()
+                .index_block_size
+                .filter(|_| self.use_bloom_filters)
+            {
+                writer
+                    .set_index_block_size(index_block_size, self.bloom_fpr_for(0, entries.len()))?;
+            }
+            let puts = entries.iter().map(|(key, value)| {
+                let (written_at_ms, expires_at_ms) =
+                    self.index.get(key).map_or((None, None), |entry| {
+                        (entry.value().written_at_ms(), entry.value().expires_at_ms())
+                    });
+                SSTableRecord::Put {
+                    key: key.clone(),
+                    value: pointers
+                        .get(key.as_str())
+                        .map_or_else(|| value.clone(), ValuePointer::encode),
+                    written_at_ms,
+                    expires_at_ms,
+                    sequence: self.written_sequence(key),
+                }
+            });
+            // Persist removals so they hide older values and survive restarts;
+            // soft deletes also carry the value for undelete
+            let removals = self.removed.iter().map(|entry| SSTableRecord::Delete {
+                key: entry.key().clone(),
+                tombstone: Tombstone {
+                    deleted_at_ms: *entry.value(),
+                    value: None,
+                },
+            });
+            let deletions = self.deleted.iter().map(|entry| SSTableRecord::Delete {
+                key: entry.key().clone(),
+                tombstone: entry.value().clone(),
+            });
+            let ranges = range_tombstones
+                .iter()
+                .cloned()
+                .map(SSTableRecord::DeleteRange);
+            // Entries come out of the memtable in key order, so blocks can be
+            // built in parallel
+            writer.build_from_sorted_iter(
+                puts.chain(removals).chain(deletions).chain(ranges).map(Ok),
+            )?;
+            writer.finalize()
+        })?;
+        self.memtable.clear()?;
+        self.removed.clear();
+        self.drop_range_deleted()?;
+
+        // End checkpoint, recording what the SSTable holds
+        durability_manager.end_checkpoint_with_files(
+            checkpoint_id,
+            vec![CheckpointFile {
+                path: sstable_path.clone(),
+                entry_count: entries.len() as u64,
+                min_key: entries.first().map(|(key, _)| key.clone()),
+                max_key: entries.last().map(|(key, _)| key.clone()),
+            }],
+        )?;
+
+        // Point the index at the new SSTable entries
+        let summary = self.update_index_from_sstable(&sstable_path)?;
+
+        // Record the new file in the manifest
+        let metadata = Self::file_metadata(&sstable_path, 0, (timestamp, file_number), summary)?;
+        if record {
+            let mut manifest = self.lock_manifest()?;
+            self.options()
+                .retry_policy
+                .run("manifest update", || manifest.add_file(metadata.clone()))?;
         }
 
         // Register the checkpoint as durable
         durability_manager.register_durable_checkpoint(checkpoint_id, &sstable_path)?;
 
         // Add the SSTable reader to the cache
-        let reader = SSTableReader::open(&sstable_path)?;
+        let reader = self.open_reader(&sstable_path, 0)?;
         self.sstable_readers.insert(sstable_path.clone(), reader);
+        self.lifetime.record_flush();
+
+        let trace_ids = self.traces.flushed(&sstable_path);
+        debug!(
+            "Flushed {} entries to {}{}",
+            entries.len(),
+            sstable_path,
+            trace::describe(&trace_ids)
+        );
+        self.log_if_slow("flush", started.elapsed(), &trace_ids);
+        Ok((sstable_path, metadata))
+    }
 
-        Ok(())
+    /// Directories SSTables are written to: the configured data directories,
+    /// or the base path if none are set
+    fn data_directories(&self) -> Vec<String> {
+        if self.options().data_directories.is_empty() {
+            vec![self.base_path.clone()]
+        } else {
+            self.options().data_directories.clone()
+        }
     }
 
-    /// Update the index with entries from an SSTable
-    fn update_index_from_sstable(&self, sstable_path: &str) -> Result<()> {
-        println!("update_index_from_sstable - Starting for {}", sstable_path);
+    /// Directories searched for SSTables: the base path, which holds files
+    /// written before any data directories were configured, the data
+    /// directories and the cold path
+    fn sstable_directories(&self) -> Vec<String> {
+        let mut directories = vec![self.base_path.clone()];
+        let options = self.options();
+        for directory in options.data_directories.iter().chain(&options.cold_path) {
+            if !directories.contains(directory) {
+                directories.push(directory.clone());
+            }
+        }
+        directories
+    }
 
-        // Get file size first
-        let file_size = fs::metadata(sstable_path)?.len();
-        println!("update_index_from_sstable - File size: {} bytes", file_size);
+    /// Live SSTables and their total size in each data directory
+    pub fn directory_usage(&self) -> Vec<DirectoryUsage> {
+        let mut usage: Vec<DirectoryUsage> = self
+            .data_directories()
+            .into_iter()
+            .map(|path| DirectoryUsage {
+                path,
+                file_count: 0,
+                size_bytes: 0,
+            })
+            .collect();
+        for file in locks::recover(self.manifest.lock()).files() {
+            let parent = Path::new(&file.path).parent();
+            if let Some(dir) = usage
+                .iter_mut()
+                .find(|dir| parent == Some(Path::new(&dir.path)))
+            {
+                dir.file_count += 1;
+                dir.size_bytes += file.size_bytes;
+            }
+        }
+        usage
+    }
 
-        // Open the SSTable file
-        let file = File::open(sstable_path)?;
-        let mut reader = BufReader::new(file);
+    /// Choose the directory, file number and name for a new SSTable on
+    /// `level`. Numbers come from the manifest; one whose name is already
+    /// taken in a data directory, say by a file the manifest never recorded,
+    /// is skipped.
+    fn new_sstable_path(
+        &self,
+        level: u32,
+        expected_entries: usize,
+        created_at_secs: u64,
+    ) -> Result<(String, u64)> {
+        let directories = self.directory_usage();
+        let context = PlacementContext {
+            level,
+            expected_entries,
+            directories,
+        };
+        let chosen = self
+            .options()
+            .placement_policy
+            .choose_directory(&context)
+            .min(context.directories.len() - 1);
+        self.new_sstable_path_in(&context.directories[chosen].path, level, created_at_secs)
+    }
 
-        // Read magic number
-        let mut magic_buf = [0u8; 8];
-        reader.read_exact(&mut magic_buf)?;
-        let magic = u64::from_le_bytes(magic_buf);
-        println!("update_index_from_sstable - Magic number: 0x{:X}", magic);
-
-        // Read version
-        let mut version_buf = [0u8; 4];
-        reader.read_exact(&mut version_buf)?;
-        let version = u32::from_le_bytes(version_buf);
-        println!("update_index_from_sstable - Version: {}", version);
-
-        // Read entry count
-        let mut count_buf = [0u8; 8];
-        reader.read_exact(&mut count_buf)?;
-        let entry_count = u64::from_le_bytes(count_buf);
-        println!("update_index_from_sstable - Entry count: {}", entry_count);
-
-        // Read index offset
-        let mut index_offset_buf = [0u8; 8];
-        reader.read_exact(&mut index_offset_buf)?;
-        let index_offset = u64::from_le_bytes(index_offset_buf);
-        println!("update_index_from_sstable - Index offset: {}", index_offset);
+    /// Pick a path in `directory` for a new SSTable, allocating its file
+    /// number, with a name not taken in any directory SSTables are kept in
+    fn new_sstable_path_in(
+        &self,
+        directory: &str,
+        level: u32,
+        created_at_secs: u64,
+    ) -> Result<(String, u64)> {
+        let directories = self.sstable_directories();
+        loop {
+            let file_number = self.lock_manifest()?.allocate_file_number()?;
+            let name = self.options().file_namer.file_name(&FileNameContext {
+                level,
+                created_at_secs,
+                file_number,
+            });
+            let taken = directories
+                .iter()
+                .any(|dir| Path::new(dir).join(&name).exists());
+            if !taken {
+                return Ok((format!("{}/{}", directory, name), file_number));
+            }
+        }
+    }
 
-        // Validate the format
-        if index_offset > file_size {
-            return Err(LsmIndexError::InvalidOperation(format!(
-                "Invalid index offset {} exceeds file size {}",
-                index_offset, file_size
-            )));
+    /// Live SSTables and their total size in the cold path, if one is set
+    pub fn cold_storage_usage(&self) -> Option<DirectoryUsage> {
+        let path = self.options().cold_path.clone()?;
+        let mut usage = DirectoryUsage {
+            path,
+            file_count: 0,
+            size_bytes: 0,
+        };
+        for file in locks::recover(self.manifest.lock()).files() {
+            if Path::new(&file.path).parent() == Some(Path::new(&usage.path)) {
+                usage.file_count += 1;
+                usage.size_bytes += file.size_bytes;
+            }
         }
+        Some(usage)
+    }
 
-        // Start at the beginning of the data section
-        reader.seek(SeekFrom::Start(28))?; // Magic(8) + Version(4) + Count(8) + IndexOffset(8)
-        println!(
-            "update_index_from_sstable - Positioned at data section, position: {}",
-            reader.stream_position()?
-        );
+    /// Build the manifest record for an indexed SSTable
+    fn file_metadata(
+        path: &str,
+        level: u32,
+        (created_at_secs, file_number): (u64, u64),
+        summary: SSTableSummary,
+    ) -> Result<FileMetadata> {
+        Ok(FileMetadata {
+            path: path.to_string(),
+            level,
+            entry_count: summary.entry_count,
+            size_bytes: fs::metadata(path)?.len(),
+            created_at_secs,
+            bloom_bytes: summary.bloom_bytes,
+            raw_bytes: summary.raw_bytes,
+            data_bytes: summary.data_bytes,
+            tombstone_count: summary.tombstone_count,
+            file_number,
+            flush_number: summary.flush_number.unwrap_or(file_number),
+            applied_lsn: summary.applied_lsn,
+            max_sequence: summary.max_sequence,
+            min_key: summary.min_key,
+            max_key: summary.max_key,
+        })
+    }
 
-        println!("update_index_from_sstable - Starting to process entries");
+    /// Estimate the number of live keys from the SSTables' entry and
+    /// tombstone counts and the memtable's size, without reading any file.
+    /// Each tombstone is taken to hide one flushed entry; a key written
+    /// again after it was flushed counts once per copy.
+    pub fn estimate_num_keys(&self) -> Result<u64> {
+        let flushed = SSTableCompaction::estimate_num_keys(&self.list_sstables());
+        Ok(flushed + self.memtable.len()? as u64)
+    }
 
-        // Process entries one by one, with careful error handling
-        for i in 0..entry_count {
-            let entry_pos = reader.stream_position()?;
-            println!(
-                "update_index_from_sstable - Reading entry {} at position {}",
-                i, entry_pos
-            );
+    /// The live SSTables recorded in the manifest, ordered by level and then
+    /// from oldest to newest data
+    pub fn list_sstables(&self) -> Vec<SSTableInfo> {
+        let mut infos: Vec<SSTableInfo> = locks::recover(self.manifest.lock())
+            .files()
+            .map(FileMetadata::to_info)
+            .collect();
+        infos.sort_by(|a, b| {
+            (a.level, a.flush_number, a.file_number, &a.path).cmp(&(
+                b.level,
+                b.flush_number,
+                b.file_number,
+                &b.path,
+            ))
+        });
+        infos
+    }
 
-            // Read key length
-            let mut key_len_buf = [0u8; 4];
-            match reader.read_exact(&mut key_len_buf) {
-                Ok(_) => {}
-                Err(e) => {
-                    println!(
-                        "update_index_from_sstable - Failed to read key length for entry {}: {}",
-                        i, e
-                    );
-                    return Err(LsmIndexError::IoError(e));
+    /// Report the file handles, memory and background tasks the index holds,
+    /// so embedding applications can enforce their own limits.
+    ///
+    /// Memory figures are estimates of heap usage. Counting the bytes held by
+    /// the index walks every entry, so avoid calling this on a hot path.
+    pub fn resource_usage(&self) -> ResourceUsage {
+        let (open_readers, cache_bytes) =
+            self.sstable_readers
+                .iter()
+                .fold((0, 0), |(open, bytes), entry| {
+                    let reader = entry.value();
+                    (
+                        open + reader.holds_file() as usize,
+                        bytes + reader.memory_usage() as u64,
+                    )
+                });
+        let index_bytes = self
+            .index
+            .iter()
+            .map(|entry| (entry.key().len() + entry.value().value_len().unwrap_or(0)) as u64)
+            .sum();
+
+        ResourceUsage {
+            // The WAL stays open for the life of the index
+            open_files: open_readers + 1,
+            mmapped_bytes: 0,
+            cache_bytes: cache_bytes
+                + self
+                    .options()
+                    .filter_cache
+                    .as_ref()
+                    .map_or(0, |cache| cache.stats().resident_bytes as u64),
+            memtable_bytes: self.memtable.current_size().unwrap_or(0) as u64,
+            index_bytes,
+            background_tasks: self.background_tasks.load(Ordering::Relaxed),
+            reader_evictions: self.sstable_readers.evictions(),
+            reader_reopens: self.sstable_readers.reopens(),
+        }
+    }
+
+    /// Number of lookups the memtable's key filter answered without probing
+    /// the memtable; 0 unless `LsmIndexOptions::with_memtable_filter` is set
+    pub fn memtable_filter_negatives(&self) -> u64 {
+        self.memtable.filter_negatives()
+    }
+
+    /// Highest WAL LSN reflected in the live SSTables, as recorded in the
+    /// manifest; WAL records up to it need not be replayed. 0 if no live
+    /// file records one.
+    pub fn applied_lsn(&self) -> u64 {
+        locks::recover(self.manifest.lock())
+            .files()
+            .map(|file| file.applied_lsn)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Count a background task as running until the returned guard is dropped
+    pub(crate) fn track_background_task(&self) -> stats::BackgroundTaskGuard {
+        stats::BackgroundTaskGuard::new(self.background_tasks.clone())
+    }
+
+    /// Read-path counters per level in ascending order, for watching level 0
+    /// build up before it slows reads. Levels no read has touched are left
+    /// out, and levels from 6 down share one entry.
+    pub fn level_read_stats(&self) -> Vec<LevelReadStats> {
+        (0..stats::MAX_LEVELS as u32)
+            .map(|level| self.level_stats.read_stats(level))
+            .filter(|stats| !stats.is_empty())
+            .collect()
+    }
+
+    /// Raw and on-disk bytes of the live SSTables, per level in ascending order
+    pub fn level_storage_stats(&self) -> Vec<LevelStorageStats> {
+        let mut levels: BTreeMap<u32, LevelStorageStats> = BTreeMap::new();
+        for file in locks::recover(self.manifest.lock()).files() {
+            let stats = levels
+                .entry(file.level)
+                .or_insert_with(|| LevelStorageStats {
+                    level: file.level,
+                    ..Default::default()
+                });
+            stats.file_count += 1;
+            stats.entry_count += file.entry_count;
+            stats.raw_bytes += file.raw_bytes;
+            stats.data_bytes += file.data_bytes;
+            stats.size_bytes += file.size_bytes;
+        }
+        levels.into_values().collect()
+    }
+
+    /// Estimate how many bytes the SSTables occupy per byte of live data.
+    ///
+    /// Live data is the keys and values of flushed entries the index still
+    /// serves, so overwritten, removed and expired versions, as well as
+    /// filters and metadata, all count as amplification. Entries only in the
+    /// memtable are left out on both sides. Returns `None` when nothing live
+    /// has been flushed.
+    pub fn space_amplification_estimate(&self) -> Option<f64> {
+        let now_ms = self.now_ms();
+        let live_bytes: u64 = self
+            .index
+            .iter()
+            .filter(|entry| {
+                let entry = entry.value();
+                entry
+                    .storage_ref()
+                    .is_some_and(|storage_ref| !storage_ref.is_tombstone)
+                    && !entry.is_expired_at(now_ms)
+            })
+            .filter_map(|entry| {
+                let value_len = entry.value().written_len()?;
+                Some(entry.key().len() as u64 + value_len)
+            })
+            .sum();
+        if live_bytes == 0 {
+            return None;
+        }
+
+        let disk_bytes: u64 = self
+            .level_storage_stats()
+            .iter()
+            .map(|level| level.size_bytes)
+            .sum();
+        Some(disk_bytes as f64 / live_bytes as f64)
+    }
+
+    /// Count a sampled read against every SSTable whose range covers `key`
+    fn sample_read(&self, key: &str) {
+        if let Some(sampler) = &self.read_sampler
+            && sampler.should_sample()
+        {
+            for file in self.sstables_covering(key) {
+                sampler.record(&file.path);
+            }
+        }
+    }
+
+    /// Sampled reads per live SSTable, hottest first; empty unless the index
+    /// was created with `LsmIndexOptions::with_read_sampling`
+    pub fn read_hotness(&self) -> Vec<FileHotness> {
+        match &self.read_sampler {
+            Some(sampler) => sampler.all(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Groups of live SSTables with overlapping key ranges, ordered so the
+    /// groups that sampled reads hit most come first.
+    ///
+    /// Compacting a hot group removes the most read amplification. Groups
+    /// smaller than `min_group_size` are left out, and each group lists its
+    /// files from oldest to newest.
+    pub fn hot_compaction_groups(&self, min_group_size: usize) -> Vec<Vec<SSTableInfo>> {
+        let sstables = self.list_sstables();
+        let hotness: Vec<u64> = sstables
+            .iter()
+            .map(|info| {
+                self.read_sampler
+                    .as_ref()
+                    .map_or(0, |sampler| sampler.get(&info.path))
+            })
+            .collect();
+
+        crate::sstable::SSTableCompaction::identify_hot_compaction_groups(
+            &sstables,
+            &hotness,
+            min_group_size,
+        )
+        .into_iter()
+        .map(|group| group.into_iter().map(|i| sstables[i].clone()).collect())
+        .collect()
+    }
+
+    /// Probe counters for every SSTable looked at so far
+    pub fn sstable_access_stats(&self) -> Vec<SSTableAccessStats> {
+        self.file_access.all()
+    }
+
+    /// Live SSTables whose key range covers `key`, oldest first
+    fn sstables_covering(&self, key: &str) -> Vec<FileMetadata> {
+        let mut files: Vec<FileMetadata> = locks::recover(self.manifest.lock())
+            .files()
+            .filter(|file| {
+                file.min_key.as_deref().is_some_and(|min| min <= key)
+                    && file.max_key.as_deref().is_some_and(|max| key <= max)
+            })
+            .cloned()
+            .collect();
+        files.sort_by(|a, b| Self::recency(a).cmp(&Self::recency(b)));
+        files
+    }
+
+    /// Ordering key placing files holding newer data after older ones.
+    ///
+    /// Flush numbers, unlike write times, never go backwards, and a
+    /// compaction output takes the newest flush number of its inputs, so it
+    /// stays behind a newer flush however long after that it was written.
+    fn recency(file: &FileMetadata) -> (u64, u64, &str) {
+        (file.flush_number, file.file_number, &file.path)
+    }
+
+    /// The order in which SSTables that may hold `key` are worth probing.
+    ///
+    /// Files are ranked by how often probes against them have found their
+    /// key, so a hot file is tried before a cold one even if the cold file is
+    /// newer; files with equal odds are tried newest first.
+    pub fn probe_order(&self, key: &str) -> Vec<String> {
+        let mut ranked: Vec<(f64, FileMetadata)> = self
+            .sstables_covering(key)
+            .into_iter()
+            .map(|file| (self.file_access.get(&file.path).expected_hit_rate(), file))
+            .collect();
+        ranked.sort_by(|(a_rate, a), (b_rate, b)| {
+            b_rate
+                .total_cmp(a_rate)
+                .then_with(|| Self::recency(b).cmp(&Self::recency(a)))
+        });
+        ranked.into_iter().map(|(_, file)| file.path).collect()
+    }
+
+    /// Look a key up in the flushed SSTables alone, bypassing the memtable
+    /// and the in-memory index.
+    ///
+    /// Files are probed in `probe_order`. Once a file holds the key, only
+    /// newer files can still shadow it, so older candidates are skipped.
+    /// A tombstone for the key, or for a range holding it that the file
+    /// does not rewrite the key after, counts as finding it removed.
+    pub fn get_flushed(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let covering = self.sstables_covering(key);
+        let mut found: Option<(usize, Option<Vec<u8>>)> = None;
+
+        for path in self.probe_order(key) {
+            // Position in `covering` doubles as the file's recency rank
+            // A file compacted away between the two reads of the manifest
+            // holds nothing newer than its output
+            let Some(rank) = covering.iter().position(|file| file.path == path) else {
+                continue;
+            };
+            if found.as_ref().is_some_and(|(best, _)| rank < *best) {
+                continue;
+            }
+
+            // Tombstones are not in the Bloom filter, so check them first
+            if let Some(reader_entry) = self.sstable_readers.get(&path)
+                && !reader_entry.value().has_tombstone(key)
+                && !reader_entry.value().may_contain(key)
+            {
+                self.record_probe(&path, stats::ProbeOutcome::BloomNegative);
+                continue;
+            }
+
+            let mut reader = self.open_sstable(&path)?;
+            if reader.tombstones().contains_key(key) {
+                self.record_probe(&path, stats::ProbeOutcome::Hit);
+                found = Some((rank, None));
+                continue;
+            }
+            // A range tombstone hides the key unless the file rewrote it
+            let range_deleted = reader.range_tombstones().covers(key);
+            if !reader.may_contain(key) || !reader.block_may_contain(key) {
+                self.record_probe(&path, stats::ProbeOutcome::BloomNegative);
+                if range_deleted {
+                    found = Some((rank, None));
                 }
+                continue;
             }
-            let key_len = u32::from_le_bytes(key_len_buf) as usize;
-            if key_len > 1024 * 1024 {
-                // Sanity check - keys shouldn't be huge
-                return Err(LsmIndexError::InvalidOperation(format!(
-                    "Invalid key length {} for entry {}",
-                    key_len, i
-                )));
+            self.level_stats.record_read(self.sstable_level(&path));
+            match reader.get(key)? {
+                Some(value) => {
+                    self.record_probe(&path, stats::ProbeOutcome::Hit);
+                    let value = if reader.value_log_segment(key).is_some() {
+                        self.read_value_pointer(key, &value)?
+                    } else {
+                        value
+                    };
+                    found = Some((rank, Some(value)));
+                }
+                None if range_deleted => {
+                    self.record_probe(&path, stats::ProbeOutcome::Hit);
+                    found = Some((rank, None));
+                }
+                None => self.record_probe(&path, stats::ProbeOutcome::FalsePositive),
             }
+        }
+
+        Ok(found.and_then(|(_, value)| value))
+    }
+
+    /// Handle to a live SSTable, registering it on first use
+    fn live_file(&self, path: &str) -> SSTableFileRef {
+        self.live_files
+            .get_or_insert_with(path.to_string(), || SSTableFile::new_ref(path))
+            .value()
+            .clone()
+    }
+
+    /// Remove an SSTable from the live set and schedule it for deletion.
+    ///
+    /// The file leaves the manifest and the reader cache immediately, but is
+    /// only deleted once no index entry points into it any more, so reads
+    /// that already resolved a storage reference never hit a missing file.
+    /// Returns false if the path is not a live SSTable.
+    pub fn retire_sstable(&self, path: &str) -> Result<bool> {
+        let entry = match self.live_files.remove(path) {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+
+        let file = entry.value();
+        file.get().retire();
+        file.invalidate();
+
+        self.sstable_readers.remove(path);
+        if let Some(sampler) = &self.read_sampler {
+            sampler.forget(path);
+        }
+        self.traces.forget(path);
+        self.lock_manifest()?.remove_file(path)?;
+        self.lifetime.record_retirement();
+        Ok(true)
+    }
+
+    /// Number of index entries currently pinning a live SSTable
+    pub fn sstable_references(&self, path: &str) -> usize {
+        self.live_files
+            .get(path)
+            .map_or(0, |entry| entry.value().ref_count() - 1)
+    }
+
+    /// Update the index with entries from an SSTable
+    fn update_index_from_sstable(&self, sstable_path: &str) -> Result<SSTableSummary> {
+        self.index_sstable(sstable_path, None)
+    }
+
+    /// Index the entries of an SSTable.
+    ///
+    /// With `replacing`, the file is a compaction output standing in for
+    /// those files: only keys still served from them are pointed at the new
+    /// file, and its tombstones, which the inputs already applied, are left
+    /// alone.
+    fn index_sstable(
+        &self,
+        sstable_path: &str,
+        replacing: Option<&HashSet<String>>,
+    ) -> Result<SSTableSummary> {
+        debug!("update_index_from_sstable - Starting for {}", sstable_path);
+        let loaded = self.load_sstable(sstable_path)?;
+        self.apply_sstable(sstable_path, loaded, replacing)
+    }
+
+    /// Read an SSTable's entries and metadata for `apply_sstable`, checking
+    /// each entry's checksum. Touches nothing but the file, so several
+    /// files can be loaded at once.
+    fn load_sstable(&self, sstable_path: &str) -> Result<LoadedSSTable> {
+        // Open the SSTable file and position at the data section
+        let file = TableFile::open(sstable_path, self.options().key_provider.as_deref())?;
+        let file_size = file.len()?;
+        let mut reader = BufReader::new(file);
+        let layout = Self::read_sstable_layout(&mut reader)?;
+        trace!(
+            "update_index_from_sstable - Entry count: {}, checksums: {}",
+            layout.entry_count, layout.has_entry_checksums
+        );
+
+        // Write times, tombstones and the compression dictionary live in the
+        // meta section; legacy files have none of them
+        let (
+            write_times,
+            expiries,
+            tombstones,
+            range_tombstones,
+            decoder,
+            mut key_expander,
+            applied_lsn,
+            sequences,
+            max_sequence,
+            flush_number,
+            value_log_segments,
+        ) = self
+            .open_sstable(sstable_path)
+            .map(|reader| {
+                (
+                    reader.write_times().clone(),
+                    reader.expiries().clone(),
+                    reader.tombstones().clone(),
+                    reader.range_tombstones().clone(),
+                    reader.value_decoder().clone(),
+                    reader.key_expander(),
+                    reader.applied_lsn().unwrap_or(0),
+                    reader.sequences().clone(),
+                    reader.max_sequence(),
+                    reader.flush_number(),
+                    reader.value_log_segments().clone(),
+                )
+            })
+            .unwrap_or_default();
+
+        // Read and check every entry before any reaches the index
+        let mut entries = Vec::new();
+        for i in 0..layout.entry_count {
+            let entry_pos = reader.stream_position()?;
 
-            // Read key
+            // Read key length
+            let key_len =
+                checked_len::read_len(&mut reader, file_size, crate::sstable::MAX_KEY_SIZE, "Key")?;
+
+            // Read key, rebuilding it if stored prefix-compressed
             let mut key_buf = vec![0u8; key_len];
-            match reader.read_exact(&mut key_buf) {
-                Ok(_) => {}
-                Err(e) => {
-                    println!(
-                        "update_index_from_sstable - Failed to read key for entry {}: {}",
-                        i, e
-                    );
-                    return Err(LsmIndexError::IoError(e));
-                }
+            reader.read_exact(&mut key_buf)?;
+            if let Some(expander) = &mut key_expander {
+                expander.prime(&mut reader, file_size, entry_pos)?;
+                key_buf = expander.expand(entry_pos, &key_buf)?;
             }
             let key = String::from_utf8_lossy(&key_buf).to_string();
 
             // Read value length
-            let mut value_len_buf = [0u8; 4];
-            match reader.read_exact(&mut value_len_buf) {
-                Ok(_) => {}
-                Err(e) => {
-                    println!(
-                        "update_index_from_sstable - Failed to read value length for entry {}: {}",
-                        i, e
-                    );
-                    return Err(LsmIndexError::IoError(e));
-                }
-            }
-            let value_len = u32::from_le_bytes(value_len_buf) as usize;
-            if value_len > 10 * 1024 * 1024 {
-                // Sanity check - values shouldn't be massive
-                return Err(LsmIndexError::InvalidOperation(format!(
-                    "Invalid value length {} for entry {}",
-                    value_len, i
-                )));
-            }
+            let value_len = checked_len::read_len(
+                &mut reader,
+                file_size,
+                crate::sstable::MAX_VALUE_SIZE,
+                "Value",
+            )?;
 
             // Read value
             let mut value_buf = vec![0u8; value_len];
-            match reader.read_exact(&mut value_buf) {
-                Ok(_) => {}
-                Err(e) => {
-                    println!(
-                        "update_index_from_sstable - Failed to read value for entry {}: {}",
-                        i, e
-                    );
-                    return Err(LsmIndexError::IoError(e));
+            reader.read_exact(&mut value_buf)?;
+
+            // Verify the entry checksum when the format has one
+            if layout.has_entry_checksums {
+                let mut checksum_buf = [0u8; 4];
+                reader.read_exact(&mut checksum_buf)?;
+                let stored = u32::from_le_bytes(checksum_buf);
+                if stored != layout.checksum_kind.entry_checksum(&key, &value_buf) {
+                    return Err(LsmIndexError::InvalidOperation(format!(
+                        "Checksum mismatch for entry {} in {}",
+                        i, sstable_path
+                    )));
                 }
             }
 
-            println!(
-                "update_index_from_sstable - Read entry {}: key='{}', value_len={}",
-                i, key, value_len
-            );
+            // Separated values are as long as their pointer says
+            let value = decoder.decode(value_buf)?;
+            let value_len = if value_log_segments.contains_key(&key) {
+                ValuePointer::decode(&value)?.len as u64
+            } else {
+                value.len() as u64
+            };
+            entries.push((key, entry_pos, value_len));
+        }
+
+        Ok(LoadedSSTable {
+            layout,
+            write_times,
+            expiries,
+            tombstones,
+            range_tombstones,
+            applied_lsn,
+            sequences,
+            max_sequence,
+            flush_number,
+            entries,
+        })
+    }
 
+    /// Point the index at the entries of a loaded SSTable and apply its
+    /// tombstones, as `index_sstable` describes
+    fn apply_sstable(
+        &self,
+        sstable_path: &str,
+        loaded: LoadedSSTable,
+        replacing: Option<&HashSet<String>>,
+    ) -> Result<SSTableSummary> {
+        let LoadedSSTable {
+            layout,
+            write_times,
+            expiries,
+            tombstones,
+            range_tombstones,
+            applied_lsn,
+            sequences,
+            max_sequence,
+            flush_number,
+            entries,
+        } = loaded;
+        // Writes from now on must order after the file's entries
+        if let Some(sequence) = max_sequence {
+            self.observe_sequence(sequence);
+        }
+        let now_ms = self.now_ms();
+
+        let file = self.live_file(sstable_path);
+        let mut summary = SSTableSummary {
+            entry_count: layout.entry_count,
+            min_key: None,
+            max_key: None,
+            raw_bytes: 0,
+            data_bytes: layout.data_bytes,
+            bloom_bytes: layout.bloom_bytes,
+            tombstone_count: tombstones.len() as u64,
+            applied_lsn,
+            max_sequence: max_sequence.unwrap_or(0),
+            flush_number,
+        };
+
+        for (key, entry_pos, value_len) in entries {
             // Create storage reference
             let storage_ref = StorageReference {
                 file_path: sstable_path.to_string(),
@@ -609,47 +2598,155 @@ impl LsmIndex {
                 is_tombstone: false,
             };
 
-            // Update index - lock-free update with SkipMap
-            self.index
-                .insert(key, GenIndexEntry::new(Some(value_buf), Some(storage_ref)));
+            summary.raw_bytes += key.len() as u64 + value_len;
+            if summary.min_key.as_ref().is_none_or(|min| key < *min) {
+                summary.min_key = Some(key.clone());
+            }
+            if summary.max_key.as_ref().is_none_or(|max| key > *max) {
+                summary.max_key = Some(key.clone());
+            }
+
+            // A compaction output only takes over keys its inputs served
+            if let Some(inputs) = replacing {
+                let served_from_input = self.index.get(&key).is_some_and(|current| {
+                    current
+                        .value()
+                        .storage_ref()
+                        .is_some_and(|storage_ref| inputs.contains(&storage_ref.file_path))
+                });
+                if !served_from_input {
+                    continue;
+                }
+            }
+
+            // An entry older than the one indexed for the key is shadowed,
+            // whatever order the files are indexed in
+            let sequence = sequences.get(&key).copied();
+            if let Some(sequence) = sequence
+                && self.index.get(&key).is_some_and(|current| {
+                    current
+                        .value()
+                        .sequence()
+                        .is_some_and(|current| current > sequence)
+                })
+            {
+                continue;
+            }
+
+            // The entry points at its value in the file rather than holding
+            // it, so the index stays small however much is on disk
+            let mut entry =
+                GenIndexEntry::on_disk(storage_ref, value_len).with_file(file.clone());
+            if let Some(&written_at_ms) = write_times.get(&key) {
+                entry = entry.with_written_at_ms(written_at_ms);
+            }
+            if let Some(sequence) = sequence {
+                entry = entry.with_sequence(sequence);
+            }
+            self.deleted.remove(&key);
+            match expiries.get(&key) {
+                // An expired entry still hides values from older files
+                Some(&expires_at_ms) if expires_at_ms <= now_ms => {
+                    self.index.remove(&key);
+                }
+                Some(&expires_at_ms) => {
+                    self.index
+                        .insert(key, entry.with_expires_at_ms(expires_at_ms));
+                }
+                None => {
+                    self.index.insert(key, entry);
+                }
+            }
         }
 
-        println!(
+        // Tombstones hide values indexed from older files, and count towards
+        // the key range so lookups consult this file for them
+        for (key, tombstone) in tombstones {
+            if summary.min_key.as_ref().is_none_or(|min| key < *min) {
+                summary.min_key = Some(key.clone());
+            }
+            if summary.max_key.as_ref().is_none_or(|max| key > *max) {
+                summary.max_key = Some(key.clone());
+            }
+            if replacing.is_none() {
+                self.index.remove(&key);
+                self.restore_soft_delete(key, tombstone);
+            }
+        }
+
+        // Range tombstones likewise hide entries from older files, but
+        // never the file's own
+        for fragment in range_tombstones.fragments() {
+            if summary
+                .min_key
+                .as_ref()
+                .is_none_or(|min| fragment.start < *min)
+            {
+                summary.min_key = Some(fragment.start.clone());
+            }
+            if summary
+                .max_key
+                .as_ref()
+                .is_none_or(|max| fragment.end > *max)
+            {
+                summary.max_key = Some(fragment.end.clone());
+            }
+            if replacing.is_some() {
+                continue;
+            }
+            let hidden: Vec<String> = self
+                .index
+                .range::<str, _>((
+                    Bound::Included(fragment.start.as_str()),
+                    Bound::Excluded(fragment.end.as_str()),
+                ))
+                .filter(|entry| {
+                    entry
+                        .value()
+                        .storage_ref()
+                        .is_some_and(|storage_ref| storage_ref.file_path != sstable_path)
+                })
+                .map(|entry| entry.key().clone())
+                .collect();
+            for key in hidden {
+                self.index.remove(&key);
+            }
+        }
+
+        debug!(
             "update_index_from_sstable - Successfully processed all {} entries",
-            entry_count
-        );
-        println!(
-            "update_index_from_sstable - Final index size: {}",
-            self.index.len()
+            layout.entry_count
         );
-        Ok(())
+        Ok(summary)
     }
 
     /// Recover state from existing SSTables
     pub fn recover(&mut self) -> Result<()> {
-        println!("LsmIndex::recover - Starting recovery");
-        // Find all SSTables in the base directory
-        let entries = fs::read_dir(&self.base_path)?;
-        println!("LsmIndex::recover - Reading directory: {}", self.base_path);
-
+        info!("LsmIndex::recover - Starting recovery");
+        // Find all SSTables in the base directory and the data directories
         let mut sstable_paths = Vec::new();
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
-
-            if path.is_file() && path.extension().unwrap_or_default() == "db" {
-                let path_str = path.to_string_lossy().to_string();
-                println!("LsmIndex::recover - Found potential SSTable: {}", path_str);
-                sstable_paths.push(path_str);
+        for directory in &self.sstable_directories() {
+            let entries = fs::read_dir(directory)?;
+            debug!("LsmIndex::recover - Reading directory: {}", directory);
+
+            for entry in entries {
+                let entry = entry?;
+                let path = entry.path();
+
+                if path.is_file() && path.extension().unwrap_or_default() == "db" {
+                    let path_str = path.to_string_lossy().to_string();
+                    debug!("LsmIndex::recover - Found potential SSTable: {}", path_str);
+                    sstable_paths.push(path_str);
+                }
             }
         }
 
         if sstable_paths.is_empty() {
-            println!("LsmIndex::recover - No SSTables found, nothing to recover");
+            debug!("LsmIndex::recover - No SSTables found, nothing to recover");
             return Ok(());
         }
 
-        println!(
+        debug!(
             "LsmIndex::recover - Found {} SSTables to recover",
             sstable_paths.len()
         );
@@ -657,21 +2754,120 @@ impl LsmIndex {
         // In a lock-free structure, we can just create a new index and update it
         // No need to explicitly clear it
 
+        // Files are read and opened on a bounded pool, a window at a time,
+        // then applied to the index in order
+        let concurrency = self.options().open_concurrency.max(1);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(concurrency.min(sstable_paths.len()))
+            .build()
+            .map_err(|e| LsmIndexError::InvalidOperation(format!("Open pool failed: {}", e)))?;
+        let index = &*self;
+
+        // Index files from oldest to newest data so newer entries and
+        // tombstones win
+        let mut sstable_paths = pool.install(|| {
+            sstable_paths
+                .into_par_iter()
+                .map(|path| Ok((index.sstable_age(&path)?, path)))
+                .collect::<Result<Vec<_>>>()
+        })?;
+        sstable_paths.sort();
+
         // Update the index from each SSTable
-        for sstable_path in sstable_paths {
-            println!("LsmIndex::recover - Processing SSTable: {}", sstable_path);
-            self.update_index_from_sstable(&sstable_path)?;
+        for window in sstable_paths.chunks(concurrency) {
+            // Files written before the manifest existed are adopted on
+            // level 0
+            let levels: Vec<u32> = {
+                let manifest = self.lock_manifest()?;
+                window
+                    .iter()
+                    .map(|(_, path)| manifest.get(path).map_or(0, |file| file.level))
+                    .collect()
+            };
+            let opened: Vec<_> = pool.install(|| {
+                window
+                    .par_iter()
+                    .zip(levels)
+                    .map(|((_, path), level)| {
+                        (index.load_sstable(path), index.open_reader(path, level))
+                    })
+                    .collect()
+            });
+
+            for ((age, sstable_path), (loaded, reader)) in window.iter().zip(opened) {
+                debug!("LsmIndex::recover - Processing SSTable: {}", sstable_path);
+                let summary = self.apply_sstable(sstable_path, loaded?, None)?;
+
+                let mut manifest = self.lock_manifest()?;
+                if let Ok(reader) = reader {
+                    self.sstable_readers.insert(sstable_path.clone(), reader);
+                }
+                if manifest.get(sstable_path).is_none() {
+                    manifest.mark_file_number_used(age.file_number)?;
+                    manifest.add_file(Self::file_metadata(
+                        sstable_path,
+                        0,
+                        (age.created_at_secs, age.file_number),
+                        summary,
+                    )?)?;
+                }
+            }
+        }
+
+        if let Some(max_file_bytes) = self.options().startup_compaction_bytes {
+            let summary = self.compact_tiny_files(max_file_bytes)?;
+            if !summary.files_removed.is_empty() {
+                info!(
+                    "LsmIndex::recover - Merged {} small SSTables into one",
+                    summary.files_removed.len()
+                );
+            }
         }
 
-        println!("LsmIndex::recover - Recovery completed successfully");
+        info!("LsmIndex::recover - Recovery completed successfully");
         Ok(())
     }
 
+    /// How new an SSTable's data is: its manifest record if it has one,
+    /// and otherwise the numbers in the file and its modification time
+    fn sstable_age(&self, path: &str) -> Result<SSTableAge> {
+        if let Some(file) = self.lock_manifest()?.get(path) {
+            return Ok(SSTableAge {
+                flush_number: file.flush_number,
+                file_number: file.file_number,
+                created_at_secs: file.created_at_secs,
+            });
+        }
+        let (file_number, flush_number) = self
+            .open_sstable(path)
+            .map(|reader| (reader.file_number(), reader.flush_number()))
+            .unwrap_or_default();
+        let created_at_secs = fs::metadata(path)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |age| age.as_secs());
+        Ok(SSTableAge {
+            flush_number: flush_number.unwrap_or(0),
+            file_number: file_number.unwrap_or(0),
+            created_at_secs,
+        })
+    }
+
     /// Clear the index and memtable
     pub fn clear(&self) -> Result<()> {
+        self.clear_with_options(&self.default_write_options())
+    }
+
+    /// Clear the index and memtable with explicit durability settings.
+    /// Clearing has no single key for `if_unchanged_since` to check, so it
+    /// fails with `InvalidOperation` if that is set.
+    pub fn clear_with_options(&self, write_options: &WriteOptions) -> Result<()> {
+        let started = Instant::now();
+        self.refuse_precondition("Clearing the index", write_options)?;
+
         // Log the operation for durability
-        let mut durability_manager = self.durability_manager.lock().unwrap();
-        durability_manager.log_operation(Operation::Clear)?;
+        let mut durability_manager = self.lock_wal()?;
+        Self::log_write(&mut durability_manager, Operation::Clear, write_options)?;
 
         // Clear the memtable
         self.memtable.clear()?;
@@ -686,13 +2882,18 @@ impl LsmIndex {
         {
             self.index.remove(&key);
         }
+        self.deleted.clear();
+        self.removed.clear();
+        self.checked_lock(self.range_tombstones.write(), "range tombstone")?.clear();
+        self.finish_write(write_options, started);
 
         Ok(())
     }
 
-    /// Shutdown the LSM index, flushing any pending data to disk
+    /// Shutdown the LSM index, saving its lifetime statistics
     pub fn shutdown(&mut self) -> io::Result<()> {
         // No need to call shutdown on StringMemtable as it doesn't have this method
-        Ok(())
+        self.save_lifetime_stats()
+            .map_err(|e| io::Error::other(format!("{:?}", e)))
     }
 }
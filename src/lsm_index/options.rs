@@ -0,0 +1,729 @@
+use super::bloom_policy::BloomFprPolicy;
+use super::compaction_scheduler::CompactionScheduler;
+use super::consistency::ConsistencyCheck;
+use super::disk_space::{DiskSpaceProbe, FileSystemProbe};
+use super::locks::PoisonPolicy;
+use super::placement::{
+    ColdStoragePolicy, FileNamer, NumberedFileNamer, PlacementPolicy, RoundRobinPlacement,
+};
+use super::retry::RetryPolicy;
+use super::runtime_options::OptionsObserver;
+use crate::clock::{Clock, SystemClock};
+use crate::memtable::KeyFilterOptions;
+use crate::sstable::{
+    ChecksumKind, Compression, CompressionType, FilterCache, KeyProvider, PrefixExtractor, DATA_BLOCK_SIZE,
+    MAX_KEY_SIZE, MAX_VALUE_SIZE,
+};
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Tunable limits and behaviour for an `LsmIndex`
+#[derive(Debug, Clone)]
+pub struct LsmIndexOptions {
+    /// Maximum size of a key in bytes accepted by `insert`
+    pub max_key_size: usize,
+    /// Maximum size of a value in bytes accepted by `insert`
+    pub max_value_size: usize,
+    /// Policy choosing each new SSTable's Bloom filter false positive rate;
+    /// when unset, the rate passed to the constructor is used for every file
+    pub bloom_fpr_policy: Option<Arc<dyn BloomFprPolicy>>,
+    /// Extractor shared by prefix Bloom filters, prefix iteration and the
+    /// memtable's prefix counts
+    pub prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    /// Whether to record each entry's wall-clock write time and persist it
+    /// alongside the entry in SSTables
+    pub track_write_times: bool,
+    /// How long removed values are kept for `undelete`; `None` removes
+    /// values outright
+    pub soft_delete_retention: Option<Duration>,
+    /// How long superseded values are kept for `get_at` and `history`;
+    /// `None` keeps only the latest value of each key
+    pub version_retention: Option<Duration>,
+    /// Compression applied to values in flushed SSTables
+    pub compression: Compression,
+    /// Compression applied to whole data blocks of flushed and compacted
+    /// SSTables
+    pub block_compression: CompressionType,
+    /// Provider whose current key flushed and compacted SSTables encrypt
+    /// their data blocks under, and which encrypted SSTables are read with;
+    /// `None` leaves new SSTables unencrypted
+    pub key_provider: Option<Arc<dyn KeyProvider>>,
+    /// Function computing the checksums of entries and meta sections in
+    /// flushed and compacted SSTables, and of records in a newly created WAL
+    pub checksum_kind: ChecksumKind,
+    /// False positive rate of a Bloom filter given to each data block of
+    /// new SSTables, so point lookups skip blocks without the key; `None`
+    /// gives blocks no filter
+    pub block_filter_fpr: Option<f64>,
+    /// Levels whose SSTables index entries by key hash rather than by
+    /// block, for point lookups that never range-scan them
+    pub hash_index_levels: Vec<u32>,
+    /// Size in bytes at which new SSTables close a data block
+    pub block_size: usize,
+    /// Partition the index and Bloom filter of new SSTables into partitions
+    /// of this many bytes of block handles, loaded on demand; `None` keeps
+    /// the whole index in memory
+    pub index_block_size: Option<usize>,
+    /// Record a restart point every this many entries of each data block of
+    /// new SSTables, so lookups scan at most that many; `None` scans whole
+    /// blocks
+    pub restart_interval: Option<usize>,
+    /// Store the keys of new SSTables as the length of the prefix they
+    /// share with the key at the restart point before them, followed by the
+    /// rest of the key
+    pub key_prefix_compression: bool,
+    /// Values at least this many bytes long are flushed to a value log
+    /// segment, leaving a pointer in the SSTable; `None` keeps every value
+    /// inline
+    pub value_separation_threshold: Option<usize>,
+    /// Sample one in every this many reads to measure per-file hotness;
+    /// `None` disables sampling
+    pub read_sample_interval: Option<u32>,
+    /// Directories SSTables are spread across; when empty they are written
+    /// to the index's base path
+    pub data_directories: Vec<String>,
+    /// Policy choosing which data directory each new SSTable goes to
+    pub placement_policy: Arc<dyn PlacementPolicy>,
+    /// Scheme used to name new SSTable files
+    pub file_namer: Arc<dyn FileNamer>,
+    /// Directory on slower, cheaper storage that compaction moves cold
+    /// SSTables to, as chosen by `cold_storage_policy`
+    pub cold_path: Option<String>,
+    /// Policy choosing which compaction outputs go to `cold_path`; neither
+    /// takes effect without the other
+    pub cold_storage_policy: Option<Arc<dyn ColdStoragePolicy>>,
+    /// Whether opening the index cross-checks, and optionally repairs, the
+    /// manifest, data directories and WAL checkpoints
+    pub consistency_check: ConsistencyCheck,
+    /// How flushes retry file operations that fail with transient errors
+    pub retry_policy: RetryPolicy,
+    /// Free space, in bytes, that inserts and flushes leave untouched so
+    /// compaction, which needs room for its output before it frees its
+    /// inputs, can still run on a nearly full disk; 0 disables the check
+    pub reserved_headroom_bytes: u64,
+    /// How free space is measured for `reserved_headroom_bytes`
+    pub disk_space_probe: Arc<dyn DiskSpaceProbe>,
+    /// Cache holding the Bloom filters of the index's SSTables, loaded on
+    /// first use; when unset, each reader loads its filter when opened and
+    /// keeps it
+    pub filter_cache: Option<Arc<FilterCache>>,
+    /// Bloom filter kept over the active memtable's keys, so lookups of keys
+    /// it does not hold skip it; emptied at each flush
+    pub memtable_filter: Option<KeyFilterOptions>,
+    /// Source of wall-clock time for write times, expiry, retention, file
+    /// names and checkpoint IDs
+    pub clock: Arc<dyn Clock>,
+    /// Size in bytes below which SSTables at the newest end of the index are
+    /// merged into one by `recover`; `None` leaves them as they are
+    pub startup_compaction_bytes: Option<u64>,
+    /// Most SSTables `recover` reads and opens at once; 1 opens them one
+    /// after another
+    pub open_concurrency: usize,
+    /// Whether writes made without explicit `WriteOptions`, such as
+    /// `insert` and `remove`, sync the WAL before returning
+    pub sync_writes: bool,
+    /// Most SSTable readers kept open at once; past it the least recently
+    /// used are closed and reopened when next needed. `None` keeps every
+    /// reader open
+    pub max_open_files: Option<usize>,
+    /// Told about each option changed with `LsmIndex::set_options`
+    pub options_observer: Option<Arc<dyn OptionsObserver>>,
+    /// Consulted before each compaction, to defer it or set its priority;
+    /// when unset, every compaction runs in the order asked for
+    pub compaction_scheduler: Option<Arc<dyn CompactionScheduler>>,
+    /// Reads, writes, flushes and compactions taking at least this long are
+    /// logged as warnings with the trace IDs they carried; `None` logs none
+    pub slow_op_threshold: Option<Duration>,
+    /// What operations do on finding a lock poisoned by a thread that
+    /// panicked while holding it
+    pub poison_policy: PoisonPolicy,
+    /// Properties stamped on every SSTable the index writes, such as the
+    /// schema version of the values, read back with
+    /// `SSTableReader::user_property`
+    pub user_properties: BTreeMap<String, String>,
+}
+
+impl Default for LsmIndexOptions {
+    fn default() -> Self {
+        LsmIndexOptions {
+            max_key_size: MAX_KEY_SIZE,
+            max_value_size: MAX_VALUE_SIZE,
+            bloom_fpr_policy: None,
+            prefix_extractor: None,
+            track_write_times: false,
+            soft_delete_retention: None,
+            version_retention: None,
+            compression: Compression::None,
+            block_compression: CompressionType::None,
+            key_provider: None,
+            checksum_kind: ChecksumKind::Crc32,
+            block_filter_fpr: None,
+            hash_index_levels: Vec::new(),
+            block_size: DATA_BLOCK_SIZE,
+            index_block_size: None,
+            restart_interval: None,
+            key_prefix_compression: false,
+            value_separation_threshold: None,
+            read_sample_interval: None,
+            data_directories: Vec::new(),
+            placement_policy: Arc::new(RoundRobinPlacement::default()),
+            file_namer: Arc::new(NumberedFileNamer),
+            cold_path: None,
+            cold_storage_policy: None,
+            consistency_check: ConsistencyCheck::Off,
+            retry_policy: RetryPolicy::default(),
+            reserved_headroom_bytes: 0,
+            disk_space_probe: Arc::new(FileSystemProbe),
+            filter_cache: None,
+            memtable_filter: None,
+            clock: Arc::new(SystemClock),
+            startup_compaction_bytes: None,
+            open_concurrency: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            sync_writes: true,
+            max_open_files: None,
+            options_observer: None,
+            compaction_scheduler: None,
+            slow_op_threshold: None,
+            poison_policy: PoisonPolicy::Fail,
+            user_properties: BTreeMap::new(),
+        }
+    }
+}
+
+impl LsmIndexOptions {
+    /// Set the maximum key size in bytes
+    pub fn with_max_key_size(mut self, max_key_size: usize) -> Self {
+        self.max_key_size = max_key_size;
+        self
+    }
+
+    /// Set the maximum value size in bytes
+    pub fn with_max_value_size(mut self, max_value_size: usize) -> Self {
+        self.max_value_size = max_value_size;
+        self
+    }
+
+    /// Set the policy used to size Bloom filters for new SSTables
+    pub fn with_bloom_fpr_policy(mut self, policy: impl BloomFprPolicy + 'static) -> Self {
+        self.bloom_fpr_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Set the extractor used for prefix Bloom filters and prefix iteration
+    pub fn with_prefix_extractor(mut self, extractor: impl PrefixExtractor + 'static) -> Self {
+        self.prefix_extractor = Some(Arc::new(extractor));
+        self
+    }
+
+    /// Enable or disable recording per-entry write times
+    pub fn with_write_times(mut self, track_write_times: bool) -> Self {
+        self.track_write_times = track_write_times;
+        self
+    }
+
+    /// Keep removed values for `retention` so they can be undeleted
+    pub fn with_soft_delete_retention(mut self, retention: Duration) -> Self {
+        self.soft_delete_retention = Some(retention);
+        self
+    }
+
+    /// Keep every value written within `retention` of now, and the one each
+    /// key held at the start of that window, so `get_at` can read the index
+    /// as of an earlier sequence or time. Versions are kept in memory from the time
+    /// the index is opened.
+    pub fn with_version_retention(mut self, retention: Duration) -> Self {
+        self.version_retention = Some(retention);
+        self
+    }
+
+    /// Compress values in flushed SSTables. With a Zstd dictionary enabled,
+    /// each flush trains one from a sample of the memtable's values.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Compress each data block of flushed and compacted SSTables as a
+    /// whole with `compression_type`. Blocks compress better than values on
+    /// their own when many small values repeat each other, as text often
+    /// does, and lookups decompress only the block holding the key.
+    pub fn with_block_compression(mut self, compression_type: CompressionType) -> Self {
+        self.block_compression = compression_type;
+        self
+    }
+
+    /// Encrypt the data blocks of flushed and compacted SSTables under the
+    /// current key of `provider`. Rotating the provider's key takes effect
+    /// with the next file written; files under older keys are rewritten
+    /// under the current one as compaction merges them, and stay readable
+    /// until then as long as the provider still has their key.
+    ///
+    /// Only SSTables are encrypted: the WAL is not, and since value log
+    /// segments would not be either, `validate` rejects encryption combined
+    /// with `with_value_separation`.
+    pub fn with_encryption(mut self, provider: Arc<dyn KeyProvider>) -> Self {
+        self.key_provider = Some(provider);
+        self
+    }
+
+    /// Checksum entries and meta sections of new SSTables with `kind`, such
+    /// as CRC32C on CPUs with CRC instructions, or XXH3 on those without.
+    /// Each file records its kind, so files written with different kinds
+    /// can be read side by side.
+    ///
+    /// A WAL created by the index checksums its records with `kind` too; an
+    /// existing WAL keeps the kind recorded in its header.
+    pub fn with_checksum_kind(mut self, kind: ChecksumKind) -> Self {
+        self.checksum_kind = kind;
+        self
+    }
+
+    /// Give each data block of flushed and compacted SSTables its own Bloom
+    /// filter at `false_positive_rate`. Worth it with large blocks, such as
+    /// under compression: a lookup that passes the file's filter then skips
+    /// reading and decompressing a block that does not hold the key.
+    pub fn with_block_filters(mut self, false_positive_rate: f64) -> Self {
+        self.block_filter_fpr = Some(false_positive_rate);
+        self
+    }
+
+    /// Index the entries of SSTables written to `levels` by key hash, so
+    /// point lookups in them go straight to the entry. Range scans over those
+    /// files read them in full, so this suits data that is only ever looked
+    /// up by key.
+    pub fn with_hash_index_levels(mut self, levels: impl IntoIterator<Item = u32>) -> Self {
+        self.hash_index_levels = levels.into_iter().collect();
+        self
+    }
+
+    /// Whether SSTables written to `level` get a hash index
+    pub fn uses_hash_index(&self, level: u32) -> bool {
+        self.hash_index_levels.contains(&level)
+    }
+
+    /// Close data blocks of new SSTables at `block_size` bytes. Larger
+    /// blocks compress better and shrink the index; smaller ones make point
+    /// lookups read less.
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Partition the index and Bloom filter of new SSTables every
+    /// `index_block_size` bytes of block handles, so readers keep only the
+    /// top level in memory. Levels with a hash index are not partitioned.
+    pub fn with_index_block_size(mut self, index_block_size: usize) -> Self {
+        self.index_block_size = Some(index_block_size);
+        self
+    }
+
+    /// Record a restart point every `restart_interval` entries of each data
+    /// block of new SSTables
+    pub fn with_restart_interval(mut self, restart_interval: usize) -> Self {
+        self.restart_interval = Some(restart_interval);
+        self
+    }
+
+    /// Store each key of new SSTables after the first of its restart
+    /// interval as the length of the prefix it shares with that key plus the
+    /// rest of the key, which shrinks files whose keys share long prefixes
+    /// such as tenant IDs. Restart points are recorded every
+    /// `DEFAULT_PREFIX_RESTART_INTERVAL` entries unless
+    /// `with_restart_interval` sets the interval.
+    pub fn with_key_prefix_compression(mut self, enabled: bool) -> Self {
+        self.key_prefix_compression = enabled;
+        self
+    }
+
+    /// Flush values of at least `threshold` bytes to the value log, a set
+    /// of append-only segment files in the base path, and store only a
+    /// pointer to each in the SSTable. Compaction then rewrites the
+    /// pointers rather than the values, which cuts write amplification for
+    /// large values at the cost of an extra read per lookup.
+    /// `LsmIndex::collect_value_log_garbage` reclaims the space of values
+    /// overwritten or deleted since.
+    pub fn with_value_separation(mut self, threshold: usize) -> Self {
+        self.value_separation_threshold = Some(threshold);
+        self
+    }
+
+    /// Sample one in every `interval` reads to track which SSTables are hot
+    pub fn with_read_sampling(mut self, interval: u32) -> Self {
+        self.read_sample_interval = Some(interval);
+        self
+    }
+
+    /// Spread SSTables across `directories`, for example one per disk. The
+    /// base path still holds the WAL and manifest, and is searched for
+    /// SSTables written before the directories were configured.
+    pub fn with_data_directories<I, S>(mut self, directories: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.data_directories = directories.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the policy choosing the data directory for each new SSTable
+    pub fn with_placement_policy(mut self, policy: impl PlacementPolicy + 'static) -> Self {
+        self.placement_policy = Arc::new(policy);
+        self
+    }
+
+    /// Set the scheme used to name new SSTable files
+    pub fn with_file_namer(mut self, namer: impl FileNamer + 'static) -> Self {
+        self.file_namer = Arc::new(namer);
+        self
+    }
+
+    /// Write compaction outputs `policy` deems cold to `path`, such as a
+    /// slower disk or a mounted object store. Files there are found by
+    /// recovery and read like any other.
+    pub fn with_cold_storage(
+        mut self,
+        path: impl Into<String>,
+        policy: impl ColdStoragePolicy + 'static,
+    ) -> Self {
+        self.cold_path = Some(path.into());
+        self.cold_storage_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Check the manifest, data directories and WAL checkpoints against each
+    /// other when the index is opened
+    pub fn with_consistency_check(mut self, check: ConsistencyCheck) -> Self {
+        self.consistency_check = check;
+        self
+    }
+
+    /// Set how flushes retry file operations that fail with transient errors
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Refuse inserts and flushes with `DiskFull` once they would leave
+    /// less than `bytes` free. Removals are still accepted, so space can be
+    /// reclaimed.
+    pub fn with_reserved_headroom(mut self, bytes: u64) -> Self {
+        self.reserved_headroom_bytes = bytes;
+        self
+    }
+
+    /// Set how free space is measured
+    pub fn with_disk_space_probe(mut self, probe: impl DiskSpaceProbe + 'static) -> Self {
+        self.disk_space_probe = Arc::new(probe);
+        self
+    }
+
+    /// Leave SSTable Bloom filters to `cache`, which may be shared between
+    /// indexes to bound their filter memory together
+    pub fn with_filter_cache(mut self, cache: Arc<FilterCache>) -> Self {
+        self.filter_cache = Some(cache);
+        self
+    }
+
+    /// Keep at most `max_open_files` SSTable readers open, closing the least
+    /// recently used ones past it. The count shows in
+    /// `ResourceUsage::open_files`, along with the readers closed and
+    /// reopened to stay within it, so the limit can be tuned.
+    pub fn with_max_open_files(mut self, max_open_files: usize) -> Self {
+        self.max_open_files = Some(max_open_files);
+        self
+    }
+
+    /// Read time from `clock` instead of the system clock, such as a
+    /// `MockClock` in tests
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Keep a Bloom filter over the active memtable's keys, sized for
+    /// `expected_keys` at `false_positive_rate`. Worth it when most lookups
+    /// are for keys the memtable does not hold.
+    pub fn with_memtable_filter(mut self, expected_keys: usize, false_positive_rate: f64) -> Self {
+        self.memtable_filter = Some(KeyFilterOptions {
+            expected_keys,
+            false_positive_rate,
+        });
+        self
+    }
+
+    /// Merge the run of SSTables smaller than `max_file_bytes` at the newest
+    /// end of the index into one file when it is recovered, so restarts
+    /// after frequent small flushes do not pile up tiny files for every
+    /// read to consult
+    pub fn with_startup_compaction(mut self, max_file_bytes: u64) -> Self {
+        self.startup_compaction_bytes = Some(max_file_bytes);
+        self
+    }
+
+    /// Read and open up to `open_concurrency` SSTables at once when the
+    /// index is recovered, including loading their Bloom filters. Files are
+    /// still applied to the index oldest first, so the result is the same
+    /// as opening them one at a time. Defaults to the number of CPUs.
+    pub fn with_open_concurrency(mut self, open_concurrency: usize) -> Self {
+        self.open_concurrency = open_concurrency.max(1);
+        self
+    }
+
+    /// Set whether writes made without explicit `WriteOptions` sync the WAL
+    /// before returning
+    pub fn with_sync_writes(mut self, sync_writes: bool) -> Self {
+        self.sync_writes = sync_writes;
+        self
+    }
+
+    /// Log reads, writes, flushes and compactions that take at least
+    /// `threshold` as warnings, naming the trace IDs given in their
+    /// `ReadOptions` or `WriteOptions`, or carried by the writes flushed or
+    /// compacted
+    pub fn with_slow_op_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_op_threshold = Some(threshold);
+        self
+    }
+
+    /// Set what operations do on finding a lock poisoned by a panic: fail
+    /// with `LsmIndexError::Internal` (the default), recover the lock, or
+    /// panic
+    pub fn with_poison_policy(mut self, policy: PoisonPolicy) -> Self {
+        self.poison_policy = policy;
+        self
+    }
+
+    /// Stamp every SSTable the index flushes or compacts with a user
+    /// property; files written before the property was set keep what they
+    /// were stamped with until compacted
+    pub fn with_user_property(mut self, name: &str, value: &str) -> Self {
+        self.user_properties
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Set the observer told about each option changed on the live index
+    pub fn with_options_observer(mut self, observer: impl OptionsObserver + 'static) -> Self {
+        self.options_observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Set the scheduler consulted before each compaction
+    pub fn with_compaction_scheduler(
+        mut self,
+        scheduler: impl CompactionScheduler + 'static,
+    ) -> Self {
+        self.compaction_scheduler = Some(Arc::new(scheduler));
+        self
+    }
+
+    /// Check that the options can be honoured by the on-disk format.
+    ///
+    /// Limits above the SSTable format limits are rejected, since data written
+    /// under them could never be read back.
+    pub fn validate(&self) -> io::Result<()> {
+        if self.max_key_size == 0 || self.max_key_size > MAX_KEY_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "max_key_size must be between 1 and {} bytes, got {}",
+                    MAX_KEY_SIZE, self.max_key_size
+                ),
+            ));
+        }
+
+        if self.max_value_size > MAX_VALUE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "max_value_size must be at most {} bytes, got {}",
+                    MAX_VALUE_SIZE, self.max_value_size
+                ),
+            ));
+        }
+
+        for (name, size) in [
+            ("block_size", Some(self.block_size)),
+            ("index_block_size", self.index_block_size),
+            ("restart_interval", self.restart_interval),
+        ] {
+            if size == Some(0) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} must be at least 1", name),
+                ));
+            }
+        }
+
+        if self.key_provider.is_some() && self.value_separation_threshold.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "value separation cannot be combined with encryption, as value log segments are not encrypted",
+            ));
+        }
+
+        if let Some(rate) = self.block_filter_fpr
+            && !(rate > 0.0 && rate < 1.0)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "block filter false positive rate must be between 0 and 1, got {}",
+                    rate
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Durability settings for a single write. Every write API has a
+/// `_with_options` form that takes them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Skip the WAL; the write is lost on a crash before the next flush.
+    /// Suits bulk loads that can be replayed from their source.
+    pub disable_wal: bool,
+    /// Sync the WAL to disk before the write returns. Unsynced writes may be
+    /// lost on power failure until a later synced write or `sync_wal`.
+    pub sync: bool,
+    /// Only write if the key has not changed since this commit sequence,
+    /// as returned by a previous write or `LsmIndex::sequence_of`;
+    /// otherwise fail with `PreconditionFailed`
+    pub if_unchanged_since: Option<u64>,
+    /// Correlation ID of the request making the write. It is carried to the
+    /// flush that persists the write and the compactions that later merge
+    /// its file, and named in their log messages and summaries.
+    pub trace_id: Option<String>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            disable_wal: false,
+            sync: true,
+            if_unchanged_since: None,
+            trace_id: None,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Skip or use the WAL
+    pub fn with_disable_wal(mut self, disable_wal: bool) -> Self {
+        self.disable_wal = disable_wal;
+        self
+    }
+
+    /// Sync or don't sync the WAL before returning
+    pub fn with_sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    /// Only write if the key has not changed since `sequence`, like an HTTP
+    /// `If-Match` on an ETag
+    pub fn with_if_unchanged_since(mut self, sequence: u64) -> Self {
+        self.if_unchanged_since = Some(sequence);
+        self
+    }
+
+    /// Tag the write with the correlation ID of the request making it
+    pub fn with_trace_id(mut self, trace_id: impl Into<String>) -> Self {
+        self.trace_id = Some(trace_id.into());
+        self
+    }
+}
+
+/// Settings for a single read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadOptions {
+    /// Check the stored CRC of entries read from SSTables and fail on a
+    /// mismatch. Turning this off trades corruption detection for speed.
+    pub verify_checksums: bool,
+    /// Keep readers opened to serve this read in the reader cache. Scans
+    /// over cold files can turn this off so they don't crowd the cache.
+    pub fill_cache: bool,
+    /// Only see entries written at or before this time, in milliseconds
+    /// since the Unix epoch, as returned by `LsmIndex::snapshot`. The index
+    /// keeps one version per key, so a key overwritten after the snapshot
+    /// reads as missing rather than as its older value; `LsmIndex::get_at`
+    /// reads older values when versions are retained. Needs write times to
+    /// be tracked; entries without a write time are always visible.
+    pub snapshot: Option<u64>,
+    /// Smallest key a range read returns, inclusive
+    pub lower_bound: Option<String>,
+    /// Key a range read stops before, exclusive
+    pub upper_bound: Option<String>,
+    /// Correlation ID of the request making the read, named in its slow
+    /// log message. Reads start no background work.
+    pub trace_id: Option<String>,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            verify_checksums: true,
+            fill_cache: true,
+            snapshot: None,
+            lower_bound: None,
+            upper_bound: None,
+            trace_id: None,
+        }
+    }
+}
+
+impl ReadOptions {
+    /// Verify or skip entry checksums
+    pub fn with_verify_checksums(mut self, verify_checksums: bool) -> Self {
+        self.verify_checksums = verify_checksums;
+        self
+    }
+
+    /// Cache or don't cache readers opened by the read
+    pub fn with_fill_cache(mut self, fill_cache: bool) -> Self {
+        self.fill_cache = fill_cache;
+        self
+    }
+
+    /// Read as of a snapshot taken with `LsmIndex::snapshot`
+    pub fn with_snapshot(mut self, snapshot: u64) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Set the inclusive lower bound for range reads
+    pub fn with_lower_bound(mut self, lower_bound: impl Into<String>) -> Self {
+        self.lower_bound = Some(lower_bound.into());
+        self
+    }
+
+    /// Set the exclusive upper bound for range reads
+    pub fn with_upper_bound(mut self, upper_bound: impl Into<String>) -> Self {
+        self.upper_bound = Some(upper_bound.into());
+        self
+    }
+
+    /// Tag the read with the correlation ID of the request making it
+    pub fn with_trace_id(mut self, trace_id: impl Into<String>) -> Self {
+        self.trace_id = Some(trace_id.into());
+        self
+    }
+
+    /// Whether a key lies within the configured bounds
+    pub fn in_bounds(&self, key: &str) -> bool {
+        self.lower_bound.as_deref().is_none_or(|lower| key >= lower)
+            && self.upper_bound.as_deref().is_none_or(|upper| key < upper)
+    }
+
+    /// Whether an entry written at `written_at_ms` is visible to the read
+    pub fn sees_write(&self, written_at_ms: Option<u64>) -> bool {
+        match (self.snapshot, written_at_ms) {
+            (Some(snapshot), Some(written_at_ms)) => written_at_ms <= snapshot,
+            _ => true,
+        }
+    }
+}
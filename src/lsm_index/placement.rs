@@ -0,0 +1,184 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// What is known about an SSTable when its file name is chosen
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileNameContext {
+    /// Level the file is written to; flushes write level 0
+    pub level: u32,
+    /// When the file is being written, in seconds since the Unix epoch
+    pub created_at_secs: u64,
+    /// Number the manifest allocated to the file; numbers increase and are
+    /// never reused
+    pub file_number: u64,
+}
+
+/// Chooses the file name for a new SSTable.
+///
+/// Names must end in `.db`, which is how recovery recognises SSTables. The
+/// index skips names that are already taken by allocating another
+/// `file_number`, so implementations should include it in the name.
+pub trait FileNamer: Debug + Send + Sync {
+    /// Return the file name, without any directory
+    fn file_name(&self, context: &FileNameContext) -> String;
+}
+
+/// Names files `sstable_<number>.db`, zero-padded so names sort in the order
+/// the files were written
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NumberedFileNamer;
+
+impl FileNamer for NumberedFileNamer {
+    fn file_name(&self, context: &FileNameContext) -> String {
+        format!("sstable_{:06}.db", context.file_number)
+    }
+}
+
+/// Names files `sstable_<secs>_<number>.db`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimestampFileNamer;
+
+impl FileNamer for TimestampFileNamer {
+    fn file_name(&self, context: &FileNameContext) -> String {
+        format!(
+            "sstable_{}_{:06}.db",
+            context.created_at_secs, context.file_number
+        )
+    }
+}
+
+/// Names files `<prefix>_L<level>_<secs>_<number>.db`, so files of several
+/// indexes or column families can share a directory and a listing shows
+/// each file's level
+#[derive(Debug, Clone)]
+pub struct PrefixedFileNamer {
+    pub prefix: String,
+}
+
+impl PrefixedFileNamer {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        PrefixedFileNamer {
+            prefix: prefix.into(),
+        }
+    }
+}
+
+impl FileNamer for PrefixedFileNamer {
+    fn file_name(&self, context: &FileNameContext) -> String {
+        format!(
+            "{}_L{}_{}_{:06}.db",
+            self.prefix, context.level, context.created_at_secs, context.file_number
+        )
+    }
+}
+
+/// How much of the index's data a directory currently holds
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectoryUsage {
+    /// The data directory
+    pub path: String,
+    /// Number of live SSTables in the directory
+    pub file_count: usize,
+    /// Total size of those SSTables in bytes
+    pub size_bytes: u64,
+}
+
+/// What is known about an SSTable when its directory is chosen
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacementContext {
+    /// Level the file is written to; flushes write level 0
+    pub level: u32,
+    /// Number of entries the file will hold
+    pub expected_entries: usize,
+    /// The configured data directories, in the order they were given
+    pub directories: Vec<DirectoryUsage>,
+}
+
+/// Chooses which data directory a new SSTable is written to
+pub trait PlacementPolicy: Debug + Send + Sync {
+    /// Return an index into `context.directories`; out-of-range indexes are
+    /// clamped to the last directory
+    fn choose_directory(&self, context: &PlacementContext) -> usize;
+}
+
+/// Cycles through the directories in order
+#[derive(Debug, Default)]
+pub struct RoundRobinPlacement {
+    next: AtomicUsize,
+}
+
+impl PlacementPolicy for RoundRobinPlacement {
+    fn choose_directory(&self, context: &PlacementContext) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed) % context.directories.len().max(1)
+    }
+}
+
+/// Picks the directory holding the fewest SSTable bytes, preferring earlier
+/// directories on ties
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LeastUsedPlacement;
+
+impl PlacementPolicy for LeastUsedPlacement {
+    fn choose_directory(&self, context: &PlacementContext) -> usize {
+        context
+            .directories
+            .iter()
+            .enumerate()
+            .min_by_key(|(i, usage)| (usage.size_bytes, *i))
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+/// Puts level `n` in directory `n`, and every deeper level in the last
+/// directory, so small hot levels can live on fast disks
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LevelPlacement;
+
+impl PlacementPolicy for LevelPlacement {
+    fn choose_directory(&self, context: &PlacementContext) -> usize {
+        (context.level as usize).min(context.directories.len().saturating_sub(1))
+    }
+}
+
+/// What is known about a compaction's output when deciding whether it
+/// belongs in cold storage
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColdStorageContext {
+    /// Level the output is written to
+    pub level: u32,
+    /// Number of entries the output will hold
+    pub expected_entries: usize,
+    /// When the newest input was written, in seconds since the Unix epoch;
+    /// nothing in the output was written later
+    pub newest_input_secs: u64,
+    /// Current time, in seconds since the Unix epoch
+    pub now_secs: u64,
+}
+
+/// Decides which compaction outputs are moved to the cold path
+pub trait ColdStoragePolicy: Debug + Send + Sync {
+    /// Return true to write the output to the cold path
+    fn is_cold(&self, context: &ColdStorageContext) -> bool;
+}
+
+/// Moves outputs on this level or deeper to cold storage
+#[derive(Debug, Clone, Copy)]
+pub struct ColdByLevel(pub u32);
+
+impl ColdStoragePolicy for ColdByLevel {
+    fn is_cold(&self, context: &ColdStorageContext) -> bool {
+        context.level >= self.0
+    }
+}
+
+/// Moves outputs to cold storage once all of their data is at least this
+/// old
+#[derive(Debug, Clone, Copy)]
+pub struct ColdByAge(pub Duration);
+
+impl ColdStoragePolicy for ColdByAge {
+    fn is_cold(&self, context: &ColdStorageContext) -> bool {
+        context.now_secs.saturating_sub(context.newest_input_secs) >= self.0.as_secs()
+    }
+}
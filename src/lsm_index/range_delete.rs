@@ -0,0 +1,110 @@
+use super::locks::recover;
+use super::{GenIndexEntry, LsmIndex, Result, WriteOptions};
+use crate::memtable::Memtable;
+use crate::sstable::RangeTombstone;
+use crate::wal::durability::Operation;
+use std::ops::Bound;
+use std::time::Instant;
+
+impl LsmIndex {
+    /// Remove every key from `start` up to but not including `end`, logging
+    /// a single WAL record for the whole range.
+    ///
+    /// Keys still in the memtable are removed right away. Flushed keys stay
+    /// in the index, hidden by a range tombstone that reads consult, until
+    /// the next flush writes the tombstone to its SSTable and drops them.
+    /// An empty range removes nothing.
+    pub fn delete_range(&self, start: &str, end: &str) -> Result<()> {
+        self.delete_range_with_options(start, end, &self.default_write_options())
+    }
+
+    /// Remove a range of keys, as `delete_range` does, with explicit
+    /// durability settings. A range has no single key for
+    /// `if_unchanged_since` to check, so it fails with `InvalidOperation` if
+    /// that is set.
+    pub fn delete_range_with_options(
+        &self,
+        start: &str,
+        end: &str,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let started = Instant::now();
+        self.refuse_precondition("Deleting a range", write_options)?;
+        if start >= end {
+            return Ok(());
+        }
+
+        let mut durability_manager = self.lock_wal()?;
+        Self::log_write(
+            &mut durability_manager,
+            Operation::DeleteRange {
+                start: start.to_string(),
+                end: end.to_string(),
+            },
+            write_options,
+        )?;
+
+        self.apply_delete_range(RangeTombstone::new(start, end, self.now_ms()))?;
+        self.finish_write(write_options, started);
+        Ok(())
+    }
+
+    /// Apply a logged range deletion to the memtable and index. Called with
+    /// the WAL lock held.
+    fn apply_delete_range(&self, tombstone: RangeTombstone) -> Result<()> {
+        // The memtable is small, so its keys are removed one by one; every
+        // key written to it from now on is newer than the tombstone
+        let range = (
+            Bound::Included(tombstone.start.clone()),
+            Bound::Excluded(tombstone.end.clone()),
+        );
+        for (key, _) in self.memtable.range(range)? {
+            self.memtable.remove(&key)?;
+            self.index.remove(&key);
+            let sequence = self.commit_sequence(&key);
+            self.record_version(&key, sequence, None);
+        }
+
+        self.checked_lock(self.range_tombstones.write(), "range tombstone")?.add(tombstone);
+        self.lifetime.record_remove();
+        Ok(())
+    }
+
+    /// Whether a range deleted since the last flush hides `key`.
+    ///
+    /// Only entries served from an SSTable can be hidden: entries written
+    /// since the deletion have not been flushed yet.
+    pub(super) fn is_range_deleted(&self, key: &str, entry: &GenIndexEntry) -> bool {
+        entry.storage_ref().is_some() && recover(self.range_tombstones.read()).covers(key)
+    }
+
+    /// Key ranges deleted since the last flush, for the flush to write
+    pub(super) fn pending_range_tombstones(&self) -> Vec<RangeTombstone> {
+        recover(self.range_tombstones.read()).fragments().to_vec()
+    }
+
+    /// Drop the flushed entries the pending range tombstones hide, then
+    /// forget the tombstones. Called by a flush, with the WAL lock held, once
+    /// the tombstones are in its SSTable and before its entries are indexed,
+    /// so no entry from the new file is dropped.
+    pub(super) fn drop_range_deleted(&self) -> Result<()> {
+        let mut range_tombstones =
+            self.checked_lock(self.range_tombstones.write(), "range tombstone")?;
+        for fragment in range_tombstones.fragments() {
+            let hidden: Vec<String> = self
+                .index
+                .range::<str, _>((
+                    Bound::Included(fragment.start.as_str()),
+                    Bound::Excluded(fragment.end.as_str()),
+                ))
+                .filter(|entry| entry.value().storage_ref().is_some())
+                .map(|entry| entry.key().clone())
+                .collect();
+            for key in hidden {
+                self.index.remove(&key);
+            }
+        }
+        range_tombstones.clear();
+        Ok(())
+    }
+}
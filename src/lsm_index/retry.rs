@@ -0,0 +1,174 @@
+use super::{LsmIndexError, Result};
+use std::fmt::Debug;
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How a failed file operation is treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Interrupted by a signal; retried straight away
+    Interrupted,
+    /// The disk is full; retried only if the observer frees space
+    NoSpace,
+    /// Likely to clear up on its own, such as a timeout or a stale network
+    /// file system handle; retried after a backoff
+    Transient,
+    /// Retrying will not help, such as a missing file or denied permission
+    Permanent,
+}
+
+impl ErrorClass {
+    /// Classify an I/O error
+    pub fn of(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::Interrupted => ErrorClass::Interrupted,
+            io::ErrorKind::StorageFull => ErrorClass::NoSpace,
+            io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ResourceBusy
+            | io::ErrorKind::StaleNetworkFileHandle => ErrorClass::Transient,
+            _ => ErrorClass::Permanent,
+        }
+    }
+}
+
+/// A failed attempt that is about to be retried
+#[derive(Debug)]
+pub struct RetryEvent<'a> {
+    /// The operation being retried, such as `"flush"`
+    pub operation: &'a str,
+    /// Number of attempts made so far, starting at 1
+    pub attempt: u32,
+    /// How the error was classified
+    pub class: ErrorClass,
+    /// The error the attempt failed with
+    pub error: &'a io::Error,
+    /// How long the policy waits before the next attempt
+    pub backoff: Duration,
+}
+
+/// Told about each retry, and asked what to do when the disk is full
+pub trait RetryObserver: Debug + Send + Sync {
+    /// Called before each retry
+    fn on_retry(&self, event: &RetryEvent<'_>);
+
+    /// Called when `operation` fails because the disk is full. Return true
+    /// once space has been freed, for example by deleting obsolete files, to
+    /// retry; the default gives up.
+    fn on_no_space(&self, operation: &str) -> bool {
+        let _ = operation;
+        false
+    }
+}
+
+/// Retries file operations that fail with transient errors, backing off
+/// exponentially between attempts
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries allowed after the first attempt
+    pub max_retries: u32,
+    /// Wait before the first retry; doubled for each one after
+    pub initial_backoff: Duration,
+    /// Longest wait between attempts
+    pub max_backoff: Duration,
+    /// Told about each retry
+    pub observer: Option<Arc<dyn RetryObserver>>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            observer: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that makes a single attempt
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Set the number of retries allowed after the first attempt
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the first and longest waits between attempts
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Set the observer told about each retry
+    pub fn with_observer(mut self, observer: impl RetryObserver + 'static) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Run `attempt` until it succeeds, fails permanently or runs out of
+    /// retries.
+    ///
+    /// Permanent errors are returned as `IoError` straight away. Retryable
+    /// errors become `Unrecoverable` once the retries are used up, as does
+    /// a full disk the observer does not free space on.
+    pub fn run<T>(&self, operation: &str, mut attempt: impl FnMut() -> io::Result<T>) -> Result<T> {
+        let mut attempts = 0;
+        let mut backoff = self.initial_backoff;
+        loop {
+            let error = match attempt() {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            attempts += 1;
+
+            let class = ErrorClass::of(&error);
+            let retryable = match class {
+                ErrorClass::Permanent => return Err(LsmIndexError::IoError(error)),
+                ErrorClass::NoSpace => self
+                    .observer
+                    .as_ref()
+                    .is_some_and(|observer| observer.on_no_space(operation)),
+                ErrorClass::Interrupted | ErrorClass::Transient => true,
+            };
+            if !retryable || attempts > self.max_retries {
+                return Err(LsmIndexError::Unrecoverable {
+                    operation: operation.to_string(),
+                    attempts,
+                    error,
+                });
+            }
+
+            let wait = if class == ErrorClass::Interrupted {
+                Duration::ZERO
+            } else {
+                backoff.min(self.max_backoff)
+            };
+            if let Some(observer) = &self.observer {
+                observer.on_retry(&RetryEvent {
+                    operation,
+                    attempt: attempts,
+                    class,
+                    error: &error,
+                    backoff: wait,
+                });
+            }
+            if !wait.is_zero() {
+                thread::sleep(wait);
+                backoff = backoff.saturating_mul(2);
+            }
+        }
+    }
+}
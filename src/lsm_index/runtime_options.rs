@@ -0,0 +1,211 @@
+use super::bloom_policy::BloomFprPolicy;
+use super::compaction_scheduler::CompactionScheduler;
+use super::options::LsmIndexOptions;
+use super::retry::RetryPolicy;
+use super::{LsmIndex, LsmIndexError, Result};
+use crate::sstable::Compression;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// One option of a live index to change with `LsmIndex::set_options`.
+///
+/// Only options that take effect without reopening are offered: the ones
+/// read afresh by each write, flush or compaction. Those baked into files or
+/// structures at open, such as the prefix extractor or data directories,
+/// are not.
+#[derive(Debug, Clone)]
+pub enum OptionChange {
+    /// Maximum size of a key in bytes accepted by `insert`
+    MaxKeySize(usize),
+    /// Maximum size of a value in bytes accepted by `insert`
+    MaxValueSize(usize),
+    /// Compression applied to values in SSTables written from now on
+    Compression(Compression),
+    /// False positive rate of the per-block filters of SSTables written
+    /// from now on; `None` stops giving blocks filters
+    BlockFilters(Option<f64>),
+    /// Levels whose SSTables written from now on get a hash index
+    HashIndexLevels(Vec<u32>),
+    /// Size in bytes at which SSTables written from now on close data blocks
+    BlockSize(usize),
+    /// Size of the index partitions of SSTables written from now on; `None`
+    /// stops partitioning their index
+    IndexBlockSize(Option<usize>),
+    /// Entries between restart points in SSTables written from now on;
+    /// `None` stops recording restart points
+    RestartInterval(Option<usize>),
+    /// Policy sizing the Bloom filters of SSTables written from now on;
+    /// `None` goes back to the rate passed to the constructor
+    BloomFprPolicy(Option<Arc<dyn BloomFprPolicy>>),
+    /// How long removed values are kept for `undelete`
+    SoftDeleteRetention(Option<Duration>),
+    /// How flushes, compactions and manifest updates retry
+    RetryPolicy(RetryPolicy),
+    /// Free space, in bytes, inserts and flushes leave untouched
+    ReservedHeadroom(u64),
+    /// Size below which SSTables are merged at recovery
+    StartupCompaction(Option<u64>),
+    /// Scheduler consulted before each compaction from now on
+    CompactionScheduler(Option<Arc<dyn CompactionScheduler>>),
+    /// Whether writes made without explicit `WriteOptions` sync the WAL
+    SyncWrites(bool),
+    /// Budget of the filter cache, which must be configured; shrinking it
+    /// evicts filters at once
+    FilterCacheCapacity(usize),
+}
+
+impl OptionChange {
+    /// Name of the option the change sets, as given in events
+    pub fn name(&self) -> &'static str {
+        match self {
+            OptionChange::MaxKeySize(_) => "max_key_size",
+            OptionChange::MaxValueSize(_) => "max_value_size",
+            OptionChange::Compression(_) => "compression",
+            OptionChange::BlockFilters(_) => "block_filter_fpr",
+            OptionChange::HashIndexLevels(_) => "hash_index_levels",
+            OptionChange::BlockSize(_) => "block_size",
+            OptionChange::IndexBlockSize(_) => "index_block_size",
+            OptionChange::RestartInterval(_) => "restart_interval",
+            OptionChange::BloomFprPolicy(_) => "bloom_fpr_policy",
+            OptionChange::SoftDeleteRetention(_) => "soft_delete_retention",
+            OptionChange::RetryPolicy(_) => "retry_policy",
+            OptionChange::ReservedHeadroom(_) => "reserved_headroom_bytes",
+            OptionChange::StartupCompaction(_) => "startup_compaction_bytes",
+            OptionChange::CompactionScheduler(_) => "compaction_scheduler",
+            OptionChange::SyncWrites(_) => "sync_writes",
+            OptionChange::FilterCacheCapacity(_) => "filter_cache_capacity",
+        }
+    }
+
+    /// The option's current setting in `options`, formatted for events
+    fn describe(&self, options: &LsmIndexOptions) -> String {
+        match self {
+            OptionChange::MaxKeySize(_) => format!("{}", options.max_key_size),
+            OptionChange::MaxValueSize(_) => format!("{}", options.max_value_size),
+            OptionChange::Compression(_) => format!("{:?}", options.compression),
+            OptionChange::BlockFilters(_) => format!("{:?}", options.block_filter_fpr),
+            OptionChange::HashIndexLevels(_) => format!("{:?}", options.hash_index_levels),
+            OptionChange::BlockSize(_) => format!("{}", options.block_size),
+            OptionChange::IndexBlockSize(_) => format!("{:?}", options.index_block_size),
+            OptionChange::RestartInterval(_) => format!("{:?}", options.restart_interval),
+            OptionChange::BloomFprPolicy(_) => format!("{:?}", options.bloom_fpr_policy),
+            OptionChange::SoftDeleteRetention(_) => format!("{:?}", options.soft_delete_retention),
+            OptionChange::RetryPolicy(_) => format!("{:?}", options.retry_policy),
+            OptionChange::ReservedHeadroom(_) => format!("{}", options.reserved_headroom_bytes),
+            OptionChange::StartupCompaction(_) => {
+                format!("{:?}", options.startup_compaction_bytes)
+            }
+            OptionChange::CompactionScheduler(_) => format!("{:?}", options.compaction_scheduler),
+            OptionChange::SyncWrites(_) => format!("{}", options.sync_writes),
+            OptionChange::FilterCacheCapacity(_) => format!(
+                "{:?}",
+                options
+                    .filter_cache
+                    .as_ref()
+                    .map(|cache| cache.capacity_bytes())
+            ),
+        }
+    }
+
+    /// Apply the change to `options`; the filter cache's budget is applied
+    /// separately, since the cache is shared rather than copied
+    fn apply(&self, options: &mut LsmIndexOptions) -> Result<()> {
+        match self.clone() {
+            OptionChange::MaxKeySize(size) => options.max_key_size = size,
+            OptionChange::MaxValueSize(size) => options.max_value_size = size,
+            OptionChange::Compression(compression) => options.compression = compression,
+            OptionChange::BlockFilters(rate) => options.block_filter_fpr = rate,
+            OptionChange::HashIndexLevels(levels) => options.hash_index_levels = levels,
+            OptionChange::BlockSize(size) => options.block_size = size,
+            OptionChange::IndexBlockSize(size) => options.index_block_size = size,
+            OptionChange::RestartInterval(interval) => options.restart_interval = interval,
+            OptionChange::BloomFprPolicy(policy) => options.bloom_fpr_policy = policy,
+            OptionChange::SoftDeleteRetention(retention) => {
+                options.soft_delete_retention = retention
+            }
+            OptionChange::RetryPolicy(policy) => options.retry_policy = policy,
+            OptionChange::ReservedHeadroom(bytes) => options.reserved_headroom_bytes = bytes,
+            OptionChange::StartupCompaction(bytes) => options.startup_compaction_bytes = bytes,
+            OptionChange::CompactionScheduler(scheduler) => {
+                options.compaction_scheduler = scheduler
+            }
+            OptionChange::SyncWrites(sync) => options.sync_writes = sync,
+            OptionChange::FilterCacheCapacity(_) => {
+                if options.filter_cache.is_none() {
+                    return Err(LsmIndexError::InvalidOperation(
+                        "filter_cache_capacity needs a filter cache configured at open".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An option changed on a live index
+#[derive(Debug)]
+pub struct OptionChangeEvent<'a> {
+    /// Name of the option, as returned by `OptionChange::name`
+    pub name: &'a str,
+    /// The setting before the change, formatted with `Debug`
+    pub previous: String,
+    /// The setting after the change, formatted with `Debug`
+    pub current: String,
+}
+
+/// Told about each option changed with `LsmIndex::set_options`
+pub trait OptionsObserver: Debug + Send + Sync {
+    /// Called once per change, after every change in the call is in effect
+    fn on_option_changed(&self, event: &OptionChangeEvent<'_>);
+}
+
+impl LsmIndex {
+    /// Adjust options of the live index without reopening it.
+    ///
+    /// The changes are validated together and applied all at once or not at
+    /// all: limits the on-disk format cannot honour are refused with
+    /// `IoError`, and a filter cache budget without a filter cache with
+    /// `InvalidOperation`. Operations already running finish under the
+    /// options they started with. The options observer, if any, is then
+    /// told about each change in order.
+    pub fn set_options(&self, changes: impl IntoIterator<Item = OptionChange>) -> Result<()> {
+        let changes: Vec<OptionChange> = changes.into_iter().collect();
+        let events = {
+            let mut current = self.checked_lock(self.options.write(), "options")?;
+            let mut updated = LsmIndexOptions::clone(&current);
+            let mut events = Vec::with_capacity(changes.len());
+            for change in &changes {
+                let previous = change.describe(&updated);
+                change.apply(&mut updated)?;
+                if let OptionChange::FilterCacheCapacity(capacity) = change {
+                    events.push((change.name(), previous, format!("{:?}", Some(capacity))));
+                } else {
+                    events.push((change.name(), previous, change.describe(&updated)));
+                }
+            }
+            updated.validate()?;
+
+            for change in &changes {
+                if let (OptionChange::FilterCacheCapacity(capacity), Some(cache)) =
+                    (change, &updated.filter_cache)
+                {
+                    cache.set_capacity(*capacity);
+                }
+            }
+            *current = Arc::new(updated);
+            events
+        };
+
+        if let Some(observer) = &self.options().options_observer {
+            for (name, previous, current) in events {
+                observer.on_option_changed(&OptionChangeEvent {
+                    name,
+                    previous,
+                    current,
+                });
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use super::{LsmIndex, LsmIndexError, Result, WriteOptions};
+use crossbeam_skiplist::SkipMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Commit sequence numbers handed out to writes, and the last one that
+/// changed each key
+#[derive(Debug)]
+pub(super) struct Sequences {
+    /// The last sequence issued when the index was opened. Keys not written
+    /// since are treated as changed at this point, since their history was
+    /// not kept.
+    opened_at: u64,
+    last: AtomicU64,
+    by_key: SkipMap<String, u64>,
+}
+
+impl Sequences {
+    /// Start from `now_ms` in microseconds, so sequences keep increasing
+    /// across restarts unless writes outpace one per microsecond
+    pub(super) fn new(now_ms: u64) -> Self {
+        let opened_at = now_ms.saturating_mul(1000);
+        Sequences {
+            opened_at,
+            last: AtomicU64::new(opened_at),
+            by_key: SkipMap::new(),
+        }
+    }
+}
+
+impl LsmIndex {
+    /// The sequence of the most recent write, usable as a token for
+    /// `WriteOptions::with_if_unchanged_since`
+    pub fn last_sequence(&self) -> u64 {
+        self.sequences.last.load(Ordering::SeqCst)
+    }
+
+    /// The sequence of the last write to `key`, inserting or removing it.
+    ///
+    /// Keys not written since the index was opened report the sequence it
+    /// was opened at, so tokens taken before a restart no longer match.
+    pub fn sequence_of(&self, key: &str) -> u64 {
+        self.sequences
+            .by_key
+            .get(key)
+            .map_or(self.sequences.opened_at, |entry| *entry.value())
+    }
+
+    /// Fail with `PreconditionFailed` if `key` changed after the sequence
+    /// the write is conditioned on. Called with the WAL lock held, so no
+    /// other write can slip in before the write commits.
+    pub(super) fn check_unchanged(&self, key: &str, write_options: &WriteOptions) -> Result<()> {
+        if let Some(since) = write_options.if_unchanged_since {
+            let sequence = self.sequence_of(key);
+            if sequence > since {
+                return Err(LsmIndexError::PreconditionFailed {
+                    key: key.to_string(),
+                    sequence,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail with `InvalidOperation` if a write that has no single key to
+    /// check is conditioned on one being unchanged
+    pub(super) fn refuse_precondition(
+        &self,
+        what: &str,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        if write_options.if_unchanged_since.is_some() {
+            return Err(LsmIndexError::InvalidOperation(format!(
+                "{} cannot be conditioned on a key being unchanged",
+                what
+            )));
+        }
+        Ok(())
+    }
+
+    /// The last sequence issued when the index was opened
+    pub(super) fn opened_sequence(&self) -> u64 {
+        self.sequences.opened_at
+    }
+
+    /// The sequence of the last write to `key` since the index was opened,
+    /// if it has been written since
+    pub(super) fn written_sequence(&self, key: &str) -> Option<u64> {
+        self.sequences.by_key.get(key).map(|entry| *entry.value())
+    }
+
+    /// Assign the next sequence to a write of `key`
+    pub(super) fn commit_sequence(&self, key: &str) -> u64 {
+        let sequence = self.next_sequence();
+        self.sequences.by_key.insert(key.to_string(), sequence);
+        sequence
+    }
+
+    /// Issue the next sequence without tying it to a key
+    pub(super) fn next_sequence(&self) -> u64 {
+        self.sequences.last.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Record that `key` was last changed at `sequence`, as by an ingested
+    /// file's global sequence
+    pub(super) fn record_sequence(&self, key: &str, sequence: u64) {
+        self.sequences.by_key.insert(key.to_string(), sequence);
+    }
+
+    /// Make sure sequences issued from now on follow `sequence`, one found
+    /// on disk that may be ahead of the clock the index was opened at
+    pub(super) fn observe_sequence(&self, sequence: u64) {
+        self.sequences.last.fetch_max(sequence, Ordering::SeqCst);
+    }
+}
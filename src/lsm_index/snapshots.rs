@@ -0,0 +1,110 @@
+use super::{LsmIndex, ReadOptions};
+use crossbeam_skiplist::SkipMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Snapshots and cursors open against an index, ordered by the commit
+/// sequence they were taken at.
+///
+/// Acquiring and releasing are lock-free, and the oldest entry is always at
+/// the front, so the minimum active sequence is read without a scan.
+#[derive(Debug, Default)]
+pub(super) struct SnapshotList {
+    /// Open snapshots keyed by sequence and a unique ID, holding their
+    /// read time in milliseconds
+    open: SkipMap<(u64, u64), u64>,
+    next_id: AtomicU64,
+}
+
+impl SnapshotList {
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    /// The sequence and read time of the oldest open snapshot
+    fn oldest(&self) -> Option<(u64, u64)> {
+        self.open
+            .front()
+            .map(|entry| (entry.key().0, *entry.value()))
+    }
+}
+
+/// A read point held open until dropped.
+///
+/// While any snapshot is held, compaction and soft-delete purging keep the
+/// versions and tombstones it can still see. Take one with
+/// `LsmIndex::acquire_snapshot` and read through it with `read_options`.
+pub struct Snapshot {
+    list: Arc<SnapshotList>,
+    sequence: u64,
+    id: u64,
+    at_ms: u64,
+}
+
+impl Snapshot {
+    /// The commit sequence of the last write visible to the snapshot
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// The time the snapshot was taken in milliseconds, usable as
+    /// `ReadOptions::snapshot`
+    pub fn at_ms(&self) -> u64 {
+        self.at_ms
+    }
+
+    /// Read options reading as of the snapshot
+    pub fn read_options(&self) -> ReadOptions {
+        ReadOptions::default().with_snapshot(self.at_ms)
+    }
+}
+
+impl fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Snapshot")
+            .field("sequence", &self.sequence)
+            .field("at_ms", &self.at_ms)
+            .finish()
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        self.list.open.remove(&(self.sequence, self.id));
+    }
+}
+
+impl LsmIndex {
+    /// Take a snapshot that stays registered until it is dropped, so
+    /// compaction respects it
+    pub fn acquire_snapshot(&self) -> Snapshot {
+        let list = self.snapshots.clone();
+        let id = list.next_id.fetch_add(1, Ordering::Relaxed);
+        let sequence = self.last_sequence();
+        let at_ms = self.now_ms();
+        list.open.insert((sequence, id), at_ms);
+        Snapshot {
+            list,
+            sequence,
+            id,
+            at_ms,
+        }
+    }
+
+    /// The sequence of the oldest snapshot or cursor still open, if any
+    pub fn min_active_sequence(&self) -> Option<u64> {
+        self.snapshots.oldest().map(|(sequence, _)| sequence)
+    }
+
+    /// The read time in milliseconds of the oldest snapshot or cursor still
+    /// open, if any; pass it to `CompactionOptions::with_oldest_snapshot`
+    pub fn min_active_snapshot(&self) -> Option<u64> {
+        self.snapshots.oldest().map(|(_, at_ms)| at_ms)
+    }
+
+    /// Number of snapshots and cursors currently open
+    pub fn active_snapshots(&self) -> usize {
+        self.snapshots.open.len()
+    }
+}
@@ -0,0 +1,93 @@
+use super::{LsmIndex, LsmIndexError, Result, WriteOptions};
+use crate::sstable::Tombstone;
+
+impl LsmIndex {
+    /// Restore a soft-deleted key to the value it held before it was removed.
+    ///
+    /// Returns false if the key was not soft-deleted or its retention period
+    /// has ended. Fails with `InvalidOperation` unless the index was created
+    /// with `LsmIndexOptions::with_soft_delete_retention`.
+    pub fn undelete(&self, key: &str) -> Result<bool> {
+        self.undelete_with_options(key, &self.default_write_options())
+    }
+
+    /// Restore a soft-deleted key, as `undelete` does, with explicit
+    /// durability settings and preconditions
+    pub fn undelete_with_options(&self, key: &str, write_options: &WriteOptions) -> Result<bool> {
+        let retention_ms = self.soft_delete_retention_ms().ok_or_else(|| {
+            LsmIndexError::InvalidOperation("Soft deletes are not enabled".to_string())
+        })?;
+
+        let tombstone = match self.deleted.get(key) {
+            Some(entry) => entry.value().clone(),
+            None => return Ok(false),
+        };
+        if tombstone.is_expired(retention_ms, self.now_ms()) {
+            self.deleted.remove(key);
+            return Ok(false);
+        }
+
+        match tombstone.value {
+            // Inserting the value again also drops the tombstone
+            Some(value) => {
+                self.insert_with_options(key.to_string(), value, write_options)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Keys that are soft-deleted and can still be undeleted, in key order
+    pub fn soft_deleted_keys(&self) -> Vec<String> {
+        let retention_ms = match self.soft_delete_retention_ms() {
+            Some(retention_ms) => retention_ms,
+            None => return Vec::new(),
+        };
+        let now_ms = self.now_ms();
+
+        self.deleted
+            .iter()
+            .filter(|entry| !entry.value().is_expired(retention_ms, now_ms))
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Forget soft-deleted values whose retention period has ended, returning
+    /// how many were dropped. Flushes call this before writing tombstones.
+    ///
+    /// Values deleted after the oldest open snapshot are kept, since the
+    /// snapshot may still read them.
+    pub fn purge_expired_deletions(&self) -> usize {
+        let retention_ms = self.soft_delete_retention_ms().unwrap_or(0);
+        let now_ms = self.now_ms();
+        let oldest_snapshot = self.min_active_snapshot();
+
+        let mut purged = 0;
+        for entry in self.deleted.iter() {
+            let tombstone = entry.value();
+            if tombstone.is_expired(retention_ms, now_ms)
+                && oldest_snapshot.is_none_or(|oldest| tombstone.deleted_at_ms < oldest)
+            {
+                entry.remove();
+                purged += 1;
+            }
+        }
+        purged
+    }
+
+    /// Track a tombstone read back from an SSTable, if it is still retained
+    pub(super) fn restore_soft_delete(&self, key: String, tombstone: Tombstone) {
+        if let Some(retention_ms) = self.soft_delete_retention_ms()
+            && tombstone.value.is_some()
+            && !tombstone.is_expired(retention_ms, self.now_ms())
+        {
+            self.deleted.insert(key, tombstone);
+        }
+    }
+
+    fn soft_delete_retention_ms(&self) -> Option<u64> {
+        self.options()
+            .soft_delete_retention
+            .map(|retention| retention.as_millis() as u64)
+    }
+}
@@ -0,0 +1,55 @@
+use super::gen_ref::{make_gen_ref, GenRefHandle};
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// An SSTable file whose lifetime is tied to the index entries pointing into it.
+///
+/// The index holds one handle per live file, and every index entry whose
+/// storage reference points into the file holds another. Retiring the file
+/// drops the index's handle and invalidates the rest, but the file itself is
+/// only deleted once the last entry referencing it lets go, so a reader that
+/// found a storage reference can always finish reading it.
+#[derive(Debug)]
+pub struct SSTableFile {
+    /// Path to the SSTable file
+    path: String,
+    /// Set once the file has left the live set and may be deleted
+    retired: AtomicBool,
+}
+
+/// Shared, generationally reference-counted handle to an SSTable file
+pub type SSTableFileRef = GenRefHandle<SSTableFile>;
+
+impl SSTableFile {
+    /// Create the first handle to a live SSTable file
+    pub fn new_ref(path: &str) -> SSTableFileRef {
+        make_gen_ref(SSTableFile {
+            path: path.to_string(),
+            retired: AtomicBool::new(false),
+        })
+    }
+
+    /// Path to the SSTable file
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns true once the file has been retired
+    pub fn is_retired(&self) -> bool {
+        self.retired.load(Ordering::Acquire)
+    }
+
+    /// Mark the file for deletion once its last handle is dropped
+    pub(crate) fn retire(&self) {
+        self.retired.store(true, Ordering::Release);
+    }
+}
+
+impl Drop for SSTableFile {
+    fn drop(&mut self) {
+        if self.is_retired() {
+            // Best effort: a file that is already gone needs no cleanup
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
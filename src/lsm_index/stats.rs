@@ -0,0 +1,435 @@
+use crossbeam_skiplist::SkipMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Number of levels statistics are tracked for; deeper levels share the last slot
+pub const MAX_LEVELS: usize = 7;
+
+/// Per-level counters gathered while the index runs
+#[derive(Debug)]
+pub(crate) struct LevelStats {
+    /// When collection started
+    started: Instant,
+    /// SSTable reads served by files on each level
+    reads: [AtomicU64; MAX_LEVELS],
+    /// Files retired from each level
+    retired_files: [AtomicU64; MAX_LEVELS],
+    /// Total lifetime of retired files on each level, in milliseconds
+    retired_lifetime_ms: [AtomicU64; MAX_LEVELS],
+    /// Gets answered by an entry in a file on each level
+    gets: [AtomicU64; MAX_LEVELS],
+    /// Total latency of those gets, in nanoseconds
+    get_latency_ns: [AtomicU64; MAX_LEVELS],
+    /// Slowest of those gets, in nanoseconds
+    max_get_latency_ns: [AtomicU64; MAX_LEVELS],
+    /// Probes of files on each level that found their key
+    hits: [AtomicU64; MAX_LEVELS],
+    /// Probes of files on each level rejected by their Bloom filter
+    bloom_negatives: [AtomicU64; MAX_LEVELS],
+    /// Probes of files on each level that passed the Bloom filter but missed
+    false_positives: [AtomicU64; MAX_LEVELS],
+}
+
+impl LevelStats {
+    pub(crate) fn new() -> Self {
+        LevelStats {
+            started: Instant::now(),
+            reads: Default::default(),
+            retired_files: Default::default(),
+            retired_lifetime_ms: Default::default(),
+            gets: Default::default(),
+            get_latency_ns: Default::default(),
+            max_get_latency_ns: Default::default(),
+            hits: Default::default(),
+            bloom_negatives: Default::default(),
+            false_positives: Default::default(),
+        }
+    }
+
+    /// Map a level onto its statistics slot
+    fn slot(level: u32) -> usize {
+        (level as usize).min(MAX_LEVELS - 1)
+    }
+
+    /// Count a read served by a file on the given level
+    pub(crate) fn record_read(&self, level: u32) {
+        self.reads[Self::slot(level)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a file leaving the given level after living for `lifetime`
+    pub(crate) fn record_retirement(&self, level: u32, lifetime: Duration) {
+        let slot = Self::slot(level);
+        self.retired_files[slot].fetch_add(1, Ordering::Relaxed);
+        self.retired_lifetime_ms[slot].fetch_add(lifetime.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Count a get answered from a file on the given level in `latency`
+    pub(crate) fn record_get(&self, level: u32, latency: Duration) {
+        let slot = Self::slot(level);
+        let latency_ns = latency.as_nanos().min(u128::from(u64::MAX)) as u64;
+        self.gets[slot].fetch_add(1, Ordering::Relaxed);
+        self.get_latency_ns[slot].fetch_add(latency_ns, Ordering::Relaxed);
+        self.max_get_latency_ns[slot].fetch_max(latency_ns, Ordering::Relaxed);
+    }
+
+    /// Count one probe of a file on the given level
+    pub(crate) fn record_probe(&self, level: u32, outcome: ProbeOutcome) {
+        let counter = match outcome {
+            ProbeOutcome::Hit => &self.hits,
+            ProbeOutcome::BloomNegative => &self.bloom_negatives,
+            ProbeOutcome::FalsePositive => &self.false_positives,
+        };
+        counter[Self::slot(level)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Read-path counters for the given level
+    pub(crate) fn read_stats(&self, level: u32) -> LevelReadStats {
+        let slot = Self::slot(level);
+        LevelReadStats {
+            level,
+            gets: self.gets[slot].load(Ordering::Relaxed),
+            total_get_latency: Duration::from_nanos(
+                self.get_latency_ns[slot].load(Ordering::Relaxed),
+            ),
+            max_get_latency: Duration::from_nanos(
+                self.max_get_latency_ns[slot].load(Ordering::Relaxed),
+            ),
+            block_reads: self.reads[slot].load(Ordering::Relaxed),
+            hits: self.hits[slot].load(Ordering::Relaxed),
+            bloom_negatives: self.bloom_negatives[slot].load(Ordering::Relaxed),
+            false_positives: self.false_positives[slot].load(Ordering::Relaxed),
+        }
+    }
+
+    /// Total reads served by files on the given level
+    pub(crate) fn reads(&self, level: u32) -> u64 {
+        self.reads[Self::slot(level)].load(Ordering::Relaxed)
+    }
+
+    /// Average read rate against the given level since collection started
+    pub(crate) fn reads_per_sec(&self, level: u32) -> f64 {
+        let elapsed = self.started.elapsed().as_secs_f64().max(1.0);
+        self.reads(level) as f64 / elapsed
+    }
+
+    /// Average lifetime of files retired from the given level
+    pub(crate) fn average_lifetime(&self, level: u32) -> Option<Duration> {
+        let slot = Self::slot(level);
+        let files = self.retired_files[slot].load(Ordering::Relaxed);
+        if files == 0 {
+            return None;
+        }
+        let total_ms = self.retired_lifetime_ms[slot].load(Ordering::Relaxed);
+        Some(Duration::from_millis(total_ms / files))
+    }
+}
+
+/// How reads against the SSTables on one level have fared since the index
+/// was opened
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LevelReadStats {
+    /// Level the counters belong to; the deepest tracked level also counts
+    /// every level below it
+    pub level: u32,
+    /// Gets answered by an entry in a file on the level, whether its value
+    /// was held in memory or read from disk
+    pub gets: u64,
+    /// Total time those gets took
+    pub total_get_latency: Duration,
+    /// Time the slowest of those gets took
+    pub max_get_latency: Duration,
+    /// Entries read from files on the level
+    pub block_reads: u64,
+    /// Probes of files on the level that found their key
+    pub hits: u64,
+    /// Probes of files on the level rejected by their Bloom filter
+    pub bloom_negatives: u64,
+    /// Probes of files on the level that passed the Bloom filter but missed
+    pub false_positives: u64,
+}
+
+impl LevelReadStats {
+    /// Mean time of the gets answered from the level, `None` before any
+    pub fn average_get_latency(&self) -> Option<Duration> {
+        if self.gets == 0 {
+            return None;
+        }
+        let average_ns = self.total_get_latency.as_nanos() / u128::from(self.gets);
+        Some(Duration::from_nanos(average_ns as u64))
+    }
+
+    /// Fraction of probes that passed the Bloom filter yet missed, out of
+    /// all probes the filter did not reject
+    pub fn false_positive_rate(&self) -> f64 {
+        let passed = self.hits + self.false_positives;
+        if passed == 0 {
+            return 0.0;
+        }
+        self.false_positives as f64 / passed as f64
+    }
+
+    /// Whether any read has touched the level
+    pub fn is_empty(&self) -> bool {
+        self.gets == 0
+            && self.block_reads == 0
+            && self.hits == 0
+            && self.bloom_negatives == 0
+            && self.false_positives == 0
+    }
+}
+
+/// How much data the SSTables on one level hold, before and after encoding
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LevelStorageStats {
+    /// Level the files belong to
+    pub level: u32,
+    /// Number of live SSTables on the level
+    pub file_count: u64,
+    /// Entries across those files
+    pub entry_count: u64,
+    /// Total size of their keys and values as written
+    pub raw_bytes: u64,
+    /// Total on-disk size of their data sections
+    pub data_bytes: u64,
+    /// Total size of the files, including filters and metadata
+    pub size_bytes: u64,
+}
+
+impl LevelStorageStats {
+    /// Raw key and value bytes per on-disk data byte; 1.0 for an empty level
+    pub fn compression_ratio(&self) -> f64 {
+        if self.data_bytes == 0 {
+            return 1.0;
+        }
+        self.raw_bytes as f64 / self.data_bytes as f64
+    }
+}
+
+/// File handles, memory and background work held by an index
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// File descriptors held open: one per open SSTable reader, at most
+    /// `max_open_files` of them, plus the WAL
+    pub open_files: usize,
+    /// Bytes of files mapped into memory; SSTables are read through buffered
+    /// file handles, so this is currently always 0
+    pub mmapped_bytes: u64,
+    /// Estimated heap bytes of the SSTable reader cache, covering read
+    /// buffers, Bloom filters and per-file metadata, plus the filter cache's
+    /// resident filters, which may be shared with other indexes
+    pub cache_bytes: u64,
+    /// Bytes of keys and values in the memtable
+    pub memtable_bytes: u64,
+    /// Bytes of keys and values the index holds in memory
+    pub index_bytes: u64,
+    /// Background tasks, such as TTL sweepers, running against the index
+    pub background_tasks: usize,
+    /// SSTable readers closed to stay within `max_open_files`
+    pub reader_evictions: u64,
+    /// Closed SSTable readers reopened because a lookup needed them; a
+    /// count growing about as fast as lookups means the limit is too low
+    pub reader_reopens: u64,
+}
+
+/// Counts a background task as running for as long as it is held
+#[derive(Debug)]
+pub(crate) struct BackgroundTaskGuard {
+    running: Arc<AtomicUsize>,
+}
+
+impl BackgroundTaskGuard {
+    pub(crate) fn new(running: Arc<AtomicUsize>) -> Self {
+        running.fetch_add(1, Ordering::Relaxed);
+        BackgroundTaskGuard { running }
+    }
+}
+
+impl Drop for BackgroundTaskGuard {
+    fn drop(&mut self) {
+        self.running.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// How often sampled reads fell within an SSTable's key range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHotness {
+    /// Path to the SSTable file
+    pub path: String,
+    /// Sampled reads whose key the file's range covers
+    pub sampled_reads: u64,
+}
+
+/// Counts one in every `every` reads against the SSTables that cover the
+/// key read, so files that keep adding read amplification stand out
+#[derive(Debug)]
+pub(crate) struct ReadSampler {
+    every: u64,
+    reads: AtomicU64,
+    files: SkipMap<String, AtomicU64>,
+}
+
+impl ReadSampler {
+    pub(crate) fn new(every: u32) -> Self {
+        ReadSampler {
+            every: u64::from(every.max(1)),
+            reads: AtomicU64::new(0),
+            files: SkipMap::new(),
+        }
+    }
+
+    /// Count a read, returning true if it is one to sample
+    pub(crate) fn should_sample(&self) -> bool {
+        self.reads
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(self.every)
+    }
+
+    /// Count a sampled read against the file at `path`
+    pub(crate) fn record(&self, path: &str) {
+        self.files
+            .get_or_insert_with(path.to_string(), || AtomicU64::new(0))
+            .value()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Sampled reads of the file at `path`
+    pub(crate) fn get(&self, path: &str) -> u64 {
+        self.files
+            .get(path)
+            .map_or(0, |entry| entry.value().load(Ordering::Relaxed))
+    }
+
+    /// Forget a file that has left the live set
+    pub(crate) fn forget(&self, path: &str) {
+        self.files.remove(path);
+    }
+
+    /// Sampled reads of every file read so far, hottest first
+    pub(crate) fn all(&self) -> Vec<FileHotness> {
+        let mut files: Vec<FileHotness> = self
+            .files
+            .iter()
+            .map(|entry| FileHotness {
+                path: entry.key().clone(),
+                sampled_reads: entry.value().load(Ordering::Relaxed),
+            })
+            .collect();
+        files.sort_by(|a, b| {
+            b.sampled_reads
+                .cmp(&a.sampled_reads)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        files
+    }
+}
+
+/// Result of probing one SSTable for a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ProbeOutcome {
+    /// The key was found in the file
+    Hit,
+    /// The Bloom filter ruled the key out without touching the file
+    BloomNegative,
+    /// The Bloom filter passed but the file did not hold the key
+    FalsePositive,
+}
+
+/// Access counters for one SSTable
+#[derive(Debug, Default)]
+struct FileCounters {
+    probes: AtomicU64,
+    hits: AtomicU64,
+    bloom_negatives: AtomicU64,
+    false_positives: AtomicU64,
+}
+
+/// Snapshot of how often an SSTable has been probed and with what result
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SSTableAccessStats {
+    /// Path to the SSTable file
+    pub path: String,
+    /// Lookups that consulted this file
+    pub probes: u64,
+    /// Lookups that found their key in this file
+    pub hits: u64,
+    /// Lookups rejected by the file's Bloom filter
+    pub bloom_negatives: u64,
+    /// Lookups that passed the Bloom filter but missed in the file
+    pub false_positives: u64,
+}
+
+impl SSTableAccessStats {
+    /// Fraction of probes that did not find their key
+    pub fn negative_rate(&self) -> f64 {
+        if self.probes == 0 {
+            return 0.0;
+        }
+        (self.probes - self.hits) as f64 / self.probes as f64
+    }
+
+    /// Estimated chance that the next probe finds its key, smoothed so that
+    /// files with no history start at one half
+    pub fn expected_hit_rate(&self) -> f64 {
+        (self.hits as f64 + 1.0) / (self.probes as f64 + 2.0)
+    }
+}
+
+/// Per-SSTable access counters, keyed by path
+#[derive(Debug, Default)]
+pub(crate) struct FileAccessStats {
+    files: SkipMap<String, FileCounters>,
+}
+
+impl FileAccessStats {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count one probe of the file at `path`
+    pub(crate) fn record(&self, path: &str, outcome: ProbeOutcome) {
+        let entry = self
+            .files
+            .get_or_insert_with(path.to_string(), FileCounters::default);
+        let counters = entry.value();
+        counters.probes.fetch_add(1, Ordering::Relaxed);
+        let counter = match outcome {
+            ProbeOutcome::Hit => &counters.hits,
+            ProbeOutcome::BloomNegative => &counters.bloom_negatives,
+            ProbeOutcome::FalsePositive => &counters.false_positives,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counters for the file at `path`, zero if it has never been probed
+    pub(crate) fn get(&self, path: &str) -> SSTableAccessStats {
+        match self.files.get(path) {
+            Some(entry) => Self::snapshot(entry.key(), entry.value()),
+            None => SSTableAccessStats {
+                path: path.to_string(),
+                probes: 0,
+                hits: 0,
+                bloom_negatives: 0,
+                false_positives: 0,
+            },
+        }
+    }
+
+    /// Counters for every file probed so far, in path order
+    pub(crate) fn all(&self) -> Vec<SSTableAccessStats> {
+        self.files
+            .iter()
+            .map(|entry| Self::snapshot(entry.key(), entry.value()))
+            .collect()
+    }
+
+    fn snapshot(path: &str, counters: &FileCounters) -> SSTableAccessStats {
+        SSTableAccessStats {
+            path: path.to_string(),
+            probes: counters.probes.load(Ordering::Relaxed),
+            hits: counters.hits.load(Ordering::Relaxed),
+            bloom_negatives: counters.bloom_negatives.load(Ordering::Relaxed),
+            false_positives: counters.false_positives.load(Ordering::Relaxed),
+        }
+    }
+}
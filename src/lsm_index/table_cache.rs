@@ -0,0 +1,179 @@
+use super::locks::recover;
+use super::SSTableReader;
+use crate::sstable::{FilterCache, KeyProvider};
+use crossbeam_skiplist::map::Entry;
+use crossbeam_skiplist::SkipMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Open SSTable readers, keyed by path, holding at most `max_open_files`
+/// file handles.
+///
+/// Past the limit the least recently used readers are closed, keeping the
+/// file's level, entry count and digest, and are reopened the next time
+/// they are looked up.
+pub(super) struct TableCache {
+    readers: SkipMap<String, SSTableReader>,
+    max_open_files: Option<usize>,
+    filter_cache: Option<Arc<FilterCache>>,
+    /// Provider of the keys encrypted files are reopened with
+    key_provider: Option<Arc<dyn KeyProvider>>,
+    /// Incremented on each lookup, to order readers by last use
+    clock: AtomicU64,
+    evictions: AtomicU64,
+    reopens: AtomicU64,
+    /// Held while closing, reopening or removing readers, so a reader is
+    /// never reopened after its file has been retired
+    churn: Mutex<()>,
+}
+
+impl TableCache {
+    pub(super) fn new(
+        max_open_files: Option<usize>,
+        filter_cache: Option<Arc<FilterCache>>,
+        key_provider: Option<Arc<dyn KeyProvider>>,
+    ) -> Self {
+        TableCache {
+            readers: SkipMap::new(),
+            max_open_files,
+            filter_cache,
+            key_provider,
+            clock: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            reopens: AtomicU64::new(0),
+            churn: Mutex::new(()),
+        }
+    }
+
+    /// The reader for `path`, reopening its file if it was closed
+    pub(super) fn get(&self, path: &str) -> Option<Entry<'_, String, SSTableReader>> {
+        let entry = self.readers.get(path)?;
+        if entry.value().holds_file() {
+            entry.value().touch(self.tick());
+            return Some(entry);
+        }
+
+        let _churn = recover(self.churn.lock());
+        let entry = self.readers.get(path)?;
+        if entry.value().holds_file() {
+            entry.value().touch(self.tick());
+            return Some(entry);
+        }
+        // A file that cannot be reopened is served as the closed reader,
+        // whose answers stay conservative
+        let Ok(reader) = SSTableReader::open_with(
+            path,
+            entry.value().level(),
+            self.filter_cache.as_ref(),
+            self.key_provider.as_deref(),
+        ) else {
+            return Some(entry);
+        };
+        self.reopens.fetch_add(1, Ordering::Relaxed);
+        reader.touch(self.tick());
+        self.readers.insert(path.to_string(), reader);
+        self.enforce_limit(path);
+        self.readers.get(path)
+    }
+
+    /// The reader for `path` as cached, without reopening it, for what a
+    /// closed reader still knows such as its level and value decoder
+    pub(super) fn peek(&self, path: &str) -> Option<Entry<'_, String, SSTableReader>> {
+        self.readers.get(path)
+    }
+
+    /// Cache `reader` for `path`, replacing any reader it had
+    pub(super) fn insert(&self, path: String, reader: SSTableReader) {
+        let _churn = recover(self.churn.lock());
+        reader.touch(self.tick());
+        self.readers.insert(path.clone(), reader);
+        self.enforce_limit(&path);
+    }
+
+    /// Drop the reader for `path`, closing its file
+    pub(super) fn remove(&self, path: &str) {
+        let _churn = recover(self.churn.lock());
+        self.readers.remove(path);
+    }
+
+    /// Every cached reader, open or closed, in path order
+    pub(super) fn iter(&self) -> impl Iterator<Item = Entry<'_, String, SSTableReader>> {
+        self.readers.iter()
+    }
+
+    /// Paths of every cached reader
+    pub(super) fn paths(&self) -> Vec<String> {
+        self.readers
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Readers closed to stay within the limit
+    pub(super) fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+
+    /// Closed readers reopened because they were looked up
+    pub(super) fn reopens(&self) -> u64 {
+        self.reopens.load(Ordering::Relaxed)
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Close the least recently used readers, other than the one for `keep`,
+    /// until no more than `max_open_files` hold their file. Called with
+    /// `churn` held.
+    fn enforce_limit(&self, keep: &str) {
+        let Some(max_open_files) = self.max_open_files else {
+            return;
+        };
+        let mut open: Vec<(u64, String)> = self
+            .readers
+            .iter()
+            .filter(|entry| entry.value().holds_file() && entry.key() != keep)
+            .map(|entry| (entry.value().last_used(), entry.key().clone()))
+            .collect();
+        // The reader being kept counts towards the limit
+        let excess = (open.len() + 1).saturating_sub(max_open_files.max(1));
+        open.sort();
+        for (_, path) in open.into_iter().take(excess) {
+            if let Some(entry) = self.readers.get(&path) {
+                self.readers.insert(path, entry.value().closed());
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl SSTableReader {
+    /// Record a lookup at `tick`
+    fn touch(&self, tick: u64) {
+        self.last_used.fetch_max(tick, Ordering::Relaxed);
+    }
+
+    /// Tick of the last lookup
+    fn last_used(&self) -> u64 {
+        self.last_used.load(Ordering::Relaxed)
+    }
+
+    /// A copy of the reader that has let go of its file, keeping what it
+    /// learned when opened
+    fn closed(&self) -> Self {
+        SSTableReader {
+            file_path: self.file_path.clone(),
+            reader: None,
+            entry_count: self.entry_count,
+            has_bloom_filter: self.has_bloom_filter,
+            content_digest: self.content_digest,
+            level: self.level,
+            decoder: self.decoder.clone(),
+            key_expander: self.key_expander.clone(),
+            min_expiry_ms: self.min_expiry_ms,
+            value_log_segments: self.value_log_segments.clone(),
+            last_used: AtomicU64::new(self.last_used()),
+        }
+    }
+}
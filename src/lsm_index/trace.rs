@@ -0,0 +1,115 @@
+use super::LsmIndex;
+use super::locks::recover;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Most trace IDs remembered for the memtable or for one file; IDs seen
+/// once the limit is reached are not recorded
+pub const MAX_TRACE_IDS: usize = 64;
+
+/// Trace IDs of writes, carried from the memtable to the file a flush
+/// writes it to and on to the files compactions merge that file into, so
+/// background work can be tied back to the requests that caused it.
+///
+/// Kept in memory only: memtable entries replayed from the WAL and files
+/// written before the index was opened carry no IDs.
+#[derive(Debug, Default)]
+pub(super) struct Traces {
+    /// IDs of writes held in the memtable, in the order first seen
+    memtable: Mutex<Vec<String>>,
+    /// IDs carried by each live file written since the index was opened
+    files: Mutex<HashMap<String, Vec<String>>>,
+}
+
+/// Add the IDs in `ids` that `into` lacks, up to `MAX_TRACE_IDS`
+fn merge(into: &mut Vec<String>, ids: impl IntoIterator<Item = String>) {
+    for id in ids {
+        if into.len() >= MAX_TRACE_IDS {
+            break;
+        }
+        if !into.contains(&id) {
+            into.push(id);
+        }
+    }
+}
+
+impl Traces {
+    /// Remember that a write traced as `trace_id` reached the memtable
+    pub(super) fn record_write(&self, trace_id: &str) {
+        let mut memtable = recover(self.memtable.lock());
+        if memtable.len() < MAX_TRACE_IDS && !memtable.iter().any(|id| id == trace_id) {
+            memtable.push(trace_id.to_string());
+        }
+    }
+
+    /// Move the memtable's IDs to the file a flush wrote it to, returning them
+    pub(super) fn flushed(&self, path: &str) -> Vec<String> {
+        let ids = std::mem::take(&mut *recover(self.memtable.lock()));
+        if !ids.is_empty() {
+            recover(self.files.lock()).insert(path.to_string(), ids.clone());
+        }
+        ids
+    }
+
+    /// Move the IDs of `inputs` to the file a compaction merged them into,
+    /// returning them
+    pub(super) fn compacted(&self, inputs: &[String], output: &str) -> Vec<String> {
+        let mut files = recover(self.files.lock());
+        let mut ids = Vec::new();
+        for input in inputs {
+            if let Some(input_ids) = files.remove(input) {
+                merge(&mut ids, input_ids);
+            }
+        }
+        if !ids.is_empty() {
+            files.insert(output.to_string(), ids.clone());
+        }
+        ids
+    }
+
+    /// IDs carried by the file at `path`
+    pub(super) fn of_file(&self, path: &str) -> Vec<String> {
+        recover(self.files.lock())
+            .get(path)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Stop tracking a file that left the live set
+    pub(super) fn forget(&self, path: &str) {
+        recover(self.files.lock()).remove(path);
+    }
+}
+
+/// Trace IDs as appended to a log message: ` [trace IDs: a, b]`, or nothing
+/// if there are none
+pub(super) fn describe(trace_ids: &[String]) -> String {
+    if trace_ids.is_empty() {
+        String::new()
+    } else {
+        format!(" [trace IDs: {}]", trace_ids.join(", "))
+    }
+}
+
+impl LsmIndex {
+    /// Trace IDs of the writes held in the live SSTable at `path`, as given
+    /// in `WriteOptions::trace_id`, whether it was flushed from them or
+    /// compacted from files that were. At most `MAX_TRACE_IDS` are kept per
+    /// file, and only for files written since the index was opened.
+    pub fn file_trace_ids(&self, path: &str) -> Vec<String> {
+        self.traces.of_file(path)
+    }
+
+    /// Log `operation` as slow if it took at least
+    /// `LsmIndexOptions::slow_op_threshold`, with the trace IDs it carried
+    pub(super) fn log_if_slow(&self, operation: &str, elapsed: Duration, trace_ids: &[String]) {
+        if self
+            .options()
+            .slow_op_threshold
+            .is_some_and(|threshold| elapsed >= threshold)
+        {
+            warn!("Slow {} took {:?}{}", operation, elapsed, describe(trace_ids));
+        }
+    }
+}
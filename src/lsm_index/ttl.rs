@@ -0,0 +1,155 @@
+use super::{LsmIndex, Result, WriteOptions};
+use crate::memtable::Memtable;
+use crate::wal::durability::Operation;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::interval;
+
+impl LsmIndex {
+    /// Insert a key-value pair that expires after `ttl`.
+    ///
+    /// Expired keys read as missing straight away; `sweep_expired` or a
+    /// `TtlSweeper` removes them from the index for good.
+    pub fn insert_with_ttl(&self, key: String, value: Vec<u8>, ttl: Duration) -> Result<()> {
+        self.insert_with_ttl_and_options(key, value, ttl, &self.default_write_options())?;
+        Ok(())
+    }
+
+    /// Insert a key-value pair that expires after `ttl`, with explicit
+    /// durability settings and preconditions, returning the write's commit
+    /// sequence
+    pub fn insert_with_ttl_and_options(
+        &self,
+        key: String,
+        value: Vec<u8>,
+        ttl: Duration,
+        write_options: &WriteOptions,
+    ) -> Result<u64> {
+        let expires_at_ms = self.now_ms().saturating_add(ttl.as_millis() as u64);
+        self.insert_entry(key, value, Some(expires_at_ms), write_options)
+    }
+
+    /// Check if the live entry for `key` has expired
+    pub(super) fn is_expired(&self, key: &str) -> bool {
+        self.index
+            .get(key)
+            .is_some_and(|entry| entry.value().is_expired_at(self.now_ms()))
+    }
+
+    /// Delete every expired key, returning how many were deleted.
+    ///
+    /// Candidates come from the keys written with a TTL that have fallen
+    /// due and from SSTables whose earliest expiry has passed, so neither
+    /// the memtable nor files without expiring entries are scanned. The
+    /// deletions are logged as one batch. Compaction later turns the
+    /// expired entries on disk into tombstones.
+    pub fn sweep_expired(&self) -> Result<usize> {
+        let now_ms = self.now_ms();
+
+        let mut candidates: BTreeSet<String> = {
+            let mut queue = self.checked_lock(self.ttl_queue.lock(), "TTL queue")?;
+            let pending = queue.split_off(&(now_ms.saturating_add(1), String::new()));
+            std::mem::replace(&mut *queue, pending)
+                .into_iter()
+                .map(|(_, key)| key)
+                .collect()
+        };
+        // Closed readers are only reopened if they hold expired entries
+        for path in self.sstable_readers.paths() {
+            let expiring = self
+                .sstable_readers
+                .peek(&path)
+                .and_then(|reader| reader.value().min_expiry_ms())
+                .is_some_and(|min| min <= now_ms);
+            if !expiring {
+                continue;
+            }
+            if let Some(reader) = self.sstable_readers.get(&path) {
+                candidates.extend(reader.value().expired_keys(now_ms));
+            }
+        }
+
+        // Holding the WAL lock keeps a concurrent write from being deleted
+        let mut durability_manager = self.lock_wal()?;
+        let expired: Vec<String> = candidates
+            .into_iter()
+            .filter(|key| {
+                self.index
+                    .get(key)
+                    .is_some_and(|entry| entry.value().is_expired_at(now_ms))
+            })
+            .collect();
+        durability_manager.execute_batch_with_sync(
+            expired
+                .iter()
+                .map(|key| Operation::Remove { key: key.clone() })
+                .collect(),
+            self.options().sync_writes,
+        )?;
+        for key in &expired {
+            self.memtable.remove(key)?;
+            self.index.remove(key);
+        }
+        Ok(expired.len())
+    }
+}
+
+/// Background task that periodically deletes expired keys from an index.
+///
+/// The task stops when the sweeper is stopped or dropped.
+pub struct TtlSweeper {
+    shutdown: Option<oneshot::Sender<()>>,
+    task: JoinHandle<()>,
+}
+
+impl TtlSweeper {
+    /// Start sweeping `index` every `period` on the current Tokio runtime
+    pub fn spawn(index: Arc<LsmIndex>, period: Duration) -> Self {
+        let (shutdown, mut shutdown_rx) = oneshot::channel();
+
+        let running = index.track_background_task();
+        let task = tokio::spawn(async move {
+            let _running = running;
+            let mut ticker = interval(period);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        // A sweep does blocking I/O, so keep it off the
+                        // runtime's worker threads
+                        let index = index.clone();
+                        match tokio::task::spawn_blocking(move || index.sweep_expired()).await {
+                            Ok(Ok(_)) => {}
+                            Ok(Err(e)) => warn!("TTL sweep failed: {:?}", e),
+                            Err(e) => warn!("TTL sweep task failed: {:?}", e),
+                        }
+                    }
+                    _ = &mut shutdown_rx => break,
+                }
+            }
+        });
+
+        TtlSweeper {
+            shutdown: Some(shutdown),
+            task,
+        }
+    }
+
+    /// Stop the sweeper and wait for the current sweep to finish
+    pub async fn stop(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for TtlSweeper {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use super::{GenIndexEntry, LsmIndex, LsmIndexError, Result, StoredEntry};
+use crate::bptree::StorageReference;
+use crate::checked_len;
+use crate::memtable::{Memtable, MemtableError};
+use crate::sstable::ChecksumKind;
+use crate::wal::durability::Operation;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Extension of value log segment files
+pub const VALUE_LOG_EXTENSION: &str = "vlog";
+
+/// Bytes every segment starts with
+const SEGMENT_MAGIC: &[u8; 8] = b"LSMVLOG1";
+
+/// Size of an encoded `ValuePointer`
+pub(crate) const POINTER_SIZE: usize = 20;
+
+/// Where a value separated from its SSTable entry lies in the value log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ValuePointer {
+    /// Segment holding the value, numbered after the flush that wrote it
+    pub(crate) segment: u64,
+    /// Offset of the value's record in the segment
+    pub(crate) offset: u64,
+    /// Length of the value
+    pub(crate) len: u32,
+}
+
+impl ValuePointer {
+    /// Encode the pointer as stored in place of the value in an SSTable
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(POINTER_SIZE);
+        buf.extend_from_slice(&self.segment.to_le_bytes());
+        buf.extend_from_slice(&self.offset.to_le_bytes());
+        buf.extend_from_slice(&self.len.to_le_bytes());
+        buf
+    }
+
+    /// Decode a pointer written by `encode`
+    pub(crate) fn decode(buf: &[u8]) -> io::Result<Self> {
+        if buf.len() != POINTER_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Value log pointer of {} bytes", buf.len()),
+            ));
+        }
+        Ok(ValuePointer {
+            segment: u64::from_le_bytes(buf[0..8].try_into().unwrap()),
+            offset: u64::from_le_bytes(buf[8..16].try_into().unwrap()),
+            len: u32::from_le_bytes(buf[16..20].try_into().unwrap()),
+        })
+    }
+}
+
+/// What a pass of `LsmIndex::collect_value_log_garbage` did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValueLogGcSummary {
+    /// Segments no SSTable pointed into any more, deleted
+    pub segments_deleted: usize,
+    /// Bytes freed by deleting them
+    pub bytes_reclaimed: u64,
+    /// Live values moved out of mostly dead segments into the memtable,
+    /// and from there into a new segment
+    pub values_relocated: usize,
+}
+
+/// Path of value log segment `segment` in `dir`
+pub(crate) fn segment_path(dir: &str, segment: u64) -> PathBuf {
+    Path::new(dir).join(format!("{:06}.{}", segment, VALUE_LOG_EXTENSION))
+}
+
+/// Every value log segment in `dir`, by segment number
+pub(crate) fn segment_files(dir: &str) -> io::Result<Vec<(u64, PathBuf)>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_none_or(|extension| extension != VALUE_LOG_EXTENSION)
+        {
+            continue;
+        }
+        let number = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok());
+        if let Some(number) = number
+            && path.is_file()
+        {
+            segments.push((number, path));
+        }
+    }
+    segments.sort();
+    Ok(segments)
+}
+
+/// Appends values to a new segment. Each record is laid out like an SSTable
+/// entry: key length, key, value length, value and a CRC32 over them.
+pub(crate) struct SegmentWriter {
+    file: BufWriter<File>,
+    segment: u64,
+    offset: u64,
+}
+
+impl SegmentWriter {
+    /// Create segment `segment` in `dir`, replacing any left by an earlier
+    /// attempt
+    pub(crate) fn create(dir: &str, segment: u64) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(segment_path(dir, segment))?);
+        file.write_all(SEGMENT_MAGIC)?;
+        Ok(SegmentWriter {
+            file,
+            segment,
+            offset: SEGMENT_MAGIC.len() as u64,
+        })
+    }
+
+    /// Append a value, returning the pointer to store in its place
+    pub(crate) fn append(&mut self, key: &str, value: &[u8]) -> io::Result<ValuePointer> {
+        let pointer = ValuePointer {
+            segment: self.segment,
+            offset: self.offset,
+            len: value.len() as u32,
+        };
+        self.file.write_all(&(key.len() as u32).to_le_bytes())?;
+        self.file.write_all(key.as_bytes())?;
+        self.file.write_all(&(value.len() as u32).to_le_bytes())?;
+        self.file.write_all(value)?;
+        self.file
+            .write_all(&ChecksumKind::Crc32.entry_checksum(key, value).to_le_bytes())?;
+        self.offset += 12 + key.len() as u64 + value.len() as u64;
+        Ok(pointer)
+    }
+
+    /// Write out and sync the segment, which must happen before any SSTable
+    /// pointing into it is written
+    pub(crate) fn finish(self) -> io::Result<()> {
+        self.file.into_inner()?.sync_all()
+    }
+}
+
+/// Read the value of `key` that `pointer` locates in the value log in `dir`,
+/// checking the record belongs to the key and its checksum
+pub(crate) fn read_value(dir: &str, key: &str, pointer: ValuePointer) -> io::Result<Vec<u8>> {
+    let path = segment_path(dir, pointer.segment);
+    let mut reader = BufReader::new(File::open(&path)?);
+    let file_size = reader.get_ref().metadata()?.len();
+    reader.seek(SeekFrom::Start(pointer.offset))?;
+
+    let key_len =
+        checked_len::read_len(&mut reader, file_size, crate::sstable::MAX_KEY_SIZE, "Key")?;
+    let mut stored_key = vec![0u8; key_len];
+    reader.read_exact(&mut stored_key)?;
+    let value_len = checked_len::read_len(
+        &mut reader,
+        file_size,
+        crate::sstable::MAX_VALUE_SIZE,
+        "Value",
+    )?;
+    if stored_key != key.as_bytes() || value_len != pointer.len as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Value log record at offset {} in {} does not hold {:?}",
+                pointer.offset,
+                path.display(),
+                key
+            ),
+        ));
+    }
+    let mut value = vec![0u8; value_len];
+    reader.read_exact(&mut value)?;
+    let mut checksum_buf = [0u8; 4];
+    reader.read_exact(&mut checksum_buf)?;
+    if ChecksumKind::Crc32.entry_checksum(key, &value) != u32::from_le_bytes(checksum_buf) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Checksum mismatch for value log record at offset {} in {}",
+                pointer.offset,
+                path.display()
+            ),
+        ));
+    }
+    Ok(value)
+}
+
+impl LsmIndex {
+    /// The value behind a pointer stored in place of `key`'s value
+    pub(super) fn read_value_pointer(&self, key: &str, stored: &[u8]) -> Result<Vec<u8>> {
+        let pointer = ValuePointer::decode(stored)?;
+        Ok(read_value(&self.base_path, key, pointer)?)
+    }
+
+    /// The value of an entry read back from an SSTable, from the value log
+    /// if the file only holds a pointer to it
+    pub(super) fn resolve_stored_value(&self, entry: StoredEntry) -> Result<Vec<u8>> {
+        if entry.value_log_segments.contains_key(&entry.key) {
+            let key = entry.key.clone();
+            let pointer = entry.into_value()?;
+            return self.read_value_pointer(&key, &pointer);
+        }
+        entry.into_value()
+    }
+
+    /// Reclaim space in the value log.
+    ///
+    /// Segments no live SSTable points into, nor any retired one index
+    /// entries still pin, are deleted. In the others, if
+    /// at least `min_garbage_ratio` of the bytes belong to overwritten,
+    /// deleted or expired values, the live values are moved into the
+    /// memtable and flushed into a new segment. The old segment is deleted
+    /// by a later pass, once compaction has dropped the SSTable entries
+    /// still pointing into it.
+    pub fn collect_value_log_garbage(&self, min_garbage_ratio: f64) -> Result<ValueLogGcSummary> {
+        let mut summary = ValueLogGcSummary::default();
+
+        // No flush writes a segment while the WAL is locked, and no
+        // compaction swaps the files pointing into them while the manifest
+        // is
+        let relocations = {
+            let _wal = self.lock_wal()?;
+            let manifest = self.lock_manifest()?;
+            let mut pointers: HashMap<String, Arc<HashMap<String, u64>>> = HashMap::new();
+            for file in manifest.files() {
+                let segments = self.open_sstable(&file.path)?.value_log_segments().clone();
+                pointers.insert(file.path.clone(), segments);
+            }
+            // A retired file is only deleted once the last entry pinning it
+            // lets go, and until then a read may still follow its pointers
+            let pinned: HashSet<String> = self
+                .live_files
+                .iter()
+                .map(|entry| entry.key().clone())
+                .chain(self.index.iter().filter_map(|entry| {
+                    entry.value().file().map(|file| file.get().path().to_string())
+                }))
+                .filter(|path| !pointers.contains_key(path))
+                .collect();
+            for path in pinned {
+                match self.open_sstable(&path) {
+                    Ok(reader) => {
+                        pointers.insert(path, reader.value_log_segments().clone());
+                    }
+                    // Its last handle was dropped since, deleting it
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            let referenced: HashSet<u64> = pointers
+                .values()
+                .flat_map(|segments| segments.values().copied())
+                .collect();
+
+            // Bytes of live records in each segment, and the entries holding
+            // them
+            let mut live: HashMap<u64, (u64, Vec<(String, StorageReference)>)> = HashMap::new();
+            let now_ms = self.now_ms();
+            for entry in self.index.iter() {
+                let index_entry = entry.value();
+                if index_entry.has_value()
+                    || index_entry.is_expired_at(now_ms)
+                    || self.is_range_deleted(entry.key(), index_entry)
+                {
+                    continue;
+                }
+                let Some(storage_ref) = index_entry.storage_ref().filter(|r| !r.is_tombstone)
+                else {
+                    continue;
+                };
+                let Some(&segment) = pointers
+                    .get(&storage_ref.file_path)
+                    .and_then(|segments| segments.get(entry.key()))
+                else {
+                    continue;
+                };
+                let (bytes, entries) = live.entry(segment).or_default();
+                *bytes += 12 + entry.key().len() as u64 + index_entry.written_len().unwrap_or(0);
+                entries.push((entry.key().clone(), storage_ref.clone()));
+            }
+
+            let mut relocations = Vec::new();
+            for (segment, path) in segment_files(&self.base_path)? {
+                let size = fs::metadata(&path)?.len();
+                if !referenced.contains(&segment) {
+                    fs::remove_file(&path)?;
+                    summary.segments_deleted += 1;
+                    summary.bytes_reclaimed += size;
+                    continue;
+                }
+                let (live_bytes, entries) = live.remove(&segment).unwrap_or_default();
+                let record_bytes = size.saturating_sub(SEGMENT_MAGIC.len() as u64).max(1);
+                let garbage_ratio = 1.0 - live_bytes as f64 / record_bytes as f64;
+                if garbage_ratio >= min_garbage_ratio {
+                    relocations.extend(entries);
+                }
+            }
+            relocations
+        };
+
+        for (key, storage_ref) in relocations {
+            if self.relocate_value(&key, &storage_ref)? {
+                summary.values_relocated += 1;
+            }
+        }
+        if summary.values_relocated > 0 {
+            self.lock_wal()?.sync()?;
+            self.flush()?;
+        }
+        debug!(
+            "Value log GC deleted {} segments ({} bytes) and relocated {} values",
+            summary.segments_deleted, summary.bytes_reclaimed, summary.values_relocated
+        );
+        Ok(summary)
+    }
+
+    /// Move the value of `key`, found in the value log through
+    /// `storage_ref`, into the memtable with the entry's write time, expiry
+    /// and sequence, logging it to the WAL as an insert left for the caller
+    /// to sync. Returns false if the key has been written or removed since.
+    fn relocate_value(&self, key: &str, storage_ref: &StorageReference) -> Result<bool> {
+        let mut flushed = false;
+        loop {
+            let mut wal = self.lock_wal()?;
+            let Some(current) = self.index.get(key).map(|entry| entry.value().clone()) else {
+                return Ok(false);
+            };
+            if current.storage_ref() != Some(storage_ref) {
+                return Ok(false);
+            }
+            let Some(value) = self.load_value_from_sstable(storage_ref)? else {
+                return Ok(false);
+            };
+            // Logged again if the insert needs a flush first; replaying the
+            // same insert twice is harmless
+            wal.log_operation_with_sync(
+                Operation::Insert {
+                    key: key.to_string(),
+                    value: value.clone(),
+                },
+                false,
+            )?;
+            match self.memtable.insert(key.to_string(), value.clone()) {
+                Ok(_) => {}
+                // Make room once, outside the WAL lock the flush takes
+                Err(MemtableError::CapacityExceeded) if !flushed => {
+                    drop(wal);
+                    self.flush()?;
+                    flushed = true;
+                    continue;
+                }
+                Err(e) => return Err(LsmIndexError::MemtableError(e)),
+            }
+
+            let mut entry = GenIndexEntry::new(Some(value), None);
+            if let Some(written_at_ms) = current.written_at_ms() {
+                entry = entry.with_written_at_ms(written_at_ms);
+            }
+            if let Some(expires_at_ms) = current.expires_at_ms() {
+                entry = entry.with_expires_at_ms(expires_at_ms);
+            }
+            if let Some(sequence) = current.sequence() {
+                entry = entry.with_sequence(sequence);
+            }
+            self.index.insert(key.to_string(), entry);
+            return Ok(true);
+        }
+    }
+}
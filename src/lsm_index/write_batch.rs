@@ -0,0 +1,174 @@
+use super::{LsmIndex, Result, WriteOptions};
+use crate::wal::durability::Operation;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// A pending change to one key in a `WriteBatchWithIndex`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchEntry<'a> {
+    /// The key will be set to this value
+    Put(&'a [u8]),
+    /// The key will be removed
+    Delete,
+}
+
+/// Writes gathered for `LsmIndex::write`, indexed by key so they can be
+/// read back before they are committed.
+///
+/// A request handler can stage its writes here, read them layered over the
+/// index with `get_from_batch_and_db`, and then either commit the batch as
+/// one WAL record or drop it. Only the latest change to each key is kept.
+///
+/// ```no_run
+/// # use lsmer::lsm_index::{LsmIndex, WriteBatchWithIndex};
+/// # let index = LsmIndex::new(1024, "data".to_string(), None, false, 0.01).unwrap();
+/// let mut batch = WriteBatchWithIndex::new();
+/// batch.put("balance", b"90".to_vec());
+/// assert_eq!(
+///     batch.get_from_batch_and_db(&index, "balance").unwrap(),
+///     Some(b"90".to_vec())
+/// );
+/// index.write(batch).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteBatchWithIndex {
+    /// Latest change to each key; `None` is a removal
+    entries: BTreeMap<String, Option<Vec<u8>>>,
+}
+
+impl WriteBatchWithIndex {
+    /// Create an empty batch
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stage setting `key` to `value`, replacing any earlier change to it
+    pub fn put(&mut self, key: impl Into<String>, value: Vec<u8>) {
+        self.entries.insert(key.into(), Some(value));
+    }
+
+    /// Stage removing `key`, replacing any earlier change to it
+    pub fn delete(&mut self, key: impl Into<String>) {
+        self.entries.insert(key.into(), None);
+    }
+
+    /// The pending change to `key`, if the batch holds one
+    pub fn get_from_batch(&self, key: &str) -> Option<BatchEntry<'_>> {
+        self.entries.get(key).map(|change| match change {
+            Some(value) => BatchEntry::Put(value),
+            None => BatchEntry::Delete,
+        })
+    }
+
+    /// Read `key` as it would be if the batch were committed now: the
+    /// batch's change if it holds one, otherwise the index's value
+    pub fn get_from_batch_and_db(&self, index: &LsmIndex, key: &str) -> Result<Option<Vec<u8>>> {
+        match self.get_from_batch(key) {
+            Some(BatchEntry::Put(value)) => Ok(Some(value.to_vec())),
+            Some(BatchEntry::Delete) => Ok(None),
+            None => index.get(key),
+        }
+    }
+
+    /// Pending changes in key order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, BatchEntry<'_>)> {
+        self.entries.iter().map(|(key, change)| {
+            let entry = match change {
+                Some(value) => BatchEntry::Put(value),
+                None => BatchEntry::Delete,
+            };
+            (key.as_str(), entry)
+        })
+    }
+
+    /// Number of keys with a pending change
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the batch holds no changes
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drop every pending change
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl LsmIndex {
+    /// Commit a batch with the default write options
+    pub fn write(&self, batch: WriteBatchWithIndex) -> Result<u64> {
+        self.write_with_options(batch, &self.default_write_options())
+    }
+
+    /// Commit every change in `batch` as a single WAL record, returning the
+    /// commit sequence of the last one.
+    ///
+    /// Every entry is checked before anything is logged, and
+    /// `WriteOptions::if_unchanged_since` applies to each key, so a batch
+    /// that fails a check writes nothing. An empty batch writes nothing and
+    /// returns `last_sequence`.
+    pub fn write_with_options(
+        &self,
+        batch: WriteBatchWithIndex,
+        write_options: &WriteOptions,
+    ) -> Result<u64> {
+        if batch.is_empty() {
+            return Ok(self.last_sequence());
+        }
+        let started = Instant::now();
+
+        let mut bytes = 0;
+        for (key, value) in &batch.entries {
+            self.validate_key(key)?;
+            if let Some(value) = value {
+                self.check_entry_size(key, value)?;
+                bytes += (key.len() + value.len()) as u64;
+            }
+        }
+        self.ensure_disk_space(&self.base_path, bytes)?;
+
+        // Removals need the values they hide, for tombstones and undelete
+        let mut current_values = BTreeMap::new();
+        for (key, value) in &batch.entries {
+            if value.is_none() {
+                current_values.insert(key.clone(), self.get(key)?);
+            }
+        }
+
+        let mut durability_manager = self.lock_wal()?;
+        for key in batch.entries.keys() {
+            self.check_unchanged(key, write_options)?;
+        }
+        if !write_options.disable_wal {
+            let operations = batch
+                .entries
+                .iter()
+                .map(|(key, value)| match value {
+                    Some(value) => Operation::Insert {
+                        key: key.clone(),
+                        value: value.clone(),
+                    },
+                    None => Operation::Remove { key: key.clone() },
+                })
+                .collect();
+            durability_manager
+                .log_operation_with_sync(Operation::Batch { operations }, write_options.sync)?;
+        }
+
+        let mut sequence = self.last_sequence();
+        for (key, value) in batch.entries {
+            sequence = match value {
+                Some(value) => self.apply_insert(key, value, None)?,
+                None => {
+                    let current_value = current_values.remove(&key).flatten();
+                    self.apply_remove(&key, current_value.as_deref())?
+                }
+            };
+        }
+        self.finish_write(write_options, started);
+        Ok(sequence)
+    }
+}
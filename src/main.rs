@@ -0,0 +1,47 @@
+use lsmer::sstable::upgrade;
+use std::env;
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: lsmer upgrade <directory-or-sstable>...";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.split_first() {
+        Some((command, paths)) if command == "upgrade" && !paths.is_empty() => {
+            let mut failed = false;
+            for path in paths.iter().map(Path::new) {
+                let result = if path.is_dir() {
+                    upgrade::upgrade_directory(path)
+                } else {
+                    upgrade::upgrade_sstable(path)
+                        .map(|upgraded| upgraded.then(|| path.display().to_string()))
+                        .map(Vec::from_iter)
+                };
+                match result {
+                    Ok(upgraded) if upgraded.is_empty() => {
+                        println!("{}: already current", path.display());
+                    }
+                    Ok(upgraded) => {
+                        for file in upgraded {
+                            println!("upgraded {}", file);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("{}: {}", path.display(), e);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -137,23 +137,23 @@ where
                                 let _ = result_sender.send(memtable.size_bytes());
                             }
                             MemtableMessage::ForceCompaction(result_sender) => {
-                                println!("Received ForceCompaction message");
+                                debug!("Received ForceCompaction message");
                                 let result = Self::do_compaction(&memtable, &worker_base_path).await;
-                                println!("do_compaction finished with result: {:?}", result);
+                                debug!("do_compaction finished with result: {:?}", result);
 
                                 // If compaction was successful, clear the memtable
                                 if result.is_ok() {
-                                    println!("Clearing memtable after successful compaction");
+                                    debug!("Clearing memtable after successful compaction");
                                     if let Err(e) = memtable.clear() {
-                                        println!("Error clearing memtable: {:?}", e);
+                                        warn!("Error clearing memtable: {:?}", e);
                                     } else {
-                                        println!("Memtable successfully cleared");
+                                        debug!("Memtable successfully cleared");
                                     }
                                 }
 
                                 match result_sender.send(result) {
-                                    Ok(_) => println!("Sent compaction result back to caller"),
-                                    Err(e) => println!("Failed to send compaction result: {:?}", e),
+                                    Ok(_) => debug!("Sent compaction result back to caller"),
+                                    Err(e) => warn!("Failed to send compaction result: {:?}", e),
                                 }
                             }
                             MemtableMessage::Shutdown => {
@@ -189,48 +189,48 @@ where
 
     /// Perform a compaction operation to flush the memtable to disk
     async fn do_compaction(memtable: &StringMemtable, base_path: &str) -> io::Result<String> {
-        println!("do_compaction: Starting compaction");
+        debug!("do_compaction: Starting compaction");
         // Clone the data needed for the blocking task
-        println!("do_compaction: Cloning memtable data");
+        debug!("do_compaction: Cloning memtable data");
         let memtable_data: Vec<(String, Vec<u8>)> = memtable.iter().unwrap_or_default();
-        println!("do_compaction: Cloned {} items", memtable_data.len());
+        debug!("do_compaction: Cloned {} items", memtable_data.len());
         let base_path = base_path.to_string();
 
         // Use tokio's spawn_blocking for CPU-bound flush_to_sstable operation
-        println!("do_compaction: Spawning blocking task");
+        debug!("do_compaction: Spawning blocking task");
         let result = tokio::task::spawn_blocking(move || {
-            println!("  blocking task: Creating temporary memtable");
+            debug!("  blocking task: Creating temporary memtable");
 
             // Make sure the output directory exists
             if let Err(e) = std::fs::create_dir_all(&base_path) {
-                println!(
+                debug!(
                     "  blocking task: Failed to create directory {}: {}",
                     base_path, e
                 );
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Failed to create directory: {}", e),
-                ));
+                return Err(io::Error::other(format!(
+                    "Failed to create directory: {}",
+                    e
+                )));
             }
 
             // Create a new memtable with the cloned data
             let temp_memtable = StringMemtable::new(1024 * 1024);
 
-            println!("  blocking task: Inserting {} items", memtable_data.len());
+            debug!("  blocking task: Inserting {} items", memtable_data.len());
             // Insert the data into the temporary memtable
             for (key, value) in memtable_data {
                 let _ = temp_memtable.insert(key, value);
             }
 
-            println!("  blocking task: Flushing to SSTable at {}", base_path);
+            debug!("  blocking task: Flushing to SSTable at {}", base_path);
             // Flush the memtable to the SSTable
             let result = temp_memtable.flush_to_sstable(&base_path);
-            println!("  blocking task: Flush result: {:?}", result);
+            debug!("  blocking task: Flush result: {:?}", result);
             result
         })
         .await;
 
-        println!(
+        debug!(
             "do_compaction: Blocking task completed with result: {:?}",
             result
         );
@@ -260,11 +260,11 @@ where
         self.sender
             .send(MemtableMessage::ForceCompaction(sender))
             .await
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "channel closed"))?;
+            .map_err(|_| io::Error::other("channel closed"))?;
 
         receiver
             .await
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Worker thread did not respond"))?
+            .map_err(|_| io::Error::other("Worker thread did not respond"))?
     }
 
     /// Shut down the memtable worker task
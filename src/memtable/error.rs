@@ -45,7 +45,7 @@ impl From<WalError> for MemtableError {
     fn from(error: WalError) -> Self {
         match error {
             WalError::IoError(e) => MemtableError::WalError(e),
-            e => MemtableError::WalError(io::Error::new(io::ErrorKind::Other, e.to_string())),
+            e => MemtableError::WalError(io::Error::other(e.to_string())),
         }
     }
 }
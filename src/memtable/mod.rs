@@ -8,7 +8,7 @@ use std::sync::mpsc;
 
 pub use async_memtable::AsyncStringMemtable;
 pub use error::MemtableError;
-pub use string_memtable::StringMemtable;
+pub use string_memtable::{KeyFilterOptions, StringMemtable};
 pub use traits::{ByteSize, Memtable, SSTableWriter, ToBytes};
 
 // Messages that can be sent to the background thread
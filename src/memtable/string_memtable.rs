@@ -1,13 +1,25 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::{self, Seek, SeekFrom, Write};
 use std::ops::RangeBounds;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
-use std::time::{SystemTime, UNIX_EPOCH};
 
 use super::error::MemtableError;
 use super::traits::{ByteSize, Memtable, SSTableWriter};
-use crate::sstable::{SSTableCompaction, SSTableInfo, MAGIC, VERSION};
+use crate::bloom::BloomFilter;
+use crate::clock::{Clock, SystemClock};
+use crate::sstable::{PrefixExtractor, SSTableCompaction, SSTableInfo, LEGACY_VERSION, MAGIC};
+
+/// Sizing of the Bloom filter a `StringMemtable` keeps over its keys
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyFilterOptions {
+    /// Keys the memtable is expected to hold before it is cleared; more
+    /// keys raise the false positive rate
+    pub expected_keys: usize,
+    /// Target false positive rate at `expected_keys`
+    pub false_positive_rate: f64,
+}
 
 /// A string-based memtable implementation
 #[derive(Debug)]
@@ -15,6 +27,15 @@ pub struct StringMemtable {
     data: Arc<RwLock<BTreeMap<String, Vec<u8>>>>,
     max_size_bytes: usize,
     current_size_bytes: Arc<RwLock<usize>>,
+    /// Extractor used to hash keys by prefix, if any
+    prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    /// Number of keys held under each prefix
+    prefix_counts: Arc<RwLock<HashMap<String, usize>>>,
+    /// Filter over every key inserted since the memtable was last cleared,
+    /// so lookups of absent keys skip the map
+    key_filter: Option<Arc<RwLock<BloomFilter<String>>>>,
+    /// Lookups the key filter answered without probing the map
+    filter_negatives: Arc<AtomicU64>,
 }
 
 impl StringMemtable {
@@ -23,7 +44,111 @@ impl StringMemtable {
             data: Arc::new(RwLock::new(BTreeMap::new())),
             max_size_bytes,
             current_size_bytes: Arc::new(RwLock::new(0)),
+            prefix_extractor: None,
+            prefix_counts: Arc::new(RwLock::new(HashMap::new())),
+            key_filter: None,
+            filter_negatives: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Keep a Bloom filter over the memtable's keys, so lookups of keys it
+    /// does not hold skip the map. Removed keys stay in the filter until the
+    /// memtable is cleared.
+    pub fn with_key_filter(mut self, options: KeyFilterOptions) -> Self {
+        self.key_filter = Some(Arc::new(RwLock::new(BloomFilter::new(
+            options.expected_keys,
+            options.false_positive_rate,
+        ))));
+        self
+    }
+
+    /// Whether the memtable keeps a key filter
+    pub fn has_key_filter(&self) -> bool {
+        self.key_filter.is_some()
+    }
+
+    /// Number of lookups the key filter answered without probing the map,
+    /// counting the check each insert makes for an existing value
+    pub fn filter_negatives(&self) -> u64 {
+        self.filter_negatives.load(Ordering::Relaxed)
+    }
+
+    /// Whether the key filter rules `key` out; false without a filter
+    fn filtered_out(&self, key: &String) -> Result<bool, MemtableError> {
+        let Some(filter) = &self.key_filter else {
+            return Ok(false);
+        };
+        let absent = !filter
+            .read()
+            .map_err(|_| MemtableError::LockError)?
+            .may_contain(key);
+        if absent {
+            self.filter_negatives.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(absent)
+    }
+
+    /// Empty the key filter along with the map
+    fn clear_key_filter(&self) -> Result<(), MemtableError> {
+        if let Some(filter) = &self.key_filter {
+            filter
+                .write()
+                .map_err(|_| MemtableError::LockError)?
+                .clear();
+        }
+        Ok(())
+    }
+
+    /// Create a memtable that also counts its keys by prefix, so prefix
+    /// lookups can be answered without scanning
+    pub fn with_prefix_extractor(
+        max_size_bytes: usize,
+        prefix_extractor: Arc<dyn PrefixExtractor>,
+    ) -> Self {
+        StringMemtable {
+            prefix_extractor: Some(prefix_extractor),
+            ..Self::new(max_size_bytes)
+        }
+    }
+
+    /// The extractor keys are hashed by, if any
+    pub fn prefix_extractor(&self) -> Option<&Arc<dyn PrefixExtractor>> {
+        self.prefix_extractor.as_ref()
+    }
+
+    /// Number of keys held under a prefix; always 0 without an extractor
+    pub fn prefix_count(&self, prefix: &str) -> Result<usize, MemtableError> {
+        let guard = self
+            .prefix_counts
+            .read()
+            .map_err(|_| MemtableError::LockError)?;
+        Ok(guard.get(prefix).copied().unwrap_or(0))
+    }
+
+    /// Adjust the count for a key's prefix after it was added or removed
+    fn track_prefix(&self, key: &str, added: bool) -> Result<(), MemtableError> {
+        let prefix = match self
+            .prefix_extractor
+            .as_ref()
+            .and_then(|extractor| extractor.prefix_of(key))
+        {
+            Some(prefix) => prefix,
+            None => return Ok(()),
+        };
+
+        let mut counts = self
+            .prefix_counts
+            .write()
+            .map_err(|_| MemtableError::LockError)?;
+        if added {
+            *counts.entry(prefix.to_string()).or_insert(0) += 1;
+        } else if let Some(count) = counts.get_mut(prefix) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(prefix);
+            }
         }
+        Ok(())
     }
 
     pub fn max_capacity(&self) -> usize {
@@ -58,10 +183,7 @@ impl StringMemtable {
     }
 
     fn generate_timestamp(&self) -> u64 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs()
+        SystemClock.now_secs()
     }
 }
 
@@ -88,18 +210,36 @@ impl Memtable<String, Vec<u8>> for StringMemtable {
 
         let mut data_guard = self.data.write().map_err(|_| MemtableError::LockError)?;
 
+        // The key enters the filter before the map, so a lookup never finds
+        // it filtered out once it can be read
+        if let Some(filter) = &self.key_filter {
+            filter
+                .write()
+                .map_err(|_| MemtableError::LockError)?
+                .insert(&key);
+        }
+
+        // Keep a copy of the key for the prefix count, which is only
+        // updated once the insert has gone through
+        let tracked_key = self.prefix_extractor.as_ref().map(|_| key.clone());
         let old_value = data_guard.insert(key, value);
         if let Some(old_val) = &old_value {
             let old_size = key_size + old_val.byte_size() + std::mem::size_of::<usize>();
             *size_guard = *size_guard - old_size + entry_size;
         } else {
             *size_guard += entry_size;
+            if let Some(key) = &tracked_key {
+                self.track_prefix(key, true)?;
+            }
         }
 
         Ok(old_value)
     }
 
     fn get(&self, key: &String) -> Result<Option<Vec<u8>>, MemtableError> {
+        if self.filtered_out(key)? {
+            return Ok(None);
+        }
         let guard = self.data.read().map_err(|_| MemtableError::LockError)?;
         Ok(guard.get(key).cloned())
     }
@@ -114,6 +254,7 @@ impl Memtable<String, Vec<u8>> for StringMemtable {
         let old_value = data_guard.remove(key);
         if let Some(old_val) = &old_value {
             *size_guard -= key.byte_size() + old_val.byte_size();
+            self.track_prefix(key, false)?;
         }
         Ok(old_value)
     }
@@ -137,7 +278,11 @@ impl Memtable<String, Vec<u8>> for StringMemtable {
 
         data_guard.clear();
         *size_guard = 0;
-        Ok(())
+        self.prefix_counts
+            .write()
+            .map_err(|_| MemtableError::LockError)?
+            .clear();
+        self.clear_key_filter()
     }
 
     fn size_bytes(&self) -> Result<usize, MemtableError> {
@@ -147,16 +292,16 @@ impl Memtable<String, Vec<u8>> for StringMemtable {
 
 impl SSTableWriter for StringMemtable {
     fn flush_to_sstable(&self, base_path: &str) -> io::Result<String> {
-        println!("flush_to_sstable: Starting to flush memtable");
+        debug!("flush_to_sstable: Starting to flush memtable");
 
         // Clone the data while holding a read lock, and then release it immediately
         let data_clone: Vec<(String, Vec<u8>)>;
         {
             let guard = self.data.read().map_err(|_| {
-                println!("flush_to_sstable: Failed to acquire read lock on data");
-                io::Error::new(io::ErrorKind::Other, "Failed to acquire read lock on data")
+                warn!("flush_to_sstable: Failed to acquire read lock on data");
+                io::Error::other("Failed to acquire read lock on data")
             })?;
-            println!(
+            debug!(
                 "flush_to_sstable: Acquired read lock, found {} items",
                 guard.len()
             );
@@ -164,26 +309,23 @@ impl SSTableWriter for StringMemtable {
             // Clone data to avoid holding the lock during file I/O
             data_clone = guard.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
         } // read lock is released here
-        println!("flush_to_sstable: Released read lock after cloning");
+        debug!("flush_to_sstable: Released read lock after cloning");
 
         // Generate a unique filename for the SSTable
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let timestamp = SystemClock.now_secs();
         let sstable_path = format!("{}/sstable_{}.db", base_path, timestamp);
-        println!("flush_to_sstable: Generated SSTable path: {}", sstable_path);
+        debug!("flush_to_sstable: Generated SSTable path: {}", sstable_path);
 
         // Create the SSTable file
-        println!("flush_to_sstable: Creating SSTable file");
+        debug!("flush_to_sstable: Creating SSTable file");
         let mut file = match File::create(&sstable_path) {
             Ok(f) => f,
             Err(e) => {
-                println!("flush_to_sstable: Failed to create file: {}", e);
+                warn!("flush_to_sstable: Failed to create file: {}", e);
                 return Err(e);
             }
         };
-        println!("flush_to_sstable: File created successfully");
+        debug!("flush_to_sstable: File created successfully");
 
         // Write header (we'll update the index offset later)
         let entry_count = data_clone.len() as u64;
@@ -191,7 +333,7 @@ impl SSTableWriter for StringMemtable {
 
         // Write magic number and version
         file.write_all(&MAGIC.to_le_bytes())?;
-        file.write_all(&VERSION.to_le_bytes())?;
+        file.write_all(&LEGACY_VERSION.to_le_bytes())?;
 
         // Write entry count
         file.write_all(&entry_count.to_le_bytes())?;
@@ -240,23 +382,25 @@ impl SSTableWriter for StringMemtable {
         // Update the index offset in the header
         file.seek(SeekFrom::Start(index_offset_pos))?;
         file.write_all(&index_offset.to_le_bytes())?;
-        println!("flush_to_sstable: Updated index offset in header");
+        debug!("flush_to_sstable: Updated index offset in header");
 
         // Clear the memtable after successful flush
-        println!("flush_to_sstable: Clearing memtable");
+        debug!("flush_to_sstable: Clearing memtable");
         {
             let mut data_guard = self.data.write().map_err(|_| {
-                println!("flush_to_sstable: Failed to acquire write lock on data");
-                io::Error::new(io::ErrorKind::Other, "Failed to acquire write lock on data")
+                warn!("flush_to_sstable: Failed to acquire write lock on data");
+                io::Error::other("Failed to acquire write lock on data")
             })?;
             let mut size_guard = self.current_size_bytes.write().map_err(|_| {
-                println!("flush_to_sstable: Failed to acquire write lock on size");
-                io::Error::new(io::ErrorKind::Other, "Failed to acquire write lock on size")
+                warn!("flush_to_sstable: Failed to acquire write lock on size");
+                io::Error::other("Failed to acquire write lock on size")
             })?;
             data_guard.clear();
             *size_guard = 0;
+            self.clear_key_filter()
+                .map_err(|_| io::Error::other("Failed to acquire write lock on key filter"))?;
         } // write locks are released here
-        println!(
+        debug!(
             "flush_to_sstable: Memtable cleared, returning path: {}",
             sstable_path
         );
@@ -0,0 +1,122 @@
+use super::codec::{self, CodecRegistry};
+use super::compression::ZSTD_COMPRESSION_NAME;
+use std::io;
+
+/// Zstd level data blocks are compressed at
+#[cfg(feature = "zstd")]
+const ZSTD_BLOCK_LEVEL: i32 = 3;
+
+/// Codec applied to whole data blocks of an SSTable, recorded in the header
+/// of version 7 and later files.
+///
+/// Where `Compression` compresses each value on its own, a block codec
+/// compresses keys, lengths and checksums along with the values, so it also
+/// finds what neighbouring entries repeat. The two can be combined, though
+/// values compressed on their own leave blocks little to gain.
+///
+/// Every codec but `None` needs its cargo feature; writing or reading a file
+/// with a codec that is not compiled in fails with `ErrorKind::Unsupported`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CompressionType {
+    /// Blocks are stored as written
+    #[default]
+    None,
+    /// Each block is compressed with LZ4
+    Lz4,
+    /// Each block is compressed with Snappy
+    Snappy,
+    /// Each block is compressed with Zstd
+    Zstd,
+}
+
+impl CompressionType {
+    /// Byte recording the codec in the file header
+    pub fn id(self) -> u8 {
+        match self {
+            CompressionType::None => 0,
+            CompressionType::Lz4 => 1,
+            CompressionType::Snappy => 2,
+            CompressionType::Zstd => 3,
+        }
+    }
+
+    /// The codec recorded as `id`; fails with `InvalidData` for an unknown one
+    pub fn from_id(id: u8) -> io::Result<Self> {
+        match id {
+            0 => Ok(CompressionType::None),
+            1 => Ok(CompressionType::Lz4),
+            2 => Ok(CompressionType::Snappy),
+            3 => Ok(CompressionType::Zstd),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown block compression type: {}", id),
+            )),
+        }
+    }
+
+    /// Name of the codec; `None` when blocks are stored as written
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            CompressionType::None => None,
+            CompressionType::Lz4 => Some(codec::LZ4_COMPRESSION_NAME),
+            CompressionType::Snappy => Some(codec::SNAPPY_COMPRESSION_NAME),
+            CompressionType::Zstd => Some(ZSTD_COMPRESSION_NAME),
+        }
+    }
+
+    /// Compress a data block
+    pub(crate) fn compress(self, block: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            CompressionType::None => Ok(block.to_vec()),
+            #[cfg(feature = "zstd")]
+            CompressionType::Zstd => zstd::bulk::compress(block, ZSTD_BLOCK_LEVEL),
+            #[cfg(not(feature = "zstd"))]
+            CompressionType::Zstd => Err(codec::unavailable(ZSTD_COMPRESSION_NAME)),
+            CompressionType::Lz4 | CompressionType::Snappy => {
+                let name = self.name().unwrap_or_default();
+                CodecRegistry::global().get(name)?.compress(block)
+            }
+        }
+    }
+
+    /// Restore a data block of `len` bytes compressed by `compress`
+    pub(crate) fn decompress(self, stored: &[u8], len: usize) -> io::Result<Vec<u8>> {
+        let block = match self {
+            CompressionType::None => stored.to_vec(),
+            #[cfg(feature = "zstd")]
+            CompressionType::Zstd => zstd::bulk::decompress(stored, len)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            #[cfg(not(feature = "zstd"))]
+            CompressionType::Zstd => return Err(codec::unavailable(ZSTD_COMPRESSION_NAME)),
+            CompressionType::Lz4 | CompressionType::Snappy => {
+                let name = self.name().unwrap_or_default();
+                CodecRegistry::global().get(name)?.decompress(stored, len)?
+            }
+        };
+        if block.len() != len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Data block decompressed to {} bytes rather than {}",
+                    block.len(),
+                    len
+                ),
+            ));
+        }
+        Ok(block)
+    }
+
+    /// Check the codec can be used, failing with `ErrorKind::Unsupported`
+    /// if it is not compiled in
+    pub(crate) fn ensure_available(self) -> io::Result<()> {
+        match self {
+            CompressionType::None => Ok(()),
+            CompressionType::Zstd if cfg!(feature = "zstd") => Ok(()),
+            CompressionType::Zstd => Err(codec::unavailable(ZSTD_COMPRESSION_NAME)),
+            CompressionType::Lz4 | CompressionType::Snappy => {
+                CodecRegistry::global().get(self.name().unwrap_or_default())?;
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::bloom::BloomFilter;
+use std::io;
+
+/// Bloom filter over the keys of one data block, so a point lookup that
+/// lands in the block can rule the key out without reading the block
+#[derive(Debug)]
+pub(crate) struct BlockFilter {
+    /// File offset of the block's first entry, as in its `BlockHandle`
+    pub(crate) offset: u64,
+    /// Filter holding the block's keys
+    pub(crate) filter: BloomFilter<String>,
+}
+
+/// Build the filter for a block starting at `offset`
+pub(crate) fn build(offset: u64, keys: &[String], false_positive_rate: f64) -> BlockFilter {
+    let mut filter = BloomFilter::new(keys.len().max(1), false_positive_rate);
+    for key in keys {
+        filter.insert(key);
+    }
+    BlockFilter { offset, filter }
+}
+
+/// Encode block filters as a count followed by each block's offset and
+/// filter parameters and bits
+pub(crate) fn encode(filters: &[BlockFilter]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(filters.len() as u32).to_le_bytes());
+    for block in filters {
+        buf.extend_from_slice(&block.offset.to_le_bytes());
+        buf.extend_from_slice(&(block.filter.size_bits() as u64).to_le_bytes());
+        buf.extend_from_slice(&(block.filter.num_hashes() as u32).to_le_bytes());
+        buf.extend_from_slice(block.filter.get_bits());
+    }
+    buf
+}
+
+/// Decode block filters written by `encode`
+pub(crate) fn decode(buf: &[u8]) -> io::Result<Vec<BlockFilter>> {
+    let mut cursor = buf;
+    let count = u32::from_le_bytes(take(&mut cursor, 4)?.try_into().unwrap());
+
+    let mut filters = Vec::with_capacity((count as usize).min(buf.len()));
+    for _ in 0..count {
+        let offset = u64::from_le_bytes(take(&mut cursor, 8)?.try_into().unwrap());
+        let size_bits = u64::from_le_bytes(take(&mut cursor, 8)?.try_into().unwrap()) as usize;
+        let num_hashes = u32::from_le_bytes(take(&mut cursor, 4)?.try_into().unwrap()) as usize;
+        let bits = take(&mut cursor, size_bits.div_ceil(8))?.to_vec();
+        filters.push(BlockFilter {
+            offset,
+            filter: BloomFilter::from_parts(bits, size_bits, num_hashes),
+        });
+    }
+
+    Ok(filters)
+}
+
+/// Split `len` bytes off the front of a buffer
+fn take<'a>(cursor: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if cursor.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Truncated SSTable block filters",
+        ));
+    }
+    let (bytes, rest) = cursor.split_at(len);
+    *cursor = rest;
+    Ok(bytes)
+}
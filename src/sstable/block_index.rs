@@ -0,0 +1,76 @@
+use std::io;
+
+/// Location and key span of one data block in an SSTable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockHandle {
+    /// First key in the block
+    pub first_key: String,
+    /// Last key in the block
+    pub last_key: String,
+    /// File offset of the block's first entry
+    pub offset: u64,
+    /// Number of entries in the block
+    pub entry_count: u64,
+}
+
+/// Encode block handles as a count followed by each block's length-prefixed
+/// first and last keys, offset and entry count
+pub(crate) fn encode(blocks: &[BlockHandle]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+    for block in blocks {
+        for key in [&block.first_key, &block.last_key] {
+            buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            buf.extend_from_slice(key.as_bytes());
+        }
+        buf.extend_from_slice(&block.offset.to_le_bytes());
+        buf.extend_from_slice(&block.entry_count.to_le_bytes());
+    }
+    buf
+}
+
+/// Decode block handles written by `encode`
+pub(crate) fn decode(buf: &[u8]) -> io::Result<Vec<BlockHandle>> {
+    let mut cursor = buf;
+    let count = u32::from_le_bytes(take(&mut cursor, 4)?.try_into().unwrap());
+
+    let mut blocks = Vec::with_capacity((count as usize).min(buf.len()));
+    for _ in 0..count {
+        let first_key = take_key(&mut cursor)?;
+        let last_key = take_key(&mut cursor)?;
+        let offset = u64::from_le_bytes(take(&mut cursor, 8)?.try_into().unwrap());
+        let entry_count = u64::from_le_bytes(take(&mut cursor, 8)?.try_into().unwrap());
+        blocks.push(BlockHandle {
+            first_key,
+            last_key,
+            offset,
+            entry_count,
+        });
+    }
+
+    Ok(blocks)
+}
+
+/// Read a length-prefixed key
+fn take_key(cursor: &mut &[u8]) -> io::Result<String> {
+    let len = u32::from_le_bytes(take(cursor, 4)?.try_into().unwrap()) as usize;
+    String::from_utf8(take(cursor, len)?.to_vec()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "SSTable block index key is not valid UTF-8",
+        )
+    })
+}
+
+/// Split `len` bytes off the front of a buffer
+fn take<'a>(cursor: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if cursor.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Truncated SSTable block index",
+        ));
+    }
+    let (bytes, rest) = cursor.split_at(len);
+    *cursor = rest;
+    Ok(bytes)
+}
@@ -0,0 +1,244 @@
+use super::block_compression::CompressionType;
+use super::calculate_checksum;
+use super::encryption::{BlockCipher, CIPHER_OVERHEAD};
+use std::borrow::Cow;
+use std::io;
+
+/// Size of the trailer ending a block map: the block count and the map's
+/// checksum
+pub(crate) const BLOCK_MAP_TRAILER_SIZE: usize = 8 + 4;
+/// Size of each block's record in a block map: its length, stored length and
+/// the checksum of its stored bytes
+pub(crate) const BLOCK_RECORD_SIZE: usize = 4 + 4 + 4;
+/// Size of the length of the key ID that ends the block map of an encrypted
+/// file, ahead of the trailer
+pub(crate) const KEY_ID_LEN_SIZE: usize = 4;
+
+/// Where one data block lies, at the offsets its entries were written at and
+/// in the file as stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct StoredBlock {
+    /// Offset of the block's first entry
+    pub(crate) offset: u64,
+    /// Length of the block as written
+    pub(crate) len: u64,
+    /// Offset in the file of the block's stored bytes
+    pub(crate) stored_offset: u64,
+    /// Length of the stored bytes. Less the cipher's overhead in an
+    /// encrypted file, it equals `len` if the block is stored uncompressed
+    /// because compressing did not shrink it.
+    pub(crate) stored_len: u64,
+    /// CRC32 of the stored bytes
+    pub(crate) checksum: u32,
+}
+
+impl StoredBlock {
+    /// Restore the block from its stored bytes, after checking them against
+    /// their checksum and decrypting them with `cipher` if the file is
+    /// encrypted
+    pub(crate) fn decode(
+        &self,
+        compression: CompressionType,
+        cipher: Option<&BlockCipher>,
+        stored: &[u8],
+    ) -> io::Result<Vec<u8>> {
+        if calculate_checksum(stored) != self.checksum {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Checksum mismatch for the stored data block at offset {}",
+                    self.offset
+                ),
+            ));
+        }
+        let opened = match cipher {
+            Some(cipher) => Cow::Owned(cipher.open(self.offset, stored)?),
+            None => Cow::Borrowed(stored),
+        };
+        if opened.len() as u64 == self.len {
+            return Ok(opened.into_owned());
+        }
+        compression.decompress(&opened, self.len as usize)
+    }
+}
+
+/// Compresses and encrypts data blocks as an SSTable is written, recording
+/// where each one is stored for the block map written after the footer.
+///
+/// The map holds each block's length as written and as stored, with the
+/// checksum of its stored bytes. An encrypted file's map goes on with the
+/// ID of the key its blocks are sealed with and the ID's length. The block
+/// count and a CRC32 of everything before it end the map.
+#[derive(Debug)]
+pub(crate) struct BlockEncoder {
+    compression: CompressionType,
+    cipher: Option<BlockCipher>,
+    records: Vec<u8>,
+    count: u64,
+    stored_bytes: u64,
+}
+
+impl BlockEncoder {
+    /// Encoder storing blocks as written, until a codec or cipher is set
+    pub(crate) fn new() -> Self {
+        BlockEncoder {
+            compression: CompressionType::None,
+            cipher: None,
+            records: Vec::new(),
+            count: 0,
+            stored_bytes: 0,
+        }
+    }
+
+    /// Compress blocks with `compression`
+    pub(crate) fn set_compression_type(&mut self, compression: CompressionType) {
+        self.compression = compression;
+    }
+
+    /// Seal blocks with `cipher`, or store them in the clear
+    pub(crate) fn set_cipher(&mut self, cipher: Option<BlockCipher>) {
+        self.cipher = cipher;
+    }
+
+    /// Whether blocks are stored other than as written, so the file needs a
+    /// block map
+    pub(crate) fn is_active(&self) -> bool {
+        self.compression != CompressionType::None || self.cipher.is_some()
+    }
+
+    /// Compress and seal the block written at `offset`, recording it and
+    /// returning the bytes to store. Blocks that would not shrink are stored
+    /// uncompressed.
+    pub(crate) fn encode<'a>(&mut self, offset: u64, block: &'a [u8]) -> io::Result<Cow<'a, [u8]>> {
+        let mut stored = Cow::Borrowed(block);
+        if self.compression != CompressionType::None {
+            let compressed = self.compression.compress(block)?;
+            if compressed.len() < block.len() {
+                stored = Cow::Owned(compressed);
+            }
+        }
+        if let Some(cipher) = &self.cipher {
+            stored = Cow::Owned(cipher.seal(offset, &stored)?);
+        }
+        let too_long = |_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Data block is too long to store",
+            )
+        };
+        let len = u32::try_from(block.len()).map_err(too_long)?;
+        let stored_len = u32::try_from(stored.len()).map_err(too_long)?;
+        self.records.extend_from_slice(&len.to_le_bytes());
+        self.records.extend_from_slice(&stored_len.to_le_bytes());
+        self.records
+            .extend_from_slice(&calculate_checksum(&stored).to_le_bytes());
+        self.count += 1;
+        self.stored_bytes += stored.len() as u64;
+        Ok(stored)
+    }
+
+    /// Codec blocks are compressed with
+    pub(crate) fn compression_type(&self) -> CompressionType {
+        self.compression
+    }
+
+    /// ID of the key blocks are sealed with, if they are encrypted
+    pub(crate) fn key_id(&self) -> Option<&str> {
+        self.cipher.as_ref().map(BlockCipher::key_id)
+    }
+
+    /// Bytes the blocks recorded so far take in the file
+    pub(crate) fn stored_bytes(&self) -> u64 {
+        self.stored_bytes
+    }
+
+    /// The block map, ending with its trailer
+    pub(crate) fn finish(self) -> Vec<u8> {
+        let mut map = self.records;
+        if let Some(cipher) = &self.cipher {
+            let key_id = cipher.key_id().as_bytes();
+            map.extend_from_slice(key_id);
+            map.extend_from_slice(&(key_id.len() as u32).to_le_bytes());
+        }
+        map.extend_from_slice(&self.count.to_le_bytes());
+        let checksum = calculate_checksum(&map);
+        map.extend_from_slice(&checksum.to_le_bytes());
+        map
+    }
+}
+
+/// Decode the block map `map`, without its trailer, of a file whose data
+/// section starts at `data_start`, checking it against the trailer's
+/// `count` and `checksum`. Returns the blocks and, for an encrypted file,
+/// the ID of the key they are sealed with.
+pub(crate) fn decode_block_map(
+    map: &[u8],
+    count: u64,
+    checksum: u32,
+    data_start: u64,
+    encrypted: bool,
+) -> io::Result<(Vec<StoredBlock>, Option<String>)> {
+    let mut checked = map.to_vec();
+    checked.extend_from_slice(&count.to_le_bytes());
+    if calculate_checksum(&checked) != checksum {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "SSTable block map checksum verification failed",
+        ));
+    }
+
+    let records_len = (count as usize).saturating_mul(BLOCK_RECORD_SIZE);
+    let malformed = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "SSTable block map does not hold its block count",
+        )
+    };
+    let records = map.get(..records_len).ok_or_else(malformed)?;
+    let key_id = if encrypted {
+        let key_id = map[records_len..]
+            .len()
+            .checked_sub(KEY_ID_LEN_SIZE)
+            .map(|len| &map[records_len..records_len + len])
+            .ok_or_else(malformed)?;
+        let key_id = String::from_utf8(key_id.to_vec()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "SSTable encryption key ID is not valid UTF-8",
+            )
+        })?;
+        Some(key_id)
+    } else if map.len() == records_len {
+        None
+    } else {
+        return Err(malformed());
+    };
+    let overhead = if encrypted { CIPHER_OVERHEAD as u64 } else { 0 };
+
+    let mut blocks = Vec::with_capacity(count as usize);
+    let mut offset = data_start;
+    let mut stored_offset = data_start;
+    for record in records.chunks_exact(BLOCK_RECORD_SIZE) {
+        let field = |at: usize| u32::from_le_bytes(record[at..at + 4].try_into().unwrap());
+        let (len, stored_len) = (field(0) as u64, field(4) as u64);
+        if stored_len > len + overhead {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Data block at offset {} is stored in more bytes than it holds",
+                    offset
+                ),
+            ));
+        }
+        blocks.push(StoredBlock {
+            offset,
+            len,
+            stored_offset,
+            stored_len,
+            checksum: field(8),
+        });
+        offset += len;
+        stored_offset += stored_len;
+    }
+    Ok((blocks, key_id))
+}
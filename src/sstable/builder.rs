@@ -0,0 +1,999 @@
+use super::compression::{Compression, ValueEncoder};
+use super::digest::{entry_digest, Digest};
+use super::hash_index::HashIndex;
+use super::index_partitions::{self, IndexPartition};
+use super::key_prefixes;
+use super::key_index::KeyIndex;
+use super::prefix::PrefixExtractor;
+use super::properties::{self, SSTableProperties};
+use super::restart_points;
+use super::{
+    block_filters::{self, BlockFilter},
+    block_index, calculate_checksum, key_times, ChecksumKind, range_tombstones, tombstones,
+    BlockHandle, FragmentedRangeTombstones, RangeTombstone, Tombstone, BLOCK_FILTERS_SECTION,
+    BLOCK_INDEX_SECTION, COMPRESSION_DICT_SECTION, EXPIRIES_SECTION, HASH_INDEX_SECTION,
+    INDEX_PARTITIONS_SECTION, KEY_INDEX_SECTION, MAX_KEY_SIZE, MAX_VALUE_SIZE, PROPERTIES_SECTION,
+    RANGE_TOMBSTONES_SECTION, RESTART_POINTS_SECTION, SEQUENCES_SECTION, TOMBSTONES_SECTION,
+    VALUE_LOG_SECTION, WRITE_TIMES_SECTION,
+};
+use crate::bloom::{BloomFilter, PartitionedBloomBuilder, PartitionedBloomFilter};
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+
+/// A run of encoded entries, ready to be appended to an SSTable.
+///
+/// Blocks are produced by a `DataBlockBuilder` and consumed by
+/// `SSTableWriter::append_block`; they hold everything the writer needs to
+/// account for their entries without decoding them again.
+#[derive(Debug, Default)]
+pub struct DataBlock {
+    /// Entries in their on-disk encoding
+    pub(crate) bytes: Vec<u8>,
+    /// Keys in the order they were added, for the writer's filter
+    pub(crate) keys: Vec<String>,
+    /// Checksum of each entry
+    pub(crate) checksums: Vec<u32>,
+    /// Merkle leaf digest of each entry, over the uncompressed value
+    pub(crate) leaves: Vec<Digest>,
+    /// Whether keys within the block strictly ascend
+    pub(crate) keys_sorted: bool,
+    /// Total size of the keys and values before compression
+    pub(crate) raw_bytes: u64,
+    /// Write times recorded for entries in the block
+    pub(crate) write_times: Vec<(String, u64)>,
+    /// Expiry times recorded for entries in the block
+    pub(crate) expiries: Vec<(String, u64)>,
+    /// Sequences recorded for entries in the block
+    pub(crate) sequences: Vec<(String, u64)>,
+    /// Values stored as written by a compressing builder
+    pub(crate) uncompressed_values: u64,
+    /// Identifies the compression settings the values were encoded with
+    pub(crate) compression_id: Option<u32>,
+    /// Function the entry checksums were computed with
+    pub(crate) checksum_kind: ChecksumKind,
+    /// Restart interval keys were prefix-compressed against, if they were
+    pub(crate) prefix_restart_interval: Option<usize>,
+}
+
+impl DataBlock {
+    /// Number of entries in the block
+    pub fn entry_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns true if the block holds no entries
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Size of the block's encoded entries in bytes
+    pub fn encoded_len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// First key in the block
+    pub fn first_key(&self) -> Option<&str> {
+        self.keys.first().map(String::as_str)
+    }
+
+    /// Last key in the block
+    pub fn last_key(&self) -> Option<&str> {
+        self.keys.last().map(String::as_str)
+    }
+
+    /// Whether keys within the block strictly ascend
+    pub fn keys_sorted(&self) -> bool {
+        self.keys_sorted
+    }
+
+    /// File offsets of the block's entries once it is written at
+    /// `block_offset`, read from the key and value lengths in its bytes
+    pub(crate) fn entry_offsets(&self, block_offset: u64) -> Vec<u64> {
+        let length_at = |position: usize| {
+            u32::from_le_bytes(self.bytes[position..position + 4].try_into().unwrap()) as usize
+        };
+        let mut offsets = Vec::with_capacity(self.keys.len());
+        let mut position = 0usize;
+        for _ in 0..self.keys.len() {
+            offsets.push(block_offset + position as u64);
+            let key_len = length_at(position);
+            let value_len = length_at(position + 4 + key_len);
+            // Key length, key, value length, value and checksum
+            position += 4 + key_len + 4 + value_len + 4;
+        }
+        offsets
+    }
+}
+
+/// Tag compression settings so blocks encoded under different settings are
+/// never mixed in one file
+pub(crate) fn compression_id(compression: &Compression, dictionary: Option<&[u8]>) -> Option<u32> {
+    match compression {
+        Compression::None => None,
+        Compression::Zstd(_) => Some(dictionary.map_or(0, calculate_checksum)),
+        other => other.name().map(|name| calculate_checksum(name.as_bytes())),
+    }
+}
+
+/// One record handed to `SSTableWriter::write_all`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SSTableRecord {
+    /// A key's value, with its optional write and expiry times in
+    /// milliseconds since the Unix epoch, and the optional sequence of the
+    /// write that stored it
+    Put {
+        key: String,
+        value: Vec<u8>,
+        written_at_ms: Option<u64>,
+        expires_at_ms: Option<u64>,
+        sequence: Option<u64>,
+    },
+    /// A deleted key
+    Delete { key: String, tombstone: Tombstone },
+    /// A deleted key range
+    DeleteRange(RangeTombstone),
+}
+
+impl SSTableRecord {
+    /// A value without write or expiry times or a sequence
+    pub fn put(key: impl Into<String>, value: impl Into<Vec<u8>>) -> Self {
+        SSTableRecord::Put {
+            key: key.into(),
+            value: value.into(),
+            written_at_ms: None,
+            expires_at_ms: None,
+            sequence: None,
+        }
+    }
+}
+
+/// Encodes entries into `DataBlock`s in the SSTable entry format.
+///
+/// A builder is not tied to a file, so parallel subcompactions can each
+/// encode their own key range and hand the blocks to one writer in order.
+pub struct DataBlockBuilder {
+    block: DataBlock,
+    encoder: Option<ValueEncoder>,
+    /// Settings the encoder was created with, so equivalent builders can be made
+    compression: Compression,
+    dictionary: Option<Vec<u8>>,
+    compression_id: Option<u32>,
+    checksum_kind: ChecksumKind,
+    /// Store keys prefix-compressed against every this many entries' keys
+    prefix_restart_interval: Option<usize>,
+}
+
+impl Default for DataBlockBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DataBlockBuilder {
+    /// Create a builder that stores values uncompressed
+    pub fn new() -> Self {
+        DataBlockBuilder {
+            block: DataBlock {
+                keys_sorted: true,
+                ..Default::default()
+            },
+            encoder: None,
+            compression: Compression::None,
+            dictionary: None,
+            compression_id: None,
+            checksum_kind: ChecksumKind::default(),
+            prefix_restart_interval: None,
+        }
+    }
+
+    /// Create a builder that compresses values, priming Zstd with
+    /// `dictionary` if one was trained
+    pub fn with_compression(
+        compression: Compression,
+        dictionary: Option<&[u8]>,
+    ) -> io::Result<Self> {
+        let mut builder = Self::new();
+        builder.encoder = ValueEncoder::new(&compression, dictionary)?;
+        builder.compression_id = compression_id(&compression, dictionary);
+        builder.block.compression_id = builder.compression_id;
+        if builder.compression_id.is_some() {
+            builder.dictionary = dictionary.map(<[u8]>::to_vec);
+        }
+        builder.compression = compression;
+        Ok(builder)
+    }
+
+    /// Create an empty builder with the same compression settings,
+    /// checksum kind and key prefix compression
+    pub fn configured_like(&self) -> io::Result<Self> {
+        let mut builder = Self::with_compression(self.compression, self.dictionary.as_deref())?;
+        builder.set_checksum_kind(self.checksum_kind);
+        builder.set_key_prefix_compression(self.prefix_restart_interval);
+        Ok(builder)
+    }
+
+    /// Compute entry checksums with `kind` from the next entry on. Blocks
+    /// are only accepted by writers using the same kind.
+    pub fn set_checksum_kind(&mut self, kind: ChecksumKind) {
+        self.checksum_kind = kind;
+        self.block.checksum_kind = kind;
+    }
+
+    /// Store each key from the next block on as the length of the prefix it
+    /// shares with the key of every `restart_interval`th entry of the block,
+    /// followed by the rest of the key; `None` stores keys whole. Blocks are
+    /// only accepted by writers compressing keys the same way.
+    pub fn set_key_prefix_compression(&mut self, restart_interval: Option<usize>) {
+        self.prefix_restart_interval = restart_interval;
+        if self.block.is_empty() {
+            self.block.prefix_restart_interval = restart_interval;
+        }
+    }
+
+    /// Encode an entry along with its optional write and expiry times
+    pub fn add(
+        &mut self,
+        key: &str,
+        value: &[u8],
+        written_at_ms: Option<u64>,
+        expires_at_ms: Option<u64>,
+    ) -> io::Result<()> {
+        self.add_with_sequence(key, value, written_at_ms, expires_at_ms, None)
+    }
+
+    /// Encode an entry along with its optional write and expiry times and
+    /// the optional sequence of the write that stored it
+    pub fn add_with_sequence(
+        &mut self,
+        key: &str,
+        value: &[u8],
+        written_at_ms: Option<u64>,
+        expires_at_ms: Option<u64>,
+        sequence: Option<u64>,
+    ) -> io::Result<()> {
+        // Refuse entries the reader would later reject as corrupt
+        if key.len() > MAX_KEY_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Key length {} exceeds maximum of {} bytes",
+                    key.len(),
+                    MAX_KEY_SIZE
+                ),
+            ));
+        }
+        if value.len() > MAX_VALUE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Value length {} exceeds maximum of {} bytes",
+                    value.len(),
+                    MAX_VALUE_SIZE
+                ),
+            ));
+        }
+
+        // Keys at restart points are stored whole, and the rest against them
+        let stored_key = match self.block.prefix_restart_interval {
+            Some(interval) => {
+                let index = self.block.keys.len();
+                let restart_key = (!index.is_multiple_of(interval))
+                    .then(|| self.block.keys[index - index % interval].as_bytes());
+                let field = key_prefixes::encode(key.as_bytes(), restart_key);
+                if field.len() > MAX_KEY_SIZE {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "Prefix-compressed key length {} exceeds maximum of {} bytes",
+                            field.len(),
+                            MAX_KEY_SIZE
+                        ),
+                    ));
+                }
+                Some(field)
+            }
+            None => None,
+        };
+        let stored_key = stored_key.as_deref().unwrap_or(key.as_bytes());
+
+        // Compress the value if enabled; the digest still covers the original
+        let encoded = match &mut self.encoder {
+            Some(encoder) => {
+                let (stored, compressed) = encoder.encode(value)?;
+                if !compressed {
+                    self.block.uncompressed_values += 1;
+                }
+                Some(stored)
+            }
+            None => None,
+        };
+        let stored = encoded.as_deref().unwrap_or(value);
+        if stored.len() > MAX_VALUE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Compressed value length {} exceeds maximum of {} bytes",
+                    stored.len(),
+                    MAX_VALUE_SIZE
+                ),
+            ));
+        }
+
+        let block = &mut self.block;
+        block
+            .bytes
+            .extend_from_slice(&(stored_key.len() as u32).to_le_bytes());
+        block.bytes.extend_from_slice(stored_key);
+        block
+            .bytes
+            .extend_from_slice(&(stored.len() as u32).to_le_bytes());
+        block.bytes.extend_from_slice(stored);
+
+        // The checksum covers the stored value, so scans can verify entries
+        // without decompressing them, but the whole key
+        let checksum = self.checksum_kind.entry_checksum(key, stored);
+        block.bytes.extend_from_slice(&checksum.to_le_bytes());
+        block.checksums.push(checksum);
+        block.leaves.push(entry_digest(key, value));
+
+        if block.last_key().is_some_and(|last| last >= key) {
+            block.keys_sorted = false;
+        }
+        block.keys.push(key.to_string());
+        block.raw_bytes += (key.len() + value.len()) as u64;
+
+        if let Some(written_at_ms) = written_at_ms {
+            block.write_times.push((key.to_string(), written_at_ms));
+        }
+        if let Some(expires_at_ms) = expires_at_ms {
+            block.expiries.push((key.to_string(), expires_at_ms));
+        }
+        if let Some(sequence) = sequence {
+            block.sequences.push((key.to_string(), sequence));
+        }
+        Ok(())
+    }
+
+    /// Key of the last entry added since the last `finish`
+    pub fn last_key(&self) -> Option<&str> {
+        self.block.last_key()
+    }
+
+    /// Number of entries added since the last `finish`
+    pub fn entry_count(&self) -> usize {
+        self.block.entry_count()
+    }
+
+    /// Size of the entries encoded since the last `finish`
+    pub fn encoded_len(&self) -> usize {
+        self.block.encoded_len()
+    }
+
+    /// Take the entries added so far as a block, leaving the builder empty
+    /// but configured the same way
+    pub fn finish(&mut self) -> DataBlock {
+        std::mem::replace(
+            &mut self.block,
+            DataBlock {
+                keys_sorted: true,
+                compression_id: self.compression_id,
+                checksum_kind: self.checksum_kind,
+                prefix_restart_interval: self.prefix_restart_interval,
+                ..Default::default()
+            },
+        )
+    }
+}
+
+/// Builds the Bloom filter section of an SSTable from the keys written
+pub struct FilterBuilder {
+    /// Whether the file gets a filter at all
+    enabled: bool,
+    use_partitioned_bloom: bool,
+    false_positive_rate: f64,
+    /// Standard filter, filled as keys arrive
+    bloom_filter: Option<BloomFilter<String>>,
+    /// Partitioned filter supplied ready-made
+    partitioned_bloom_filter: Option<PartitionedBloomFilter<String>>,
+    /// Hashes of the keys for the partitioned filter, grouped by partition;
+    /// the filter is sized and filled in parallel at the end
+    partitioned_hashes: PartitionedBloomBuilder<String>,
+    /// Extractor whose prefixes are added alongside keys
+    prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    /// Last prefix added, so runs of keys sharing a prefix add it once
+    last_prefix: Option<String>,
+}
+
+impl FilterBuilder {
+    /// Create a builder for a filter sized for `expected_entries` keys
+    pub fn new(
+        expected_entries: usize,
+        use_bloom_filter: bool,
+        false_positive_rate: f64,
+        use_partitioned_bloom: bool,
+    ) -> Self {
+        // A regular bloom filter is filled as entries arrive; a partitioned one
+        // is built from the buffered key hashes in parallel at the end
+        let bloom_filter = if use_bloom_filter && !use_partitioned_bloom {
+            Some(BloomFilter::new(expected_entries, false_positive_rate))
+        } else {
+            None
+        };
+
+        FilterBuilder {
+            enabled: use_bloom_filter,
+            use_partitioned_bloom,
+            false_positive_rate,
+            bloom_filter,
+            partitioned_bloom_filter: None,
+            partitioned_hashes: PartitionedBloomBuilder::new(num_cpus::get()),
+            prefix_extractor: None,
+            last_prefix: None,
+        }
+    }
+
+    /// Whether the file gets a filter
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Also add each key's prefix under `extractor`
+    pub fn set_prefix_extractor(&mut self, extractor: Arc<dyn PrefixExtractor>) {
+        self.prefix_extractor = Some(extractor);
+    }
+
+    /// Extractor whose prefixes are added, if any
+    pub fn prefix_extractor(&self) -> Option<&Arc<dyn PrefixExtractor>> {
+        self.prefix_extractor.as_ref()
+    }
+
+    /// Add a key and, once per run of keys sharing it, its prefix
+    pub fn add_key(&mut self, key: &str) {
+        self.insert(key);
+
+        let prefix = self
+            .prefix_extractor
+            .as_ref()
+            .and_then(|extractor| extractor.prefix_of(key));
+        if let Some(prefix) = prefix
+            && self.last_prefix.as_deref() != Some(prefix)
+        {
+            let prefix = prefix.to_string();
+            self.insert(&prefix);
+            self.last_prefix = Some(prefix);
+        }
+    }
+
+    /// Add a run of keys in file order, as `add_key` would one at a time,
+    /// hashing them for the standard filter in parallel
+    pub fn add_keys(&mut self, keys: &[&str]) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut items = Vec::with_capacity(keys.len());
+        for &key in keys {
+            items.push(key.to_string());
+            let prefix = self
+                .prefix_extractor
+                .as_ref()
+                .and_then(|extractor| extractor.prefix_of(key));
+            if let Some(prefix) = prefix
+                && self.last_prefix.as_deref() != Some(prefix)
+            {
+                items.push(prefix.to_string());
+                self.last_prefix = Some(prefix.to_string());
+            }
+        }
+
+        if let Some(ref mut bloom) = self.bloom_filter {
+            bloom.insert_bulk(&items);
+        } else if self.use_partitioned_bloom {
+            self.partitioned_hashes.insert_bulk(&items);
+        }
+    }
+
+    /// Add an item to whichever filter is being built
+    fn insert(&mut self, item: &str) {
+        if let Some(ref mut bloom) = self.bloom_filter {
+            bloom.insert(&item.to_string());
+        } else if self.enabled && self.use_partitioned_bloom {
+            self.partitioned_hashes.insert(item);
+        }
+    }
+
+    /// Use a filter that was built elsewhere and already covers every key
+    pub fn set_bloom_filter(&mut self, filter: BloomFilter<String>) {
+        self.bloom_filter = Some(filter);
+        self.partitioned_bloom_filter = None;
+        self.partitioned_hashes.clear();
+        self.enabled = true;
+        self.use_partitioned_bloom = false;
+    }
+
+    /// Use a partitioned filter that was built elsewhere and already covers
+    /// every key
+    pub fn set_partitioned_bloom_filter(&mut self, filter: PartitionedBloomFilter<String>) {
+        self.bloom_filter = None;
+        self.partitioned_bloom_filter = Some(filter);
+        self.partitioned_hashes.clear();
+        self.enabled = true;
+        self.use_partitioned_bloom = true;
+    }
+
+    /// Encode the filter section, or `None` if the file gets no filter
+    pub fn finish(mut self) -> Option<Vec<u8>> {
+        if !self.enabled {
+            return None;
+        }
+
+        // Build the partitioned filter across all cores now that every key is known
+        if self.use_partitioned_bloom && self.partitioned_bloom_filter.is_none() {
+            let hashes = std::mem::replace(
+                &mut self.partitioned_hashes,
+                PartitionedBloomBuilder::new(1),
+            );
+            self.partitioned_bloom_filter = Some(hashes.finish(self.false_positive_rate));
+        }
+
+        let mut buf = Vec::new();
+        if let Some(ref bloom) = self.bloom_filter {
+            // Write standard bloom filter metadata and data
+            let bloom_size_bits = bloom.size_bits();
+            let bloom_num_hashes = bloom.num_hashes();
+
+            // First, write bloom filter type (0 = standard)
+            trace!("Writing standard bloom filter (type 0)");
+            buf.push(0u8);
+
+            // Write metadata
+            trace!("Writing size_bits: {}", bloom_size_bits);
+            buf.extend_from_slice(&(bloom_size_bits as u64).to_le_bytes());
+
+            trace!("Writing num_hashes: {}", bloom_num_hashes);
+            buf.extend_from_slice(&(bloom_num_hashes as u32).to_le_bytes());
+
+            // Write bloom filter data
+            let bits = bloom.get_bits();
+            trace!("Writing {} bytes of bloom data", bits.len());
+            buf.extend_from_slice(bits);
+        } else if let Some(ref bloom) = self.partitioned_bloom_filter {
+            // For partitioned bloom filter, we'll serialize each partition individually
+
+            // Get the number of partitions
+            let num_partitions = bloom.num_partitions();
+
+            // First write the filter type byte (1 = partitioned)
+            trace!("Writing partitioned bloom filter (type 1)");
+            buf.push(1u8);
+
+            // Then write number of partitions
+            trace!("Writing num_partitions: {}", num_partitions);
+            buf.extend_from_slice(&(num_partitions as u32).to_le_bytes());
+
+            // Since we're serializing actual partitions, we need to get size_bits/num_hashes from the first partition
+            // We'll just use these as metadata for compatibility - not actually used since each partition has its own
+            let size_bits = if let Some(partition) = bloom.get_partition(0) {
+                partition.size_bits()
+            } else {
+                100000 // Fallback value
+            };
+
+            let num_hashes = if let Some(partition) = bloom.get_partition(0) {
+                partition.num_hashes()
+            } else {
+                7 // Fallback value
+            };
+
+            trace!("Writing partition metadata size_bits: {}", size_bits);
+            buf.extend_from_slice(&(size_bits as u64).to_le_bytes());
+
+            trace!("Writing partition metadata num_hashes: {}", num_hashes);
+            buf.extend_from_slice(&(num_hashes as u32).to_le_bytes());
+
+            // Now write each partition's data
+            for i in 0..num_partitions {
+                if let Some(partition) = bloom.get_partition(i) {
+                    // Get this partition's bits
+                    let bits = partition.get_bits();
+
+                    // Write size of this partition's bit array
+                    let bits_len = bits.len() as u32;
+                    trace!("Writing partition {} bits length: {}", i, bits_len);
+                    buf.extend_from_slice(&bits_len.to_le_bytes());
+
+                    // Write the partition's bits
+                    trace!("Writing partition {} data ({} bytes)", i, bits.len());
+                    buf.extend_from_slice(bits);
+                } else {
+                    // Write empty partition as fallback
+                    trace!("Writing empty partition {}", i);
+                    buf.extend_from_slice(&0u32.to_le_bytes()); // 0 length
+                }
+            }
+        }
+        Some(buf)
+    }
+}
+
+/// Summary of the data section, recorded in the table properties
+pub(crate) struct DataSummary {
+    pub(crate) entry_count: u64,
+    pub(crate) keys_sorted: bool,
+    pub(crate) raw_bytes: u64,
+    pub(crate) data_bytes: u64,
+    pub(crate) content_digest: Digest,
+    /// Name of the extractor whose prefixes are in the filter, if any
+    pub(crate) prefix_extractor: Option<String>,
+    /// ID of the key the data blocks are encrypted with, if they are
+    pub(crate) encryption_key_id: Option<String>,
+    /// Function the meta sections are checksummed with
+    pub(crate) checksum_kind: ChecksumKind,
+}
+
+/// Builds the meta section of an SSTable: the table properties and the
+/// per-key sections that follow the data
+#[derive(Debug, Default)]
+pub(crate) struct MetaBuilder {
+    /// Write times recorded for entries, in milliseconds since the Unix epoch
+    write_times: Vec<(String, u64)>,
+    /// Expiry times recorded for entries, in milliseconds since the Unix epoch
+    expiries: Vec<(String, u64)>,
+    /// Sequences of the writes that stored entries
+    sequences: Vec<(String, u64)>,
+    /// Value log segment of each entry whose value is a pointer into one
+    value_log_segments: Vec<(String, u64)>,
+    /// Tombstones for keys deleted since the data in the file was written
+    tombstones: BTreeMap<String, Tombstone>,
+    /// Key ranges deleted since the data in the file was written
+    range_tombstones: FragmentedRangeTombstones,
+    /// Name of the codec values are compressed with, if any
+    codec: Option<&'static str>,
+    /// Values stored as written although the file is compressed
+    uncompressed_values: u64,
+    /// Smallest and largest key written, not counting tombstones
+    min_key: Option<String>,
+    max_key: Option<String>,
+    /// Dictionary values were compressed with, stored for readers
+    compression_dict: Option<Vec<u8>>,
+    /// Data blocks written so far, in file order
+    blocks: Vec<BlockHandle>,
+    /// Top level of a partitioned index, which replaces `blocks`
+    index_partitions: Option<Vec<IndexPartition>>,
+    /// Filter over each data block's keys, in file order
+    block_filters: Vec<BlockFilter>,
+    /// Hash table from keys to entry offsets, which replaces `blocks`
+    hash_index: Option<HashIndex>,
+    /// Index from every key to its entry's offset, for unsorted keys
+    key_index: Option<KeyIndex>,
+    /// Offsets of every `restart_interval`th entry of each block
+    restart_points: Vec<u64>,
+    /// Entries between restart points, if they are recorded
+    restart_interval: Option<usize>,
+    /// Whether keys are stored prefix-compressed against restart point keys
+    key_prefix_compression: bool,
+    /// Size at which the writer closed data blocks
+    data_block_size: Option<usize>,
+    /// Size at which the writer closed index partitions, if it partitioned
+    /// by size
+    index_block_size: Option<usize>,
+    /// WAL LSN up to which logged writes are reflected in the file
+    applied_lsn: Option<u64>,
+    /// Sequence assigned to the whole file when it was ingested
+    global_sequence: Option<u64>,
+    /// File number of the newest flush whose writes the file holds
+    flush_number: Option<u64>,
+    /// When the file was written, in seconds since the Unix epoch
+    created_at_secs: Option<u64>,
+    /// Level the file was written for
+    level: Option<u32>,
+    /// WAL checkpoint a flush wrote the file under
+    checkpoint_id: Option<u64>,
+    /// Properties set by the application, by name without the user prefix
+    user_properties: BTreeMap<String, String>,
+}
+
+impl MetaBuilder {
+    /// Record that a key was deleted
+    pub(crate) fn add_tombstone(&mut self, key: &str, tombstone: Tombstone) {
+        self.tombstones.insert(key.to_string(), tombstone);
+    }
+
+    /// Record that a key range was deleted
+    pub(crate) fn add_range_tombstone(&mut self, tombstone: RangeTombstone) {
+        self.range_tombstones.add(tombstone);
+    }
+
+    /// Record the compression applied to values and its dictionary
+    pub(crate) fn set_compression(
+        &mut self,
+        codec: Option<&'static str>,
+        dictionary: Option<Vec<u8>>,
+    ) {
+        self.codec = codec;
+        self.compression_dict = dictionary;
+    }
+
+    /// Widen the recorded key range to take in `key`
+    pub(crate) fn observe_key(&mut self, key: &str) {
+        if self.min_key.as_deref().is_none_or(|min| key < min) {
+            self.min_key = Some(key.to_string());
+        }
+        if self.max_key.as_deref().is_none_or(|max| key > max) {
+            self.max_key = Some(key.to_string());
+        }
+    }
+
+    /// Count values a compressing builder stored as written
+    pub(crate) fn add_uncompressed_values(&mut self, count: u64) {
+        self.uncompressed_values += count;
+    }
+
+    /// Record that keys are stored prefix-compressed
+    pub(crate) fn set_key_prefix_compression(&mut self) {
+        self.key_prefix_compression = true;
+    }
+
+    /// Record where a data block was written
+    pub(crate) fn add_block(&mut self, block: BlockHandle) {
+        self.blocks.push(block);
+    }
+
+    /// Record the offsets of every `interval`th entry of a block, starting
+    /// with its first
+    pub(crate) fn add_restart_points(&mut self, interval: usize, offsets: &[u64]) {
+        self.restart_interval = Some(interval);
+        self.restart_points
+            .extend(offsets.iter().step_by(interval).copied());
+    }
+
+    /// Record the block and index partition sizes the file was written with
+    pub(crate) fn set_block_sizes(
+        &mut self,
+        data_block_size: usize,
+        index_block_size: Option<usize>,
+    ) {
+        self.data_block_size = Some(data_block_size);
+        self.index_block_size = index_block_size;
+    }
+
+    /// Record the filter over a data block's keys
+    pub(crate) fn add_block_filter(&mut self, filter: BlockFilter) {
+        self.block_filters.push(filter);
+    }
+
+    /// Record a hash index over the entries; point lookups use it instead
+    /// of blocks, so the block index is not written
+    pub(crate) fn set_hash_index(&mut self, index: HashIndex) {
+        self.hash_index = Some(index);
+    }
+
+    /// Record an index from every key to its entry's offset, for files
+    /// whose keys are unsorted
+    pub(crate) fn set_key_index(&mut self, index: KeyIndex) {
+        self.key_index = Some(index);
+    }
+
+    /// Record the top level of a partitioned index; the partitions hold the
+    /// blocks, so the block index is not written
+    pub(crate) fn set_index_partitions(&mut self, partitions: Vec<IndexPartition>) {
+        self.index_partitions = Some(partitions);
+    }
+
+    /// Record the WAL LSN up to which logged writes are in the file
+    pub(crate) fn set_applied_lsn(&mut self, lsn: u64) {
+        self.applied_lsn = Some(lsn);
+    }
+
+    /// Record the sequence assigned to the file by an ingest
+    pub(crate) fn set_global_sequence(&mut self, sequence: u64) {
+        self.global_sequence = Some(sequence);
+    }
+
+    /// Record the file number of the newest flush whose writes the file holds
+    pub(crate) fn set_flush_number(&mut self, flush_number: u64) {
+        self.flush_number = Some(flush_number);
+    }
+
+    /// Record when the file was written
+    pub(crate) fn set_created_at(&mut self, created_at_secs: u64) {
+        self.created_at_secs = Some(created_at_secs);
+    }
+
+    /// Record the level the file was written for
+    pub(crate) fn set_level(&mut self, level: u32) {
+        self.level = Some(level);
+    }
+
+    /// Record the WAL checkpoint a flush wrote the file under
+    pub(crate) fn set_checkpoint_id(&mut self, checkpoint_id: u64) {
+        self.checkpoint_id = Some(checkpoint_id);
+    }
+
+    /// Record that the value of `key` points into value log `segment`
+    pub(crate) fn add_value_pointer(&mut self, key: &str, segment: u64) {
+        self.value_log_segments.push((key.to_string(), segment));
+    }
+
+    /// Record a property set by the application
+    pub(crate) fn set_user_property(&mut self, name: &str, value: &str) {
+        self.user_properties
+            .insert(name.to_string(), value.to_string());
+    }
+
+    /// Take over the per-key times and sequences recorded in a block
+    pub(crate) fn extend_times(
+        &mut self,
+        write_times: Vec<(String, u64)>,
+        expiries: Vec<(String, u64)>,
+        sequences: Vec<(String, u64)>,
+    ) {
+        self.write_times.extend(write_times);
+        self.expiries.extend(expiries);
+        self.sequences.extend(sequences);
+    }
+
+    /// Encode the meta section: a count followed by named, checksummed blocks
+    pub(crate) fn finish(mut self, summary: DataSummary) -> Vec<u8> {
+        // Tombstones count towards the key range, so lookups of deleted keys
+        // still consult the file
+        let first = self.tombstones.keys().next().cloned();
+        let last = self.tombstones.keys().next_back().cloned();
+        if let (Some(first), Some(last)) = (first, last) {
+            self.observe_key(&first);
+            self.observe_key(&last);
+        }
+
+        let mut properties = SSTableProperties::new();
+        if let (Some(min), Some(max)) = (&self.min_key, &self.max_key) {
+            properties.insert(properties::PROP_MIN_KEY, min);
+            properties.insert(properties::PROP_MAX_KEY, max);
+        }
+        properties.insert(
+            properties::PROP_CONTENT_DIGEST,
+            summary.content_digest.to_hex(),
+        );
+        properties.insert(properties::PROP_NUM_ENTRIES, summary.entry_count);
+        properties.insert(properties::PROP_KEYS_SORTED, summary.keys_sorted);
+        properties.insert(properties::PROP_RAW_BYTES, summary.raw_bytes);
+        properties.insert(
+            properties::PROP_NUM_TOMBSTONES,
+            self.tombstones.len() as u64,
+        );
+        properties.insert(
+            properties::PROP_NUM_RANGE_DELETIONS,
+            self.range_tombstones.fragments().len() as u64,
+        );
+        properties.insert(properties::PROP_DATA_BYTES, summary.data_bytes);
+        if let Some(extractor) = summary.prefix_extractor {
+            properties.insert(properties::PROP_PREFIX_EXTRACTOR, extractor);
+        }
+        if let Some(key_id) = &summary.encryption_key_id {
+            properties.insert(properties::PROP_ENCRYPTION_KEY_ID, key_id);
+        }
+        let expiry_times = self
+            .expiries
+            .iter()
+            .map(|(_, expires_at_ms)| *expires_at_ms);
+        if let (Some(min), Some(max)) = (expiry_times.clone().min(), expiry_times.max()) {
+            properties.insert(properties::PROP_MIN_EXPIRY, min);
+            properties.insert(properties::PROP_MAX_EXPIRY, max);
+        }
+        if let Some(lsn) = self.applied_lsn {
+            properties.insert(properties::PROP_APPLIED_LSN, lsn);
+        }
+        if let Some(sequence) = self.global_sequence {
+            properties.insert(properties::PROP_GLOBAL_SEQUENCE, sequence);
+        }
+        if let Some(flush_number) = self.flush_number {
+            properties.insert(properties::PROP_FLUSH_NUMBER, flush_number);
+        }
+        if let Some(max) = self.sequences.iter().map(|(_, sequence)| *sequence).max() {
+            properties.insert(properties::PROP_MAX_SEQUENCE, max);
+        }
+        if let Some(codec) = self.codec {
+            properties.insert(properties::PROP_COMPRESSION, codec);
+            properties.insert(properties::PROP_VALUE_FLAGS, 1);
+            properties.insert(
+                properties::PROP_UNCOMPRESSED_VALUES,
+                self.uncompressed_values,
+            );
+        }
+        if let Some(size) = self.data_block_size {
+            properties.insert(properties::PROP_DATA_BLOCK_SIZE, size);
+        }
+        if let Some(size) = self.index_block_size {
+            properties.insert(properties::PROP_INDEX_BLOCK_SIZE, size);
+        }
+        if let Some(interval) = self.restart_interval {
+            properties.insert(properties::PROP_RESTART_INTERVAL, interval);
+        }
+        if self.key_prefix_compression {
+            properties.insert(properties::PROP_KEY_PREFIX_COMPRESSION, 1);
+        }
+        if let Some(created_at_secs) = self.created_at_secs {
+            properties.insert(properties::PROP_CREATED_AT, created_at_secs);
+        }
+        if let Some(level) = self.level {
+            properties.insert(properties::PROP_LEVEL, level);
+        }
+        if let Some(checkpoint_id) = self.checkpoint_id {
+            properties.insert(properties::PROP_CHECKPOINT_ID, checkpoint_id);
+        }
+        for (name, value) in &self.user_properties {
+            properties.insert(
+                &format!("{}{}", properties::USER_PROPERTY_PREFIX, name),
+                value,
+            );
+        }
+
+        let mut sections = vec![(PROPERTIES_SECTION, properties.encode())];
+        if let Some(dictionary) = self.compression_dict {
+            sections.push((COMPRESSION_DICT_SECTION, dictionary));
+        }
+        if !self.write_times.is_empty() {
+            sections.push((WRITE_TIMES_SECTION, key_times::encode(&self.write_times)));
+        }
+        if !self.expiries.is_empty() {
+            sections.push((EXPIRIES_SECTION, key_times::encode(&self.expiries)));
+        }
+        if !self.sequences.is_empty() {
+            sections.push((SEQUENCES_SECTION, key_times::encode(&self.sequences)));
+        }
+        if !self.value_log_segments.is_empty() {
+            sections.push((
+                VALUE_LOG_SECTION,
+                key_times::encode(&self.value_log_segments),
+            ));
+        }
+        if !self.tombstones.is_empty() {
+            sections.push((
+                TOMBSTONES_SECTION,
+                tombstones::encode(self.tombstones.iter()),
+            ));
+        }
+        if !self.range_tombstones.is_empty() {
+            sections.push((
+                RANGE_TOMBSTONES_SECTION,
+                range_tombstones::encode(&self.range_tombstones),
+            ));
+        }
+        if let Some(index) = &self.hash_index {
+            sections.push((HASH_INDEX_SECTION, index.encode()));
+        } else if let Some(partitions) = &self.index_partitions {
+            sections.push((
+                INDEX_PARTITIONS_SECTION,
+                index_partitions::encode(partitions),
+            ));
+        } else if !self.blocks.is_empty() {
+            sections.push((BLOCK_INDEX_SECTION, block_index::encode(&self.blocks)));
+        }
+        if let Some(index) = &self.key_index {
+            sections.push((KEY_INDEX_SECTION, index.encode()));
+        }
+        if !self.restart_points.is_empty() {
+            sections.push((
+                RESTART_POINTS_SECTION,
+                restart_points::encode(&self.restart_points),
+            ));
+        }
+        if !self.block_filters.is_empty() {
+            sections.push((
+                BLOCK_FILTERS_SECTION,
+                block_filters::encode(&self.block_filters),
+            ));
+        }
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+        for (name, data) in &sections {
+            buf.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            buf.extend_from_slice(name.as_bytes());
+            buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            buf.extend_from_slice(data);
+            buf.extend_from_slice(&summary.checksum_kind.checksum(data).to_le_bytes());
+        }
+        buf
+    }
+}
@@ -0,0 +1,194 @@
+use crate::locks;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Name recorded in the compression property for LZ4-compressed files
+pub const LZ4_COMPRESSION_NAME: &str = "lz4";
+/// Name recorded in the compression property for Snappy-compressed files
+pub const SNAPPY_COMPRESSION_NAME: &str = "snappy";
+
+/// Codecs shipped with the crate, the feature that compiles each in, and
+/// whether it was
+const BUILTIN_CODECS: &[(&str, &str, bool)] = &[
+    (
+        super::compression::ZSTD_COMPRESSION_NAME,
+        "zstd",
+        cfg!(feature = "zstd"),
+    ),
+    (LZ4_COMPRESSION_NAME, "lz4", cfg!(feature = "lz4")),
+    (SNAPPY_COMPRESSION_NAME, "snappy", cfg!(feature = "snappy")),
+];
+
+/// A value compression codec, identified in SSTables by its name.
+///
+/// Codecs compress each value on its own. Implement this to store values
+/// with a codec the crate does not ship, and register it with
+/// `CodecRegistry::global()` before writing or reading files that use it.
+pub trait Codec: fmt::Debug + Send + Sync {
+    /// Name recorded in files compressed with the codec
+    fn name(&self) -> &str;
+
+    /// Compress one value
+    fn compress(&self, value: &[u8]) -> io::Result<Vec<u8>>;
+
+    /// Restore a value compressed by `compress`. Damaged input, or input
+    /// that would decompress to more than `max_len` bytes, fails with
+    /// `ErrorKind::InvalidData`.
+    fn decompress(&self, stored: &[u8], max_len: usize) -> io::Result<Vec<u8>>;
+}
+
+/// Codecs available to SSTable writers and readers, by name.
+///
+/// Zstd is handled by the crate itself, with dictionary support, so a codec
+/// registered under its name is never consulted.
+#[derive(Default)]
+pub struct CodecRegistry {
+    codecs: RwLock<HashMap<String, Arc<dyn Codec>>>,
+}
+
+impl fmt::Debug for CodecRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CodecRegistry")
+            .field("codecs", &self.names())
+            .finish()
+    }
+}
+
+impl CodecRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry holding the built-in codecs compiled in
+    pub fn with_builtin_codecs() -> Self {
+        let registry = Self::new();
+        #[cfg(feature = "lz4")]
+        registry.register(Lz4Codec);
+        #[cfg(feature = "snappy")]
+        registry.register(SnappyCodec);
+        registry
+    }
+
+    /// The registry SSTable writers and readers look codecs up in, holding
+    /// the built-in codecs compiled in
+    pub fn global() -> &'static CodecRegistry {
+        static GLOBAL: OnceLock<CodecRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(Self::with_builtin_codecs)
+    }
+
+    /// Add a codec, returning the one it replaced under the same name
+    pub fn register(&self, codec: impl Codec + 'static) -> Option<Arc<dyn Codec>> {
+        let codec: Arc<dyn Codec> = Arc::new(codec);
+        locks::recover(self.codecs.write()).insert(codec.name().to_string(), codec)
+    }
+
+    /// Remove the codec registered under `name`
+    pub fn remove(&self, name: &str) -> Option<Arc<dyn Codec>> {
+        locks::recover(self.codecs.write()).remove(name)
+    }
+
+    /// The codec registered under `name`.
+    ///
+    /// A missing codec fails with `ErrorKind::Unsupported`, naming the
+    /// feature to enable if it is a built-in codec that was not compiled in.
+    pub fn get(&self, name: &str) -> io::Result<Arc<dyn Codec>> {
+        locks::checked(self.codecs.read(), "codec registry")?
+            .get(name)
+            .cloned()
+            .ok_or_else(|| unavailable(name))
+    }
+
+    /// Names of the registered codecs, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = locks::recover(self.codecs.read()).keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+/// Error for a codec that is neither compiled in nor registered
+pub(crate) fn unavailable(name: &str) -> io::Error {
+    let message = match BUILTIN_CODECS
+        .iter()
+        .find(|(codec, _, compiled)| *codec == name && !compiled)
+    {
+        Some((_, feature, _)) => format!(
+            "SSTable codec '{}' is not compiled in; enable the `{}` feature of lsmer",
+            name, feature
+        ),
+        None => format!("SSTable codec '{}' is not registered", name),
+    };
+    io::Error::new(io::ErrorKind::Unsupported, message)
+}
+
+/// Check a decompressed length read from stored bytes against the limit
+#[cfg(any(feature = "lz4", feature = "snappy"))]
+fn check_len(len: usize, max_len: usize) -> io::Result<()> {
+    if len > max_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Compressed value would decompress to {} bytes", len),
+        ));
+    }
+    Ok(())
+}
+
+/// LZ4 block compression, with the value's length prepended
+#[cfg(feature = "lz4")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lz4Codec;
+
+#[cfg(feature = "lz4")]
+impl Codec for Lz4Codec {
+    fn name(&self) -> &str {
+        LZ4_COMPRESSION_NAME
+    }
+
+    fn compress(&self, value: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(lz4_flex::block::compress_prepend_size(value))
+    }
+
+    fn decompress(&self, stored: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+        let len = stored
+            .get(..4)
+            .map(|prefix| u32::from_le_bytes(prefix.try_into().unwrap()) as usize)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Compressed value is missing its length",
+                )
+            })?;
+        check_len(len, max_len)?;
+        lz4_flex::block::decompress_size_prepended(stored)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Snappy raw compression
+#[cfg(feature = "snappy")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SnappyCodec;
+
+#[cfg(feature = "snappy")]
+impl Codec for SnappyCodec {
+    fn name(&self) -> &str {
+        SNAPPY_COMPRESSION_NAME
+    }
+
+    fn compress(&self, value: &[u8]) -> io::Result<Vec<u8>> {
+        snap::raw::Encoder::new()
+            .compress_vec(value)
+            .map_err(io::Error::other)
+    }
+
+    fn decompress(&self, stored: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+        let invalid = |e: snap::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+        check_len(snap::raw::decompress_len(stored).map_err(invalid)?, max_len)?;
+        snap::raw::Decoder::new()
+            .decompress_vec(stored)
+            .map_err(invalid)
+    }
+}
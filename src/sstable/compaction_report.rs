@@ -0,0 +1,326 @@
+use super::{CompactionOptions, SSTableReader};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+
+/// Decisions kept when not every decision is recorded, so a report written
+/// on failure shows what the compaction was doing when it stopped
+const RECENT_DECISIONS: usize = 100;
+
+/// Path of the report written alongside a compaction's output
+pub fn report_path(output_path: &str) -> String {
+    format!("{}.compaction.json", output_path)
+}
+
+/// What a compaction did with one version of a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Decision {
+    /// Written to the output
+    Kept,
+    /// Dropped because a newer input holds the same key
+    Shadowed,
+    /// Dropped because a newer input holds a tombstone for the key
+    DeletedLater,
+    /// Expired, so written as a tombstone instead of a value
+    Expired,
+    /// A tombstone carried into the output
+    TombstoneCarried,
+    /// A tombstone dropped for having outlived its retention period
+    TombstoneDropped,
+}
+
+impl Decision {
+    const ALL: [Decision; 6] = [
+        Decision::Kept,
+        Decision::Shadowed,
+        Decision::DeletedLater,
+        Decision::Expired,
+        Decision::TombstoneCarried,
+        Decision::TombstoneDropped,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Decision::Kept => "kept",
+            Decision::Shadowed => "shadowed",
+            Decision::DeletedLater => "deleted_later",
+            Decision::Expired => "expired",
+            Decision::TombstoneCarried => "tombstone_carried",
+            Decision::TombstoneDropped => "tombstone_dropped",
+        }
+    }
+}
+
+/// What is known about one compaction input
+#[derive(Debug, Default)]
+struct InputSummary {
+    path: String,
+    opened: bool,
+    file_number: Option<u64>,
+    entry_count: u64,
+    keys_sorted: bool,
+    tombstone_count: usize,
+    /// Range of write times recorded in the file, in milliseconds
+    write_times: Option<(u64, u64)>,
+    /// Smallest and largest keys merged from the file
+    key_range: Option<(String, String)>,
+}
+
+/// Record of a compaction, written as JSON on failure or when
+/// `CompactionOptions::debug_dump` is set
+#[derive(Debug)]
+pub(crate) struct CompactionTrace {
+    output_path: String,
+    started_at_ms: u64,
+    options: String,
+    inputs: Vec<InputSummary>,
+    merge_strategy: Option<&'static str>,
+    filter_merged: Option<bool>,
+    counts: [u64; Decision::ALL.len()],
+    /// Every decision when `record_all` is set, otherwise the most recent
+    decisions: VecDeque<(String, usize, Decision)>,
+    record_all: bool,
+}
+
+impl CompactionTrace {
+    pub(crate) fn new(
+        sstable_paths: &[String],
+        output_path: &str,
+        options: &CompactionOptions,
+        started_at_ms: u64,
+    ) -> Self {
+        CompactionTrace {
+            output_path: output_path.to_string(),
+            started_at_ms,
+            options: Self::describe_options(options),
+            inputs: sstable_paths
+                .iter()
+                .map(|path| InputSummary {
+                    path: path.clone(),
+                    ..InputSummary::default()
+                })
+                .collect(),
+            merge_strategy: None,
+            filter_merged: None,
+            counts: [0; Decision::ALL.len()],
+            decisions: VecDeque::new(),
+            record_all: options.debug_dump,
+        }
+    }
+
+    /// Record what the opened reader of input `input` says about the file
+    pub(crate) fn opened(&mut self, input: usize, reader: &SSTableReader) {
+        let times = reader.write_times.values().copied();
+        let summary = &mut self.inputs[input];
+        summary.opened = true;
+        summary.file_number = reader.file_number();
+        summary.entry_count = reader.entry_count();
+        summary.keys_sorted = reader.keys_sorted();
+        summary.tombstone_count = reader.tombstones.len();
+        summary.write_times = times.clone().min().zip(times.max());
+    }
+
+    /// Record how the inputs are merged and whether the output's filter was
+    /// built by merging the inputs' filters
+    pub(crate) fn planned(&mut self, merge_strategy: &'static str, filter_merged: Option<bool>) {
+        self.merge_strategy = Some(merge_strategy);
+        self.filter_merged = filter_merged;
+    }
+
+    /// Record that a version of `key` from `input` was merged
+    pub(crate) fn saw(&mut self, input: usize, key: &str) {
+        match &mut self.inputs[input].key_range {
+            Some((first, last)) => {
+                if key < first.as_str() {
+                    *first = key.to_string();
+                }
+                if key > last.as_str() {
+                    *last = key.to_string();
+                }
+            }
+            range @ None => *range = Some((key.to_string(), key.to_string())),
+        }
+    }
+
+    /// Record a decision made for the version of `key` from `input`
+    pub(crate) fn decide(&mut self, key: &str, input: usize, decision: Decision) {
+        self.counts[decision as usize] += 1;
+        if !self.record_all && self.decisions.len() == RECENT_DECISIONS {
+            self.decisions.pop_front();
+        }
+        self.decisions.push_back((key.to_string(), input, decision));
+    }
+
+    /// Write the report next to the output, describing how the compaction
+    /// ended
+    pub(crate) fn write<T>(&self, result: &io::Result<T>, finished_at_ms: u64) -> io::Result<()> {
+        fs::write(
+            report_path(&self.output_path),
+            self.to_json(result, finished_at_ms),
+        )
+    }
+
+    fn to_json<T>(&self, result: &io::Result<T>, finished_at_ms: u64) -> String {
+        let mut out = String::from("{\n");
+        let _ = writeln!(out, "  \"output\": {},", json_string(&self.output_path));
+        let _ = writeln!(
+            out,
+            "  \"status\": {},",
+            json_string(if result.is_ok() { "ok" } else { "failed" })
+        );
+        let error = match result {
+            Ok(_) => "null".to_string(),
+            Err(e) => json_string(&e.to_string()),
+        };
+        let _ = writeln!(out, "  \"error\": {},", error);
+        let _ = writeln!(out, "  \"started_at_ms\": {},", self.started_at_ms);
+        let _ = writeln!(out, "  \"finished_at_ms\": {},", finished_at_ms);
+        let _ = writeln!(out, "  \"options\": {},", self.options);
+        let _ = writeln!(
+            out,
+            "  \"merge_strategy\": {},",
+            self.merge_strategy.map_or("null".to_string(), json_string)
+        );
+        let _ = writeln!(
+            out,
+            "  \"filter_merged\": {},",
+            self.filter_merged
+                .map_or("null".to_string(), |merged| merged.to_string())
+        );
+
+        out.push_str("  \"inputs\": [\n");
+        for (i, input) in self.inputs.iter().enumerate() {
+            let separator = if i + 1 < self.inputs.len() { "," } else { "" };
+            let _ = writeln!(out, "    {}{}", input.to_json(i), separator);
+        }
+        out.push_str("  ],\n");
+
+        let counts: Vec<String> = Decision::ALL
+            .iter()
+            .map(|decision| {
+                format!(
+                    "\"{}\": {}",
+                    decision.name(),
+                    self.counts[*decision as usize]
+                )
+            })
+            .collect();
+        let _ = writeln!(out, "  \"decision_counts\": {{{}}},", counts.join(", "));
+        let _ = writeln!(out, "  \"all_decisions_recorded\": {},", self.record_all);
+
+        out.push_str("  \"decisions\": [\n");
+        for (i, (key, input, decision)) in self.decisions.iter().enumerate() {
+            let separator = if i + 1 < self.decisions.len() {
+                ","
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                out,
+                "    {{\"key\": {}, \"input\": {}, \"decision\": \"{}\"}}{}",
+                json_string(key),
+                input,
+                decision.name(),
+                separator
+            );
+        }
+        out.push_str("  ]\n}\n");
+        out
+    }
+
+    fn describe_options(options: &CompactionOptions) -> String {
+        let compression = options.compression.name().unwrap_or("none");
+        format!(
+            "{{\"use_bloom_filter\": {}, \"false_positive_rate\": {}, \
+             \"use_partitioned_bloom\": {}, \"delete_originals\": {}, \
+             \"tombstone_retention_ms\": {}, \"oldest_snapshot_ms\": {}, \
+             \"compression\": \"{}\", \
+             \"prefix_extractor\": {}, \"partitioned_index\": {}, \"block_filters\": {}, \"hash_index\": {}, \
+             \"block_size\": {}, \"index_block_size\": {}, \"restart_interval\": {}}}",
+            options.use_bloom_filter,
+            options.false_positive_rate,
+            options.use_partitioned_bloom,
+            options.delete_originals,
+            options
+                .tombstone_retention
+                .map_or("null".to_string(), |retention| retention
+                    .as_millis()
+                    .to_string()),
+            options
+                .oldest_snapshot
+                .map_or("null".to_string(), |at_ms| at_ms.to_string()),
+            compression,
+            options
+                .prefix_extractor
+                .as_ref()
+                .map_or("null".to_string(), |extractor| json_string(
+                    &extractor.name()
+                )),
+            options
+                .partitioned_index
+                .map_or("null".to_string(), |blocks| blocks.to_string()),
+            options
+                .block_filters
+                .map_or("null".to_string(), |rate| rate.to_string()),
+            options.hash_index,
+            options
+                .block_size
+                .map_or("null".to_string(), |size| size.to_string()),
+            options
+                .index_block_size
+                .map_or("null".to_string(), |size| size.to_string()),
+            options
+                .restart_interval
+                .map_or("null".to_string(), |interval| interval.to_string()),
+        )
+    }
+}
+
+impl InputSummary {
+    fn to_json(&self, index: usize) -> String {
+        let optional = |value: Option<u64>| value.map_or("null".to_string(), |v| v.to_string());
+        let (first_key, last_key) = match &self.key_range {
+            Some((first, last)) => (json_string(first), json_string(last)),
+            None => ("null".to_string(), "null".to_string()),
+        };
+        format!(
+            "{{\"index\": {}, \"path\": {}, \"opened\": {}, \"file_number\": {}, \
+             \"entry_count\": {}, \"keys_sorted\": {}, \"tombstone_count\": {}, \
+             \"min_write_time_ms\": {}, \"max_write_time_ms\": {}, \
+             \"first_key\": {}, \"last_key\": {}}}",
+            index,
+            json_string(&self.path),
+            self.opened,
+            optional(self.file_number),
+            self.entry_count,
+            self.keys_sorted,
+            self.tombstone_count,
+            optional(self.write_times.map(|(min, _)| min)),
+            optional(self.write_times.map(|(_, max)| max)),
+            first_key,
+            last_key,
+        )
+    }
+}
+
+/// Quote a string for JSON
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,407 @@
+use super::codec::{self, Codec, CodecRegistry};
+use super::MAX_VALUE_SIZE;
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+#[cfg(feature = "zstd")]
+use zstd::bulk::{Compressor, Decompressor};
+#[cfg(feature = "zstd")]
+use zstd::dict::DecoderDictionary;
+#[cfg(feature = "zstd")]
+use zstd::zstd_safe::CParameter;
+
+/// Name recorded in the compression property for Zstd-compressed files
+pub const ZSTD_COMPRESSION_NAME: &str = "zstd";
+
+/// Leading byte of a value in a file with value flags that is stored as
+/// written
+const VALUE_STORED_RAW: u8 = 0;
+/// Leading byte of a value in a file with value flags that is compressed
+const VALUE_COMPRESSED: u8 = 1;
+
+/// Magic bytes opening data that is already compressed: gzip, zlib's
+/// common header, Zstd, xz, bzip2, LZ4 frames, zip, 7z, PNG, JPEG and WebP
+/// or other RIFF containers
+const COMPRESSED_MAGIC: &[&[u8]] = &[
+    &[0x1f, 0x8b],
+    &[0x78, 0x9c],
+    &[0x28, 0xb5, 0x2f, 0xfd],
+    &[0xfd, b'7', b'z', b'X', b'Z', 0x00],
+    b"BZh",
+    &[0x04, 0x22, 0x4d, 0x18],
+    &[b'P', b'K', 0x03, 0x04],
+    &[b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c],
+    &[0x89, b'P', b'N', b'G'],
+    &[0xff, 0xd8, 0xff],
+    b"RIFF",
+];
+/// Bytes sampled from the front of a value to estimate its entropy
+const ENTROPY_SAMPLE_BYTES: usize = 4096;
+/// Shortest sample whose entropy is trusted; shorter ones understate it
+const MIN_ENTROPY_SAMPLE_BYTES: usize = 1024;
+/// Entropy, in bits per byte, above which a value is not worth compressing
+const INCOMPRESSIBLE_BITS_PER_BYTE: f64 = 7.5;
+
+/// Whether `value` looks not worth compressing: it starts with the magic
+/// bytes of a compressed format, or a sample from its front has close to
+/// eight bits of entropy per byte.
+///
+/// Writers store such values as written, without spending time on them.
+pub fn looks_incompressible(value: &[u8]) -> bool {
+    if COMPRESSED_MAGIC
+        .iter()
+        .any(|magic| value.starts_with(magic))
+    {
+        return true;
+    }
+
+    let sample = &value[..value.len().min(ENTROPY_SAMPLE_BYTES)];
+    if sample.len() < MIN_ENTROPY_SAMPLE_BYTES {
+        return false;
+    }
+    let mut counts = [0u32; 256];
+    for &byte in sample {
+        counts[byte as usize] += 1;
+    }
+    let len = sample.len() as f64;
+    let entropy: f64 = counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum();
+    entropy > INCOMPRESSIBLE_BITS_PER_BYTE
+}
+
+/// Codec applied to values stored in an SSTable.
+///
+/// Every codec but `None` needs its cargo feature, or for `Custom` a codec
+/// registered with `CodecRegistry::global()`; writing or reading a file
+/// with a codec that is missing fails with `ErrorKind::Unsupported`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Values are stored as written
+    #[default]
+    None,
+    /// Each value is compressed with Zstd, optionally against a dictionary
+    /// trained for the file
+    Zstd(ZstdOptions),
+    /// Each value is compressed with LZ4
+    Lz4,
+    /// Each value is compressed with Snappy
+    Snappy,
+    /// Each value is compressed with the codec registered under this name
+    Custom(&'static str),
+}
+
+impl Compression {
+    /// Zstd at its default level, without a dictionary
+    pub fn zstd() -> Self {
+        Compression::Zstd(ZstdOptions::default())
+    }
+
+    /// Name recorded in files compressed with the codec; `None` when values
+    /// are stored as written
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Zstd(_) => Some(ZSTD_COMPRESSION_NAME),
+            Compression::Lz4 => Some(codec::LZ4_COMPRESSION_NAME),
+            Compression::Snappy => Some(codec::SNAPPY_COMPRESSION_NAME),
+            Compression::Custom(name) => Some(name),
+        }
+    }
+}
+
+/// Settings for Zstd value compression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZstdOptions {
+    /// Compression level
+    pub level: i32,
+    /// Largest dictionary to train per file; 0 disables dictionaries
+    pub max_dictionary_bytes: usize,
+    /// How many bytes of values to sample when training a dictionary
+    pub max_sample_bytes: usize,
+}
+
+impl Default for ZstdOptions {
+    fn default() -> Self {
+        ZstdOptions {
+            level: 3,
+            max_dictionary_bytes: 0,
+            max_sample_bytes: 0,
+        }
+    }
+}
+
+impl ZstdOptions {
+    /// Set the compression level
+    pub fn with_level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Train a dictionary of up to `max_dictionary_bytes` for each file from
+    /// up to `max_sample_bytes` of its values
+    pub fn with_dictionary(mut self, max_dictionary_bytes: usize, max_sample_bytes: usize) -> Self {
+        self.max_dictionary_bytes = max_dictionary_bytes;
+        self.max_sample_bytes = max_sample_bytes;
+        self
+    }
+
+    /// Whether files get a trained dictionary
+    pub fn trains_dictionary(&self) -> bool {
+        self.max_dictionary_bytes > 0 && self.max_sample_bytes > 0
+    }
+
+    /// Train a dictionary from sample values, stopping once
+    /// `max_sample_bytes` have been taken.
+    ///
+    /// Returns `None` if dictionaries are disabled or Zstd cannot train one
+    /// from the samples, e.g. because there are too few of them.
+    pub fn train_dictionary<'a, I>(&self, samples: I) -> Option<Vec<u8>>
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        if !self.trains_dictionary() {
+            return None;
+        }
+
+        let mut taken = Vec::new();
+        let mut total = 0;
+        for sample in samples {
+            if total >= self.max_sample_bytes {
+                break;
+            }
+            if sample.is_empty() {
+                continue;
+            }
+            total += sample.len();
+            taken.push(sample);
+        }
+
+        #[cfg(feature = "zstd")]
+        return zstd::dict::from_samples(&taken, self.max_dictionary_bytes).ok();
+        #[cfg(not(feature = "zstd"))]
+        None
+    }
+}
+
+/// Compresses values as an SSTable is written
+pub(crate) enum ValueEncoder {
+    #[cfg(feature = "zstd")]
+    Zstd(Compressor<'static>),
+    Codec(Arc<dyn Codec>),
+}
+
+impl ValueEncoder {
+    /// Encoder for `compression`, priming Zstd with `dictionary`; `None`
+    /// if values are stored as written
+    pub(crate) fn new(
+        compression: &Compression,
+        dictionary: Option<&[u8]>,
+    ) -> io::Result<Option<Self>> {
+        #[cfg(not(feature = "zstd"))]
+        let _ = dictionary;
+        match compression {
+            Compression::None => Ok(None),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(options) => {
+                let mut compressor = match dictionary {
+                    Some(dictionary) => Compressor::with_dictionary(options.level, dictionary)?,
+                    None => Compressor::new(options.level)?,
+                };
+                // Each file has at most one dictionary, so the per-value
+                // dictionary ID would only cost bytes; the content size lets
+                // decode preallocate
+                compressor.set_parameter(CParameter::DictIdFlag(false))?;
+                compressor.set_parameter(CParameter::ContentSizeFlag(true))?;
+                Ok(Some(ValueEncoder::Zstd(compressor)))
+            }
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd(_) => Err(codec::unavailable(ZSTD_COMPRESSION_NAME)),
+            other => {
+                let name = other.name().unwrap_or_default();
+                Ok(Some(ValueEncoder::Codec(
+                    CodecRegistry::global().get(name)?,
+                )))
+            }
+        }
+    }
+
+    /// Encode `value` behind a flag byte, returning the stored bytes and
+    /// whether they are compressed. Values that look incompressible, or
+    /// that would not shrink, are stored as written.
+    pub(crate) fn encode(&mut self, value: &[u8]) -> io::Result<(Vec<u8>, bool)> {
+        if !looks_incompressible(value) {
+            let compressed = match self {
+                #[cfg(feature = "zstd")]
+                ValueEncoder::Zstd(compressor) => compressor.compress(value)?,
+                ValueEncoder::Codec(codec) => codec.compress(value)?,
+            };
+            if compressed.len() < value.len() {
+                let mut stored = Vec::with_capacity(compressed.len() + 1);
+                stored.push(VALUE_COMPRESSED);
+                stored.extend_from_slice(&compressed);
+                return Ok((stored, true));
+            }
+        }
+
+        let mut stored = Vec::with_capacity(value.len() + 1);
+        stored.push(VALUE_STORED_RAW);
+        stored.extend_from_slice(value);
+        Ok((stored, false))
+    }
+}
+
+/// How stored values were compressed
+#[derive(Clone)]
+enum StoredCodec {
+    #[cfg(feature = "zstd")]
+    Zstd(Option<Arc<DecoderDictionary<'static>>>),
+    Codec(Arc<dyn Codec>),
+}
+
+/// Restores values read from an SSTable to the bytes that were written.
+///
+/// Cloning is cheap, so callers that read entries outside `SSTableReader`
+/// can keep one per file.
+#[derive(Clone, Default)]
+pub struct ValueDecoder {
+    codec: Option<StoredCodec>,
+    /// Whether each stored value starts with a flag byte saying if it was
+    /// compressed
+    flagged: bool,
+}
+
+impl fmt::Debug for ValueDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let codec = match &self.codec {
+            None => None,
+            #[cfg(feature = "zstd")]
+            Some(StoredCodec::Zstd(_)) => Some(ZSTD_COMPRESSION_NAME),
+            Some(StoredCodec::Codec(codec)) => Some(codec.name()),
+        };
+        #[cfg(feature = "zstd")]
+        let has_dictionary = matches!(&self.codec, Some(StoredCodec::Zstd(Some(_))));
+        #[cfg(not(feature = "zstd"))]
+        let has_dictionary = false;
+        f.debug_struct("ValueDecoder")
+            .field("codec", &codec)
+            .field("has_dictionary", &has_dictionary)
+            .finish()
+    }
+}
+
+impl ValueDecoder {
+    /// Decoder for values compressed with the codec named `name` in a
+    /// file's properties, using `dictionary` if the file has one.
+    ///
+    /// With `flagged`, each value starts with a byte saying whether it was
+    /// compressed, as in files written since incompressible values have
+    /// been stored as written.
+    ///
+    /// A codec that is not compiled in or registered fails with
+    /// `ErrorKind::Unsupported`, rather than as corruption.
+    pub(crate) fn for_codec(
+        name: &str,
+        dictionary: Option<&[u8]>,
+        flagged: bool,
+    ) -> io::Result<Self> {
+        let codec = if name == ZSTD_COMPRESSION_NAME {
+            #[cfg(feature = "zstd")]
+            {
+                StoredCodec::Zstd(
+                    dictionary.map(|dictionary| Arc::new(DecoderDictionary::copy(dictionary))),
+                )
+            }
+            #[cfg(not(feature = "zstd"))]
+            {
+                let _ = dictionary;
+                return Err(codec::unavailable(name));
+            }
+        } else {
+            StoredCodec::Codec(CodecRegistry::global().get(name)?)
+        };
+        Ok(ValueDecoder {
+            codec: Some(codec),
+            flagged,
+        })
+    }
+
+    /// Whether stored values are compressed
+    pub fn is_compressed(&self) -> bool {
+        self.codec.is_some()
+    }
+
+    /// Decode a stored value
+    pub fn decode(&self, stored: Vec<u8>) -> io::Result<Vec<u8>> {
+        let Some(codec) = &self.codec else {
+            return Ok(stored);
+        };
+        if !self.flagged {
+            return Self::decompress(codec, &stored);
+        }
+        match stored.split_first() {
+            Some((&VALUE_COMPRESSED, compressed)) => Self::decompress(codec, compressed),
+            Some((&VALUE_STORED_RAW, _)) => {
+                let mut value = stored;
+                value.remove(0);
+                Ok(value)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Stored value has an invalid compression flag",
+            )),
+        }
+    }
+
+    /// Decode a stored value without copying it when it was stored
+    /// uncompressed, in which case the result borrows from `stored`
+    pub fn decode_ref<'a>(&self, stored: &'a [u8]) -> io::Result<Cow<'a, [u8]>> {
+        let Some(codec) = &self.codec else {
+            return Ok(Cow::Borrowed(stored));
+        };
+        if !self.flagged {
+            return Self::decompress(codec, stored).map(Cow::Owned);
+        }
+        match stored.split_first() {
+            Some((&VALUE_COMPRESSED, compressed)) => {
+                Self::decompress(codec, compressed).map(Cow::Owned)
+            }
+            Some((&VALUE_STORED_RAW, value)) => Ok(Cow::Borrowed(value)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Stored value has an invalid compression flag",
+            )),
+        }
+    }
+
+    /// Decompress a value stored compressed with `codec`
+    fn decompress(codec: &StoredCodec, stored: &[u8]) -> io::Result<Vec<u8>> {
+        match codec {
+            #[cfg(feature = "zstd")]
+            StoredCodec::Zstd(dictionary) => {
+                let size = match zstd::zstd_safe::get_frame_content_size(stored) {
+                    Ok(Some(size)) if size as usize <= MAX_VALUE_SIZE => size as usize,
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "Compressed value has an invalid frame header",
+                        ));
+                    }
+                };
+
+                let mut decompressor = match dictionary {
+                    Some(dictionary) => Decompressor::with_prepared_dictionary(dictionary)?,
+                    None => Decompressor::new()?,
+                };
+                decompressor.decompress(stored, size)
+            }
+            StoredCodec::Codec(codec) => codec.decompress(stored, MAX_VALUE_SIZE),
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use siphasher::sip128::{Hasher128, SipHasher24};
+use std::fmt;
+use std::hash::Hasher;
+
+/// Domain separation tag for leaf (entry) hashes
+const LEAF_TAG: u8 = 0;
+/// Domain separation tag for interior node hashes
+const NODE_TAG: u8 = 1;
+/// Domain separation tag for the digest of an empty sequence
+const EMPTY_TAG: u8 = 2;
+
+/// A 128-bit content digest.
+///
+/// Digests are computed with SipHash-2-4 under a fixed all-zero key, so they are
+/// stable across processes and platforms and can be compared between replicas.
+/// They detect accidental divergence; they are not a cryptographic commitment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Digest(pub [u8; 16]);
+
+impl Digest {
+    /// Digest of an empty sequence of entries
+    pub fn empty() -> Self {
+        hash_parts(&[&[EMPTY_TAG]])
+    }
+
+    /// Render the digest as 32 lowercase hex characters
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Parse a digest from its hex representation
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() != 32 || !hex.is_ascii() {
+            return None;
+        }
+
+        let mut bytes = [0u8; 16];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        Some(Digest(bytes))
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_hex())
+    }
+}
+
+/// Hash a sequence of byte slices into a digest
+fn hash_parts(parts: &[&[u8]]) -> Digest {
+    let mut hasher = SipHasher24::new_with_keys(0, 0);
+    for part in parts {
+        hasher.write(part);
+    }
+    Digest(hasher.finish128().as_bytes())
+}
+
+/// Digest of a single key-value entry (a Merkle leaf)
+pub fn entry_digest(key: &str, value: &[u8]) -> Digest {
+    hash_parts(&[
+        &[LEAF_TAG],
+        &(key.len() as u32).to_le_bytes(),
+        key.as_bytes(),
+        &(value.len() as u32).to_le_bytes(),
+        value,
+    ])
+}
+
+/// Digest of an interior node joining two subtrees
+fn node_digest(left: &Digest, right: &Digest) -> Digest {
+    hash_parts(&[&[NODE_TAG], &left.0, &right.0])
+}
+
+/// Streaming Merkle tree over entries fed in key order.
+///
+/// Only one pending subtree per level is kept, so memory use is logarithmic in
+/// the number of entries. Two hashers fed the same entries in the same order
+/// always produce the same root.
+///
+/// # Examples
+///
+/// ```
+/// use lsmer::sstable::digest::MerkleHasher;
+///
+/// let mut a = MerkleHasher::new();
+/// a.update("key1", b"value1");
+/// a.update("key2", b"value2");
+///
+/// let mut b = MerkleHasher::new();
+/// b.update("key1", b"value1");
+/// b.update("key2", b"value2");
+///
+/// assert_eq!(a.finish(), b.finish());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MerkleHasher {
+    /// Pending subtree roots, with the height of each subtree
+    stack: Vec<(u32, Digest)>,
+    /// Number of entries hashed so far
+    entry_count: u64,
+}
+
+impl MerkleHasher {
+    /// Create an empty hasher
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a key-value entry
+    pub fn update(&mut self, key: &str, value: &[u8]) {
+        self.update_leaf(entry_digest(key, value));
+    }
+
+    /// Add a precomputed leaf digest
+    pub fn update_leaf(&mut self, leaf: Digest) {
+        let mut height = 0;
+        let mut digest = leaf;
+
+        // Merge equal-height subtrees, like carrying in a binary counter
+        while let Some(&(top_height, top)) = self.stack.last() {
+            if top_height != height {
+                break;
+            }
+            self.stack.pop();
+            digest = node_digest(&top, &digest);
+            height += 1;
+        }
+
+        self.stack.push((height, digest));
+        self.entry_count += 1;
+    }
+
+    /// Number of entries hashed so far
+    pub fn entry_count(&self) -> u64 {
+        self.entry_count
+    }
+
+    /// Compute the root digest over all entries added so far
+    pub fn finish(&self) -> Digest {
+        let mut subtrees = self.stack.iter().rev();
+        let mut root = match subtrees.next() {
+            Some((_, digest)) => *digest,
+            None => return Digest::empty(),
+        };
+
+        // Fold the remaining subtrees in from right to left
+        for (_, digest) in subtrees {
+            root = node_digest(digest, &root);
+        }
+        root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_round_trip() {
+        let digest = entry_digest("key", b"value");
+        assert_eq!(Digest::from_hex(&digest.to_hex()), Some(digest));
+        assert_eq!(Digest::from_hex("not hex"), None);
+    }
+
+    #[test]
+    fn test_root_depends_on_content_and_order() {
+        let build = |entries: &[(&str, &[u8])]| {
+            let mut hasher = MerkleHasher::new();
+            for (key, value) in entries {
+                hasher.update(key, value);
+            }
+            hasher.finish()
+        };
+
+        let base = build(&[("a", b"1"), ("b", b"2"), ("c", b"3")]);
+        assert_eq!(base, build(&[("a", b"1"), ("b", b"2"), ("c", b"3")]));
+        assert_ne!(base, build(&[("a", b"1"), ("b", b"2"), ("c", b"4")]));
+        assert_ne!(base, build(&[("b", b"2"), ("a", b"1"), ("c", b"3")]));
+        assert_ne!(base, build(&[("a", b"1"), ("b", b"2")]));
+        assert_eq!(build(&[]), Digest::empty());
+    }
+
+    #[test]
+    fn test_entry_boundaries_are_unambiguous() {
+        // Moving bytes between key and value must change the digest
+        assert_ne!(entry_digest("ab", b"c"), entry_digest("a", b"bc"));
+    }
+}
@@ -0,0 +1,235 @@
+use crate::locks;
+#[cfg(feature = "encryption")]
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+#[cfg(feature = "encryption")]
+use aes_gcm::{Aes256Gcm, Nonce};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::RwLock;
+
+/// Size of a data-encryption key, in bytes; blocks are sealed with AES-256-GCM
+pub const KEY_SIZE: usize = 32;
+/// Byte recording in the header that data blocks are sealed with AES-256-GCM
+pub(crate) const AES_256_GCM_CIPHER_ID: u8 = 1;
+/// Size of the random nonce stored before each sealed block
+const NONCE_SIZE: usize = 12;
+/// Size of the authentication tag stored after each sealed block
+const TAG_SIZE: usize = 16;
+/// Bytes sealing adds to a block: its nonce and authentication tag
+pub(crate) const CIPHER_OVERHEAD: usize = NONCE_SIZE + TAG_SIZE;
+
+/// Hands out the data-encryption keys SSTable blocks are sealed with, by key
+/// ID.
+///
+/// New files are sealed with the current key and record its ID, so a
+/// provider rotates keys by making a new one current while still handing out
+/// the old ones for files written before. Pass one to
+/// `SSTableWriter::set_encryption` to write encrypted files, and to
+/// `SSTableReader::open_with_key_provider` to read them.
+pub trait KeyProvider: fmt::Debug + Send + Sync {
+    /// ID of the key new files are sealed with
+    fn current_key_id(&self) -> io::Result<String>;
+
+    /// The key with ID `key_id`. An ID the provider does not know fails with
+    /// `ErrorKind::NotFound`.
+    fn key(&self, key_id: &str) -> io::Result<[u8; KEY_SIZE]>;
+}
+
+/// Key provider holding its keys in memory, for applications that fetch
+/// them from a key management service themselves, and for tests
+pub struct StaticKeyProvider {
+    /// ID of the current key and every key by ID
+    keys: RwLock<(String, HashMap<String, [u8; KEY_SIZE]>)>,
+}
+
+impl fmt::Debug for StaticKeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys = locks::recover(self.keys.read());
+        f.debug_struct("StaticKeyProvider")
+            .field("current_key_id", &keys.0)
+            .field("key_count", &keys.1.len())
+            .finish()
+    }
+}
+
+impl StaticKeyProvider {
+    /// Create a provider whose current key is `key`, under `key_id`
+    pub fn new(key_id: impl Into<String>, key: [u8; KEY_SIZE]) -> Self {
+        let key_id = key_id.into();
+        let keys = HashMap::from([(key_id.clone(), key)]);
+        StaticKeyProvider {
+            keys: RwLock::new((key_id, keys)),
+        }
+    }
+
+    /// Make `key` current under `key_id`. Earlier keys are kept, so files
+    /// sealed with them stay readable until compaction rewrites them.
+    pub fn rotate(&self, key_id: impl Into<String>, key: [u8; KEY_SIZE]) {
+        let key_id = key_id.into();
+        let mut keys = locks::recover(self.keys.write());
+        keys.1.insert(key_id.clone(), key);
+        keys.0 = key_id;
+    }
+
+    /// Forget the key with ID `key_id`, once no file is sealed with it. The
+    /// current key cannot be removed.
+    pub fn remove(&self, key_id: &str) -> io::Result<()> {
+        let mut keys = locks::checked(self.keys.write(), "encryption key")?;
+        if keys.0 == key_id {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Key '{}' is current and cannot be removed", key_id),
+            ));
+        }
+        keys.1.remove(key_id);
+        Ok(())
+    }
+}
+
+impl KeyProvider for StaticKeyProvider {
+    fn current_key_id(&self) -> io::Result<String> {
+        Ok(locks::checked(self.keys.read(), "encryption key")?
+            .0
+            .clone())
+    }
+
+    fn key(&self, key_id: &str) -> io::Result<[u8; KEY_SIZE]> {
+        locks::checked(self.keys.read(), "encryption key")?
+            .1
+            .get(key_id)
+            .copied()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Unknown encryption key '{}'", key_id),
+                )
+            })
+    }
+}
+
+/// Error for opening an encrypted SSTable without a key provider
+pub(crate) fn no_key_provider() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "SSTable is encrypted but no key provider was given to read it with",
+    )
+}
+
+/// Error for encrypting or decrypting without the `encryption` feature
+#[cfg(not(feature = "encryption"))]
+fn unavailable() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "SSTable encryption is not compiled in; enable the `encryption` feature of lsmer",
+    )
+}
+
+/// Seals and opens the data blocks of one file with AES-256-GCM under one
+/// key. Each block gets a random nonce, stored before it, and its offset as
+/// associated data, so a block moved elsewhere in the file fails to open.
+pub(crate) struct BlockCipher {
+    key_id: String,
+    #[cfg(feature = "encryption")]
+    cipher: Aes256Gcm,
+}
+
+impl fmt::Debug for BlockCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockCipher")
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
+impl BlockCipher {
+    /// Cipher under `provider`'s current key
+    pub(crate) fn current(provider: &dyn KeyProvider) -> io::Result<Self> {
+        let key_id = provider.current_key_id()?;
+        Self::for_key(provider, &key_id)
+    }
+
+    /// Cipher under `provider`'s key with ID `key_id`
+    pub(crate) fn for_key(provider: &dyn KeyProvider, key_id: &str) -> io::Result<Self> {
+        #[cfg(feature = "encryption")]
+        {
+            let key = provider.key(key_id)?;
+            Ok(BlockCipher {
+                key_id: key_id.to_string(),
+                cipher: Aes256Gcm::new(&key.into()),
+            })
+        }
+        #[cfg(not(feature = "encryption"))]
+        {
+            let _ = (provider, key_id);
+            Err(unavailable())
+        }
+    }
+
+    /// ID of the key blocks are sealed with
+    pub(crate) fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// Seal the block written at `offset`: its nonce, then the ciphertext
+    /// and authentication tag
+    pub(crate) fn seal(&self, offset: u64, block: &[u8]) -> io::Result<Vec<u8>> {
+        #[cfg(feature = "encryption")]
+        {
+            let mut nonce = [0u8; NONCE_SIZE];
+            getrandom::fill(&mut nonce).map_err(io::Error::other)?;
+            let aad = offset.to_le_bytes();
+            let payload = Payload {
+                msg: block,
+                aad: &aad,
+            };
+            let sealed = self
+                .cipher
+                .encrypt(Nonce::from_slice(&nonce), payload)
+                .map_err(|_| io::Error::other("Failed to encrypt a data block"))?;
+            let mut stored = Vec::with_capacity(NONCE_SIZE + sealed.len());
+            stored.extend_from_slice(&nonce);
+            stored.extend_from_slice(&sealed);
+            Ok(stored)
+        }
+        #[cfg(not(feature = "encryption"))]
+        {
+            let _ = (offset, block);
+            Err(unavailable())
+        }
+    }
+
+    /// Open a block `seal` stored for `offset`, failing with
+    /// `ErrorKind::InvalidData` if it does not authenticate
+    pub(crate) fn open(&self, offset: u64, stored: &[u8]) -> io::Result<Vec<u8>> {
+        #[cfg(feature = "encryption")]
+        {
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Data block at offset {} does not decrypt under key '{}'",
+                        offset, self.key_id
+                    ),
+                )
+            };
+            if stored.len() < CIPHER_OVERHEAD {
+                return Err(invalid());
+            }
+            let (nonce, sealed) = stored.split_at(NONCE_SIZE);
+            let aad = offset.to_le_bytes();
+            let payload = Payload {
+                msg: sealed,
+                aad: &aad,
+            };
+            self.cipher
+                .decrypt(Nonce::from_slice(nonce), payload)
+                .map_err(|_| invalid())
+        }
+        #[cfg(not(feature = "encryption"))]
+        {
+            let _ = (offset, stored);
+            Err(unavailable())
+        }
+    }
+}
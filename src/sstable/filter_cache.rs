@@ -0,0 +1,210 @@
+use crate::bloom::{BloomFilter, PartitionedBloomFilter};
+use crate::locks;
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A Bloom filter read from an SSTable
+#[derive(Debug)]
+pub enum LoadedFilter {
+    /// A standard filter over every key
+    Standard(BloomFilter<String>),
+    /// A filter hash-partitioned for parallel lookups
+    Partitioned(PartitionedBloomFilter<String>),
+}
+
+impl LoadedFilter {
+    /// Check if a key might be in the filter
+    pub fn may_contain(&self, key: &str) -> bool {
+        match self {
+            LoadedFilter::Standard(filter) => filter.may_contain(&key.to_string()),
+            LoadedFilter::Partitioned(filter) => filter.may_contain(&key.to_string()),
+        }
+    }
+
+    /// Bytes of filter bits held in memory
+    pub fn memory_usage(&self) -> usize {
+        match self {
+            LoadedFilter::Standard(filter) => filter.get_bits().len(),
+            LoadedFilter::Partitioned(filter) => (0..filter.num_partitions())
+                .filter_map(|i| filter.get_partition(i))
+                .map(|partition| partition.get_bits().len())
+                .sum(),
+        }
+    }
+}
+
+/// Hits, misses and evictions of a `FilterCache`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FilterCacheStats {
+    /// Lookups that found their filter resident
+    pub hits: u64,
+    /// Lookups that read their filter from the file
+    pub misses: u64,
+    /// Filters dropped to stay within the capacity
+    pub evictions: u64,
+    /// Filters currently resident
+    pub resident_filters: usize,
+    /// Bytes of filter bits currently resident
+    pub resident_bytes: usize,
+}
+
+#[derive(Debug)]
+struct CachedFilter {
+    filter: Arc<LoadedFilter>,
+    bytes: usize,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    capacity_bytes: usize,
+    filters: HashMap<u64, CachedFilter>,
+    used_bytes: usize,
+    clock: u64,
+    stats: FilterCacheStats,
+}
+
+impl CacheState {
+    /// Drop the least recently used filters until `used_bytes` fits
+    fn evict_to(&mut self, capacity_bytes: usize) {
+        while self.used_bytes > capacity_bytes {
+            let Some(oldest) = self
+                .filters
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(id, _)| *id)
+            else {
+                break;
+            };
+            let cached = self.filters.remove(&oldest).unwrap();
+            self.used_bytes -= cached.bytes;
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+/// Bloom filters of SSTable readers opened with
+/// `SSTableReader::open_with_filter_cache`, shared across readers and held
+/// within a byte budget.
+///
+/// Readers load their filter on first use rather than at open. The least
+/// recently used filters are dropped when the budget is exceeded and read
+/// back from their file when next needed. A filter larger than the whole
+/// budget is used for the lookup that loaded it and then dropped.
+#[derive(Debug)]
+pub struct FilterCache {
+    state: Mutex<CacheState>,
+    next_id: AtomicU64,
+}
+
+impl FilterCache {
+    /// Create a cache holding at most `capacity_bytes` of filter bits
+    pub fn new(capacity_bytes: usize) -> Self {
+        FilterCache {
+            state: Mutex::new(CacheState {
+                capacity_bytes,
+                ..CacheState::default()
+            }),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Bytes of filter bits the cache may hold
+    pub fn capacity_bytes(&self) -> usize {
+        locks::recover(self.state.lock()).capacity_bytes
+    }
+
+    /// Change the budget, evicting filters at once if it shrank; use this to
+    /// give memory back under pressure
+    pub fn set_capacity(&self, capacity_bytes: usize) {
+        let mut state = locks::recover(self.state.lock());
+        state.capacity_bytes = capacity_bytes;
+        state.evict_to(capacity_bytes);
+    }
+
+    /// Drop every resident filter
+    pub fn clear(&self) {
+        locks::recover(self.state.lock()).evict_to(0);
+    }
+
+    /// Hits, misses, evictions and what is resident now
+    pub fn stats(&self) -> FilterCacheStats {
+        let state = locks::recover(self.state.lock());
+        FilterCacheStats {
+            resident_filters: state.filters.len(),
+            resident_bytes: state.used_bytes,
+            ..state.stats
+        }
+    }
+
+    /// Allocate an ID for a reader's filter
+    pub(crate) fn register(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// The filter registered as `id`, calling `load` to read it if it is not
+    /// resident. Loading happens outside the lock, so readers of other files
+    /// are not held up.
+    pub(crate) fn get_or_load(
+        &self,
+        id: u64,
+        load: impl FnOnce() -> io::Result<LoadedFilter>,
+    ) -> io::Result<Arc<LoadedFilter>> {
+        {
+            let mut state = locks::checked(self.state.lock(), "filter cache")?;
+            state.clock += 1;
+            let now = state.clock;
+            if let Some(cached) = state.filters.get_mut(&id) {
+                cached.last_used = now;
+                let filter = cached.filter.clone();
+                state.stats.hits += 1;
+                return Ok(filter);
+            }
+            state.stats.misses += 1;
+        }
+
+        let filter = Arc::new(load()?);
+        let bytes = filter.memory_usage();
+
+        let mut state = locks::checked(self.state.lock(), "filter cache")?;
+        if bytes <= state.capacity_bytes && !state.filters.contains_key(&id) {
+            let capacity_bytes = state.capacity_bytes;
+            state.evict_to(capacity_bytes - bytes);
+            state.clock += 1;
+            let last_used = state.clock;
+            state.filters.insert(
+                id,
+                CachedFilter {
+                    filter: filter.clone(),
+                    bytes,
+                    last_used,
+                },
+            );
+            state.used_bytes += bytes;
+        }
+        Ok(filter)
+    }
+
+    /// Drop the filter registered as `id`, once its reader is gone
+    pub(crate) fn forget(&self, id: u64) {
+        let mut state = locks::recover(self.state.lock());
+        if let Some(cached) = state.filters.remove(&id) {
+            state.used_bytes -= cached.bytes;
+        }
+    }
+}
+
+/// A reader's registration with a `FilterCache`, released when dropped
+#[derive(Debug)]
+pub(crate) struct FilterCacheHandle {
+    pub(crate) cache: Arc<FilterCache>,
+    pub(crate) id: u64,
+}
+
+impl Drop for FilterCacheHandle {
+    fn drop(&mut self) {
+        self.cache.forget(self.id);
+    }
+}
@@ -0,0 +1,119 @@
+use super::{MAGIC, calculate_checksum};
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Size of the footer ending version 8 and later files: entry count, index
+/// offset, filter offset and size, filter flag, file number, checksums
+/// offset, checksum and magic number
+pub const FOOTER_SIZE: usize = 8 + 8 + 8 + 8 + 1 + 8 + 8 + 4 + 8;
+
+/// Where a version 8 or later SSTable keeps what is only known once every
+/// entry is written.
+///
+/// The footer takes the last `FOOTER_SIZE` bytes of the file, so a writer
+/// appends it after the checksums instead of seeking back to fill in the
+/// header, and a reader finds it from the end of the file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Footer {
+    /// Number of entries in the data section
+    pub entry_count: u64,
+    /// Offset of the meta section, just past the last entry
+    pub index_offset: u64,
+    /// Offset of the filter region
+    pub bloom_offset: u64,
+    /// Size of the filter region in bytes
+    pub bloom_size: u64,
+    /// Whether the file has a Bloom filter
+    pub has_bloom_filter: bool,
+    /// Number the manifest allocated to the file, or 0 outside an index
+    pub file_number: u64,
+    /// Offset of the per-entry checksums following the filter region
+    pub checksums_offset: u64,
+}
+
+impl Footer {
+    /// Encode the footer, ending with its checksum and the magic number
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(FOOTER_SIZE);
+        buf.extend_from_slice(&self.entry_count.to_le_bytes());
+        buf.extend_from_slice(&self.index_offset.to_le_bytes());
+        buf.extend_from_slice(&self.bloom_offset.to_le_bytes());
+        buf.extend_from_slice(&self.bloom_size.to_le_bytes());
+        buf.push(self.has_bloom_filter as u8);
+        buf.extend_from_slice(&self.file_number.to_le_bytes());
+        buf.extend_from_slice(&self.checksums_offset.to_le_bytes());
+        let checksum = calculate_checksum(&buf);
+        buf.extend_from_slice(&checksum.to_le_bytes());
+        buf.extend_from_slice(&MAGIC.to_le_bytes());
+        buf
+    }
+
+    /// Decode a footer written by `encode`, checking its magic number and
+    /// checksum
+    pub fn decode(buf: &[u8]) -> io::Result<Self> {
+        let buf: &[u8; FOOTER_SIZE] = buf.try_into().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("SSTable footer is {} bytes, not {}", buf.len(), FOOTER_SIZE),
+            )
+        })?;
+        let u64_at = |at: usize| u64::from_le_bytes(buf[at..at + 8].try_into().unwrap());
+
+        if u64_at(FOOTER_SIZE - 8) != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid magic number at the end of the SSTable footer",
+            ));
+        }
+        let checksum_at = FOOTER_SIZE - 12;
+        let stored = u32::from_le_bytes(buf[checksum_at..checksum_at + 4].try_into().unwrap());
+        if calculate_checksum(&buf[..checksum_at]) != stored {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "SSTable footer checksum verification failed",
+            ));
+        }
+
+        Ok(Footer {
+            entry_count: u64_at(0),
+            index_offset: u64_at(8),
+            bloom_offset: u64_at(16),
+            bloom_size: u64_at(24),
+            has_bloom_filter: buf[32] != 0,
+            file_number: u64_at(33),
+            checksums_offset: u64_at(41),
+        })
+    }
+
+    /// Read and check the footer at the end of a file, rejecting offsets
+    /// that point past the start of the footer
+    pub fn read_from<R: Read + Seek>(reader: &mut R) -> io::Result<Self> {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        let footer_start = file_size.checked_sub(FOOTER_SIZE as u64).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "SSTable of {} bytes is too small to hold a footer",
+                    file_size
+                ),
+            )
+        })?;
+        reader.seek(SeekFrom::Start(footer_start))?;
+        let mut buf = [0u8; FOOTER_SIZE];
+        reader.read_exact(&mut buf)?;
+        let footer = Self::decode(&buf)?;
+
+        let in_bounds = footer.index_offset <= footer_start
+            && footer.checksums_offset <= footer_start
+            && footer
+                .bloom_offset
+                .checked_add(footer.bloom_size)
+                .is_some_and(|end| end <= footer_start);
+        if !in_bounds {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "SSTable footer offsets extend past the footer",
+            ));
+        }
+        Ok(footer)
+    }
+}
@@ -0,0 +1,136 @@
+use siphasher::sip::SipHasher;
+use std::hash::Hasher;
+use std::io;
+
+/// Size of one slot: a 32-bit key fingerprint and a 64-bit entry offset
+const SLOT_SIZE: usize = 12;
+
+/// Fingerprint marking an empty slot; real fingerprints are never zero
+const EMPTY: u32 = 0;
+
+/// Fraction of slots left empty, so probe runs stay short
+const MAX_LOAD_FACTOR: f64 = 0.75;
+
+/// Open-addressing hash table from key fingerprints to entry offsets,
+/// answering point lookups without searching blocks.
+///
+/// Slots are probed linearly from the key's home slot until an empty one.
+/// Fingerprints can collide, so every offset whose fingerprint matches is a
+/// candidate the reader must check against the stored key.
+#[derive(Debug, Default)]
+pub(crate) struct HashIndex {
+    /// Slot count minus one; the slot count is a power of two
+    mask: usize,
+    fingerprints: Vec<u32>,
+    offsets: Vec<u64>,
+}
+
+impl HashIndex {
+    /// Build a table over `(key hash, entry offset)` pairs in file order, so
+    /// a key written twice yields its first offset first
+    pub(crate) fn build(entries: &[(u64, u64)]) -> Self {
+        let slots = ((entries.len() as f64 / MAX_LOAD_FACTOR).ceil() as usize)
+            .max(1)
+            .next_power_of_two();
+        let mut index = HashIndex {
+            mask: slots - 1,
+            fingerprints: vec![EMPTY; slots],
+            offsets: vec![0; slots],
+        };
+        for &(hash, offset) in entries {
+            let mut slot = hash as usize & index.mask;
+            while index.fingerprints[slot] != EMPTY {
+                slot = (slot + 1) & index.mask;
+            }
+            index.fingerprints[slot] = fingerprint(hash);
+            index.offsets[slot] = offset;
+        }
+        index
+    }
+
+    /// Offsets of the entries that may hold `key`, in file order
+    pub(crate) fn candidates(&self, key: &str) -> Vec<u64> {
+        let hash = hash_key(key);
+        let wanted = fingerprint(hash);
+        let mut candidates = Vec::new();
+        let mut slot = hash as usize & self.mask;
+        // A full table cannot be written, but a corrupt one must not spin
+        for _ in 0..self.fingerprints.len() {
+            match self.fingerprints[slot] {
+                EMPTY => break,
+                found if found == wanted => candidates.push(self.offsets[slot]),
+                _ => {}
+            }
+            slot = (slot + 1) & self.mask;
+        }
+        candidates
+    }
+
+    /// Number of slots in the table
+    pub(crate) fn slot_count(&self) -> usize {
+        self.fingerprints.len()
+    }
+
+    /// Heap bytes held by the table
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.slot_count() * SLOT_SIZE
+    }
+
+    /// Encode the table as its slot count followed by each slot's
+    /// fingerprint and offset
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(4 + self.slot_count() * SLOT_SIZE);
+        buf.extend_from_slice(&(self.slot_count() as u32).to_le_bytes());
+        for (fingerprint, offset) in self.fingerprints.iter().zip(&self.offsets) {
+            buf.extend_from_slice(&fingerprint.to_le_bytes());
+            buf.extend_from_slice(&offset.to_le_bytes());
+        }
+        buf
+    }
+
+    /// Decode a table written by `encode`
+    pub(crate) fn decode(buf: &[u8]) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        if buf.len() < 4 {
+            return Err(invalid("Truncated SSTable hash index"));
+        }
+        let slots = u32::from_le_bytes(buf[..4].try_into().unwrap()) as usize;
+        if !slots.is_power_of_two() {
+            return Err(invalid(
+                "SSTable hash index slot count is not a power of two",
+            ));
+        }
+        let body = &buf[4..];
+        if body.len() != slots * SLOT_SIZE {
+            return Err(invalid("Truncated SSTable hash index"));
+        }
+
+        let (fingerprints, offsets) = body
+            .chunks_exact(SLOT_SIZE)
+            .map(|slot| {
+                (
+                    u32::from_le_bytes(slot[..4].try_into().unwrap()),
+                    u64::from_le_bytes(slot[4..].try_into().unwrap()),
+                )
+            })
+            .unzip();
+        Ok(HashIndex {
+            mask: slots - 1,
+            fingerprints,
+            offsets,
+        })
+    }
+}
+
+/// Hash a key for the table; stable across builds, since tables are stored
+pub(crate) fn hash_key(key: &str) -> u64 {
+    let mut hasher = SipHasher::new_with_keys(0x5AB1E5EED0F00D42, 0x0DDBA11CAFEF00D5);
+    hasher.write(key.as_bytes());
+    hasher.finish()
+}
+
+/// Fingerprint stored for a key hash, taken from the bits the home slot
+/// does not use in small tables
+fn fingerprint(hash: u64) -> u32 {
+    ((hash >> 32) as u32).max(1)
+}
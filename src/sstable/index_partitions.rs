@@ -0,0 +1,225 @@
+use super::block_index;
+use super::BlockHandle;
+use crate::bloom::BloomFilter;
+use std::io;
+
+/// Type byte of a filter region holding per-partition filters
+pub(crate) const PARTITIONED_FILTER_TYPE: u8 = 2;
+
+/// One partition of a two-level index: the key span of a run of data blocks,
+/// and where the run's block handles and Bloom filter are stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexPartition {
+    /// Key span, first block offset and entry count of the run of blocks
+    pub span: BlockHandle,
+    /// Offset of the partition's payload from the start of the filter region
+    pub payload_offset: u64,
+    /// Size of the payload in bytes, including its checksum
+    pub payload_len: u64,
+}
+
+/// Block handles and Bloom filter of one partition, loaded on demand
+#[derive(Debug)]
+pub struct PartitionPayload {
+    /// Data blocks of the partition in file order
+    pub blocks: Vec<BlockHandle>,
+    /// Filter holding the keys of the partition
+    pub filter: BloomFilter<String>,
+}
+
+impl PartitionPayload {
+    /// Heap bytes held by the payload
+    pub(crate) fn memory_usage(&self) -> usize {
+        let block_bytes: usize = self
+            .blocks
+            .iter()
+            .map(|block| block.first_key.len() + block.last_key.len() + 16)
+            .sum();
+        block_bytes + self.filter.get_bits().len()
+    }
+}
+
+/// Groups data blocks into partitions as they are written, building each
+/// partition's filter once its last block is in
+#[derive(Debug)]
+pub(crate) struct PartitionBuilder {
+    blocks_per_partition: usize,
+    /// Encoded size of block handles at which a partition is closed, if
+    /// partitions are closed by size rather than block count
+    max_partition_bytes: Option<usize>,
+    /// Encoded size of the handles in the partition being filled
+    partition_bytes: usize,
+    false_positive_rate: f64,
+    /// Blocks and keys of the partition being filled
+    blocks: Vec<BlockHandle>,
+    keys: Vec<String>,
+    /// Finished partitions and their encoded payloads
+    partitions: Vec<IndexPartition>,
+    payloads: Vec<u8>,
+}
+
+impl PartitionBuilder {
+    pub(crate) fn new(blocks_per_partition: usize, false_positive_rate: f64) -> Self {
+        PartitionBuilder {
+            blocks_per_partition: blocks_per_partition.max(1),
+            max_partition_bytes: None,
+            partition_bytes: 0,
+            false_positive_rate,
+            blocks: Vec::new(),
+            keys: Vec::new(),
+            partitions: Vec::new(),
+            // Offsets are relative to the region, which starts with its type
+            payloads: vec![PARTITIONED_FILTER_TYPE],
+        }
+    }
+
+    /// Create a builder closing each partition once its block handles
+    /// encode to at least `max_partition_bytes`
+    pub(crate) fn by_size(max_partition_bytes: usize, false_positive_rate: f64) -> Self {
+        PartitionBuilder {
+            max_partition_bytes: Some(max_partition_bytes.max(1)),
+            ..Self::new(usize::MAX, false_positive_rate)
+        }
+    }
+
+    /// Add a written block and its keys
+    pub(crate) fn add_block(&mut self, block: BlockHandle, keys: &[String]) {
+        // Length-prefixed keys, offset and entry count, as in `block_index`
+        self.partition_bytes += block.first_key.len() + block.last_key.len() + 24;
+        self.blocks.push(block);
+        self.keys.extend_from_slice(keys);
+        let full = match self.max_partition_bytes {
+            Some(max_bytes) => self.partition_bytes >= max_bytes,
+            None => self.blocks.len() >= self.blocks_per_partition,
+        };
+        if full {
+            self.finish_partition();
+        }
+    }
+
+    /// Close the partition being filled, if it holds any blocks
+    fn finish_partition(&mut self) {
+        let blocks = std::mem::take(&mut self.blocks);
+        self.partition_bytes = 0;
+        let (Some(first), Some(last)) = (blocks.first(), blocks.last()) else {
+            return;
+        };
+        let span = BlockHandle {
+            first_key: first.first_key.clone(),
+            last_key: last.last_key.clone(),
+            offset: first.offset,
+            entry_count: blocks.iter().map(|block| block.entry_count).sum(),
+        };
+
+        let mut filter = BloomFilter::new(self.keys.len().max(1), self.false_positive_rate);
+        for key in self.keys.drain(..) {
+            filter.insert(&key);
+        }
+        let payload = encode_payload(&blocks, &filter);
+
+        self.partitions.push(IndexPartition {
+            span,
+            payload_offset: self.payloads.len() as u64,
+            payload_len: payload.len() as u64,
+        });
+        self.payloads.extend_from_slice(&payload);
+    }
+
+    /// Close the last partition and return the top-level index and the
+    /// filter region
+    pub(crate) fn finish(mut self) -> (Vec<IndexPartition>, Vec<u8>) {
+        self.finish_partition();
+        (self.partitions, self.payloads)
+    }
+}
+
+/// Encode the top-level index as a count followed by each partition's span
+/// and payload location
+pub(crate) fn encode(partitions: &[IndexPartition]) -> Vec<u8> {
+    let spans: Vec<BlockHandle> = partitions.iter().map(|p| p.span.clone()).collect();
+    let mut buf = block_index::encode(&spans);
+    for partition in partitions {
+        buf.extend_from_slice(&partition.payload_offset.to_le_bytes());
+        buf.extend_from_slice(&partition.payload_len.to_le_bytes());
+    }
+    buf
+}
+
+/// Decode a top-level index written by `encode`
+pub(crate) fn decode(buf: &[u8]) -> io::Result<Vec<IndexPartition>> {
+    let spans = block_index::decode(buf)?;
+    let locations_len = spans.len() * 16;
+    if buf.len() < locations_len {
+        return Err(truncated());
+    }
+    let locations = &buf[buf.len() - locations_len..];
+
+    Ok(spans
+        .into_iter()
+        .zip(locations.chunks_exact(16))
+        .map(|(span, location)| IndexPartition {
+            span,
+            payload_offset: u64::from_le_bytes(location[..8].try_into().unwrap()),
+            payload_len: u64::from_le_bytes(location[8..].try_into().unwrap()),
+        })
+        .collect())
+}
+
+/// Encode a partition's block handles and filter, followed by a checksum
+fn encode_payload(blocks: &[BlockHandle], filter: &BloomFilter<String>) -> Vec<u8> {
+    let handles = block_index::encode(blocks);
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(handles.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&handles);
+    buf.extend_from_slice(&(filter.size_bits() as u64).to_le_bytes());
+    buf.extend_from_slice(&(filter.num_hashes() as u32).to_le_bytes());
+    buf.extend_from_slice(filter.get_bits());
+    let checksum = super::calculate_checksum(&buf);
+    buf.extend_from_slice(&checksum.to_le_bytes());
+    buf
+}
+
+/// Decode a payload written by `encode_payload`, verifying its checksum
+pub(crate) fn decode_payload(buf: &[u8]) -> io::Result<PartitionPayload> {
+    if buf.len() < 4 {
+        return Err(truncated());
+    }
+    let (data, checksum) = buf.split_at(buf.len() - 4);
+    if super::calculate_checksum(data) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Index partition checksum verification failed",
+        ));
+    }
+
+    let handles_len = u32::from_le_bytes(take(data, 0, 4)?.try_into().unwrap()) as usize;
+    let blocks = block_index::decode(take(data, 4, handles_len)?)?;
+    let filter_start = 4 + handles_len;
+    let size_bits = u64::from_le_bytes(take(data, filter_start, 8)?.try_into().unwrap()) as usize;
+    let num_hashes =
+        u32::from_le_bytes(take(data, filter_start + 8, 4)?.try_into().unwrap()) as usize;
+    let bits = data[(filter_start + 12).min(data.len())..].to_vec();
+    if bits.len() != size_bits.div_ceil(8) {
+        return Err(truncated());
+    }
+
+    Ok(PartitionPayload {
+        blocks,
+        filter: BloomFilter::from_parts(bits, size_bits, num_hashes),
+    })
+}
+
+/// Borrow `len` bytes at `start`
+fn take(buf: &[u8], start: usize, len: usize) -> io::Result<&[u8]> {
+    start
+        .checked_add(len)
+        .and_then(|end| buf.get(start..end))
+        .ok_or_else(truncated)
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Truncated SSTable index partition",
+    )
+}
@@ -0,0 +1,185 @@
+use super::{Compression, PrefixExtractor, SSTableReader, SSTableRecord, SSTableWriter};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Options controlling how `rewrite_sstable` stamps the file it writes
+#[derive(Debug, Clone)]
+pub struct IngestOptions {
+    /// Sequence every entry and tombstone in the file takes
+    pub global_sequence: u64,
+    /// Number to record in the file; `None` keeps the source's
+    pub file_number: Option<u64>,
+    /// Write time given to every entry, in milliseconds since the Unix
+    /// epoch, so snapshots taken before the ingest do not see the file;
+    /// `None` keeps the times the source recorded
+    pub written_at_ms: Option<u64>,
+    /// Whether the output gets a Bloom filter
+    pub use_bloom_filter: bool,
+    /// Target false positive rate of the output's Bloom filter
+    pub false_positive_rate: f64,
+    /// Compression applied to the output's values
+    pub compression: Compression,
+    /// Extractor whose prefixes are added to the output's Bloom filter
+    pub prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+}
+
+impl IngestOptions {
+    /// Options stamping a file with `global_sequence` and otherwise keeping
+    /// what the source recorded, with values stored uncompressed
+    pub fn new(global_sequence: u64) -> Self {
+        IngestOptions {
+            global_sequence,
+            file_number: None,
+            written_at_ms: None,
+            use_bloom_filter: true,
+            false_positive_rate: 0.01,
+            compression: Compression::None,
+            prefix_extractor: None,
+        }
+    }
+
+    /// Record `file_number` in the output's header
+    pub fn with_file_number(mut self, file_number: u64) -> Self {
+        self.file_number = Some(file_number);
+        self
+    }
+
+    /// Give every entry the write time `written_at_ms`
+    pub fn with_written_at_ms(mut self, written_at_ms: u64) -> Self {
+        self.written_at_ms = Some(written_at_ms);
+        self
+    }
+
+    /// Set whether the output gets a Bloom filter
+    pub fn with_bloom_filter(mut self, use_bloom_filter: bool) -> Self {
+        self.use_bloom_filter = use_bloom_filter;
+        self
+    }
+
+    /// Set the target false positive rate of the output's Bloom filter
+    pub fn with_false_positive_rate(mut self, false_positive_rate: f64) -> Self {
+        self.false_positive_rate = false_positive_rate;
+        self
+    }
+
+    /// Set the compression applied to the output's values
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Add each key's prefix under `extractor` to the output's Bloom filter
+    pub fn with_prefix_extractor(mut self, extractor: Arc<dyn PrefixExtractor>) -> Self {
+        self.prefix_extractor = Some(extractor);
+        self
+    }
+}
+
+/// Rewrite the SSTable at `source` to `target` stamped with the options'
+/// global sequence, returning every key the file writes or deletes.
+///
+/// Entries keep their order and expiries, and tombstones, range
+/// tombstones and user properties are carried over. The applied WAL LSN is not, since it refers
+/// to the WAL of whichever index wrote the source. Nor is the flush number:
+/// the output is its own newest flush, numbered by its file number, as it
+/// orders after the data it joins.
+pub fn rewrite_sstable(
+    source: &str,
+    target: &str,
+    options: &IngestOptions,
+) -> io::Result<Vec<String>> {
+    let reader = SSTableReader::open(source)?;
+    let mut writer = SSTableWriter::new(
+        target,
+        reader.entry_count() as usize,
+        options.use_bloom_filter,
+        options.false_positive_rate,
+    )?;
+    if let Some(file_number) = options.file_number.or(reader.file_number()) {
+        writer.set_file_number(file_number);
+        writer.set_flush_number(file_number);
+    }
+    writer.set_global_sequence(options.global_sequence);
+    for (name, value) in reader.properties().user_properties() {
+        writer.set_user_property(name, value)?;
+    }
+    if let Some(extractor) = &options.prefix_extractor {
+        writer.set_prefix_extractor(extractor.clone());
+    }
+    writer.set_compression(options.compression, None)?;
+    writer.set_checksum_kind(reader.checksum_kind())?;
+    // Entries are copied in file order, so an unsorted file stays unsorted
+    if !reader.keys_sorted() {
+        writer.allow_unsorted_keys()?;
+    }
+    if reader.has_prefix_compressed_keys() {
+        writer.set_key_prefix_compression()?;
+        if let Some(restart_interval) = reader.restart_interval() {
+            writer.set_restart_interval(restart_interval)?;
+        }
+    }
+
+    let mut keys = Vec::with_capacity(reader.entry_count() as usize);
+    keys.extend(reader.tombstones().keys().cloned());
+    let deletions = reader
+        .tombstones()
+        .iter()
+        .map(|(key, tombstone)| SSTableRecord::Delete {
+            key: key.clone(),
+            tombstone: tombstone.clone(),
+        })
+        .chain(
+            reader
+                .range_tombstones()
+                .fragments()
+                .iter()
+                .cloned()
+                .map(SSTableRecord::DeleteRange),
+        );
+    writer.write_all(deletions.map(Ok))?;
+
+    let write_times = reader.write_times().clone();
+    let expiries = reader.expiries().clone();
+    let sequences = reader.sequences().clone();
+    for (key, &segment) in reader.value_log_segments().iter() {
+        writer.mark_value_pointer(key, segment);
+    }
+    writer.write_all(reader.into_entries()?.map(|entry| {
+        let (key, value) = entry?;
+        keys.push(key.clone());
+        Ok(SSTableRecord::Put {
+            written_at_ms: options
+                .written_at_ms
+                .or_else(|| write_times.get(&key).copied()),
+            expires_at_ms: expiries.get(&key).copied(),
+            sequence: sequences.get(&key).copied(),
+            key,
+            value,
+        })
+    }))?;
+    writer.finalize()?;
+
+    Ok(keys)
+}
+
+/// Stamp the SSTable at `path` with `global_sequence` in place, for a file
+/// restored from a backup to order after the data already around it.
+///
+/// The file is rewritten with its values stored uncompressed, through a
+/// temporary file that replaces the original only once complete.
+pub fn assign_global_sequence(path: &Path, global_sequence: u64) -> io::Result<()> {
+    let path_str = path.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("SSTable path is not UTF-8: {}", path.display()),
+        )
+    })?;
+    let tmp_path = format!("{}.ingest", path_str);
+    rewrite_sstable(path_str, &tmp_path, &IngestOptions::new(global_sequence))?;
+
+    File::open(&tmp_path)?.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use std::io;
+
+/// Dense index from every key to the offset of its entry, sorted by key, for
+/// files whose keys were written unsorted and so cannot be located by block,
+/// and for legacy files, which have no blocks.
+///
+/// A key written more than once keeps its entries in file order, so the
+/// first offset listed for it is the one a full scan would find first.
+#[derive(Debug, Default)]
+pub(crate) struct KeyIndex {
+    keys: Vec<String>,
+    offsets: Vec<u64>,
+}
+
+impl KeyIndex {
+    /// Build an index over `(key, entry offset)` pairs in file order
+    pub(crate) fn build(mut entries: Vec<(String, u64)>) -> Self {
+        // A stable sort keeps a repeated key's entries in file order
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        let (keys, offsets) = entries.into_iter().unzip();
+        KeyIndex { keys, offsets }
+    }
+
+    /// Offsets of the entries holding `key`, in file order
+    pub(crate) fn candidates(&self, key: &str) -> &[u64] {
+        let start = self.keys.partition_point(|k| k.as_str() < key);
+        let end = start + self.keys[start..].partition_point(|k| k.as_str() == key);
+        &self.offsets[start..end]
+    }
+
+    /// Offsets of every entry, in key order
+    pub(crate) fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    /// Whether some key has more than one entry
+    pub(crate) fn has_repeated_keys(&self) -> bool {
+        self.keys.windows(2).any(|pair| pair[0] == pair[1])
+    }
+
+    /// Heap bytes held by the index
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.keys.iter().map(|key| key.len() + 8).sum()
+    }
+
+    /// Encode the index as an entry count followed by each key, as a length
+    /// and bytes, and its offset
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&(self.keys.len() as u32).to_le_bytes());
+        for (key, offset) in self.keys.iter().zip(&self.offsets) {
+            buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            buf.extend_from_slice(key.as_bytes());
+            buf.extend_from_slice(&offset.to_le_bytes());
+        }
+        buf
+    }
+
+    /// Decode an index written by `encode`
+    pub(crate) fn decode(buf: &[u8]) -> io::Result<Self> {
+        let mut cursor = buf;
+        let count = u32::from_le_bytes(take(&mut cursor, 4)?.try_into().unwrap()) as usize;
+
+        let mut keys = Vec::with_capacity(count.min(buf.len() / 12));
+        let mut offsets = Vec::with_capacity(count.min(buf.len() / 12));
+        for _ in 0..count {
+            let len = u32::from_le_bytes(take(&mut cursor, 4)?.try_into().unwrap()) as usize;
+            let key = String::from_utf8(take(&mut cursor, len)?.to_vec()).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "SSTable key index key is not valid UTF-8",
+                )
+            })?;
+            keys.push(key);
+            offsets.push(u64::from_le_bytes(
+                take(&mut cursor, 8)?.try_into().unwrap(),
+            ));
+        }
+        if keys.windows(2).any(|pair| pair[0] > pair[1]) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "SSTable key index is not sorted",
+            ));
+        }
+
+        Ok(KeyIndex { keys, offsets })
+    }
+
+    /// Decode the index a legacy file keeps from `index_offset` to its end:
+    /// each key, as a length and bytes, and the offset of its entry from
+    /// `data_offset`, where the entries start. Offsets are converted to file
+    /// offsets, and must fall among the entries.
+    pub(crate) fn decode_legacy(
+        buf: &[u8],
+        data_offset: u64,
+        index_offset: u64,
+    ) -> io::Result<Self> {
+        let mut cursor = buf;
+        let mut entries = Vec::new();
+        while !cursor.is_empty() {
+            let len = u32::from_le_bytes(take(&mut cursor, 4)?.try_into().unwrap()) as usize;
+            let key = String::from_utf8(take(&mut cursor, len)?.to_vec()).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Legacy SSTable index key is not valid UTF-8",
+                )
+            })?;
+            let offset = u64::from_le_bytes(take(&mut cursor, 8)?.try_into().unwrap());
+            let offset = data_offset
+                .checked_add(offset)
+                .filter(|offset| *offset < index_offset)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Legacy SSTable index points past the entries for {}", key),
+                    )
+                })?;
+            entries.push((key, offset));
+        }
+        Ok(Self::build(entries))
+    }
+
+    /// Number of entries indexed
+    pub(crate) fn len(&self) -> usize {
+        self.keys.len()
+    }
+}
+
+/// Split `len` bytes off the front of a buffer
+fn take<'a>(cursor: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if cursor.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Truncated SSTable key index",
+        ));
+    }
+    let (bytes, rest) = cursor.split_at(len);
+    *cursor = rest;
+    Ok(bytes)
+}
@@ -0,0 +1,152 @@
+use super::MAX_KEY_SIZE;
+use crate::checked_len;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+/// Size of the shared prefix length that starts each stored key
+pub(crate) const SHARED_LEN_SIZE: usize = 4;
+
+/// Restart interval used when key prefix compression is enabled without one
+pub const DEFAULT_PREFIX_RESTART_INTERVAL: usize = 16;
+
+/// Encode `key` as the length of the prefix it shares with `restart_key`,
+/// as a little-endian `u32`, followed by the rest of the key. Keys at a
+/// restart point have no `restart_key` and are stored whole.
+pub(crate) fn encode(key: &[u8], restart_key: Option<&[u8]>) -> Vec<u8> {
+    let shared = restart_key.map_or(0, |restart_key| {
+        key.iter()
+            .zip(restart_key)
+            .take_while(|(a, b)| a == b)
+            .count()
+    });
+    let mut field = Vec::with_capacity(SHARED_LEN_SIZE + key.len() - shared);
+    field.extend_from_slice(&(shared as u32).to_le_bytes());
+    field.extend_from_slice(&key[shared..]);
+    field
+}
+
+/// Split a stored key written by `encode` into its shared prefix length and
+/// the rest of the key
+fn decode(field: &[u8]) -> io::Result<(usize, &[u8])> {
+    if field.len() < SHARED_LEN_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Prefix-compressed key is missing its shared length",
+        ));
+    }
+    let (shared, suffix) = field.split_at(SHARED_LEN_SIZE);
+    Ok((
+        u32::from_le_bytes(shared.try_into().unwrap()) as usize,
+        suffix,
+    ))
+}
+
+/// Rebuilds the keys of a file written with key prefix compression, where
+/// each key is stored as the length of the prefix it shares with the key at
+/// the last restart point before it, followed by the rest of the key.
+///
+/// The expander remembers the last restart key it saw, so entries read in
+/// file order from a restart point need nothing else. An entry read on its
+/// own needs `prime` first, to read the restart key it depends on.
+#[derive(Debug, Clone)]
+pub(crate) struct KeyExpander {
+    restart_points: Arc<[u64]>,
+    /// Offset and key of the last restart entry read
+    restart: Option<(u64, Vec<u8>)>,
+}
+
+impl KeyExpander {
+    pub(crate) fn new(restart_points: Arc<[u64]>) -> Self {
+        KeyExpander {
+            restart_points,
+            restart: None,
+        }
+    }
+
+    /// Offset of the restart entry at or before `offset`
+    fn restart_for(&self, offset: u64) -> io::Result<u64> {
+        match self
+            .restart_points
+            .partition_point(|&point| point <= offset)
+        {
+            0 => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Entry at {} precedes every restart point", offset),
+            )),
+            index => Ok(self.restart_points[index - 1]),
+        }
+    }
+
+    /// Offset of the restart entry whose key the entry at `offset` needs,
+    /// if it has not been read
+    pub(crate) fn missing_restart(&self, offset: u64) -> io::Result<Option<u64>> {
+        let restart = self.restart_for(offset)?;
+        let known = self
+            .restart
+            .as_ref()
+            .is_some_and(|(known, _)| *known == restart);
+        Ok((restart != offset && !known).then_some(restart))
+    }
+
+    /// Read the restart key the entry at `offset` needs from `file`, if it
+    /// has not been read, leaving the file where it was
+    pub(crate) fn prime<R: Read + Seek>(
+        &mut self,
+        file: &mut R,
+        file_size: u64,
+        offset: u64,
+    ) -> io::Result<()> {
+        let Some(restart) = self.missing_restart(offset)? else {
+            return Ok(());
+        };
+        let position = file.stream_position()?;
+        file.seek(SeekFrom::Start(restart))?;
+        let len = checked_len::read_len(file, file_size, MAX_KEY_SIZE, "Key")?;
+        let mut field = vec![0u8; len];
+        file.read_exact(&mut field)?;
+        file.seek(SeekFrom::Start(position))?;
+        self.expand(restart, &field).map(drop)
+    }
+
+    /// Full key of the entry at `offset` from its stored key. The restart
+    /// key it shares a prefix with must have been read, by expanding the
+    /// restart entry or through `prime`.
+    pub(crate) fn expand(&mut self, offset: u64, field: &[u8]) -> io::Result<Vec<u8>> {
+        let restart = self.restart_for(offset)?;
+        let (shared, suffix) = decode(field)?;
+        if restart == offset {
+            if shared != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Key at restart point {} shares a prefix", offset),
+                ));
+            }
+            self.restart = Some((offset, suffix.to_vec()));
+            return Ok(suffix.to_vec());
+        }
+
+        match &self.restart {
+            Some((known, restart_key)) if *known == restart => {
+                let prefix = restart_key.get(..shared).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Key at {} shares {} bytes with a {}-byte restart key",
+                            offset,
+                            shared,
+                            restart_key.len()
+                        ),
+                    )
+                })?;
+                let mut key = Vec::with_capacity(shared + suffix.len());
+                key.extend_from_slice(prefix);
+                key.extend_from_slice(suffix);
+                Ok(key)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Restart key for the entry at {} has not been read", offset),
+            )),
+        }
+    }
+}
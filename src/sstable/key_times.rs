@@ -0,0 +1,60 @@
+use crate::checked_len;
+use std::collections::HashMap;
+use std::io;
+
+/// Encode per-key times, such as write or expiry times, as a count followed
+/// by length-prefixed keys, each with its time in milliseconds since the
+/// Unix epoch. Per-key sequences and value log segments are stored the same
+/// way.
+pub(crate) fn encode(times: &[(String, u64)]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(times.len() as u32).to_le_bytes());
+    for (key, time_ms) in times {
+        buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        buf.extend_from_slice(key.as_bytes());
+        buf.extend_from_slice(&time_ms.to_le_bytes());
+    }
+    buf
+}
+
+/// Decode per-key times written by `encode`
+pub(crate) fn decode(buf: &[u8]) -> io::Result<HashMap<String, u64>> {
+    let mut cursor = buf;
+    let count = u32::from_le_bytes(take(&mut cursor, 4)?.try_into().unwrap());
+    // Each key takes at least its length and time
+    let count = checked_len::check_count(
+        count as u64,
+        u32::MAX as usize,
+        12,
+        cursor.len() as u64,
+        "SSTable key times",
+    )?;
+
+    let mut times = HashMap::with_capacity(count);
+    for _ in 0..count {
+        let key_len = u32::from_le_bytes(take(&mut cursor, 4)?.try_into().unwrap()) as usize;
+        let key = String::from_utf8(take(&mut cursor, key_len)?.to_vec()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "SSTable key time key is not valid UTF-8",
+            )
+        })?;
+        let time_ms = u64::from_le_bytes(take(&mut cursor, 8)?.try_into().unwrap());
+        times.insert(key, time_ms);
+    }
+
+    Ok(times)
+}
+
+/// Split `len` bytes off the front of a buffer
+fn take<'a>(cursor: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if cursor.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Truncated SSTable key times block",
+        ));
+    }
+    let (bytes, rest) = cursor.split_at(len);
+    *cursor = rest;
+    Ok(bytes)
+}
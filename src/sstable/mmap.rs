@@ -0,0 +1,158 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+/// A read-only memory map of a whole file
+pub(crate) struct MappedFile {
+    ptr: *const u8,
+    len: usize,
+}
+
+// SAFETY: the mapping is read-only and only ever read through shared slices
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    /// Map `file` read-only. Empty files, and platforms without `mmap`,
+    /// are unsupported.
+    #[cfg(unix)]
+    pub(crate) fn map(file: &File) -> io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if len == 0 {
+            return Err(io::Error::from(io::ErrorKind::Unsupported));
+        }
+        // SAFETY: the file descriptor is open for reading and the length is
+        // the file's size. SSTables are never modified once written, so the
+        // mapped bytes do not change under the slices handed out.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(MappedFile {
+            ptr: ptr as *const u8,
+            len,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn map(_file: &File) -> io::Result<Self> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: `ptr` points at `len` mapped bytes until the map is dropped
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was created by `map` with this length and no
+        // slice of it outlives `self`
+        #[cfg(unix)]
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+impl fmt::Debug for MappedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MappedFile")
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// A value returned by `SSTableReader::get_ref`. Values stored
+/// uncompressed are borrowed from a memory map of the file rather than
+/// copied; the map stays alive for as long as any value borrowed from it,
+/// even after the reader is dropped. Cloning is cheap either way.
+#[derive(Clone)]
+pub struct ValueRef {
+    inner: Inner,
+}
+
+#[derive(Clone)]
+enum Inner {
+    Mapped(Arc<MappedFile>, Range<usize>),
+    Owned(Arc<[u8]>),
+}
+
+impl ValueRef {
+    pub(crate) fn mapped(map: Arc<MappedFile>, range: Range<usize>) -> Self {
+        ValueRef {
+            inner: Inner::Mapped(map, range),
+        }
+    }
+
+    pub(crate) fn owned(value: Vec<u8>) -> Self {
+        ValueRef {
+            inner: Inner::Owned(value.into()),
+        }
+    }
+
+    /// Whether the value is borrowed from the file's memory map rather than
+    /// decoded into memory of its own
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.inner, Inner::Mapped(..))
+    }
+}
+
+impl Deref for ValueRef {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.inner {
+            Inner::Mapped(map, range) => &map[range.clone()],
+            Inner::Owned(value) => value,
+        }
+    }
+}
+
+impl AsRef<[u8]> for ValueRef {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl PartialEq for ValueRef {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for ValueRef {}
+
+impl fmt::Debug for ValueRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValueRef")
+            .field("len", &self.len())
+            .field("mapped", &self.is_mapped())
+            .finish()
+    }
+}
+
+impl From<ValueRef> for Vec<u8> {
+    fn from(value: ValueRef) -> Self {
+        value.to_vec()
+    }
+}
@@ -1,16 +1,204 @@
 use crate::bloom::{BloomFilter, PartitionedBloomFilter};
+use crate::checked_len;
+use crate::clock::{Clock, SystemClock};
+use crate::locks;
 use crc32fast;
-use std::collections::BTreeMap;
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, VecDeque};
 use std::fs::{self, File};
 use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod block_compression;
+mod block_filters;
+pub mod block_index;
+mod block_map;
+pub mod builder;
+pub mod checksum;
+pub mod codec;
+pub mod compaction_report;
+pub mod compression;
+pub mod digest;
+pub mod encryption;
+pub mod filter_cache;
+pub mod footer;
+mod hash_index;
+mod index_partitions;
+pub mod ingest;
+mod key_index;
+mod key_prefixes;
+mod key_times;
+mod mmap;
+pub mod prefix;
+pub mod properties;
+pub mod range_tombstones;
+mod restart_points;
+pub mod set_reader;
+mod table_file;
+pub mod tombstones;
+pub mod upgrade;
+mod verify;
+
+pub use block_compression::CompressionType;
+use block_filters::BlockFilter;
+pub use block_index::BlockHandle;
+use block_map::BlockEncoder;
+pub use builder::{DataBlock, DataBlockBuilder, FilterBuilder, SSTableRecord};
+use builder::{DataSummary, MetaBuilder};
+pub use checksum::ChecksumKind;
+pub use codec::{Codec, CodecRegistry};
+use compaction_report::{CompactionTrace, Decision};
+pub use compression::{looks_incompressible, Compression, ValueDecoder, ZstdOptions};
+pub use digest::{Digest, MerkleHasher};
+use encryption::BlockCipher;
+pub use encryption::{KeyProvider, StaticKeyProvider};
+use filter_cache::FilterCacheHandle;
+pub use filter_cache::{FilterCache, FilterCacheStats, LoadedFilter};
+pub use footer::{FOOTER_SIZE, Footer};
+use hash_index::HashIndex;
+use index_partitions::{PartitionBuilder, PartitionPayload};
+use key_index::KeyIndex;
+pub use key_prefixes::DEFAULT_PREFIX_RESTART_INTERVAL;
+pub(crate) use key_prefixes::KeyExpander;
+use mmap::MappedFile;
+pub use mmap::ValueRef;
+pub use prefix::{DelimiterPrefixExtractor, FixedPrefixExtractor, PrefixExtractor};
+pub use properties::SSTableProperties;
+pub use range_tombstones::{FragmentedRangeTombstones, RangeTombstone};
+pub use set_reader::SSTableSetReader;
+pub(crate) use table_file::TableFile;
+pub use tombstones::Tombstone;
+pub use verify::VerifyReport;
 
 /// Calculate a CRC32 checksum
 fn calculate_checksum(data: &[u8]) -> u32 {
     crc32fast::hash(data)
 }
 
+/// Calculate the checksum stored alongside an entry by a file using CRC32,
+/// the default `ChecksumKind` and the only one before version 9.
+///
+/// The CRC32 covers the encoded entry: key length, key, value length and value,
+/// with lengths as little-endian `u32`s.
+pub fn entry_checksum(key: &str, value: &[u8]) -> u32 {
+    ChecksumKind::Crc32.entry_checksum(key, value)
+}
+
+/// Size of the header written by SSTable format `version`; files from
+/// version 8 keep only the magic number, version, cipher and block
+/// compression type in it, joined by the checksum kind from version 9, files
+/// from version 7 record the block compression type and files from version 6
+/// the cipher after the version 5 fields, files before version 5 have no file
+/// number, and files before version 3 have the legacy memtable header
+pub fn header_size(version: u32) -> usize {
+    if version >= CHECKSUM_KIND_VERSION {
+        MINIMAL_HEADER_SIZE
+            + HEADER_CIPHER_SIZE
+            + HEADER_COMPRESSION_TYPE_SIZE
+            + HEADER_CHECKSUM_KIND_SIZE
+    } else if version >= FOOTER_VERSION {
+        MINIMAL_HEADER_SIZE + HEADER_CIPHER_SIZE + HEADER_COMPRESSION_TYPE_SIZE
+    } else if version >= BLOCK_COMPRESSION_VERSION {
+        HEADER_SIZE + HEADER_CIPHER_SIZE + HEADER_COMPRESSION_TYPE_SIZE
+    } else if version >= ENCRYPTION_VERSION {
+        HEADER_SIZE + HEADER_CIPHER_SIZE
+    } else if version >= FILE_NUMBER_VERSION {
+        HEADER_SIZE
+    } else if version >= CHECKSUMMED_VERSION {
+        HEADER_SIZE - HEADER_FILE_NUMBER_SIZE
+    } else {
+        LEGACY_HEADER_SIZE
+    }
+}
+
+/// Check whether a buffer starts with a well-formed version 3 or later header.
+///
+/// Files written by the memtable's legacy flush path share the magic number
+/// but not the header layout, so the header checksum is what tells them apart.
+pub fn is_valid_header(header: &[u8]) -> bool {
+    if header.len() < HEADER_MAGIC_SIZE + HEADER_VERSION_SIZE {
+        return false;
+    }
+    let version = u32::from_le_bytes(
+        header[HEADER_MAGIC_SIZE..HEADER_MAGIC_SIZE + HEADER_VERSION_SIZE]
+            .try_into()
+            .unwrap(),
+    );
+    let size = header_size(version);
+    if version < CHECKSUMMED_VERSION || header.len() < size {
+        return false;
+    }
+
+    let magic = u64::from_le_bytes(header[..HEADER_MAGIC_SIZE].try_into().unwrap());
+    let checksum_offset = size - HEADER_CHECKSUM_SIZE;
+    let stored = u32::from_le_bytes(header[checksum_offset..size].try_into().unwrap());
+
+    magic == MAGIC && calculate_checksum(&header[..checksum_offset]) == stored
+}
+
+/// Whether the data blocks are encrypted, as recorded in a header
+/// `is_valid_header` accepted; files before version 6 are not
+pub fn header_encrypted(header: &[u8]) -> io::Result<bool> {
+    let version_at = HEADER_MAGIC_SIZE..HEADER_MAGIC_SIZE + HEADER_VERSION_SIZE;
+    let version = u32::from_le_bytes(header[version_at].try_into().unwrap());
+    if version < ENCRYPTION_VERSION {
+        return Ok(false);
+    }
+    cipher_from_id(header[header_cipher_offset(version)])
+}
+
+/// Codec the data blocks are compressed with, as recorded in a header
+/// `is_valid_header` accepted; files before version 7 are not compressed
+pub fn header_compression_type(header: &[u8]) -> io::Result<CompressionType> {
+    let version_at = HEADER_MAGIC_SIZE..HEADER_MAGIC_SIZE + HEADER_VERSION_SIZE;
+    let version = u32::from_le_bytes(header[version_at].try_into().unwrap());
+    if version < BLOCK_COMPRESSION_VERSION {
+        return Ok(CompressionType::None);
+    }
+    CompressionType::from_id(header[header_cipher_offset(version) + HEADER_CIPHER_SIZE])
+}
+
+/// Offset of the cipher byte in a version 6 or later header, which the block
+/// compression type follows: after the version from version 8, after the
+/// version 5 fields before it
+fn header_cipher_offset(version: u32) -> usize {
+    if version >= FOOTER_VERSION {
+        HEADER_MAGIC_SIZE + HEADER_VERSION_SIZE
+    } else {
+        HEADER_SIZE - HEADER_CHECKSUM_SIZE
+    }
+}
+
+/// Whether the cipher byte of a header says blocks are encrypted
+fn cipher_from_id(id: u8) -> io::Result<bool> {
+    match id {
+        0 => Ok(false),
+        encryption::AES_256_GCM_CIPHER_ID => Ok(true),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unknown SSTable cipher: {}", id),
+        )),
+    }
+}
+
+/// Checksum kind recorded in a header `is_valid_header` accepted, after the
+/// block compression type; files before version 9 use CRC32
+pub fn header_checksum_kind(header: &[u8]) -> io::Result<ChecksumKind> {
+    let version_at = HEADER_MAGIC_SIZE..HEADER_MAGIC_SIZE + HEADER_VERSION_SIZE;
+    let version = u32::from_le_bytes(header[version_at].try_into().unwrap());
+    if version < CHECKSUM_KIND_VERSION {
+        return Ok(ChecksumKind::Crc32);
+    }
+    let at = header_cipher_offset(version) + HEADER_CIPHER_SIZE + HEADER_COMPRESSION_TYPE_SIZE;
+    ChecksumKind::from_id(header[at])
+}
+
 /// Represents metadata about an SSTable file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct SSTableInfo {
     /// Path to the SSTable file
     pub path: String,
@@ -20,11 +208,126 @@ pub struct SSTableInfo {
     pub entry_count: u64,
     /// Flag indicating if this SSTable has a Bloom filter
     pub has_bloom_filter: bool,
+    /// Level the SSTable belongs to
+    pub level: u32,
+    /// Smallest key in the SSTable, if it has any entries
+    pub min_key: Option<String>,
+    /// Largest key in the SSTable, if it has any entries
+    pub max_key: Option<String>,
+    /// When the SSTable was written, in seconds since the Unix epoch
+    pub created_at_secs: u64,
+    /// Size of the Bloom filter section in bytes
+    pub bloom_bytes: u64,
+    /// Total size of the keys and values as written, before compression
+    pub raw_bytes: u64,
+    /// On-disk size of the data section holding the entries
+    pub data_bytes: u64,
+    /// Number of tombstones for deleted keys recorded in the SSTable
+    pub tombstone_count: u64,
+    /// Number the manifest allocated to the file, 0 if it has none
+    pub file_number: u64,
+    /// File number of the newest flush whose writes the SSTable holds;
+    /// files with overlapping keys are read newest flush first
+    pub flush_number: u64,
+    /// Largest sequence of any entry in the SSTable, 0 if its entries carry
+    /// no sequences
+    pub max_sequence: u64,
+    /// Raw key and value bytes divided by the on-disk size of the data
+    /// section; below 1.0 when framing and checksums outweigh compression
+    pub compression_ratio: f64,
+}
+
+impl SSTableInfo {
+    /// Fraction of the file's records that are tombstones; 0.0 for an empty file
+    pub fn deletion_ratio(&self) -> f64 {
+        let total = self.entry_count + self.tombstone_count;
+        if total == 0 {
+            return 0.0;
+        }
+        self.tombstone_count as f64 / total as f64
+    }
+
+    /// Whether the key ranges of two files overlap
+    fn overlaps(&self, other: &SSTableInfo) -> bool {
+        match (&self.min_key, &self.max_key, &other.min_key, &other.max_key) {
+            (Some(min), Some(max), Some(other_min), Some(other_max)) => {
+                min <= other_max && other_min <= max
+            }
+            _ => false,
+        }
+    }
 }
 
 /// Constants for SSTable format
 pub const MAGIC: u64 = 0x4C534D_5353544142; // "LSM-SSTAB" in hex
-pub const VERSION: u32 = 3; // Updated to version 3 to support block checksums
+pub const VERSION: u32 = 10; // Version 10 may store keys prefix-compressed
+/// First version whose index offset points at a meta section
+pub const META_SECTION_VERSION: u32 = 4;
+/// First version whose header records the file number
+pub const FILE_NUMBER_VERSION: u32 = 5;
+/// First version whose header records whether data blocks are encrypted
+pub const ENCRYPTION_VERSION: u32 = 6;
+/// First version whose header records the `CompressionType` of its data
+/// blocks
+pub const BLOCK_COMPRESSION_VERSION: u32 = 7;
+/// First version keeping the entry count, offsets and file number in a
+/// footer, so the header is written once and never revisited
+pub const FOOTER_VERSION: u32 = 8;
+/// First version whose header records the `ChecksumKind` of its entries
+/// and meta sections
+pub const CHECKSUM_KIND_VERSION: u32 = 9;
+/// First version whose keys may be stored prefix-compressed against the
+/// keys at restart points, as its properties record
+pub const KEY_PREFIX_VERSION: u32 = 10;
+/// Version written by the memtable's legacy `flush_to_sstable` layout
+pub const LEGACY_VERSION: u32 = 1;
+/// First version with a checksummed header, a Bloom filter and per-entry
+/// checksums; versions 1 and 2 use the legacy memtable layout
+pub const CHECKSUMMED_VERSION: u32 = 3;
+/// Size of the legacy header: magic, version, entry count and index offset
+pub const LEGACY_HEADER_SIZE: usize = 28;
+/// Name of the meta section holding `SSTableProperties`
+pub const PROPERTIES_SECTION: &str = "properties";
+/// Name of the meta section holding per-key write times
+pub const WRITE_TIMES_SECTION: &str = "write_times";
+/// Name of the meta section holding per-key expiry times
+pub const EXPIRIES_SECTION: &str = "expiries";
+/// Name of the meta section holding the sequence of the write behind each
+/// entry
+pub const SEQUENCES_SECTION: &str = "sequences";
+/// Name of the meta section holding tombstones for deleted keys
+pub const TOMBSTONES_SECTION: &str = "tombstones";
+/// Name of the meta section holding tombstones for deleted key ranges
+pub const RANGE_TOMBSTONES_SECTION: &str = "range_tombstones";
+/// Name of the meta section holding the dictionary values were compressed with
+pub const COMPRESSION_DICT_SECTION: &str = "compression_dict";
+/// Name of the meta section locating each data block and its key span
+pub const BLOCK_INDEX_SECTION: &str = "block_index";
+/// Name of the meta section holding the top level of a partitioned index
+pub const INDEX_PARTITIONS_SECTION: &str = "index_partitions";
+/// Name of the meta section holding a Bloom filter for each data block
+pub const BLOCK_FILTERS_SECTION: &str = "block_filters";
+/// Name of the meta section holding a hash table from keys to entry offsets
+pub const HASH_INDEX_SECTION: &str = "hash_index";
+/// Name of the meta section holding the offsets of entries lookups can start
+/// scanning a block from
+pub const RESTART_POINTS_SECTION: &str = "restart_points";
+/// Name of the meta section holding every key and its entry's offset, for
+/// files whose keys are unsorted
+pub const KEY_INDEX_SECTION: &str = "key_index";
+/// Name of the meta section naming the entries whose value is a pointer into
+/// a value log, with the log segment each points into
+pub const VALUE_LOG_SECTION: &str = "value_log";
+/// Upper bound on meta sections, to reject garbage counts early
+const MAX_META_SECTIONS: usize = 64;
+/// Largest Bloom filter, in bits, a reader will load
+const MAX_FILTER_BITS: usize = 100_000_000;
+/// Upper bound on a stored filter's hash functions
+const MAX_FILTER_HASHES: usize = 20;
+/// Upper bound on a stored partitioned filter's partitions
+const MAX_FILTER_PARTITIONS: usize = 64;
+/// Blocks each thread builds per window of `build_from_sorted_iter`
+const PARALLEL_BLOCKS_PER_THREAD: usize = 4;
 pub const HEADER_MAGIC_SIZE: usize = 8;
 pub const HEADER_VERSION_SIZE: usize = 4;
 pub const HEADER_ENTRY_COUNT_SIZE: usize = 8;
@@ -32,7 +335,12 @@ pub const HEADER_INDEX_OFFSET_SIZE: usize = 8;
 pub const HEADER_BLOOM_OFFSET_SIZE: usize = 8; // Offset to bloom filter
 pub const HEADER_BLOOM_SIZE_SIZE: usize = 8; // Size of bloom filter in bytes
 pub const HEADER_HAS_BLOOM_SIZE: usize = 1; // Flag indicating if bloom filter exists
+pub const HEADER_FILE_NUMBER_SIZE: usize = 8; // Number allocated to the file by the manifest
+pub const HEADER_CIPHER_SIZE: usize = 1; // Cipher data blocks are encrypted with, 0 for none
+pub const HEADER_COMPRESSION_TYPE_SIZE: usize = 1; // Codec data blocks are compressed with
 pub const HEADER_CHECKSUM_SIZE: usize = 4; // File header checksum
+pub const HEADER_CHECKSUM_KIND_SIZE: usize = 1; // Checksum kind of entries and meta sections
+/// Size of the version 5 header, which versions 6 and 7 extend
 pub const HEADER_SIZE: usize = HEADER_MAGIC_SIZE
     + HEADER_VERSION_SIZE
     + HEADER_ENTRY_COUNT_SIZE
@@ -40,21 +348,105 @@ pub const HEADER_SIZE: usize = HEADER_MAGIC_SIZE
     + HEADER_BLOOM_OFFSET_SIZE
     + HEADER_BLOOM_SIZE_SIZE
     + HEADER_HAS_BLOOM_SIZE
+    + HEADER_FILE_NUMBER_SIZE
     + HEADER_CHECKSUM_SIZE;
-
-/// SSTable writer that supports both regular and partitioned Bloom filters
-pub struct SSTableWriter {
-    file: File,
+/// Size of the fixed part of the header of version 8 and later files: magic,
+/// version and header checksum, with the offsets and counts in the footer
+pub const MINIMAL_HEADER_SIZE: usize =
+    HEADER_MAGIC_SIZE + HEADER_VERSION_SIZE + HEADER_CHECKSUM_SIZE;
+/// Size of the largest header of any version, that of version 7
+pub const MAX_HEADER_SIZE: usize = HEADER_SIZE + HEADER_CIPHER_SIZE + HEADER_COMPRESSION_TYPE_SIZE;
+
+/// Largest key, in bytes, that the SSTable format will write or read back
+pub const MAX_KEY_SIZE: usize = 1024 * 1024;
+/// Largest value, in bytes, that the SSTable format will write or read back
+pub const MAX_VALUE_SIZE: usize = 10 * 1024 * 1024;
+
+/// Default size at which buffered entries are written out as a data block
+pub const DATA_BLOCK_SIZE: usize = 64 * 1024;
+
+/// Index partitions a reader keeps loaded at once
+const MAX_RESIDENT_PARTITIONS: usize = 16;
+
+/// SSTable writer that supports both regular and partitioned Bloom filters.
+///
+/// Entries are encoded by a `DataBlockBuilder`, keys go to a `FilterBuilder`
+/// and per-key metadata to a `MetaBuilder`; the writer only lays their output
+/// out in the file. Blocks built elsewhere, for example by parallel
+/// subcompactions, can be appended with `append_block`.
+///
+/// The writer only appends, so besides files it can stream to any
+/// `io::Write` sink, such as an upload, an encrypting wrapper or a
+/// `Vec<u8>`, through `from_writer` and `finish`.
+pub struct SSTableWriter<W: Write = File> {
+    sink: W,
+    /// Function entry and meta section checksums are computed with
+    checksum_kind: ChecksumKind,
     entry_count: u64,
-    bloom_filter: Option<BloomFilter<String>>,
-    partitioned_bloom_filter: Option<PartitionedBloomFilter<String>>,
     index_offset: u64,
     bloom_offset: u64,
     bloom_size: u64,
     has_bloom_filter: bool,
-    #[allow(dead_code)] // For future optimistic concurrency implementation
-    use_partitioned_bloom: bool,
+    /// Number allocated to the file by the manifest, 0 if it has none
+    file_number: u64,
+    /// Entries written but not yet flushed to the file
+    pending: DataBlockBuilder,
+    filter: FilterBuilder,
+    meta: MetaBuilder,
     checksums: Vec<u32>, // Added checksums for data blocks
+    content_hasher: MerkleHasher,
+    /// Last key written, used to track whether keys arrive in sorted order
+    last_key: Option<String>,
+    keys_sorted: bool,
+    /// Identifies the compression settings blocks must be encoded with
+    compression_id: Option<u32>,
+    /// Total size of the keys and values written, before compression
+    raw_bytes: u64,
+    /// Groups blocks into index partitions, if the index is partitioned
+    partitions: Option<PartitionBuilder>,
+    /// Offset the next byte lands at, counting data blocks as written
+    /// rather than as stored
+    position: u64,
+    /// Whether the header has been written, ahead of the first bytes after it
+    header_written: bool,
+    /// Encrypts data blocks, if they are
+    block_encoder: BlockEncoder,
+    /// False positive rate of each data block's own filter, if blocks get one
+    block_filter_fpr: Option<f64>,
+    /// Hash and offset of every entry in file order, if the file gets a
+    /// hash index
+    hash_entries: Option<Vec<(u64, u64)>>,
+    /// Key and offset of every entry in file order, for the key index
+    /// written if keys turn out unsorted; only kept when unsorted keys are
+    /// allowed, and not with a hash index
+    key_entries: Option<Vec<(String, u64)>>,
+    /// Whether keys that do not strictly ascend are refused
+    strict_key_order: bool,
+    /// Size at which buffered entries are written out as a data block
+    block_size: usize,
+    /// Entries between restart points, if restart points are recorded
+    restart_interval: Option<usize>,
+    /// Whether keys are stored prefix-compressed against restart point keys
+    key_prefix_compression: bool,
+    /// Size at which index partitions are closed, if they are closed by size
+    index_block_size: Option<usize>,
+}
+
+/// Encode the values in `chunk` into a block with `builder`
+fn build_block(mut builder: DataBlockBuilder, chunk: Vec<SSTableRecord>) -> io::Result<DataBlock> {
+    for record in chunk {
+        if let SSTableRecord::Put {
+            key,
+            value,
+            written_at_ms,
+            expires_at_ms,
+            sequence,
+        } = record
+        {
+            builder.add_with_sequence(&key, &value, written_at_ms, expires_at_ms, sequence)?;
+        }
+    }
+    Ok(builder.finish())
 }
 
 impl SSTableWriter {
@@ -71,490 +463,1426 @@ impl SSTableWriter {
             use_bloom_filter,
             false_positive_rate,
             false,
+            CompressionType::None,
         )
     }
 
-    /// Create a new SSTable writer with additional options for partitioned bloom filter
+    /// Create a new SSTable writer with additional options for partitioned
+    /// bloom filter and for compressing whole data blocks with
+    /// `compression_type`
     pub fn new_with_options(
         path: &str,
         expected_entries: usize,
         use_bloom_filter: bool,
         false_positive_rate: f64,
         use_partitioned_bloom: bool,
+        compression_type: CompressionType,
     ) -> io::Result<Self> {
-        let file = File::create(path)?;
-
-        // Create appropriate bloom filter type if requested
-        let (bloom_filter, partitioned_bloom_filter) = if use_bloom_filter {
-            if use_partitioned_bloom {
-                // Use partitioned bloom filter for parallel lookups
-                let num_partitions = num_cpus::get();
-                (
-                    None,
-                    Some(PartitionedBloomFilter::new(
-                        expected_entries,
-                        false_positive_rate,
-                        num_partitions,
-                    )),
-                )
-            } else {
-                // Use regular bloom filter
-                (
-                    Some(BloomFilter::new(expected_entries, false_positive_rate)),
-                    None,
-                )
-            }
-        } else {
-            (None, None)
-        };
+        let mut writer = Self::from_writer(
+            File::create(path)?,
+            expected_entries,
+            use_bloom_filter,
+            false_positive_rate,
+            use_partitioned_bloom,
+        )?;
+        writer.set_compression_type(compression_type)?;
+        Ok(writer)
+    }
+
+    /// Finalize the SSTable by writing the index and Bloom filter, then
+    /// sync the file
+    pub fn finalize(self) -> io::Result<()> {
+        self.finish()?.sync_all()
+    }
+}
 
-        let mut writer = SSTableWriter {
-            file,
+impl<W: Write> SSTableWriter<W> {
+    /// Create an SSTable writer streaming the table to `sink`. The header
+    /// is written with the first bytes after it, and what is only known at
+    /// the end goes in the footer.
+    pub fn from_writer(
+        sink: W,
+        expected_entries: usize,
+        use_bloom_filter: bool,
+        false_positive_rate: f64,
+        use_partitioned_bloom: bool,
+    ) -> io::Result<Self> {
+        Ok(SSTableWriter {
+            sink,
+            checksum_kind: ChecksumKind::default(),
             entry_count: 0,
-            bloom_filter,
-            partitioned_bloom_filter,
             index_offset: 0,
             bloom_offset: 0,
             bloom_size: 0,
             has_bloom_filter: use_bloom_filter,
-            #[allow(dead_code)] // For future optimistic concurrency implementation
-            use_partitioned_bloom,
+            file_number: 0,
+            pending: DataBlockBuilder::new(),
+            filter: FilterBuilder::new(
+                expected_entries,
+                use_bloom_filter,
+                false_positive_rate,
+                use_partitioned_bloom,
+            ),
+            meta: MetaBuilder::default(),
             checksums: Vec::new(),
-        };
-
-        // Write header with placeholders for values we'll fill in later
-        writer.write_header()?;
-
-        Ok(writer)
+            content_hasher: MerkleHasher::new(),
+            last_key: None,
+            keys_sorted: true,
+            compression_id: None,
+            raw_bytes: 0,
+            partitions: None,
+            position: header_size(VERSION) as u64,
+            header_written: false,
+            block_encoder: BlockEncoder::new(),
+            block_filter_fpr: None,
+            hash_entries: None,
+            key_entries: None,
+            strict_key_order: true,
+            block_size: DATA_BLOCK_SIZE,
+            restart_interval: None,
+            key_prefix_compression: false,
+            index_block_size: None,
+        })
     }
 
     /// Write a key-value pair to the SSTable
     pub fn write_entry(&mut self, key: &str, value: &[u8]) -> io::Result<()> {
-        // Write key length (4 bytes)
-        let key_len = key.len() as u32;
-        self.file.write_all(&key_len.to_le_bytes())?;
-
-        // Write key
-        self.file.write_all(key.as_bytes())?;
-
-        // Write value length (4 bytes)
-        let value_len = value.len() as u32;
-        self.file.write_all(&value_len.to_le_bytes())?;
+        self.write_entry_with_metadata(key, value, None, None)
+    }
 
-        // Write value
-        self.file.write_all(value)?;
+    /// Write a key-value pair along with the time it was written, in
+    /// milliseconds since the Unix epoch
+    pub fn write_entry_with_time(
+        &mut self,
+        key: &str,
+        value: &[u8],
+        written_at_ms: u64,
+    ) -> io::Result<()> {
+        self.write_entry_with_metadata(key, value, Some(written_at_ms), None)
+    }
 
-        // Calculate and store checksum for this entry
-        let mut entry_data = Vec::new();
-        entry_data.extend_from_slice(&key_len.to_le_bytes());
-        entry_data.extend_from_slice(key.as_bytes());
-        entry_data.extend_from_slice(&value_len.to_le_bytes());
-        entry_data.extend_from_slice(value);
+    /// Write a key-value pair along with its optional write and expiry
+    /// times, in milliseconds since the Unix epoch
+    pub fn write_entry_with_metadata(
+        &mut self,
+        key: &str,
+        value: &[u8],
+        written_at_ms: Option<u64>,
+        expires_at_ms: Option<u64>,
+    ) -> io::Result<()> {
+        self.write_put(key, value, written_at_ms, expires_at_ms, None)
+    }
 
-        let checksum = calculate_checksum(&entry_data);
-        self.file.write_all(&checksum.to_le_bytes())?;
-        self.checksums.push(checksum);
+    /// Write a key-value pair along with the sequence of the write that
+    /// stored it, so merges and reads can tell which of several files holding
+    /// the key has the newest value
+    pub fn write_entry_with_sequence(
+        &mut self,
+        key: &str,
+        value: &[u8],
+        sequence: u64,
+    ) -> io::Result<()> {
+        self.write_put(key, value, None, None, Some(sequence))
+    }
 
-        // Add key to appropriate bloom filter if enabled
-        if let Some(ref mut bloom) = self.bloom_filter {
-            bloom.insert(&key.to_string());
-        } else if let Some(ref mut bloom) = self.partitioned_bloom_filter {
-            bloom.insert(&key.to_string());
+    /// Buffer an entry with everything recorded about it, writing out the
+    /// block once it is full
+    fn write_put(
+        &mut self,
+        key: &str,
+        value: &[u8],
+        written_at_ms: Option<u64>,
+        expires_at_ms: Option<u64>,
+        sequence: Option<u64>,
+    ) -> io::Result<()> {
+        self.check_key_order(key)?;
+        self.pending
+            .add_with_sequence(key, value, written_at_ms, expires_at_ms, sequence)?;
+        if self.pending.encoded_len() >= self.block_size {
+            self.flush_pending()?;
         }
-
-        // Update entry count
-        self.entry_count += 1;
-
         Ok(())
     }
 
-    /// Finalize the SSTable by writing the index and Bloom filter
-    pub fn finalize(mut self) -> io::Result<()> {
-        // Remember the current position - this is where the index starts
-        self.index_offset = self.file.stream_position()?;
-
-        // Write the index (empty for now as we're not using it yet)
-        // This is a placeholder for future enhancements
-
-        // Write bloom filter if enabled
-        if self.has_bloom_filter {
-            self.bloom_offset = self.file.stream_position()?;
-
-            if let Some(ref bloom) = self.bloom_filter {
-                // Write standard bloom filter metadata and data
-                let bloom_size_bits = bloom.size_bits();
-                let bloom_num_hashes = bloom.num_hashes();
-
-                // First, write bloom filter type (0 = standard)
-                println!("Writing standard bloom filter (type 0)");
-                self.file.write_all(&[0u8])?;
-
-                // Write metadata
-                println!("Writing size_bits: {}", bloom_size_bits);
-                self.file
-                    .write_all(&(bloom_size_bits as u64).to_le_bytes())?;
-
-                println!("Writing num_hashes: {}", bloom_num_hashes);
-                self.file
-                    .write_all(&(bloom_num_hashes as u32).to_le_bytes())?;
-
-                // Write bloom filter data
-                let bits = bloom.get_bits();
-                println!("Writing {} bytes of bloom data", bits.len());
-                for byte in bits {
-                    self.file.write_all(&[*byte])?;
-                }
-            } else if let Some(ref bloom) = self.partitioned_bloom_filter {
-                // For partitioned bloom filter, we'll serialize each partition individually
-
-                // Get the number of partitions
-                let num_partitions = bloom.num_partitions();
-
-                // First write the filter type byte (1 = partitioned)
-                println!("Writing partitioned bloom filter (type 1)");
-                self.file.write_all(&[1u8])?;
-
-                // Then write number of partitions
-                println!("Writing num_partitions: {}", num_partitions);
-                self.file
-                    .write_all(&(num_partitions as u32).to_le_bytes())?;
-
-                // Since we're serializing actual partitions, we need to get size_bits/num_hashes from the first partition
-                // We'll just use these as metadata for compatibility - not actually used since each partition has its own
-                let size_bits = if let Some(partition) = bloom.get_partition(0) {
-                    partition.size_bits()
-                } else {
-                    100000 // Fallback value
-                };
+    /// Write every record from `records` in order: values become entries,
+    /// buffered into data blocks and added to the filters and indexes, and
+    /// deletions become tombstones. Stops at the first error, from the
+    /// iterator or from writing.
+    pub fn write_all<I>(&mut self, records: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = io::Result<SSTableRecord>>,
+    {
+        for record in records {
+            match record? {
+                SSTableRecord::Put {
+                    key,
+                    value,
+                    written_at_ms,
+                    expires_at_ms,
+                    sequence,
+                } => self.write_put(&key, &value, written_at_ms, expires_at_ms, sequence)?,
+                SSTableRecord::Delete { key, tombstone } => self.write_tombstone(&key, tombstone),
+                SSTableRecord::DeleteRange(tombstone) => self.write_range_tombstone(tombstone),
+            }
+        }
+        Ok(())
+    }
 
-                let num_hashes = if let Some(partition) = bloom.get_partition(0) {
-                    partition.num_hashes()
-                } else {
-                    7 // Fallback value
+    /// Write every record from `records` like `write_all`, building data
+    /// blocks in parallel. Values must arrive in strictly ascending key
+    /// order, following any entries already written.
+    ///
+    /// Values are taken a window at a time and cut into chunks of about
+    /// `block_size` bytes of keys and values. Each chunk is encoded,
+    /// compressed and checksummed into a block on its own rayon thread, and
+    /// the window's keys are hashed for the Bloom filter in parallel before
+    /// the blocks are written in key order. Blocks are cut before
+    /// compression, so compressed files get larger blocks than `write_all`
+    /// writes.
+    pub fn build_from_sorted_iter<I>(&mut self, records: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = io::Result<SSTableRecord>>,
+    {
+        self.flush_pending()?;
+        let window_blocks = rayon::current_num_threads() * PARALLEL_BLOCKS_PER_THREAD;
+        let mut records = records.into_iter().peekable();
+        let mut last_key = self.last_key.clone();
+
+        while records.peek().is_some() {
+            let mut chunks: Vec<Vec<SSTableRecord>> = Vec::new();
+            let mut chunk = Vec::new();
+            let mut chunk_bytes = 0;
+            while chunks.len() < window_blocks {
+                let Some(record) = records.next() else {
+                    break;
                 };
-
-                println!("Writing partition metadata size_bits: {}", size_bits);
-                self.file.write_all(&(size_bits as u64).to_le_bytes())?;
-
-                println!("Writing partition metadata num_hashes: {}", num_hashes);
-                self.file.write_all(&(num_hashes as u32).to_le_bytes())?;
-
-                // Now write each partition's data
-                for i in 0..num_partitions {
-                    if let Some(partition) = bloom.get_partition(i) {
-                        // Get this partition's bits
-                        let bits = partition.get_bits();
-
-                        // Write size of this partition's bit array
-                        let bits_len = bits.len() as u32;
-                        println!("Writing partition {} bits length: {}", i, bits_len);
-                        self.file.write_all(&bits_len.to_le_bytes())?;
-
-                        // Write the partition's bits
-                        println!("Writing partition {} data ({} bytes)", i, bits.len());
-                        for byte in bits {
-                            self.file.write_all(&[*byte])?;
+                match record? {
+                    SSTableRecord::Delete { key, tombstone } => {
+                        self.write_tombstone(&key, tombstone)
+                    }
+                    SSTableRecord::DeleteRange(tombstone) => self.write_range_tombstone(tombstone),
+                    put @ SSTableRecord::Put { .. } => {
+                        let SSTableRecord::Put { key, value, .. } = &put else {
+                            unreachable!()
+                        };
+                        if let Some(last) = &last_key
+                            && last >= key
+                        {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!(
+                                    "Key {:?} does not follow {:?}; keys must strictly ascend",
+                                    key, last
+                                ),
+                            ));
+                        }
+                        chunk_bytes += key.len() + value.len();
+                        last_key = Some(key.clone());
+                        chunk.push(put);
+                        if chunk_bytes >= self.block_size {
+                            chunks.push(std::mem::take(&mut chunk));
+                            chunk_bytes = 0;
                         }
-                    } else {
-                        // Write empty partition as fallback
-                        println!("Writing empty partition {}", i);
-                        self.file.write_all(&0u32.to_le_bytes())?; // 0 length
                     }
                 }
             }
+            if !chunk.is_empty() {
+                chunks.push(chunk);
+            }
 
-            // Calculate bloom filter size for header
-            self.bloom_size = self.file.stream_position()? - self.bloom_offset;
+            // A single block is not worth handing to other threads
+            if chunks.len() <= 1 {
+                for chunk in chunks {
+                    let block = build_block(self.data_block_builder()?, chunk)?;
+                    self.write_block(block)?;
+                }
+                continue;
+            }
+            let chunks = chunks
+                .into_iter()
+                .map(|chunk| Ok((self.data_block_builder()?, chunk)))
+                .collect::<io::Result<Vec<_>>>()?;
+            let blocks = chunks
+                .into_par_iter()
+                .map(|(builder, chunk)| build_block(builder, chunk))
+                .collect::<io::Result<Vec<DataBlock>>>()?;
+
+            let keys: Vec<&str> = blocks
+                .iter()
+                .flat_map(|block| block.keys.iter().map(String::as_str))
+                .collect();
+            self.filter.add_keys(&keys);
+            for block in blocks {
+                self.place_block(block)?;
+            }
         }
+        Ok(())
+    }
 
-        // Write file checksums
-        let _file_checksums_offset = self.file.stream_position()?;
-        for checksum in &self.checksums {
-            self.file.write_all(&checksum.to_le_bytes())?;
-        }
+    /// Record the number the manifest allocated to this file in its footer
+    pub fn set_file_number(&mut self, file_number: u64) {
+        self.file_number = file_number;
+    }
 
-        // Go back to the beginning and write the header
-        self.file.seek(SeekFrom::Start(0))?;
-        self.write_header()?;
+    /// Record that the file reflects every write logged in the WAL up to
+    /// `lsn`, so replay can skip those records
+    pub fn set_applied_lsn(&mut self, lsn: u64) {
+        self.meta.set_applied_lsn(lsn);
+    }
 
-        // Ensure all data is written to disk
-        self.file.sync_all()?;
+    /// Record that every entry in the file takes sequence `sequence`, as
+    /// assigned when the file is ingested into an index
+    pub fn set_global_sequence(&mut self, sequence: u64) {
+        self.meta.set_global_sequence(sequence);
+    }
 
-        Ok(())
+    /// Record the file number of the newest flush whose writes the file
+    /// holds, which orders it among files with overlapping keys
+    pub fn set_flush_number(&mut self, flush_number: u64) {
+        self.meta.set_flush_number(flush_number);
     }
 
-    /// Write the SSTable header
-    fn write_header(&mut self) -> io::Result<()> {
-        // Magic number (8 bytes)
-        self.file.write_all(&MAGIC.to_le_bytes())?;
+    /// Record when the file was written, in seconds since the Unix epoch
+    pub fn set_created_at(&mut self, created_at_secs: u64) {
+        self.meta.set_created_at(created_at_secs);
+    }
 
-        // Version (4 bytes)
-        self.file.write_all(&VERSION.to_le_bytes())?;
+    /// Record the level of the index the file is written for
+    pub fn set_level(&mut self, level: u32) {
+        self.meta.set_level(level);
+    }
 
-        // Entry count (8 bytes)
-        self.file.write_all(&self.entry_count.to_le_bytes())?;
+    /// Record the WAL checkpoint a flush writes the file under
+    pub fn set_checkpoint_id(&mut self, checkpoint_id: u64) {
+        self.meta.set_checkpoint_id(checkpoint_id);
+    }
 
-        // Index offset (8 bytes)
-        self.file.write_all(&self.index_offset.to_le_bytes())?;
+    /// Record that the value written for `key` is a pointer into value log
+    /// segment `segment` rather than the value itself. The pointer is stored
+    /// and compacted like any other value; `LsmIndex` resolves it on reads.
+    pub fn mark_value_pointer(&mut self, key: &str, segment: u64) {
+        self.meta.add_value_pointer(key, segment);
+    }
 
-        // Bloom filter offset (8 bytes)
-        self.file.write_all(&self.bloom_offset.to_le_bytes())?;
+    /// Record an application property, such as the schema version the
+    /// file's values were written with, read back with
+    /// `SSTableReader::user_property`. Setting a name again replaces its
+    /// value; empty names are refused.
+    pub fn set_user_property(&mut self, name: &str, value: &str) -> io::Result<()> {
+        if name.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "User property names must not be empty",
+            ));
+        }
+        self.meta.set_user_property(name, value);
+        Ok(())
+    }
 
-        // Bloom filter size (8 bytes)
-        self.file.write_all(&self.bloom_size.to_le_bytes())?;
+    /// Record that a key was deleted; the tombstone hides the key in older
+    /// files and may carry the deleted value for undeletion
+    pub fn write_tombstone(&mut self, key: &str, tombstone: Tombstone) {
+        self.meta.add_tombstone(key, tombstone);
+    }
 
-        // Has bloom filter flag (1 byte)
-        self.file.write_all(&[self.has_bloom_filter as u8])?;
+    /// Record that a key range was deleted; the tombstone hides keys in the
+    /// range in older files, but not entries written to this one
+    pub fn write_range_tombstone(&mut self, tombstone: RangeTombstone) {
+        self.meta.add_range_tombstone(tombstone);
+    }
 
-        // Calculate header checksum (excluding the checksum field itself)
-        let mut header_data = Vec::new();
-        header_data.extend_from_slice(&MAGIC.to_le_bytes());
-        header_data.extend_from_slice(&VERSION.to_le_bytes());
-        header_data.extend_from_slice(&self.entry_count.to_le_bytes());
-        header_data.extend_from_slice(&self.index_offset.to_le_bytes());
-        header_data.extend_from_slice(&self.bloom_offset.to_le_bytes());
-        header_data.extend_from_slice(&self.bloom_size.to_le_bytes());
-        header_data.push(self.has_bloom_filter as u8);
+    /// Also add each key's prefix under `extractor` to the Bloom filter, so
+    /// readers can rule out whole prefixes. Must be set before the first entry
+    /// is written. The filter is sized for keys only, so prefixes raise its
+    /// false positive rate slightly.
+    pub fn set_prefix_extractor(&mut self, extractor: Arc<dyn PrefixExtractor>) {
+        self.filter.set_prefix_extractor(extractor);
+    }
 
-        let header_checksum = calculate_checksum(&header_data);
-        self.file.write_all(&header_checksum.to_le_bytes())?;
+    /// Split the block index and Bloom filter into partitions of
+    /// `blocks_per_partition` data blocks, each with its own filter at
+    /// `false_positive_rate`. Readers then keep only the top level of the
+    /// index in memory and load partitions as lookups need them. Must be set
+    /// before the first entry is written.
+    ///
+    /// Partitioning needs keys in sorted order; a file whose keys arrive
+    /// unsorted is written with the whole-file filter the writer was created
+    /// with, if any. Prefixes are not added to partitioned filters.
+    pub fn set_partitioned_index(
+        &mut self,
+        blocks_per_partition: usize,
+        false_positive_rate: f64,
+    ) -> io::Result<()> {
+        if self.entry_count > 0 || self.pending.entry_count() > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The index must be partitioned before any entry is written",
+            ));
+        }
+        if self.hash_entries.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A hash index cannot be partitioned",
+            ));
+        }
+        self.partitions = Some(PartitionBuilder::new(
+            blocks_per_partition,
+            false_positive_rate,
+        ));
+        Ok(())
+    }
 
+    /// Refuse any entry whose key does not strictly follow the one before,
+    /// by byte order, as readers searching blocks and restart points assume.
+    /// This is the default; it undoes `allow_unsorted_keys`. Must be set
+    /// before the first entry is written.
+    pub fn set_strict_key_order(&mut self) -> io::Result<()> {
+        self.ensure_unwritten("Strict key order")?;
+        self.strict_key_order = true;
+        // Sorted keys are found through the block index
+        self.key_entries = None;
         Ok(())
     }
-}
 
-/// SSTable reader that supports Bloom filters
-#[derive(Debug)]
-pub struct SSTableReader {
-    file: BufReader<File>,
-    entry_count: u64,
-    index_offset: u64,
-    bloom_offset: u64, // Add this field to store bloom filter offset
-    bloom_filter: Option<BloomFilter<String>>,
-    partitioned_bloom_filter: Option<PartitionedBloomFilter<String>>,
-    has_bloom_filter: bool,
-    #[allow(dead_code)] // Needed for future data integrity features
-    block_checksums: Vec<u32>, // Added checksums for data blocks
-    #[allow(dead_code)] // Needed for future data integrity features
-    header_checksum: u32, // Header checksum for verification
-}
+    /// Take keys in any order, for callers that write unsorted data on
+    /// purpose. The file records whether the keys were sorted, and readers
+    /// fall back to a key index and full scans if not. Not allowed with key
+    /// prefix compression, which needs sorted keys. Must be set before the
+    /// first entry is written.
+    pub fn allow_unsorted_keys(&mut self) -> io::Result<()> {
+        self.ensure_unwritten("Unsorted keys")?;
+        if self.key_prefix_compression {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Prefix-compressed keys must be sorted",
+            ));
+        }
+        self.strict_key_order = false;
+        if self.hash_entries.is_none() {
+            self.key_entries = Some(Vec::new());
+        }
+        Ok(())
+    }
 
-impl SSTableReader {
-    /// Open an SSTable for reading
-    pub fn open(path: &str) -> io::Result<Self> {
-        let file = File::open(path)?;
-        let mut reader = BufReader::new(file);
+    /// Check that `key` may follow the entries written so far
+    fn check_key_order(&self, key: &str) -> io::Result<()> {
+        if !self.strict_key_order {
+            return Ok(());
+        }
+        match self.pending.last_key().or(self.last_key.as_deref()) {
+            Some(last) if last >= key => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Key {:?} does not follow {:?}; keys must strictly ascend",
+                    key, last
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
 
-        // Read header
-        let mut magic_buf = [0u8; 8];
-        reader.read_exact(&mut magic_buf)?;
-        let magic = u64::from_le_bytes(magic_buf);
-        println!("Header: Magic = {:X}", magic);
-        if magic != MAGIC {
+    /// Write buffered entries out as a data block once they take
+    /// `block_size` bytes, instead of `DATA_BLOCK_SIZE`. Larger blocks
+    /// compress better and shrink the index, but lookups read more of them.
+    /// Must be set before the first entry is written.
+    pub fn set_block_size(&mut self, block_size: usize) -> io::Result<()> {
+        self.ensure_unwritten("The block size")?;
+        if block_size == 0 {
             return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Invalid magic number - not an SSTable file",
+                io::ErrorKind::InvalidInput,
+                "The block size must be at least 1 byte",
             ));
         }
+        self.block_size = block_size;
+        Ok(())
+    }
 
-        let mut version_buf = [0u8; 4];
-        reader.read_exact(&mut version_buf)?;
-        let version = u32::from_le_bytes(version_buf);
-        println!("Header: Version = {}", version);
-        if version > VERSION {
+    /// Record the offset of every `restart_interval`th entry of each data
+    /// block, so a point lookup in a block of sorted keys searches those
+    /// entries' keys and scans at most `restart_interval` entries, rather
+    /// than the whole block. Must be set before the first entry is written.
+    pub fn set_restart_interval(&mut self, restart_interval: usize) -> io::Result<()> {
+        self.ensure_unwritten("The restart interval")?;
+        if restart_interval == 0 {
             return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Unsupported SSTable version: {}", version),
+                io::ErrorKind::InvalidInput,
+                "The restart interval must be at least 1 entry",
             ));
         }
+        self.restart_interval = Some(restart_interval);
+        self.configure_pending();
+        Ok(())
+    }
 
-        let mut entry_count_buf = [0u8; 8];
-        reader.read_exact(&mut entry_count_buf)?;
-        let entry_count = u64::from_le_bytes(entry_count_buf);
-        println!("Header: Entry count = {}", entry_count);
+    /// Store each key as the length of the prefix it shares with the key at
+    /// the restart point before it, followed by the rest of the key, so runs
+    /// of keys with long common prefixes store the prefix once per restart
+    /// interval. Keys must strictly ascend, and restart points are recorded
+    /// every `DEFAULT_PREFIX_RESTART_INTERVAL` entries unless
+    /// `set_restart_interval` chooses otherwise. Must be set before the
+    /// first entry is written.
+    ///
+    /// Readers rebuild each key from its restart point's key, so lookups
+    /// still read a single run of entries.
+    pub fn set_key_prefix_compression(&mut self) -> io::Result<()> {
+        self.set_strict_key_order()?;
+        self.key_prefix_compression = true;
+        self.meta.set_key_prefix_compression();
+        self.restart_interval
+            .get_or_insert(DEFAULT_PREFIX_RESTART_INTERVAL);
+        self.configure_pending();
+        Ok(())
+    }
 
-        let mut index_offset_buf = [0u8; 8];
-        reader.read_exact(&mut index_offset_buf)?;
-        let index_offset = u64::from_le_bytes(index_offset_buf);
-        println!("Header: Index offset = {}", index_offset);
+    /// Whether keys are stored prefix-compressed
+    pub fn key_prefix_compression(&self) -> bool {
+        self.key_prefix_compression
+    }
 
-        let mut bloom_offset_buf = [0u8; 8];
-        reader.read_exact(&mut bloom_offset_buf)?;
-        let bloom_offset = u64::from_le_bytes(bloom_offset_buf);
-        println!("Header: Bloom offset = {}", bloom_offset);
+    /// Apply the checksum kind and key prefix compression to the builder
+    /// buffering entries
+    fn configure_pending(&mut self) {
+        self.pending.set_checksum_kind(self.checksum_kind);
+        self.pending.set_key_prefix_compression(
+            self.restart_interval
+                .filter(|_| self.key_prefix_compression),
+        );
+    }
 
-        let mut bloom_size_buf = [0u8; 8];
-        reader.read_exact(&mut bloom_size_buf)?;
-        let bloom_size = u64::from_le_bytes(bloom_size_buf);
-        println!("Header: Bloom size = {}", bloom_size);
+    /// Partition the block index and Bloom filter like
+    /// `set_partitioned_index`, but close each partition once its block
+    /// handles take `index_block_size` bytes rather than after a fixed number
+    /// of blocks, so partitions stay the same size whatever the key lengths.
+    /// Must be set before the first entry is written.
+    pub fn set_index_block_size(
+        &mut self,
+        index_block_size: usize,
+        false_positive_rate: f64,
+    ) -> io::Result<()> {
+        self.set_partitioned_index(1, false_positive_rate)?;
+        self.partitions = Some(PartitionBuilder::by_size(
+            index_block_size,
+            false_positive_rate,
+        ));
+        self.index_block_size = Some(index_block_size);
+        Ok(())
+    }
 
-        let mut has_bloom_buf = [0u8; 1];
-        reader.read_exact(&mut has_bloom_buf)?;
-        let has_bloom_filter = has_bloom_buf[0] != 0;
-        println!("Header: Has bloom filter = {}", has_bloom_filter);
+    /// Compute the checksums of entries and meta sections with `kind`
+    /// instead of CRC32, recording it in the header so readers verify them
+    /// the same way. Must be set before the first entry is written.
+    pub fn set_checksum_kind(&mut self, kind: ChecksumKind) -> io::Result<()> {
+        self.ensure_unwritten("The checksum kind")?;
+        self.checksum_kind = kind;
+        self.configure_pending();
+        Ok(())
+    }
 
-        let mut header_checksum_buf = [0u8; 4];
-        reader.read_exact(&mut header_checksum_buf)?;
-        let header_checksum = u32::from_le_bytes(header_checksum_buf);
-        println!("Header: Checksum = {}", header_checksum);
+    /// Function entry and meta section checksums are computed with
+    pub fn checksum_kind(&self) -> ChecksumKind {
+        self.checksum_kind
+    }
 
-        // Create new reader instance
-        let mut sstable_reader = SSTableReader {
-            file: reader,
-            entry_count,
-            index_offset,
-            bloom_offset, // Add this field to use the bloom offset value
-            bloom_filter: None,
-            partitioned_bloom_filter: None,
-            has_bloom_filter,
-            #[allow(dead_code)] // Needed for future data integrity features
-            block_checksums: Vec::new(),
-            #[allow(dead_code)] // Needed for future data integrity features
-            header_checksum,
-        };
+    /// Fail if entries were written, naming the `setting` that must come first
+    fn ensure_unwritten(&self, setting: &str) -> io::Result<()> {
+        if self.entry_count > 0 || self.pending.entry_count() > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} must be set before any entry is written", setting),
+            ));
+        }
+        Ok(())
+    }
 
-        // Load the bloom filter if present
-        if has_bloom_filter {
-            sstable_reader.load_bloom_filter()?;
+    /// Index entries by a hash of their key instead of by block, so point
+    /// lookups go straight to the entry rather than scanning a block. Must
+    /// be set before the first entry is written.
+    ///
+    /// The hash index replaces the block index, so range scans over the
+    /// file read it from the start even when its keys are sorted. Suits
+    /// files that only serve point lookups. It cannot be combined with a
+    /// partitioned index.
+    pub fn set_hash_index(&mut self) -> io::Result<()> {
+        if self.entry_count > 0 || self.pending.entry_count() > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The hash index must be enabled before any entry is written",
+            ));
         }
+        if self.partitions.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A partitioned index cannot also be hashed",
+            ));
+        }
+        self.hash_entries = Some(Vec::new());
+        self.key_entries = None;
+        Ok(())
+    }
 
-        Ok(sstable_reader)
+    /// Give each data block its own Bloom filter at `false_positive_rate`,
+    /// so a point lookup that lands in a block the key is absent from skips
+    /// reading and decoding it. Worth it when blocks hold many entries,
+    /// since the whole-file filter only rules out keys absent from every
+    /// block. Must be set before the first entry is written.
+    ///
+    /// Readers can only find the block a key lands in when keys are
+    /// written in sorted order; otherwise the filters are stored but unused.
+    pub fn set_block_filters(&mut self, false_positive_rate: f64) -> io::Result<()> {
+        if self.entry_count > 0 || self.pending.entry_count() > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Block filters must be enabled before any entry is written",
+            ));
+        }
+        self.block_filter_fpr = Some(false_positive_rate);
+        Ok(())
     }
 
-    /// Load the Bloom filter from the SSTable file
-    fn load_bloom_filter(&mut self) -> io::Result<()> {
-        if !self.has_bloom_filter {
-            return Ok(());
+    /// Compress values written from now on, priming Zstd with `dictionary`
+    /// if one was trained. Must be set before the first entry is written.
+    pub fn set_compression(
+        &mut self,
+        compression: Compression,
+        dictionary: Option<Vec<u8>>,
+    ) -> io::Result<()> {
+        if self.entry_count > 0 || self.pending.entry_count() > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Compression must be set before any entry is written",
+            ));
         }
 
-        // Position the file at the bloom filter offset from the header
-        let file_pos = self.file.stream_position()?;
-        println!("Current file position: {}", file_pos);
+        // Only Zstd compresses against a dictionary
+        let dictionary = match compression {
+            Compression::Zstd(_) => dictionary,
+            _ => None,
+        };
+        self.pending = DataBlockBuilder::with_compression(compression, dictionary.as_deref())?;
+        self.configure_pending();
+        self.compression_id = builder::compression_id(&compression, dictionary.as_deref());
+        self.meta.set_compression(compression.name(), dictionary);
+        Ok(())
+    }
 
-        // Use the bloom_offset directly from the header
-        println!("Seeking to bloom filter offset: {}", self.bloom_offset);
-        self.file.seek(SeekFrom::Start(self.bloom_offset))?;
+    /// Compress each data block as a whole with `compression_type`,
+    /// recording the codec in the header so readers decompress blocks
+    /// without being told. Entry offsets stay those of the blocks as
+    /// written. Must be set before the first entry is written.
+    pub fn set_compression_type(&mut self, compression_type: CompressionType) -> io::Result<()> {
+        self.ensure_unwritten("The compression type")?;
+        compression_type.ensure_available()?;
+        self.block_encoder.set_compression_type(compression_type);
+        Ok(())
+    }
 
-        // Dump a few bytes from this position to see what's in the file
-        let mut preview_buf = [0u8; 16];
-        let bytes_read = self.file.read(&mut preview_buf)?;
-        println!(
-            "Preview bytes at bloom filter offset (read {} bytes): {:?}",
-            bytes_read, preview_buf
-        );
+    /// Codec data blocks are compressed with
+    pub fn compression_type(&self) -> CompressionType {
+        self.block_encoder.compression_type()
+    }
 
-        // Seek back to the start position
-        self.file.seek(SeekFrom::Start(self.bloom_offset))?;
+    /// Encrypt data blocks with AES-256-GCM under the current key of
+    /// `provider`. Must be set before the first entry is written, and needs
+    /// the `encryption` feature.
+    pub fn set_encryption(&mut self, provider: &dyn KeyProvider) -> io::Result<()> {
+        if self.entry_count > 0 || self.pending.entry_count() > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Encryption must be set before any entry is written",
+            ));
+        }
+        self.block_encoder.set_cipher(Some(BlockCipher::current(provider)?));
+        Ok(())
+    }
 
-        // First, read the bloom filter type byte
+    /// ID of the key data blocks are sealed with, if they are encrypted
+    pub fn encryption_key_id(&self) -> Option<&str> {
+        self.block_encoder.key_id()
+    }
+
+    /// Create a block builder whose blocks this writer accepts, encoding
+    /// values with the same compression settings.
+    ///
+    /// Builders are independent of the writer, so several can fill blocks on
+    /// other threads; blocks are then appended in key order.
+    pub fn data_block_builder(&self) -> io::Result<DataBlockBuilder> {
+        self.pending.configured_like()
+    }
+
+    /// Append a block built by a `DataBlockBuilder` after the entries written
+    /// so far. The block must have been encoded with this writer's
+    /// compression settings, checksum kind and key prefix compression.
+    pub fn append_block(&mut self, block: DataBlock) -> io::Result<()> {
+        if block.compression_id != self.compression_id {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Block was encoded with different compression settings than the writer",
+            ));
+        }
+        if !block.is_empty() && block.checksum_kind != self.checksum_kind {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Block was checksummed with a different checksum kind than the writer",
+            ));
+        }
+        let prefix_restart_interval = self.restart_interval.filter(|_| self.key_prefix_compression);
+        if !block.is_empty() && block.prefix_restart_interval != prefix_restart_interval {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Block keys were prefix-compressed differently than the writer's",
+            ));
+        }
+        self.flush_pending()?;
+        if self.strict_key_order {
+            if let Some(first_key) = block.first_key() {
+                self.check_key_order(first_key)?;
+            }
+            if !block.keys_sorted() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Block keys must strictly ascend",
+                ));
+            }
+        }
+        self.write_block(block)
+    }
+
+    /// Write out entries buffered by `write_entry`
+    fn flush_pending(&mut self) -> io::Result<()> {
+        if self.pending.entry_count() == 0 {
+            return Ok(());
+        }
+        let block = self.pending.finish();
+        self.write_block(block)
+    }
+
+    /// Write a block's entries to the file and account for them
+    fn write_block(&mut self, block: DataBlock) -> io::Result<()> {
+        for key in &block.keys {
+            self.filter.add_key(key);
+        }
+        self.place_block(block)
+    }
+
+    /// Write a block whose keys are already in the filter and account for
+    /// its entries
+    fn place_block(&mut self, block: DataBlock) -> io::Result<()> {
+        if block.is_empty() {
+            return Ok(());
+        }
+        let offset = self.position;
+        self.write_data(&block.bytes)?;
+        if let (Some(first_key), Some(last_key)) = (block.first_key(), block.last_key()) {
+            let handle = BlockHandle {
+                first_key: first_key.to_string(),
+                last_key: last_key.to_string(),
+                offset,
+                entry_count: block.entry_count() as u64,
+            };
+            if let Some(partitions) = &mut self.partitions {
+                partitions.add_block(handle.clone(), &block.keys);
+            }
+            let offsets = if self.hash_entries.is_some()
+                || self.key_entries.is_some()
+                || self.restart_interval.is_some()
+            {
+                block.entry_offsets(offset)
+            } else {
+                Vec::new()
+            };
+            if let Some(interval) = self.restart_interval {
+                self.meta.add_restart_points(interval, &offsets);
+            }
+            if let Some(key_entries) = &mut self.key_entries {
+                key_entries.extend(block.keys.iter().cloned().zip(offsets.iter().copied()));
+            }
+            if let Some(hash_entries) = &mut self.hash_entries {
+                hash_entries.extend(
+                    block
+                        .keys
+                        .iter()
+                        .zip(offsets)
+                        .map(|(key, offset)| (hash_index::hash_key(key), offset)),
+                );
+            }
+            if let Some(false_positive_rate) = self.block_filter_fpr {
+                self.meta.add_block_filter(block_filters::build(
+                    offset,
+                    &block.keys,
+                    false_positive_rate,
+                ));
+            }
+            self.meta.add_block(handle);
+        }
+
+        if self.keys_sorted {
+            let continues = match (self.last_key.as_deref(), block.first_key()) {
+                (Some(last), Some(first)) => last < first,
+                _ => true,
+            };
+            self.keys_sorted = block.keys_sorted && continues;
+        }
+        self.last_key = block.keys.last().cloned();
+
+        for key in &block.keys {
+            self.meta.observe_key(key);
+        }
+        for leaf in block.leaves {
+            self.content_hasher.update_leaf(leaf);
+        }
+        self.checksums.extend(block.checksums);
+        self.meta
+            .extend_times(block.write_times, block.expiries, block.sequences);
+        self.meta.add_uncompressed_values(block.uncompressed_values);
+        self.entry_count += block.keys.len() as u64;
+        self.raw_bytes += block.raw_bytes;
+        Ok(())
+    }
+
+    /// Use a Bloom filter that was built elsewhere and already covers every key
+    /// written to this SSTable, instead of the one the writer maintains
+    pub fn set_bloom_filter(&mut self, filter: BloomFilter<String>) {
+        self.filter.set_bloom_filter(filter);
+        self.has_bloom_filter = true;
+    }
+
+    /// Use a partitioned Bloom filter that was built elsewhere and already
+    /// covers every key written to this SSTable
+    pub fn set_partitioned_bloom_filter(&mut self, filter: PartitionedBloomFilter<String>) {
+        self.filter.set_partitioned_bloom_filter(filter);
+        self.has_bloom_filter = true;
+    }
+
+    /// Returns the file offset at which the next entry will be written
+    pub fn offset(&mut self) -> io::Result<u64> {
+        Ok(self.position + self.pending.encoded_len() as u64)
+    }
+
+    /// Write the index, Bloom filter and footer that complete the table,
+    /// then flush the sink and hand it back
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush_pending()?;
+
+        // Remember the current position - this is where the index starts
+        self.index_offset = self.position;
+
+        // A partitioned index replaces the block index and whole-file filter
+        let partitioned = self
+            .partitions
+            .take()
+            .filter(|_| self.keys_sorted)
+            .map(|partitions| {
+                let (partitions, filter_region) = partitions.finish();
+                self.meta.set_index_partitions(partitions);
+                filter_region
+            });
+        let index_block_size = self.index_block_size.filter(|_| partitioned.is_some());
+        self.meta.set_block_sizes(self.block_size, index_block_size);
+        if let Some(hash_entries) = self.hash_entries.take() {
+            self.meta.set_hash_index(HashIndex::build(&hash_entries));
+        }
+        // Sorted keys are found through the block index; unsorted ones need
+        // every key indexed
+        if let Some(key_entries) = self.key_entries.take().filter(|_| !self.keys_sorted) {
+            self.meta.set_key_index(KeyIndex::build(key_entries));
+        }
+
+        // Write the meta section
+        let prefix_extractor = self
+            .filter
+            .prefix_extractor()
+            .filter(|_| self.has_bloom_filter && partitioned.is_none())
+            .map(|extractor| extractor.name());
+        let summary = DataSummary {
+            entry_count: self.entry_count,
+            keys_sorted: self.keys_sorted,
+            raw_bytes: self.raw_bytes,
+            data_bytes: if self.block_encoder.is_active() {
+                self.block_encoder.stored_bytes()
+            } else {
+                self.index_offset - header_size(VERSION) as u64
+            },
+            encryption_key_id: self.block_encoder.key_id().map(str::to_string),
+            content_digest: self.content_hasher.finish(),
+            prefix_extractor,
+            checksum_kind: self.checksum_kind,
+        };
+        let meta = std::mem::take(&mut self.meta).finish(summary);
+        self.write(&meta)?;
+
+        // Write bloom filter if enabled
+        let filter = std::mem::replace(&mut self.filter, FilterBuilder::new(0, false, 0.0, false));
+        self.has_bloom_filter = filter.is_enabled() || partitioned.is_some();
+        if let Some(filter) = partitioned.or_else(|| filter.finish()) {
+            self.bloom_offset = self.position;
+            self.write(&filter)?;
+
+            // Calculate bloom filter size for header
+            self.bloom_size = self.position - self.bloom_offset;
+        }
+
+        // Write file checksums
+        let checksums_offset = self.position;
+        for checksum in std::mem::take(&mut self.checksums) {
+            self.write(&checksum.to_le_bytes())?;
+        }
+
+        // End with the footer, leaving the header as first written
+        let footer = Footer {
+            entry_count: self.entry_count,
+            index_offset: self.index_offset,
+            bloom_offset: self.bloom_offset,
+            bloom_size: self.bloom_size,
+            has_bloom_filter: self.has_bloom_filter,
+            file_number: self.file_number,
+            checksums_offset,
+        };
+        self.write(&footer.encode())?;
+
+        // Compressed or encrypted blocks are found through the block map
+        // that ends the file
+        let encoder = std::mem::replace(&mut self.block_encoder, BlockEncoder::new());
+        if encoder.is_active() {
+            self.sink.write_all(&encoder.finish())?;
+        }
+        self.sink.flush()?;
+
+        Ok(self.sink)
+    }
+
+    /// The SSTable header: the magic number and version, the cipher and
+    /// compression type of data blocks, the checksum kind, and their
+    /// checksum
+    fn header(&self) -> Vec<u8> {
+        let cipher = match self.encryption_key_id() {
+            Some(_) => encryption::AES_256_GCM_CIPHER_ID,
+            None => 0,
+        };
+        let mut header = Vec::with_capacity(header_size(VERSION));
+        header.extend_from_slice(&MAGIC.to_le_bytes());
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.push(cipher);
+        header.push(self.compression_type().id());
+        header.push(self.checksum_kind.id());
+        let header_checksum = calculate_checksum(&header);
+        header.extend_from_slice(&header_checksum.to_le_bytes());
+        header
+    }
+
+    /// Write `bytes` after everything written so far, keeping count of the
+    /// position
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.write_stored(bytes, bytes.len())
+    }
+
+    /// Store `stored`, the bytes of `len` written ones, moving the position
+    /// on by `len`. The header goes first: the cipher, compression type and
+    /// checksum kind it records can no longer change once an entry is
+    /// written, so it is final and never revisited.
+    fn write_stored(&mut self, stored: &[u8], len: usize) -> io::Result<()> {
+        if !self.header_written {
+            let header = self.header();
+            self.sink.write_all(&header)?;
+            self.header_written = true;
+        }
+        self.sink.write_all(stored)?;
+        self.position += len as u64;
+        Ok(())
+    }
+
+    /// Write a data block's bytes, compressed and encrypted if blocks are. The position
+    /// moves on by the block as written, which is where readers look for the
+    /// entries after it.
+    fn write_data(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if !self.block_encoder.is_active() {
+            return self.write(bytes);
+        }
+        let stored = self.block_encoder.encode(self.position, bytes)?;
+        self.write_stored(&stored, bytes.len())
+    }
+}
+
+/// SSTable reader that supports Bloom filters
+#[derive(Debug)]
+pub struct SSTableReader {
+    file: BufReader<TableFile>,
+    entry_count: u64,
+    index_offset: u64,
+    bloom_offset: u64, // Add this field to store bloom filter offset
+    /// Size of the filter region
+    bloom_size: u64,
+    bloom_filter: Option<BloomFilter<String>>,
+    partitioned_bloom_filter: Option<PartitionedBloomFilter<String>>,
+    has_bloom_filter: bool,
+    #[allow(dead_code)] // Needed for future data integrity features
+    block_checksums: Vec<u32>, // Added checksums for data blocks
+    #[allow(dead_code)] // Needed for future data integrity features
+    header_checksum: u32, // Header checksum for verification
+    /// Offset of the per-entry checksum table; 0 for legacy files
+    checksums_offset: u64,
+    version: u32,
+    /// Whether each entry is followed by a checksum; legacy files have none
+    has_entry_checksums: bool,
+    /// Function entry and meta section checksums were computed with
+    checksum_kind: ChecksumKind,
+    /// Number allocated to the file by the manifest, if the file records one
+    file_number: Option<u64>,
+    properties: SSTableProperties,
+    /// Per-key write times in milliseconds since the Unix epoch, if recorded
+    write_times: HashMap<String, u64>,
+    /// Per-key expiry times in milliseconds since the Unix epoch, if any
+    expiries: HashMap<String, u64>,
+    /// Per-key sequences of the writes behind entries, if recorded
+    sequences: HashMap<String, u64>,
+    /// Value log segment of each entry whose value is a pointer into one
+    value_log_segments: Arc<HashMap<String, u64>>,
+    /// Tombstones recorded in the file, keyed by deleted key
+    tombstones: HashMap<String, Tombstone>,
+    /// Key ranges recorded in the file as deleted
+    range_tombstones: FragmentedRangeTombstones,
+    /// Restores values if the file stores them compressed
+    decoder: ValueDecoder,
+    /// Data blocks in file order, if the file records them; for a
+    /// partitioned index, the span of each partition
+    block_index: Vec<BlockHandle>,
+    /// Where each index partition's payload lies in the filter region, if
+    /// the index is partitioned
+    partition_locations: Vec<(u64, u64)>,
+    /// Filters over each data block's keys in file order, if the file has them
+    block_filters: Vec<BlockFilter>,
+    /// Table from key hashes to entry offsets, if the file has one
+    hash_index: Option<HashIndex>,
+    /// Index from every key to its entry's offset, if the file's keys are
+    /// unsorted and it has one
+    key_index: Option<KeyIndex>,
+    /// Offsets of every `restart_interval`th entry of each block, if recorded
+    restart_points: Arc<[u64]>,
+    /// Rebuilds keys if the file stores them prefix-compressed
+    key_expander: Option<KeyExpander>,
+    /// Partitions loaded by lookups, oldest first
+    resident_partitions: Mutex<VecDeque<(usize, Arc<PartitionPayload>)>>,
+    /// Cache holding the filter instead of the reader, if it was opened
+    /// with one
+    filter_cache: Option<FilterCacheHandle>,
+    /// Memory map of the file, once `get_ref` has made one
+    mapping: Option<Arc<MappedFile>>,
+}
+
+impl SSTableReader {
+    /// Open an SSTable for reading, loading its Bloom filter
+    pub fn open(path: &str) -> io::Result<Self> {
+        Self::open_with(path, None, None)
+    }
+
+    /// Open an SSTable for reading, leaving its Bloom filter to `cache`. The
+    /// filter is read on the first lookup that needs it, and again after the
+    /// cache evicts it.
+    pub fn open_with_filter_cache(path: &str, cache: Arc<FilterCache>) -> io::Result<Self> {
+        Self::open_with(path, Some(cache), None)
+    }
+
+    /// Open an encrypted SSTable for reading, getting the key its blocks are
+    /// sealed with from `provider`
+    pub fn open_with_key_provider(path: &str, provider: &dyn KeyProvider) -> io::Result<Self> {
+        Self::open_with(path, None, Some(provider))
+    }
+
+    /// Open an SSTable for reading, leaving its Bloom filter to
+    /// `filter_cache` and getting its key from `key_provider` if given
+    pub(crate) fn open_with(
+        path: &str,
+        filter_cache: Option<Arc<FilterCache>>,
+        key_provider: Option<&dyn KeyProvider>,
+    ) -> io::Result<Self> {
+        let file = TableFile::open(path, key_provider)?;
+        let mut reader = BufReader::new(file);
+
+        // Read header
+        let mut magic_buf = [0u8; 8];
+        reader.read_exact(&mut magic_buf)?;
+        let magic = u64::from_le_bytes(magic_buf);
+        trace!("Header: Magic = {:X}", magic);
+        if magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid magic number - not an SSTable file",
+            ));
+        }
+
+        let mut version_buf = [0u8; 4];
+        reader.read_exact(&mut version_buf)?;
+        let version = u32::from_le_bytes(version_buf);
+        trace!("Header: Version = {}", version);
+        if version == 0 || version > VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported SSTable version: {}", version),
+            ));
+        }
+
+        // Version 8 and later files keep what is only known once every entry
+        // is written in the footer; older ones rewrote it into the header
+        let mut checksum_kind_buf = Vec::new();
+        let (footer, header_checksum) = if version >= FOOTER_VERSION {
+            // The file opened above already decrypts and decompresses
+            // blocks if the cipher and compression type say they are stored
+            // other than as written
+            let mut cipher_buf = [0u8; HEADER_CIPHER_SIZE];
+            reader.read_exact(&mut cipher_buf)?;
+            let mut compression_type_buf = [0u8; HEADER_COMPRESSION_TYPE_SIZE];
+            reader.read_exact(&mut compression_type_buf)?;
+            if version >= CHECKSUM_KIND_VERSION {
+                checksum_kind_buf.resize(HEADER_CHECKSUM_KIND_SIZE, 0);
+                reader.read_exact(&mut checksum_kind_buf)?;
+            }
+            let mut header_checksum_buf = [0u8; 4];
+            reader.read_exact(&mut header_checksum_buf)?;
+            let header_checksum = u32::from_le_bytes(header_checksum_buf);
+            let header_data = [
+                magic_buf.as_slice(),
+                &version_buf,
+                &cipher_buf,
+                &compression_type_buf,
+                &checksum_kind_buf,
+            ]
+            .concat();
+            if calculate_checksum(&header_data) != header_checksum {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "SSTable header checksum verification failed",
+                ));
+            }
+            cipher_from_id(cipher_buf[0])?;
+            CompressionType::from_id(compression_type_buf[0])?;
+            (Footer::read_from(&mut reader)?, header_checksum)
+        } else {
+            Self::read_header_fields(&mut reader, version)?
+        };
+        let checksum_kind = match checksum_kind_buf.first() {
+            Some(&id) => ChecksumKind::from_id(id)?,
+            None => ChecksumKind::Crc32,
+        };
+        trace!("Header: Checksum kind = {:?}", checksum_kind);
+        let Footer {
+            entry_count,
+            index_offset,
+            bloom_offset,
+            bloom_size,
+            has_bloom_filter,
+            ..
+        } = footer;
+        let legacy = version < CHECKSUMMED_VERSION;
+        let file_number = (version >= FILE_NUMBER_VERSION).then_some(footer.file_number);
+
+        // Create new reader instance
+        let mut sstable_reader = SSTableReader {
+            file: reader,
+            entry_count,
+            index_offset,
+            bloom_offset, // Add this field to use the bloom offset value
+            bloom_size,
+            bloom_filter: None,
+            partitioned_bloom_filter: None,
+            has_bloom_filter,
+            #[allow(dead_code)] // Needed for future data integrity features
+            block_checksums: Vec::new(),
+            #[allow(dead_code)] // Needed for future data integrity features
+            header_checksum,
+            checksums_offset: footer.checksums_offset,
+            version,
+            has_entry_checksums: !legacy,
+            checksum_kind,
+            file_number,
+            properties: SSTableProperties::new(),
+            write_times: HashMap::new(),
+            expiries: HashMap::new(),
+            sequences: HashMap::new(),
+            value_log_segments: Arc::default(),
+            tombstones: HashMap::new(),
+            range_tombstones: FragmentedRangeTombstones::new(),
+            decoder: ValueDecoder::default(),
+            block_index: Vec::new(),
+            partition_locations: Vec::new(),
+            block_filters: Vec::new(),
+            hash_index: None,
+            key_index: None,
+            restart_points: Arc::new([]),
+            key_expander: None,
+            resident_partitions: Mutex::new(VecDeque::new()),
+            filter_cache: filter_cache.map(|cache| FilterCacheHandle {
+                id: cache.register(),
+                cache,
+            }),
+            mapping: None,
+        };
+
+        // Load the bloom filter if present and not left to a cache
+        if has_bloom_filter && sstable_reader.filter_cache.is_none() {
+            sstable_reader.load_bloom_filter()?;
+        }
+
+        // Load table properties from the meta section
+        if version >= META_SECTION_VERSION {
+            sstable_reader.load_meta_sections()?;
+        }
+        if legacy {
+            sstable_reader.load_legacy_index()?;
+        }
+
+        Ok(sstable_reader)
+    }
+
+    /// Read the fields versions 7 and earlier keep in the header after the
+    /// version, along with the header checksum; legacy files have only the
+    /// entry count and index offset
+    fn read_header_fields(
+        reader: &mut BufReader<TableFile>,
+        version: u32,
+    ) -> io::Result<(Footer, u32)> {
+        let mut entry_count_buf = [0u8; 8];
+        reader.read_exact(&mut entry_count_buf)?;
+        let entry_count = u64::from_le_bytes(entry_count_buf);
+        trace!("Header: Entry count = {}", entry_count);
+
+        let mut index_offset_buf = [0u8; 8];
+        reader.read_exact(&mut index_offset_buf)?;
+        let index_offset = u64::from_le_bytes(index_offset_buf);
+        trace!("Header: Index offset = {}", index_offset);
+
+        // Legacy files hold only entries and a key index after the index
+        // offset: no filter, no file number and no checksums
+        let legacy = version < CHECKSUMMED_VERSION;
+        let (bloom_offset, bloom_size, has_bloom_filter, file_number, header_checksum) = if legacy {
+            let file_size = reader.get_ref().len()?;
+            if index_offset < LEGACY_HEADER_SIZE as u64 || index_offset > file_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Invalid index offset {} for legacy SSTable of {} bytes",
+                        index_offset, file_size
+                    ),
+                ));
+            }
+            (0, 0, false, 0, 0)
+        } else {
+            let mut bloom_offset_buf = [0u8; 8];
+            reader.read_exact(&mut bloom_offset_buf)?;
+            let bloom_offset = u64::from_le_bytes(bloom_offset_buf);
+            trace!("Header: Bloom offset = {}", bloom_offset);
+
+            let mut bloom_size_buf = [0u8; 8];
+            reader.read_exact(&mut bloom_size_buf)?;
+            let bloom_size = u64::from_le_bytes(bloom_size_buf);
+            trace!("Header: Bloom size = {}", bloom_size);
+
+            let mut has_bloom_buf = [0u8; 1];
+            reader.read_exact(&mut has_bloom_buf)?;
+            let has_bloom_filter = has_bloom_buf[0] != 0;
+            trace!("Header: Has bloom filter = {}", has_bloom_filter);
+
+            let file_number = if version >= FILE_NUMBER_VERSION {
+                let mut file_number_buf = [0u8; 8];
+                reader.read_exact(&mut file_number_buf)?;
+                u64::from_le_bytes(file_number_buf)
+            } else {
+                0
+            };
+
+            // The file opened above already decrypts and decompresses
+            // blocks if the cipher and compression type say they are stored
+            // other than as written
+            if version >= ENCRYPTION_VERSION {
+                let mut cipher_buf = [0u8; HEADER_CIPHER_SIZE];
+                reader.read_exact(&mut cipher_buf)?;
+                cipher_from_id(cipher_buf[0])?;
+            }
+            if version >= BLOCK_COMPRESSION_VERSION {
+                let mut compression_type_buf = [0u8; HEADER_COMPRESSION_TYPE_SIZE];
+                reader.read_exact(&mut compression_type_buf)?;
+                CompressionType::from_id(compression_type_buf[0])?;
+            }
+
+            let mut header_checksum_buf = [0u8; 4];
+            reader.read_exact(&mut header_checksum_buf)?;
+            let header_checksum = u32::from_le_bytes(header_checksum_buf);
+            trace!("Header: Checksum = {}", header_checksum);
+
+            (
+                bloom_offset,
+                bloom_size,
+                has_bloom_filter,
+                file_number,
+                header_checksum,
+            )
+        };
+        // Before the footer, the checksum table ended the file
+        let checksums_offset = if legacy {
+            0
+        } else {
+            let file_size = reader.get_ref().len()?;
+            file_size.saturating_sub(entry_count.saturating_mul(4))
+        };
+
+        Ok((
+            Footer {
+                entry_count,
+                index_offset,
+                bloom_offset,
+                bloom_size,
+                has_bloom_filter,
+                file_number,
+                checksums_offset,
+            },
+            header_checksum,
+        ))
+    }
+
+    /// Load the index a legacy file keeps after its entries, so lookups read
+    /// one entry and `iter` walks the entries in key order. An index that
+    /// cannot be read, or does not list every entry, is ignored and the
+    /// file is scanned instead, as it was before the index was read.
+    fn load_legacy_index(&mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(self.index_offset))?;
+        let mut buf = Vec::new();
+        self.file.read_to_end(&mut buf)?;
+        match KeyIndex::decode_legacy(&buf, self.data_offset(), self.index_offset) {
+            Ok(key_index) if key_index.len() as u64 == self.entry_count => {
+                self.key_index = Some(key_index);
+            }
+            Ok(key_index) => warn!(
+                "Ignoring legacy SSTable index listing {} keys for {} entries",
+                key_index.len(),
+                self.entry_count
+            ),
+            Err(e) => warn!("Ignoring unreadable legacy SSTable index: {}", e),
+        }
+        Ok(())
+    }
+
+    /// Load the Bloom filter from the SSTable file
+    fn load_bloom_filter(&mut self) -> io::Result<()> {
+        if !self.has_bloom_filter {
+            return Ok(());
+        }
+
+        match Self::read_filter(&mut self.file, self.bloom_offset)? {
+            Some(LoadedFilter::Standard(filter)) => self.bloom_filter = Some(filter),
+            Some(LoadedFilter::Partitioned(filter)) => self.partitioned_bloom_filter = Some(filter),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Read the filter region at the file's bloom offset; `None` for a
+    /// partitioned index, whose filters are loaded per partition
+    fn read_filter<R: Read + Seek>(file: &mut R, offset: u64) -> io::Result<Option<LoadedFilter>> {
+        // Lengths in the filter region are checked against the end of the file
+        let end = file.seek(SeekFrom::End(0))?;
+
+        // Position the file at the bloom filter offset from the header
+        let file_pos = file.stream_position()?;
+        trace!("Current file position: {}", file_pos);
+
+        // Use the bloom_offset directly from the header
+        trace!("Seeking to bloom filter offset: {}", offset);
+        file.seek(SeekFrom::Start(offset))?;
+
+        // Dump a few bytes from this position to see what's in the file
+        let mut preview_buf = [0u8; 16];
+        let bytes_read = file.read(&mut preview_buf)?;
+        trace!(
+            "Preview bytes at bloom filter offset (read {} bytes): {:?}",
+            bytes_read, preview_buf
+        );
+
+        // Seek back to the start position
+        file.seek(SeekFrom::Start(offset))?;
+
+        // First, read the bloom filter type byte
         let mut bloom_type_buf = [0u8; 1];
-        self.file.read_exact(&mut bloom_type_buf)?;
+        file.read_exact(&mut bloom_type_buf)?;
         let bloom_type = bloom_type_buf[0];
-        println!("Bloom filter type: {}", bloom_type);
+        trace!("Bloom filter type: {}", bloom_type);
 
         // Process based on bloom filter type
         match bloom_type {
+            index_partitions::PARTITIONED_FILTER_TYPE => {
+                // Partition filters are loaded by the lookups that need them
+                Ok(None)
+            }
             0 => {
                 // Standard bloom filter - read size and hash count
                 let mut size_bits_buf = [0u8; 8];
-                self.file.read_exact(&mut size_bits_buf)?;
-                println!("Raw size_bits_buf: {:?}", size_bits_buf);
-                let size_bits = u64::from_le_bytes(size_bits_buf) as usize;
-                println!("Parsed size_bits: {}", size_bits);
+                file.read_exact(&mut size_bits_buf)?;
+                trace!("Raw size_bits_buf: {:?}", size_bits_buf);
+                let size_bits = u64::from_le_bytes(size_bits_buf);
+                trace!("Parsed size_bits: {}", size_bits);
 
                 let mut num_hashes_buf = [0u8; 4];
-                self.file.read_exact(&mut num_hashes_buf)?;
+                file.read_exact(&mut num_hashes_buf)?;
                 let num_hashes = u32::from_le_bytes(num_hashes_buf) as usize;
-                println!("Parsed num_hashes: {}", num_hashes);
-
-                // Sanity check for bloom filter size
-                const MAX_BLOOM_FILTER_BITS: usize = 100_000_000; // 100M bits (12.5MB) is reasonably large
-                if size_bits > MAX_BLOOM_FILTER_BITS {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Bloom filter bits too large: {} bits", size_bits),
-                    ));
-                }
+                trace!("Parsed num_hashes: {}", num_hashes);
 
-                // Reasonable limit for number of hash functions
-                const MAX_HASH_FUNCTIONS: usize = 20;
-                if num_hashes > MAX_HASH_FUNCTIONS {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Unreasonable number of hash functions: {}", num_hashes),
-                    ));
-                }
-
-                // Calculate the number of bytes needed for the bloom filter
-                let size_bytes = match (size_bits + 7).checked_div(8) {
-                    Some(bytes) => bytes,
-                    None => {
-                        return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            "Integer overflow calculating bloom filter size",
-                        ));
-                    }
-                };
+                check_filter_hashes(num_hashes)?;
 
-                // One more safety check on the byte size
-                if size_bytes > MAX_BLOOM_FILTER_BITS / 8 {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Bloom filter byte size too large: {} bytes", size_bytes),
-                    ));
-                }
+                // The filter's bytes, checked before they are allocated
+                let size_bytes = checked_len::check_len(
+                    size_bits.div_ceil(8),
+                    MAX_FILTER_BITS / 8,
+                    end.saturating_sub(file.stream_position()?),
+                    "Bloom filter",
+                )?;
+                let size_bits = size_bits as usize;
 
                 // Read bloom filter data
                 let mut bits = vec![0u8; size_bytes];
-                self.file.read_exact(&mut bits)?;
+                file.read_exact(&mut bits)?;
 
                 // Create a new bloom filter with the loaded data
                 let bloom_filter = BloomFilter::<String>::from_parts(bits, size_bits, num_hashes);
-                self.bloom_filter = Some(bloom_filter);
+                Ok(Some(LoadedFilter::Standard(bloom_filter)))
             }
             1 => {
                 // Partitioned bloom filter - read number of partitions first
                 let mut num_partitions_buf = [0u8; 4];
-                self.file.read_exact(&mut num_partitions_buf)?;
-                let num_partitions = u32::from_le_bytes(num_partitions_buf) as usize;
-                println!("Partitions: {}", num_partitions);
-
-                // Safety check for number of partitions
-                if num_partitions == 0 || num_partitions > 64 {
+                file.read_exact(&mut num_partitions_buf)?;
+                let num_partitions = u32::from_le_bytes(num_partitions_buf);
+                trace!("Partitions: {}", num_partitions);
+
+                // Each partition takes at least its length
+                let num_partitions = checked_len::check_count(
+                    num_partitions as u64,
+                    MAX_FILTER_PARTITIONS,
+                    4,
+                    end.saturating_sub(file.stream_position()?),
+                    "Bloom filter partitions",
+                )?;
+                if num_partitions == 0 {
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidData,
-                        format!("Invalid number of partitions: {}", num_partitions),
+                        "Partitioned Bloom filter has no partitions",
                     ));
                 }
 
                 // Read the metadata (size_bits and num_hashes)
                 // These are used as overall metadata for the partitioned filter
                 let mut size_bits_buf = [0u8; 8];
-                self.file.read_exact(&mut size_bits_buf)?;
+                file.read_exact(&mut size_bits_buf)?;
                 let size_bits = u64::from_le_bytes(size_bits_buf) as usize;
-                println!("Metadata size_bits: {}", size_bits);
+                trace!("Metadata size_bits: {}", size_bits);
 
                 let mut num_hashes_buf = [0u8; 4];
-                self.file.read_exact(&mut num_hashes_buf)?;
+                file.read_exact(&mut num_hashes_buf)?;
                 let num_hashes = u32::from_le_bytes(num_hashes_buf) as usize;
-                println!("Metadata num_hashes: {}", num_hashes);
+                trace!("Metadata num_hashes: {}", num_hashes);
 
-                // Safety checks
-                const MAX_BLOOM_FILTER_BITS: usize = 100_000_000;
-                if size_bits > MAX_BLOOM_FILTER_BITS {
+                if size_bits > MAX_FILTER_BITS {
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidData,
                         format!("Bloom filter bits too large: {} bits", size_bits),
                     ));
                 }
-
-                const MAX_HASH_FUNCTIONS: usize = 20;
-                if num_hashes > MAX_HASH_FUNCTIONS {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Unreasonable number of hash functions: {}", num_hashes),
-                    ));
-                }
+                check_filter_hashes(num_hashes)?;
 
                 // Create a new partitioned bloom filter with expected parameters
                 // The actual parameters will be loaded from each partition
@@ -568,16 +1896,19 @@ impl SSTableReader {
                 let mut partitions = Vec::with_capacity(num_partitions);
                 for i in 0..num_partitions {
                     // Read partition size
-                    let mut bits_len_buf = [0u8; 4];
-                    self.file.read_exact(&mut bits_len_buf)?;
-                    let bits_len = u32::from_le_bytes(bits_len_buf) as usize;
-                    println!("Partition {} bits length: {}", i, bits_len);
+                    let bits_len = checked_len::read_len(
+                        file,
+                        end,
+                        MAX_FILTER_BITS / 8,
+                        "Bloom filter partition",
+                    )?;
+                    trace!("Partition {} bits length: {}", i, bits_len);
 
                     if bits_len > 0 {
                         // Read partition data
                         let mut bits = vec![0u8; bits_len];
-                        self.file.read_exact(&mut bits)?;
-                        println!("Read partition {} ({} bytes)", i, bits_len);
+                        file.read_exact(&mut bits)?;
+                        trace!("Read partition {} ({} bytes)", i, bits_len);
 
                         // Create a bloom filter from the data
                         let partition =
@@ -585,333 +1916,1711 @@ impl SSTableReader {
                         partitions.push(partition);
                     } else {
                         // Empty partition - create an empty one
-                        println!("Partition {} is empty", i);
+                        trace!("Partition {} is empty", i);
                         partitions.push(BloomFilter::new(100, 0.01)); // Empty filter
                     }
                 }
 
-                // Replace the partitions in the filter with our loaded ones
-                partitioned_filter.set_partitions(partitions);
-                self.partitioned_bloom_filter = Some(partitioned_filter);
-            }
-            _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Unknown bloom filter type: {}", bloom_type),
-                ));
+                // Replace the partitions in the filter with our loaded ones
+                partitioned_filter.set_partitions(partitions);
+                Ok(Some(LoadedFilter::Partitioned(partitioned_filter)))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown bloom filter type: {}", bloom_type),
+            )),
+        }
+    }
+
+    /// Read the meta section at the index offset and decode known sections
+    fn load_meta_sections(&mut self) -> io::Result<()> {
+        let file_size = self.file.get_ref().len()?;
+        self.file.seek(SeekFrom::Start(self.index_offset))?;
+
+        let mut count_buf = [0u8; 4];
+        self.file.read_exact(&mut count_buf)?;
+        // Each section takes at least its name length, length and checksum
+        let count = checked_len::check_count(
+            u32::from_le_bytes(count_buf) as u64,
+            MAX_META_SECTIONS,
+            10,
+            file_size.saturating_sub(self.file.stream_position()?),
+            "meta sections",
+        )?;
+
+        let mut compression_dict = None;
+        for _ in 0..count {
+            let mut name_len_buf = [0u8; 2];
+            self.file.read_exact(&mut name_len_buf)?;
+            let name_len = checked_len::check_len(
+                u16::from_le_bytes(name_len_buf) as u64,
+                u16::MAX as usize,
+                file_size.saturating_sub(self.file.stream_position()?),
+                "Meta section name",
+            )?;
+            let mut name_buf = vec![0u8; name_len];
+            self.file.read_exact(&mut name_buf)?;
+
+            let len = checked_len::read_len(
+                &mut self.file,
+                file_size,
+                u32::MAX as usize,
+                "Meta section",
+            )?;
+            let mut data = vec![0u8; len];
+            self.file.read_exact(&mut data)?;
+            let mut checksum_buf = [0u8; 4];
+            self.file.read_exact(&mut checksum_buf)?;
+            if self.checksum_kind.checksum(&data) != u32::from_le_bytes(checksum_buf) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Meta section checksum verification failed",
+                ));
+            }
+
+            // Unknown sections are skipped so newer writers stay readable
+            if name_buf == PROPERTIES_SECTION.as_bytes() {
+                self.properties = SSTableProperties::decode(&data)?;
+            } else if name_buf == WRITE_TIMES_SECTION.as_bytes() {
+                self.write_times = key_times::decode(&data)?;
+            } else if name_buf == EXPIRIES_SECTION.as_bytes() {
+                self.expiries = key_times::decode(&data)?;
+            } else if name_buf == SEQUENCES_SECTION.as_bytes() {
+                self.sequences = key_times::decode(&data)?;
+            } else if name_buf == VALUE_LOG_SECTION.as_bytes() {
+                self.value_log_segments = Arc::new(key_times::decode(&data)?);
+            } else if name_buf == TOMBSTONES_SECTION.as_bytes() {
+                self.tombstones = tombstones::decode(&data)?;
+            } else if name_buf == RANGE_TOMBSTONES_SECTION.as_bytes() {
+                self.range_tombstones = range_tombstones::decode(&data)?;
+            } else if name_buf == COMPRESSION_DICT_SECTION.as_bytes() {
+                compression_dict = Some(data);
+            } else if name_buf == BLOCK_INDEX_SECTION.as_bytes() {
+                self.block_index = block_index::decode(&data)?;
+            } else if name_buf == INDEX_PARTITIONS_SECTION.as_bytes() {
+                let partitions = index_partitions::decode(&data)?;
+                self.partition_locations = partitions
+                    .iter()
+                    .map(|partition| (partition.payload_offset, partition.payload_len))
+                    .collect();
+                self.block_index = partitions
+                    .into_iter()
+                    .map(|partition| partition.span)
+                    .collect();
+            } else if name_buf == BLOCK_FILTERS_SECTION.as_bytes() {
+                self.block_filters = block_filters::decode(&data)?;
+            } else if name_buf == HASH_INDEX_SECTION.as_bytes() {
+                self.hash_index = Some(HashIndex::decode(&data)?);
+            } else if name_buf == KEY_INDEX_SECTION.as_bytes() {
+                self.key_index = Some(KeyIndex::decode(&data)?);
+            } else if name_buf == RESTART_POINTS_SECTION.as_bytes() {
+                self.restart_points = restart_points::decode(&data)?.into();
+            }
+        }
+
+        if let Some(codec) = self.properties.get(properties::PROP_COMPRESSION) {
+            let flagged = self.properties.get(properties::PROP_VALUE_FLAGS).is_some();
+            self.decoder = ValueDecoder::for_codec(codec, compression_dict.as_deref(), flagged)?;
+        }
+        if self.has_prefix_compressed_keys() {
+            self.key_expander = Some(KeyExpander::new(self.restart_points.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Format version of the file
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Number the manifest allocated to the file; `None` for files older
+    /// than version 5, and 0 for files written outside an index
+    pub fn file_number(&self) -> Option<u64> {
+        self.file_number
+    }
+
+    /// Function the file's entry and meta section checksums were computed
+    /// with; CRC32 for files older than version 9
+    pub fn checksum_kind(&self) -> ChecksumKind {
+        self.checksum_kind
+    }
+
+    /// WAL LSN up to which logged writes are reflected in the file; `None`
+    /// if it was not written from the WAL
+    pub fn applied_lsn(&self) -> Option<u64> {
+        self.properties.get_u64(properties::PROP_APPLIED_LSN)
+    }
+
+    /// Sequence every entry in the file takes, if it was ingested
+    pub fn global_sequence(&self) -> Option<u64> {
+        self.properties.get_u64(properties::PROP_GLOBAL_SEQUENCE)
+    }
+
+    /// Codec the file's data blocks are compressed with; `None` for files
+    /// older than version 7
+    pub fn compression_type(&self) -> CompressionType {
+        self.file.get_ref().compression_type()
+    }
+
+    /// ID of the key the file's data blocks are encrypted with, from its
+    /// `lsmer.encryption_key_id` property; `None` for unencrypted files
+    pub fn encryption_key_id(&self) -> Option<&str> {
+        self.properties.get(properties::PROP_ENCRYPTION_KEY_ID)
+    }
+
+    /// File number of the newest flush whose writes the file holds. Files
+    /// written before flush numbers were recorded fall back to their own
+    /// file number; `None` if they have neither.
+    pub fn flush_number(&self) -> Option<u64> {
+        self.properties
+            .get_u64(properties::PROP_FLUSH_NUMBER)
+            .or_else(|| self.file_number())
+    }
+
+    /// When the file was written, in seconds since the Unix epoch, if the
+    /// writer recorded it
+    pub fn created_at_secs(&self) -> Option<u64> {
+        self.properties.get_u64(properties::PROP_CREATED_AT)
+    }
+
+    /// Level of the index the file was written for, if the writer recorded it
+    pub fn level(&self) -> Option<u32> {
+        self.properties
+            .get(properties::PROP_LEVEL)
+            .and_then(|level| level.parse().ok())
+    }
+
+    /// WAL checkpoint a flush wrote the file under; `None` for files not
+    /// flushed from the WAL
+    pub fn checkpoint_id(&self) -> Option<u64> {
+        self.properties.get_u64(properties::PROP_CHECKPOINT_ID)
+    }
+
+    /// Property the application set with `SSTableWriter::set_user_property`
+    pub fn user_property(&self, name: &str) -> Option<&str> {
+        self.properties.user_property(name)
+    }
+
+    /// Sequence of the write behind a key's entry: the one recorded for the
+    /// entry, or else the file's global sequence. `None` for files written
+    /// without sequences.
+    pub fn sequence(&self, key: &str) -> Option<u64> {
+        self.sequences
+            .get(key)
+            .copied()
+            .or_else(|| self.global_sequence())
+    }
+
+    /// Sequences recorded for the file's entries, keyed by entry key
+    pub fn sequences(&self) -> &HashMap<String, u64> {
+        &self.sequences
+    }
+
+    /// Value log segment the value of `key` points into, if the file stores
+    /// a pointer for it rather than the value
+    pub fn value_log_segment(&self, key: &str) -> Option<u64> {
+        self.value_log_segments.get(key).copied()
+    }
+
+    /// Value log segment of each entry whose value is a pointer, keyed by
+    /// entry key
+    pub fn value_log_segments(&self) -> &Arc<HashMap<String, u64>> {
+        &self.value_log_segments
+    }
+
+    /// Largest sequence of any entry in the file, counting the global
+    /// sequence of an ingested file
+    pub fn max_sequence(&self) -> Option<u64> {
+        let recorded = self.properties.get_u64(properties::PROP_MAX_SEQUENCE);
+        recorded.max(self.global_sequence())
+    }
+
+    /// Offset of the first entry, just past the header
+    pub fn data_offset(&self) -> u64 {
+        header_size(self.version) as u64
+    }
+
+    /// Table properties; empty for files older than the meta section
+    pub fn properties(&self) -> &SSTableProperties {
+        &self.properties
+    }
+
+    /// Merkle root of the entries in the file, if recorded
+    pub fn content_digest(&self) -> Option<Digest> {
+        self.properties
+            .get(properties::PROP_CONTENT_DIGEST)
+            .and_then(Digest::from_hex)
+    }
+
+    /// Check if a key might exist in the SSTable
+    pub fn may_contain(&self, key: &str) -> bool {
+        if self.has_partitioned_index() {
+            // A partition that cannot be loaded rules nothing out
+            self.partition_of(key).is_some_and(|partition| {
+                self.load_partition(partition)
+                    .map_or(true, |payload| payload.filter.may_contain(&key.to_string()))
+            })
+        } else if self.filter_cache.is_some() {
+            // Likewise a filter that cannot be loaded
+            self.cached_filter()
+                .is_none_or(|filter| filter.map_or(true, |filter| filter.may_contain(key)))
+        } else if let Some(bloom_filter) = &self.bloom_filter {
+            bloom_filter.may_contain(&key.to_string())
+        } else if let Some(partitioned_filter) = &self.partitioned_bloom_filter {
+            partitioned_filter.may_contain(&key.to_string())
+        } else {
+            true // If no bloom filter, we have to assume the key might exist
+        }
+    }
+
+    /// Check if multiple keys might exist in the SSTable (using parallel lookups if available)
+    pub fn may_contain_batch(&self, keys: &[String]) -> Vec<bool> {
+        if let Some(partitioned_filter) = &self.partitioned_bloom_filter {
+            // Use parallel lookups for partitioned filter
+            partitioned_filter.may_contain_parallel(keys)
+        } else if self.bloom_filter.is_some()
+            || self.has_partitioned_index()
+            || self.filter_cache.is_some()
+        {
+            // Fall back to sequential lookups for standard filter
+            keys.iter().map(|key| self.may_contain(key)).collect()
+        } else {
+            // No filter, assume all keys might exist
+            vec![true; keys.len()]
+        }
+    }
+
+    /// Get the value for a key, if it exists
+    pub fn get(&mut self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        if !self.may_hold(key) {
+            return Ok(None);
+        }
+
+        // Get the file size to help with validation
+        let file_size = self.file.get_ref().len()?;
+        for (start, count) in self.lookup_runs(key, file_size)? {
+            if let Some(value) = self.scan_for(key, start, count, file_size)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get the value for a key without copying it. The first call maps the
+    /// file into memory; values stored uncompressed are then returned as
+    /// slices of the map, and compressed ones decoded as `get` would. On
+    /// platforms without memory maps, and for files whose data blocks are
+    /// compressed or encrypted, every value is read as by `get`.
+    pub fn get_ref(&mut self, key: &str) -> io::Result<Option<ValueRef>> {
+        if !self.may_hold(key) {
+            return Ok(None);
+        }
+        let mapped = match &self.mapping {
+            Some(map) => Ok(map.clone()),
+            None => match self.file.get_ref().mappable() {
+                Some(file) => MappedFile::map(file).map(Arc::new),
+                None => Err(io::Error::from(io::ErrorKind::Unsupported)),
+            },
+        };
+        let map = match mapped {
+            Ok(map) => self.mapping.insert(map).clone(),
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                return Ok(self.get(key)?.map(ValueRef::owned));
+            }
+            Err(e) => return Err(e),
+        };
+
+        for (start, count) in self.lookup_runs(key, map.len() as u64)? {
+            let Some(stored) = self.scan_mapped(&map, key, start, count)? else {
+                continue;
+            };
+            let value = match self.decoder.decode_ref(&map[stored.clone()])? {
+                // A borrowed value is what follows any flag byte
+                Cow::Borrowed(value) => {
+                    ValueRef::mapped(map.clone(), stored.end - value.len()..stored.end)
+                }
+                Cow::Owned(value) => ValueRef::owned(value),
+            };
+            return Ok(Some(value));
+        }
+        Ok(None)
+    }
+
+    /// Bytes of the file mapped into memory by `get_ref`, if any
+    pub fn mapped_bytes(&self) -> usize {
+        self.mapping.as_ref().map_or(0, |map| map.len())
+    }
+
+    /// Whether the key can be in the file, by its key range and filters
+    fn may_hold(&self, key: &str) -> bool {
+        // Keys outside the file's range need no filter probe
+        self.key_range_contains(key) && self.may_contain(key)
+    }
+
+    /// Runs of entries, as offset and count, that a lookup of `key` reads
+    /// in turn until one holds the key
+    fn lookup_runs(&self, key: &str, file_size: u64) -> io::Result<Vec<(u64, u64)>> {
+        // A hash index names the only entries that can hold the key
+        if let Some(hash_index) = &self.hash_index {
+            return Ok(hash_index
+                .candidates(key)
+                .into_iter()
+                .map(|offset| (offset, 1))
+                .collect());
+        }
+        if let Some(key_index) = &self.key_index {
+            return Ok(key_index
+                .candidates(key)
+                .iter()
+                .map(|&offset| (offset, 1))
+                .collect());
+        }
+
+        // Sorted blocks narrow the scan to the one block that can hold the
+        // key, which its own filter may rule out
+        match self.block_for(key) {
+            Some(block) => match block? {
+                Some((offset, _)) if !self.block_filter_allows(offset, key) => Ok(Vec::new()),
+                Some((offset, count)) => Ok(vec![self.restart_run(key, offset, count, file_size)?]),
+                None => Ok(Vec::new()),
+            },
+            None => Ok(vec![(self.data_offset(), self.entry_count)]),
+        }
+    }
+
+    /// Narrow a lookup in the sorted block of `count` entries at `offset` to
+    /// the run of entries from the last restart point whose key is at or
+    /// before `key`. Returns the run's offset and length, or the whole block
+    /// if it has no restart points.
+    fn restart_run(
+        &self,
+        key: &str,
+        offset: u64,
+        count: u64,
+        file_size: u64,
+    ) -> io::Result<(u64, u64)> {
+        let Some(interval) = self.restart_interval().map(|interval| interval as u64) else {
+            return Ok((offset, count));
+        };
+        let first = self.restart_points.partition_point(|&point| point < offset);
+        if self.restart_points.get(first) != Some(&offset) {
+            return Ok((offset, count));
+        }
+        let runs = (count.div_ceil(interval) as usize).min(self.restart_points.len() - first);
+        let restarts = &self.restart_points[first..first + runs];
+
+        // The first run always starts at or before the key
+        let (mut low, mut high) = (0, restarts.len());
+        while high - low > 1 {
+            let mid = (low + high) / 2;
+            if self.key_at(restarts[mid], file_size)?.as_str() <= key {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        let remaining = count - low as u64 * interval;
+        Ok((restarts[low], remaining.min(interval)))
+    }
+
+    /// Read the key of the entry at `offset`
+    fn key_at(&self, offset: u64, file_size: u64) -> io::Result<String> {
+        let mut key = self.stored_key_at(offset, file_size)?;
+        if let Some(expander) = &self.key_expander {
+            let mut expander = expander.clone();
+            if let Some(restart) = expander.missing_restart(offset)? {
+                expander.expand(restart, &self.stored_key_at(restart, file_size)?)?;
+            }
+            key = expander.expand(offset, &key)?;
+        }
+        String::from_utf8(key)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Key data is not valid UTF-8"))
+    }
+
+    /// Read the key of the entry at `offset` as stored, which may be
+    /// prefix-compressed
+    fn stored_key_at(&self, offset: u64, file_size: u64) -> io::Result<Vec<u8>> {
+        let mut len_buf = [0u8; 4];
+        self.file.get_ref().read_exact_at(&mut len_buf, offset)?;
+        let len = checked_len::check_len(
+            u32::from_le_bytes(len_buf) as u64,
+            MAX_KEY_SIZE,
+            file_size.saturating_sub(offset + 4),
+            "Key",
+        )?;
+        let mut key = vec![0u8; len];
+        self.file.get_ref().read_exact_at(&mut key, offset + 4)?;
+        Ok(key)
+    }
+
+    /// Read `count` entries from `start`, returning the value of the first
+    /// whose key is `key`
+    fn scan_for(
+        &mut self,
+        key: &str,
+        start: u64,
+        count: u64,
+        file_size: u64,
+    ) -> io::Result<Option<Vec<u8>>> {
+        // Prefix-compressed keys are rebuilt from the restart key before them
+        let mut expander = self.key_expander.clone();
+        if let Some(expander) = &mut expander {
+            expander.prime(&mut self.file, file_size, start)?;
+        }
+
+        // Reset file position to the start of data
+        self.file.seek(SeekFrom::Start(start))?;
+
+        // Scan the file for the key
+        let mut offset = start;
+        for _ in 0..count {
+            // Read key length
+            let key_len = checked_len::read_len(&mut self.file, file_size, MAX_KEY_SIZE, "Key")?;
+
+            // Read key
+            let mut key_buf = vec![0u8; key_len];
+            match self.file.read_exact(&mut key_buf) {
+                Ok(_) => {}
+                Err(e) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Failed to read key data: {}", e),
+                    ));
+                }
+            }
+            let entry_offset = offset;
+            offset += 4 + key_len as u64;
+            if let Some(expander) = &mut expander {
+                key_buf = expander.expand(entry_offset, &key_buf)?;
+            }
+
+            // Check UTF-8 for key
+            let current_key = match std::str::from_utf8(&key_buf) {
+                Ok(s) => s,
+                Err(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Key data is not valid UTF-8",
+                    ));
+                }
+            };
+
+            // Read value length
+            let value_len =
+                checked_len::read_len(&mut self.file, file_size, MAX_VALUE_SIZE, "Value")?;
+
+            // Read value
+            let mut value = vec![0u8; value_len];
+            match self.file.read_exact(&mut value) {
+                Ok(_) => {}
+                Err(e) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Failed to read value data: {}", e),
+                    ));
+                }
+            }
+            offset += 4 + value_len as u64;
+
+            // Read and verify the checksum, if the file has them
+            if self.has_entry_checksums {
+                let mut checksum_buf = [0u8; 4];
+                match self.file.read_exact(&mut checksum_buf) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Failed to read checksum: {}", e),
+                        ));
+                    }
+                }
+
+                // Verify checksum
+                if self.checksum_kind.entry_checksum(current_key, &value)
+                    != u32::from_le_bytes(checksum_buf)
+                {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "SSTable data block checksum verification failed",
+                    ));
+                }
+                offset += 4;
+            }
+
+            if current_key == key {
+                // Found the key, return the value
+                return self.decoder.decode(value).map(Some);
+            }
+            // No need to skip past the value, we've already read it
+        }
+
+        Ok(None)
+    }
+
+    /// Read `count` entries of the mapped file from `start`, returning where
+    /// the stored value of the first whose key is `key` lies in the map
+    fn scan_mapped(
+        &self,
+        map: &[u8],
+        key: &str,
+        start: u64,
+        count: u64,
+    ) -> io::Result<Option<Range<usize>>> {
+        let file_size = map.len() as u64;
+        let mut cursor = io::Cursor::new(map);
+        let mut expander = self.key_expander.clone();
+        if let Some(expander) = &mut expander {
+            expander.prime(&mut cursor, file_size, start)?;
+        }
+
+        // Slices the next `len` bytes, which `read_len` has checked lie in
+        // the map
+        let take = |cursor: &mut io::Cursor<&[u8]>, len: usize| {
+            let start = cursor.position() as usize;
+            cursor.set_position((start + len) as u64);
+            start..start + len
+        };
+
+        cursor.set_position(start);
+        for _ in 0..count {
+            let entry_offset = cursor.position();
+            let key_len = checked_len::read_len(&mut cursor, file_size, MAX_KEY_SIZE, "Key")?;
+            let mut key_buf = Cow::Borrowed(&map[take(&mut cursor, key_len)]);
+            if let Some(expander) = &mut expander {
+                key_buf = Cow::Owned(expander.expand(entry_offset, &key_buf)?);
+            }
+
+            let value_len = checked_len::read_len(&mut cursor, file_size, MAX_VALUE_SIZE, "Value")?;
+            let value = take(&mut cursor, value_len);
+
+            if self.has_entry_checksums {
+                let mut checksum_buf = [0u8; 4];
+                cursor.read_exact(&mut checksum_buf)?;
+                if self
+                    .checksum_kind
+                    .entry_checksum_bytes(&key_buf, &map[value.clone()])
+                    != u32::from_le_bytes(checksum_buf)
+                {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "SSTable data block checksum verification failed",
+                    ));
+                }
+            }
+
+            if *key_buf == *key.as_bytes() {
+                return Ok(Some(value));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Whether point lookups go through a hash index rather than the blocks
+    pub fn has_hash_index(&self) -> bool {
+        self.hash_index.is_some()
+    }
+
+    /// Whether point lookups binary-search an index of every key, as in
+    /// files whose keys were written unsorted
+    pub fn has_key_index(&self) -> bool {
+        self.key_index.is_some()
+    }
+
+    /// Whether each data block has its own Bloom filter
+    pub fn has_block_filters(&self) -> bool {
+        !self.block_filters.is_empty()
+    }
+
+    /// Check whether the data block `key` would lie in might contain it, by
+    /// the block's own filter. Keys between blocks are ruled out too. Files
+    /// without block filters, or whose block cannot be found, rule nothing
+    /// out.
+    pub fn block_may_contain(&self, key: &str) -> bool {
+        if self.block_filters.is_empty() {
+            return true;
+        }
+        match self.block_for(key) {
+            Some(Ok(Some((offset, _)))) => self.block_filter_allows(offset, key),
+            Some(Ok(None)) => false,
+            // A partition that cannot be loaded rules nothing out
+            Some(Err(_)) | None => true,
+        }
+    }
+
+    /// Offset and entry count of the data block whose key span holds `key`,
+    /// or `Ok(None)` if no block can hold it. `None` if the blocks cannot be
+    /// searched, because keys are unsorted or blocks were not recorded.
+    fn block_for(&self, key: &str) -> Option<io::Result<Option<(u64, u64)>>> {
+        let holds =
+            |block: &BlockHandle| block.first_key.as_str() <= key && key <= block.last_key.as_str();
+        if self.has_partitioned_index() {
+            let Some(partition) = self.partition_of(key) else {
+                return Some(Ok(None));
+            };
+            return Some(self.load_partition(partition).map(|payload| {
+                payload
+                    .blocks
+                    .iter()
+                    .find(|block| holds(block))
+                    .map(|block| (block.offset, block.entry_count))
+            }));
+        }
+        if !self.keys_sorted() || self.block_index.is_empty() {
+            return None;
+        }
+        let block = self
+            .block_index
+            .partition_point(|block| block.last_key.as_str() < key);
+        Some(Ok(self
+            .block_index
+            .get(block)
+            .filter(|block| holds(block))
+            .map(|block| (block.offset, block.entry_count))))
+    }
+
+    /// Whether the filter of the block at `offset` might contain `key`; true
+    /// if the block has no filter
+    fn block_filter_allows(&self, offset: u64, key: &str) -> bool {
+        self.block_filters
+            .binary_search_by_key(&offset, |block| block.offset)
+            .map_or(true, |found| {
+                self.block_filters[found]
+                    .filter
+                    .may_contain(&key.to_string())
+            })
+    }
+
+    /// Whether the file's block index and filter are split into partitions
+    /// that are loaded on demand
+    pub fn has_partitioned_index(&self) -> bool {
+        !self.partition_locations.is_empty()
+    }
+
+    /// Number of index partitions currently loaded
+    pub fn resident_partitions(&self) -> usize {
+        locks::recover(self.resident_partitions.lock()).len()
+    }
+
+    /// The filter from the cache the reader was opened with, reading it from
+    /// the file if it is not resident. `None` if the file has no filter or
+    /// the reader was opened without a cache.
+    fn cached_filter(&self) -> Option<io::Result<Arc<LoadedFilter>>> {
+        let handle = self.filter_cache.as_ref()?;
+        if !self.has_bloom_filter || self.bloom_size == 0 {
+            return None;
+        }
+        Some(handle.cache.get_or_load(handle.id, || {
+            let file_size = self.file.get_ref().len()?;
+            let len = checked_len::check_len(
+                self.bloom_size,
+                usize::MAX,
+                file_size.saturating_sub(self.bloom_offset),
+                "Bloom filter region",
+            )?;
+            let mut buf = vec![0u8; len];
+            self.file
+                .get_ref()
+                .read_exact_at(&mut buf, self.bloom_offset)?;
+            Self::read_filter(&mut io::Cursor::new(buf), 0)?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Filter region holds partition filters",
+                )
+            })
+        }))
+    }
+
+    /// Partition whose key span holds `key`, if the index is partitioned
+    fn partition_of(&self, key: &str) -> Option<usize> {
+        if !self.has_partitioned_index() {
+            return None;
+        }
+        let partition = self
+            .block_index
+            .partition_point(|span| span.last_key.as_str() < key);
+        self.block_index
+            .get(partition)
+            .filter(|span| span.first_key.as_str() <= key)
+            .map(|_| partition)
+    }
+
+    /// Load an index partition, reusing it if it is resident. Only the
+    /// `MAX_RESIDENT_PARTITIONS` most recently loaded stay in memory.
+    fn load_partition(&self, partition: usize) -> io::Result<Arc<PartitionPayload>> {
+        let mut resident = locks::checked(self.resident_partitions.lock(), "index partition")?;
+        if let Some((_, payload)) = resident.iter().find(|(i, _)| *i == partition) {
+            return Ok(payload.clone());
+        }
+
+        let (offset, len) = self.partition_locations[partition];
+        let offset = self.bloom_offset.saturating_add(offset);
+        let file_size = self.file.get_ref().len()?;
+        let len = checked_len::check_len(
+            len,
+            usize::MAX,
+            file_size.saturating_sub(offset),
+            "Index partition",
+        )?;
+        let mut buf = vec![0u8; len];
+        self.file.get_ref().read_exact_at(&mut buf, offset)?;
+        let payload = Arc::new(index_partitions::decode_payload(&buf)?);
+
+        if resident.len() == MAX_RESIDENT_PARTITIONS {
+            resident.pop_front();
+        }
+        resident.push_back((partition, payload.clone()));
+        Ok(payload)
+    }
+
+    /// Estimate the heap memory the reader holds: its read buffer, Bloom
+    /// filter and the per-key metadata loaded from the meta section. A filter
+    /// held by a filter cache is counted by the cache instead.
+    pub fn memory_usage(&self) -> usize {
+        let filter_bytes = match (&self.bloom_filter, &self.partitioned_bloom_filter) {
+            (Some(filter), _) => filter.get_bits().len(),
+            (None, Some(filter)) => (0..filter.num_partitions())
+                .filter_map(|i| filter.get_partition(i))
+                .map(|partition| partition.get_bits().len())
+                .sum(),
+            (None, None) => 0,
+        };
+        let key_time_bytes: usize = self
+            .write_times
+            .keys()
+            .chain(self.expiries.keys())
+            .chain(self.sequences.keys())
+            .chain(self.value_log_segments.keys())
+            .map(|key| key.len() + 8)
+            .sum();
+        let tombstone_bytes: usize = self
+            .tombstones
+            .iter()
+            .map(|(key, tombstone)| key.len() + 8 + tombstone.value.as_ref().map_or(0, Vec::len))
+            .sum::<usize>()
+            + self
+                .range_tombstones
+                .fragments()
+                .iter()
+                .map(|fragment| fragment.start.len() + fragment.end.len() + 8)
+                .sum::<usize>();
+        let block_index_bytes: usize = self
+            .block_index
+            .iter()
+            .map(|block| block.first_key.len() + block.last_key.len() + 16)
+            .sum::<usize>()
+            + self
+                .block_filters
+                .iter()
+                .map(|block| block.filter.get_bits().len() + 8)
+                .sum::<usize>()
+            + self.hash_index.as_ref().map_or(0, HashIndex::memory_usage)
+            + self.key_index.as_ref().map_or(0, KeyIndex::memory_usage)
+            + self.restart_points.len() * 8;
+        let partition_bytes: usize = self.partition_locations.len() * 16
+            + locks::recover(self.resident_partitions.lock())
+                .iter()
+                .map(|(_, payload)| payload.memory_usage())
+                .sum::<usize>();
+
+        self.file.capacity()
+            + filter_bytes
+            + key_time_bytes
+            + tombstone_bytes
+            + block_index_bytes
+            + partition_bytes
+            + self.block_checksums.len() * 4
+    }
+
+    /// Get the number of entries in the SSTable
+    pub fn entry_count(&self) -> u64 {
+        self.entry_count
+    }
+
+    /// Check if the SSTable has a Bloom filter
+    pub fn has_bloom_filter(&self) -> bool {
+        self.has_bloom_filter
+    }
+
+    /// The standard Bloom filter loaded from the file, if it has one; `None`
+    /// if the filter is held by a filter cache
+    pub fn bloom_filter(&self) -> Option<&BloomFilter<String>> {
+        self.bloom_filter.as_ref()
+    }
+
+    /// The partitioned Bloom filter loaded from the file, if it has one;
+    /// `None` if the filter is held by a filter cache
+    pub fn partitioned_bloom_filter(&self) -> Option<&PartitionedBloomFilter<String>> {
+        self.partitioned_bloom_filter.as_ref()
+    }
+
+    /// Returns true if the writer recorded that keys are in strictly ascending
+    /// order; files without the property are assumed unsorted
+    pub fn keys_sorted(&self) -> bool {
+        self.properties.get(properties::PROP_KEYS_SORTED) == Some("true")
+    }
+
+    /// Name of the prefix extractor whose prefixes the Bloom filter holds
+    pub fn prefix_extractor_name(&self) -> Option<&str> {
+        self.properties.get(properties::PROP_PREFIX_EXTRACTOR)
+    }
+
+    /// Check if any key with the given prefix might exist in the SSTable.
+    ///
+    /// Only files whose filter was built with an extractor of the same name
+    /// can rule a prefix out; for any other file this returns true.
+    pub fn may_contain_prefix(&self, extractor: &dyn PrefixExtractor, prefix: &str) -> bool {
+        if self.prefix_extractor_name() != Some(extractor.name().as_str()) {
+            return true;
+        }
+        self.may_contain(prefix)
+    }
+
+    /// When a key was written, in milliseconds since the Unix epoch, if the
+    /// file recorded write times
+    pub fn write_time(&self, key: &str) -> Option<u64> {
+        self.write_times.get(key).copied()
+    }
+
+    /// Write times recorded for the file's entries, keyed by entry key
+    pub fn write_times(&self) -> &HashMap<String, u64> {
+        &self.write_times
+    }
+
+    /// When a key expires, in milliseconds since the Unix epoch, if it was
+    /// written with a time to live
+    pub fn expires_at(&self, key: &str) -> Option<u64> {
+        self.expiries.get(key).copied()
+    }
+
+    /// Expiry times recorded for the file's entries, keyed by entry key
+    pub fn expiries(&self) -> &HashMap<String, u64> {
+        &self.expiries
+    }
+
+    /// Earliest expiry time of any entry in the file, if any entry expires
+    pub fn min_expiry_ms(&self) -> Option<u64> {
+        self.properties.get_u64(properties::PROP_MIN_EXPIRY)
+    }
+
+    /// Latest expiry time of any entry in the file, if any entry expires
+    pub fn max_expiry_ms(&self) -> Option<u64> {
+        self.properties.get_u64(properties::PROP_MAX_EXPIRY)
+    }
+
+    /// Tombstones recorded in the file, keyed by deleted key
+    pub fn tombstones(&self) -> &HashMap<String, Tombstone> {
+        &self.tombstones
+    }
+
+    /// Key ranges recorded in the file as deleted. They hide keys in older
+    /// files only, never the file's own entries.
+    pub fn range_tombstones(&self) -> &FragmentedRangeTombstones {
+        &self.range_tombstones
+    }
+
+    /// Total size of the keys and values as written, if recorded
+    pub fn raw_bytes(&self) -> Option<u64> {
+        self.properties.get_u64(properties::PROP_RAW_BYTES)
+    }
+
+    /// On-disk size of the data section, if recorded
+    pub fn data_bytes(&self) -> Option<u64> {
+        self.properties.get_u64(properties::PROP_DATA_BYTES)
+    }
+
+    /// Number of tombstones in the file
+    pub fn tombstone_count(&self) -> u64 {
+        self.properties
+            .get_u64(properties::PROP_NUM_TOMBSTONES)
+            .unwrap_or(self.tombstones.len() as u64)
+    }
+
+    /// Number of fragments of deleted key ranges in the file
+    pub fn range_tombstone_count(&self) -> u64 {
+        self.properties
+            .get_u64(properties::PROP_NUM_RANGE_DELETIONS)
+            .unwrap_or(self.range_tombstones.fragments().len() as u64)
+    }
+
+    /// Compression recorded for the file's values, if any
+    pub fn compression_name(&self) -> Option<&str> {
+        self.properties.get(properties::PROP_COMPRESSION)
+    }
+
+    /// Smallest key the file has an entry or tombstone for, if recorded
+    pub fn min_key(&self) -> Option<&str> {
+        self.properties.get(properties::PROP_MIN_KEY)
+    }
+
+    /// Largest key the file has an entry or tombstone for, if recorded
+    pub fn max_key(&self) -> Option<&str> {
+        self.properties.get(properties::PROP_MAX_KEY)
+    }
+
+    /// Whether the file's recorded key range takes in `key`; files written
+    /// before ranges were recorded may hold any key unless they are empty.
+    /// Range tombstones are not counted, so they must be checked on their
+    /// own.
+    pub fn key_range_contains(&self, key: &str) -> bool {
+        match (self.min_key(), self.max_key()) {
+            (Some(min), Some(max)) => min <= key && key <= max,
+            _ => self.entry_count > 0 || !self.tombstones.is_empty(),
+        }
+    }
+
+    /// Whether the file's recorded key range meets keys from `lower`
+    /// (inclusive) up to `upper` (exclusive)
+    pub fn key_range_overlaps(&self, lower: Option<&str>, upper: Option<&str>) -> bool {
+        match (self.min_key(), self.max_key()) {
+            (Some(min), Some(max)) => {
+                lower.is_none_or(|lower| lower <= max) && upper.is_none_or(|upper| min < upper)
             }
+            _ => self.entry_count > 0 || !self.tombstones.is_empty(),
         }
+    }
 
-        Ok(())
+    /// Number of values a compressed file stores as written because they
+    /// looked incompressible or would not shrink; `None` for uncompressed
+    /// files and files that compressed every value
+    pub fn uncompressed_value_count(&self) -> Option<u64> {
+        self.properties.get_u64(properties::PROP_UNCOMPRESSED_VALUES)
     }
 
-    /// Check if a key might exist in the SSTable
-    pub fn may_contain(&self, key: &str) -> bool {
-        if let Some(bloom_filter) = &self.bloom_filter {
-            bloom_filter.may_contain(&key.to_string())
-        } else if let Some(partitioned_filter) = &self.partitioned_bloom_filter {
-            partitioned_filter.may_contain(&key.to_string())
+    /// Size at which the writer closed data blocks, if recorded
+    pub fn data_block_size(&self) -> Option<u64> {
+        self.properties.get_u64(properties::PROP_DATA_BLOCK_SIZE)
+    }
+
+    /// Size at which the writer closed index partitions, if it partitioned
+    /// the index by size
+    pub fn index_block_size(&self) -> Option<u64> {
+        self.properties.get_u64(properties::PROP_INDEX_BLOCK_SIZE)
+    }
+
+    /// Whether keys are stored prefix-compressed against the keys at
+    /// restart points
+    pub fn has_prefix_compressed_keys(&self) -> bool {
+        self.properties
+            .get(properties::PROP_KEY_PREFIX_COMPRESSION)
+            .is_some()
+    }
+
+    /// Rebuilds keys read straight from the file, if it stores them
+    /// prefix-compressed
+    pub(crate) fn key_expander(&self) -> Option<KeyExpander> {
+        self.key_expander.clone()
+    }
+
+    /// Entries between restart points, if the file records them
+    pub fn restart_interval(&self) -> Option<usize> {
+        self.properties
+            .get_u64(properties::PROP_RESTART_INTERVAL)
+            .map(|interval| interval as usize)
+            .filter(|&interval| interval > 0)
+    }
+
+    /// Decoder for values read from the file outside this reader
+    pub fn value_decoder(&self) -> &ValueDecoder {
+        &self.decoder
+    }
+
+    /// Data blocks in file order; empty for files written before blocks
+    /// were recorded
+    pub fn block_index(&self) -> &[BlockHandle] {
+        &self.block_index
+    }
+
+    /// Consume the reader and scan its entries in file order
+    pub fn into_entries(self) -> io::Result<SSTableEntries> {
+        self.into_range_entries(None, None)
+    }
+
+    /// Consume the reader and scan the entries with keys from `lower`
+    /// (inclusive) up to `upper` (exclusive), in file order.
+    ///
+    /// When the file's keys are sorted and its blocks are recorded, blocks
+    /// wholly outside the bounds are never read and the scan stops at the
+    /// first key past `upper`. Other files are scanned in full, skipping
+    /// entries outside the bounds, unless their recorded key range misses
+    /// the bounds altogether and nothing is read.
+    pub fn into_range_entries(
+        self,
+        lower: Option<&str>,
+        upper: Option<&str>,
+    ) -> io::Result<SSTableEntries> {
+        let file_size = self.file.get_ref().len()?;
+        let bounded = lower.is_some() || upper.is_some();
+        let sorted = bounded && self.keys_sorted() && !self.block_index.is_empty();
+
+        let (start, remaining) = if sorted {
+            // Sorted blocks in range are contiguous
+            let mut blocks = self.block_index.iter().filter(|block| {
+                lower.is_none_or(|lower| block.last_key.as_str() >= lower)
+                    && upper.is_none_or(|upper| block.first_key.as_str() < upper)
+            });
+            match blocks.next() {
+                Some(first) => (
+                    first.offset,
+                    first.entry_count + blocks.map(|block| block.entry_count).sum::<u64>(),
+                ),
+                None => (self.data_offset(), 0),
+            }
+        } else if bounded && !self.key_range_overlaps(lower, upper) {
+            (self.data_offset(), 0)
         } else {
-            true // If no bloom filter, we have to assume the key might exist
-        }
+            (self.data_offset(), self.entry_count)
+        };
+        let mut file = self.file.into_inner();
+        file.seek(SeekFrom::Start(start))?;
+        Ok(SSTableEntries {
+            file: BufReader::new(file),
+            remaining,
+            file_size,
+            decoder: self.decoder,
+            has_entry_checksums: self.has_entry_checksums,
+            checksum_kind: self.checksum_kind,
+            key_expander: self.key_expander,
+            lower: lower.map(str::to_string),
+            upper: upper.map(str::to_string),
+            sorted,
+            offsets: None,
+        })
     }
 
-    /// Check if multiple keys might exist in the SSTable (using parallel lookups if available)
-    pub fn may_contain_batch(&self, keys: &[String]) -> Vec<bool> {
-        if let Some(partitioned_filter) = &self.partitioned_bloom_filter {
-            // Use parallel lookups for partitioned filter
-            partitioned_filter.may_contain_parallel(keys)
-        } else if let Some(bloom_filter) = &self.bloom_filter {
-            // Fall back to sequential lookups for standard filter
-            keys.iter()
-                .map(|key| bloom_filter.may_contain(key))
-                .collect()
+    /// Scan the entries in key order without consuming the reader,
+    /// verifying each entry's checksum.
+    ///
+    /// Sorted files are read front to back. Files whose keys were written
+    /// unsorted are walked through their key index, and a key written more
+    /// than once yields each of its entries in file order. Unsorted files
+    /// without a key index, such as version 3 files or files with a hash
+    /// index, fail with `InvalidInput`; `into_entries` scans them in file
+    /// order.
+    pub fn iter(&self) -> io::Result<SSTableEntries> {
+        let offsets = if self.keys_sorted() {
+            None
+        } else if let Some(key_index) = &self.key_index {
+            Some(key_index.offsets().to_vec())
         } else {
-            // No filter, assume all keys might exist
-            vec![true; keys.len()]
-        }
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "SSTable keys are unsorted and the file has no key index",
+            ));
+        };
+
+        Ok(SSTableEntries {
+            file: BufReader::new(self.file.get_ref().try_clone_at(self.data_offset())?),
+            remaining: self.entry_count,
+            file_size: self.file.get_ref().len()?,
+            decoder: self.decoder.clone(),
+            has_entry_checksums: self.has_entry_checksums,
+            checksum_kind: self.checksum_kind,
+            key_expander: self.key_expander.clone(),
+            lower: None,
+            upper: None,
+            sorted: true,
+            offsets: offsets.map(Vec::into_iter),
+        })
     }
 
-    /// Get the value for a key, if it exists
-    pub fn get(&mut self, key: &str) -> io::Result<Option<Vec<u8>>> {
-        // First check the bloom filter
-        if !self.may_contain(key) {
-            return Ok(None);
-        }
+    /// Read the entry starting at `offset`, as reported by
+    /// `SSTableEntries::next_with_offset`, verifying its checksum
+    pub fn entry_at(&self, offset: u64) -> io::Result<(String, Vec<u8>)> {
+        let mut entries = SSTableEntries {
+            file: BufReader::new(self.file.get_ref().try_clone_at(offset)?),
+            remaining: 1,
+            file_size: self.file.get_ref().len()?,
+            decoder: self.decoder.clone(),
+            has_entry_checksums: self.has_entry_checksums,
+            checksum_kind: self.checksum_kind,
+            key_expander: self.key_expander.clone(),
+            lower: None,
+            upper: None,
+            sorted: true,
+            offsets: None,
+        };
+        entries.read_entry()
+    }
 
-        // Get the file size to help with validation
-        let file_size = self.file.get_ref().metadata()?.len();
+    /// Whether `iter` yields strictly ascending keys, so the file can be
+    /// merged with others one entry at a time
+    fn iterates_strictly_in_key_order(&self) -> bool {
+        self.keys_sorted()
+            || self
+                .key_index
+                .as_ref()
+                .is_some_and(|key_index| !key_index.has_repeated_keys())
+    }
 
-        // Reset file position to the start of data
-        self.file.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
+    /// Load block checksums from the file
+    #[allow(dead_code)] // Will be used in future data integrity features
+    fn load_block_checksums(&mut self, file_size: u64) -> io::Result<()> {
+        // If this is an older version file, no checksums to load
+        if VERSION <= 2 {
+            return Ok(());
+        }
 
-        // Scan the file for the key
-        for _ in 0..self.entry_count {
-            // Get current position for better error reporting
-            let entry_start_pos = self.file.stream_position()?;
+        // Safety check: Don't try to read if entry count is suspicious
+        const MAX_REASONABLE_ENTRIES: u64 = 1_000_000; // Limit to prevent huge allocations
+        if self.entry_count > MAX_REASONABLE_ENTRIES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unreasonable entry count: {}", self.entry_count),
+            ));
+        }
 
-            // Read key length
-            let mut key_len_buf = [0u8; 4];
-            match self.file.read_exact(&mut key_len_buf) {
-                Ok(_) => {}
-                Err(e) => {
+        // Calculate where checksums are stored (after data and bloom filter)
+        // Use checked arithmetic to prevent integer overflow
+        let checksums_start = if self.has_bloom_filter {
+            match self
+                .index_offset
+                .checked_add(self.entry_count.checked_mul(4).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Integer overflow calculating checksum location",
+                    )
+                })?) {
+                Some(start) => start,
+                None => {
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidData,
-                        format!(
-                            "Failed to read key length at position {}: {}",
-                            entry_start_pos, e
-                        ),
+                        "Integer overflow calculating checksum location",
                     ));
                 }
             }
+        } else {
+            self.index_offset
+        };
 
-            let key_len = u32::from_le_bytes(key_len_buf);
-
-            // Additional check - if key length would extend beyond file, it's corrupt
-            if entry_start_pos + 4 + key_len as u64 > file_size {
+        // Only try to read checksums if the file is large enough
+        // Use checked arithmetic to prevent overflow
+        match checksums_start.checked_add(self.entry_count.checked_mul(4).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Integer overflow calculating checksum size",
+            )
+        })?) {
+            Some(end_pos) if end_pos > file_size => return Ok(()),
+            None => {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidData,
-                    format!(
-                        "Key length {} would extend beyond file size at position {}",
-                        key_len, entry_start_pos
-                    ),
+                    "Integer overflow calculating checksum size",
                 ));
             }
+            _ => {}
+        };
 
-            // Sanity check for key length
-            const MIN_KEY_SIZE: u32 = 1; // At least 1 byte
-            const MAX_KEY_SIZE: u32 = 1024 * 1024; // 1MB max key size
+        // Check that entry_count can be safely converted to usize
+        if self.entry_count > usize::MAX as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Entry count too large for architecture: {}",
+                    self.entry_count
+                ),
+            ));
+        }
 
-            if key_len < MIN_KEY_SIZE {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Key length too small: {}", key_len),
-                ));
-            }
+        // Seek to the checksums section
+        self.file.seek(SeekFrom::Start(checksums_start))?;
 
-            if key_len > MAX_KEY_SIZE {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Key length too large: {}", key_len),
-                ));
-            }
+        // Read checksums (4 bytes each)
+        let capacity = self.entry_count as usize;
+        self.block_checksums = Vec::with_capacity(capacity);
+        for _ in 0..self.entry_count {
+            let mut checksum_buf = [0u8; 4];
+            self.file.read_exact(&mut checksum_buf)?;
+            self.block_checksums.push(u32::from_le_bytes(checksum_buf));
+        }
+
+        Ok(())
+    }
+}
+
+/// Reject a stored filter's hash function count past `MAX_FILTER_HASHES`
+fn check_filter_hashes(num_hashes: usize) -> io::Result<()> {
+    if num_hashes > MAX_FILTER_HASHES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unreasonable number of hash functions: {}", num_hashes),
+        ));
+    }
+    Ok(())
+}
+
+/// Sequential scan over the entries of an SSTable, verifying each entry's checksum
+#[derive(Debug)]
+pub struct SSTableEntries {
+    file: BufReader<TableFile>,
+    remaining: u64,
+    file_size: u64,
+    decoder: ValueDecoder,
+    /// Whether each entry is followed by a checksum
+    has_entry_checksums: bool,
+    /// Function entry checksums were computed with
+    checksum_kind: ChecksumKind,
+    /// Rebuilds keys if the file stores them prefix-compressed
+    key_expander: Option<KeyExpander>,
+    /// Entries with keys below this are skipped
+    lower: Option<String>,
+    /// Entries with keys at or above this are skipped
+    upper: Option<String>,
+    /// Whether keys arrive in order, so the scan can end at `upper`
+    sorted: bool,
+    /// Offsets of the entries to read in turn, if they are not read in
+    /// file order
+    offsets: Option<std::vec::IntoIter<u64>>,
+}
 
-            // Check for potential overflow when allocating buffer
-            if key_len as usize > isize::MAX as usize {
+impl SSTableEntries {
+    /// Read the entry at the current position
+    fn read_entry(&mut self) -> io::Result<(String, Vec<u8>)> {
+        let offset = match self.key_expander {
+            Some(_) => self.file.stream_position()?,
+            None => 0,
+        };
+        let key_len = self.read_len(MAX_KEY_SIZE, "Key")?;
+        let mut key_buf = vec![0u8; key_len];
+        self.file.read_exact(&mut key_buf)?;
+        if let Some(expander) = &mut self.key_expander {
+            expander.prime(&mut self.file, self.file_size, offset)?;
+            key_buf = expander.expand(offset, &key_buf)?;
+        }
+
+        let value_len = self.read_len(MAX_VALUE_SIZE, "Value")?;
+        let mut value = vec![0u8; value_len];
+        self.file.read_exact(&mut value)?;
+
+        let key = String::from_utf8(key_buf).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "Key data is not valid UTF-8")
+        })?;
+        if self.has_entry_checksums {
+            let mut checksum_buf = [0u8; 4];
+            self.file.read_exact(&mut checksum_buf)?;
+            if self.checksum_kind.entry_checksum(&key, &value) != u32::from_le_bytes(checksum_buf) {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidData,
-                    format!("Key length exceeds maximum allocatable size: {}", key_len),
+                    "SSTable data block checksum verification failed",
                 ));
             }
+        }
 
-            // Read key
-            let mut key_buf = vec![0u8; key_len as usize];
-            match self.file.read_exact(&mut key_buf) {
-                Ok(_) => {}
+        Ok((key, self.decoder.decode(value)?))
+    }
+
+    /// Read a length prefix and check it against the limit and the file size
+    fn read_len(&mut self, max: usize, what: &str) -> io::Result<usize> {
+        checked_len::read_len(&mut self.file, self.file_size, max, what)
+    }
+}
+
+impl SSTableEntries {
+    /// Read the next entry together with the offset it starts at, which
+    /// `SSTableReader::entry_at` reads it back from
+    pub fn next_with_offset(&mut self) -> Option<io::Result<(String, u64, Vec<u8>)>> {
+        while self.remaining > 0 {
+            let offset = match self.offsets.as_mut().and_then(Iterator::next) {
+                Some(offset) => self.file.seek(SeekFrom::Start(offset)),
+                None => self.file.stream_position(),
+            };
+            let offset = match offset {
+                Ok(offset) => offset,
                 Err(e) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Failed to read key data: {}", e),
-                    ));
+                    self.remaining = 0;
+                    return Some(Err(e));
                 }
-            }
-
-            // Check UTF-8 for key
-            let current_key = match std::str::from_utf8(&key_buf) {
-                Ok(s) => s,
-                Err(_) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "Key data is not valid UTF-8",
-                    ));
+            };
+            let (key, value) = match self.read_entry() {
+                Ok(entry) => entry,
+                Err(e) => {
+                    // Stop after the first error rather than reading from a bad offset
+                    self.remaining = 0;
+                    return Some(Err(e));
                 }
             };
+            self.remaining -= 1;
 
-            // Read value length
-            let mut value_len_buf = [0u8; 4];
-            match self.file.read_exact(&mut value_len_buf) {
-                Ok(_) => {}
-                Err(e) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Failed to read value length: {}", e),
-                    ));
+            if self
+                .upper
+                .as_deref()
+                .is_some_and(|upper| key.as_str() >= upper)
+            {
+                if self.sorted {
+                    self.remaining = 0;
                 }
+                continue;
             }
-            let value_len = u32::from_le_bytes(value_len_buf);
-
-            // Sanity check for value length
-            const MAX_VALUE_SIZE: u32 = 10 * 1024 * 1024; // 10MB max value size
-            if value_len > MAX_VALUE_SIZE {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Value length too large: {}", value_len),
-                ));
+            if self
+                .lower
+                .as_deref()
+                .is_some_and(|lower| key.as_str() < lower)
+            {
+                continue;
             }
+            return Some(Ok((key, offset, value)));
+        }
+        None
+    }
+}
 
-            // Check for potential overflow when allocating buffer
-            if value_len as usize > isize::MAX as usize {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!(
-                        "Value length exceeds maximum allocatable size: {}",
-                        value_len
-                    ),
-                ));
-            }
+impl Iterator for SSTableEntries {
+    type Item = io::Result<(String, Vec<u8>)>;
 
-            // Additional check - if value length would extend beyond file, it's corrupt
-            let current_pos = self.file.stream_position()?;
-            if current_pos + value_len as u64 > file_size {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Value length {} would read past end of file", value_len),
-                ));
-            }
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_offset()
+            .map(|entry| entry.map(|(key, _, value)| (key, value)))
+    }
+}
 
-            // Read value
-            let mut value = vec![0u8; value_len as usize];
-            match self.file.read_exact(&mut value) {
-                Ok(_) => {}
-                Err(e) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Failed to read value data: {}", e),
-                    ));
-                }
-            }
+/// Options controlling how `SSTableCompaction` writes its output
+#[derive(Debug, Clone)]
+pub struct CompactionOptions {
+    /// Whether the output gets a Bloom filter
+    pub use_bloom_filter: bool,
+    /// Target false positive rate of the output's Bloom filter
+    pub false_positive_rate: f64,
+    /// Whether the output's Bloom filter is partitioned rather than standard
+    pub use_partitioned_bloom: bool,
+    /// Whether the input files are deleted once the output is written
+    pub delete_originals: bool,
+    /// Extractor whose prefixes are added to the output's Bloom filter
+    pub prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    /// How long tombstones are carried into the output; `None` keeps them all.
+    ///
+    /// Dropping a tombstone is only safe when no file outside the compaction
+    /// still holds an older value for its key.
+    pub tombstone_retention: Option<Duration>,
+    /// Read time in milliseconds of the oldest snapshot still open against
+    /// the inputs, such as `LsmIndex::min_active_snapshot`. Tombstones
+    /// written at or after it are carried whatever their retention.
+    pub oldest_snapshot: Option<u64>,
+    /// Compression applied to the output's values
+    pub compression: Compression,
+    /// Compression applied to the output's data blocks as a whole
+    pub block_compression: CompressionType,
+    /// Provider of the keys encrypted inputs are read with. The output's
+    /// data blocks are encrypted under its current key, whichever keys the
+    /// inputs were encrypted with.
+    pub key_provider: Option<Arc<dyn KeyProvider>>,
+    /// Write a report of the compaction next to the output even when it
+    /// succeeds, recording every decision made for every key. Failed
+    /// compactions always write a report, with only the latest decisions.
+    pub debug_dump: bool,
+    /// Split the output's block index and Bloom filter into partitions of
+    /// this many data blocks, loaded on demand by readers
+    pub partitioned_index: Option<usize>,
+    /// False positive rate of a Bloom filter given to each of the output's
+    /// data blocks, if they get one
+    pub block_filters: Option<f64>,
+    /// Index the output's entries by key hash instead of by block
+    pub hash_index: bool,
+    /// Size at which the output's data blocks are closed; `None` uses
+    /// `DATA_BLOCK_SIZE`
+    pub block_size: Option<usize>,
+    /// Partition the output's index and filter into partitions of this many
+    /// bytes of block handles; takes precedence over `partitioned_index`
+    pub index_block_size: Option<usize>,
+    /// Record a restart point every this many entries of each output block
+    pub restart_interval: Option<usize>,
+    /// Store the output's keys prefix-compressed against the keys at its
+    /// restart points
+    pub key_prefix_compression: bool,
+    /// Function the output's entry and meta section checksums are computed
+    /// with
+    pub checksum_kind: ChecksumKind,
+    /// Clock deciding which entries have expired and which tombstones have
+    /// outlived their retention
+    pub clock: Arc<dyn Clock>,
+    /// Level of the index the output is written for, recorded in its
+    /// properties
+    pub level: Option<u32>,
+    /// User properties stamped on the output, replacing those of the same
+    /// name carried over from the inputs
+    pub user_properties: BTreeMap<String, String>,
+}
 
-            // Read checksum
-            let mut checksum_buf = [0u8; 4];
-            match self.file.read_exact(&mut checksum_buf) {
-                Ok(_) => {}
-                Err(e) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Failed to read checksum: {}", e),
-                    ));
-                }
-            }
+impl Default for CompactionOptions {
+    fn default() -> Self {
+        CompactionOptions {
+            use_bloom_filter: true,
+            false_positive_rate: 0.01,
+            use_partitioned_bloom: false,
+            delete_originals: false,
+            prefix_extractor: None,
+            tombstone_retention: None,
+            oldest_snapshot: None,
+            compression: Compression::None,
+            block_compression: CompressionType::None,
+            key_provider: None,
+            debug_dump: false,
+            partitioned_index: None,
+            block_filters: None,
+            hash_index: false,
+            block_size: None,
+            index_block_size: None,
+            restart_interval: None,
+            key_prefix_compression: false,
+            checksum_kind: ChecksumKind::default(),
+            clock: Arc::new(SystemClock),
+            level: None,
+            user_properties: BTreeMap::new(),
+        }
+    }
+}
+
+impl CompactionOptions {
+    /// Choose between a partitioned and a standard Bloom filter for the output
+    pub fn with_partitioned_bloom(mut self, use_partitioned_bloom: bool) -> Self {
+        self.use_partitioned_bloom = use_partitioned_bloom;
+        self
+    }
+
+    /// Set the output's Bloom filter false positive rate
+    pub fn with_false_positive_rate(mut self, false_positive_rate: f64) -> Self {
+        self.false_positive_rate = false_positive_rate;
+        self
+    }
+
+    /// Enable or disable the output's Bloom filter
+    pub fn with_bloom_filter(mut self, use_bloom_filter: bool) -> Self {
+        self.use_bloom_filter = use_bloom_filter;
+        self
+    }
+
+    /// Delete the inputs once the output is written
+    pub fn with_delete_originals(mut self, delete_originals: bool) -> Self {
+        self.delete_originals = delete_originals;
+        self
+    }
+
+    /// Add key prefixes under `extractor` to the output's Bloom filter
+    pub fn with_prefix_extractor(mut self, extractor: Arc<dyn PrefixExtractor>) -> Self {
+        self.prefix_extractor = Some(extractor);
+        self
+    }
+
+    /// Drop tombstones older than `retention` from the output
+    pub fn with_tombstone_retention(mut self, retention: Duration) -> Self {
+        self.tombstone_retention = Some(retention);
+        self
+    }
+
+    /// Keep tombstones an open snapshot taken at `at_ms` may still need
+    pub fn with_oldest_snapshot(mut self, at_ms: u64) -> Self {
+        self.oldest_snapshot = Some(at_ms);
+        self
+    }
+
+    /// Compress the output's values, training a dictionary from the inputs
+    /// if the Zstd options ask for one
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Compress each of the output's data blocks as a whole
+    pub fn with_block_compression(mut self, compression_type: CompressionType) -> Self {
+        self.block_compression = compression_type;
+        self
+    }
+
+    /// Encrypt the output's data blocks under `provider`'s current key, so
+    /// inputs encrypted under older keys are rewritten under the new one
+    pub fn with_encryption(mut self, provider: Arc<dyn KeyProvider>) -> Self {
+        self.key_provider = Some(provider);
+        self
+    }
+
+    /// Always write a full report to `compaction_report::report_path`
+    pub fn with_debug_dump(mut self, debug_dump: bool) -> Self {
+        self.debug_dump = debug_dump;
+        self
+    }
 
-            let stored_checksum = u32::from_le_bytes(checksum_buf);
+    /// Partition the output's index and filter every `blocks_per_partition`
+    /// data blocks. Partitioned filters do not hold prefixes.
+    pub fn with_partitioned_index(mut self, blocks_per_partition: usize) -> Self {
+        self.partitioned_index = Some(blocks_per_partition);
+        self
+    }
 
-            // Verify checksum
-            let mut entry_data = Vec::new();
-            entry_data.extend_from_slice(&key_len_buf);
-            entry_data.extend_from_slice(&key_buf);
-            entry_data.extend_from_slice(&value_len_buf);
-            entry_data.extend_from_slice(&value);
+    /// Give each of the output's data blocks its own Bloom filter at
+    /// `false_positive_rate`
+    pub fn with_block_filters(mut self, false_positive_rate: f64) -> Self {
+        self.block_filters = Some(false_positive_rate);
+        self
+    }
 
-            let calculated_checksum = calculate_checksum(&entry_data);
+    /// Index the output by key hash, for files that only serve point
+    /// lookups. Takes precedence over a partitioned index.
+    pub fn with_hash_index(mut self, hash_index: bool) -> Self {
+        self.hash_index = hash_index;
+        self
+    }
 
-            if calculated_checksum != stored_checksum {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "SSTable data block checksum verification failed",
-                ));
-            }
+    /// Close the output's data blocks at `block_size` bytes
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = Some(block_size);
+        self
+    }
 
-            if current_key == key {
-                // Found the key, return the value
-                return Ok(Some(value));
-            }
-            // No need to skip past the value, we've already read it
-        }
+    /// Partition the output's index and filter every `index_block_size`
+    /// bytes of block handles
+    pub fn with_index_block_size(mut self, index_block_size: usize) -> Self {
+        self.index_block_size = Some(index_block_size);
+        self
+    }
 
-        Ok(None)
+    /// Record a restart point every `restart_interval` entries of each
+    /// output block
+    pub fn with_restart_interval(mut self, restart_interval: usize) -> Self {
+        self.restart_interval = Some(restart_interval);
+        self
     }
 
-    /// Get the number of entries in the SSTable
-    pub fn entry_count(&self) -> u64 {
-        self.entry_count
+    /// Store the output's keys prefix-compressed against the keys at its
+    /// restart points, recorded every `DEFAULT_PREFIX_RESTART_INTERVAL`
+    /// entries unless `with_restart_interval` says otherwise
+    pub fn with_key_prefix_compression(mut self, key_prefix_compression: bool) -> Self {
+        self.key_prefix_compression = key_prefix_compression;
+        self
     }
 
-    /// Check if the SSTable has a Bloom filter
-    pub fn has_bloom_filter(&self) -> bool {
-        self.has_bloom_filter
+    /// Checksum the output's entries and meta sections with `kind`
+    pub fn with_checksum_kind(mut self, kind: ChecksumKind) -> Self {
+        self.checksum_kind = kind;
+        self
     }
 
-    /// Load block checksums from the file
-    #[allow(dead_code)] // Will be used in future data integrity features
-    fn load_block_checksums(&mut self, file_size: u64) -> io::Result<()> {
-        // If this is an older version file, no checksums to load
-        if VERSION <= 2 {
-            return Ok(());
+    /// Read time from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Record `level` as the level the output is written for
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Stamp the output with a user property
+    pub fn with_user_property(mut self, name: &str, value: &str) -> Self {
+        self.user_properties
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+/// Bloom filter being assembled for a compaction output
+enum CompactionFilter {
+    Standard(BloomFilter<String>),
+    Partitioned(PartitionedBloomFilter<String>),
+}
+
+impl CompactionFilter {
+    /// A fresh filter sized for the output, filled as entries are merged
+    fn empty(options: &CompactionOptions, expected_entries: usize) -> Self {
+        if options.use_partitioned_bloom {
+            CompactionFilter::Partitioned(PartitionedBloomFilter::new(
+                expected_entries,
+                options.false_positive_rate,
+                num_cpus::get(),
+            ))
+        } else {
+            CompactionFilter::Standard(BloomFilter::new(
+                expected_entries,
+                options.false_positive_rate,
+            ))
         }
+    }
 
-        // Safety check: Don't try to read if entry count is suspicious
-        const MAX_REASONABLE_ENTRIES: u64 = 1_000_000; // Limit to prevent huge allocations
-        if self.entry_count > MAX_REASONABLE_ENTRIES {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Unreasonable entry count: {}", self.entry_count),
-            ));
+    /// Union of the inputs' filters, if every input has a compatible filter of
+    /// the requested kind and the union stays within the target false positive rate.
+    ///
+    /// The output holds exactly the union of the input keys, so the merged
+    /// filter answers the same as one rebuilt from scratch.
+    fn merge_inputs(
+        readers: &[SSTableReader],
+        options: &CompactionOptions,
+        total_entries: usize,
+    ) -> Option<Self> {
+        let (first, rest) = readers.split_first()?;
+
+        // Inputs must hold exactly the prefixes the output should
+        let prefix_name = options.prefix_extractor.as_ref().map(|e| e.name());
+        if readers
+            .iter()
+            .any(|reader| reader.prefix_extractor_name() != prefix_name.as_deref())
+        {
+            return None;
         }
 
-        // Calculate where checksums are stored (after data and bloom filter)
-        // Use checked arithmetic to prevent integer overflow
-        let checksums_start = if self.has_bloom_filter {
-            match self
-                .index_offset
-                .checked_add(self.entry_count.checked_mul(4).ok_or_else(|| {
-                    io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "Integer overflow calculating checksum location",
-                    )
-                })?) {
-                Some(start) => start,
-                None => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "Integer overflow calculating checksum location",
-                    ));
-                }
+        if options.use_partitioned_bloom {
+            let mut merged = first.partitioned_bloom_filter()?.clone();
+            for reader in rest {
+                merged.merge(reader.partitioned_bloom_filter()?).ok()?;
             }
+            (merged.false_positive_rate(total_entries) <= options.false_positive_rate)
+                .then_some(CompactionFilter::Partitioned(merged))
         } else {
-            self.index_offset
-        };
-
-        // Only try to read checksums if the file is large enough
-        // Use checked arithmetic to prevent overflow
-        match checksums_start.checked_add(self.entry_count.checked_mul(4).ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Integer overflow calculating checksum size",
-            )
-        })?) {
-            Some(end_pos) if end_pos > file_size => return Ok(()),
-            None => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Integer overflow calculating checksum size",
-                ));
+            let mut merged = first.bloom_filter()?.clone();
+            for reader in rest {
+                merged.merge(reader.bloom_filter()?).ok()?;
             }
-            _ => {}
-        };
+            (merged.false_positive_rate(total_entries) <= options.false_positive_rate)
+                .then_some(CompactionFilter::Standard(merged))
+        }
+    }
 
-        // Check that entry_count can be safely converted to usize
-        if self.entry_count > usize::MAX as u64 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!(
-                    "Entry count too large for architecture: {}",
-                    self.entry_count
-                ),
-            ));
+    fn insert(&mut self, item: &String) {
+        match self {
+            CompactionFilter::Standard(filter) => filter.insert(item),
+            CompactionFilter::Partitioned(filter) => filter.insert(item),
         }
+    }
 
-        // Seek to the checksums section
-        self.file.seek(SeekFrom::Start(checksums_start))?;
+    /// Insert a key and, if the options carry an extractor, its prefix
+    fn insert_key(&mut self, key: &String, options: &CompactionOptions) {
+        self.insert(key);
+        if let Some(prefix) = options
+            .prefix_extractor
+            .as_ref()
+            .and_then(|extractor| extractor.prefix_of(key))
+        {
+            self.insert(&prefix.to_string());
+        }
+    }
 
-        // Read checksums (4 bytes each)
-        let capacity = self.entry_count as usize;
-        self.block_checksums = Vec::with_capacity(capacity);
-        for _ in 0..self.entry_count {
-            let mut checksum_buf = [0u8; 4];
-            self.file.read_exact(&mut checksum_buf)?;
-            self.block_checksums.push(u32::from_le_bytes(checksum_buf));
+    fn install(self, writer: &mut SSTableWriter) {
+        match self {
+            CompactionFilter::Standard(filter) => writer.set_bloom_filter(filter),
+            CompactionFilter::Partitioned(filter) => writer.set_partitioned_bloom_filter(filter),
         }
+    }
+}
+
+/// One input of a streaming merge and the entry it is positioned on
+struct MergeSource {
+    entries: SSTableEntries,
+    current: Option<(String, Vec<u8>)>,
+}
 
+impl MergeSource {
+    /// Move to the next entry, checking that keys keep ascending
+    fn advance(&mut self) -> io::Result<()> {
+        let next = self.entries.next().transpose()?;
+        if let (Some((previous, _)), Some((key, _))) = (&self.current, &next)
+            && key <= previous
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("SSTable keys out of order: {:?} after {:?}", key, previous),
+            ));
+        }
+        self.current = next;
         Ok(())
     }
 }
@@ -920,6 +3629,35 @@ impl SSTableReader {
 pub struct SSTableCompaction;
 
 impl SSTableCompaction {
+    /// Estimates the number of live keys across `sstables` from their entry
+    /// and tombstone counts alone.
+    ///
+    /// Each tombstone is taken to hide one entry of an older file, rather
+    /// than treating every entry as live; keys overwritten in newer files
+    /// and keys hidden by range deletions are not subtracted, since the
+    /// counts say nothing about them.
+    pub fn estimate_num_keys(sstables: &[SSTableInfo]) -> u64 {
+        let entries: u64 = sstables.iter().map(|info| info.entry_count).sum();
+        let tombstones: u64 = sstables.iter().map(|info| info.tombstone_count).sum();
+        entries.saturating_sub(tombstones)
+    }
+
+    /// Estimates the fraction of the records in `group`, entries and
+    /// tombstones alike, that are not live keys and so are worth compacting
+    /// away: 0.0 when every entry is live, approaching 1.0 as deletions
+    /// shadow more of the data
+    pub fn compaction_benefit(sstables: &[SSTableInfo], group: &[usize]) -> f64 {
+        let members: Vec<SSTableInfo> = group.iter().map(|&i| sstables[i].clone()).collect();
+        let records: u64 = members
+            .iter()
+            .map(|info| info.entry_count + info.tombstone_count)
+            .sum();
+        if records == 0 {
+            return 0.0;
+        }
+        1.0 - Self::estimate_num_keys(&members) as f64 / records as f64
+    }
+
     /// Identifies groups of SSTables that should be compacted together based on similar size
     pub fn identify_compaction_groups(
         sstables: &[SSTableInfo],
@@ -964,6 +3702,133 @@ impl SSTableCompaction {
         compaction_groups
     }
 
+    /// Identifies SSTables whose deletion ratio exceeds the threshold, most
+    /// deletion-heavy first
+    pub fn identify_deletion_heavy(
+        sstables: &[SSTableInfo],
+        deletion_ratio_threshold: f64,
+    ) -> Vec<usize> {
+        let mut heavy: Vec<usize> = (0..sstables.len())
+            .filter(|&i| sstables[i].deletion_ratio() > deletion_ratio_threshold)
+            .collect();
+        heavy.sort_by(|&a, &b| {
+            sstables[b]
+                .deletion_ratio()
+                .total_cmp(&sstables[a].deletion_ratio())
+        });
+        heavy
+    }
+
+    /// Identifies groups of SSTables with overlapping key ranges, hottest
+    /// first.
+    ///
+    /// `hotness[i]` is how often reads hit `sstables[i]`, e.g. sampled reads.
+    /// Files chain into one group when their ranges overlap, since a read in
+    /// the shared range may probe each of them. Groups are ordered by their
+    /// total hotness, list files from oldest to newest, and are left out if
+    /// smaller than `min_group_size`.
+    pub fn identify_hot_compaction_groups(
+        sstables: &[SSTableInfo],
+        hotness: &[u64],
+        min_group_size: usize,
+    ) -> Vec<Vec<usize>> {
+        let mut by_min: Vec<usize> = (0..sstables.len())
+            .filter(|&i| sstables[i].min_key.is_some() && sstables[i].max_key.is_some())
+            .collect();
+        by_min.sort_by(|&a, &b| sstables[a].min_key.cmp(&sstables[b].min_key));
+
+        // Sweep in key order, extending the group while ranges keep overlapping
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_max: Option<&String> = None;
+        for i in by_min {
+            let (min, max) = (
+                sstables[i].min_key.as_ref().unwrap(),
+                sstables[i].max_key.as_ref().unwrap(),
+            );
+            match (groups.last_mut(), group_max) {
+                (Some(group), Some(current_max)) if min <= current_max => {
+                    group.push(i);
+                    group_max = Some(current_max.max(max));
+                }
+                _ => {
+                    groups.push(vec![i]);
+                    group_max = Some(max);
+                }
+            }
+        }
+
+        let heat = |group: &Vec<usize>| -> u64 {
+            group
+                .iter()
+                .map(|&i| hotness.get(i).copied().unwrap_or(0))
+                .sum()
+        };
+        groups.retain(|group| group.len() >= min_group_size.max(1));
+        for group in &mut groups {
+            group.sort_by(|&a, &b| {
+                let age = |i: usize| {
+                    let info = &sstables[i];
+                    (info.created_at_secs, info.file_number, &info.path)
+                };
+                age(a).cmp(&age(b))
+            });
+        }
+        groups.sort_by(|a, b| heat(b).cmp(&heat(a)).then_with(|| b.len().cmp(&a.len())));
+        groups
+    }
+
+    /// Identifies compaction groups like `identify_compaction_groups`, but
+    /// puts deletion-heavy SSTables first.
+    ///
+    /// Each file whose deletion ratio exceeds `deletion_ratio_threshold` leads
+    /// a group together with every older file whose key range overlaps it,
+    /// since those hold the values its tombstones hide. Groups list files
+    /// from oldest to newest, as compaction expects. The remaining files are
+    /// grouped by size, and no file appears in two groups.
+    pub fn identify_compaction_groups_with_deletions(
+        sstables: &[SSTableInfo],
+        size_ratio_threshold: f64,
+        min_group_size: usize,
+        deletion_ratio_threshold: f64,
+    ) -> Vec<Vec<usize>> {
+        let age = |i: usize| {
+            let info = &sstables[i];
+            (info.created_at_secs, info.file_number, &info.path)
+        };
+        let mut taken = vec![false; sstables.len()];
+        let mut groups = Vec::new();
+
+        for heavy in Self::identify_deletion_heavy(sstables, deletion_ratio_threshold) {
+            if taken[heavy] {
+                continue;
+            }
+            let mut group: Vec<usize> = (0..sstables.len())
+                .filter(|&i| {
+                    i == heavy
+                        || (!taken[i]
+                            && age(i) < age(heavy)
+                            && sstables[i].overlaps(&sstables[heavy]))
+                })
+                .collect();
+            group.sort_by(|&a, &b| age(a).cmp(&age(b)));
+            for &i in &group {
+                taken[i] = true;
+            }
+            groups.push(group);
+        }
+
+        let remaining: Vec<usize> = (0..sstables.len()).filter(|&i| !taken[i]).collect();
+        let remaining_infos: Vec<SSTableInfo> =
+            remaining.iter().map(|&i| sstables[i].clone()).collect();
+        for group in
+            Self::identify_compaction_groups(&remaining_infos, size_ratio_threshold, min_group_size)
+        {
+            groups.push(group.into_iter().map(|i| remaining[i]).collect());
+        }
+
+        groups
+    }
+
     /// Compacts multiple SSTables into a single one, with a Bloom filter
     pub fn compact_sstables(
         sstable_paths: &[String],
@@ -972,64 +3837,293 @@ impl SSTableCompaction {
         use_bloom_filter: bool,
         false_positive_rate: f64,
     ) -> io::Result<String> {
-        // First count total entries
-        let mut total_entries = 0;
-        for path in sstable_paths {
-            let reader = SSTableReader::open(path)?;
-            total_entries += reader.entry_count();
-        }
-
-        // Create a new SSTable writer with a Bloom filter
-        let mut writer = SSTableWriter::new(
-            output_path,
-            total_entries as usize,
+        let options = CompactionOptions {
             use_bloom_filter,
             false_positive_rate,
-        )?;
+            delete_originals,
+            ..CompactionOptions::default()
+        };
+        Self::compact_sstables_with_options(sstable_paths, output_path, &options)
+    }
+
+    /// Compacts multiple SSTables into a single one.
+    ///
+    /// When a key appears in several inputs, the entry with the highest
+    /// sequence wins; if either of two entries lacks a sequence, the one from
+    /// the input listed later wins. Kept entries keep their sequences.
+    /// Inputs whose keys are recorded as sorted, or that have a
+    /// key index without repeated keys, are merged in a single streaming
+    /// pass; any other input forces the whole merge to be buffered in
+    /// memory. If every input already has a compatible Bloom filter
+    /// of the requested kind, the output's filter is the union of those filters,
+    /// otherwise it is built as entries are merged.
+    ///
+    /// If the compaction fails, or `debug_dump` is set, a JSON report of the
+    /// inputs and the decisions made for their keys is written to
+    /// `compaction_report::report_path(output_path)`.
+    pub fn compact_sstables_with_options(
+        sstable_paths: &[String],
+        output_path: &str,
+        options: &CompactionOptions,
+    ) -> io::Result<String> {
+        let mut trace =
+            CompactionTrace::new(sstable_paths, output_path, options, options.clock.now_ms());
+        let result = Self::compact_traced(sstable_paths, output_path, options, &mut trace);
+
+        if (result.is_err() || options.debug_dump)
+            && let Err(e) = trace.write(&result, options.clock.now_ms())
+        {
+            warn!(
+                "Failed to write compaction report for {}: {}",
+                output_path, e
+            );
+        }
+        result
+    }
 
-        // Map to accumulate all key-value pairs
-        let mut map = BTreeMap::new();
+    /// Run a compaction, recording what it does in `trace`
+    fn compact_traced(
+        sstable_paths: &[String],
+        output_path: &str,
+        options: &CompactionOptions,
+        trace: &mut CompactionTrace,
+    ) -> io::Result<String> {
+        let mut readers = Vec::with_capacity(sstable_paths.len());
+        for (input, path) in sstable_paths.iter().enumerate() {
+            let reader = SSTableReader::open_with(path, None, options.key_provider.as_deref())?;
+            trace.opened(input, &reader);
+            readers.push(reader);
+        }
+        let total_entries: usize = readers.iter().map(|r| r.entry_count() as usize).sum();
+        let write_times: Vec<HashMap<String, u64>> = readers
+            .iter_mut()
+            .map(|r| std::mem::take(&mut r.write_times))
+            .collect();
+        let expiries: Vec<HashMap<String, u64>> = readers
+            .iter_mut()
+            .map(|r| std::mem::take(&mut r.expiries))
+            .collect();
+        let global_sequences: Vec<Option<u64>> =
+            readers.iter().map(SSTableReader::global_sequence).collect();
+        let sequences: Vec<HashMap<String, u64>> = readers
+            .iter_mut()
+            .map(|r| std::mem::take(&mut r.sequences))
+            .collect();
+        let sequence_of = |input: usize, key: &str| {
+            sequences[input]
+                .get(key)
+                .copied()
+                .or(global_sequences[input])
+        };
+        let value_log_segments: Vec<Arc<HashMap<String, u64>>> = readers
+            .iter_mut()
+            .map(|r| std::mem::take(&mut r.value_log_segments))
+            .collect();
+        let tombstones: Vec<HashMap<String, Tombstone>> = readers
+            .iter_mut()
+            .map(|r| std::mem::take(&mut r.tombstones))
+            .collect();
+        let range_tombstones: Vec<FragmentedRangeTombstones> = readers
+            .iter_mut()
+            .map(|r| std::mem::take(&mut r.range_tombstones))
+            .collect();
+        // Input each surviving entry came from, for keys that may also have a tombstone
+        let mut written_from: HashMap<String, usize> = HashMap::new();
+        let now_ms = options.clock.now_ms();
+        let retention_ms = options
+            .tombstone_retention
+            .map(|retention| retention.as_millis() as u64);
+        // Tombstones past retention can go unless an open snapshot predates them
+        let droppable = |deleted_at_ms: u64| {
+            retention_ms
+                .is_some_and(|retention_ms| deleted_at_ms.saturating_add(retention_ms) <= now_ms)
+                && options
+                    .oldest_snapshot
+                    .is_none_or(|oldest| deleted_at_ms < oldest)
+        };
 
-        // Read all SSTables and merge them
-        for path in sstable_paths {
-            let mut reader = SSTableReader::open(path)?;
-            reader.file.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
-
-            for _ in 0..reader.entry_count() {
-                // Read key length
-                let mut key_len_buf = [0u8; 4];
-                reader.file.read_exact(&mut key_len_buf)?;
-                let key_len = u32::from_le_bytes(key_len_buf) as usize;
-
-                // Read key
-                let mut key_buf = vec![0u8; key_len];
-                reader.file.read_exact(&mut key_buf)?;
-                let key = String::from_utf8_lossy(&key_buf).to_string();
-
-                // Read value length
-                let mut value_len_buf = [0u8; 4];
-                reader.file.read_exact(&mut value_len_buf)?;
-                let value_len = u32::from_le_bytes(value_len_buf) as usize;
-
-                // Read value
-                let mut value = vec![0u8; value_len];
-                reader.file.read_exact(&mut value)?;
-
-                // Store in map, overwriting any previous value for this key
-                map.insert(key, value);
+        // The output's own filter is installed just before finalize
+        let mut writer = SSTableWriter::new(output_path, total_entries, false, 0.0)?;
+        writer.set_checksum_kind(options.checksum_kind)?;
+        if let Some(extractor) = &options.prefix_extractor {
+            writer.set_prefix_extractor(extractor.clone());
+        }
+        let dictionary = match &options.compression {
+            Compression::Zstd(zstd) if zstd.trains_dictionary() => {
+                let samples = Self::sample_values(
+                    sstable_paths,
+                    zstd.max_sample_bytes,
+                    options.key_provider.as_deref(),
+                )?;
+                zstd.train_dictionary(samples.iter().map(Vec::as_slice))
+            }
+            _ => None,
+        };
+        writer.set_compression(options.compression, dictionary)?;
+        writer.set_compression_type(options.block_compression)?;
+        if let Some(provider) = &options.key_provider {
+            writer.set_encryption(provider.as_ref())?;
+        }
+        if let Some(false_positive_rate) = options.block_filters {
+            writer.set_block_filters(false_positive_rate)?;
+        }
+        if let Some(block_size) = options.block_size {
+            writer.set_block_size(block_size)?;
+        }
+        if let Some(restart_interval) = options.restart_interval {
+            writer.set_restart_interval(restart_interval)?;
+        }
+        if options.key_prefix_compression {
+            writer.set_key_prefix_compression()?;
+        }
+
+        // The output reflects whatever WAL records its inputs did, and
+        // holds data as new as the newest of them
+        if let Some(lsn) = readers.iter().filter_map(SSTableReader::applied_lsn).max() {
+            writer.set_applied_lsn(lsn);
+        }
+        if let Some(flush_number) = readers.iter().filter_map(SSTableReader::flush_number).max() {
+            writer.set_flush_number(flush_number);
+        }
+        writer.set_created_at(options.clock.now_secs());
+        if let Some(level) = options.level {
+            writer.set_level(level);
+        }
+
+        // User properties carry over from the inputs, the newest flush's
+        // winning, unless the options stamp their own
+        let mut by_age: Vec<&SSTableReader> = readers.iter().collect();
+        by_age.sort_by_key(|reader| reader.flush_number());
+        let mut user_properties = BTreeMap::new();
+        for reader in by_age {
+            user_properties.extend(reader.properties().user_properties());
+        }
+        for (name, value) in &options.user_properties {
+            user_properties.insert(name, value);
+        }
+        for (name, value) in user_properties {
+            writer.set_user_property(name, value)?;
+        }
+
+        // Merged output is sorted, so a partitioned index always applies and
+        // replaces the whole-file filter
+        writer.set_strict_key_order()?;
+        let partitioned = options.use_bloom_filter
+            && (options.partitioned_index.is_some() || options.index_block_size.is_some())
+            && !options.hash_index;
+        if options.hash_index {
+            writer.set_hash_index()?;
+        }
+        if partitioned {
+            match (options.index_block_size, options.partitioned_index) {
+                (Some(index_block_size), _) => {
+                    writer.set_index_block_size(index_block_size, options.false_positive_rate)?
+                }
+                (None, Some(blocks_per_partition)) => writer
+                    .set_partitioned_index(blocks_per_partition, options.false_positive_rate)?,
+                (None, None) => {}
+            }
+        }
+
+        // The flag records whether merged keys still need inserting into the filter
+        let mut filter = if options.use_bloom_filter && !partitioned {
+            match CompactionFilter::merge_inputs(&readers, options, total_entries) {
+                Some(merged) => Some((merged, false)),
+                None => Some((CompactionFilter::empty(options, total_entries), true)),
             }
+        } else {
+            None
+        };
+        let sorted = readers
+            .iter()
+            .all(SSTableReader::iterates_strictly_in_key_order);
+        trace.planned(
+            if sorted { "sorted" } else { "buffered" },
+            filter.as_ref().map(|(_, building)| !building),
+        );
+
+        // Entries keep the write and expiry times and the sequence recorded by
+        // the input they came from
+        let mut write =
+            |input: usize, key: &String, value: &[u8], shadowed: &[usize]| -> io::Result<()> {
+                trace.saw(input, key);
+                for &older in shadowed {
+                    trace.saw(older, key);
+                    trace.decide(key, older, Decision::Shadowed);
+                }
+
+                // A tombstone in a newer input hides the entry
+                let deleted_later = tombstones[input + 1..]
+                    .iter()
+                    .any(|later| later.contains_key(key))
+                    || range_tombstones[input + 1..]
+                        .iter()
+                        .any(|later| later.covers(key));
+                if deleted_later {
+                    trace.decide(key, input, Decision::DeletedLater);
+                    return Ok(());
+                }
+
+                // Expired entries become tombstones so they keep hiding older values
+                let expires_at_ms = expiries[input].get(key).copied();
+                if let Some(expires_at_ms) = expires_at_ms
+                    && expires_at_ms <= now_ms
+                {
+                    trace.decide(key, input, Decision::Expired);
+                    written_from.insert(key.clone(), input);
+                    let tombstone = Tombstone {
+                        deleted_at_ms: expires_at_ms,
+                        value: None,
+                    };
+                    if !droppable(tombstone.deleted_at_ms) {
+                        writer.write_tombstone(key, tombstone);
+                    }
+                    return Ok(());
+                }
+                if tombstones.iter().any(|t| t.contains_key(key)) {
+                    written_from.insert(key.clone(), input);
+                }
+
+                let written_at_ms = write_times[input].get(key).copied();
+                writer.write_put(
+                    key,
+                    value,
+                    written_at_ms,
+                    expires_at_ms,
+                    sequence_of(input, key),
+                )?;
+                // Pointers into the value log are carried as they are
+                if let Some(&segment) = value_log_segments[input].get(key) {
+                    writer.mark_value_pointer(key, segment);
+                }
+                trace.decide(key, input, Decision::Kept);
+                if let Some((filter, true)) = &mut filter {
+                    filter.insert_key(key, options);
+                }
+                Ok(())
+            };
+
+        if sorted {
+            Self::merge_sorted(readers, &sequence_of, &mut write)?;
+        } else {
+            Self::merge_buffered(readers, &sequence_of, &mut write)?;
         }
 
-        // Write all entries to the new SSTable
-        for (key, value) in map {
-            writer.write_entry(&key, &value)?;
+        Self::carry_tombstones(&mut writer, &tombstones, &written_from, &droppable, trace);
+        // Range tombstones keep hiding keys in files older than the inputs
+        for fragment in range_tombstones.iter().flat_map(|r| r.fragments()) {
+            if !droppable(fragment.deleted_at_ms) {
+                writer.write_range_tombstone(fragment.clone());
+            }
         }
 
-        // Finalize the SSTable
+        if let Some((filter, _)) = filter {
+            filter.install(&mut writer);
+        }
         writer.finalize()?;
 
         // Delete original files if requested
-        if delete_originals {
+        if options.delete_originals {
             for path in sstable_paths {
                 fs::remove_file(path)?;
             }
@@ -1037,6 +4131,160 @@ impl SSTableCompaction {
 
         Ok(output_path.to_string())
     }
+
+    /// Read values from the inputs for dictionary training, taking an equal
+    /// share of `max_bytes` from each so every input is represented
+    fn sample_values(
+        sstable_paths: &[String],
+        max_bytes: usize,
+        key_provider: Option<&dyn KeyProvider>,
+    ) -> io::Result<Vec<Vec<u8>>> {
+        let share = max_bytes / sstable_paths.len().max(1);
+        let mut samples = Vec::new();
+        for path in sstable_paths {
+            let mut taken = 0;
+            for entry in SSTableReader::open_with(path, None, key_provider)?.into_entries()? {
+                if taken >= share {
+                    break;
+                }
+                let (_, value) = entry?;
+                taken += value.len();
+                samples.push(value);
+            }
+        }
+        Ok(samples)
+    }
+
+    /// Write the newest tombstone for each key that no newer input rewrote,
+    /// unless `droppable` says it is no longer needed
+    fn carry_tombstones(
+        writer: &mut SSTableWriter,
+        tombstones: &[HashMap<String, Tombstone>],
+        written_from: &HashMap<String, usize>,
+        droppable: &impl Fn(u64) -> bool,
+        trace: &mut CompactionTrace,
+    ) {
+        let mut newest: BTreeMap<&String, (usize, &Tombstone)> = BTreeMap::new();
+        for (input, input_tombstones) in tombstones.iter().enumerate() {
+            for (key, tombstone) in input_tombstones {
+                newest.insert(key, (input, tombstone));
+            }
+        }
+
+        for (key, (input, tombstone)) in newest {
+            if written_from.get(key).is_some_and(|&from| from > input) {
+                continue;
+            }
+            if droppable(tombstone.deleted_at_ms) {
+                trace.decide(key, input, Decision::TombstoneDropped);
+                continue;
+            }
+            trace.decide(key, input, Decision::TombstoneCarried);
+            writer.write_tombstone(key, tombstone.clone());
+        }
+    }
+
+    /// Of the inputs holding `key`, in ascending order, the one whose entry
+    /// wins: a later input wins unless both entries have sequences and the
+    /// earlier one's is higher
+    pub(super) fn newest_input(
+        inputs: &[usize],
+        key: &str,
+        sequence_of: &impl Fn(usize, &str) -> Option<u64>,
+    ) -> usize {
+        inputs
+            .iter()
+            .copied()
+            .reduce(|newest, input| {
+                match (sequence_of(newest, key), sequence_of(input, key)) {
+                    (Some(newest_sequence), Some(sequence)) if newest_sequence > sequence => newest,
+                    _ => input,
+                }
+            })
+            .expect("a merged key comes from at least one input")
+    }
+
+    /// K-way merge of sorted inputs, holding one entry per input in memory
+    fn merge_sorted(
+        readers: Vec<SSTableReader>,
+        sequence_of: &impl Fn(usize, &str) -> Option<u64>,
+        write: &mut impl FnMut(usize, &String, &[u8], &[usize]) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut sources = Vec::with_capacity(readers.len());
+        let mut heap = BinaryHeap::new();
+        for (i, reader) in readers.into_iter().enumerate() {
+            let mut source = MergeSource {
+                entries: reader.iter()?,
+                current: None,
+            };
+            source.advance()?;
+            if let Some((key, _)) = &source.current {
+                heap.push(Reverse((key.clone(), i)));
+            }
+            sources.push(source);
+        }
+
+        while let Some(Reverse((key, first))) = heap.pop() {
+            // Collect every input positioned on this key; the heap yields
+            // them in ascending order
+            let mut inputs = vec![first];
+            while let Some(Reverse((next_key, i))) = heap.peek() {
+                if *next_key != key {
+                    break;
+                }
+                inputs.push(*i);
+                heap.pop();
+            }
+
+            let winner = Self::newest_input(&inputs, &key, sequence_of);
+            let shadowed: Vec<usize> = inputs.iter().copied().filter(|&i| i != winner).collect();
+            if let Some((_, value)) = &sources[winner].current {
+                write(winner, &key, value, &shadowed)?;
+            }
+
+            for i in inputs {
+                let source = &mut sources[i];
+                source.advance()?;
+                if let Some((next_key, _)) = &source.current {
+                    heap.push(Reverse((next_key.clone(), i)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Merge inputs that are not known to be sorted by buffering every entry
+    fn merge_buffered(
+        readers: Vec<SSTableReader>,
+        sequence_of: &impl Fn(usize, &str) -> Option<u64>,
+        write: &mut impl FnMut(usize, &String, &[u8], &[usize]) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut map: BTreeMap<String, (usize, Vec<u8>, Vec<usize>)> = BTreeMap::new();
+        for (i, reader) in readers.into_iter().enumerate() {
+            for entry in reader.into_entries()? {
+                let (key, value) = entry?;
+                let Some((newest, newest_value, mut shadowed)) = map.remove(&key) else {
+                    map.insert(key, (i, value, Vec::new()));
+                    continue;
+                };
+                let winner = if Self::newest_input(&[newest, i], &key, sequence_of) == i {
+                    shadowed.push(newest);
+                    (i, value, shadowed)
+                } else {
+                    shadowed.push(i);
+                    (newest, newest_value, shadowed)
+                };
+                map.insert(key, winner);
+            }
+        }
+
+        for (key, (input, value, shadowed)) in map {
+            write(input, &key, &value, &shadowed)?;
+        }
+
+        Ok(())
+    }
 }
 
 // Tests moved to tests/sstable_checksum_test.rs
@@ -0,0 +1,116 @@
+use std::fmt::Debug;
+
+/// Maps keys to the prefix used by prefix Bloom filters and prefix iteration.
+///
+/// The extractor's name is stored in the properties of every SSTable whose
+/// filter holds prefixes, so files written with a different extractor are
+/// never consulted with the wrong prefixes.
+pub trait PrefixExtractor: Debug + Send + Sync {
+    /// Stable name identifying the extractor and its parameters
+    fn name(&self) -> String;
+
+    /// Returns true if the key has a prefix under this extractor
+    fn in_domain(&self, key: &str) -> bool;
+
+    /// The prefix of a key; only called on keys that are in the domain
+    fn transform<'a>(&self, key: &'a str) -> &'a str;
+
+    /// The prefix of a key, if it has one
+    fn prefix_of<'a>(&self, key: &'a str) -> Option<&'a str> {
+        if self.in_domain(key) {
+            Some(self.transform(key))
+        } else {
+            None
+        }
+    }
+}
+
+/// Uses the first `len` bytes of the key as its prefix. Shorter keys, and
+/// keys where `len` falls inside a multi-byte character, have no prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedPrefixExtractor {
+    /// Prefix length in bytes
+    pub len: usize,
+}
+
+impl FixedPrefixExtractor {
+    /// Create an extractor for prefixes of `len` bytes
+    pub fn new(len: usize) -> Self {
+        FixedPrefixExtractor { len }
+    }
+}
+
+impl PrefixExtractor for FixedPrefixExtractor {
+    fn name(&self) -> String {
+        format!("lsmer.fixed:{}", self.len)
+    }
+
+    fn in_domain(&self, key: &str) -> bool {
+        key.len() >= self.len && key.is_char_boundary(self.len)
+    }
+
+    fn transform<'a>(&self, key: &'a str) -> &'a str {
+        &key[..self.len]
+    }
+}
+
+/// Uses everything up to and including the first occurrence of a delimiter as
+/// the prefix, so `user:1` and `username` do not share one. Keys without the
+/// delimiter have no prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelimiterPrefixExtractor {
+    /// Delimiter ending the prefix
+    pub delimiter: String,
+}
+
+impl DelimiterPrefixExtractor {
+    /// Create an extractor splitting on `delimiter`
+    pub fn new(delimiter: &str) -> Self {
+        DelimiterPrefixExtractor {
+            delimiter: delimiter.to_string(),
+        }
+    }
+}
+
+impl PrefixExtractor for DelimiterPrefixExtractor {
+    fn name(&self) -> String {
+        format!("lsmer.delimiter:{}", self.delimiter)
+    }
+
+    fn in_domain(&self, key: &str) -> bool {
+        !self.delimiter.is_empty() && key.contains(&self.delimiter)
+    }
+
+    fn transform<'a>(&self, key: &'a str) -> &'a str {
+        let end = key
+            .find(&self.delimiter)
+            .map_or(key.len(), |position| position + self.delimiter.len());
+        &key[..end]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_prefix() {
+        let extractor = FixedPrefixExtractor::new(4);
+        assert_eq!(extractor.prefix_of("user42"), Some("user"));
+        assert_eq!(extractor.prefix_of("abcd"), Some("abcd"));
+        assert_eq!(extractor.prefix_of("abc"), None);
+        // A 4-byte cut would split the two-byte 'é'
+        assert_eq!(extractor.prefix_of("abcé"), None);
+        assert_eq!(extractor.name(), "lsmer.fixed:4");
+    }
+
+    #[test]
+    fn test_delimiter_prefix() {
+        let extractor = DelimiterPrefixExtractor::new(":");
+        assert_eq!(extractor.prefix_of("user:42:name"), Some("user:"));
+        assert_eq!(extractor.prefix_of(":x"), Some(":"));
+        assert_eq!(extractor.prefix_of("username"), None);
+        assert_eq!(DelimiterPrefixExtractor::new("").prefix_of("a:b"), None);
+        assert_eq!(extractor.name(), "lsmer.delimiter::");
+    }
+}
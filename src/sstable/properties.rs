@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::io;
+
+/// Property holding the Merkle root of the entries in the file, as hex
+pub const PROP_CONTENT_DIGEST: &str = "lsmer.content_digest";
+/// Property holding the number of entries in the file
+pub const PROP_NUM_ENTRIES: &str = "lsmer.num_entries";
+/// Property recording whether keys were written in strictly ascending order
+pub const PROP_KEYS_SORTED: &str = "lsmer.keys_sorted";
+/// Property naming the prefix extractor whose prefixes the Bloom filter holds
+pub const PROP_PREFIX_EXTRACTOR: &str = "lsmer.prefix_extractor";
+/// Property holding the earliest entry expiry time in the file, in
+/// milliseconds since the Unix epoch; absent if no entry expires
+pub const PROP_MIN_EXPIRY: &str = "lsmer.min_expiry_ms";
+/// Property holding the latest entry expiry time in the file
+pub const PROP_MAX_EXPIRY: &str = "lsmer.max_expiry_ms";
+/// Property naming the codec values are compressed with; absent if uncompressed
+pub const PROP_COMPRESSION: &str = "lsmer.compression";
+/// Property recording that each value in a compressed file starts with a
+/// byte saying whether it was compressed; absent in files that compressed
+/// every value
+pub const PROP_VALUE_FLAGS: &str = "lsmer.value_flags";
+/// Property holding the number of values a compressed file stores as
+/// written because they looked incompressible or would not shrink
+pub const PROP_UNCOMPRESSED_VALUES: &str = "lsmer.uncompressed_values";
+/// Property holding the smallest key the file has an entry or tombstone
+/// for; absent if it has neither
+pub const PROP_MIN_KEY: &str = "lsmer.min_key";
+/// Property holding the largest key the file has an entry or tombstone for
+pub const PROP_MAX_KEY: &str = "lsmer.max_key";
+/// Property holding the total size of the keys and values as written
+pub const PROP_RAW_BYTES: &str = "lsmer.raw_bytes";
+/// Property holding the on-disk size of the data section
+pub const PROP_DATA_BYTES: &str = "lsmer.data_bytes";
+/// Property holding the number of tombstones in the file
+pub const PROP_NUM_TOMBSTONES: &str = "lsmer.num_tombstones";
+/// Property holding the number of fragments of deleted key ranges in the file
+pub const PROP_NUM_RANGE_DELETIONS: &str = "lsmer.num_range_deletions";
+/// Property holding the WAL LSN up to which logged writes are reflected in
+/// the file; absent if the file was not written from the WAL
+pub const PROP_APPLIED_LSN: &str = "lsmer.applied_lsn";
+/// Property holding the sequence every entry in an ingested file takes, so
+/// it orders after the data it was ingested into; absent for files the index
+/// wrote itself
+pub const PROP_GLOBAL_SEQUENCE: &str = "lsmer.global_sequence";
+/// Property holding the ID of the key the data blocks are encrypted with;
+/// absent if they are stored in the clear
+pub const PROP_ENCRYPTION_KEY_ID: &str = "lsmer.encryption_key_id";
+/// Property holding the largest sequence recorded for an entry in the file;
+/// absent if no entry has one
+pub const PROP_MAX_SEQUENCE: &str = "lsmer.max_sequence";
+/// Property holding the file number of the newest flush whose writes the
+/// file holds: its own for a flushed or ingested file, the largest of its
+/// inputs' for a compaction output. Orders files by the age of their data
+/// rather than by when they were written; absent in older files.
+pub const PROP_FLUSH_NUMBER: &str = "lsmer.flush_number";
+/// Property holding the size, in bytes, at which the writer closed data blocks
+pub const PROP_DATA_BLOCK_SIZE: &str = "lsmer.data_block_size";
+/// Property holding the size, in bytes of block handles, at which the writer
+/// closed index partitions; absent unless partitions were closed by size
+pub const PROP_INDEX_BLOCK_SIZE: &str = "lsmer.index_block_size";
+/// Property holding the number of entries between restart points; absent if
+/// none were recorded
+pub const PROP_RESTART_INTERVAL: &str = "lsmer.restart_interval";
+/// Property recording that keys are stored as the length of the prefix they
+/// share with the key at the restart point before them, followed by the rest
+/// of the key; absent if keys are stored whole
+pub const PROP_KEY_PREFIX_COMPRESSION: &str = "lsmer.key_prefix_compression";
+/// Property holding when the file was written, in seconds since the Unix
+/// epoch; absent unless the writer was told
+pub const PROP_CREATED_AT: &str = "lsmer.created_at_secs";
+/// Property holding the level the file was written for; absent for files
+/// written outside an index
+pub const PROP_LEVEL: &str = "lsmer.level";
+/// Property holding the ID of the WAL checkpoint a flush wrote the file
+/// under; absent for files not flushed from the WAL
+pub const PROP_CHECKPOINT_ID: &str = "lsmer.checkpoint_id";
+/// Prefix of the properties applications set with
+/// `SSTableWriter::set_user_property`, keeping them apart from lsmer's own
+pub const USER_PROPERTY_PREFIX: &str = "user.";
+
+/// Key/value properties stored in an SSTable's meta section.
+///
+/// Properties are plain strings so that tools can print them without knowing
+/// every key; numeric properties are stored in decimal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SSTableProperties {
+    values: BTreeMap<String, String>,
+}
+
+impl SSTableProperties {
+    /// Create an empty property set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a property, replacing any previous value
+    pub fn insert(&mut self, key: &str, value: impl ToString) {
+        self.values.insert(key.to_string(), value.to_string());
+    }
+
+    /// Get a property
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// Get a property parsed as an unsigned integer
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get(key).and_then(|value| value.parse().ok())
+    }
+
+    /// Get a property set with `SSTableWriter::set_user_property`, by its
+    /// name without `USER_PROPERTY_PREFIX`
+    pub fn user_property(&self, name: &str) -> Option<&str> {
+        self.get(&format!("{}{}", USER_PROPERTY_PREFIX, name))
+    }
+
+    /// Iterate over the user properties in name order, named without
+    /// `USER_PROPERTY_PREFIX`
+    pub fn user_properties(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix(USER_PROPERTY_PREFIX)?, value)))
+    }
+
+    /// Iterate over all properties in key order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Number of properties
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if there are no properties
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Encode as a count followed by length-prefixed key/value pairs
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&(self.values.len() as u32).to_le_bytes());
+        for (key, value) in &self.values {
+            buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            buf.extend_from_slice(key.as_bytes());
+            buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            buf.extend_from_slice(value.as_bytes());
+        }
+        buf
+    }
+
+    /// Decode properties written by `encode`
+    pub(crate) fn decode(buf: &[u8]) -> io::Result<Self> {
+        let mut cursor = buf;
+        let count = read_u32(&mut cursor)?;
+
+        let mut values = BTreeMap::new();
+        for _ in 0..count {
+            let key = read_string(&mut cursor)?;
+            let value = read_string(&mut cursor)?;
+            values.insert(key, value);
+        }
+
+        Ok(SSTableProperties { values })
+    }
+}
+
+/// Read a little-endian u32 from the front of a buffer
+fn read_u32(cursor: &mut &[u8]) -> io::Result<u32> {
+    if cursor.len() < 4 {
+        return Err(truncated());
+    }
+    let (bytes, rest) = cursor.split_at(4);
+    *cursor = rest;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Read a length-prefixed UTF-8 string from the front of a buffer
+fn read_string(cursor: &mut &[u8]) -> io::Result<String> {
+    let len = read_u32(cursor)? as usize;
+    if cursor.len() < len {
+        return Err(truncated());
+    }
+    let (bytes, rest) = cursor.split_at(len);
+    *cursor = rest;
+    String::from_utf8(bytes.to_vec()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "SSTable property is not valid UTF-8",
+        )
+    })
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Truncated SSTable properties block",
+    )
+}
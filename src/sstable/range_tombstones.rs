@@ -0,0 +1,192 @@
+use std::io;
+
+/// Record of every key from `start` up to but not including `end` being
+/// deleted at once
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeTombstone {
+    /// First key deleted
+    pub start: String,
+    /// First key past the deleted range
+    pub end: String,
+    /// When the range was deleted, in milliseconds since the Unix epoch
+    pub deleted_at_ms: u64,
+}
+
+impl RangeTombstone {
+    /// Create a tombstone deleting `start..end` at `deleted_at_ms`
+    pub fn new(start: impl Into<String>, end: impl Into<String>, deleted_at_ms: u64) -> Self {
+        RangeTombstone {
+            start: start.into(),
+            end: end.into(),
+            deleted_at_ms,
+        }
+    }
+
+    /// Whether the tombstone deletes `key`
+    pub fn covers(&self, key: &str) -> bool {
+        self.start.as_str() <= key && key < self.end.as_str()
+    }
+}
+
+/// Range tombstones split at each other's bounds into sorted,
+/// non-overlapping fragments.
+///
+/// Each fragment carries the newest deletion time of the tombstones
+/// overlapping it, so whether a key is deleted takes one binary search no
+/// matter how many tombstones were added.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FragmentedRangeTombstones {
+    fragments: Vec<RangeTombstone>,
+}
+
+impl FragmentedRangeTombstones {
+    /// Create an empty list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a tombstone, re-fragmenting the ranges it overlaps. Empty ranges
+    /// are ignored.
+    pub fn add(&mut self, tombstone: RangeTombstone) {
+        if tombstone.start >= tombstone.end {
+            return;
+        }
+
+        // Only fragments overlapping the new range change
+        let first = self
+            .fragments
+            .partition_point(|fragment| fragment.end <= tombstone.start);
+        let last = self
+            .fragments
+            .partition_point(|fragment| fragment.start < tombstone.end);
+        let overlapping: Vec<RangeTombstone> = self.fragments.drain(first..last).collect();
+
+        let mut bounds: Vec<&str> = overlapping
+            .iter()
+            .flat_map(|fragment| [fragment.start.as_str(), fragment.end.as_str()])
+            .chain([tombstone.start.as_str(), tombstone.end.as_str()])
+            .collect();
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        let mut replacement: Vec<RangeTombstone> = Vec::with_capacity(bounds.len());
+        for pair in bounds.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            let deleted_at_ms = overlapping
+                .iter()
+                .chain(std::iter::once(&tombstone))
+                .filter(|fragment| fragment.covers(start))
+                .map(|fragment| fragment.deleted_at_ms)
+                .max();
+            let Some(deleted_at_ms) = deleted_at_ms else {
+                continue;
+            };
+            // Neighbours deleted at the same time merge back into one
+            match replacement.last_mut() {
+                Some(previous)
+                    if previous.end == start && previous.deleted_at_ms == deleted_at_ms =>
+                {
+                    previous.end = end.to_string();
+                }
+                _ => replacement.push(RangeTombstone::new(start, end, deleted_at_ms)),
+            }
+        }
+
+        self.fragments.splice(first..first, replacement);
+    }
+
+    /// The fragment deleting `key`, if any
+    pub fn covering(&self, key: &str) -> Option<&RangeTombstone> {
+        let at = self
+            .fragments
+            .partition_point(|fragment| fragment.end.as_str() <= key);
+        self.fragments
+            .get(at)
+            .filter(|fragment| fragment.covers(key))
+    }
+
+    /// Whether any tombstone deletes `key`
+    pub fn covers(&self, key: &str) -> bool {
+        self.covering(key).is_some()
+    }
+
+    /// The fragments in key order
+    pub fn fragments(&self) -> &[RangeTombstone] {
+        &self.fragments
+    }
+
+    /// Number of fragments
+    pub fn len(&self) -> usize {
+        self.fragments.len()
+    }
+
+    /// Whether no key is deleted
+    pub fn is_empty(&self) -> bool {
+        self.fragments.is_empty()
+    }
+
+    /// Remove every fragment
+    pub fn clear(&mut self) {
+        self.fragments.clear();
+    }
+}
+
+/// Encode fragments as a count followed by each fragment's length-prefixed
+/// start and end keys and its deletion time
+pub(crate) fn encode(tombstones: &FragmentedRangeTombstones) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(tombstones.len() as u32).to_le_bytes());
+    for fragment in tombstones.fragments() {
+        for key in [&fragment.start, &fragment.end] {
+            buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            buf.extend_from_slice(key.as_bytes());
+        }
+        buf.extend_from_slice(&fragment.deleted_at_ms.to_le_bytes());
+    }
+    buf
+}
+
+/// Decode fragments written by `encode`. Fragments are added back one by
+/// one, so a block that is out of order or overlapping still reads
+/// correctly.
+pub(crate) fn decode(buf: &[u8]) -> io::Result<FragmentedRangeTombstones> {
+    let mut cursor = buf;
+    let count = read_u32(&mut cursor)?;
+
+    let mut tombstones = FragmentedRangeTombstones::new();
+    for _ in 0..count {
+        let start = read_key(&mut cursor)?;
+        let end = read_key(&mut cursor)?;
+        let deleted_at_ms = u64::from_le_bytes(take(&mut cursor, 8)?.try_into().unwrap());
+        tombstones.add(RangeTombstone::new(start, end, deleted_at_ms));
+    }
+
+    Ok(tombstones)
+}
+
+fn read_key(cursor: &mut &[u8]) -> io::Result<String> {
+    let len = read_u32(cursor)? as usize;
+    String::from_utf8(take(cursor, len)?.to_vec()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "SSTable range tombstone key is not valid UTF-8",
+        )
+    })
+}
+
+fn read_u32(cursor: &mut &[u8]) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(take(cursor, 4)?.try_into().unwrap()))
+}
+
+/// Split `len` bytes off the front of a buffer
+fn take<'a>(cursor: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if cursor.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Truncated SSTable range tombstones block",
+        ));
+    }
+    let (bytes, rest) = cursor.split_at(len);
+    *cursor = rest;
+    Ok(bytes)
+}
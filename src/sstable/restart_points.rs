@@ -0,0 +1,34 @@
+use std::io;
+
+/// Encode restart points, the offsets of every `restart_interval`th entry
+/// of each block in file order, as a count followed by each offset
+pub(crate) fn encode(offsets: &[u64]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(4 + offsets.len() * 8);
+    buf.extend_from_slice(&(offsets.len() as u32).to_le_bytes());
+    for offset in offsets {
+        buf.extend_from_slice(&offset.to_le_bytes());
+    }
+    buf
+}
+
+/// Decode restart points written by `encode`
+pub(crate) fn decode(buf: &[u8]) -> io::Result<Vec<u64>> {
+    let truncated = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Truncated SSTable restart points",
+        )
+    };
+    let count = buf
+        .get(..4)
+        .map(|count| u32::from_le_bytes(count.try_into().unwrap()) as usize)
+        .ok_or_else(truncated)?;
+    let body = &buf[4..];
+    if body.len() != count * 8 {
+        return Err(truncated());
+    }
+    Ok(body
+        .chunks_exact(8)
+        .map(|offset| u64::from_le_bytes(offset.try_into().unwrap()))
+        .collect())
+}
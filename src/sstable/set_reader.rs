@@ -0,0 +1,171 @@
+use super::{SSTableCompaction, SSTableReader};
+use crate::clock::{Clock, SystemClock};
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+
+/// What one file says about a key
+#[derive(Debug, Clone)]
+enum Version {
+    /// A live value, with the entry's sequence if the file recorded one
+    Value(Vec<u8>, Option<u64>),
+    /// A tombstone, a covering range tombstone or an expired entry
+    Deleted,
+}
+
+impl Version {
+    fn sequence(&self) -> Option<u64> {
+        match self {
+            Version::Value(_, sequence) => *sequence,
+            Version::Deleted => None,
+        }
+    }
+}
+
+/// Merged, read-only view of a set of SSTables, such as the files of a
+/// checkpoint, without a WAL, memtable or manifest.
+///
+/// Files are given oldest first. When several files hold a key, the
+/// newest version wins as in compaction: the entry with the highest
+/// sequence, or the one from the file listed later if either lacks a
+/// sequence. Tombstones, range tombstones from later files and expired
+/// entries hide older values.
+pub struct SSTableSetReader {
+    paths: Vec<String>,
+    readers: Vec<SSTableReader>,
+    clock: Arc<dyn Clock>,
+}
+
+impl SSTableSetReader {
+    /// Open the SSTables at `paths`, oldest first
+    pub fn open(paths: &[String]) -> io::Result<Self> {
+        let readers = paths
+            .iter()
+            .map(|path| SSTableReader::open(path))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(SSTableSetReader {
+            paths: paths.to_vec(),
+            readers,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Decide whether entries have expired by `clock` instead of the
+    /// system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Paths of the files in the set, oldest first
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// Total number of entries across the files, counting shadowed ones
+    pub fn entry_count(&self) -> u64 {
+        self.readers.iter().map(SSTableReader::entry_count).sum()
+    }
+
+    /// The value the set holds for `key`, if it is live
+    pub fn get(&mut self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let now_ms = self.clock.now_ms();
+        let mut versions = Vec::new();
+        for (i, reader) in self.readers.iter_mut().enumerate() {
+            let version = if reader.tombstones().contains_key(key) {
+                Some(Version::Deleted)
+            } else if let Some(value) = reader.get(key)? {
+                Some(Self::value_version(reader, key, value, now_ms))
+            } else if reader.range_tombstones().covers(key) {
+                Some(Version::Deleted)
+            } else {
+                None
+            };
+            if let Some(version) = version {
+                versions.push((i, version));
+            }
+        }
+        Ok(Self::resolve(key, versions))
+    }
+
+    /// Live entries with keys from `lower` (inclusive) up to `upper`
+    /// (exclusive), in key order. Each file is read once from disk.
+    pub fn scan(
+        &self,
+        lower: Option<&str>,
+        upper: Option<&str>,
+    ) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let now_ms = self.clock.now_ms();
+        let in_range = |key: &str| {
+            lower.is_none_or(|lower| key >= lower) && upper.is_none_or(|upper| key < upper)
+        };
+
+        let mut versions: BTreeMap<String, Vec<(usize, Version)>> = BTreeMap::new();
+        for (i, (path, reader)) in self.paths.iter().zip(&self.readers).enumerate() {
+            let mut own: BTreeMap<String, Version> = BTreeMap::new();
+            for entry in SSTableReader::open(path)?.into_range_entries(lower, upper)? {
+                let (key, value) = entry?;
+                let version = Self::value_version(reader, &key, value, now_ms);
+                own.insert(key, version);
+            }
+            for key in reader.tombstones().keys().filter(|key| in_range(key)) {
+                own.insert(key.clone(), Version::Deleted);
+            }
+            for (key, version) in own {
+                versions.entry(key).or_default().push((i, version));
+            }
+        }
+
+        // Range tombstones hide the key in older files only
+        for (key, key_versions) in versions.iter_mut() {
+            for (i, reader) in self.readers.iter().enumerate() {
+                if reader.range_tombstones().covers(key)
+                    && !key_versions.iter().any(|(input, _)| *input == i)
+                {
+                    key_versions.push((i, Version::Deleted));
+                }
+            }
+            key_versions.sort_by_key(|(input, _)| *input);
+        }
+
+        Ok(versions
+            .into_iter()
+            .filter_map(|(key, key_versions)| {
+                let value = Self::resolve(&key, key_versions)?;
+                Some((key, value))
+            })
+            .collect())
+    }
+
+    /// The version of a value read from `reader`, which is deleted if the
+    /// entry has expired
+    fn value_version(reader: &SSTableReader, key: &str, value: Vec<u8>, now_ms: u64) -> Version {
+        match reader.expires_at(key) {
+            Some(expires_at_ms) if expires_at_ms <= now_ms => Version::Deleted,
+            _ => Version::Value(value, reader.sequence(key)),
+        }
+    }
+
+    /// The live value among the versions files hold for `key`, given in
+    /// ascending file order
+    fn resolve(key: &str, versions: Vec<(usize, Version)>) -> Option<Vec<u8>> {
+        if versions.is_empty() {
+            return None;
+        }
+        let inputs: Vec<usize> = versions.iter().map(|(input, _)| *input).collect();
+        let sequence_of = |input: usize, _: &str| {
+            versions
+                .iter()
+                .find(|(i, _)| *i == input)
+                .and_then(|(_, version)| version.sequence())
+        };
+        let newest = SSTableCompaction::newest_input(&inputs, key, &sequence_of);
+        versions
+            .into_iter()
+            .find(|(input, _)| *input == newest)
+            .and_then(|(_, version)| match version {
+                Version::Value(value, _) => Some(value),
+                Version::Deleted => None,
+            })
+    }
+}
@@ -0,0 +1,386 @@
+use super::block_compression::CompressionType;
+use super::block_map::{
+    self, BLOCK_MAP_TRAILER_SIZE, BLOCK_RECORD_SIZE, KEY_ID_LEN_SIZE, StoredBlock,
+};
+use super::encryption::{self, BlockCipher, KeyProvider};
+use super::{
+    HEADER_MAGIC_SIZE, HEADER_VERSION_SIZE, MAX_HEADER_SIZE, header_compression_type,
+    header_encrypted, header_size, is_valid_header,
+};
+use crate::locks;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Read up to `buf.len()` bytes at `offset` without moving the file's cursor
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+/// Read up to `buf.len()` bytes at `offset`. This moves the file's cursor,
+/// which is harmless because every read gives its own offset.
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+/// Read exactly `buf.len()` bytes at `offset` without moving the file's cursor
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+/// Read exactly `buf.len()` bytes at `offset`. This moves the file's cursor,
+/// which is harmless because every read gives its own offset.
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    while !buf.is_empty() {
+        match std::os::windows::fs::FileExt::seek_read(file, buf, offset)? {
+            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            n => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Data blocks of a file that stores them compressed or encrypted, and where
+/// the rest of the file lies once they are
+#[derive(Debug)]
+struct BlockMap {
+    compression: CompressionType,
+    /// Cipher under the key the blocks are sealed with, if they are
+    cipher: Option<BlockCipher>,
+    blocks: Vec<StoredBlock>,
+    /// Offset of the first entry, where the first block starts
+    data_start: u64,
+    /// Offset just past the last entry, as entries were written
+    data_end: u64,
+    /// Offset in the file just past the last block's stored bytes
+    stored_end: u64,
+    /// Length of the file as written, without the block map
+    len: u64,
+}
+
+impl BlockMap {
+    /// Read the block map ending `file`, whose data blocks start at
+    /// `data_start` and were compressed with `compression` and, if
+    /// `encrypted`, sealed under the key of `key_provider` the map names
+    fn read(
+        file: &File,
+        compression: CompressionType,
+        encrypted: bool,
+        data_start: u64,
+        key_provider: Option<&dyn KeyProvider>,
+    ) -> io::Result<Self> {
+        let too_small = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "SSTable is too small to hold its block map",
+            )
+        };
+        let file_size = file.metadata()?.len();
+        let trailer_start = file_size
+            .checked_sub(BLOCK_MAP_TRAILER_SIZE as u64)
+            .filter(|&start| start >= data_start)
+            .ok_or_else(too_small)?;
+        let mut trailer = [0u8; BLOCK_MAP_TRAILER_SIZE];
+        read_exact_at(file, &mut trailer, trailer_start)?;
+        let count = u64::from_le_bytes(trailer[..8].try_into().unwrap());
+        let checksum = u32::from_le_bytes(trailer[8..].try_into().unwrap());
+
+        // An encrypted file's map ends with the key ID and its length
+        let records_end = if encrypted {
+            let mut key_id_len = [0u8; KEY_ID_LEN_SIZE];
+            let len_start = trailer_start
+                .checked_sub(KEY_ID_LEN_SIZE as u64)
+                .filter(|&start| start >= data_start)
+                .ok_or_else(too_small)?;
+            read_exact_at(file, &mut key_id_len, len_start)?;
+            len_start
+                .checked_sub(u32::from_le_bytes(key_id_len) as u64)
+                .filter(|&start| start >= data_start)
+                .ok_or_else(too_small)?
+        } else {
+            trailer_start
+        };
+        let map_start = count
+            .checked_mul(BLOCK_RECORD_SIZE as u64)
+            .and_then(|records_len| records_end.checked_sub(records_len))
+            .filter(|&start| start >= data_start)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "SSTable block map claims {} blocks, more than the file holds",
+                        count
+                    ),
+                )
+            })?;
+        let mut map = vec![0u8; (trailer_start - map_start) as usize];
+        read_exact_at(file, &mut map, map_start)?;
+        let (blocks, key_id) =
+            block_map::decode_block_map(&map, count, checksum, data_start, encrypted)?;
+        let cipher = key_id
+            .map(|key_id| {
+                let provider = key_provider.ok_or_else(encryption::no_key_provider)?;
+                BlockCipher::for_key(provider, &key_id)
+            })
+            .transpose()?;
+
+        let (data_end, stored_end) = blocks.last().map_or((data_start, data_start), |block| {
+            (
+                block.offset + block.len,
+                block.stored_offset + block.stored_len,
+            )
+        });
+        if stored_end > map_start {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Data blocks end at {} past the block map at {}",
+                    stored_end, map_start
+                ),
+            ));
+        }
+        Ok(BlockMap {
+            compression,
+            cipher,
+            blocks,
+            data_start,
+            data_end,
+            stored_end,
+            len: map_start - stored_end + data_end,
+        })
+    }
+
+    /// Index of the block holding `offset`, if it lies in the data section
+    fn block_at(&self, offset: u64) -> Option<usize> {
+        if offset < self.data_start || offset >= self.data_end {
+            return None;
+        }
+        Some(
+            self.blocks
+                .partition_point(|block| block.offset + block.len <= offset),
+        )
+    }
+
+    /// Where the byte at `offset` outside the data section is stored, and how
+    /// many bytes from it on are stored contiguously
+    fn stored_span(&self, offset: u64) -> (u64, u64) {
+        if offset < self.data_start {
+            (offset, self.data_start - offset)
+        } else {
+            (
+                offset - self.data_end + self.stored_end,
+                self.len.saturating_sub(offset),
+            )
+        }
+    }
+}
+
+/// An SSTable's open file, read at the offsets its entries and sections were
+/// written at.
+///
+/// Files whose data blocks are compressed or encrypted read as if the blocks
+/// were stored as written: a read in the data section decrypts and
+/// decompresses the block holding it, and offsets past it are moved by the
+/// bytes storing the blocks saved or added. Entry offsets recorded in
+/// indexes and storage references therefore hold however blocks are
+/// stored. Each handle keeps the block it last decoded, so a scan decodes
+/// each block once.
+///
+/// Handles have their own position, so a scan sharing a reader's open file
+/// leaves the reader's position alone.
+pub(crate) struct TableFile {
+    file: File,
+    blocks: Option<Arc<BlockMap>>,
+    /// Index and bytes of the block last decoded
+    cached: Mutex<Option<(usize, Arc<[u8]>)>>,
+    position: u64,
+}
+
+impl fmt::Debug for TableFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TableFile")
+            .field("file", &self.file)
+            .field("compression", &self.compression_type())
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+impl TableFile {
+    /// Open the SSTable at `path`, getting the key its blocks are sealed
+    /// with from `key_provider` if it is encrypted
+    pub(crate) fn open(
+        path: impl AsRef<Path>,
+        key_provider: Option<&dyn KeyProvider>,
+    ) -> io::Result<Self> {
+        Self::new(File::open(path)?, key_provider)
+    }
+
+    /// Read an open SSTable, loading its block map if its header says its
+    /// blocks are compressed or encrypted. Files without a valid header are
+    /// read as they are, leaving their reader to report what is wrong with
+    /// them.
+    pub(crate) fn new(file: File, key_provider: Option<&dyn KeyProvider>) -> io::Result<Self> {
+        let mut header = vec![0u8; MAX_HEADER_SIZE];
+        let read = read_at(&file, &mut header, 0)?;
+        header.truncate(read);
+        let (compression, encrypted) = if is_valid_header(&header) {
+            (
+                header_compression_type(&header)?,
+                header_encrypted(&header)?,
+            )
+        } else {
+            (CompressionType::None, false)
+        };
+
+        let blocks = if compression != CompressionType::None || encrypted {
+            compression.ensure_available()?;
+            let version_at = HEADER_MAGIC_SIZE..HEADER_MAGIC_SIZE + HEADER_VERSION_SIZE;
+            let version = u32::from_le_bytes(header[version_at].try_into().unwrap());
+            let data_start = header_size(version) as u64;
+            let blocks = BlockMap::read(&file, compression, encrypted, data_start, key_provider)?;
+            Some(Arc::new(blocks))
+        } else {
+            None
+        };
+        Ok(TableFile {
+            file,
+            blocks,
+            cached: Mutex::new(None),
+            position: 0,
+        })
+    }
+
+    /// Codec the file's data blocks are compressed with
+    pub(crate) fn compression_type(&self) -> CompressionType {
+        self.blocks
+            .as_ref()
+            .map_or(CompressionType::None, |blocks| blocks.compression)
+    }
+
+    /// Length of the file as written, with its data blocks as written
+    pub(crate) fn len(&self) -> io::Result<u64> {
+        match &self.blocks {
+            Some(blocks) => Ok(blocks.len),
+            None => Ok(self.file.metadata()?.len()),
+        }
+    }
+
+    /// Bytes the file stores for what was written between `start` and
+    /// `end`, which must not fall inside a data block
+    pub(crate) fn stored_bytes(&self, start: u64, end: u64) -> u64 {
+        let stored = |offset: u64| match &self.blocks {
+            Some(blocks) => match blocks.block_at(offset) {
+                Some(index) => blocks.blocks[index].stored_offset,
+                None => blocks.stored_span(offset).0,
+            },
+            None => offset,
+        };
+        stored(end).saturating_sub(stored(start))
+    }
+
+    /// The underlying file, if it stores everything as written so its bytes
+    /// can be mapped and read at the offsets entries were written at
+    pub(crate) fn mappable(&self) -> Option<&File> {
+        self.blocks.is_none().then_some(&self.file)
+    }
+
+    /// A second handle on the file, positioned at `position`
+    pub(crate) fn try_clone_at(&self, position: u64) -> io::Result<Self> {
+        Ok(TableFile {
+            file: self.file.try_clone()?,
+            blocks: self.blocks.clone(),
+            cached: Mutex::new(None),
+            position,
+        })
+    }
+
+    /// Read up to `buf.len()` bytes at `offset`, stopping at the end of the
+    /// data block or section holding it
+    pub(crate) fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let Some(blocks) = &self.blocks else {
+            return read_at(&self.file, buf, offset);
+        };
+        if let Some(index) = blocks.block_at(offset) {
+            let block = self.block(blocks, index)?;
+            let start = (offset - blocks.blocks[index].offset) as usize;
+            let read = buf.len().min(block.len() - start);
+            buf[..read].copy_from_slice(&block[start..start + read]);
+            return Ok(read);
+        }
+        let (stored_offset, available) = blocks.stored_span(offset);
+        let len = buf.len().min(available.try_into().unwrap_or(usize::MAX));
+        read_at(&self.file, &mut buf[..len], stored_offset)
+    }
+
+    /// Read exactly `buf.len()` bytes at `offset`
+    pub(crate) fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+        if self.blocks.is_none() {
+            return read_exact_at(&self.file, buf, offset);
+        }
+        while !buf.is_empty() {
+            match self.read_at(buf, offset)? {
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => {
+                    buf = &mut buf[n..];
+                    offset += n as u64;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The data block at `index`, decoded, from the cache if it was the last
+    /// one read
+    fn block(&self, blocks: &BlockMap, index: usize) -> io::Result<Arc<[u8]>> {
+        let mut cached = locks::checked(self.cached.lock(), "block cache")?;
+        if let Some((cached_index, block)) = cached.as_ref()
+            && *cached_index == index
+        {
+            return Ok(block.clone());
+        }
+
+        let handle = &blocks.blocks[index];
+        let mut stored = vec![0u8; handle.stored_len as usize];
+        read_exact_at(&self.file, &mut stored, handle.stored_offset)?;
+        let block: Arc<[u8]> = handle
+            .decode(blocks.compression, blocks.cipher.as_ref(), &stored)?
+            .into();
+        *cached = Some((index, block.clone()));
+        Ok(block)
+    }
+}
+
+impl Read for TableFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.read_at(buf, self.position)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for TableFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.len()?.checked_add_signed(delta),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
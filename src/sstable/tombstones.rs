@@ -0,0 +1,102 @@
+use crate::checked_len;
+use std::collections::HashMap;
+use std::io;
+
+/// Record of a key deleted while its SSTable was being written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tombstone {
+    /// When the key was deleted, in milliseconds since the Unix epoch
+    pub deleted_at_ms: u64,
+    /// The value the key held before it was deleted, kept so the deletion
+    /// can be undone until its retention period ends
+    pub value: Option<Vec<u8>>,
+}
+
+impl Tombstone {
+    /// Whether the tombstone is past `retention_ms` at time `now_ms`
+    pub fn is_expired(&self, retention_ms: u64, now_ms: u64) -> bool {
+        self.deleted_at_ms.saturating_add(retention_ms) <= now_ms
+    }
+}
+
+/// Encode tombstones as a count followed by length-prefixed keys, each with
+/// its deletion time and an optional length-prefixed shadow value
+pub(crate) fn encode<'a, I>(tombstones: I) -> Vec<u8>
+where
+    I: ExactSizeIterator<Item = (&'a String, &'a Tombstone)>,
+{
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(tombstones.len() as u32).to_le_bytes());
+    for (key, tombstone) in tombstones {
+        buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        buf.extend_from_slice(key.as_bytes());
+        buf.extend_from_slice(&tombstone.deleted_at_ms.to_le_bytes());
+        match &tombstone.value {
+            Some(value) => {
+                buf.push(1);
+                buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                buf.extend_from_slice(value);
+            }
+            None => buf.push(0),
+        }
+    }
+    buf
+}
+
+/// Decode tombstones written by `encode`
+pub(crate) fn decode(buf: &[u8]) -> io::Result<HashMap<String, Tombstone>> {
+    let mut cursor = buf;
+    // Each tombstone takes at least its key length, time and value flag
+    let count = checked_len::check_count(
+        read_u32(&mut cursor)? as u64,
+        u32::MAX as usize,
+        13,
+        cursor.len() as u64,
+        "SSTable tombstones",
+    )?;
+
+    let mut tombstones = HashMap::with_capacity(count);
+    for _ in 0..count {
+        let key_len = read_u32(&mut cursor)? as usize;
+        let key = String::from_utf8(take(&mut cursor, key_len)?.to_vec()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "SSTable tombstone key is not valid UTF-8",
+            )
+        })?;
+        let deleted_at_ms = u64::from_le_bytes(take(&mut cursor, 8)?.try_into().unwrap());
+        let value = match take(&mut cursor, 1)?[0] {
+            0 => None,
+            _ => {
+                let len = read_u32(&mut cursor)? as usize;
+                Some(take(&mut cursor, len)?.to_vec())
+            }
+        };
+        tombstones.insert(
+            key,
+            Tombstone {
+                deleted_at_ms,
+                value,
+            },
+        );
+    }
+
+    Ok(tombstones)
+}
+
+fn read_u32(cursor: &mut &[u8]) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(take(cursor, 4)?.try_into().unwrap()))
+}
+
+/// Split `len` bytes off the front of a buffer
+fn take<'a>(cursor: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if cursor.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Truncated SSTable tombstones block",
+        ));
+    }
+    let (bytes, rest) = cursor.split_at(len);
+    *cursor = rest;
+    Ok(bytes)
+}
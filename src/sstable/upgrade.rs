@@ -0,0 +1,135 @@
+use super::{SSTableReader, SSTableRecord, SSTableWriter, MAGIC, VERSION};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+/// False positive rate of the Bloom filter given to upgraded files
+const UPGRADE_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// The format version of the SSTable at `path`, or `None` if the file does
+/// not start with the SSTable magic number
+pub fn format_version(path: &Path) -> io::Result<Option<u32>> {
+    let mut header = [0u8; 12];
+    match File::open(path)?.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    if u64::from_le_bytes(header[..8].try_into().unwrap()) != MAGIC {
+        return Ok(None);
+    }
+    Ok(Some(u32::from_le_bytes(header[8..].try_into().unwrap())))
+}
+
+/// Rewrite the SSTable at `path` in the current format if it was written
+/// by an older one, returning whether it was rewritten.
+///
+/// Entries keep their order, write times and expiries, and tombstones, the
+/// file number, the applied WAL LSN and the recorded properties such as
+/// user properties are carried over. The new file gets
+/// a Bloom filter whether or not the old one had one, and values are
+/// stored uncompressed. The rewrite goes to a temporary file that replaces
+/// the original only once complete, so an interrupted upgrade leaves the
+/// old file in place.
+///
+/// Files are rewritten in place without updating a manifest that records
+/// their size, so upgrade an index's directory while it is closed.
+pub fn upgrade_sstable(path: &Path) -> io::Result<bool> {
+    let path_str = path.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("SSTable path is not UTF-8: {}", path.display()),
+        )
+    })?;
+    let reader = SSTableReader::open(path_str)?;
+    if reader.version() >= VERSION {
+        return Ok(false);
+    }
+
+    let tmp_path = format!("{}.upgrade", path_str);
+    let mut writer = SSTableWriter::new(
+        &tmp_path,
+        reader.entry_count() as usize,
+        true,
+        UPGRADE_FALSE_POSITIVE_RATE,
+    )?;
+    writer.set_checksum_kind(reader.checksum_kind())?;
+    // Entries are copied in file order, so an unsorted file stays unsorted
+    if !reader.keys_sorted() {
+        writer.allow_unsorted_keys()?;
+    }
+    if let Some(file_number) = reader.file_number() {
+        writer.set_file_number(file_number);
+    }
+    if let Some(lsn) = reader.applied_lsn() {
+        writer.set_applied_lsn(lsn);
+    }
+    if let Some(flush_number) = reader.flush_number() {
+        writer.set_flush_number(flush_number);
+    }
+    if let Some(created_at_secs) = reader.created_at_secs() {
+        writer.set_created_at(created_at_secs);
+    }
+    if let Some(level) = reader.level() {
+        writer.set_level(level);
+    }
+    if let Some(checkpoint_id) = reader.checkpoint_id() {
+        writer.set_checkpoint_id(checkpoint_id);
+    }
+    for (name, value) in reader.properties().user_properties() {
+        writer.set_user_property(name, value)?;
+    }
+    writer.write_all(reader.tombstones().iter().map(|(key, tombstone)| {
+        Ok(SSTableRecord::Delete {
+            key: key.clone(),
+            tombstone: tombstone.clone(),
+        })
+    }))?;
+
+    let write_times = reader.write_times().clone();
+    let expiries = reader.expiries().clone();
+    let sequences = reader.sequences().clone();
+    for (key, &segment) in reader.value_log_segments().iter() {
+        writer.mark_value_pointer(key, segment);
+    }
+    writer.write_all(reader.into_entries()?.map(|entry| {
+        let (key, value) = entry?;
+        Ok(SSTableRecord::Put {
+            written_at_ms: write_times.get(&key).copied(),
+            expires_at_ms: expiries.get(&key).copied(),
+            sequence: sequences.get(&key).copied(),
+            key,
+            value,
+        })
+    }))?;
+    writer.finalize()?;
+
+    File::open(&tmp_path)?.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(true)
+}
+
+/// Upgrade every SSTable in `dir` written by an older format, returning the
+/// paths rewritten. Files without the SSTable magic number are skipped.
+pub fn upgrade_directory(dir: &Path) -> io::Result<Vec<String>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    paths.sort();
+
+    let mut upgraded = Vec::new();
+    for path in paths {
+        let is_sstable = path
+            .extension()
+            .is_some_and(|extension| extension == "sst" || extension == "db");
+        if !is_sstable || !path.is_file() {
+            continue;
+        }
+        if format_version(&path)?.is_some_and(|version| version < VERSION)
+            && upgrade_sstable(&path)?
+        {
+            upgraded.push(path.to_string_lossy().into_owned());
+        }
+    }
+    Ok(upgraded)
+}
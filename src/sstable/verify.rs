@@ -0,0 +1,147 @@
+use super::{
+    FOOTER_SIZE, FOOTER_VERSION, KeyExpander, MAX_KEY_SIZE, MAX_VALUE_SIZE, SSTableReader,
+};
+use crate::checked_len;
+use std::io::{self, BufReader, Read, Seek};
+
+/// Outcome of `SSTableReader::verify_all`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Entries whose bytes matched their own checksum and the checksum table
+    pub entries_checked: u64,
+    /// Offset of the first entry, or of the checksum table, that failed
+    /// verification
+    pub first_corrupt_offset: Option<u64>,
+    /// What was wrong at `first_corrupt_offset`
+    pub problem: Option<String>,
+}
+
+impl VerifyReport {
+    /// Whether every entry verified
+    pub fn is_intact(&self) -> bool {
+        self.first_corrupt_offset.is_none()
+    }
+
+    fn corrupt(&mut self, offset: u64, problem: impl Into<String>) {
+        self.first_corrupt_offset = Some(offset);
+        self.problem = Some(problem.into());
+    }
+}
+
+impl SSTableReader {
+    /// Read every entry in file order, recomputing its checksum and comparing
+    /// it with the checksum stored after the entry and with the file's
+    /// trailing checksum table. Stops at the first entry that does not
+    /// verify.
+    ///
+    /// Damage is reported in the returned `VerifyReport`; an error means
+    /// the file could not be read at all. Files older than version 3 have no
+    /// checksums, so only the framing of their entries is checked.
+    pub fn verify_all(&self) -> io::Result<VerifyReport> {
+        let file_size = self.file.get_ref().len()?;
+        let mut report = VerifyReport::default();
+
+        let table = if self.has_entry_checksums {
+            match self.read_checksum_table(file_size) {
+                Ok(table) => Some(table),
+                Err(e) => {
+                    report.corrupt(self.checksums_offset, e.to_string());
+                    return Ok(report);
+                }
+            }
+        } else {
+            None
+        };
+
+        let mut file = BufReader::new(self.file.get_ref().try_clone_at(self.data_offset())?);
+        let mut offset = self.data_offset();
+        let mut keys = self.key_expander();
+        for index in 0..self.entry_count as usize {
+            let checksum = match self.read_entry_checksum(&mut file, offset, keys.as_mut()) {
+                Ok(checksum) => checksum,
+                Err(e) => {
+                    report.corrupt(offset, e.to_string());
+                    return Ok(report);
+                }
+            };
+            if let (Some((stored, recomputed)), Some(table)) = (checksum, &table) {
+                if stored != recomputed {
+                    report.corrupt(offset, "Entry checksum mismatch");
+                    return Ok(report);
+                }
+                if table[index] != recomputed {
+                    report.corrupt(offset, "Entry does not match the checksum table");
+                    return Ok(report);
+                }
+            }
+            report.entries_checked += 1;
+            offset = file.stream_position()?;
+        }
+
+        if offset != self.index_offset {
+            report.corrupt(
+                offset,
+                format!(
+                    "Entries end at {} rather than at the meta section at {}",
+                    offset, self.index_offset
+                ),
+            );
+        }
+        Ok(report)
+    }
+
+    /// Read the entry at the reader's position, `offset`, returning the
+    /// checksum stored after it and the one recomputed from its bytes, if
+    /// the file has per-entry checksums. Prefix-compressed keys are rebuilt
+    /// with `keys` first, as their checksums cover the whole key.
+    fn read_entry_checksum<R: Read + Seek>(
+        &self,
+        file: &mut R,
+        offset: u64,
+        keys: Option<&mut KeyExpander>,
+    ) -> io::Result<Option<(u32, u32)>> {
+        let end = self.index_offset;
+        let key_len = checked_len::read_len(file, end, MAX_KEY_SIZE, "Key")?;
+        let mut key = vec![0u8; key_len];
+        file.read_exact(&mut key)?;
+        if let Some(keys) = keys {
+            keys.prime(file, end, offset)?;
+            key = keys.expand(offset, &key)?;
+        }
+        let value_len = checked_len::read_len(file, end, MAX_VALUE_SIZE, "Value")?;
+        let mut value = vec![0u8; value_len];
+        file.read_exact(&mut value)?;
+        if !self.has_entry_checksums {
+            return Ok(None);
+        }
+
+        let mut checksum_buf = [0u8; 4];
+        file.read_exact(&mut checksum_buf)?;
+        let recomputed = self.checksum_kind.entry_checksum_bytes(&key, &value);
+        Ok(Some((u32::from_le_bytes(checksum_buf), recomputed)))
+    }
+
+    /// Read the table of per-entry checksums that follows the filter region
+    fn read_checksum_table(&self, file_size: u64) -> io::Result<Vec<u32>> {
+        let end = if self.version >= FOOTER_VERSION {
+            file_size.saturating_sub(FOOTER_SIZE as u64)
+        } else {
+            file_size
+        };
+        let count = checked_len::check_count(
+            self.entry_count,
+            usize::MAX,
+            4,
+            end.saturating_sub(self.checksums_offset),
+            "entry checksums",
+        )?;
+
+        let mut buf = vec![0u8; count * 4];
+        let mut file = self.file.get_ref().try_clone_at(self.checksums_offset)?;
+        file.read_exact(&mut buf)?;
+        Ok(buf
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect())
+    }
+}
@@ -0,0 +1,352 @@
+//! Proptest strategies for keys, values, Bloom filters and WAL operations,
+//! and checks that SSTables and WAL records read back exactly what was
+//! written, or fail loudly when a byte of them is corrupted.
+//!
+//! Enabled with the `test-utils` feature. The checks return a
+//! `TestCaseError` rather than panicking, so they can be called from inside
+//! `proptest!` and shrink to a minimal failing case:
+//!
+//! ```ignore
+//! use lsmer::test_utils;
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     #[test]
+//!     fn sstables_round_trip(entries in test_utils::entries()) {
+//!         let dir = tempfile::tempdir().unwrap();
+//!         test_utils::assert_sstable_round_trip(&dir.path().join("t.sst"), &entries, true)?;
+//!     }
+//! }
+//! ```
+
+use crate::bloom::BloomFilter;
+use crate::sstable::{self, SSTableReader, SSTableWriter, Tombstone};
+use crate::wal::durability::{CheckpointFile, Operation};
+use crate::wal::{WalRecord, WriteAheadLog, WAL_HEADER_SIZE};
+use proptest::collection::{btree_map, hash_set, vec};
+use proptest::option;
+use proptest::prelude::*;
+use proptest::sample::Index;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Seek, SeekFrom};
+use std::path::Path;
+
+/// Keys of up to 32 printable characters, including the empty key and
+/// multi-byte UTF-8. Control characters are left out, since the WAL
+/// separates keys from values with a null byte.
+pub fn key() -> impl Strategy<Value = String> {
+    "\\PC{0,32}"
+}
+
+/// Values of up to 256 arbitrary bytes
+pub fn value() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..256)
+}
+
+/// Up to 64 distinct keys with their values, in key order
+pub fn entries() -> impl Strategy<Value = BTreeMap<String, Vec<u8>>> {
+    btree_map(key(), value(), 0..64)
+}
+
+/// Tombstones with an arbitrary deletion time, with or without the value
+/// they hide
+pub fn tombstone() -> impl Strategy<Value = Tombstone> {
+    (any::<u64>(), proptest::option::of(value())).prop_map(|(deleted_at_ms, value)| Tombstone {
+        deleted_at_ms,
+        value,
+    })
+}
+
+/// Up to 32 tombstones keyed by distinct keys
+pub fn tombstones() -> impl Strategy<Value = BTreeMap<String, Tombstone>> {
+    btree_map(key(), tombstone(), 0..32)
+}
+
+/// A Bloom filter with a random target false positive rate, along with the
+/// keys inserted into it
+pub fn bloom_filter() -> impl Strategy<Value = (BloomFilter<String>, Vec<String>)> {
+    (hash_set(key(), 0..128), 0.001f64..0.5).prop_map(|(keys, false_positive_rate)| {
+        let keys: Vec<String> = keys.into_iter().collect();
+        let mut filter = BloomFilter::new(keys.len().max(1), false_positive_rate);
+        for key in &keys {
+            filter.insert(key);
+        }
+        (filter, keys)
+    })
+}
+
+/// An SSTable as a CheckpointEnd record describes it
+pub fn checkpoint_file() -> impl Strategy<Value = CheckpointFile> {
+    (key(), any::<u64>(), option::of(key()), option::of(key())).prop_map(
+        |(path, entry_count, min_key, max_key)| CheckpointFile {
+            path,
+            entry_count,
+            min_key,
+            max_key,
+        },
+    )
+}
+
+/// Any operation the WAL can log, including batches nested up to two deep
+pub fn operation() -> impl Strategy<Value = Operation> {
+    let leaf = prop_oneof![
+        4 => (key(), value()).prop_map(|(key, value)| Operation::Insert { key, value }),
+        2 => key().prop_map(|key| Operation::Remove { key }),
+        1 => (key(), key()).prop_map(|(start, end)| Operation::DeleteRange { start, end }),
+        1 => Just(Operation::Clear),
+        1 => any::<u64>().prop_map(|id| Operation::CheckpointStart { id }),
+        1 => (any::<u64>(), vec(checkpoint_file(), 0..3))
+            .prop_map(|(id, files)| Operation::CheckpointEnd { id, files }),
+        1 => any::<u64>().prop_map(|id| Operation::TransactionBegin { id }),
+        1 => any::<u64>().prop_map(|id| Operation::TransactionPrepare { id }),
+        1 => any::<u64>().prop_map(|id| Operation::TransactionCommit { id }),
+        1 => any::<u64>().prop_map(|id| Operation::TransactionAbort { id }),
+    ];
+    leaf.prop_recursive(2, 32, 8, |inner| {
+        vec(inner, 0..8).prop_map(|operations| Operation::Batch { operations })
+    })
+}
+
+/// Rebuild `filter` from its raw parts, as SSTables store it, and check the
+/// copy has the same bits and still holds every key in `keys`
+pub fn assert_bloom_filter_round_trip(
+    filter: &BloomFilter<String>,
+    keys: &[String],
+) -> Result<(), TestCaseError> {
+    let copy = BloomFilter::<String>::from_parts(
+        filter.get_bits().to_vec(),
+        filter.size_bits(),
+        filter.num_hashes(),
+    );
+    prop_assert_eq!(copy.get_bits(), filter.get_bits());
+    prop_assert_eq!(copy.size_bits(), filter.size_bits());
+    prop_assert_eq!(copy.num_hashes(), filter.num_hashes());
+    for key in keys {
+        prop_assert!(copy.may_contain(key), "rebuilt filter lost {:?}", key);
+    }
+    Ok(())
+}
+
+/// Write `entries` to an SSTable at `path` and check that point lookups and
+/// a full scan return exactly them
+pub fn assert_sstable_round_trip(
+    path: &Path,
+    entries: &BTreeMap<String, Vec<u8>>,
+    use_bloom_filter: bool,
+) -> Result<(), TestCaseError> {
+    write_sstable(path, entries, use_bloom_filter)?;
+
+    let mut reader = SSTableReader::open(path_str(path)?).map_err(fail)?;
+    prop_assert_eq!(reader.entry_count(), entries.len() as u64);
+    prop_assert!(reader.keys_sorted());
+    for (key, value) in entries {
+        prop_assert!(reader.may_contain(key), "filter lost {:?}", key);
+        let found = reader.get(key).map_err(fail)?;
+        prop_assert_eq!(found.as_ref(), Some(value));
+    }
+
+    let scanned = reader
+        .into_entries()
+        .map_err(fail)?
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(fail)?;
+    let expected: Vec<_> = entries
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    prop_assert_eq!(scanned, expected);
+    Ok(())
+}
+
+/// Write `entries` and `tombstones` to an SSTable at `path` and check the
+/// tombstones read back unchanged, without disturbing the entries
+pub fn assert_sstable_tombstones_round_trip(
+    path: &Path,
+    entries: &BTreeMap<String, Vec<u8>>,
+    tombstones: &BTreeMap<String, Tombstone>,
+) -> Result<(), TestCaseError> {
+    let mut writer =
+        SSTableWriter::new(path_str(path)?, entries.len(), true, 0.01).map_err(fail)?;
+    for (key, value) in entries {
+        writer.write_entry(key, value).map_err(fail)?;
+    }
+    for (key, tombstone) in tombstones {
+        writer.write_tombstone(key, tombstone.clone());
+    }
+    writer.finalize().map_err(fail)?;
+
+    let reader = SSTableReader::open(path_str(path)?).map_err(fail)?;
+    prop_assert_eq!(reader.tombstone_count(), tombstones.len() as u64);
+    let read: BTreeMap<_, _> = reader
+        .tombstones()
+        .iter()
+        .map(|(key, tombstone)| (key.clone(), tombstone.clone()))
+        .collect();
+    prop_assert_eq!(&read, tombstones);
+
+    let scanned = reader
+        .into_entries()
+        .map_err(fail)?
+        .collect::<std::io::Result<BTreeMap<_, _>>>()
+        .map_err(fail)?;
+    prop_assert_eq!(&scanned, entries);
+    Ok(())
+}
+
+/// Write `entries` to an SSTable at `path`, XOR one byte of its header or
+/// data with `mask`, and check the damage is caught: a damaged header fails
+/// `is_valid_header`, and damaged data fails a full scan.
+///
+/// `entries` must not be empty, and `mask` must not be zero.
+pub fn assert_sstable_detects_corruption(
+    path: &Path,
+    entries: &BTreeMap<String, Vec<u8>>,
+    position: Index,
+    mask: u8,
+) -> Result<(), TestCaseError> {
+    prop_assert!(!entries.is_empty() && mask != 0);
+    write_sstable(path, entries, false)?;
+
+    // Entries are stored uncompressed right after the header, each as a
+    // length-prefixed key and value followed by a checksum
+    let header_len = sstable::header_size(sstable::VERSION);
+    let data_len: usize = entries
+        .iter()
+        .map(|(key, value)| 4 + key.len() + 4 + value.len() + 4)
+        .sum();
+    let offset = position.index(header_len + data_len);
+    let bytes = flip_byte(path, offset, mask)?;
+
+    if offset < header_len {
+        prop_assert!(
+            !sstable::is_valid_header(&bytes[..header_len]),
+            "header damaged at byte {} was accepted",
+            offset
+        );
+        return Ok(());
+    }
+
+    let scanned = SSTableReader::open(path_str(path)?)
+        .and_then(SSTableReader::into_entries)
+        .and_then(|scan| scan.collect::<std::io::Result<Vec<_>>>());
+    prop_assert!(
+        scanned.is_err(),
+        "data damaged at byte {} was read without error",
+        offset
+    );
+    Ok(())
+}
+
+/// Check that a record survives serialization unchanged
+pub fn assert_wal_record_round_trip(operation: &Operation) -> Result<(), TestCaseError> {
+    let record = operation.clone().into_record();
+    let bytes = record.serialize().map_err(fail)?;
+    let decoded = WalRecord::deserialize(&bytes).map_err(fail)?;
+    prop_assert_eq!(decoded.record_type, record.record_type);
+    prop_assert_eq!(&decoded.data, &record.data);
+    prop_assert_eq!(&Operation::from_record(decoded).map_err(fail)?, operation);
+    Ok(())
+}
+
+/// Log `operations` to a new WAL at `path`, then reopen it and check every
+/// operation reads back unchanged and in order
+pub fn assert_wal_round_trip(path: &Path, operations: &[Operation]) -> Result<(), TestCaseError> {
+    write_wal(path, operations)?;
+
+    let (read, end) = read_wal(path)?;
+    prop_assert!(end.is_ok(), "reading the log failed: {:?}", end);
+    prop_assert_eq!(read.as_slice(), operations);
+    Ok(())
+}
+
+/// Log `operations` to a new WAL at `path`, XOR one byte after the header
+/// with `mask`, and check the damage is caught: reading returns the
+/// operations before the damaged record unchanged, then an error, and never
+/// a record that was not written.
+///
+/// `operations` must not be empty, and `mask` must not be zero.
+pub fn assert_wal_detects_corruption(
+    path: &Path,
+    operations: &[Operation],
+    position: Index,
+    mask: u8,
+) -> Result<(), TestCaseError> {
+    prop_assert!(!operations.is_empty() && mask != 0);
+    write_wal(path, operations)?;
+
+    let log_len = fs::metadata(path).map_err(fail)?.len() - WAL_HEADER_SIZE;
+    let offset = WAL_HEADER_SIZE as usize + position.index(log_len as usize);
+    flip_byte(path, offset, mask)?;
+
+    let (read, end) = read_wal(path)?;
+    prop_assert!(
+        end.is_err(),
+        "log damaged at byte {} was read without error",
+        offset
+    );
+    prop_assert!(read.len() < operations.len());
+    prop_assert_eq!(read.as_slice(), &operations[..read.len()]);
+    Ok(())
+}
+
+fn write_sstable(
+    path: &Path,
+    entries: &BTreeMap<String, Vec<u8>>,
+    use_bloom_filter: bool,
+) -> Result<(), TestCaseError> {
+    let mut writer =
+        SSTableWriter::new(path_str(path)?, entries.len(), use_bloom_filter, 0.01).map_err(fail)?;
+    for (key, value) in entries {
+        writer.write_entry(key, value).map_err(fail)?;
+    }
+    writer.finalize().map_err(fail)
+}
+
+fn write_wal(path: &Path, operations: &[Operation]) -> Result<(), TestCaseError> {
+    let mut wal = WriteAheadLog::new(path_str(path)?).map_err(fail)?;
+    for operation in operations {
+        wal.append_and_sync(operation.clone().into_record())
+            .map_err(fail)?;
+    }
+    Ok(())
+}
+
+/// Read a WAL's operations up to its end or the first record that fails,
+/// along with how reading ended
+fn read_wal(path: &Path) -> Result<(Vec<Operation>, Result<(), String>), TestCaseError> {
+    let mut wal = WriteAheadLog::new(path_str(path)?).map_err(fail)?;
+    wal.file
+        .seek(SeekFrom::Start(WAL_HEADER_SIZE))
+        .map_err(fail)?;
+
+    let mut operations = Vec::new();
+    loop {
+        let record = match wal.read_next_record() {
+            Ok(Some(record)) => record,
+            Ok(None) => return Ok((operations, Ok(()))),
+            Err(e) => return Ok((operations, Err(format!("{:?}", e)))),
+        };
+        match Operation::from_record(record) {
+            Ok(operation) => operations.push(operation),
+            Err(e) => return Ok((operations, Err(format!("{:?}", e)))),
+        }
+    }
+}
+
+/// XOR the byte at `offset` with `mask`, returning the damaged file
+fn flip_byte(path: &Path, offset: usize, mask: u8) -> Result<Vec<u8>, TestCaseError> {
+    let mut bytes = fs::read(path).map_err(fail)?;
+    bytes[offset] ^= mask;
+    fs::write(path, &bytes).map_err(fail)?;
+    Ok(bytes)
+}
+
+fn path_str(path: &Path) -> Result<&str, TestCaseError> {
+    path.to_str()
+        .ok_or_else(|| TestCaseError::fail(format!("path is not UTF-8: {:?}", path)))
+}
+
+fn fail(e: impl std::fmt::Debug) -> TestCaseError {
+    TestCaseError::fail(format!("{:?}", e))
+}
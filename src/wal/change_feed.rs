@@ -0,0 +1,298 @@
+//! Change feed over the write-ahead log for external consumers.
+//!
+//! A `ChangeFeed` tails a WAL file and surfaces each committed write as a
+//! `Change` tagged with an `OperationId`: the LSN of the record that
+//! committed it and the transaction that wrote it. Each consumer's
+//! acknowledgements are persisted beside the WAL, so a consumer that
+//! crashes resumes just past the last change it acknowledged, seeing every
+//! later change once and no acknowledged change again.
+//!
+//! A checkpoint truncates the log at its start and later records reuse the
+//! LSNs past that point. A feed whose position no longer holds the record
+//! it read fails with `DurabilityError::RecoveryFailed` instead of skipping
+//! or repeating changes.
+
+use super::durability::{DurabilityError, Operation};
+use super::{WalRecord, WriteAheadLog};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Size of an acknowledgement file: LSN, transaction ID, fingerprint of the
+/// acknowledged record and a checksum
+const ACK_SIZE: usize = 8 + 8 + 4 + 4;
+
+/// Identifies a change surfaced by a `ChangeFeed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OperationId {
+    /// LSN of the record that committed the change, the WAL offset just
+    /// past it. Unique within the log, and increasing in commit order.
+    pub lsn: u64,
+    /// Transaction that wrote the change, or 0 for operations logged
+    /// outside a transaction. IDs restart with each `DurabilityManager`, so
+    /// only the LSN orders changes.
+    pub tx_id: u64,
+}
+
+/// Operations made durable by one WAL record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Where the change was committed
+    pub id: OperationId,
+    /// Operations in the order they are applied
+    pub operations: Vec<Operation>,
+}
+
+/// Tails a WAL on behalf of a named consumer, whose acknowledgements are
+/// kept in `<wal path>.consumers/<consumer>`.
+///
+/// Prepared transactions surface at their commit record, and aborted ones
+/// not at all. Checkpoint and transaction control records carry no changes.
+pub struct ChangeFeed {
+    /// Read-only handle on the log being followed
+    wal: WriteAheadLog,
+    consumer: String,
+    ack_path: PathBuf,
+    acked: Option<OperationId>,
+    /// Offset of the next record to read
+    position: u64,
+    /// Start and fingerprint of the record ending at `position`
+    last_record: Option<(u64, u32)>,
+    /// Operations of prepared transactions, held until their outcome
+    prepared: HashMap<u64, Vec<Operation>>,
+    /// Changes returned by `poll` and not yet acknowledged, with the
+    /// fingerprints of their records
+    unacked: VecDeque<(OperationId, u32)>,
+}
+
+impl ChangeFeed {
+    /// Open a feed over the WAL at `wal_path` for `consumer`, positioned
+    /// just past the last change the consumer acknowledged, or at the start
+    /// of the log for a new consumer
+    pub fn open(wal_path: &str, consumer: &str) -> Result<Self, DurabilityError> {
+        if consumer.is_empty()
+            || consumer == "."
+            || consumer == ".."
+            || consumer.contains(['/', '\\'])
+        {
+            return Err(DurabilityError::IoError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid change feed consumer name: {:?}", consumer),
+            )));
+        }
+        let ack_path = Path::new(&format!("{}.consumers", wal_path)).join(consumer);
+        let acked = read_ack(&ack_path)?;
+
+        let wal = WriteAheadLog::open_for_reading(wal_path)?;
+        let mut feed = ChangeFeed {
+            position: wal.data_start(),
+            wal,
+            consumer: consumer.to_string(),
+            ack_path,
+            acked: acked.map(|(id, _)| id),
+            last_record: None,
+            prepared: HashMap::new(),
+            unacked: VecDeque::new(),
+        };
+
+        // Replay up to the acknowledged record, so transactions prepared
+        // before it and committed after it are still known
+        if let Some((id, fingerprint)) = acked {
+            while feed.position < id.lsn {
+                let Some(record) = feed.next_record()? else {
+                    break;
+                };
+                feed.resolve(record);
+            }
+            if feed.position != id.lsn || feed.last_record.map(|(_, f)| f) != Some(fingerprint) {
+                return Err(feed.moved());
+            }
+        }
+
+        Ok(feed)
+    }
+
+    /// Name the feed's acknowledgements are kept under
+    pub fn consumer(&self) -> &str {
+        &self.consumer
+    }
+
+    /// Last change the consumer acknowledged
+    pub fn acked(&self) -> Option<OperationId> {
+        self.acked
+    }
+
+    /// Changes committed since the last poll, in commit order. Reading stops
+    /// at a record that is incomplete or damaged, and resumes there on the
+    /// next poll.
+    pub fn poll(&mut self) -> Result<Vec<Change>, DurabilityError> {
+        self.check_position()?;
+
+        let mut changes = Vec::new();
+        while let Some(record) = self.next_record()? {
+            let Some((tx_id, operations)) = self.resolve(record) else {
+                continue;
+            };
+            let id = OperationId {
+                lsn: self.position,
+                tx_id,
+            };
+            if let Some((_, fingerprint)) = self.last_record {
+                self.unacked.push_back((id, fingerprint));
+            }
+            changes.push(Change { id, operations });
+        }
+        Ok(changes)
+    }
+
+    /// Record that the consumer has processed every change up to and
+    /// including `id`, which must have been returned by `poll`. The
+    /// acknowledgement is synced before this returns, and acknowledging a
+    /// change at or before the last acknowledged one does nothing.
+    pub fn ack(&mut self, id: OperationId) -> Result<(), DurabilityError> {
+        if self.acked.is_some_and(|acked| id <= acked) {
+            return Ok(());
+        }
+        let Some(index) = self.unacked.iter().position(|(unacked, _)| *unacked == id) else {
+            return Err(DurabilityError::IoError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Change {:?} was not read from this feed", id),
+            )));
+        };
+
+        write_ack(&self.ack_path, id, self.unacked[index].1)?;
+        self.unacked.drain(..=index);
+        self.acked = Some(id);
+        Ok(())
+    }
+
+    /// Read the record at `position`, moving past it. An incomplete or
+    /// damaged record reads as the end of the log.
+    fn next_record(&mut self) -> Result<Option<WalRecord>, DurabilityError> {
+        self.wal.file.seek(SeekFrom::Start(self.position))?;
+        let Ok(Some(record)) = self.wal.read_next_record() else {
+            return Ok(None);
+        };
+        self.last_record = Some((self.position, fingerprint(&record)));
+        self.position = self.wal.file.stream_position()?;
+        Ok(Some(record))
+    }
+
+    /// Check the record the feed last read is still where it was, so a log
+    /// truncated behind the feed is not mistaken for new records
+    fn check_position(&mut self) -> Result<(), DurabilityError> {
+        let Some((start, fingerprint_read)) = self.last_record else {
+            return Ok(());
+        };
+        self.wal.file.seek(SeekFrom::Start(start))?;
+        let unchanged = match self.wal.read_next_record() {
+            Ok(Some(record)) => {
+                fingerprint(&record) == fingerprint_read
+                    && self.wal.file.stream_position()? == self.position
+            }
+            _ => false,
+        };
+        if unchanged { Ok(()) } else { Err(self.moved()) }
+    }
+
+    /// The transaction and operations a record commits, if any, tracking
+    /// prepared transactions until their commit or abort
+    fn resolve(&mut self, record: WalRecord) -> Option<(u64, Vec<Operation>)> {
+        // Records that cannot be decoded are skipped, as replay skips them
+        match Operation::from_record(record).ok()? {
+            Operation::Batch { mut operations } => match operations.first() {
+                Some(Operation::TransactionPrepare { id }) => {
+                    let id = *id;
+                    operations.remove(0);
+                    self.prepared.insert(id, operations);
+                    None
+                }
+                _ => match operations.last() {
+                    Some(Operation::TransactionCommit { id }) => {
+                        let id = *id;
+                        operations.pop();
+                        Some((id, operations))
+                    }
+                    _ => Some((0, operations)),
+                },
+            },
+            Operation::TransactionCommit { id } => {
+                self.prepared.remove(&id).map(|operations| (id, operations))
+            }
+            Operation::TransactionAbort { id } => {
+                self.prepared.remove(&id);
+                None
+            }
+            Operation::TransactionBegin { .. }
+            | Operation::TransactionPrepare { .. }
+            | Operation::CheckpointStart { .. }
+            | Operation::CheckpointEnd { .. } => None,
+            operation => Some((0, vec![operation])),
+        }
+    }
+
+    fn moved(&self) -> DurabilityError {
+        DurabilityError::RecoveryFailed(format!(
+            "WAL {} no longer holds the record before LSN {} that consumer {} read",
+            self.wal.path(),
+            self.position,
+            self.consumer
+        ))
+    }
+}
+
+/// Fingerprint telling apart records that come to share an LSN
+fn fingerprint(record: &WalRecord) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&[record.record_type as u8]);
+    hasher.update(&record.data);
+    hasher.finalize()
+}
+
+/// Read a consumer's acknowledgement, if it has made one
+fn read_ack(path: &Path) -> Result<Option<(OperationId, u32)>, DurabilityError> {
+    let buf = match fs::read(path) {
+        Ok(buf) => buf,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let checksum_at = ACK_SIZE - 4;
+    if buf.len() != ACK_SIZE
+        || crc32fast::hash(&buf[..checksum_at])
+            != u32::from_le_bytes(buf[checksum_at..].try_into().unwrap())
+    {
+        return Err(DurabilityError::DataCorruption(format!(
+            "Change feed acknowledgement {} is damaged",
+            path.display()
+        )));
+    }
+
+    let id = OperationId {
+        lsn: u64::from_le_bytes(buf[0..8].try_into().unwrap()),
+        tx_id: u64::from_le_bytes(buf[8..16].try_into().unwrap()),
+    };
+    let fingerprint = u32::from_le_bytes(buf[16..20].try_into().unwrap());
+    Ok(Some((id, fingerprint)))
+}
+
+/// Replace a consumer's acknowledgement, syncing it before it takes effect
+fn write_ack(path: &Path, id: OperationId, fingerprint: u32) -> Result<(), DurabilityError> {
+    let mut buf = Vec::with_capacity(ACK_SIZE);
+    buf.extend_from_slice(&id.lsn.to_le_bytes());
+    buf.extend_from_slice(&id.tx_id.to_le_bytes());
+    buf.extend_from_slice(&fingerprint.to_le_bytes());
+    let checksum = crc32fast::hash(&buf);
+    buf.extend_from_slice(&checksum.to_le_bytes());
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&buf)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
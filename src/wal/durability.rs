@@ -1,12 +1,17 @@
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
 use std::fs::{self, File};
 use std::io::{self, Seek, SeekFrom};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
 
+use crate::bptree::StorageReference;
+use crate::clock::{Clock, SystemClock};
 use crate::memtable::{Memtable, MemtableError, StringMemtable};
-use crate::sstable::SSTableReader;
+use crate::sstable::{ChecksumKind, KeyProvider, SSTableReader, SSTableWriter};
 use crate::wal::{RecordType, WalError, WalRecord, WriteAheadLog};
 
 /// Error types specific to durability operations
@@ -40,6 +45,9 @@ pub enum DurabilityError {
     TransactionAlreadyCommitted(u64),
     /// Transaction already aborted
     TransactionAlreadyAborted(u64),
+    /// A `DurabilityLevel::Replicated` commit was asked for with no
+    /// `WalReplicator` set
+    ReplicatorNotConfigured,
 }
 
 impl From<WalError> for DurabilityError {
@@ -60,6 +68,29 @@ impl From<MemtableError> for DurabilityError {
     }
 }
 
+/// How far a logged record must get before the call logging it returns.
+/// Levels are ordered from fastest to most durable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DurabilityLevel {
+    /// Buffered in memory and written out with the next record logged at a
+    /// higher level or by `sync`; lost if the process exits first
+    Memory,
+    /// Written to the WAL file; survives the process crashing but may be
+    /// lost on power failure until the next sync
+    WalWritten,
+    /// Synced to disk
+    WalSynced,
+    /// Synced to disk and acknowledged by the `WalReplicator`
+    Replicated,
+}
+
+/// Ships the WAL to replicas for `DurabilityLevel::Replicated` commits
+pub trait WalReplicator: Debug + Send + Sync {
+    /// Copy the WAL at `wal_path` up to `lsn` to the replicas, returning
+    /// once they hold it
+    fn replicate(&self, wal_path: &str, lsn: u64) -> io::Result<()>;
+}
+
 /// Status of a checkpoint
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CheckpointStatus {
@@ -69,8 +100,36 @@ pub enum CheckpointStatus {
     Durable,
 }
 
+/// First byte of a versioned Insert payload. Legacy payloads start with a
+/// UTF-8 key or its NUL separator, and 0xFF is neither.
+const VERSIONED_PAYLOAD_MARKER: u8 = 0xFF;
+
+/// Layout of Insert payloads written by `into_record`: the marker, this
+/// version, the key's length as a little-endian u32, the key and the value
+const INSERT_PAYLOAD_VERSION: u8 = 1;
+
+/// Layout of the file list `into_record` appends to CheckpointEnd records
+/// after the checkpoint ID: this version, the number of files as a
+/// little-endian u32, and then each file's path, entry count, and minimum
+/// and maximum keys
+const CHECKPOINT_FILES_VERSION: u8 = 1;
+
+/// An SSTable written by a checkpoint, as its CheckpointEnd record describes
+/// it, so recovery can check the file is there and whole
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointFile {
+    /// Path of the SSTable
+    pub path: String,
+    /// Number of entries written to it
+    pub entry_count: u64,
+    /// Smallest key written, if any
+    pub min_key: Option<String>,
+    /// Largest key written, if any
+    pub max_key: Option<String>,
+}
+
 /// Operations that can be written to the WAL
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Operation {
     /// Insert a key-value pair
     Insert {
@@ -95,6 +154,9 @@ pub enum Operation {
     CheckpointEnd {
         /// Checkpoint ID
         id: u64,
+        /// SSTables the checkpoint wrote; empty for checkpoints logged
+        /// without them
+        files: Vec<CheckpointFile>,
     },
     /// Begin a transaction
     TransactionBegin {
@@ -116,6 +178,18 @@ pub enum Operation {
         /// Transaction ID
         id: u64,
     },
+    /// Several operations logged as a single record
+    Batch {
+        /// Operations in the order they are applied
+        operations: Vec<Operation>,
+    },
+    /// Remove every key from `start` up to but not including `end`
+    DeleteRange {
+        /// First key removed
+        start: String,
+        /// First key past the removed range
+        end: String,
+    },
 }
 
 impl Operation {
@@ -123,8 +197,11 @@ impl Operation {
     pub fn into_record(self) -> WalRecord {
         match self {
             Operation::Insert { key, value } => {
-                let mut data = key.as_bytes().to_vec();
-                data.push(0);
+                let mut data = Vec::with_capacity(6 + key.len() + value.len());
+                data.push(VERSIONED_PAYLOAD_MARKER);
+                data.push(INSERT_PAYLOAD_VERSION);
+                data.extend_from_slice(&(key.len() as u32).to_le_bytes());
+                data.extend_from_slice(key.as_bytes());
                 data.extend_from_slice(&value);
                 WalRecord::new(RecordType::Insert, data)
             }
@@ -135,8 +212,13 @@ impl Operation {
             Operation::CheckpointStart { id } => {
                 WalRecord::new(RecordType::CheckpointStart, id.to_be_bytes().to_vec())
             }
-            Operation::CheckpointEnd { id } => {
-                WalRecord::new(RecordType::CheckpointEnd, id.to_be_bytes().to_vec())
+            Operation::CheckpointEnd { id, files } => {
+                let mut data = id.to_be_bytes().to_vec();
+                // Records without files keep the layout older logs used
+                if !files.is_empty() {
+                    encode_checkpoint_files(&files, &mut data);
+                }
+                WalRecord::new(RecordType::CheckpointEnd, data)
             }
             Operation::TransactionBegin { id } => {
                 WalRecord::new(RecordType::TransactionBegin, id.to_be_bytes().to_vec())
@@ -150,13 +232,27 @@ impl Operation {
             Operation::TransactionAbort { id } => {
                 WalRecord::new(RecordType::TransactionAbort, id.to_be_bytes().to_vec())
             }
+            Operation::Batch { operations } => {
+                WalRecord::new(RecordType::Batch, encode_batch(operations))
+            }
+            Operation::DeleteRange { start, end } => {
+                let mut data = (start.len() as u32).to_le_bytes().to_vec();
+                data.extend_from_slice(start.as_bytes());
+                data.extend_from_slice(end.as_bytes());
+                WalRecord::new(RecordType::DeleteRange, data)
+            }
         }
     }
 
     /// Convert a WAL record back to an operation
     pub fn from_record(record: WalRecord) -> Result<Self, DurabilityError> {
         match record.record_type {
+            RecordType::Insert if record.data.first() == Some(&VERSIONED_PAYLOAD_MARKER) => {
+                decode_insert(&record.data[1..])
+            }
             RecordType::Insert => {
+                // Logs written before payloads were versioned separate the
+                // key from the value with a NUL byte
                 let key_end = record.data.iter().position(|&b| b == 0).ok_or_else(|| {
                     DurabilityError::RecoveryFailed(
                         "Missing null byte separator in Insert record".to_string(),
@@ -194,7 +290,11 @@ impl Operation {
                     let mut id_bytes = [0u8; 8];
                     id_bytes.copy_from_slice(&record.data[0..8]);
                     let id = u64::from_be_bytes(id_bytes);
-                    Ok(Operation::CheckpointEnd { id })
+                    let files = match record.data.len() {
+                        8 => Vec::new(),
+                        _ => decode_checkpoint_files(&record.data[8..])?,
+                    };
+                    Ok(Operation::CheckpointEnd { id, files })
                 } else {
                     Err(DurabilityError::RecoveryFailed(
                         "Invalid checkpoint end record".to_string(),
@@ -249,6 +349,24 @@ impl Operation {
                     ))
                 }
             }
+            RecordType::Batch => Ok(Operation::Batch {
+                operations: decode_batch(&record.data)?,
+            }),
+            RecordType::DeleteRange => {
+                let start_len = record
+                    .data
+                    .get(..4)
+                    .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+                    .filter(|&len| len <= record.data.len() - 4)
+                    .ok_or_else(|| {
+                        DurabilityError::RecoveryFailed("Invalid delete range record".to_string())
+                    })?;
+                let (start, end) = record.data[4..].split_at(start_len);
+                Ok(Operation::DeleteRange {
+                    start: String::from_utf8_lossy(start).to_string(),
+                    end: String::from_utf8_lossy(end).to_string(),
+                })
+            }
             _ => Err(DurabilityError::RecoveryFailed(format!(
                 "Unknown record type: {:?}",
                 record.record_type
@@ -257,6 +375,179 @@ impl Operation {
     }
 }
 
+/// Decode a versioned Insert payload, after its marker
+fn decode_insert(payload: &[u8]) -> Result<Operation, DurabilityError> {
+    let invalid =
+        |reason: &str| DurabilityError::RecoveryFailed(format!("{} Insert record", reason));
+    let (&version, rest) = payload.split_first().ok_or_else(|| invalid("Truncated"))?;
+    if version != INSERT_PAYLOAD_VERSION {
+        return Err(DurabilityError::RecoveryFailed(format!(
+            "Unsupported Insert record version: {}",
+            version
+        )));
+    }
+    let key_len = rest
+        .get(..4)
+        .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+        .filter(|&len| len <= rest.len() - 4)
+        .ok_or_else(|| invalid("Truncated"))?;
+    let (key, value) = rest[4..].split_at(key_len);
+    let key = String::from_utf8(key.to_vec()).map_err(|_| invalid("Non-UTF-8 key in"))?;
+    Ok(Operation::Insert {
+        key,
+        value: value.to_vec(),
+    })
+}
+
+/// Append the files of a CheckpointEnd record, in the layout
+/// `CHECKPOINT_FILES_VERSION` describes
+fn encode_checkpoint_files(files: &[CheckpointFile], data: &mut Vec<u8>) {
+    let put_bytes = |data: &mut Vec<u8>, bytes: &[u8]| {
+        data.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        data.extend_from_slice(bytes);
+    };
+    // A missing key is flagged rather than written as an empty one, since
+    // the empty string is a valid key
+    let put_key = |data: &mut Vec<u8>, key: &Option<String>| match key {
+        Some(key) => {
+            data.push(1);
+            put_bytes(data, key.as_bytes());
+        }
+        None => data.push(0),
+    };
+
+    data.push(CHECKPOINT_FILES_VERSION);
+    data.extend_from_slice(&(files.len() as u32).to_le_bytes());
+    for file in files {
+        put_bytes(data, file.path.as_bytes());
+        data.extend_from_slice(&file.entry_count.to_le_bytes());
+        put_key(data, &file.min_key);
+        put_key(data, &file.max_key);
+    }
+}
+
+/// Decode the files of a CheckpointEnd record, after its checkpoint ID
+fn decode_checkpoint_files(payload: &[u8]) -> Result<Vec<CheckpointFile>, DurabilityError> {
+    let mut rest = payload;
+    let version = take_bytes(&mut rest, 1)?[0];
+    if version != CHECKPOINT_FILES_VERSION {
+        return Err(DurabilityError::RecoveryFailed(format!(
+            "Unsupported checkpoint end record version: {}",
+            version
+        )));
+    }
+    let count = take_u32(&mut rest)?;
+
+    // Each file takes at least 14 bytes, which bounds a corrupt count
+    let mut files = Vec::with_capacity((count as usize).min(rest.len() / 14));
+    for _ in 0..count {
+        let path = take_string(&mut rest)?;
+        let entry_count = u64::from_le_bytes(take_bytes(&mut rest, 8)?.try_into().unwrap());
+        let min_key = take_key(&mut rest)?;
+        let max_key = take_key(&mut rest)?;
+        files.push(CheckpointFile {
+            path,
+            entry_count,
+            min_key,
+            max_key,
+        });
+    }
+    if !rest.is_empty() {
+        return Err(invalid_checkpoint_end());
+    }
+    Ok(files)
+}
+
+fn invalid_checkpoint_end() -> DurabilityError {
+    DurabilityError::RecoveryFailed("Invalid checkpoint end record".to_string())
+}
+
+/// Split `len` bytes off the front of `rest`
+fn take_bytes<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8], DurabilityError> {
+    if len > rest.len() {
+        return Err(invalid_checkpoint_end());
+    }
+    let (taken, remaining) = rest.split_at(len);
+    *rest = remaining;
+    Ok(taken)
+}
+
+fn take_u32(rest: &mut &[u8]) -> Result<u32, DurabilityError> {
+    Ok(u32::from_le_bytes(take_bytes(rest, 4)?.try_into().unwrap()))
+}
+
+/// Split a length-prefixed UTF-8 string off the front of `rest`
+fn take_string(rest: &mut &[u8]) -> Result<String, DurabilityError> {
+    let len = take_u32(rest)? as usize;
+    String::from_utf8(take_bytes(rest, len)?.to_vec()).map_err(|_| invalid_checkpoint_end())
+}
+
+/// Split a key written by `encode_checkpoint_files` off the front of `rest`
+fn take_key(rest: &mut &[u8]) -> Result<Option<String>, DurabilityError> {
+    match take_bytes(rest, 1)?[0] {
+        0 => Ok(None),
+        1 => take_string(rest).map(Some),
+        _ => Err(invalid_checkpoint_end()),
+    }
+}
+
+/// Encode the operations of a batch as a count, the offset of each
+/// operation from the end of the offsets, and then each operation's record
+/// type followed by its data
+fn encode_batch(operations: Vec<Operation>) -> Vec<u8> {
+    let mut offsets = Vec::with_capacity(operations.len());
+    let mut body = Vec::new();
+    for operation in operations {
+        let record = operation.into_record();
+        offsets.push(body.len() as u32);
+        body.push(record.record_type as u8);
+        body.extend_from_slice(&record.data);
+    }
+
+    let mut data = Vec::with_capacity(4 + offsets.len() * 4 + body.len());
+    data.extend_from_slice(&(offsets.len() as u32).to_le_bytes());
+    for offset in offsets {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(&body);
+    data
+}
+
+/// Decode the operations of a batch written by `encode_batch`. The whole
+/// batch is rejected if any operation in it cannot be decoded.
+fn decode_batch(data: &[u8]) -> Result<Vec<Operation>, DurabilityError> {
+    let invalid = || DurabilityError::RecoveryFailed("Invalid batch record".to_string());
+    let read_u32 = |at: usize| -> Result<usize, DurabilityError> {
+        let bytes = data.get(at..at + 4).ok_or_else(invalid)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()) as usize)
+    };
+
+    let count = read_u32(0)?;
+    let body_start = count
+        .checked_mul(4)
+        .and_then(|len| len.checked_add(4))
+        .filter(|&start| start <= data.len())
+        .ok_or_else(invalid)?;
+    let body = &data[body_start..];
+
+    let mut operations = Vec::with_capacity(count);
+    for i in 0..count {
+        let start = read_u32(4 + i * 4)?;
+        let end = if i + 1 < count {
+            read_u32(8 + i * 4)?
+        } else {
+            body.len()
+        };
+        let op = body
+            .get(start..end)
+            .filter(|op| !op.is_empty())
+            .ok_or_else(invalid)?;
+        let record = WalRecord::new(RecordType::from_u8(op[0]), op[1..].to_vec());
+        operations.push(Operation::from_record(record)?);
+    }
+    Ok(operations)
+}
+
 /// Key-value pair for SSTable writing
 pub struct KeyValuePair {
     pub key: String,
@@ -274,6 +565,9 @@ pub struct CheckpointMetadata {
     pub end_time: Option<u64>,
     /// SSTable path
     pub sstable_path: Option<String>,
+    /// WAL LSN the checkpoint started at; every write logged before it is
+    /// in the checkpoint's SSTable
+    pub applied_lsn: u64,
 }
 
 /// Transaction tracker for active transactions
@@ -283,7 +577,8 @@ pub struct TransactionTracker {
     pub id: u64,
     /// Current status
     pub status: crate::wal::TransactionStatus,
-    /// Operations logged in the transaction
+    /// Operations buffered in the transaction, logged when it is prepared
+    /// or committed
     pub operations: Vec<Operation>,
     /// Start time
     pub start_time: u64,
@@ -293,6 +588,244 @@ pub struct TransactionTracker {
     pub end_time: Option<u64>,
 }
 
+/// Number of replayed WAL records between progress reports
+pub const RECOVERY_PROGRESS_INTERVAL: u64 = 1024;
+
+/// Stage of crash recovery a progress report belongs to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecoveryPhase {
+    /// Loading the latest complete SSTable
+    #[default]
+    LoadingSSTable,
+    /// Replaying WAL records into the recovered memtable
+    ReplayingWal,
+    /// Writing the recovered state out as a new checkpoint
+    WritingCheckpoint,
+    /// Recovery has finished
+    Complete,
+}
+
+/// Progress of crash recovery, passed to the callback of
+/// `recover_from_crash_with_progress`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryProgress {
+    /// Stage recovery is in
+    pub phase: RecoveryPhase,
+    /// File being worked on: the SSTable being loaded or the WAL
+    pub segment: String,
+    /// WAL bytes replayed so far
+    pub bytes_replayed: u64,
+    /// WAL bytes to replay in total
+    pub total_bytes: u64,
+    /// WAL records applied to the recovered memtable
+    pub records_applied: u64,
+    /// WAL records that could not be applied and were skipped
+    pub records_failed: u64,
+}
+
+/// Default bytes of replayed values `recover_from_crash_bounded` holds in
+/// memory before spilling them to disk
+pub const DEFAULT_RECOVERY_CHUNK_BYTES: usize = 16 * 1024 * 1024;
+
+/// Prefix of the temporary SSTables memory-bounded recovery spills replayed
+/// values to
+const RECOVERY_SPILL_PREFIX: &str = "recovery_spill_";
+
+/// What `recover_from_crash_bounded` recovered: where each key's value lies
+/// in the SSTable written for the recovery checkpoint, rather than the
+/// values themselves
+#[derive(Debug, Clone, Default)]
+pub struct RecoveredReferences {
+    /// SSTable holding the recovered state; `None` if nothing was recovered
+    pub sstable_path: Option<PathBuf>,
+    /// Reference to each recovered key's entry in that SSTable, in key order
+    pub entries: BTreeMap<String, StorageReference>,
+}
+
+impl RecoveredReferences {
+    /// Number of keys recovered
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no key was recovered
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Read the value recovered for `key` from disk
+    pub fn value(&self, key: &str) -> Result<Option<Vec<u8>>, DurabilityError> {
+        let Some(reference) = self.entries.get(key) else {
+            return Ok(None);
+        };
+        let reader = SSTableReader::open(&reference.file_path)?;
+        Ok(Some(reader.entry_at(reference.offset as u64)?.1))
+    }
+}
+
+/// Where replayed WAL operations are applied
+trait ReplayTarget {
+    fn insert(&mut self, key: String, value: Vec<u8>) -> Result<(), DurabilityError>;
+    fn remove(&mut self, key: &str) -> Result<(), DurabilityError>;
+    fn clear(&mut self) -> Result<(), DurabilityError>;
+    /// Remove every key in a non-empty range
+    fn delete_range(&mut self, range: Range<String>) -> Result<(), DurabilityError>;
+}
+
+impl ReplayTarget for StringMemtable {
+    fn insert(&mut self, key: String, value: Vec<u8>) -> Result<(), DurabilityError> {
+        Memtable::insert(self, key, value)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), DurabilityError> {
+        Memtable::remove(self, &key.to_string())?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), DurabilityError> {
+        Memtable::clear(self)?;
+        Ok(())
+    }
+
+    fn delete_range(&mut self, range: Range<String>) -> Result<(), DurabilityError> {
+        for (key, _) in self.range(range)? {
+            Memtable::remove(self, &key)?;
+        }
+        Ok(())
+    }
+}
+
+/// Recovered state that keeps a storage reference for each key rather than
+/// its value. Replayed values collect in a memtable, which is written to a
+/// temporary SSTable whenever it reaches `chunk_bytes`.
+struct SpillingRecovery {
+    dir: PathBuf,
+    chunk_bytes: usize,
+    /// Where the value of each key not in `memtable` lies on disk
+    references: BTreeMap<String, StorageReference>,
+    /// Values replayed since the last spill
+    memtable: StringMemtable,
+    /// Temporary SSTables written so far
+    spills: Vec<PathBuf>,
+}
+
+impl SpillingRecovery {
+    /// Start recovering into `dir`, removing any spills an interrupted
+    /// recovery left behind
+    fn new(dir: &Path, chunk_bytes: usize) -> Result<Self, DurabilityError> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(RECOVERY_SPILL_PREFIX))
+            {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(SpillingRecovery {
+            dir: dir.to_path_buf(),
+            chunk_bytes,
+            references: BTreeMap::new(),
+            memtable: StringMemtable::new(usize::MAX),
+            spills: Vec::new(),
+        })
+    }
+
+    /// Write the replayed values out and point at them
+    fn spill(&mut self) -> Result<(), DurabilityError> {
+        let pairs = self.memtable.iter()?;
+        if pairs.is_empty() {
+            return Ok(());
+        }
+        let path = self.dir.join(format!(
+            "{}{}.tmp",
+            RECOVERY_SPILL_PREFIX,
+            self.spills.len()
+        ));
+        let path_str = path.to_string_lossy().to_string();
+        self.spills.push(path);
+        let mut writer = SSTableWriter::new(&path_str, pairs.len(), false, 0.01)?;
+        for (key, value) in &pairs {
+            writer.write_entry(key, value)?;
+        }
+        writer.finalize()?;
+        drop(pairs);
+
+        for entry in entry_references(&path_str)? {
+            let (key, reference) = entry?;
+            self.references.insert(key, reference);
+        }
+        Memtable::clear(&self.memtable)?;
+        Ok(())
+    }
+
+    /// Delete the temporary SSTables
+    fn remove_spills(&mut self) {
+        for path in self.spills.drain(..) {
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Could not remove recovery spill {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+impl ReplayTarget for SpillingRecovery {
+    fn insert(&mut self, key: String, value: Vec<u8>) -> Result<(), DurabilityError> {
+        self.references.remove(&key);
+        Memtable::insert(&self.memtable, key, value)?;
+        if self.memtable.current_size()? >= self.chunk_bytes {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), DurabilityError> {
+        self.references.remove(key);
+        Memtable::remove(&self.memtable, &key.to_string())?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), DurabilityError> {
+        self.references.clear();
+        Memtable::clear(&self.memtable)?;
+        Ok(())
+    }
+
+    fn delete_range(&mut self, range: Range<String>) -> Result<(), DurabilityError> {
+        let keys: Vec<String> = self
+            .references
+            .range(range.clone())
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            self.references.remove(&key);
+        }
+        self.memtable.delete_range(range)
+    }
+}
+
+/// A storage reference to each entry of the SSTable at `path`, in file order
+fn entry_references(
+    path: &str,
+) -> io::Result<impl Iterator<Item = io::Result<(String, StorageReference)>>> {
+    let mut entries = SSTableReader::open(path)?.into_entries()?;
+    let file_path = path.to_string();
+    Ok(
+        std::iter::from_fn(move || entries.next_with_offset()).map(move |entry| {
+            entry.map(|(key, offset, _)| {
+                let reference = StorageReference {
+                    file_path: file_path.clone(),
+                    offset: offset as usize,
+                    is_tombstone: false,
+                };
+                (key, reference)
+            })
+        }),
+    )
+}
+
 /// Manager for durability and crash recovery
 pub struct DurabilityManager {
     /// WAL for logging operations
@@ -303,10 +836,21 @@ pub struct DurabilityManager {
     checkpoint_registry: HashMap<u64, CheckpointMetadata>,
     /// Latest flushed checkpoint ID
     latest_flushed_checkpoint: AtomicU64,
+    /// Last checkpoint ID handed out; IDs only increase, even when two
+    /// checkpoints begin within the same second
+    last_checkpoint_id: u64,
+    /// Source of checkpoint IDs and timestamps
+    clock: Arc<dyn Clock>,
     /// Transaction registry
     transaction_registry: HashMap<u64, TransactionTracker>,
     /// Next transaction ID
     next_transaction_id: AtomicU64,
+    /// Records logged at `DurabilityLevel::Memory` not yet written out
+    pending: Vec<u8>,
+    /// Ships the WAL to replicas for `DurabilityLevel::Replicated`
+    replicator: Option<Arc<dyn WalReplicator>>,
+    /// Provider of the keys encrypted SSTables are read with
+    key_provider: Option<Arc<dyn KeyProvider>>,
     /// Manifest file path
     ///
     #[allow(dead_code)]
@@ -316,43 +860,159 @@ pub struct DurabilityManager {
 impl DurabilityManager {
     /// Create a new durability manager with transaction support
     pub fn new(wal_path: &str, sstable_dir: &str) -> Result<Self, DurabilityError> {
+        Self::new_with_checksum_kind(wal_path, sstable_dir, ChecksumKind::Crc32)
+    }
+
+    /// Create a new durability manager whose WAL, if it has to be created,
+    /// checksums its records with `kind`. An existing WAL keeps the kind
+    /// recorded in its header.
+    pub fn new_with_checksum_kind(
+        wal_path: &str,
+        sstable_dir: &str,
+        kind: ChecksumKind,
+    ) -> Result<Self, DurabilityError> {
         // Create directories if they don't exist
         fs::create_dir_all(sstable_dir)?;
 
         let wal_dir = Path::new(wal_path).parent().unwrap_or(Path::new("."));
         fs::create_dir_all(wal_dir)?;
 
-        let wal = WriteAheadLog::new(wal_path)?;
+        let wal = WriteAheadLog::new_with_checksum_kind(wal_path, kind)?;
         let manifest_path = Path::new(sstable_dir).join("MANIFEST");
 
-        let manager = Self {
+        let mut manager = Self {
             wal,
             sstable_dir: PathBuf::from(sstable_dir),
             checkpoint_registry: HashMap::new(),
             latest_flushed_checkpoint: AtomicU64::new(0),
+            last_checkpoint_id: 0,
+            clock: Arc::new(SystemClock),
             transaction_registry: HashMap::new(),
             next_transaction_id: AtomicU64::new(1),
+            pending: Vec::new(),
+            replicator: None,
+            key_provider: None,
             manifest_path,
         };
 
+        // Continue past the checkpoints already written
+        manager.last_checkpoint_id = manager
+            .find_sstables()?
+            .iter()
+            .filter_map(|path| manager.extract_checkpoint_id(path).ok())
+            .max()
+            .unwrap_or(0);
+
         Ok(manager)
     }
 
+    /// Read time from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Set the replicator `DurabilityLevel::Replicated` commits wait on
+    pub fn with_replicator(mut self, replicator: impl WalReplicator + 'static) -> Self {
+        self.replicator = Some(Arc::new(replicator));
+        self
+    }
+
+    /// Read encrypted SSTables with the keys of `provider`
+    pub fn with_key_provider(mut self, provider: Arc<dyn KeyProvider>) -> Self {
+        self.key_provider = Some(provider);
+        self
+    }
+
+    /// Open the SSTable at `path`, with the key provider if one is set
+    fn open_sstable(&self, path: &str) -> io::Result<SSTableReader> {
+        SSTableReader::open_with(path, None, self.key_provider.as_deref())
+    }
+
     /// Log an operation to the WAL and ensure it's durable
     pub fn log_operation(&mut self, operation: Operation) -> Result<(), DurabilityError> {
-        let record = operation.into_record();
-        self.wal.append_and_sync(record)?;
+        self.log_operation_with_sync(operation, true)
+    }
+
+    /// Log an operation, syncing the WAL to disk only if `sync` is set.
+    ///
+    /// Unsynced records reach the OS but may be lost on power failure until
+    /// the next sync.
+    pub fn log_operation_with_sync(
+        &mut self,
+        operation: Operation,
+        sync: bool,
+    ) -> Result<(), DurabilityError> {
+        let level = if sync {
+            DurabilityLevel::WalSynced
+        } else {
+            DurabilityLevel::WalWritten
+        };
+        self.log_operation_with_durability(operation, level)
+    }
+
+    /// Log an operation, returning once it has reached `level`. Records
+    /// always reach the WAL in the order they were logged.
+    pub fn log_operation_with_durability(
+        &mut self,
+        operation: Operation,
+        level: DurabilityLevel,
+    ) -> Result<(), DurabilityError> {
+        // Refuse a level that cannot be reached before logging anything
+        if level == DurabilityLevel::Replicated && self.replicator.is_none() {
+            return Err(DurabilityError::ReplicatorNotConfigured);
+        }
+        self.pending
+            .extend_from_slice(&operation.into_record().serialize_with(self.wal.checksum_kind())?);
+        if level == DurabilityLevel::Memory {
+            return Ok(());
+        }
+        self.write_pending()?;
+        if level == DurabilityLevel::WalWritten {
+            return Ok(());
+        }
+        self.wal.sync()?;
+        if level == DurabilityLevel::Replicated {
+            self.replicate()?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the replicator to hold the WAL up to its end
+    fn replicate(&self) -> Result<(), DurabilityError> {
+        let replicator = self
+            .replicator
+            .as_ref()
+            .ok_or(DurabilityError::ReplicatorNotConfigured)?;
+        replicator.replicate(self.wal.path(), self.wal.end_lsn()?)?;
+        Ok(())
+    }
+
+    /// Write out records buffered at `DurabilityLevel::Memory`
+    fn write_pending(&mut self) -> Result<(), DurabilityError> {
+        if !self.pending.is_empty() {
+            self.wal.append(&self.pending)?;
+            self.pending.clear();
+        }
+        Ok(())
+    }
+
+    /// Force records logged without syncing, including those buffered in
+    /// memory, to disk
+    pub fn sync(&mut self) -> Result<(), DurabilityError> {
+        self.write_pending()?;
+        self.wal.sync()?;
         Ok(())
     }
 
     /// Begin a checkpoint - returns the checkpoint ID
     pub fn begin_checkpoint(&mut self) -> Result<u64, DurabilityError> {
-        let checkpoint_id = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let checkpoint_id = self.clock.now_secs().max(self.last_checkpoint_id + 1);
+        self.last_checkpoint_id = checkpoint_id;
 
-        // Log checkpoint start
+        // Log checkpoint start, noting where the log stood before it
+        self.write_pending()?;
+        let applied_lsn = self.wal.end_lsn()?;
         self.log_operation(Operation::CheckpointStart { id: checkpoint_id })?;
 
         // Register checkpoint
@@ -363,19 +1023,109 @@ impl DurabilityManager {
                 start_time: checkpoint_id,
                 end_time: None,
                 sstable_path: None,
+                applied_lsn,
             },
         );
 
         Ok(checkpoint_id)
     }
 
+    /// WAL LSN a checkpoint started at, to be recorded in its SSTable with
+    /// `SSTableWriter::set_applied_lsn`
+    pub fn checkpoint_lsn(&self, checkpoint_id: u64) -> Option<u64> {
+        self.checkpoint_registry
+            .get(&checkpoint_id)
+            .map(|checkpoint| checkpoint.applied_lsn)
+    }
+
     /// End a checkpoint after SSTable has been written
     pub fn end_checkpoint(&mut self, checkpoint_id: u64) -> Result<(), DurabilityError> {
-        // Log checkpoint end
-        self.log_operation(Operation::CheckpointEnd { id: checkpoint_id })?;
+        self.end_checkpoint_with_files(checkpoint_id, Vec::new())
+    }
+
+    /// End a checkpoint, recording the SSTables it wrote so recovery can
+    /// check each is there and whole before trusting it
+    pub fn end_checkpoint_with_files(
+        &mut self,
+        checkpoint_id: u64,
+        files: Vec<CheckpointFile>,
+    ) -> Result<(), DurabilityError> {
+        self.log_operation(Operation::CheckpointEnd {
+            id: checkpoint_id,
+            files,
+        })?;
         Ok(())
     }
 
+    /// Files recorded by the CheckpointEnd records in the WAL, by
+    /// checkpoint ID. Reading stops at the first record that cannot be read.
+    pub fn logged_checkpoint_files(
+        &mut self,
+    ) -> Result<HashMap<u64, Vec<CheckpointFile>>, DurabilityError> {
+        self.wal.file.seek(SeekFrom::Start(self.wal.data_start()))?;
+
+        let mut logged = HashMap::new();
+        while let Ok(Some(record)) = self.wal.read_next_record() {
+            if let Ok(Operation::CheckpointEnd { id, files }) = Operation::from_record(record)
+                && !files.is_empty()
+            {
+                logged.insert(id, files);
+            }
+        }
+        Ok(logged)
+    }
+
+    /// Describe an SSTable as a CheckpointEnd record would, reading every
+    /// entry. Fails if any entry cannot be read.
+    pub fn describe_checkpoint_file(
+        &self,
+        sstable_path: &str,
+    ) -> Result<CheckpointFile, DurabilityError> {
+        let mut file = CheckpointFile {
+            path: sstable_path.to_string(),
+            entry_count: 0,
+            min_key: None,
+            max_key: None,
+        };
+        for entry in self.open_sstable(sstable_path)?.into_entries()? {
+            let (key, _) = entry?;
+            file.entry_count += 1;
+            if file.min_key.as_ref().is_none_or(|min| key < *min) {
+                file.min_key = Some(key.clone());
+            }
+            if file.max_key.as_ref().is_none_or(|max| key > *max) {
+                file.max_key = Some(key);
+            }
+        }
+        Ok(file)
+    }
+
+    /// Whether the SSTable a CheckpointEnd record describes exists and holds
+    /// what the record says was written to it
+    pub fn verify_checkpoint_file(&self, file: &CheckpointFile) -> bool {
+        Path::new(&file.path).is_file()
+            && self
+                .describe_checkpoint_file(&file.path)
+                .is_ok_and(|found| found == *file)
+    }
+
+    /// IDs of checkpoints whose start is logged in the WAL with no matching
+    /// end, oldest first. Reading stops at the first record that cannot be
+    /// read.
+    pub fn unfinished_checkpoints(&mut self) -> Result<Vec<u64>, DurabilityError> {
+        self.wal.file.seek(SeekFrom::Start(self.wal.data_start()))?;
+
+        let mut unfinished = Vec::new();
+        while let Ok(Some(record)) = self.wal.read_next_record() {
+            match Operation::from_record(record) {
+                Ok(Operation::CheckpointStart { id }) => unfinished.push(id),
+                Ok(Operation::CheckpointEnd { id, .. }) => unfinished.retain(|&started| started != id),
+                _ => {}
+            }
+        }
+        Ok(unfinished)
+    }
+
     /// Register a durable checkpoint after SSTable is safely on disk
     pub fn register_durable_checkpoint(
         &mut self,
@@ -384,6 +1134,7 @@ impl DurabilityManager {
     ) -> Result<(), DurabilityError> {
         // Verify SSTable exists and is valid
         if self.verify_sstable_integrity(sstable_path)? {
+            let applied_lsn = self.checkpoint_lsn(checkpoint_id).unwrap_or(0);
             self.checkpoint_registry.insert(
                 checkpoint_id,
                 CheckpointMetadata {
@@ -391,6 +1142,7 @@ impl DurabilityManager {
                     start_time: checkpoint_id,
                     end_time: None,
                     sstable_path: Some(sstable_path.to_string()),
+                    applied_lsn,
                 },
             );
             self.latest_flushed_checkpoint
@@ -413,13 +1165,29 @@ impl DurabilityManager {
         // Truncate WAL
         self.wal.truncate(checkpoint_position)?;
 
+        // Log again the prepares truncation cut off, so the commit of a
+        // transaction still prepared can be replayed
+        let logged = self.unresolved_prepares(checkpoint_position)?;
+        let mut cut: Vec<u64> = self
+            .transaction_registry
+            .values()
+            .filter(|tracker| tracker.status == crate::wal::TransactionStatus::Prepared)
+            .map(|tracker| tracker.id)
+            .filter(|id| !logged.contains_key(id))
+            .collect();
+        cut.sort_unstable();
+        for tx_id in cut {
+            let record = self.prepare_record(tx_id);
+            self.log_operation(record)?;
+        }
+
         Ok(())
     }
 
     /// Verify SSTable integrity by checking all checksums
     pub fn verify_sstable_integrity(&self, sstable_path: &str) -> Result<bool, DurabilityError> {
         // Open the SSTable reader - this will automatically verify the header checksum
-        let _sstable_reader = match SSTableReader::open(sstable_path) {
+        let _sstable_reader = match self.open_sstable(sstable_path) {
             Ok(reader) => reader,
             Err(e) => {
                 return Err(DurabilityError::IoError(e));
@@ -442,53 +1210,49 @@ impl DurabilityManager {
         &self,
         sstable_path: &str,
     ) -> Result<bool, DurabilityError> {
-        // Open the SSTable
-        let mut reader = match SSTableReader::open(sstable_path) {
-            Ok(reader) => reader,
-            Err(e) => {
-                return Err(DurabilityError::IoError(e));
-            }
-        };
-
-        // Sample a few keys to verify their checksums
-        // In a production environment, this would scan all entries or a larger sample
-        let mut _verified_count = 0;
-        let keys_to_verify = vec!["test_key1", "test_key2", "test_key3"];
-
-        for key in keys_to_verify {
-            match reader.get(key) {
-                Ok(_) => {
-                    // If get() succeeds, the checksum verification passed
-                    _verified_count += 1;
-                }
-                Err(e) => {
-                    if e.kind() == io::ErrorKind::InvalidData {
-                        // This indicates a checksum verification failure
-                        return Err(DurabilityError::DataCorruption(format!(
-                            "Data corruption detected in SSTable {}: {}",
-                            sstable_path, e
-                        )));
-                    }
-                    // Other errors are ignored as the key might not exist
-                }
-            }
+        let reader = self.open_sstable(sstable_path)?;
+
+        // Check every entry against its checksums
+        let report = reader.verify_all()?;
+        if let Some(offset) = report.first_corrupt_offset {
+            return Err(DurabilityError::DataCorruption(format!(
+                "Data corruption detected in SSTable {} at offset {}: {}",
+                sstable_path,
+                offset,
+                report.problem.unwrap_or_default()
+            )));
         }
 
-        // If we could read at least one key or the file is empty, consider it valid
         Ok(true)
     }
 
-    /// Write memtable data to an SSTable file atomically
+    /// Write memtable data to an SSTable file atomically. The pairs may come
+    /// in any order but their keys must be distinct.
     pub fn write_sstable_atomically(
         &self,
         memtable_data: &[KeyValuePair],
         checkpoint_id: u64,
+    ) -> Result<String, DurabilityError> {
+        let mut pairs: Vec<&KeyValuePair> = memtable_data.iter().collect();
+        pairs.sort_by(|a, b| a.key.cmp(&b.key));
+        self.write_sstable_entries(
+            pairs.len(),
+            pairs.into_iter().map(|pair| Ok((&pair.key, &pair.value))),
+            checkpoint_id,
+        )
+    }
+
+    /// Write `entry_count` entries, in key order, to a new SSTable for a
+    /// checkpoint, as `write_sstable_atomically` does. Entries are taken
+    /// one at a time, so they need not all be in memory.
+    fn write_sstable_entries<K: AsRef<str>, V: AsRef<[u8]>>(
+        &self,
+        entry_count: usize,
+        entries: impl IntoIterator<Item = Result<(K, V), DurabilityError>>,
+        checkpoint_id: u64,
     ) -> Result<String, DurabilityError> {
         // Generate temporary SSTable path
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+        let timestamp = self.clock.now_secs();
 
         // Include the checkpoint ID in the filename
         let temp_path = format!(
@@ -509,12 +1273,15 @@ impl DurabilityManager {
         fs::create_dir_all(&self.sstable_dir)?;
 
         // Create new SSTable with checksums
-        use crate::sstable::SSTableWriter;
-        let mut writer = SSTableWriter::new(&temp_path, memtable_data.len(), true, 0.01)?;
+        let mut writer = SSTableWriter::new(&temp_path, entry_count, true, 0.01)?;
+        if let Some(lsn) = self.checkpoint_lsn(checkpoint_id) {
+            writer.set_applied_lsn(lsn);
+        }
 
         // Write all key-value pairs
-        for pair in memtable_data {
-            writer.write_entry(&pair.key, &pair.value)?;
+        for entry in entries {
+            let (key, value) = entry?;
+            writer.write_entry(key.as_ref(), value.as_ref())?;
         }
 
         // Finalize the SSTable
@@ -545,12 +1312,12 @@ impl DurabilityManager {
             let entry = entry?;
             let path = entry.path();
 
-            if path.is_file() && path.extension().is_some_and(|ext| ext == "sst") {
-                if let Some(file_name) = path.file_name().and_then(|s| s.to_str()) {
-                    if file_name.starts_with("sstable_") {
-                        sstables.push(path);
-                    }
-                }
+            if path.is_file()
+                && path.extension().is_some_and(|ext| ext == "sst")
+                && let Some(file_name) = path.file_name().and_then(|s| s.to_str())
+                && file_name.starts_with("sstable_")
+            {
+                sstables.push(path);
             }
         }
 
@@ -578,16 +1345,43 @@ impl DurabilityManager {
         Ok(None)
     }
 
+    /// Find the latest valid SSTable that recovery can load. An SSTable a
+    /// CheckpointEnd record in the WAL describes is passed over unless it
+    /// still holds what the record says was written, so a file left partial
+    /// or damaged after its checkpoint ended is not loaded.
+    fn find_latest_recoverable_sstable(&mut self) -> Result<Option<PathBuf>, DurabilityError> {
+        let logged = self.logged_checkpoint_files()?;
+
+        for sstable in self.find_sstables()?.iter().rev() {
+            let described = logged
+                .values()
+                .flatten()
+                .find(|file| Path::new(&file.path) == sstable);
+            if let Some(file) = described
+                && !self.verify_checkpoint_file(file)
+            {
+                warn!("Skipping incomplete checkpoint SSTable: {:?}", sstable);
+                continue;
+            }
+            if self.verify_sstable_integrity(sstable.to_str().unwrap())? {
+                return Ok(Some(sstable.clone()));
+            }
+        }
+
+        Ok(None)
+    }
+
     /// Extract checkpoint ID from SSTable path
     pub fn extract_checkpoint_id(&self, sstable_path: &Path) -> Result<u64, DurabilityError> {
-        if let Some(file_name) = sstable_path.file_name().and_then(|s| s.to_str()) {
-            if file_name.starts_with("sstable_") && file_name.ends_with(".sst") {
-                let parts: Vec<&str> = file_name["sstable_".len()..file_name.len() - 4]
-                    .split('_')
-                    .collect();
-                if let Ok(id) = parts[0].parse::<u64>() {
-                    return Ok(id);
-                }
+        if let Some(file_name) = sstable_path.file_name().and_then(|s| s.to_str())
+            && file_name.starts_with("sstable_")
+            && file_name.ends_with(".sst")
+        {
+            let parts: Vec<&str> = file_name["sstable_".len()..file_name.len() - 4]
+                .split('_')
+                .collect();
+            if let Ok(id) = parts[0].parse::<u64>() {
+                return Ok(id);
             }
         }
 
@@ -601,76 +1395,130 @@ impl DurabilityManager {
         &self,
         sstable_path: &Path,
     ) -> Result<StringMemtable, DurabilityError> {
-        use std::io::{Read, Seek, SeekFrom};
-
         let memtable = StringMemtable::new(usize::MAX); // No size limit during recovery
         let reader = SSTableReader::open(sstable_path.to_str().unwrap())?;
 
-        // Get basic information from the reader
-        let entry_count = reader.entry_count();
-
-        // Open the file directly for manual reading
-        let mut file = File::open(sstable_path)?;
-
-        // Skip to where data begins
-        file.seek(SeekFrom::Start(crate::sstable::HEADER_SIZE as u64))?;
-
-        // Read each entry
-        for _ in 0..entry_count {
-            // Read key length
-            let mut key_len_buf = [0u8; 4];
-            if file.read_exact(&mut key_len_buf).is_err() {
-                break; // Exit gracefully on error
-            }
-            let key_len = u32::from_le_bytes(key_len_buf) as usize;
-
-            // Safety check for key length
-            if key_len == 0 || key_len > 10_000_000 {
-                break; // Suspicious key length, stop reading
-            }
-
-            // Read key
-            let mut key_buf = vec![0u8; key_len];
-            if file.read_exact(&mut key_buf).is_err() {
+        // The reader knows each version's layout; stop at the first entry
+        // that cannot be read
+        for entry in reader.into_entries()? {
+            let Ok((key, value)) = entry else {
                 break;
+            };
+            if memtable.insert(key, value).is_err() {
+                break; // Stop if we can't insert
             }
+        }
 
-            // Only try to parse valid UTF-8 keys
-            let key = match String::from_utf8(key_buf) {
-                Ok(k) => k,
-                Err(_) => break, // Invalid UTF-8, stop reading
-            };
+        Ok(memtable)
+    }
 
-            // Read value length
-            let mut value_len_buf = [0u8; 4];
-            if file.read_exact(&mut value_len_buf).is_err() {
-                break;
-            }
-            let value_len = u32::from_le_bytes(value_len_buf) as usize;
+    /// Replay WAL records from `start` to the end of the log into a
+    /// memtable, returning how many were applied. Records that fail to apply
+    /// are skipped.
+    fn replay_wal(
+        &mut self,
+        memtable: &mut impl ReplayTarget,
+        start: u64,
+        progress: &mut impl FnMut(&RecoveryProgress),
+    ) -> Result<u64, DurabilityError> {
+        let total_bytes = self.wal.file.metadata()?.len().saturating_sub(start);
+
+        // Operations of prepared transactions, held until their outcome.
+        // Those prepared before `start` are not in the SSTable recovery
+        // loaded, so their commit can still be replayed.
+        let mut prepared = self.unresolved_prepares(start)?;
+        self.wal.file.seek(SeekFrom::Start(start))?;
+        let mut update = RecoveryProgress {
+            phase: RecoveryPhase::ReplayingWal,
+            segment: self.wal.path().to_string(),
+            total_bytes,
+            ..RecoveryProgress::default()
+        };
+        progress(&update);
 
-            // Safety check for value length
-            if value_len > 50_000_000 {
-                break; // Suspicious value length, stop reading
+        while let Ok(Some(record)) = self.wal.read_next_record() {
+            match Self::replay_record(memtable, record, &mut prepared) {
+                Ok(_) => {
+                    update.records_applied += 1;
+                }
+                Err(e) => {
+                    warn!("Error replaying WAL record: {:?}", e);
+                    // Continue processing other records even if one fails
+                    update.records_failed += 1;
+                }
             }
 
-            // Read value
-            let mut value = vec![0u8; value_len];
-            if file.read_exact(&mut value).is_err() {
-                break;
+            let records = update.records_applied + update.records_failed;
+            if records.is_multiple_of(RECOVERY_PROGRESS_INTERVAL) {
+                update.bytes_replayed = self.wal.file.stream_position()?.saturating_sub(start);
+                progress(&update);
             }
+        }
 
-            // Skip checksum (4 bytes)
-            if file.seek(SeekFrom::Current(4)).is_err() {
-                break;
-            }
+        update.bytes_replayed = self.wal.file.stream_position()?.saturating_sub(start);
+        progress(&update);
+        Ok(update.records_applied)
+    }
 
-            // Insert into memtable
-            if memtable.insert(key, value).is_err() {
-                break; // Stop if we can't insert
+    /// Operations of the transactions prepared in the WAL before `end`
+    /// whose commit or abort is not logged before it, by transaction ID.
+    /// Reading stops at the first record that cannot be read.
+    fn unresolved_prepares(
+        &mut self,
+        end: u64,
+    ) -> Result<HashMap<u64, Vec<Operation>>, DurabilityError> {
+        self.wal.file.seek(SeekFrom::Start(self.wal.data_start()))?;
+
+        let mut prepared = HashMap::new();
+        while self.wal.file.stream_position()? < end
+            && let Ok(Some(record)) = self.wal.read_next_record()
+        {
+            match Operation::from_record(record) {
+                Ok(Operation::Batch { operations }) => {
+                    if let Some((Operation::TransactionPrepare { id }, staged)) =
+                        operations.split_first()
+                    {
+                        prepared.insert(*id, staged.to_vec());
+                    }
+                }
+                Ok(Operation::TransactionCommit { id } | Operation::TransactionAbort { id }) => {
+                    prepared.remove(&id);
+                }
+                _ => {}
             }
         }
+        Ok(prepared)
+    }
 
-        Ok(memtable)
+    /// Replay one WAL record into a memtable. The operations of a prepared
+    /// transaction are held in `prepared` and applied only once its commit
+    /// is read; an abort, or a log that ends first, discards them. A commit
+    /// with no prepare held for it fails.
+    fn replay_record(
+        memtable: &mut impl ReplayTarget,
+        record: WalRecord,
+        prepared: &mut HashMap<u64, Vec<Operation>>,
+    ) -> Result<(), DurabilityError> {
+        match Operation::from_record(record)? {
+            Operation::Batch { operations } => match operations.split_first() {
+                Some((Operation::TransactionPrepare { id }, staged)) => {
+                    prepared.insert(*id, staged.to_vec());
+                    Ok(())
+                }
+                _ => Self::apply_operation_to_memtable(memtable, Operation::Batch { operations }),
+            },
+            Operation::TransactionCommit { id } => match prepared.remove(&id) {
+                Some(operations) => {
+                    Self::apply_operation_to_memtable(memtable, Operation::Batch { operations })
+                }
+                None => Err(DurabilityError::TransactionNotPrepared(id)),
+            },
+            Operation::TransactionAbort { id } => {
+                prepared.remove(&id);
+                Ok(())
+            }
+            operation => Self::apply_operation_to_memtable(memtable, operation),
+        }
     }
 
     /// Apply a WAL record to a memtable
@@ -680,7 +1528,15 @@ impl DurabilityManager {
         record: WalRecord,
     ) -> Result<(), DurabilityError> {
         let operation = Operation::from_record(record)?;
+        Self::apply_operation_to_memtable(memtable, operation)
+    }
 
+    /// Apply a decoded operation to a memtable. The operations of a batch
+    /// were all decoded with it, so a damaged batch applies none of them.
+    fn apply_operation_to_memtable(
+        memtable: &mut impl ReplayTarget,
+        operation: Operation,
+    ) -> Result<(), DurabilityError> {
         match operation {
             Operation::Insert { key, value } => {
                 memtable.insert(key, value)?;
@@ -697,6 +1553,16 @@ impl DurabilityManager {
             | Operation::TransactionPrepare { .. }
             | Operation::TransactionCommit { .. }
             | Operation::TransactionAbort { .. } => {}
+            Operation::Batch { operations } => {
+                for operation in operations {
+                    Self::apply_operation_to_memtable(memtable, operation)?;
+                }
+            }
+            // An empty range removes nothing, and would fail the range lookup
+            Operation::DeleteRange { start, end } if start < end => {
+                memtable.delete_range(start..end)?;
+            }
+            Operation::DeleteRange { .. } => {}
         }
 
         Ok(())
@@ -704,94 +1570,44 @@ impl DurabilityManager {
 
     /// Recover from a crash with enhanced integrity checking
     pub fn recover_from_crash(&mut self) -> Result<StringMemtable, DurabilityError> {
-        println!("Starting crash recovery process...");
-
-        // Find all SSTable files in the SSTable directory
-        let _sstable_files = self.find_sstables()?;
-
-        // Find the latest complete SSTable
-        let latest_sstable = self.find_latest_complete_sstable()?;
-
-        // Create a new memtable for recovery
-        let mut memtable = StringMemtable::new(u64::MAX as usize);
-
-        // If we found a valid SSTable, load it into the memtable
-        if let Some(sstable_path) = latest_sstable {
-            println!("Found latest SSTable: {:?}", sstable_path);
-
-            // Verify the SSTable's integrity before loading it
-            if !self.verify_sstable_integrity(&sstable_path.to_string_lossy())? {
-                return Err(DurabilityError::SsTableIntegrityCheckFailed);
-            }
-
-            // Perform enhanced data integrity check
-            if !self.verify_sstable_data_integrity(&sstable_path.to_string_lossy())? {
-                return Err(DurabilityError::DataCorruption(format!(
-                    "Data corruption detected in SSTable {}",
-                    sstable_path.display()
-                )));
-            }
-
-            // Extract the checkpoint ID from the SSTable filename
-            let checkpoint_id = self.extract_checkpoint_id(&sstable_path)?;
-            println!("Loading from checkpoint: {}", checkpoint_id);
-
-            // Load the SSTable into the memtable
-            memtable = self.load_from_sstable(&sstable_path)?;
-
-            // Update the latest flushed checkpoint ID
-            self.latest_flushed_checkpoint
-                .store(checkpoint_id, Ordering::SeqCst);
+        self.recover_from_crash_with_progress(|_| {})
+    }
 
-            // Get the position in the WAL for the checkpoint
-            // to truncate older records
-            if let Ok(checkpoint_position) = self.wal.get_checkpoint_position(checkpoint_id) {
-                // Apply any WAL records that came after this checkpoint
-                // Reset WAL position to the checkpoint
-                self.wal.file.seek(SeekFrom::Start(checkpoint_position))?;
-
-                // Read and apply WAL records after the checkpoint
-                let mut replay_count = 0;
-                while let Ok(Some(record)) = self.wal.read_next_record() {
-                    match self.apply_wal_record_to_memtable(&mut memtable, record) {
-                        Ok(_) => {
-                            replay_count += 1;
-                        }
-                        Err(e) => {
-                            println!("Error replaying WAL record: {:?}", e);
-                            // Continue processing other records even if one fails
-                        }
-                    }
-                }
-                println!("Replayed {} WAL records after checkpoint", replay_count);
-            } else {
-                println!("Could not find checkpoint position in WAL");
-            }
-        } else {
-            println!("No valid SSTable found, replaying entire WAL");
+    /// Recover from a crash, reporting progress as it goes.
+    ///
+    /// `progress` is called when each phase starts, every
+    /// `RECOVERY_PROGRESS_INTERVAL` replayed WAL records, and once recovery
+    /// is complete, so callers can show that a long recovery is not hung.
+    /// Forward the updates into a channel to consume them as a stream.
+    pub fn recover_from_crash_with_progress(
+        &mut self,
+        mut progress: impl FnMut(&RecoveryProgress),
+    ) -> Result<StringMemtable, DurabilityError> {
+        info!("Starting crash recovery process...");
 
-            // No valid SSTable found, replay the entire WAL
-            self.wal.file.seek(SeekFrom::Start(0))?;
+        let (latest_sstable, replay_start) = self.find_recovery_start(&mut progress)?;
 
-            // Read all records from the WAL and apply them to the memtable
-            let mut replay_count = 0;
-            while let Ok(Some(record)) = self.wal.read_next_record() {
-                match self.apply_wal_record_to_memtable(&mut memtable, record) {
-                    Ok(_) => {
-                        replay_count += 1;
-                    }
-                    Err(e) => {
-                        println!("Error replaying WAL record: {:?}", e);
-                        // Continue processing other records even if one fails
-                    }
-                }
-            }
-            println!("Replayed {} WAL records from scratch", replay_count);
+        // Load the SSTable into a memtable with no size limit
+        let mut memtable = match &latest_sstable {
+            Some(sstable_path) => self.load_from_sstable(sstable_path)?,
+            None => StringMemtable::new(usize::MAX),
+        };
+        if let Some(start) = replay_start {
+            let replay_count = self.replay_wal(&mut memtable, start, &mut progress)?;
+            info!(
+                "Replayed {} WAL records from offset {}",
+                replay_count, start
+            );
         }
 
         // Create a new checkpoint after recovery to ensure consistency
+        progress(&RecoveryProgress {
+            phase: RecoveryPhase::WritingCheckpoint,
+            segment: self.wal.path().to_string(),
+            ..RecoveryProgress::default()
+        });
         let recovery_checkpoint_id = self.begin_checkpoint()?;
-        println!("Created recovery checkpoint: {}", recovery_checkpoint_id);
+        info!("Created recovery checkpoint: {}", recovery_checkpoint_id);
 
         // Write the recovered state to an SSTable
         let recovered_pairs: Vec<KeyValuePair> = memtable
@@ -804,22 +1620,227 @@ impl DurabilityManager {
         if !recovered_pairs.is_empty() {
             let new_sstable_path =
                 self.write_sstable_atomically(&recovered_pairs, recovery_checkpoint_id)?;
-            println!("Written recovered state to SSTable: {}", new_sstable_path);
-
-            // Mark the recovery checkpoint as durable
+            info!("Written recovered state to SSTable: {}", new_sstable_path);
+            self.end_checkpoint_with_files(
+                recovery_checkpoint_id,
+                vec![CheckpointFile {
+                    path: new_sstable_path.clone(),
+                    entry_count: recovered_pairs.len() as u64,
+                    min_key: recovered_pairs.first().map(|pair| pair.key.clone()),
+                    max_key: recovered_pairs.last().map(|pair| pair.key.clone()),
+                }],
+            )?;
+
+            // Mark the recovery checkpoint as durable, which also truncates
+            // the WAL at it
             self.register_durable_checkpoint(recovery_checkpoint_id, &new_sstable_path)?;
-            println!("Registered durable recovery checkpoint");
-
-            // Truncate the WAL now that we have a new recovery checkpoint
-            self.truncate_wal_at_checkpoint(recovery_checkpoint_id)?;
-            println!("Truncated WAL at recovery checkpoint");
+            info!("Registered durable recovery checkpoint");
         }
 
-        println!("Crash recovery complete");
+        info!("Crash recovery complete");
+        progress(&RecoveryProgress {
+            phase: RecoveryPhase::Complete,
+            segment: self.wal.path().to_string(),
+            ..RecoveryProgress::default()
+        });
 
         Ok(memtable)
     }
 
+    /// Find the latest complete SSTable recovery starts from, checking its
+    /// integrity, and the WAL offset replay starts at.
+    ///
+    /// Records up to the LSN the SSTable recorded are already in it, so
+    /// replay starts there; older SSTables carry no LSN and fall back to the
+    /// checkpoint's start record. Without an SSTable the whole WAL is
+    /// replayed. The offset is `None` if the checkpoint cannot be found in
+    /// the WAL, and nothing is replayed.
+    fn find_recovery_start(
+        &mut self,
+        progress: &mut impl FnMut(&RecoveryProgress),
+    ) -> Result<(Option<PathBuf>, Option<u64>), DurabilityError> {
+        let Some(sstable_path) = self.find_latest_recoverable_sstable()? else {
+            info!("No valid SSTable found, replaying entire WAL");
+            return Ok((None, Some(self.wal.data_start())));
+        };
+        debug!("Found latest SSTable: {:?}", sstable_path);
+
+        // Verify the SSTable's integrity before loading it
+        if !self.verify_sstable_integrity(&sstable_path.to_string_lossy())? {
+            return Err(DurabilityError::SsTableIntegrityCheckFailed);
+        }
+
+        // Perform enhanced data integrity check
+        if !self.verify_sstable_data_integrity(&sstable_path.to_string_lossy())? {
+            return Err(DurabilityError::DataCorruption(format!(
+                "Data corruption detected in SSTable {}",
+                sstable_path.display()
+            )));
+        }
+
+        // Extract the checkpoint ID from the SSTable filename
+        let checkpoint_id = self.extract_checkpoint_id(&sstable_path)?;
+        info!("Loading from checkpoint: {}", checkpoint_id);
+        progress(&RecoveryProgress {
+            phase: RecoveryPhase::LoadingSSTable,
+            segment: sstable_path.to_string_lossy().to_string(),
+            ..RecoveryProgress::default()
+        });
+
+        // Update the latest flushed checkpoint ID
+        self.latest_flushed_checkpoint
+            .store(checkpoint_id, Ordering::SeqCst);
+
+        let wal_end = self.wal.end_lsn()?;
+        let applied_lsn = SSTableReader::open(&sstable_path.to_string_lossy())?
+            .applied_lsn()
+            .filter(|lsn| (self.wal.data_start()..=wal_end).contains(lsn));
+        let start = match applied_lsn {
+            Some(applied_lsn) => Some(applied_lsn),
+            None => match self.wal.get_checkpoint_position(checkpoint_id) {
+                Ok(checkpoint_position) => Some(checkpoint_position),
+                Err(_) => {
+                    warn!("Could not find checkpoint position in WAL");
+                    None
+                }
+            },
+        };
+        Ok((Some(sstable_path), start))
+    }
+
+    /// Recover from a crash without holding the recovered values in memory
+    pub fn recover_from_crash_bounded(
+        &mut self,
+        chunk_bytes: usize,
+    ) -> Result<RecoveredReferences, DurabilityError> {
+        self.recover_from_crash_bounded_with_progress(chunk_bytes, |_| {})
+    }
+
+    /// Recover from a crash as `recover_from_crash_with_progress` does, but
+    /// with peak memory bounded by the number of keys rather than the size
+    /// of the data.
+    ///
+    /// The latest SSTable is indexed key by key, keeping a storage
+    /// reference to each entry instead of loading its value. The WAL tail
+    /// is replayed into a memtable that is written to a temporary SSTable
+    /// each time it holds `chunk_bytes` of data, so a large tail is streamed
+    /// through in chunks. The recovery checkpoint's SSTable is then written
+    /// one value at a time, and the references returned point into it.
+    pub fn recover_from_crash_bounded_with_progress(
+        &mut self,
+        chunk_bytes: usize,
+        mut progress: impl FnMut(&RecoveryProgress),
+    ) -> Result<RecoveredReferences, DurabilityError> {
+        info!("Starting memory-bounded crash recovery...");
+
+        let (latest_sstable, replay_start) = self.find_recovery_start(&mut progress)?;
+        let mut state = SpillingRecovery::new(&self.sstable_dir, chunk_bytes)?;
+        let result = self.recover_into(
+            &mut state,
+            latest_sstable.as_deref(),
+            replay_start,
+            &mut progress,
+        );
+        state.remove_spills();
+        let recovered = result?;
+
+        info!("Crash recovery complete");
+        progress(&RecoveryProgress {
+            phase: RecoveryPhase::Complete,
+            segment: self.wal.path().to_string(),
+            ..RecoveryProgress::default()
+        });
+        Ok(recovered)
+    }
+
+    /// Rebuild the state memory-bounded recovery starts from in `state`,
+    /// replay the WAL into it and write it out as the recovery checkpoint
+    fn recover_into(
+        &mut self,
+        state: &mut SpillingRecovery,
+        latest_sstable: Option<&Path>,
+        replay_start: Option<u64>,
+        progress: &mut impl FnMut(&RecoveryProgress),
+    ) -> Result<RecoveredReferences, DurabilityError> {
+        if let Some(sstable_path) = latest_sstable {
+            // Stop at the first entry that cannot be read, as
+            // `load_from_sstable` does
+            for entry in entry_references(&sstable_path.to_string_lossy())? {
+                let Ok((key, reference)) = entry else {
+                    break;
+                };
+                state.references.insert(key, reference);
+            }
+        }
+        if let Some(start) = replay_start {
+            let replay_count = self.replay_wal(state, start, progress)?;
+            info!(
+                "Replayed {} WAL records from offset {}",
+                replay_count, start
+            );
+        }
+        state.spill()?;
+
+        progress(&RecoveryProgress {
+            phase: RecoveryPhase::WritingCheckpoint,
+            segment: self.wal.path().to_string(),
+            ..RecoveryProgress::default()
+        });
+        let recovery_checkpoint_id = self.begin_checkpoint()?;
+        info!("Created recovery checkpoint: {}", recovery_checkpoint_id);
+        if state.references.is_empty() {
+            return Ok(RecoveredReferences::default());
+        }
+
+        // Read each value back from wherever it was left
+        let mut readers: HashMap<String, SSTableReader> = HashMap::new();
+        let entries = state.references.iter().map(|(key, reference)| {
+            let reader = match readers.entry(reference.file_path.clone()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(SSTableReader::open(&reference.file_path)?),
+            };
+            let (stored_key, value) = reader.entry_at(reference.offset as u64)?;
+            if stored_key != *key {
+                return Err(DurabilityError::DataCorruption(format!(
+                    "Entry at offset {} in {} holds {:?} rather than {:?}",
+                    reference.offset, reference.file_path, stored_key, key
+                )));
+            }
+            Ok((key, value))
+        });
+        let new_sstable_path =
+            self.write_sstable_entries(state.references.len(), entries, recovery_checkpoint_id)?;
+        info!("Written recovered state to SSTable: {}", new_sstable_path);
+        let entry_count = state.references.len() as u64;
+        let min_key = state.references.keys().next().cloned();
+        let max_key = state.references.keys().next_back().cloned();
+        state.references.clear();
+
+        self.end_checkpoint_with_files(
+            recovery_checkpoint_id,
+            vec![CheckpointFile {
+                path: new_sstable_path.clone(),
+                entry_count,
+                min_key,
+                max_key,
+            }],
+        )?;
+        // Mark the recovery checkpoint as durable, which also truncates the
+        // WAL at it
+        self.register_durable_checkpoint(recovery_checkpoint_id, &new_sstable_path)?;
+        info!("Registered durable recovery checkpoint");
+
+        let mut entries = BTreeMap::new();
+        for entry in entry_references(&new_sstable_path)? {
+            let (key, reference) = entry?;
+            entries.insert(key, reference);
+        }
+        Ok(RecoveredReferences {
+            sstable_path: Some(PathBuf::from(new_sstable_path)),
+            entries,
+        })
+    }
+
     /// Begin a new transaction
     pub fn begin_transaction(&mut self) -> Result<u64, DurabilityError> {
         // Generate a new transaction ID
@@ -829,15 +1850,12 @@ impl DurabilityManager {
         self.log_operation(Operation::TransactionBegin { id: tx_id })?;
 
         // Create transaction tracker
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let now = self.clock.now_secs();
 
         let tracker = TransactionTracker {
             id: tx_id,
             status: crate::wal::TransactionStatus::Started,
-            operations: vec![Operation::TransactionBegin { id: tx_id }],
+            operations: Vec::new(),
             start_time: now,
             prepare_time: None,
             end_time: None,
@@ -849,7 +1867,8 @@ impl DurabilityManager {
         Ok(tx_id)
     }
 
-    /// Add an operation to a transaction (without committing)
+    /// Add an operation to a transaction (without committing). Nothing is
+    /// logged until the transaction is prepared or committed.
     pub fn add_to_transaction(
         &mut self,
         tx_id: u64,
@@ -880,14 +1899,7 @@ impl DurabilityManager {
             }
         }
 
-        // Create a WalRecord with the transaction ID
-        let mut record = operation.clone().into_record();
-        record.transaction_id = tx_id;
-
-        // Log the operation
-        self.wal.append_and_sync(record)?;
-
-        // Add to tracker
+        // Buffer the operation until the transaction ends
         tracker.operations.push(operation);
 
         Ok(())
@@ -918,30 +1930,43 @@ impl DurabilityManager {
             }
         }
 
-        // Log prepare operation
-        self.log_operation(Operation::TransactionPrepare { id: tx_id })?;
-
-        // Force sync to disk to ensure prepare is durable
-        self.wal.sync()?;
+        // Log the buffered operations behind the prepare marker in one
+        // synced record, so replay holds them until the outcome is logged
+        let record = self.prepare_record(tx_id);
+        self.log_operation(record)?;
 
         // Update transaction state
         if let Some(tracker) = self.transaction_registry.get_mut(&tx_id) {
             tracker.status = crate::wal::TransactionStatus::Prepared;
-            tracker.prepare_time = Some(
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-            );
+            tracker.prepare_time = Some(self.clock.now_secs());
         }
 
         Ok(())
     }
 
+    /// The record a transaction is prepared with: its buffered operations
+    /// behind the prepare marker
+    fn prepare_record(&self, tx_id: u64) -> Operation {
+        let mut operations = vec![Operation::TransactionPrepare { id: tx_id }];
+        operations.extend(self.transaction_registry[&tx_id].operations.iter().cloned());
+        Operation::Batch { operations }
+    }
+
     /// Commit a transaction (phase 2 of 2PC)
     pub fn commit_transaction(&mut self, tx_id: u64) -> Result<(), DurabilityError> {
+        self.commit_with_durability(tx_id, DurabilityLevel::WalSynced)
+    }
+
+    /// Commit a transaction, returning once its commit record has reached
+    /// `level`. An unprepared transaction's operations are logged in the
+    /// same record as the commit, so replay applies all of them or none.
+    pub fn commit_with_durability(
+        &mut self,
+        tx_id: u64,
+        level: DurabilityLevel,
+    ) -> Result<(), DurabilityError> {
         // Verify transaction exists and is in correct state
-        {
+        let prepared = {
             let tracker = self
                 .transaction_registry
                 .get(&tx_id)
@@ -951,9 +1976,11 @@ impl DurabilityManager {
                 crate::wal::TransactionStatus::Started => {
                     // For simple, one-phase commits we can allow this
                     // but ideally it should be prepared first
+                    false
                 }
                 crate::wal::TransactionStatus::Prepared => {
                     // Ideal path: prepared -> commit
+                    true
                 }
                 crate::wal::TransactionStatus::Committed => {
                     return Err(DurabilityError::TransactionAlreadyCommitted(tx_id));
@@ -962,29 +1989,49 @@ impl DurabilityManager {
                     return Err(DurabilityError::TransactionAlreadyAborted(tx_id));
                 }
             }
-        }
-
-        // Log commit operation
-        self.log_operation(Operation::TransactionCommit { id: tx_id })?;
+        };
 
-        // Force sync to disk
-        self.wal.sync()?;
+        // Log commit operation, with the operations unless prepare logged them
+        let commit = Operation::TransactionCommit { id: tx_id };
+        let record = if prepared {
+            commit
+        } else {
+            let mut operations = self.transaction_registry[&tx_id].operations.clone();
+            operations.push(commit);
+            Operation::Batch { operations }
+        };
+        if level == DurabilityLevel::Replicated && self.replicator.is_none() {
+            return Err(DurabilityError::ReplicatorNotConfigured);
+        }
+        self.log_operation_with_durability(record, level.min(DurabilityLevel::WalSynced))?;
 
-        // Update transaction state
+        // The commit is in the WAL, so the transaction is committed even if
+        // replicating it then fails
         if let Some(tracker) = self.transaction_registry.get_mut(&tx_id) {
             tracker.status = crate::wal::TransactionStatus::Committed;
-            tracker.end_time = Some(
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-            );
+            tracker.end_time = Some(self.clock.now_secs());
+        }
+        if level == DurabilityLevel::Replicated {
+            self.replicate()?;
         }
 
         Ok(())
     }
 
-    /// Abort a transaction
+    /// Commit a transaction and apply its operations to `memtable`, which
+    /// sees none of them until the commit is synced
+    pub fn commit_and_apply(
+        &mut self,
+        tx_id: u64,
+        memtable: &mut StringMemtable,
+    ) -> Result<(), DurabilityError> {
+        self.commit_transaction(tx_id)?;
+        let operations = self.transaction_registry[&tx_id].operations.clone();
+        Self::apply_operation_to_memtable(memtable, Operation::Batch { operations })
+    }
+
+    /// Abort a transaction. Its buffered operations are dropped, and if it
+    /// was prepared, replay discards the operations the prepare logged.
     pub fn abort_transaction(&mut self, tx_id: u64) -> Result<(), DurabilityError> {
         // Verify transaction exists and is in correct state
         {
@@ -1013,12 +2060,7 @@ impl DurabilityManager {
         // Update transaction state
         if let Some(tracker) = self.transaction_registry.get_mut(&tx_id) {
             tracker.status = crate::wal::TransactionStatus::Aborted;
-            tracker.end_time = Some(
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-            );
+            tracker.end_time = Some(self.clock.now_secs());
         }
 
         Ok(())
@@ -1038,24 +2080,27 @@ impl DurabilityManager {
         Ok(())
     }
 
-    /// Execute multiple operations in a single transaction
+    /// Execute multiple operations as one `RecordType::Batch` record.
+    ///
+    /// The batch is framed, checksummed and synced once, so replay applies
+    /// either all of its operations or, if the record was torn by a crash,
+    /// none of them.
     pub fn execute_batch(&mut self, operations: Vec<Operation>) -> Result<(), DurabilityError> {
+        self.execute_batch_with_sync(operations, true)
+    }
+
+    /// Execute multiple operations as one record, as `execute_batch` does,
+    /// syncing the WAL to disk only if `sync` is set
+    pub fn execute_batch_with_sync(
+        &mut self,
+        operations: Vec<Operation>,
+        sync: bool,
+    ) -> Result<(), DurabilityError> {
         if operations.is_empty() {
             return Ok(());
         }
 
-        // Begin transaction
-        let tx_id = self.begin_transaction()?;
-
-        // Execute each operation in the transaction
-        for op in operations {
-            self.add_to_transaction(tx_id, op)?;
-        }
-
-        // Commit transaction
-        self.commit_transaction(tx_id)?;
-
-        Ok(())
+        self.log_operation_with_sync(Operation::Batch { operations }, sync)
     }
 
     /// Insert a key-value pair without using a transaction
@@ -1098,7 +2143,10 @@ impl DurabilityManager {
 
     /// For compatibility with existing code - uses transaction internally
     pub fn log_checkpoint_end(&mut self, checkpoint_id: u64) -> Result<(), DurabilityError> {
-        let operation = Operation::CheckpointEnd { id: checkpoint_id };
+        let operation = Operation::CheckpointEnd {
+            id: checkpoint_id,
+            files: Vec::new(),
+        };
         self.execute_transaction(operation)
     }
 }
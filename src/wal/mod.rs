@@ -1,17 +1,28 @@
-use crc32fast;
+use crate::clock::{Clock, SystemClock};
+use crate::checked_len;
+use crate::sstable::ChecksumKind;
 use std::error::Error;
 use std::fmt;
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
+pub mod change_feed;
 // Expose the durability module
 pub mod durability;
 
 /// Magic number for the WAL file header
 pub const WAL_MAGIC: u64 = 0x4C534D_57414C30; // "LSM-WAL0" in hex
 /// Version number for the WAL file format
-pub const WAL_VERSION: u32 = 1;
+pub const WAL_VERSION: u32 = 2; // Version 2 records the checksum kind in the header
+/// First version whose header records the `ChecksumKind` of its records
+pub const WAL_CHECKSUM_KIND_VERSION: u32 = 2;
+/// Size of the WAL file header: the magic number, the version and the
+/// checksum kind
+pub const WAL_HEADER_SIZE: u64 = 13;
+/// Size of the header of version 1 logs, which have no checksum kind and
+/// checksum their records with CRC32
+pub const WAL_V1_HEADER_SIZE: u64 = 12;
 
 /// Error type for WAL operations
 #[derive(Debug)]
@@ -63,6 +74,10 @@ pub enum RecordType {
     TransactionCommit = 8,
     /// Transaction abort
     TransactionAbort = 9,
+    /// Several operations framed as one record, applied all or nothing
+    Batch = 10,
+    /// Removal of every key in a range
+    DeleteRange = 11,
     /// Unknown record type
     Unknown = 255,
 }
@@ -80,6 +95,8 @@ impl RecordType {
             7 => RecordType::TransactionPrepare,
             8 => RecordType::TransactionCommit,
             9 => RecordType::TransactionAbort,
+            10 => RecordType::Batch,
+            11 => RecordType::DeleteRange,
             _ => RecordType::Unknown,
         }
     }
@@ -120,10 +137,7 @@ impl Transaction {
             id,
             status: TransactionStatus::Started,
             records: Vec::new(),
-            start_timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            start_timestamp: SystemClock.now_secs(),
             finish_timestamp: None,
         }
     }
@@ -143,10 +157,7 @@ impl Transaction {
     pub fn commit(&mut self) {
         self.status = TransactionStatus::Committed;
         self.finish_timestamp = Some(
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            SystemClock.now_secs(),
         );
     }
 
@@ -154,10 +165,7 @@ impl Transaction {
     pub fn abort(&mut self) {
         self.status = TransactionStatus::Aborted;
         self.finish_timestamp = Some(
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            SystemClock.now_secs(),
         );
     }
 }
@@ -189,8 +197,14 @@ impl WalRecord {
         }
     }
 
-    /// Serialize a record to bytes
+    /// Serialize a record to bytes, checksummed with CRC32 as in logs
+    /// created with `WriteAheadLog::new`
     pub fn serialize(&self) -> Result<Vec<u8>, WalError> {
+        self.serialize_with(ChecksumKind::Crc32)
+    }
+
+    /// Serialize a record to bytes, checksummed with `kind`
+    pub fn serialize_with(&self, kind: ChecksumKind) -> Result<Vec<u8>, WalError> {
         let mut result = Vec::new();
 
         // Record type (1 byte)
@@ -203,15 +217,21 @@ impl WalRecord {
         // Data
         result.extend_from_slice(&self.data);
 
-        // CRC (4 bytes) - simple checksum for example purposes
-        let checksum = calculate_checksum(&result);
+        // Checksum (4 bytes) over the type, length and data
+        let checksum = kind.checksum(&result);
         result.extend_from_slice(&checksum.to_le_bytes());
 
         Ok(result)
     }
 
-    /// Deserialize a record from bytes
+    /// Deserialize a record from bytes, verifying its CRC32
     pub fn deserialize(data: &[u8]) -> Result<Self, WalError> {
+        Self::deserialize_with(data, ChecksumKind::Crc32)
+    }
+
+    /// Deserialize a record from bytes, verifying the checksum `kind`
+    /// computes
+    pub fn deserialize_with(data: &[u8], kind: ChecksumKind) -> Result<Self, WalError> {
         if data.len() < 9 {
             // 1 byte type + 4 bytes length + at least 4 bytes CRC
             return Err(WalError::InvalidRecord);
@@ -238,7 +258,7 @@ impl WalRecord {
         expected_checksum_bytes.copy_from_slice(&data[5 + data_len..5 + data_len + 4]);
         let expected_checksum = u32::from_le_bytes(expected_checksum_bytes);
 
-        let actual_checksum = calculate_checksum(&data[0..5 + data_len]);
+        let actual_checksum = kind.checksum(&data[0..5 + data_len]);
 
         if expected_checksum != actual_checksum {
             return Err(WalError::InvalidRecord);
@@ -257,10 +277,7 @@ impl WalRecord {
     pub fn new_transaction_begin(tx_id: u64) -> Self {
         let mut record = WalRecord::new(RecordType::TransactionBegin, tx_id.to_le_bytes().to_vec());
         record.transaction_id = tx_id;
-        record.timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        record.timestamp = SystemClock.now_secs();
         record
     }
 
@@ -269,10 +286,7 @@ impl WalRecord {
         let mut record =
             WalRecord::new(RecordType::TransactionPrepare, tx_id.to_le_bytes().to_vec());
         record.transaction_id = tx_id;
-        record.timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        record.timestamp = SystemClock.now_secs();
         record
     }
 
@@ -281,10 +295,7 @@ impl WalRecord {
         let mut record =
             WalRecord::new(RecordType::TransactionCommit, tx_id.to_le_bytes().to_vec());
         record.transaction_id = tx_id;
-        record.timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        record.timestamp = SystemClock.now_secs();
         record
     }
 
@@ -292,10 +303,7 @@ impl WalRecord {
     pub fn new_transaction_abort(tx_id: u64) -> Self {
         let mut record = WalRecord::new(RecordType::TransactionAbort, tx_id.to_le_bytes().to_vec());
         record.transaction_id = tx_id;
-        record.timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        record.timestamp = SystemClock.now_secs();
         record
     }
 
@@ -311,11 +319,6 @@ impl WalRecord {
     }
 }
 
-/// Calculate a CRC32 checksum
-fn calculate_checksum(data: &[u8]) -> u32 {
-    crc32fast::hash(data)
-}
-
 /// Iterator over WAL records
 pub struct WalIterator<'a> {
     wal: &'a mut WriteAheadLog,
@@ -340,31 +343,104 @@ pub struct WriteAheadLog {
     pub path: String,
     /// File handle
     pub file: File,
+    /// Function the log's records are checksummed with
+    checksum_kind: ChecksumKind,
+    /// Offset of the first record, just past the header
+    data_start: u64,
 }
 
 impl WriteAheadLog {
-    /// Create a new WAL file or open an existing one
+    /// Create a new WAL file checksumming records with CRC32, or open an
+    /// existing one
     pub fn new(path: &str) -> Result<Self, WalError> {
-        // For new files, we would write a header with the magic number and version
+        Self::new_with_checksum_kind(path, ChecksumKind::Crc32)
+    }
+
+    /// Create a new WAL file checksumming records with `kind`, recorded in
+    /// its header, or open an existing one. An existing log keeps the kind
+    /// it was created with.
+    pub fn new_with_checksum_kind(path: &str, kind: ChecksumKind) -> Result<Self, WalError> {
         let file = Self::new_file(path)?;
         let mut wal = WriteAheadLog {
             path: path.to_string(),
             file,
+            checksum_kind: kind,
+            data_start: WAL_HEADER_SIZE,
         };
 
         // For new files, write the header
         if fs::metadata(path).map(|m| m.len() == 0).unwrap_or(true) {
-            // Write header with magic number and version
+            // Write header with magic number, version and checksum kind
             let mut header = Vec::new();
             header.extend_from_slice(&WAL_MAGIC.to_le_bytes());
             header.extend_from_slice(&WAL_VERSION.to_le_bytes());
+            header.push(kind.id());
             wal.file.write_all(&header)?;
             wal.file.flush()?;
+        } else {
+            wal.read_header()?;
         }
 
         Ok(wal)
     }
 
+    /// Open an existing WAL file for reading only
+    pub(crate) fn open_for_reading(path: &str) -> Result<Self, WalError> {
+        let mut wal = WriteAheadLog {
+            path: path.to_string(),
+            file: File::open(path)?,
+            checksum_kind: ChecksumKind::Crc32,
+            data_start: WAL_HEADER_SIZE,
+        };
+        wal.read_header()?;
+        Ok(wal)
+    }
+
+    /// Learn the checksum kind and header size of an existing log from its
+    /// header, leaving the file at its start. Version 1 logs, and files
+    /// without the WAL magic number, are read as CRC32 logs with the version
+    /// 1 header.
+    fn read_header(&mut self) -> Result<(), WalError> {
+        let mut header = Vec::with_capacity(WAL_HEADER_SIZE as usize);
+        (&mut self.file)
+            .take(WAL_HEADER_SIZE)
+            .read_to_end(&mut header)?;
+        self.file.seek(SeekFrom::Start(0))?;
+
+        self.checksum_kind = ChecksumKind::Crc32;
+        self.data_start = WAL_V1_HEADER_SIZE;
+        if header.len() < WAL_V1_HEADER_SIZE as usize
+            || header[..8] != WAL_MAGIC.to_le_bytes()
+        {
+            return Ok(());
+        }
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version > WAL_VERSION {
+            return Err(WalError::IoError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported WAL version: {}", version),
+            )));
+        }
+        if version >= WAL_CHECKSUM_KIND_VERSION {
+            let id = header.get(12).copied().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Truncated WAL header")
+            })?;
+            self.checksum_kind = ChecksumKind::from_id(id)?;
+            self.data_start = WAL_HEADER_SIZE;
+        }
+        Ok(())
+    }
+
+    /// Function the log's records are checksummed with
+    pub fn checksum_kind(&self) -> ChecksumKind {
+        self.checksum_kind
+    }
+
+    /// Offset of the first record, just past the header
+    pub fn data_start(&self) -> u64 {
+        self.data_start
+    }
+
     /// Helper method to create a new file
     fn new_file(path: &str) -> Result<File, WalError> {
         // Ensure parent directory exists
@@ -416,10 +492,11 @@ impl WriteAheadLog {
             Err(e) => return Err(WalError::IoError(e)),
         }
 
-        // Read data length (4 bytes)
-        let mut len_buf = [0u8; 4];
-        self.file.read_exact(&mut len_buf)?;
-        let data_len = u32::from_le_bytes(len_buf) as usize;
+        // Read data length (4 bytes), checked against what is left of the file
+        let file_size = self.file.metadata()?.len();
+        let data_len =
+            checked_len::read_len(&mut self.file, file_size, u32::MAX as usize, "WAL record")?;
+        let len_buf = (data_len as u32).to_le_bytes();
 
         // Read data
         let mut data = vec![0u8; data_len];
@@ -437,7 +514,7 @@ impl WriteAheadLog {
         full_record.extend_from_slice(&checksum_buf);
 
         // Deserialize
-        let record = WalRecord::deserialize(&full_record)?;
+        let record = WalRecord::deserialize_with(&full_record, self.checksum_kind)?;
 
         Ok(Some(record))
     }
@@ -461,16 +538,16 @@ impl WriteAheadLog {
         // Create a clone of the file handle for reading
         let mut file = OpenOptions::new().read(true).open(&self.path)?;
 
-        // Skip the header (magic number and version)
-        let header_size = std::mem::size_of::<u64>() + std::mem::size_of::<u32>();
-        file.seek(SeekFrom::Start(header_size as u64))?;
+        // Skip the header
+        file.seek(SeekFrom::Start(self.data_start))?;
 
-        let mut position = header_size as u64;
+        let mut position = self.data_start;
         let mut found_checkpoint = false;
 
         // Read through the WAL file looking for the checkpoint start record
         loop {
-            // We don't need to store the current position since we only return position
+            // Where the record starts, which is what is returned if it matches
+            let record_start = position;
 
             // Read record type (1 byte)
             let mut type_buf = [0u8; 1];
@@ -512,12 +589,10 @@ impl WriteAheadLog {
                     || record_checkpoint_id_le == checkpoint_id
                 {
                     found_checkpoint = true;
-                    // Skip any remaining data
-                    if data_len > 8 {
-                        file.seek(SeekFrom::Current((data_len - 8) as i64))?;
-                    }
+                    position = record_start;
                     break;
                 }
+                file.seek(SeekFrom::Current(-8))?;
             }
 
             // Skip the data and checksum
@@ -533,8 +608,10 @@ impl WriteAheadLog {
         }
     }
 
-    /// Truncate the WAL at a specific position
+    /// Truncate the WAL at a specific position. The header is always kept.
     pub fn truncate(&mut self, position: u64) -> Result<(), WalError> {
+        let position = position.max(self.data_start);
+
         // Seek to the position
         self.file.seek(SeekFrom::Start(position))?;
 
@@ -548,7 +625,10 @@ impl WriteAheadLog {
     }
 
     /// Iterate over WAL records from a specific checkpoint
-    pub fn iter_from_checkpoint(&mut self, checkpoint_id: u64) -> Result<WalIterator, WalError> {
+    pub fn iter_from_checkpoint(
+        &mut self,
+        checkpoint_id: u64,
+    ) -> Result<WalIterator<'_>, WalError> {
         // Find the position of the checkpoint
         let position = self.get_checkpoint_position(checkpoint_id)?;
 
@@ -571,11 +651,11 @@ impl WriteAheadLog {
             }
             Err(e) => {
                 // If we get an EOF, just start from beginning for tests
-                if let WalError::IoError(ref io_err) = e {
-                    if io_err.kind() == io::ErrorKind::UnexpectedEof {
-                        self.file.seek(SeekFrom::Start(0))?;
-                        return Ok(WalIterator { wal: self });
-                    }
+                if let WalError::IoError(ref io_err) = e
+                    && io_err.kind() == io::ErrorKind::UnexpectedEof
+                {
+                    self.file.seek(SeekFrom::Start(0))?;
+                    return Ok(WalIterator { wal: self });
                 }
                 return Err(e);
             }
@@ -587,7 +667,7 @@ impl WriteAheadLog {
     /// Append a record to the WAL and ensure it's synced to disk
     pub fn append_and_sync(&mut self, record: WalRecord) -> Result<(), WalError> {
         // Serialize record
-        let data = record.serialize()?;
+        let data = record.serialize_with(self.checksum_kind)?;
 
         // Append to log
         self.append(&data)?;
@@ -598,6 +678,15 @@ impl WriteAheadLog {
         Ok(())
     }
 
+    /// LSN of the last record in the log.
+    ///
+    /// A record's log sequence number is the WAL offset just past it, so
+    /// LSNs grow as records are appended and a record is reflected in any
+    /// state that has applied the log up to an LSN at or beyond its own.
+    pub fn end_lsn(&self) -> Result<u64, WalError> {
+        Ok(self.file.metadata()?.len())
+    }
+
     /// Force sync data to disk
     pub fn sync(&mut self) -> Result<(), WalError> {
         self.file.sync_data()?;
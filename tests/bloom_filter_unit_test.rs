@@ -142,3 +142,23 @@ async fn test_bloom_filter_clear() {
         Err(_) => panic!("Test timed out after 10 seconds"),
     }
 }
+
+#[test]
+fn test_bloom_filter_from_parts_size_beyond_bits() {
+    // A size claiming more bits than were stored must not read past them
+    let filter = BloomFilter::<String>::from_parts(vec![0u8; 4], 1_000_000, 7);
+    assert_eq!(filter.size_bits(), 32);
+    for i in 0..100 {
+        assert!(!filter.may_contain(&format!("key{}", i)));
+    }
+}
+
+#[test]
+fn test_bloom_filter_from_parts_empty_bits() {
+    // With no bits at all the filter cannot rule anything out
+    let filter = BloomFilter::<String>::from_parts(Vec::new(), 0, 7);
+    assert!(filter.may_contain(&"anything".to_string()));
+
+    let filter = BloomFilter::<String>::from_parts(Vec::new(), 64, 7);
+    assert!(filter.may_contain(&"anything".to_string()));
+}
@@ -0,0 +1,118 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::clock::{Clock, MockClock, SystemClock};
+use lsmer::lsm_index::LsmIndexOptions;
+use lsmer::wal::durability::DurabilityManager;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tempfile::tempdir;
+
+/// 2024-01-01T00:00:00Z
+const START_MS: u64 = 1_704_067_200_000;
+
+#[test]
+fn test_mock_clock_moves_only_when_told() {
+    let clock = MockClock::new(START_MS);
+    let shared = clock.clone();
+    assert_eq!(clock.now_ms(), START_MS);
+    assert_eq!(clock.now_secs(), START_MS / 1000);
+
+    shared.advance(Duration::from_millis(1500));
+    assert_eq!(clock.now_ms(), START_MS + 1500);
+    shared.set(42);
+    assert_eq!(clock.now_ms(), 42);
+
+    assert!(SystemClock.now_ms() > START_MS);
+}
+
+#[test]
+fn test_ttl_follows_mock_clock() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        LsmIndexOptions::default().with_clock(clock.clone()),
+    );
+
+    index
+        .insert_with_ttl("key".to_string(), b"v".to_vec(), Duration::from_secs(60))
+        .unwrap();
+    clock.advance(Duration::from_secs(59));
+    assert_eq!(index.get("key").unwrap(), Some(b"v".to_vec()));
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(index.get("key").unwrap(), None);
+    assert_eq!(index.sweep_expired().unwrap(), 1);
+}
+
+#[test]
+fn test_write_times_and_retention_follow_mock_clock() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let options = LsmIndexOptions::default()
+        .with_write_times(true)
+        .with_soft_delete_retention(Duration::from_secs(10));
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        options.with_clock(clock.clone()),
+    );
+
+    index.insert("key".to_string(), b"v".to_vec()).unwrap();
+    let written_at = index.get_with_metadata("key").unwrap().unwrap().written_at;
+    assert_eq!(
+        written_at,
+        Some(UNIX_EPOCH + Duration::from_millis(START_MS))
+    );
+
+    index.remove("key").unwrap();
+    clock.advance(Duration::from_secs(10));
+    assert!(!index.undelete("key").unwrap());
+}
+
+#[test]
+fn test_flushed_files_are_dated_by_mock_clock() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        LsmIndexOptions::default().with_clock(clock.clone()),
+    );
+
+    // Several flushes within the same frozen second still get distinct files
+    for i in 0..3 {
+        index.insert(format!("key{}", i), b"v".to_vec()).unwrap();
+        index.flush().unwrap();
+    }
+    let sstables = index.list_sstables();
+    assert_eq!(sstables.len(), 3);
+    assert!(sstables
+        .iter()
+        .all(|info| info.created_at_secs == START_MS / 1000));
+    for i in 0..3 {
+        assert_eq!(
+            index.get(&format!("key{}", i)).unwrap(),
+            Some(b"v".to_vec())
+        );
+    }
+}
+
+#[test]
+fn test_checkpoint_ids_increase_within_one_second() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let clock = MockClock::new(START_MS);
+    let mut manager = DurabilityManager::new(&format!("{}/wal.log", path), path)
+        .unwrap()
+        .with_clock(Arc::new(clock.clone()));
+
+    let first = manager.begin_checkpoint().unwrap();
+    manager.end_checkpoint(first).unwrap();
+    let second = manager.begin_checkpoint().unwrap();
+    manager.end_checkpoint(second).unwrap();
+    assert_eq!(first, START_MS / 1000);
+    assert_eq!(second, first + 1);
+
+    // Once the clock passes the IDs handed out, they follow it again
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(manager.begin_checkpoint().unwrap(), START_MS / 1000 + 5);
+}
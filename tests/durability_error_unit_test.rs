@@ -28,7 +28,7 @@ async fn test_durability_error_from_wal_error() {
 async fn test_durability_error_from_io_error() {
     let test_future = async {
         // Test conversion from io::Error to DurabilityError
-        let io_error = io::Error::new(io::ErrorKind::Other, "test io error");
+        let io_error = io::Error::other("test io error");
         let durability_error = DurabilityError::from(io_error);
 
         match durability_error {
@@ -99,7 +99,7 @@ async fn test_durability_error_variants() {
         // Test creation of different DurabilityError variants
         let variants = [
             DurabilityError::WalError(WalError::InvalidRecord),
-            DurabilityError::IoError(io::Error::new(io::ErrorKind::Other, "test error")),
+            DurabilityError::IoError(io::Error::other("test error")),
             DurabilityError::MemtableError(MemtableError::KeyNotFound),
             DurabilityError::CheckpointNotFound(123),
             DurabilityError::SsTableIntegrityCheckFailed,
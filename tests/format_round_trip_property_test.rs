@@ -0,0 +1,77 @@
+use lsmer::test_utils;
+use proptest::prelude::*;
+use proptest::sample::Index;
+use tempfile::tempdir;
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_sstable_round_trip(entries in test_utils::entries(), use_bloom_filter in any::<bool>()) {
+        let dir = tempdir().unwrap();
+        test_utils::assert_sstable_round_trip(
+            &dir.path().join("round_trip.sst"),
+            &entries,
+            use_bloom_filter,
+        )?;
+    }
+
+    #[test]
+    fn test_sstable_tombstones_round_trip(
+        entries in test_utils::entries(),
+        tombstones in test_utils::tombstones(),
+    ) {
+        let dir = tempdir().unwrap();
+        test_utils::assert_sstable_tombstones_round_trip(
+            &dir.path().join("tombstones.sst"),
+            &entries,
+            &tombstones,
+        )?;
+    }
+
+    #[test]
+    fn test_sstable_detects_corruption(
+        entries in test_utils::entries().prop_filter("needs an entry", |e| !e.is_empty()),
+        position in any::<Index>(),
+        mask in 1u8..,
+    ) {
+        let dir = tempdir().unwrap();
+        test_utils::assert_sstable_detects_corruption(
+            &dir.path().join("corrupt.sst"),
+            &entries,
+            position,
+            mask,
+        )?;
+    }
+
+    #[test]
+    fn test_bloom_filter_round_trip((filter, keys) in test_utils::bloom_filter()) {
+        test_utils::assert_bloom_filter_round_trip(&filter, &keys)?;
+    }
+
+    #[test]
+    fn test_wal_record_round_trip(operation in test_utils::operation()) {
+        test_utils::assert_wal_record_round_trip(&operation)?;
+    }
+
+    #[test]
+    fn test_wal_round_trip(operations in proptest::collection::vec(test_utils::operation(), 0..16)) {
+        let dir = tempdir().unwrap();
+        test_utils::assert_wal_round_trip(&dir.path().join("round_trip.wal"), &operations)?;
+    }
+
+    #[test]
+    fn test_wal_detects_corruption(
+        operations in proptest::collection::vec(test_utils::operation(), 1..16),
+        position in any::<Index>(),
+        mask in 1u8..,
+    ) {
+        let dir = tempdir().unwrap();
+        test_utils::assert_wal_detects_corruption(
+            &dir.path().join("corrupt.wal"),
+            &operations,
+            position,
+            mask,
+        )?;
+    }
+}
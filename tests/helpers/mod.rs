@@ -1,10 +1,17 @@
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::{SSTableRecord, SSTableWriter};
 use std::fs;
+use std::io;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 static TEST_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
+/// Memtable capacity of the indexes the tests open
+pub const INDEX_CAPACITY: usize = 4 * 1024 * 1024;
+
 /// Creates a unique directory path for each test to ensure test isolation when running in parallel
+#[allow(dead_code)]
 pub fn unique_test_dir(test_name: &str) -> PathBuf {
     let test_id = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
     let pid = std::process::id();
@@ -25,6 +32,7 @@ pub struct TestDir {
 
 impl TestDir {
     /// Creates a new temporary test directory
+    #[allow(dead_code)]
     pub fn new(test_name: &str) -> Self {
         let path = unique_test_dir(test_name);
         TestDir { path }
@@ -51,3 +59,96 @@ impl Drop for TestDir {
         let _ = fs::remove_dir_all(&self.path);
     }
 }
+
+/// Opens an index at `path` with Bloom filters and the default options
+#[allow(dead_code)]
+pub fn open_index(path: &str) -> LsmIndex {
+    open_index_with_options(path, LsmIndexOptions::default())
+}
+
+/// Opens an index at `path` with Bloom filters and `options`
+#[allow(dead_code)]
+pub fn open_index_with_options(path: &str, options: LsmIndexOptions) -> LsmIndex {
+    try_open_index_with_options(path, options).unwrap()
+}
+
+/// Opens an index at `path` with Bloom filters and `options`, for tests
+/// that expect opening to fail
+#[allow(dead_code)]
+pub fn try_open_index_with_options(path: &str, options: LsmIndexOptions) -> io::Result<LsmIndex> {
+    LsmIndex::new_with_options(INDEX_CAPACITY, path.to_string(), None, true, 0.01, options)
+}
+
+/// Opens an index at `path` with Bloom filters, the default options and a
+/// memtable holding `capacity` bytes
+#[allow(dead_code)]
+pub fn open_index_with_capacity(path: &str, capacity: usize) -> LsmIndex {
+    LsmIndex::new(capacity, path.to_string(), None, true, 0.01).unwrap()
+}
+
+/// An entry `write_table` can write: a key and value, or a record
+#[allow(dead_code)]
+pub trait TableEntry {
+    fn write_to(self, writer: &mut SSTableWriter) -> io::Result<()>;
+}
+
+impl<K: AsRef<str>, V: AsRef<[u8]>> TableEntry for (K, V) {
+    fn write_to(self, writer: &mut SSTableWriter) -> io::Result<()> {
+        writer.write_entry(self.0.as_ref(), self.1.as_ref())
+    }
+}
+
+impl<K: AsRef<str>, V: AsRef<[u8]>> TableEntry for &(K, V) {
+    fn write_to(self, writer: &mut SSTableWriter) -> io::Result<()> {
+        writer.write_entry(self.0.as_ref(), self.1.as_ref())
+    }
+}
+
+impl TableEntry for SSTableRecord {
+    fn write_to(self, writer: &mut SSTableWriter) -> io::Result<()> {
+        writer.write_all([Ok(self)])
+    }
+}
+
+impl<E: TableEntry> TableEntry for io::Result<E> {
+    fn write_to(self, writer: &mut SSTableWriter) -> io::Result<()> {
+        self?.write_to(writer)
+    }
+}
+
+/// Starts an SSTable at `path` sized for `expected_entries`, with a Bloom
+/// filter if `bloom_filter` is set, lets `configure` set the writer up and
+/// writes `entries` in the order given. Returns the unfinished writer and
+/// the offset each entry was written at.
+#[allow(dead_code)]
+pub fn start_table<E: TableEntry>(
+    path: &str,
+    expected_entries: usize,
+    bloom_filter: bool,
+    entries: impl IntoIterator<Item = E>,
+    configure: impl FnOnce(&mut SSTableWriter) -> io::Result<()>,
+) -> io::Result<(SSTableWriter, Vec<u64>)> {
+    let mut writer = SSTableWriter::new(path, expected_entries, bloom_filter, 0.01)?;
+    configure(&mut writer)?;
+    let mut offsets = Vec::new();
+    for entry in entries {
+        offsets.push(writer.offset()?);
+        entry.write_to(&mut writer)?;
+    }
+    Ok((writer, offsets))
+}
+
+/// Writes an SSTable as `start_table` does and finishes it, returning the
+/// offset each entry was written at
+#[allow(dead_code)]
+pub fn write_table<E: TableEntry>(
+    path: &str,
+    expected_entries: usize,
+    bloom_filter: bool,
+    entries: impl IntoIterator<Item = E>,
+    configure: impl FnOnce(&mut SSTableWriter) -> io::Result<()>,
+) -> io::Result<Vec<u64>> {
+    let (writer, offsets) = start_table(path, expected_entries, bloom_filter, entries, configure)?;
+    writer.finalize()?;
+    Ok(offsets)
+}
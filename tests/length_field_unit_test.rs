@@ -0,0 +1,73 @@
+use lsmer::lsm_index::{decode_columns, encode_columns};
+use lsmer::sstable::{SSTableReader, SSTableWriter};
+use lsmer::wal::{WalError, WriteAheadLog};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use tempfile::tempdir;
+
+/// Overwrite the little-endian u32 at `offset` in the file at `path`
+fn patch_u32(path: &str, offset: usize, value: u32) {
+    let mut bytes = fs::read(path).unwrap();
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn test_wal_record_length_past_end_of_file_is_rejected() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("wal.log");
+    let path = path.to_str().unwrap();
+
+    // A record header claiming nearly 4 GiB of data, with none following
+    let mut wal = WriteAheadLog::new(path).unwrap();
+    let mut header = vec![1u8];
+    header.extend_from_slice(&0xffff_fff0u32.to_le_bytes());
+    wal.append(&header).unwrap();
+
+    match wal.read_all_records() {
+        Err(WalError::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+        other => panic!("Expected an invalid data error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_sstable_value_length_past_end_of_file_is_rejected() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 1, false, 0.01).unwrap();
+    writer.write_entry("only-key", b"value").unwrap();
+    writer.finalize().unwrap();
+
+    // The value length follows the key; claim more than the file holds but
+    // less than the largest value
+    let bytes = fs::read(path).unwrap();
+    let key_at = bytes
+        .windows(b"only-key".len())
+        .position(|window| window == b"only-key")
+        .unwrap();
+    patch_u32(path, key_at + b"only-key".len(), 9_000_000);
+
+    let mut reader = SSTableReader::open(path).unwrap();
+    let err = reader.get("only-key").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let entries: Vec<_> = reader.iter().unwrap().collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].as_ref().unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn test_column_count_larger_than_value_is_rejected() {
+    let encoded = encode_columns(&HashMap::new());
+    let mut value = encoded[..4].to_vec();
+    value.extend_from_slice(&u32::MAX.to_le_bytes());
+
+    let err = decode_columns(&value).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
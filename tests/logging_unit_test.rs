@@ -0,0 +1,78 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use lsmer::logging;
+use lsmer::lsm_index::LsmIndex;
+use std::sync::Mutex;
+use tempfile::tempdir;
+
+/// Logger keeping the level and target of every record it is given
+struct CaptureLogger {
+    records: Mutex<Vec<(Level, String)>>,
+}
+
+impl Log for CaptureLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.records
+            .lock()
+            .unwrap()
+            .push((record.level(), record.target().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CaptureLogger = CaptureLogger {
+    records: Mutex::new(Vec::new()),
+};
+
+fn flush_and_recover(path: &str) {
+    {
+        let index = LsmIndex::new(1024 * 1024, path.to_string(), None, true, 0.01).unwrap();
+        index.insert("key".to_string(), b"value".to_vec()).unwrap();
+        index.flush().unwrap();
+    }
+    let mut index = LsmIndex::new(1024 * 1024, path.to_string(), None, true, 0.01).unwrap();
+    index.recover().unwrap();
+}
+
+fn take_records() -> Vec<(Level, String)> {
+    std::mem::take(&mut *LOGGER.records.lock().unwrap())
+}
+
+// Verbosity and the logger are global, so one test covers both settings
+#[test]
+fn test_messages_go_to_the_logger_within_the_verbosity() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let dir = tempdir().unwrap();
+    flush_and_recover(dir.path().join("verbose").to_str().unwrap());
+    let records = take_records();
+    assert!(records.iter().any(|(level, _)| *level == Level::Trace));
+    assert!(
+        records
+            .iter()
+            .any(|(level, target)| *level == Level::Info && target == "lsmer::lsm_index")
+    );
+    assert!(
+        records
+            .iter()
+            .all(|(_, target)| target.starts_with("lsmer"))
+    );
+
+    logging::set_verbosity(LevelFilter::Warn);
+    assert_eq!(logging::verbosity(), LevelFilter::Warn);
+    flush_and_recover(dir.path().join("quiet").to_str().unwrap());
+    assert!(
+        take_records()
+            .iter()
+            .all(|(level, _)| *level <= Level::Warn)
+    );
+
+    logging::set_verbosity(LevelFilter::Off);
+    flush_and_recover(dir.path().join("silent").to_str().unwrap());
+    assert!(take_records().is_empty());
+}
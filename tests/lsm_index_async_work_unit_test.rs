@@ -0,0 +1,137 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+/// Retired inputs are deleted once the skip map reclaims the entries that
+/// pointed into them, which happens lazily
+fn wait_for_deletion(index: &LsmIndex, path: &str) -> bool {
+    for i in 0..10_000 {
+        if !Path::new(path).exists() {
+            return true;
+        }
+        let key = format!("__churn{}", i % 16);
+        index.insert(key.clone(), vec![0]).unwrap();
+        index.remove(&key).unwrap();
+    }
+    !Path::new(path).exists()
+}
+
+fn flush_keys(index: &LsmIndex, keys: &[(&str, &str)]) {
+    for (key, value) in keys {
+        index
+            .insert(key.to_string(), value.as_bytes().to_vec())
+            .unwrap();
+    }
+    index.flush().unwrap();
+}
+
+#[tokio::test]
+async fn test_flush_async_resolves_with_summary() {
+    let dir = tempdir().unwrap();
+    let index = Arc::new(open_index(dir.path().to_str().unwrap()));
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    index.insert("b".to_string(), b"2".to_vec()).unwrap();
+
+    let summary = index.flush_async().await.unwrap();
+    assert_eq!(summary.files_written.len(), 1);
+    assert!(summary.files_removed.is_empty());
+    assert_eq!(summary.entries_written, 2);
+    assert!(summary.bytes_written > 0);
+    assert!(Path::new(&summary.files_written[0]).exists());
+    assert_eq!(index.list_sstables().len(), 1);
+}
+
+#[test]
+fn test_compact_range_merges_overlapping_and_newer_files() {
+    let dir = tempdir().unwrap();
+    let index = Arc::new(open_index(dir.path().to_str().unwrap()));
+    flush_keys(&index, &[("a", "old"), ("b", "1")]);
+    flush_keys(&index, &[("x", "1"), ("y", "1")]);
+    flush_keys(&index, &[("a", "new"), ("z", "1")]);
+    index.remove("b").unwrap();
+
+    let summary = index
+        .compact_range("a".to_string().."c".to_string())
+        .unwrap();
+    assert_eq!(summary.files_removed.len(), 3);
+    assert_eq!(summary.files_written.len(), 1);
+    assert_eq!(summary.entries_written, 5);
+
+    let sstables = index.list_sstables();
+    assert_eq!(sstables.len(), 1);
+    assert_eq!(sstables[0].level, 1);
+    assert_eq!(index.get("a").unwrap(), Some(b"new".to_vec()));
+    assert_eq!(index.get("b").unwrap(), None);
+    assert_eq!(index.get("y").unwrap(), Some(b"1".to_vec()));
+
+    // The inputs are deleted once nothing points into them
+    for path in &summary.files_removed {
+        assert!(wait_for_deletion(&index, path), "{} still exists", path);
+    }
+    assert_eq!(index.lifetime_stats().files_retired, 3);
+}
+
+#[tokio::test]
+async fn test_compact_range_leaves_older_files_alone() {
+    let dir = tempdir().unwrap();
+    let index = Arc::new(open_index(dir.path().to_str().unwrap()));
+    flush_keys(&index, &[("a", "1")]);
+    flush_keys(&index, &[("m", "1")]);
+
+    let summary = index.compact_range("m".to_string()..).unwrap();
+    assert_eq!(summary.files_removed.len(), 1);
+    assert_eq!(index.list_sstables().len(), 2);
+    assert_eq!(index.get("a").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(index.get("m").unwrap(), Some(b"1".to_vec()));
+
+    // Nothing overlaps, so nothing is written
+    let summary = index.compact_range("n".to_string()..).unwrap();
+    assert!(summary.files_written.is_empty());
+}
+
+#[test]
+fn test_compaction_output_survives_restart() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = Arc::new(open_index(path));
+    flush_keys(&index, &[("a", "old"), ("b", "1")]);
+    flush_keys(&index, &[("a", "new")]);
+    index.compact_range(..).unwrap();
+    drop(index);
+
+    let mut index = LsmIndex::new_with_options(
+        4 * 1024 * 1024,
+        path.to_string(),
+        None,
+        false,
+        0.01,
+        LsmIndexOptions::default(),
+    )
+    .unwrap();
+    index.recover().unwrap();
+    assert_eq!(index.get("a").unwrap(), Some(b"new".to_vec()));
+    assert_eq!(index.get("b").unwrap(), Some(b"1".to_vec()));
+}
+
+#[tokio::test]
+async fn test_wait_for_pending_work_covers_unawaited_jobs() {
+    let dir = tempdir().unwrap();
+    let index = Arc::new(open_index(dir.path().to_str().unwrap()));
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+
+    let flush = index.flush_async();
+    let compaction = index.compact_range_async(..);
+    index.wait_for_pending_work().await;
+    assert_eq!(index.pending_jobs(), 0);
+    assert!(flush.is_finished());
+    assert!(compaction.is_finished());
+    flush.await.unwrap();
+    compaction.await.unwrap();
+
+    // Nothing pending returns straight away
+    index.wait_for_pending_work().await;
+}
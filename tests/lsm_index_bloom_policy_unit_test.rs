@@ -0,0 +1,120 @@
+use lsmer::lsm_index::{
+    AdaptiveFprPolicy, BloomFprPolicy, FilterContext, LsmIndex, LsmIndexOptions,
+};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tempfile::tempdir;
+
+fn context(level: u32, reads_per_sec: f64, lifetime: Option<Duration>) -> FilterContext {
+    FilterContext {
+        level,
+        expected_entries: 1000,
+        level_reads_per_sec: reads_per_sec,
+        average_lifetime: lifetime,
+    }
+}
+
+/// Records every context it is asked about
+#[derive(Debug, Default)]
+struct RecordingPolicy {
+    seen: Arc<Mutex<Vec<FilterContext>>>,
+}
+
+impl BloomFprPolicy for RecordingPolicy {
+    fn false_positive_rate(&self, context: &FilterContext) -> f64 {
+        self.seen.lock().unwrap().push(context.clone());
+        0.05
+    }
+}
+
+#[test]
+fn test_adaptive_policy_tightens_deeper_levels() {
+    let policy = AdaptiveFprPolicy::default();
+    let l0 = policy.false_positive_rate(&context(0, 0.0, None));
+    let l1 = policy.false_positive_rate(&context(1, 0.0, None));
+    let l3 = policy.false_positive_rate(&context(3, 0.0, None));
+
+    assert_eq!(l0, policy.base_fpr);
+    assert!(l1 < l0);
+    assert!(l3 < l1);
+}
+
+#[test]
+fn test_adaptive_policy_reacts_to_reads_and_lifetime() {
+    let policy = AdaptiveFprPolicy::default();
+    let idle = policy.false_positive_rate(&context(1, 0.0, None));
+    let hot = policy.false_positive_rate(&context(1, 1000.0, None));
+    let short_lived = policy.false_positive_rate(&context(1, 0.0, Some(Duration::from_secs(1))));
+    let long_lived = policy.false_positive_rate(&context(1, 0.0, Some(Duration::from_secs(3600))));
+
+    assert!(hot < idle);
+    assert!(short_lived > idle);
+    assert_eq!(long_lived, idle);
+}
+
+#[test]
+fn test_adaptive_policy_clamps() {
+    let policy = AdaptiveFprPolicy::default();
+    let deep = policy.false_positive_rate(&context(40, 1000.0, None));
+    assert_eq!(deep, policy.min_fpr);
+
+    let loose = AdaptiveFprPolicy {
+        base_fpr: 0.5,
+        ..AdaptiveFprPolicy::default()
+    };
+    let rate = loose.false_positive_rate(&context(0, 0.0, Some(Duration::from_secs(1))));
+    assert_eq!(rate, loose.max_fpr);
+}
+
+#[test]
+fn test_flush_consults_policy() {
+    let dir = tempdir().unwrap();
+    let policy = RecordingPolicy::default();
+    let seen = policy.seen.clone();
+    let options = LsmIndexOptions::default().with_bloom_fpr_policy(policy);
+
+    let index = LsmIndex::new_with_options(
+        4 * 1024 * 1024,
+        dir.path().to_str().unwrap().to_string(),
+        None,
+        true,
+        0.01,
+        options,
+    )
+    .unwrap();
+
+    for i in 0..25 {
+        index
+            .insert(format!("key{:02}", i), b"value".to_vec())
+            .unwrap();
+    }
+    index.flush().unwrap();
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 1);
+    assert_eq!(seen[0].level, 0);
+    assert_eq!(seen[0].expected_entries, 25);
+    assert_eq!(seen[0].average_lifetime, None);
+
+    assert_eq!(index.get("key07").unwrap(), Some(b"value".to_vec()));
+}
+
+#[test]
+fn test_filter_context_starts_without_history() {
+    let dir = tempdir().unwrap();
+    let index = LsmIndex::new(
+        4 * 1024 * 1024,
+        dir.path().to_str().unwrap().to_string(),
+        None,
+        true,
+        0.01,
+    )
+    .unwrap();
+
+    let context = index.filter_context(2, 10);
+    assert_eq!(context.level, 2);
+    assert_eq!(context.expected_entries, 10);
+    assert_eq!(context.level_reads_per_sec, 0.0);
+    assert_eq!(context.average_lifetime, None);
+    assert_eq!(index.level_read_count(2), 0);
+}
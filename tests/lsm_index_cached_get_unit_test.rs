@@ -0,0 +1,54 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::CachedValue;
+use tempfile::tempdir;
+
+#[test]
+fn test_memtable_values_are_cached() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+
+    assert_eq!(
+        index.get_if_cached("a").unwrap(),
+        CachedValue::Found(b"1".to_vec())
+    );
+    assert_eq!(index.get_if_cached("missing").unwrap(), CachedValue::Absent);
+}
+
+#[test]
+fn test_removed_keys_are_known_absent() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    index.flush().unwrap();
+    index.remove("a").unwrap();
+    assert_eq!(index.get_if_cached("a").unwrap(), CachedValue::Absent);
+
+    index.flush().unwrap();
+    assert_eq!(index.get_if_cached("a").unwrap(), CachedValue::Absent);
+}
+
+#[test]
+fn test_cached_answers_match_get_after_recovery() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    {
+        let index = open_index(path);
+        for i in 0..50 {
+            index.insert(format!("key{}", i), vec![i as u8]).unwrap();
+        }
+        index.flush().unwrap();
+    }
+
+    let mut index = open_index(path);
+    index.recover().unwrap();
+    for key in ["key0", "key49", "key50"] {
+        match index.get_if_cached(key).unwrap() {
+            CachedValue::Found(value) => assert_eq!(index.get(key).unwrap(), Some(value)),
+            CachedValue::Absent => assert_eq!(index.get(key).unwrap(), None),
+            CachedValue::NotCached => {}
+        }
+    }
+}
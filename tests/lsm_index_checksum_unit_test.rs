@@ -0,0 +1,89 @@
+use lsmer::lsm_index::LsmIndex;
+use lsmer::sstable::entry_checksum;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_get_with_checksum_before_and_after_flush() {
+    let test_future = async {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_str().unwrap().to_string();
+        let index = LsmIndex::new(1024 * 1024, path, None, true, 0.01).unwrap();
+
+        index
+            .insert("alpha".to_string(), b"first".to_vec())
+            .unwrap();
+        index
+            .insert("beta".to_string(), b"second".to_vec())
+            .unwrap();
+
+        // Unflushed values get a computed checksum but are not verified
+        let unflushed = index.get_with_checksum("alpha").unwrap().unwrap();
+        assert_eq!(unflushed.value, b"first".to_vec());
+        assert_eq!(unflushed.checksum, entry_checksum("alpha", b"first"));
+        assert!(!unflushed.verified);
+
+        index.flush().unwrap();
+
+        // Flushed values carry the stored checksum, verified against the data
+        let flushed = index.get_with_checksum("beta").unwrap().unwrap();
+        assert_eq!(flushed.value, b"second".to_vec());
+        assert_eq!(flushed.checksum, entry_checksum("beta", b"second"));
+        assert!(flushed.verified);
+
+        assert_eq!(index.get_with_checksum("missing").unwrap(), None);
+    };
+
+    match timeout(Duration::from_secs(10), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 10 seconds"),
+    }
+}
+
+#[tokio::test]
+async fn test_get_with_checksum_reports_corruption() {
+    let test_future = async {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_str().unwrap().to_string();
+        let index = LsmIndex::new(1024 * 1024, path.clone(), None, false, 0.01).unwrap();
+
+        index
+            .insert("victim".to_string(), b"precious-data".to_vec())
+            .unwrap();
+        index.flush().unwrap();
+
+        // Flip a byte of the stored value on disk
+        let sstable_path = fs::read_dir(&path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|p| p.extension().is_some_and(|ext| ext == "db"))
+            .unwrap();
+        let mut contents = Vec::new();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&sstable_path)
+            .unwrap();
+        file.read_to_end(&mut contents).unwrap();
+        let position = contents
+            .windows(b"precious".len())
+            .position(|window| window == b"precious")
+            .unwrap();
+        file.seek(SeekFrom::Start(position as u64)).unwrap();
+        file.write_all(b"P").unwrap();
+        file.sync_all().unwrap();
+
+        let result = index.get_with_checksum("victim").unwrap().unwrap();
+        assert_eq!(result.value, b"Precious-data".to_vec());
+        assert_eq!(result.checksum, entry_checksum("victim", b"precious-data"));
+        assert!(!result.verified);
+    };
+
+    match timeout(Duration::from_secs(10), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 10 seconds"),
+    }
+}
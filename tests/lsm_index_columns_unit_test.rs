@@ -0,0 +1,86 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::LsmIndexError;
+use std::collections::HashMap;
+use tempfile::tempdir;
+
+fn user_columns() -> HashMap<String, Vec<u8>> {
+    let mut columns = HashMap::new();
+    columns.insert("name".to_string(), b"alice".to_vec());
+    columns.insert("email".to_string(), b"alice@example.com".to_vec());
+    columns
+}
+
+#[test]
+fn test_put_and_get_columns() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+
+    index
+        .put_columns("user:1".to_string(), user_columns())
+        .unwrap();
+
+    assert_eq!(index.get_columns("user:1").unwrap(), Some(user_columns()));
+    assert_eq!(
+        index.get_column("user:1", "name").unwrap(),
+        Some(b"alice".to_vec())
+    );
+    assert_eq!(index.get_column("user:1", "phone").unwrap(), None);
+    assert_eq!(index.get_column("user:2", "name").unwrap(), None);
+}
+
+#[test]
+fn test_merge_columns_updates_only_given_columns() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    index
+        .put_columns("user:1".to_string(), user_columns())
+        .unwrap();
+
+    let mut updates = HashMap::new();
+    updates.insert("email".to_string(), Some(b"a@example.org".to_vec()));
+    updates.insert("name".to_string(), None);
+    updates.insert("age".to_string(), Some(b"30".to_vec()));
+    index.merge_columns("user:1".to_string(), updates).unwrap();
+
+    let columns = index.get_columns("user:1").unwrap().unwrap();
+    assert_eq!(columns.len(), 2);
+    assert_eq!(columns["email"], b"a@example.org".to_vec());
+    assert_eq!(columns["age"], b"30".to_vec());
+
+    // Merging into a missing key starts from no columns
+    let mut updates = HashMap::new();
+    updates.insert("name".to_string(), Some(b"bob".to_vec()));
+    index.merge_columns("user:2".to_string(), updates).unwrap();
+    assert_eq!(
+        index.get_column("user:2", "name").unwrap(),
+        Some(b"bob".to_vec())
+    );
+}
+
+#[test]
+fn test_columns_survive_flush() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    index
+        .put_columns("user:1".to_string(), user_columns())
+        .unwrap();
+    index.flush().unwrap();
+
+    assert_eq!(index.get_columns("user:1").unwrap(), Some(user_columns()));
+}
+
+#[test]
+fn test_plain_value_is_not_columns() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    index
+        .insert("plain".to_string(), b"bytes".to_vec())
+        .unwrap();
+
+    assert!(matches!(
+        index.get_columns("plain"),
+        Err(LsmIndexError::InvalidOperation(_))
+    ));
+}
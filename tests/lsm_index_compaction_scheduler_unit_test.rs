@@ -0,0 +1,165 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{
+    CompactionContext, CompactionDecision, CompactionReason, CompactionScheduler, LsmIndex,
+    LsmIndexOptions,
+};
+use std::ops::Bound;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tempfile::tempdir;
+
+/// Defers everything while `closed` is set, boosts ranges starting at
+/// `boosted`, and records what it was asked about
+#[derive(Debug, Default, Clone)]
+struct Scheduler {
+    closed: Arc<AtomicBool>,
+    boosted: Option<String>,
+    asked: Arc<Mutex<Vec<(CompactionReason, usize)>>>,
+}
+
+impl CompactionScheduler for Scheduler {
+    fn schedule(&self, context: &CompactionContext<'_>) -> CompactionDecision {
+        self.asked
+            .lock()
+            .unwrap()
+            .push((context.reason.clone(), context.inputs.len()));
+        assert!(!context.level_storage.is_empty());
+        if self.closed.load(Ordering::SeqCst) {
+            return CompactionDecision::Defer;
+        }
+        match (&self.boosted, context.reason) {
+            (Some(boosted), CompactionReason::Range { start, .. })
+                if *start == Bound::Included(boosted.clone()) =>
+            {
+                CompactionDecision::Run { priority: 10 }
+            }
+            _ => CompactionDecision::RUN,
+        }
+    }
+}
+
+fn index_options(scheduler: Scheduler) -> LsmIndexOptions {
+    LsmIndexOptions::default().with_compaction_scheduler(scheduler)
+}
+
+fn flush_each(index: &LsmIndex, keys: &[&str]) {
+    for key in keys {
+        index.insert(key.to_string(), b"value".to_vec()).unwrap();
+        index.flush().unwrap();
+    }
+}
+
+#[test]
+fn test_deferred_compaction_does_nothing() {
+    let dir = tempdir().unwrap();
+    let scheduler = Scheduler::default();
+    scheduler.closed.store(true, Ordering::SeqCst);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(scheduler.clone()),
+    );
+    flush_each(&index, &["a", "b", "c"]);
+
+    let summary = index.compact_range(..).unwrap();
+    assert!(summary.files_written.is_empty());
+    assert_eq!(index.list_sstables().len(), 3);
+
+    let asked = scheduler.asked.lock().unwrap();
+    assert_eq!(
+        asked[0],
+        (
+            CompactionReason::Range {
+                start: Bound::Unbounded,
+                end: Bound::Unbounded
+            },
+            3
+        )
+    );
+}
+
+#[test]
+fn test_allowed_compaction_runs() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(Scheduler::default()),
+    );
+    flush_each(&index, &["a", "b", "c"]);
+
+    let summary = index.compact_range(..).unwrap();
+    assert_eq!(summary.files_removed.len(), 3);
+    assert_eq!(index.list_sstables().len(), 1);
+}
+
+#[test]
+fn test_queued_compactions_run_by_priority_and_deferred_ones_stay_queued() {
+    let dir = tempdir().unwrap();
+    let scheduler = Scheduler {
+        boosted: Some("m".to_string()),
+        ..Default::default()
+    };
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(scheduler.clone()),
+    );
+    flush_each(&index, &["a", "m", "z"]);
+
+    // Nothing runs while the window is closed
+    scheduler.closed.store(true, Ordering::SeqCst);
+    index.queue_compaction("a".to_string()..="b".to_string());
+    index.queue_compaction("m".to_string()..="n".to_string());
+    assert!(index.run_queued_compactions().unwrap().is_empty());
+    assert_eq!(index.queued_compactions(), 2);
+    assert_eq!(index.list_sstables().len(), 3);
+
+    // Once open, the boosted range runs first; it merges the files from "m"
+    // on, and the first range then takes in the rest
+    scheduler.closed.store(false, Ordering::SeqCst);
+    let summaries = index.run_queued_compactions().unwrap();
+    assert_eq!(summaries.len(), 2);
+    assert_eq!(summaries[0].files_removed.len(), 2);
+    assert_eq!(summaries[1].files_removed.len(), 2);
+    assert_eq!(index.queued_compactions(), 0);
+    assert_eq!(index.list_sstables().len(), 1);
+    for key in ["a", "m", "z"] {
+        assert_eq!(index.get(key).unwrap(), Some(b"value".to_vec()));
+    }
+}
+
+#[test]
+fn test_ranges_without_files_leave_the_queue() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(Scheduler::default()),
+    );
+    flush_each(&index, &["a"]);
+
+    index.queue_compaction("x".to_string().."y".to_string());
+    assert!(index.run_queued_compactions().unwrap().is_empty());
+    assert_eq!(index.queued_compactions(), 0);
+}
+
+#[test]
+fn test_tiny_file_merges_ask_the_scheduler() {
+    let dir = tempdir().unwrap();
+    let scheduler = Scheduler::default();
+    scheduler.closed.store(true, Ordering::SeqCst);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(scheduler.clone()),
+    );
+    flush_each(&index, &["a", "b"]);
+
+    assert!(index
+        .compact_tiny_files(1 << 20)
+        .unwrap()
+        .files_written
+        .is_empty());
+    assert_eq!(
+        scheduler.asked.lock().unwrap()[0],
+        (CompactionReason::TinyFiles, 2)
+    );
+}
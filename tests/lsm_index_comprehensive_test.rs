@@ -220,22 +220,22 @@ async fn test_load_value_from_sstable_edge_cases() {
         // Insert test data with various edge cases
         // Empty key
         if let Err(e) = lsm.insert("".to_string(), vec![0]) {
-            return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+            return Err(io::Error::other(format!("{:?}", e)));
         }
 
         // Empty value
         if let Err(e) = lsm.insert("empty_value".to_string(), vec![]) {
-            return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+            return Err(io::Error::other(format!("{:?}", e)));
         }
 
         // Moderately long key
         if let Err(e) = lsm.insert("a".repeat(100), vec![1]) {
-            return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+            return Err(io::Error::other(format!("{:?}", e)));
         }
 
         // Moderately long value
         if let Err(e) = lsm.insert("long_value".to_string(), vec![2; 100]) {
-            return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+            return Err(io::Error::other(format!("{:?}", e)));
         }
 
         // Now test getting values - unwrap the Result before comparing
@@ -367,7 +367,7 @@ async fn test_clear_method_with_durability() {
             let key = format!("key{}", i);
             let value = vec![i as u8];
             if let Err(e) = lsm.insert(key, value) {
-                return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+                return Err(io::Error::other(format!("{:?}", e)));
             }
         }
 
@@ -382,7 +382,7 @@ async fn test_clear_method_with_durability() {
 
         // Clear the index
         if let Err(e) = lsm.clear() {
-            return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+            return Err(io::Error::other(format!("{:?}", e)));
         }
 
         // Check that no data exists anymore
@@ -404,7 +404,7 @@ async fn test_clear_method_with_durability() {
             let key = format!("key{}", i);
             let value = vec![i as u8];
             if let Err(e) = lsm.insert(key, value) {
-                return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+                return Err(io::Error::other(format!("{:?}", e)));
             }
         }
 
@@ -447,7 +447,7 @@ async fn test_lsm_index_with_huge_values() {
             let key = format!("key{}", i);
             let value = vec![i as u8; size];
             if let Err(e) = lsm.insert(key, value) {
-                return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+                return Err(io::Error::other(format!("{:?}", e)));
             }
         }
 
@@ -491,7 +491,7 @@ async fn test_lsm_index_tombstone_handling() {
             let key = format!("key{}", i);
             let value = vec![i as u8];
             if let Err(e) = lsm.insert(key, value) {
-                return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+                return Err(io::Error::other(format!("{:?}", e)));
             }
         }
 
@@ -499,7 +499,7 @@ async fn test_lsm_index_tombstone_handling() {
         for i in 0..5 {
             let key = format!("key{}", i);
             if let Err(e) = lsm.remove(&key) {
-                return Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e)));
+                return Err(io::Error::other(format!("{:?}", e)));
             }
         }
 
@@ -0,0 +1,108 @@
+mod helpers;
+
+use helpers::open_index_with_capacity;
+use lsmer::lsm_index::LsmIndex;
+use tempfile::tempdir;
+
+/// Memtable capacity that fills after a handful of writes
+const CAPACITY: usize = 256;
+
+/// Insert until the memtable rejects a write
+fn fill(index: &LsmIndex, prefix: &str) {
+    let mut i = 0;
+    while index
+        .insert(format!("{}{:04}", prefix, i), vec![b'v'; 16])
+        .is_ok()
+    {
+        i += 1;
+    }
+    assert!(index.memtable_fill().unwrap() > 0.8);
+}
+
+#[test]
+fn test_only_full_memtables_are_flushed() {
+    let dir = tempdir().unwrap();
+    let shards: Vec<LsmIndex> = (0..4)
+        .map(|i| {
+            open_index_with_capacity(
+                dir.path().join(format!("shard{}", i)).to_str().unwrap(),
+                CAPACITY,
+            )
+        })
+        .collect();
+    for (i, shard) in shards.iter().enumerate().take(3) {
+        fill(shard, &format!("shard{}_", i));
+    }
+    shards[3]
+        .insert("quiet".to_string(), b"v".to_vec())
+        .unwrap();
+
+    let refs: Vec<&LsmIndex> = shards.iter().collect();
+    assert_eq!(LsmIndex::flush_full(&refs, 0.8, 2).unwrap(), 3);
+
+    for (i, shard) in shards.iter().enumerate().take(3) {
+        assert_eq!(shard.memtable_fill().unwrap(), 0.0);
+        assert_eq!(shard.list_sstables().len(), 1);
+        assert_eq!(
+            shard.get_flushed(&format!("shard{}_0000", i)).unwrap(),
+            Some(vec![b'v'; 16])
+        );
+    }
+    assert!(shards[3].list_sstables().is_empty());
+    assert_eq!(shards[3].get("quiet").unwrap(), Some(b"v".to_vec()));
+}
+
+#[test]
+fn test_nothing_to_flush() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_capacity(dir.path().to_str().unwrap(), CAPACITY);
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+
+    assert_eq!(LsmIndex::flush_full(&[&index], 0.8, 4).unwrap(), 0);
+    assert_eq!(LsmIndex::flush_full(&[], 0.0, 4).unwrap(), 0);
+    assert!(index.list_sstables().is_empty());
+}
+
+#[test]
+fn test_repeated_index_is_flushed_once() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_capacity(dir.path().to_str().unwrap(), CAPACITY);
+    fill(&index, "k");
+
+    assert_eq!(LsmIndex::flush_full(&[&index, &index], 0.8, 2).unwrap(), 1);
+    assert_eq!(index.list_sstables().len(), 1);
+}
+
+#[test]
+fn test_flushed_shards_recover() {
+    let dir = tempdir().unwrap();
+    let paths: Vec<String> = (0..3)
+        .map(|i| {
+            dir.path()
+                .join(format!("tenant{}", i))
+                .to_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    {
+        let shards: Vec<LsmIndex> = paths
+            .iter()
+            .map(|path| open_index_with_capacity(path, CAPACITY))
+            .collect();
+        for (i, shard) in shards.iter().enumerate() {
+            fill(shard, &format!("t{}_", i));
+        }
+        let refs: Vec<&LsmIndex> = shards.iter().collect();
+        assert_eq!(LsmIndex::flush_full(&refs, 0.8, 8).unwrap(), 3);
+    }
+
+    for (i, path) in paths.iter().enumerate() {
+        let mut index = open_index_with_capacity(path, CAPACITY);
+        index.recover().unwrap();
+        assert_eq!(
+            index.get(&format!("t{}_0000", i)).unwrap(),
+            Some(vec![b'v'; 16])
+        );
+    }
+}
@@ -0,0 +1,124 @@
+mod helpers;
+
+use helpers::try_open_index_with_options;
+use lsmer::lsm_index::{CheckpointMismatch, ConsistencyCheck, LsmIndexOptions, SET_ASIDE_SUFFIX};
+use lsmer::sstable::SSTableWriter;
+use lsmer::wal::durability::DurabilityManager;
+use std::fs;
+use std::io;
+use tempfile::tempdir;
+
+fn index_options(check: ConsistencyCheck) -> LsmIndexOptions {
+    LsmIndexOptions::default().with_consistency_check(check)
+}
+
+#[test]
+fn test_flushed_index_is_consistent() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    {
+        let index =
+            try_open_index_with_options(path, index_options(ConsistencyCheck::Off)).unwrap();
+        for i in 0..3 {
+            index.insert(format!("key{}", i), b"v".to_vec()).unwrap();
+            index.flush().unwrap();
+        }
+    }
+
+    let index = try_open_index_with_options(path, index_options(ConsistencyCheck::Verify)).unwrap();
+    let report = index.check_consistency(false).unwrap();
+    assert!(report.is_consistent(), "{}", report);
+    assert!(!report.repaired);
+}
+
+#[test]
+fn test_missing_files_are_dropped_from_the_manifest() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let lost = {
+        let index =
+            try_open_index_with_options(path, index_options(ConsistencyCheck::Off)).unwrap();
+        index.insert("a".to_string(), b"1".to_vec()).unwrap();
+        index.flush().unwrap();
+        index.list_sstables()[0].path.clone()
+    };
+    fs::remove_file(&lost).unwrap();
+
+    let Err(error) = try_open_index_with_options(path, index_options(ConsistencyCheck::Verify))
+    else {
+        panic!("opened an index whose manifest lists a missing file");
+    };
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains(&lost));
+
+    let index = try_open_index_with_options(path, index_options(ConsistencyCheck::Off)).unwrap();
+    let report = index.check_consistency(true).unwrap();
+    assert_eq!(report.missing_files, vec![lost]);
+    assert!(report.repaired);
+    assert!(index.list_sstables().is_empty());
+    assert!(index.check_consistency(false).unwrap().is_consistent());
+}
+
+#[test]
+fn test_repair_adopts_readable_extras_and_sets_aside_the_rest() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().to_str().unwrap();
+
+    // A file written as if the manifest update was lost, and one cut short
+    let orphan = dir.path().join("sstable_000041.db");
+    let mut writer = SSTableWriter::new(orphan.to_str().unwrap(), 1, false, 0.01)?;
+    writer.set_file_number(41);
+    writer.write_entry("orphan", b"v")?;
+    writer.finalize()?;
+    let partial = dir.path().join("sstable_000042.db");
+    fs::write(&partial, b"not an sstable")?;
+
+    let report = try_open_index_with_options(path, index_options(ConsistencyCheck::Off))?
+        .check_consistency(false)
+        .unwrap();
+    assert_eq!(
+        report.unrecorded_files,
+        vec![orphan.to_string_lossy().to_string()]
+    );
+    assert_eq!(
+        report.unreadable_files,
+        vec![partial.to_string_lossy().to_string()]
+    );
+    assert!(try_open_index_with_options(path, index_options(ConsistencyCheck::Verify)).is_err());
+
+    let index = try_open_index_with_options(path, index_options(ConsistencyCheck::Repair))?;
+    assert_eq!(index.list_sstables()[0].file_number, 41);
+    assert_eq!(index.get("orphan").unwrap(), Some(b"v".to_vec()));
+    assert!(!partial.exists());
+    let set_aside = format!("{}{}", partial.to_string_lossy(), SET_ASIDE_SUFFIX);
+    assert!(fs::metadata(set_aside).is_ok());
+
+    drop(index);
+    try_open_index_with_options(path, index_options(ConsistencyCheck::Verify))?;
+    Ok(())
+}
+
+#[test]
+fn test_unfinished_checkpoints_are_reported_and_closed() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    fs::create_dir_all(dir.path().join("wal")).unwrap();
+    let checkpoint_id = {
+        // A flush that crashed after starting its checkpoint
+        let mut manager = DurabilityManager::new(&format!("{}/wal/wal.log", path), path).unwrap();
+        manager.begin_checkpoint().unwrap()
+    };
+
+    let report = try_open_index_with_options(path, index_options(ConsistencyCheck::Off))
+        .unwrap()
+        .check_consistency(false)
+        .unwrap();
+    assert_eq!(
+        report.checkpoint_mismatches,
+        vec![CheckpointMismatch::Unfinished { checkpoint_id }]
+    );
+    assert!(try_open_index_with_options(path, index_options(ConsistencyCheck::Verify)).is_err());
+
+    drop(try_open_index_with_options(path, index_options(ConsistencyCheck::Repair)).unwrap());
+    try_open_index_with_options(path, index_options(ConsistencyCheck::Verify)).unwrap();
+}
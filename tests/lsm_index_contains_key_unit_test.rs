@@ -0,0 +1,35 @@
+use lsmer::lsm_index::LsmIndex;
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_contains_key_and_may_exist() {
+    let test_future = async {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_str().unwrap().to_string();
+        let index = LsmIndex::new(1024 * 1024, path, None, true, 0.01).unwrap();
+
+        index.insert("present".to_string(), vec![1, 2, 3]).unwrap();
+        index.insert("removed".to_string(), vec![4, 5, 6]).unwrap();
+        index.remove("removed").unwrap();
+
+        assert!(index.contains_key("present").unwrap());
+        assert!(!index.contains_key("removed").unwrap());
+        assert!(!index.contains_key("missing").unwrap());
+
+        assert!(index.may_exist("present"));
+        assert!(!index.may_exist("removed"));
+        assert!(!index.may_exist("missing"));
+
+        // Re-inserting a removed key makes it visible again
+        index.insert("removed".to_string(), vec![7]).unwrap();
+        assert!(index.contains_key("removed").unwrap());
+        assert!(index.may_exist("removed"));
+    };
+
+    match timeout(Duration::from_secs(10), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 10 seconds"),
+    }
+}
@@ -0,0 +1,116 @@
+use lsmer::lsm_index::LsmIndex;
+use lsmer::sstable::digest::MerkleHasher;
+use lsmer::sstable::{properties, SSTableReader, SSTableWriter, VERSION};
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_content_hash_ignores_physical_layout() {
+    let test_future = async {
+        let dir_a = tempdir().unwrap();
+        let dir_b = tempdir().unwrap();
+        let a = LsmIndex::new(
+            1024 * 1024,
+            dir_a.path().to_str().unwrap().to_string(),
+            None,
+            true,
+            0.01,
+        )
+        .unwrap();
+        let b = LsmIndex::new(
+            1024 * 1024,
+            dir_b.path().to_str().unwrap().to_string(),
+            None,
+            false,
+            0.01,
+        )
+        .unwrap();
+
+        // Same live data, written in a different order and partly flushed on one side
+        for i in 0..20 {
+            a.insert(format!("key{:02}", i), vec![i as u8; 8]).unwrap();
+        }
+        a.flush().unwrap();
+        a.insert("key05".to_string(), b"updated".to_vec()).unwrap();
+        a.insert("doomed".to_string(), b"gone".to_vec()).unwrap();
+        a.remove("doomed").unwrap();
+
+        for i in (0..20).rev() {
+            b.insert(format!("key{:02}", i), vec![i as u8; 8]).unwrap();
+        }
+        b.insert("key05".to_string(), b"updated".to_vec()).unwrap();
+
+        let hash_a = a.compute_content_hash().unwrap();
+        let hash_b = b.compute_content_hash().unwrap();
+        assert_eq!(hash_a, hash_b);
+
+        // Any divergence changes the digest
+        b.insert("key07".to_string(), b"diverged".to_vec()).unwrap();
+        assert_ne!(hash_a, b.compute_content_hash().unwrap());
+    };
+
+    match timeout(Duration::from_secs(10), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 10 seconds"),
+    }
+}
+
+#[tokio::test]
+async fn test_flushed_sstables_record_digests() {
+    let test_future = async {
+        let temp_dir = tempdir().unwrap();
+        let index = LsmIndex::new(
+            1024 * 1024,
+            temp_dir.path().to_str().unwrap().to_string(),
+            None,
+            false,
+            0.01,
+        )
+        .unwrap();
+
+        index.insert("a".to_string(), b"1".to_vec()).unwrap();
+        index.insert("b".to_string(), b"2".to_vec()).unwrap();
+        index.flush().unwrap();
+
+        let mut expected = MerkleHasher::new();
+        expected.update("a", b"1");
+        expected.update("b", b"2");
+
+        let digests = index.sstable_digests();
+        assert_eq!(digests.len(), 1);
+        assert_eq!(digests[0].1, Some(expected.finish()));
+
+        // With a single SSTable holding all live data, the hashes agree
+        assert_eq!(index.compute_content_hash().unwrap(), expected.finish());
+    };
+
+    match timeout(Duration::from_secs(10), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 10 seconds"),
+    }
+}
+
+#[test]
+fn test_sstable_properties_round_trip() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("props.sst");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 10, true, 0.01).unwrap();
+    writer.write_entry("k1", b"v1").unwrap();
+    writer.write_entry("k2", b"v2").unwrap();
+    writer.finalize().unwrap();
+
+    let mut reader = SSTableReader::open(path).unwrap();
+    assert_eq!(reader.version(), VERSION);
+    assert_eq!(
+        reader.properties().get_u64(properties::PROP_NUM_ENTRIES),
+        Some(2)
+    );
+    assert!(reader.content_digest().is_some());
+
+    // The meta section must not disturb lookups or the Bloom filter
+    assert_eq!(reader.get("k2").unwrap(), Some(b"v2".to_vec()));
+    assert_eq!(reader.get("k3").unwrap(), None);
+}
@@ -0,0 +1,122 @@
+use lsmer::lsm_index::LsmIndex;
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::time::timeout;
+
+fn populated_index(path: String) -> LsmIndex {
+    let index = LsmIndex::new(1024 * 1024, path, None, false, 0.01).unwrap();
+    for i in 0..10 {
+        index.insert(format!("key{:02}", i), vec![i as u8]).unwrap();
+    }
+    index
+}
+
+#[tokio::test]
+async fn test_cursor_forward_and_backward() {
+    let test_future = async {
+        let temp_dir = tempdir().unwrap();
+        let index = populated_index(temp_dir.path().to_str().unwrap().to_string());
+        index.remove("key03").unwrap();
+
+        let mut cursor = index.cursor();
+        assert!(!cursor.valid());
+
+        cursor.seek_to_first().unwrap();
+        assert_eq!(cursor.key(), Some("key00"));
+        assert_eq!(cursor.value(), Some(&[0u8][..]));
+
+        // Walk forwards, skipping the removed key
+        let mut keys = Vec::new();
+        while cursor.valid() {
+            keys.push(cursor.key().unwrap().to_string());
+            cursor.next().unwrap();
+        }
+        assert_eq!(keys.len(), 9);
+        assert!(!keys.contains(&"key03".to_string()));
+
+        // Walk backwards from the end
+        cursor.seek_to_last().unwrap();
+        assert_eq!(cursor.key(), Some("key09"));
+        cursor.prev().unwrap();
+        assert_eq!(cursor.key(), Some("key08"));
+
+        // seek lands on the first key >= target, seek_for_prev on the last key <=
+        cursor.seek("key03").unwrap();
+        assert_eq!(cursor.key(), Some("key04"));
+        cursor.seek_for_prev("key03").unwrap();
+        assert_eq!(cursor.key(), Some("key02"));
+        cursor.seek_after("key05").unwrap();
+        assert_eq!(cursor.key(), Some("key06"));
+
+        // Moving past either end invalidates the cursor
+        cursor.seek_to_first().unwrap();
+        cursor.prev().unwrap();
+        assert!(!cursor.valid());
+        cursor.seek("zzz").unwrap();
+        assert!(!cursor.valid());
+    };
+
+    match timeout(Duration::from_secs(10), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 10 seconds"),
+    }
+}
+
+#[tokio::test]
+async fn test_cursor_keyset_pagination() {
+    let test_future = async {
+        let temp_dir = tempdir().unwrap();
+        let index = populated_index(temp_dir.path().to_str().unwrap().to_string());
+
+        let mut cursor = index.cursor().with_limit(4);
+        cursor.seek_to_first().unwrap();
+
+        let mut pages = Vec::new();
+        loop {
+            let page = cursor.collect_page().unwrap();
+            if page.is_empty() {
+                break;
+            }
+            let last_key = page.last().unwrap().0.clone();
+            pages.push(page);
+            cursor.seek_after(&last_key).unwrap();
+        }
+
+        let sizes: Vec<usize> = pages.iter().map(|page| page.len()).collect();
+        assert_eq!(sizes, vec![4, 4, 2]);
+        assert_eq!(pages[1][0].0, "key04");
+        assert_eq!(pages[2][1], ("key09".to_string(), vec![9]));
+    };
+
+    match timeout(Duration::from_secs(10), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 10 seconds"),
+    }
+}
+
+#[tokio::test]
+async fn test_cursor_reads_flushed_entries() {
+    let test_future = async {
+        let temp_dir = tempdir().unwrap();
+        let index = populated_index(temp_dir.path().to_str().unwrap().to_string());
+        index.flush().unwrap();
+        index.insert("key10".to_string(), vec![10]).unwrap();
+
+        let mut cursor = index.cursor();
+        cursor.seek("key08").unwrap();
+        let page = cursor.collect_page().unwrap();
+        assert_eq!(
+            page,
+            vec![
+                ("key08".to_string(), vec![8]),
+                ("key09".to_string(), vec![9]),
+                ("key10".to_string(), vec![10]),
+            ]
+        );
+    };
+
+    match timeout(Duration::from_secs(10), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 10 seconds"),
+    }
+}
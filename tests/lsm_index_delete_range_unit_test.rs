@@ -0,0 +1,210 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::LsmIndex;
+use lsmer::sstable::{
+    CompactionOptions, FragmentedRangeTombstones, RangeTombstone, SSTableCompaction, SSTableReader,
+    SSTableWriter,
+};
+use lsmer::wal::durability::Operation;
+use tempfile::tempdir;
+
+fn insert_all(index: &LsmIndex, keys: &[&str], value: &[u8]) {
+    for key in keys {
+        index.insert(key.to_string(), value.to_vec()).unwrap();
+    }
+}
+
+fn keys_of(index: &LsmIndex) -> Vec<String> {
+    index
+        .range(..)
+        .unwrap()
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect()
+}
+
+#[test]
+fn test_fragments_split_overlapping_ranges() {
+    let mut tombstones = FragmentedRangeTombstones::new();
+    tombstones.add(RangeTombstone::new("b", "f", 10));
+    tombstones.add(RangeTombstone::new("d", "h", 20));
+    tombstones.add(RangeTombstone::new("x", "x", 30));
+
+    assert_eq!(
+        tombstones.fragments(),
+        &[
+            RangeTombstone::new("b", "d", 10),
+            RangeTombstone::new("d", "h", 20),
+        ]
+    );
+    assert!(!tombstones.covers("a"));
+    assert_eq!(tombstones.covering("c").unwrap().deleted_at_ms, 10);
+    assert_eq!(tombstones.covering("e").unwrap().deleted_at_ms, 20);
+    assert!(!tombstones.covers("h"));
+    assert!(!tombstones.covers("x"));
+
+    // An older tombstone under a newer one leaves the newer time in place
+    tombstones.add(RangeTombstone::new("a", "z", 5));
+    assert_eq!(
+        tombstones.fragments(),
+        &[
+            RangeTombstone::new("a", "b", 5),
+            RangeTombstone::new("b", "d", 10),
+            RangeTombstone::new("d", "h", 20),
+            RangeTombstone::new("h", "z", 5),
+        ]
+    );
+}
+
+#[test]
+fn test_delete_range_record_round_trips() {
+    let operation = Operation::DeleteRange {
+        start: "user:1".to_string(),
+        end: "user:9".to_string(),
+    };
+    let record = operation.clone().into_record();
+    assert_eq!(Operation::from_record(record).unwrap(), operation);
+}
+
+#[test]
+fn test_sstable_round_trips_range_tombstones() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("ranges.db");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 1, true, 0.01).unwrap();
+    writer.write_entry("c", b"kept").unwrap();
+    writer.write_range_tombstone(RangeTombstone::new("a", "m", 7));
+    writer.write_range_tombstone(RangeTombstone::new("k", "q", 9));
+    writer.finalize().unwrap();
+
+    let reader = SSTableReader::open(path).unwrap();
+    assert_eq!(
+        reader.range_tombstones().fragments(),
+        &[
+            RangeTombstone::new("a", "k", 7),
+            RangeTombstone::new("k", "q", 9),
+        ]
+    );
+}
+
+#[test]
+fn test_delete_range_hides_memtable_and_flushed_keys() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    insert_all(&index, &["a", "b", "c"], b"flushed");
+    index.flush().unwrap();
+    insert_all(&index, &["bb", "d"], b"memtable");
+
+    index.delete_range("b", "d").unwrap();
+
+    assert_eq!(keys_of(&index), vec!["a", "d"]);
+    assert_eq!(index.get("b").unwrap(), None);
+    assert_eq!(index.get("bb").unwrap(), None);
+    assert!(!index.contains_key("c").unwrap());
+    assert!(!index.may_exist("c"));
+
+    let mut cursor = index.cursor();
+    cursor.seek("b").unwrap();
+    assert_eq!(cursor.key(), Some("d"));
+}
+
+#[test]
+fn test_writes_after_delete_range_stay_visible() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    insert_all(&index, &["b", "c"], b"old");
+    index.flush().unwrap();
+
+    index.delete_range("a", "z").unwrap();
+    index.insert("c".to_string(), b"new".to_vec()).unwrap();
+    assert_eq!(index.get("b").unwrap(), None);
+    assert_eq!(index.get("c").unwrap(), Some(b"new".to_vec()));
+
+    // The tombstone in the flushed file hides the old file but not its own entry
+    index.flush().unwrap();
+    assert_eq!(index.get("b").unwrap(), None);
+    assert_eq!(index.get("c").unwrap(), Some(b"new".to_vec()));
+    assert_eq!(index.get_flushed("b").unwrap(), None);
+    assert_eq!(index.get_flushed("c").unwrap(), Some(b"new".to_vec()));
+}
+
+#[test]
+fn test_empty_range_deletes_nothing() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    insert_all(&index, &["a", "b"], b"value");
+
+    index.delete_range("b", "b").unwrap();
+    index.delete_range("z", "a").unwrap();
+    assert_eq!(keys_of(&index), vec!["a", "b"]);
+}
+
+#[test]
+fn test_range_tombstones_survive_recovery() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    {
+        let index = open_index(path);
+        insert_all(&index, &["a", "b", "c", "d"], b"old");
+        index.flush().unwrap();
+        index.delete_range("b", "d").unwrap();
+        index.insert("c".to_string(), b"new".to_vec()).unwrap();
+        index.flush().unwrap();
+    }
+
+    let mut index = open_index(path);
+    index.recover().unwrap();
+    assert_eq!(keys_of(&index), vec!["a", "c", "d"]);
+    assert_eq!(index.get("c").unwrap(), Some(b"new".to_vec()));
+}
+
+#[test]
+fn test_compaction_drops_keys_under_newer_range_tombstones() {
+    let dir = tempdir().unwrap();
+    let older = dir.path().join("older.db");
+    let newer = dir.path().join("newer.db");
+    let output = dir.path().join("output.db");
+
+    let mut writer = SSTableWriter::new(older.to_str().unwrap(), 3, true, 0.01).unwrap();
+    for key in ["a", "b", "c"] {
+        writer.write_entry(key, b"old").unwrap();
+    }
+    writer.finalize().unwrap();
+
+    let mut writer = SSTableWriter::new(newer.to_str().unwrap(), 1, true, 0.01).unwrap();
+    writer.write_entry("b", b"new").unwrap();
+    writer.write_range_tombstone(RangeTombstone::new("b", "d", 1));
+    writer.finalize().unwrap();
+
+    let inputs = vec![
+        older.to_str().unwrap().to_string(),
+        newer.to_str().unwrap().to_string(),
+    ];
+    SSTableCompaction::compact_sstables_with_options(
+        &inputs,
+        output.to_str().unwrap(),
+        &CompactionOptions::default(),
+    )
+    .unwrap();
+
+    let reader = SSTableReader::open(output.to_str().unwrap()).unwrap();
+    let entries: Vec<(String, Vec<u8>)> = reader
+        .into_entries()
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        entries,
+        vec![
+            ("a".to_string(), b"old".to_vec()),
+            ("b".to_string(), b"new".to_vec()),
+        ]
+    );
+
+    // The tombstone is carried so it keeps hiding files older than the inputs
+    let reader = SSTableReader::open(output.to_str().unwrap()).unwrap();
+    assert!(reader.range_tombstones().covers("c"));
+}
@@ -0,0 +1,106 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::{DiffKind, KeyDifference};
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_diff_finds_divergent_keys() {
+    let test_future = async {
+        let dir_a = tempdir().unwrap();
+        let dir_b = tempdir().unwrap();
+        let a = open_index(dir_a.path().to_str().unwrap());
+        let b = open_index(dir_b.path().to_str().unwrap());
+
+        for i in 0..1000 {
+            let key = format!("key{:04}", i);
+            a.insert(key.clone(), vec![(i % 251) as u8]).unwrap();
+            b.insert(key, vec![(i % 251) as u8]).unwrap();
+        }
+        a.flush().unwrap();
+
+        // Identical stores have no differences
+        assert!(lsmer::diff(&a, &b).unwrap().is_empty());
+
+        b.insert("key0100".to_string(), b"changed".to_vec())
+            .unwrap();
+        b.remove("key0500").unwrap();
+        b.insert("key0750a".to_string(), b"extra".to_vec()).unwrap();
+        a.insert("zzz".to_string(), b"left only".to_vec()).unwrap();
+
+        let differences = lsmer::diff(&a, &b).unwrap();
+        assert_eq!(
+            differences,
+            vec![
+                KeyDifference {
+                    key: "key0100".to_string(),
+                    kind: DiffKind::ValueMismatch,
+                },
+                KeyDifference {
+                    key: "key0500".to_string(),
+                    kind: DiffKind::OnlyInLeft,
+                },
+                KeyDifference {
+                    key: "key0750a".to_string(),
+                    kind: DiffKind::OnlyInRight,
+                },
+                KeyDifference {
+                    key: "zzz".to_string(),
+                    kind: DiffKind::OnlyInLeft,
+                },
+            ]
+        );
+
+        // Repairing the differing keys makes the stores converge
+        for difference in &differences {
+            match a.get(&difference.key).unwrap() {
+                Some(value) => b.insert(difference.key.clone(), value).unwrap(),
+                None => {
+                    b.remove(&difference.key).unwrap();
+                }
+            }
+        }
+        assert!(lsmer::diff(&a, &b).unwrap().is_empty());
+        assert_eq!(
+            a.compute_content_hash().unwrap(),
+            b.compute_content_hash().unwrap()
+        );
+    };
+
+    match timeout(Duration::from_secs(30), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 30 seconds"),
+    }
+}
+
+#[tokio::test]
+async fn test_diff_against_empty_store() {
+    let test_future = async {
+        let dir_a = tempdir().unwrap();
+        let dir_b = tempdir().unwrap();
+        let a = open_index(dir_a.path().to_str().unwrap());
+        let b = open_index(dir_b.path().to_str().unwrap());
+
+        for i in 0..100 {
+            a.insert(format!("k{:03}", i), vec![1]).unwrap();
+        }
+
+        let differences = lsmer::diff(&a, &b).unwrap();
+        assert_eq!(differences.len(), 100);
+        assert!(differences
+            .iter()
+            .all(|difference| difference.kind == DiffKind::OnlyInLeft));
+
+        let reversed = lsmer::diff(&b, &a).unwrap();
+        assert!(reversed
+            .iter()
+            .all(|difference| difference.kind == DiffKind::OnlyInRight));
+    };
+
+    match timeout(Duration::from_secs(30), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 30 seconds"),
+    }
+}
@@ -0,0 +1,58 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::LsmIndexOptions;
+use tempfile::tempdir;
+
+const VALUE_SIZE: usize = 4096;
+const KEYS: usize = 200;
+
+fn index_options() -> LsmIndexOptions {
+    LsmIndexOptions::default().with_write_times(true)
+}
+
+fn value(i: usize) -> Vec<u8> {
+    vec![(i % 251) as u8; VALUE_SIZE]
+}
+
+#[test]
+fn test_flushed_and_recovered_values_stay_on_disk() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let total_bytes = (KEYS * VALUE_SIZE) as u64;
+    {
+        let index = open_index_with_options(path, index_options());
+        for i in 0..KEYS {
+            index.insert(format!("key{:04}", i), value(i)).unwrap();
+        }
+        index.remove("key0007").unwrap();
+        index.flush().unwrap();
+        assert!(index.resource_usage().index_bytes < total_bytes / 10);
+        assert_eq!(index.get("key0042").unwrap(), Some(value(42)));
+    }
+
+    let mut index = open_index_with_options(path, index_options());
+    index.recover().unwrap();
+    assert!(index.resource_usage().index_bytes < total_bytes / 10);
+
+    // Lookups, scans and metadata still come from the file
+    assert_eq!(index.get("key0199").unwrap(), Some(value(199)));
+    assert_eq!(index.get("key0007").unwrap(), None);
+    assert!(index.contains_key("key0100").unwrap());
+    let with_metadata = index.get_with_metadata("key0100").unwrap().unwrap();
+    assert_eq!(with_metadata.value, value(100));
+    assert!(with_metadata.written_at.is_some());
+    let scanned = index
+        .range("key0010".to_string().."key0013".to_string())
+        .unwrap();
+    assert_eq!(
+        scanned,
+        (10..13)
+            .map(|i| (format!("key{:04}", i), value(i)))
+            .collect::<Vec<_>>()
+    );
+
+    // Space amplification counts the values left on disk
+    let amplification = index.space_amplification_estimate().unwrap();
+    assert!((1.0..1.5).contains(&amplification), "{}", amplification);
+}
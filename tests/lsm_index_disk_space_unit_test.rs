@@ -0,0 +1,103 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{DiskSpaceProbe, FileSystemProbe, LsmIndexError, LsmIndexOptions};
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tempfile::tempdir;
+
+/// Reports whatever free space the test sets
+#[derive(Debug, Clone, Default)]
+struct FakeProbe(Arc<AtomicU64>);
+
+impl DiskSpaceProbe for FakeProbe {
+    fn available_bytes(&self, _path: &str) -> io::Result<u64> {
+        Ok(self.0.load(Ordering::SeqCst))
+    }
+}
+
+fn index_options(headroom: u64, probe: &FakeProbe) -> LsmIndexOptions {
+    LsmIndexOptions::default()
+        .with_reserved_headroom(headroom)
+        .with_disk_space_probe(probe.clone())
+}
+
+#[test]
+fn test_inserts_stop_at_the_headroom() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let probe = FakeProbe::default();
+    probe.0.store(1_000, Ordering::SeqCst);
+    let index = open_index_with_options(path, index_options(990, &probe));
+
+    index
+        .insert("a".to_string(), b"123456789".to_vec())
+        .unwrap();
+    let error = index
+        .insert("b".to_string(), b"1234567890".to_vec())
+        .unwrap_err();
+    match error {
+        LsmIndexError::DiskFull {
+            path: full_path,
+            available,
+            required,
+        } => {
+            assert_eq!(full_path, path);
+            assert_eq!(available, 1_000);
+            assert_eq!(required, 1_001);
+        }
+        other => panic!("expected DiskFull, got {:?}", other),
+    }
+    assert_eq!(index.get("b").unwrap(), None);
+
+    // Removals still go through, and writes resume once space is freed
+    assert_eq!(index.remove("a").unwrap(), Some(b"123456789".to_vec()));
+    probe.0.store(1_000_000, Ordering::SeqCst);
+    index
+        .insert("b".to_string(), b"1234567890".to_vec())
+        .unwrap();
+}
+
+#[test]
+fn test_flush_is_refused_before_writing_a_file() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let probe = FakeProbe::default();
+    probe.0.store(1_000_000, Ordering::SeqCst);
+    let index = open_index_with_options(path, index_options(1_000, &probe));
+    for i in 0..100 {
+        index.insert(format!("key{}", i), vec![b'v'; 100]).unwrap();
+    }
+
+    probe.0.store(2_000, Ordering::SeqCst);
+    assert!(matches!(index.flush(), Err(LsmIndexError::DiskFull { .. })));
+    assert!(index.list_sstables().is_empty());
+    assert!(index.check_consistency(false).unwrap().is_consistent());
+    assert_eq!(index.get("key7").unwrap(), Some(vec![b'v'; 100]));
+
+    probe.0.store(1_000_000, Ordering::SeqCst);
+    index.flush().unwrap();
+    assert_eq!(index.list_sstables().len(), 1);
+}
+
+#[test]
+fn test_no_headroom_skips_the_check() {
+    let dir = tempdir().unwrap();
+    let probe = FakeProbe::default();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(0, &probe));
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    index.flush().unwrap();
+}
+
+#[test]
+fn test_file_system_probe_reports_free_space() {
+    let dir = tempdir().unwrap();
+    let available = FileSystemProbe
+        .available_bytes(dir.path().to_str().unwrap())
+        .unwrap();
+    assert!(available > 0);
+    assert!(FileSystemProbe
+        .available_bytes("/no/such/directory")
+        .is_err());
+}
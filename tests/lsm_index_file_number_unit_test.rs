@@ -0,0 +1,113 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{LsmIndexOptions, Manifest};
+use lsmer::sstable::{SSTableReader, SSTableWriter, VERSION};
+use std::io;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn file_name(path: &str) -> &str {
+    Path::new(path).file_name().unwrap().to_str().unwrap()
+}
+
+#[test]
+fn test_flushes_get_increasing_numbers_in_names_and_headers() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = open_index_with_options(path, LsmIndexOptions::default());
+
+    for i in 0..3 {
+        index.insert(format!("key{}", i), b"v".to_vec()).unwrap();
+        index.flush().unwrap();
+    }
+
+    let infos = index.list_sstables();
+    let numbers: Vec<u64> = infos.iter().map(|info| info.file_number).collect();
+    assert_eq!(numbers, vec![1, 2, 3]);
+    for info in &infos {
+        assert_eq!(
+            file_name(&info.path),
+            format!("sstable_{:06}.db", info.file_number)
+        );
+        let reader = SSTableReader::open(&info.path).unwrap();
+        assert_eq!(reader.version(), VERSION);
+        assert_eq!(reader.file_number(), Some(info.file_number));
+    }
+    assert_eq!(Manifest::open(path).unwrap().next_file_number(), 4);
+}
+
+#[test]
+fn test_numbering_continues_after_restart() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    {
+        let index = open_index_with_options(path, LsmIndexOptions::default());
+        index.insert("a".to_string(), b"1".to_vec()).unwrap();
+        index.flush().unwrap();
+    }
+
+    let mut index = open_index_with_options(path, LsmIndexOptions::default());
+    index.recover().unwrap();
+    index.insert("b".to_string(), b"2".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    let numbers: Vec<u64> = index
+        .list_sstables()
+        .iter()
+        .map(|info| info.file_number)
+        .collect();
+    assert_eq!(numbers, vec![1, 2]);
+}
+
+#[test]
+fn test_same_second_overwrites_recover_in_write_order() {
+    let dir = tempdir().unwrap();
+    let base = dir.path().join("base");
+    let base = base.to_str().unwrap();
+    // Directory names sort against the write order, so only the file
+    // numbers can tell which flush came last
+    let disks = [
+        dir.path().join("z").to_str().unwrap().to_string(),
+        dir.path().join("a").to_str().unwrap().to_string(),
+    ];
+    let options = LsmIndexOptions::default().with_data_directories(disks);
+
+    {
+        let index = open_index_with_options(base, options.clone());
+        for value in [b"old", b"new"] {
+            index.insert("key".to_string(), value.to_vec()).unwrap();
+            index.flush().unwrap();
+        }
+        assert_eq!(index.get("key").unwrap(), Some(b"new".to_vec()));
+    }
+
+    let mut index = open_index_with_options(base, options);
+    index.recover().unwrap();
+    assert_eq!(index.get("key").unwrap(), Some(b"new".to_vec()));
+    assert_eq!(index.get_flushed("key").unwrap(), Some(b"new".to_vec()));
+}
+
+#[test]
+fn test_unrecorded_files_reserve_their_numbers() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().to_str().unwrap();
+
+    // A file written outside the index, as if the manifest update was lost
+    let orphan = dir.path().join("sstable_000041.db");
+    let mut writer = SSTableWriter::new(orphan.to_str().unwrap(), 1, false, 0.01)?;
+    writer.set_file_number(41);
+    writer.write_entry("orphan", b"v")?;
+    writer.finalize()?;
+
+    let mut index = open_index_with_options(path, LsmIndexOptions::default());
+    index.recover().unwrap();
+    assert_eq!(index.list_sstables()[0].file_number, 41);
+
+    index.insert("next".to_string(), b"v".to_vec()).unwrap();
+    index.flush().unwrap();
+    let newest = index.list_sstables().pop().unwrap();
+    assert_eq!(newest.file_number, 42);
+    Ok(())
+}
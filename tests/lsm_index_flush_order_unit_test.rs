@@ -0,0 +1,134 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::clock::MockClock;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::{SSTableCompaction, SSTableReader, SSTableWriter};
+use std::fs;
+use tempfile::tempdir;
+
+const START_MS: u64 = 1_700_000_000_000;
+
+fn index_options(clock: &MockClock) -> LsmIndexOptions {
+    LsmIndexOptions::default().with_clock(clock.clone())
+}
+
+/// Flush two values of `k`, the second with the clock set back an hour, so
+/// the newer file carries the earlier write time
+fn flush_across_clock_step(index: &LsmIndex, clock: &MockClock) {
+    index.insert("k".to_string(), b"old".to_vec()).unwrap();
+    index.insert("a".to_string(), b"a".to_vec()).unwrap();
+    index.flush().unwrap();
+    clock.set(START_MS - 3_600_000);
+    index.insert("k".to_string(), b"new".to_vec()).unwrap();
+    index.insert("z".to_string(), b"z".to_vec()).unwrap();
+    index.flush().unwrap();
+}
+
+#[test]
+fn test_flushed_files_record_their_flush_number() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(&clock));
+    flush_across_clock_step(&index, &clock);
+
+    let files = index.list_sstables();
+    assert_eq!(files.len(), 2);
+    assert!(files[0].flush_number < files[1].flush_number);
+    assert!(files[0].created_at_secs > files[1].created_at_secs);
+    for file in &files {
+        assert_eq!(file.flush_number, file.file_number);
+        let reader = SSTableReader::open(&file.path).unwrap();
+        assert_eq!(reader.flush_number(), Some(file.flush_number));
+    }
+    assert_eq!(index.get_flushed("k").unwrap(), Some(b"new".to_vec()));
+    assert_eq!(index.probe_order("k")[0], files[1].path);
+}
+
+#[test]
+fn test_recovery_applies_files_in_flush_order_despite_the_clock() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let clock = MockClock::new(START_MS);
+    {
+        let index = open_index_with_options(path, index_options(&clock));
+        flush_across_clock_step(&index, &clock);
+    }
+
+    let mut index = open_index_with_options(path, index_options(&clock));
+    index.recover().unwrap();
+    assert_eq!(index.get("k").unwrap(), Some(b"new".to_vec()));
+    drop(index);
+
+    // Without a manifest the numbers come from the files themselves
+    fs::remove_file(dir.path().join("MANIFEST")).unwrap();
+    let mut index = open_index_with_options(path, index_options(&clock));
+    index.recover().unwrap();
+    assert_eq!(index.get("k").unwrap(), Some(b"new".to_vec()));
+}
+
+#[test]
+fn test_compaction_output_takes_the_newest_flush_number_of_its_inputs() {
+    let dir = tempdir().unwrap();
+    let mut inputs = Vec::new();
+    for (flush_number, key) in [(3, "a"), (9, "b"), (5, "c")] {
+        let path = dir.path().join(format!("{}.sst", key));
+        let path = path.to_str().unwrap().to_string();
+        let mut writer = SSTableWriter::new(&path, 1, false, 0.0).unwrap();
+        writer.set_file_number(flush_number + 100);
+        writer.set_flush_number(flush_number);
+        writer.write_entry(key, b"v").unwrap();
+        writer.finalize().unwrap();
+        inputs.push(path);
+    }
+
+    let output = dir.path().join("output.sst");
+    let output = output.to_str().unwrap();
+    SSTableCompaction::compact_sstables(&inputs, output, false, false, 0.0).unwrap();
+    assert_eq!(SSTableReader::open(output).unwrap().flush_number(), Some(9));
+
+    // Files without the property fall back to their file number
+    let legacy = dir.path().join("legacy.sst");
+    let mut writer = SSTableWriter::new(legacy.to_str().unwrap(), 1, false, 0.0).unwrap();
+    writer.set_file_number(42);
+    writer.write_entry("d", b"v").unwrap();
+    writer.finalize().unwrap();
+    assert_eq!(
+        SSTableReader::open(legacy.to_str().unwrap())
+            .unwrap()
+            .flush_number(),
+        Some(42)
+    );
+}
+
+#[test]
+fn test_compacted_file_stays_behind_a_newer_flush() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let clock = MockClock::new(START_MS);
+    {
+        let index = open_index_with_options(path, index_options(&clock));
+        index.insert("k".to_string(), b"first".to_vec()).unwrap();
+        index.flush().unwrap();
+        index.insert("k".to_string(), b"second".to_vec()).unwrap();
+        index.flush().unwrap();
+        index.compact_range(..).unwrap();
+        let compacted = index.list_sstables();
+        assert_eq!(compacted.len(), 1);
+
+        clock.set(START_MS - 3_600_000);
+        index.insert("k".to_string(), b"third".to_vec()).unwrap();
+        index.flush().unwrap();
+        let files = index.list_sstables();
+        let flushed = files
+            .iter()
+            .find(|file| file.path != compacted[0].path)
+            .unwrap();
+        assert!(compacted[0].flush_number < flushed.flush_number);
+        assert_eq!(index.get_flushed("k").unwrap(), Some(b"third".to_vec()));
+    }
+
+    let mut index = open_index_with_options(path, index_options(&clock));
+    index.recover().unwrap();
+    assert_eq!(index.get("k").unwrap(), Some(b"third".to_vec()));
+}
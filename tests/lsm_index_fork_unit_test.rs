@@ -0,0 +1,84 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::LsmIndexError;
+use tempfile::tempdir;
+
+#[test]
+fn test_fork_sees_flushed_and_unflushed_data() {
+    let source_dir = tempdir().unwrap();
+    let fork_dir = tempdir().unwrap();
+    let fork_path = fork_dir.path().join("fork");
+    let fork_path = fork_path.to_str().unwrap();
+
+    let source = open_index(source_dir.path().to_str().unwrap());
+    source.insert("flushed".to_string(), b"1".to_vec()).unwrap();
+    source.flush().unwrap();
+    source.insert("pending".to_string(), b"2".to_vec()).unwrap();
+
+    let fork = source.fork(fork_path).unwrap();
+    assert_eq!(fork.get("flushed").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(fork.get("pending").unwrap(), Some(b"2".to_vec()));
+    assert_eq!(fork.list_sstables().len(), source.list_sstables().len());
+    for info in fork.list_sstables() {
+        assert!(info.path.starts_with(fork_path));
+    }
+}
+
+#[test]
+fn test_fork_is_independent_of_source() {
+    let source_dir = tempdir().unwrap();
+    let fork_dir = tempdir().unwrap();
+    let fork_path = fork_dir.path().to_str().unwrap();
+
+    let source = open_index(source_dir.path().to_str().unwrap());
+    source.insert("shared".to_string(), b"1".to_vec()).unwrap();
+    let fork = source.fork(fork_path).unwrap();
+
+    fork.insert("shared".to_string(), b"fork".to_vec()).unwrap();
+    fork.insert("fork_only".to_string(), b"2".to_vec()).unwrap();
+    source.remove("shared").unwrap();
+
+    assert_eq!(source.get("shared").unwrap(), None);
+    assert_eq!(source.get("fork_only").unwrap(), None);
+    assert_eq!(fork.get("shared").unwrap(), Some(b"fork".to_vec()));
+
+    // The fork reopens from its own manifest
+    drop(fork);
+    let mut reopened = open_index(fork_path);
+    reopened.recover().unwrap();
+    assert_eq!(reopened.get("shared").unwrap(), Some(b"1".to_vec()));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fork_hard_links_sstables() {
+    use std::os::unix::fs::MetadataExt;
+
+    let source_dir = tempdir().unwrap();
+    let fork_dir = tempdir().unwrap();
+
+    let source = open_index(source_dir.path().to_str().unwrap());
+    source.insert("key".to_string(), b"value".to_vec()).unwrap();
+    let fork = source.fork(fork_dir.path().to_str().unwrap()).unwrap();
+
+    let source_file = source.list_sstables()[0].path.clone();
+    let fork_file = fork.list_sstables()[0].path.clone();
+    let source_meta = std::fs::metadata(&source_file).unwrap();
+    let fork_meta = std::fs::metadata(&fork_file).unwrap();
+    assert_eq!(source_meta.ino(), fork_meta.ino());
+}
+
+#[test]
+fn test_fork_refuses_existing_database() {
+    let source_dir = tempdir().unwrap();
+    let other_dir = tempdir().unwrap();
+
+    let source = open_index(source_dir.path().to_str().unwrap());
+    let _other = open_index(other_dir.path().to_str().unwrap());
+
+    assert!(matches!(
+        source.fork(other_dir.path().to_str().unwrap()),
+        Err(LsmIndexError::InvalidOperation(_))
+    ));
+}
@@ -0,0 +1,157 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::clock::MockClock;
+use lsmer::lsm_index::{AsOf, LsmIndexError, LsmIndexOptions, WriteOptions};
+use std::time::Duration;
+use tempfile::tempdir;
+
+const START_MS: u64 = 1_700_000_000_000;
+const MINUTE: Duration = Duration::from_secs(60);
+
+fn index_options(clock: &MockClock, retention: Option<Duration>) -> LsmIndexOptions {
+    let mut options = LsmIndexOptions::default().with_clock(clock.clone());
+    if let Some(retention) = retention {
+        options = options.with_version_retention(retention);
+    }
+    options
+}
+
+#[test]
+fn test_get_at_sequence_and_time() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(&clock, Some(10 * MINUTE)),
+    );
+    let options = WriteOptions::default();
+
+    clock.advance(MINUTE);
+    let first = index
+        .insert_with_options("key".to_string(), b"one".to_vec(), &options)
+        .unwrap();
+    clock.advance(MINUTE);
+    let second = index
+        .insert_with_options("key".to_string(), b"two".to_vec(), &options)
+        .unwrap();
+    clock.advance(MINUTE);
+    let (_, removed) = index.remove_versioned("key", &options).unwrap();
+
+    assert_eq!(
+        index.get_at("key", AsOf::Sequence(first)).unwrap(),
+        Some(b"one".to_vec())
+    );
+    assert_eq!(
+        index.get_at("key", AsOf::Sequence(second)).unwrap(),
+        Some(b"two".to_vec())
+    );
+    assert_eq!(index.get_at("key", AsOf::Sequence(removed)).unwrap(), None);
+    assert_eq!(
+        index.get_at("key", AsOf::Time(START_MS + 90_000)).unwrap(),
+        Some(b"one".to_vec())
+    );
+    assert_eq!(
+        index.get_at("key", AsOf::Time(START_MS + 150_000)).unwrap(),
+        Some(b"two".to_vec())
+    );
+
+    let history = index.history("key").unwrap();
+    assert_eq!(
+        history.iter().map(|v| v.sequence).collect::<Vec<_>>(),
+        vec![first, second, removed]
+    );
+    assert_eq!(history[0].written_at_ms, START_MS + 60_000);
+    assert_eq!(history[2].value, None);
+
+    // Versions survive a flush
+    index.insert("other".to_string(), b"x".to_vec()).unwrap();
+    index.flush().unwrap();
+    assert_eq!(
+        index.get_at("key", AsOf::Sequence(first)).unwrap(),
+        Some(b"one".to_vec())
+    );
+}
+
+#[test]
+fn test_unwritten_keys_and_unretained_points() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(&clock, Some(10 * MINUTE)),
+    );
+
+    // A key not written since the index was opened reads as it is now
+    let opened = index.last_sequence();
+    assert_eq!(
+        index.get_at("missing", AsOf::Sequence(opened)).unwrap(),
+        None
+    );
+    assert!(index.history("missing").unwrap().is_empty());
+
+    // Before a key's first retained version there is nothing to read
+    clock.advance(MINUTE);
+    index.insert("key".to_string(), b"one".to_vec()).unwrap();
+    assert!(matches!(
+        index.get_at("key", AsOf::Sequence(opened)),
+        Err(LsmIndexError::InvalidOperation(_))
+    ));
+}
+
+#[test]
+fn test_versions_expire_after_retention() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(&clock, Some(10 * MINUTE)),
+    );
+
+    for value in ["one", "two", "three"] {
+        index
+            .insert("key".to_string(), value.as_bytes().to_vec())
+            .unwrap();
+        clock.advance(MINUTE);
+    }
+    assert_eq!(index.purge_expired_versions(), 0);
+    assert_eq!(index.history("key").unwrap().len(), 3);
+
+    // Once superseded long enough ago, only the version current at the
+    // start of the window is kept
+    clock.advance(10 * MINUTE);
+    assert_eq!(index.purge_expired_versions(), 2);
+    let history = index.history("key").unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].value, Some(b"three".to_vec()));
+
+    // An open snapshot holds on to what it can read
+    let snapshot = index.acquire_snapshot();
+    clock.advance(MINUTE);
+    index.insert("key".to_string(), b"four".to_vec()).unwrap();
+    clock.advance(20 * MINUTE);
+    assert_eq!(index.purge_expired_versions(), 0);
+    assert_eq!(
+        index.get_at("key", AsOf::Time(snapshot.at_ms())).unwrap(),
+        Some(b"three".to_vec())
+    );
+    drop(snapshot);
+    assert_eq!(index.purge_expired_versions(), 1);
+}
+
+#[test]
+fn test_requires_version_retention() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(&clock, None));
+    index.insert("key".to_string(), b"one".to_vec()).unwrap();
+
+    assert!(matches!(
+        index.history("key"),
+        Err(LsmIndexError::InvalidOperation(_))
+    ));
+    assert!(matches!(
+        index.get_at("key", AsOf::Sequence(index.last_sequence())),
+        Err(LsmIndexError::InvalidOperation(_))
+    ));
+}
@@ -0,0 +1,156 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::clock::MockClock;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::ingest::assign_global_sequence;
+use lsmer::sstable::{SSTableReader, SSTableWriter, Tombstone};
+use std::path::Path;
+use std::time::Duration;
+use tempfile::tempdir;
+
+const START_MS: u64 = 1_700_000_000_000;
+
+fn index_options(clock: &MockClock) -> LsmIndexOptions {
+    LsmIndexOptions::default().with_clock(clock.clone())
+}
+
+fn write_external(path: &Path, entries: &[(&str, &str)]) -> String {
+    let path = path.to_str().unwrap().to_string();
+    let mut writer = SSTableWriter::new(&path, entries.len(), true, 0.01).unwrap();
+    for (key, value) in entries {
+        writer.write_entry(key, value.as_bytes()).unwrap();
+    }
+    writer.finalize().unwrap();
+    path
+}
+
+/// The live file stamped with a global sequence, which only ingests write
+fn ingested_file(index: &LsmIndex) -> SSTableReader {
+    index
+        .list_sstables()
+        .iter()
+        .map(|info| SSTableReader::open(&info.path).unwrap())
+        .find(|reader| reader.global_sequence().is_some())
+        .unwrap()
+}
+
+#[test]
+fn test_ingested_file_orders_after_existing_data() {
+    let dir = tempdir().unwrap();
+    let external = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(&clock));
+
+    index.insert("a".to_string(), b"flushed".to_vec()).unwrap();
+    index.flush().unwrap();
+    index.insert("b".to_string(), b"memtable".to_vec()).unwrap();
+    let before = index.last_sequence();
+
+    let source = write_external(
+        &external.path().join("bulk.db"),
+        &[("a", "ingested"), ("b", "ingested"), ("c", "ingested")],
+    );
+    let sequence = index.ingest_sstable(&source).unwrap();
+
+    assert!(sequence > before);
+    for key in ["a", "b", "c"] {
+        assert_eq!(index.get(key).unwrap(), Some(b"ingested".to_vec()));
+        assert_eq!(index.sequence_of(key), sequence);
+    }
+    assert!(index.last_sequence() >= sequence);
+
+    // The source is untouched and the copy records its sequence
+    assert_eq!(
+        SSTableReader::open(&source).unwrap().global_sequence(),
+        None
+    );
+    assert_eq!(ingested_file(&index).global_sequence(), Some(sequence));
+}
+
+#[test]
+fn test_snapshot_taken_before_ingest_does_not_see_it() {
+    let dir = tempdir().unwrap();
+    let external = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(&clock));
+
+    let snapshot = index.acquire_snapshot();
+    clock.advance(Duration::from_millis(10));
+    let source = write_external(&external.path().join("bulk.db"), &[("k", "v")]);
+    index.ingest_sstable(&source).unwrap();
+
+    assert_eq!(
+        index
+            .get_with_options("k", &snapshot.read_options())
+            .unwrap(),
+        None
+    );
+    assert_eq!(index.get("k").unwrap(), Some(b"v".to_vec()));
+}
+
+#[test]
+fn test_ingest_carries_tombstones_over_older_data() {
+    let dir = tempdir().unwrap();
+    let external = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(&clock));
+    index.insert("gone".to_string(), b"old".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    let source = external.path().join("bulk.db");
+    let source = source.to_str().unwrap();
+    let mut writer = SSTableWriter::new(source, 1, true, 0.01).unwrap();
+    writer.write_entry("kept", b"new").unwrap();
+    writer.write_tombstone(
+        "gone",
+        Tombstone {
+            deleted_at_ms: START_MS,
+            value: None,
+        },
+    );
+    writer.finalize().unwrap();
+
+    index.ingest_sstable(source).unwrap();
+    assert_eq!(index.get("gone").unwrap(), None);
+    assert_eq!(index.get("kept").unwrap(), Some(b"new".to_vec()));
+}
+
+#[test]
+fn test_sequences_stay_ahead_of_ingested_files_after_restart() {
+    let dir = tempdir().unwrap();
+    let external = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let clock = MockClock::new(START_MS);
+
+    let sequence = {
+        let index = open_index_with_options(path, index_options(&clock));
+        let source = write_external(&external.path().join("bulk.db"), &[("k", "v")]);
+        let sequence = index.ingest_sstable(&source).unwrap();
+        assert_eq!(ingested_file(&index).global_sequence(), Some(sequence));
+        sequence
+    };
+
+    // Reopened at the same time, sequences would otherwise start behind it
+    let mut index = open_index_with_options(path, index_options(&clock));
+    index.recover().unwrap();
+    assert_eq!(index.get("k").unwrap(), Some(b"v".to_vec()));
+    assert!(index.last_sequence() >= sequence);
+
+    index.insert("k".to_string(), b"newer".to_vec()).unwrap();
+    assert!(index.sequence_of("k") > sequence);
+}
+
+#[test]
+fn test_assign_global_sequence_rewrites_in_place() {
+    let dir = tempdir().unwrap();
+    let path = write_external(&dir.path().join("restored.db"), &[("a", "1"), ("b", "2")]);
+
+    assign_global_sequence(Path::new(&path), 42).unwrap();
+
+    let mut reader = SSTableReader::open(&path).unwrap();
+    assert_eq!(reader.global_sequence(), Some(42));
+    assert_eq!(reader.get("a").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(reader.get("b").unwrap(), Some(b"2".to_vec()));
+    assert!(!Path::new(&format!("{}.ingest", path)).exists());
+}
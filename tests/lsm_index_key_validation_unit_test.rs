@@ -0,0 +1,69 @@
+use lsmer::lsm_index::{LsmIndex, LsmIndexError};
+use lsmer::sstable::{SSTableReader, SSTableWriter};
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_insert_rejects_keys_with_nul_bytes() {
+    let test_future = async {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_str().unwrap().to_string();
+        let index = LsmIndex::new(1024 * 1024, path, None, false, 0.01).unwrap();
+
+        match index.insert("bad\0key".to_string(), vec![1, 2, 3]) {
+            Err(LsmIndexError::InvalidKey(reason)) => assert!(reason.contains("NUL")),
+            other => panic!("Expected InvalidKey, got {:?}", other),
+        }
+
+        // Nothing should have been written for the rejected key
+        assert_eq!(index.get("bad\0key").unwrap(), None);
+        assert_eq!(index.get("bad").unwrap(), None);
+    };
+
+    match timeout(Duration::from_secs(10), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 10 seconds"),
+    }
+}
+
+#[tokio::test]
+async fn test_insert_bytes_validates_utf8() {
+    let test_future = async {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_str().unwrap().to_string();
+        let index = LsmIndex::new(1024 * 1024, path, None, false, 0.01).unwrap();
+
+        // Valid UTF-8 byte keys behave like string keys
+        index.insert_bytes("caf\u{e9}".as_bytes(), vec![7]).unwrap();
+        assert_eq!(index.get("caf\u{e9}").unwrap(), Some(vec![7]));
+
+        // Invalid UTF-8 is rejected up front
+        match index.insert_bytes(&[0x66, 0x6f, 0xff, 0xfe], vec![1]) {
+            Err(LsmIndexError::InvalidKey(reason)) => assert!(reason.contains("UTF-8")),
+            other => panic!("Expected InvalidKey, got {:?}", other),
+        }
+    };
+
+    match timeout(Duration::from_secs(10), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 10 seconds"),
+    }
+}
+
+#[test]
+fn test_sstable_round_trips_empty_key() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("empty_key.sst");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 10, false, 0.01).unwrap();
+    writer.write_entry("", b"empty").unwrap();
+    writer.write_entry("a", b"value").unwrap();
+    writer.finalize().unwrap();
+
+    // An empty key must not make the rest of the file unreadable
+    let mut reader = SSTableReader::open(path).unwrap();
+    assert_eq!(reader.get("").unwrap(), Some(b"empty".to_vec()));
+    assert_eq!(reader.get("a").unwrap(), Some(b"value".to_vec()));
+}
@@ -0,0 +1,72 @@
+mod helpers;
+
+use helpers::open_index;
+use tempfile::tempdir;
+
+#[test]
+fn test_no_reads_leave_no_levels() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    index.insert("a".to_string(), b"value".to_vec()).unwrap();
+    index.get("a").unwrap();
+
+    // Memtable reads are not charged to any level
+    assert!(index.level_read_stats().is_empty());
+}
+
+#[test]
+fn test_gets_are_charged_to_the_level_of_their_file() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    {
+        let index = open_index(path);
+        for key in ["a", "b", "c"] {
+            index.insert(key.to_string(), b"value".to_vec()).unwrap();
+        }
+        index.flush().unwrap();
+    }
+
+    // Recovered entries are read from disk
+    let mut index = open_index(path);
+    index.recover().unwrap();
+    for key in ["a", "b", "c", "a"] {
+        assert_eq!(index.get(key).unwrap(), Some(b"value".to_vec()));
+    }
+
+    let stats = index.level_read_stats();
+    assert_eq!(stats.len(), 1);
+    let level0 = &stats[0];
+    assert_eq!(level0.level, 0);
+    assert_eq!(level0.gets, 4);
+    assert!(level0.max_get_latency <= level0.total_get_latency);
+    assert!(level0.average_get_latency().unwrap() <= level0.max_get_latency);
+    assert_eq!(level0.block_reads, index.level_read_count(0));
+}
+
+#[test]
+fn test_filter_outcomes_are_counted_per_level() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    index.insert("a".to_string(), b"old".to_vec()).unwrap();
+    index.flush().unwrap();
+    index.insert("b".to_string(), b"new".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    assert_eq!(index.get_flushed("a").unwrap(), Some(b"old".to_vec()));
+    assert_eq!(index.get_flushed("b").unwrap(), Some(b"new".to_vec()));
+
+    let level0 = &index.level_read_stats()[0];
+    assert_eq!(level0.hits, 2);
+    assert_eq!(level0.block_reads, 2);
+    assert_eq!(level0.false_positive_rate(), 0.0);
+
+    let probes: u64 = index
+        .sstable_access_stats()
+        .iter()
+        .map(|file| file.probes)
+        .sum();
+    assert_eq!(
+        level0.hits + level0.bloom_negatives + level0.false_positives,
+        probes
+    );
+}
@@ -0,0 +1,81 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::{LifetimeStats, STATS_FILE_NAME};
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_counters_track_writes_flushes_and_retirements() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    assert_eq!(index.lifetime_stats(), LifetimeStats::default());
+
+    index.insert("a".to_string(), b"123".to_vec()).unwrap();
+    index.insert("bb".to_string(), b"45".to_vec()).unwrap();
+    index.remove("a").unwrap();
+    index.flush().unwrap();
+    let path = index.list_sstables()[0].path.clone();
+    assert!(index.retire_sstable(&path).unwrap());
+
+    assert_eq!(
+        index.lifetime_stats(),
+        LifetimeStats {
+            bytes_written: 8,
+            keys_written: 2,
+            keys_removed: 1,
+            flushes: 1,
+            files_retired: 1,
+        }
+    );
+}
+
+#[test]
+fn test_counters_survive_clean_shutdown() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let mut index = open_index(path);
+    index.insert("key".to_string(), b"value".to_vec()).unwrap();
+    index.flush().unwrap();
+    let before = index.lifetime_stats();
+    index.shutdown().unwrap();
+    drop(index);
+
+    // The reopened index continues from the saved totals
+    let index = open_index(path);
+    assert_eq!(index.lifetime_stats(), before);
+    index.insert("other".to_string(), b"v".to_vec()).unwrap();
+    assert_eq!(index.lifetime_stats().keys_written, before.keys_written + 1);
+}
+
+#[test]
+fn test_counters_without_shutdown_start_from_last_save() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = open_index(path);
+    index.insert("saved".to_string(), b"1".to_vec()).unwrap();
+    index.save_lifetime_stats().unwrap();
+    index.insert("lost".to_string(), b"2".to_vec()).unwrap();
+    drop(index);
+
+    let index = open_index(path);
+    assert_eq!(index.lifetime_stats().keys_written, 1);
+}
+
+#[test]
+fn test_damaged_stats_file_starts_from_zero() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let mut index = open_index(path);
+    index.insert("key".to_string(), b"value".to_vec()).unwrap();
+    index.shutdown().unwrap();
+    drop(index);
+
+    let stats_path = dir.path().join(STATS_FILE_NAME);
+    let mut bytes = fs::read(&stats_path).unwrap();
+    bytes[12] ^= 0xFF;
+    fs::write(&stats_path, bytes).unwrap();
+
+    let index = open_index(path);
+    assert_eq!(index.lifetime_stats(), LifetimeStats::default());
+}
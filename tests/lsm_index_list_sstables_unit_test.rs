@@ -0,0 +1,114 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::LsmIndex;
+use lsmer::lsm_index::manifest::MANIFEST_FILE_NAME;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tempfile::tempdir;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[test]
+fn test_list_sstables_reports_flushed_file() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = open_index(path);
+    assert!(index.list_sstables().is_empty());
+
+    let before = now_secs();
+    for i in 0..50 {
+        index
+            .insert(format!("key{:02}", i), vec![b'v'; 100])
+            .unwrap();
+    }
+    index.flush().unwrap();
+
+    let infos = index.list_sstables();
+    assert_eq!(infos.len(), 1);
+    let info = &infos[0];
+    assert_eq!(info.level, 0);
+    assert_eq!(info.entry_count, 50);
+    assert_eq!(info.min_key.as_deref(), Some("key00"));
+    assert_eq!(info.max_key.as_deref(), Some("key49"));
+    assert_eq!(
+        info.size_bytes,
+        std::fs::metadata(&info.path).unwrap().len()
+    );
+    assert!(info.has_bloom_filter);
+    assert!(info.bloom_bytes > 0);
+    assert!(info.compression_ratio > 0.0 && info.compression_ratio <= 1.0);
+    assert!(info.created_at_secs >= before && info.created_at_secs <= now_secs());
+}
+
+#[test]
+fn test_list_sstables_without_bloom_filter() {
+    let dir = tempdir().unwrap();
+    let index = LsmIndex::new(
+        4 * 1024 * 1024,
+        dir.path().to_str().unwrap().to_string(),
+        None,
+        false,
+        0.01,
+    )
+    .unwrap();
+
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    let infos = index.list_sstables();
+    assert_eq!(infos.len(), 1);
+    assert!(!infos[0].has_bloom_filter);
+    assert_eq!(infos[0].bloom_bytes, 0);
+}
+
+#[test]
+fn test_manifest_survives_reopen() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    let expected = {
+        let index = open_index(path);
+        index.insert("apple".to_string(), b"red".to_vec()).unwrap();
+        index.insert("pear".to_string(), b"green".to_vec()).unwrap();
+        index.flush().unwrap();
+        index.list_sstables()
+    };
+
+    assert!(dir.path().join(MANIFEST_FILE_NAME).exists());
+    let reopened = open_index(path);
+    assert_eq!(reopened.list_sstables(), expected);
+}
+
+#[test]
+fn test_recover_adopts_unlisted_files() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    let expected = {
+        let index = open_index(path);
+        index.insert("k1".to_string(), b"v1".to_vec()).unwrap();
+        index.insert("k2".to_string(), b"v2".to_vec()).unwrap();
+        index.flush().unwrap();
+        index.list_sstables()
+    };
+
+    // Simulate a directory written before the manifest existed
+    std::fs::remove_file(dir.path().join(MANIFEST_FILE_NAME)).unwrap();
+
+    let mut index = open_index(path);
+    assert!(index.list_sstables().is_empty());
+    index.recover().unwrap();
+
+    let infos = index.list_sstables();
+    assert_eq!(infos.len(), 1);
+    assert_eq!(infos[0].path, expected[0].path);
+    assert_eq!(infos[0].entry_count, 2);
+    assert_eq!(infos[0].min_key.as_deref(), Some("k1"));
+    assert_eq!(infos[0].max_key.as_deref(), Some("k2"));
+    assert_eq!(infos[0].bloom_bytes, expected[0].bloom_bytes);
+}
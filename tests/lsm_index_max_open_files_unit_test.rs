@@ -0,0 +1,63 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use tempfile::tempdir;
+
+fn flush_files(index: &LsmIndex, count: usize) {
+    for i in 0..count {
+        index
+            .insert(format!("key{}", i), format!("value{}", i).into_bytes())
+            .unwrap();
+        index.flush().unwrap();
+    }
+}
+
+#[test]
+fn test_open_readers_stay_within_the_limit() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        LsmIndexOptions::default().with_max_open_files(2),
+    );
+    flush_files(&index, 5);
+
+    let usage = index.resource_usage();
+    // Two SSTable readers plus the WAL
+    assert_eq!(usage.open_files, 3);
+    assert_eq!(usage.reader_evictions, 3);
+    assert_eq!(usage.reader_reopens, 0);
+}
+
+#[test]
+fn test_closed_readers_are_reopened_on_demand() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        LsmIndexOptions::default().with_max_open_files(2),
+    );
+    flush_files(&index, 5);
+
+    for i in 0..5 {
+        assert_eq!(
+            index.get_flushed(&format!("key{}", i)).unwrap(),
+            Some(format!("value{}", i).into_bytes())
+        );
+    }
+
+    let usage = index.resource_usage();
+    assert_eq!(usage.open_files, 3);
+    assert!(usage.reader_reopens >= 3);
+    assert!(usage.reader_evictions > 3);
+}
+
+#[test]
+fn test_unlimited_by_default() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    flush_files(&index, 5);
+
+    let usage = index.resource_usage();
+    assert_eq!(usage.open_files, 6);
+    assert_eq!(usage.reader_evictions, 0);
+}
@@ -0,0 +1,64 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use tempfile::tempdir;
+
+/// Write many small files in which later files overwrite and remove keys
+/// from earlier ones
+fn write_overlapping_files(path: &str) {
+    let index = open_index_with_options(path, LsmIndexOptions::default());
+    for round in 0..24 {
+        for key in 0..8 {
+            let key = (round + key) % 16;
+            index
+                .insert(format!("key{:02}", key), format!("v{}", round).into_bytes())
+                .unwrap();
+        }
+        if round % 5 == 4 {
+            index.remove(&format!("key{:02}", round % 16)).unwrap();
+        }
+        index.flush().unwrap();
+    }
+}
+
+fn contents(index: &LsmIndex) -> Vec<(String, Option<Vec<u8>>)> {
+    (0..16)
+        .map(|key| {
+            let key = format!("key{:02}", key);
+            let value = index.get(&key).unwrap();
+            (key, value)
+        })
+        .collect()
+}
+
+#[test]
+fn test_parallel_open_matches_serial_open() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    write_overlapping_files(path);
+
+    let mut serial =
+        open_index_with_options(path, LsmIndexOptions::default().with_open_concurrency(1));
+    serial.recover().unwrap();
+    let expected = contents(&serial);
+    drop(serial);
+
+    let mut parallel =
+        open_index_with_options(path, LsmIndexOptions::default().with_open_concurrency(8));
+    parallel.recover().unwrap();
+    assert_eq!(contents(&parallel), expected);
+    assert_eq!(parallel.list_sstables().len(), 24);
+
+    // The newest write of each key wins, and removals stay removed
+    assert_eq!(parallel.get("key03").unwrap(), None);
+    assert_eq!(parallel.get("key02").unwrap(), Some(b"v18".to_vec()));
+    assert_eq!(parallel.get("key07").unwrap(), Some(b"v23".to_vec()));
+}
+
+#[test]
+fn test_open_concurrency_is_at_least_one() {
+    let options = LsmIndexOptions::default().with_open_concurrency(0);
+    assert_eq!(options.open_concurrency, 1);
+    assert!(LsmIndexOptions::default().open_concurrency >= 1);
+}
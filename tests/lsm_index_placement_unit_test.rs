@@ -0,0 +1,118 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{
+    DirectoryUsage, LeastUsedPlacement, LevelPlacement, LsmIndex, LsmIndexOptions,
+    PlacementContext, PlacementPolicy, PrefixedFileNamer,
+};
+use std::path::Path;
+use tempfile::tempdir;
+
+fn flush_keys(index: &LsmIndex, prefix: &str) {
+    for i in 0..10 {
+        index
+            .insert(format!("{}{}", prefix, i), b"value".to_vec())
+            .unwrap();
+    }
+    index.flush().unwrap();
+}
+
+fn usage(sizes: &[u64]) -> Vec<DirectoryUsage> {
+    sizes
+        .iter()
+        .enumerate()
+        .map(|(i, size_bytes)| DirectoryUsage {
+            path: format!("/disk{}", i),
+            file_count: 1,
+            size_bytes: *size_bytes,
+        })
+        .collect()
+}
+
+#[test]
+fn test_flushes_within_one_second_do_not_collide() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+
+    for prefix in ["a", "b", "c"] {
+        flush_keys(&index, prefix);
+    }
+
+    let infos = index.list_sstables();
+    assert_eq!(infos.len(), 3);
+    assert_eq!(index.get("a3").unwrap(), Some(b"value".to_vec()));
+    assert_eq!(index.get("c7").unwrap(), Some(b"value".to_vec()));
+}
+
+#[test]
+fn test_round_robin_spreads_files_and_recovers() {
+    let dir = tempdir().unwrap();
+    let base = dir.path().join("base");
+    let disks = [dir.path().join("disk0"), dir.path().join("disk1")];
+    let disk_paths: Vec<String> = disks
+        .iter()
+        .map(|disk| disk.to_str().unwrap().to_string())
+        .collect();
+    let options = LsmIndexOptions::default().with_data_directories(disk_paths.clone());
+
+    {
+        let index = open_index_with_options(base.to_str().unwrap(), options.clone());
+        for prefix in ["a", "b", "c", "d"] {
+            flush_keys(&index, prefix);
+        }
+
+        let usage = index.directory_usage();
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[0].path, disk_paths[0]);
+        assert_eq!(usage[0].file_count, 2);
+        assert_eq!(usage[1].file_count, 2);
+        assert!(usage.iter().all(|dir| dir.size_bytes > 0));
+        for info in index.list_sstables() {
+            assert!(disks
+                .iter()
+                .any(|disk| Path::new(&info.path).parent() == Some(disk.as_path())));
+        }
+    }
+
+    let mut index = open_index_with_options(base.to_str().unwrap(), options);
+    index.recover().unwrap();
+    assert_eq!(index.list_sstables().len(), 4);
+    for prefix in ["a", "b", "c", "d"] {
+        assert_eq!(
+            index.get(&format!("{}5", prefix)).unwrap(),
+            Some(b"value".to_vec())
+        );
+    }
+}
+
+#[test]
+fn test_prefixed_names_include_level() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        LsmIndexOptions::default().with_file_namer(PrefixedFileNamer::new("users")),
+    );
+    flush_keys(&index, "a");
+
+    let info = &index.list_sstables()[0];
+    let name = Path::new(&info.path).file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with("users_L0_"), "unexpected name {}", name);
+    assert!(name.ends_with(".db"));
+}
+
+#[test]
+fn test_least_used_and_level_placement() {
+    let context = PlacementContext {
+        level: 0,
+        expected_entries: 10,
+        directories: usage(&[300, 100, 100]),
+    };
+    assert_eq!(LeastUsedPlacement.choose_directory(&context), 1);
+    assert_eq!(LevelPlacement.choose_directory(&context), 0);
+
+    let deep = PlacementContext {
+        level: 5,
+        ..context
+    };
+    assert_eq!(LevelPlacement.choose_directory(&deep), 2);
+}
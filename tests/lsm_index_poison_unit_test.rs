@@ -0,0 +1,78 @@
+use lsmer::lsm_index::{
+    CompactionContext, CompactionDecision, CompactionScheduler, LsmIndex, LsmIndexError,
+    LsmIndexOptions, PoisonPolicy,
+};
+use std::panic::{self, AssertUnwindSafe};
+use tempfile::tempdir;
+
+/// Panics when asked about a compaction, which it is with the WAL lock held
+#[derive(Debug)]
+struct PanickingScheduler;
+
+impl CompactionScheduler for PanickingScheduler {
+    fn schedule(&self, _: &CompactionContext<'_>) -> CompactionDecision {
+        panic!("scheduler failed");
+    }
+}
+
+/// Open an index under `policy` and poison its WAL lock
+fn poisoned_index(path: &str, policy: PoisonPolicy) -> LsmIndex {
+    let options = LsmIndexOptions::default()
+        .with_compaction_scheduler(PanickingScheduler)
+        .with_poison_policy(policy);
+    let index = LsmIndex::new_with_options(
+        4 * 1024 * 1024,
+        path.to_string(),
+        None,
+        false,
+        0.01,
+        options,
+    )
+    .unwrap();
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    let compaction = panic::catch_unwind(AssertUnwindSafe(|| index.compact_range(..)));
+    assert!(compaction.is_err());
+    index
+}
+
+#[test]
+fn test_poisoned_lock_fails_writes_by_default() {
+    let dir = tempdir().unwrap();
+    let index = poisoned_index(dir.path().to_str().unwrap(), PoisonPolicy::default());
+
+    let error = index.insert("b".to_string(), b"2".to_vec()).unwrap_err();
+    match error {
+        LsmIndexError::Internal(message) => assert!(message.contains("WAL")),
+        other => panic!("expected an internal error, got {:?}", other),
+    }
+    assert!(matches!(index.remove("a"), Err(LsmIndexError::Internal(_))));
+    assert!(matches!(index.flush(), Err(LsmIndexError::Internal(_))));
+
+    // Reads do not take the lock and keep working
+    assert_eq!(index.get("a").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(index.list_sstables().len(), 1);
+}
+
+#[test]
+fn test_poisoned_lock_is_recovered_when_asked() {
+    let dir = tempdir().unwrap();
+    let index = poisoned_index(dir.path().to_str().unwrap(), PoisonPolicy::Recover);
+
+    index.insert("b".to_string(), b"2".to_vec()).unwrap();
+    index.flush().unwrap();
+    assert_eq!(index.get("a").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(index.get("b").unwrap(), Some(b"2".to_vec()));
+}
+
+#[test]
+fn test_poisoned_lock_panics_when_asked() {
+    let dir = tempdir().unwrap();
+    let index = poisoned_index(dir.path().to_str().unwrap(), PoisonPolicy::Panic);
+
+    let insert = panic::catch_unwind(AssertUnwindSafe(|| {
+        index.insert("b".to_string(), b"2".to_vec())
+    }));
+    assert!(insert.is_err());
+}
@@ -0,0 +1,135 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{
+    DelimiterPrefixExtractor, FixedPrefixExtractor, LsmIndex, LsmIndexError, LsmIndexOptions,
+    PrefixExtractor,
+};
+use lsmer::memtable::{Memtable, StringMemtable};
+use lsmer::sstable::{CompactionOptions, SSTableCompaction, SSTableReader};
+use std::sync::Arc;
+use tempfile::tempdir;
+
+fn insert_users(index: &LsmIndex) {
+    for (key, value) in [
+        ("user:1", "alice"),
+        ("user:2", "bob"),
+        ("user2:1", "carol"),
+        ("order:1", "book"),
+    ] {
+        index
+            .insert(key.to_string(), value.as_bytes().to_vec())
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_prefix_iter_returns_only_matching_prefix() {
+    let dir = tempdir().unwrap();
+    let options =
+        LsmIndexOptions::default().with_prefix_extractor(DelimiterPrefixExtractor::new(":"));
+    let index = open_index_with_options(dir.path().to_str().unwrap(), options);
+    insert_users(&index);
+
+    let users = index.prefix_iter("user:9").unwrap();
+    assert_eq!(
+        users,
+        vec![
+            ("user:1".to_string(), b"alice".to_vec()),
+            ("user:2".to_string(), b"bob".to_vec()),
+        ]
+    );
+
+    index.remove("user:1").unwrap();
+    assert_eq!(index.prefix_iter("user:").unwrap().len(), 1);
+}
+
+#[test]
+fn test_prefix_iter_requires_extractor_and_domain() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    assert!(matches!(
+        index.prefix_iter("user:1"),
+        Err(LsmIndexError::InvalidOperation(_))
+    ));
+
+    let dir = tempdir().unwrap();
+    let options =
+        LsmIndexOptions::default().with_prefix_extractor(DelimiterPrefixExtractor::new(":"));
+    let index = open_index_with_options(dir.path().to_str().unwrap(), options);
+    assert!(matches!(
+        index.prefix_iter("no-delimiter"),
+        Err(LsmIndexError::InvalidOperation(_))
+    ));
+}
+
+#[test]
+fn test_flushed_sstable_records_extractor_and_prefix_filter() {
+    let dir = tempdir().unwrap();
+    let extractor = DelimiterPrefixExtractor::new(":");
+    let options = LsmIndexOptions::default().with_prefix_extractor(extractor.clone());
+    let index = open_index_with_options(dir.path().to_str().unwrap(), options);
+    insert_users(&index);
+    index.flush().unwrap();
+
+    let path = index.list_sstables()[0].path.clone();
+    let reader = SSTableReader::open(&path).unwrap();
+    assert_eq!(
+        reader.prefix_extractor_name(),
+        Some(extractor.name().as_str())
+    );
+    assert!(reader.may_contain_prefix(&extractor, "user:"));
+    assert!(reader.may_contain_prefix(&extractor, "order:"));
+    assert!(!reader.may_contain_prefix(&extractor, "invoice:"));
+
+    // A different extractor cannot rely on the file's prefix filter
+    let other = FixedPrefixExtractor::new(3);
+    assert!(reader.may_contain_prefix(&other, "inv"));
+}
+
+#[test]
+fn test_compaction_carries_prefix_extractor() {
+    let dir = tempdir().unwrap();
+    let extractor = DelimiterPrefixExtractor::new(":");
+    let options = LsmIndexOptions::default().with_prefix_extractor(extractor.clone());
+    let index = open_index_with_options(dir.path().to_str().unwrap(), options);
+    insert_users(&index);
+    index.flush().unwrap();
+
+    let input = index.list_sstables()[0].path.clone();
+    let output = dir.path().join("compacted.db");
+    let output = output.to_str().unwrap();
+    let options = CompactionOptions::default().with_prefix_extractor(Arc::new(extractor.clone()));
+    SSTableCompaction::compact_sstables_with_options(&[input], output, &options).unwrap();
+
+    let reader = SSTableReader::open(output).unwrap();
+    assert_eq!(
+        reader.prefix_extractor_name(),
+        Some(extractor.name().as_str())
+    );
+    assert!(reader.may_contain_prefix(&extractor, "user2:"));
+    assert!(!reader.may_contain_prefix(&extractor, "invoice:"));
+}
+
+#[test]
+fn test_memtable_counts_keys_by_prefix() {
+    let memtable =
+        StringMemtable::with_prefix_extractor(1024 * 1024, Arc::new(FixedPrefixExtractor::new(2)));
+
+    memtable.insert("aa1".to_string(), vec![1]).unwrap();
+    memtable.insert("aa2".to_string(), vec![2]).unwrap();
+    memtable.insert("aa2".to_string(), vec![3]).unwrap();
+    memtable.insert("bb1".to_string(), vec![4]).unwrap();
+    // Too short to have a prefix
+    memtable.insert("c".to_string(), vec![5]).unwrap();
+
+    assert_eq!(memtable.prefix_count("aa").unwrap(), 2);
+    assert_eq!(memtable.prefix_count("bb").unwrap(), 1);
+    assert_eq!(memtable.prefix_count("c").unwrap(), 0);
+
+    memtable.remove(&"aa1".to_string()).unwrap();
+    assert_eq!(memtable.prefix_count("aa").unwrap(), 1);
+
+    memtable.clear().unwrap();
+    assert_eq!(memtable.prefix_count("aa").unwrap(), 0);
+}
@@ -0,0 +1,86 @@
+use lsmer::lsm_index::LsmIndex;
+use tempfile::tempdir;
+
+/// Build an index with two overlapping SSTables: the older one holds
+/// a..c with b = "old", the newer one holds b and z with b = "new"
+fn overlapping_index(path: &str) -> LsmIndex {
+    let index = LsmIndex::new(4 * 1024 * 1024, path.to_string(), None, true, 0.01).unwrap();
+
+    index.insert("a".to_string(), b"a".to_vec()).unwrap();
+    index.insert("b".to_string(), b"old".to_vec()).unwrap();
+    index.insert("c".to_string(), b"c".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    index.insert("b".to_string(), b"new".to_vec()).unwrap();
+    index.insert("z".to_string(), b"z".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    index
+}
+
+#[test]
+fn test_probe_order_prefers_hot_files() {
+    let dir = tempdir().unwrap();
+    let index = overlapping_index(dir.path().to_str().unwrap());
+
+    let files = index.list_sstables();
+    assert_eq!(files.len(), 2);
+    let (older, newer) = (files[0].path.clone(), files[1].path.clone());
+
+    // Without history the newest file goes first
+    assert_eq!(index.probe_order("b"), vec![newer.clone(), older.clone()]);
+    // Only files whose key range covers the key are candidates
+    assert_eq!(index.probe_order("a"), vec![older.clone()]);
+    assert_eq!(index.probe_order("y"), vec![newer.clone()]);
+    assert!(index.probe_order("0").is_empty());
+
+    // Warm up the older file and let the newer one miss
+    for _ in 0..5 {
+        assert_eq!(index.get_flushed("a").unwrap(), Some(b"a".to_vec()));
+        assert_eq!(index.get_flushed("y").unwrap(), None);
+    }
+    assert_eq!(index.probe_order("b"), vec![older.clone(), newer.clone()]);
+
+    // Probing the older file first must not let its stale value win
+    assert_eq!(index.get_flushed("b").unwrap(), Some(b"new".to_vec()));
+}
+
+#[test]
+fn test_access_stats_track_hits_and_negatives() {
+    let dir = tempdir().unwrap();
+    let index = overlapping_index(dir.path().to_str().unwrap());
+    let files = index.list_sstables();
+    let newer = files[1].path.clone();
+
+    assert!(index.sstable_access_stats().is_empty());
+    for _ in 0..4 {
+        index.get_flushed("y").unwrap();
+    }
+    index.get_flushed("z").unwrap();
+
+    let stats = index
+        .sstable_access_stats()
+        .into_iter()
+        .find(|stats| stats.path == newer)
+        .unwrap();
+    assert_eq!(stats.probes, 5);
+    assert_eq!(stats.hits, 1);
+    assert_eq!(stats.bloom_negatives + stats.false_positives, 4);
+    assert!((stats.negative_rate() - 0.8).abs() < 1e-9);
+}
+
+#[test]
+fn test_newest_hit_skips_older_files() {
+    let dir = tempdir().unwrap();
+    let index = overlapping_index(dir.path().to_str().unwrap());
+    let files = index.list_sstables();
+    let older = files[0].path.clone();
+
+    // The newest file is probed first and holds the key, so the older file
+    // is never consulted
+    assert_eq!(index.get_flushed("b").unwrap(), Some(b"new".to_vec()));
+    assert!(index
+        .sstable_access_stats()
+        .iter()
+        .all(|stats| stats.path != older));
+}
@@ -0,0 +1,86 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{LsmIndexOptions, ReadOptions};
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+#[test]
+fn test_default_read_options() {
+    let options = ReadOptions::default();
+    assert!(options.verify_checksums);
+    assert!(options.fill_cache);
+    assert_eq!(options.snapshot, None);
+    assert!(options.in_bounds("anything"));
+}
+
+#[test]
+fn test_multi_get_preserves_key_order() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    index.insert("b".to_string(), b"2".to_vec()).unwrap();
+    index.flush().unwrap();
+    index.insert("c".to_string(), b"3".to_vec()).unwrap();
+
+    let values = index.multi_get(&["c", "missing", "a"]).unwrap();
+    assert_eq!(values, vec![Some(b"3".to_vec()), None, Some(b"1".to_vec())]);
+
+    let uncached = ReadOptions::default().with_fill_cache(false);
+    let values = index.multi_get_with_options(&["b"], &uncached).unwrap();
+    assert_eq!(values, vec![Some(b"2".to_vec())]);
+}
+
+#[test]
+fn test_range_respects_bounds() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    for key in ["a", "b", "c", "d", "e"] {
+        index
+            .insert(key.to_string(), key.as_bytes().to_vec())
+            .unwrap();
+    }
+    index.flush().unwrap();
+
+    let options = ReadOptions::default()
+        .with_lower_bound("b")
+        .with_upper_bound("d");
+    let keys: Vec<String> = index
+        .range_with_options("a".to_string().., &options)
+        .unwrap()
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(keys, vec!["b".to_string(), "c".to_string()]);
+}
+
+#[test]
+fn test_snapshot_hides_later_writes() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        LsmIndexOptions::default().with_write_times(true),
+    );
+    index.insert("before".to_string(), b"v".to_vec()).unwrap();
+    thread::sleep(Duration::from_millis(5));
+    let snapshot = index.snapshot();
+    thread::sleep(Duration::from_millis(5));
+    index.insert("after".to_string(), b"v".to_vec()).unwrap();
+
+    let at_snapshot = ReadOptions::default().with_snapshot(snapshot);
+    assert_eq!(
+        index.get_with_options("before", &at_snapshot).unwrap(),
+        Some(b"v".to_vec())
+    );
+    assert_eq!(index.get_with_options("after", &at_snapshot).unwrap(), None);
+    assert_eq!(index.get("after").unwrap(), Some(b"v".to_vec()));
+
+    let keys: Vec<String> = index
+        .range_with_options(.., &at_snapshot)
+        .unwrap()
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(keys, vec!["before".to_string()]);
+}
@@ -0,0 +1,124 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::clock::MockClock;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::{SSTableCompaction, SSTableInfo};
+use std::time::Duration;
+use tempfile::tempdir;
+
+const START_MS: u64 = 1_700_000_000_000;
+
+/// Flush keys `prefix0`..`prefix{count}` into their own SSTable, then move
+/// the clock on so the next file is created a second later
+fn flush_range(index: &LsmIndex, clock: &MockClock, prefix: &str, count: u32) {
+    for i in 0..count {
+        index
+            .insert(format!("{}{:03}", prefix, i), b"v".to_vec())
+            .unwrap();
+    }
+    index.flush().unwrap();
+    clock.advance(Duration::from_secs(1));
+}
+
+fn info(path: &str, created_at_secs: u64, min: &str, max: &str) -> SSTableInfo {
+    SSTableInfo {
+        path: path.to_string(),
+        created_at_secs,
+        min_key: Some(min.to_string()),
+        max_key: Some(max.to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_sampling_disabled_by_default() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        LsmIndexOptions::default().with_clock(clock.clone()),
+    );
+    flush_range(&index, &clock, "a", 10);
+    index.get("a001").unwrap();
+    assert!(index.read_hotness().is_empty());
+}
+
+#[test]
+fn test_sampled_reads_count_against_covering_files() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        LsmIndexOptions::default()
+            .with_read_sampling(2)
+            .with_clock(clock.clone()),
+    );
+    flush_range(&index, &clock, "a", 10);
+    flush_range(&index, &clock, "m", 10);
+
+    // Every other read is sampled
+    for _ in 0..20 {
+        index.get("a005").unwrap();
+    }
+    for _ in 0..4 {
+        index.get("m005").unwrap();
+    }
+
+    let hotness = index.read_hotness();
+    assert_eq!(hotness.len(), 2);
+    let infos = index.list_sstables();
+    assert_eq!(hotness[0].path, infos[0].path);
+    assert_eq!(hotness[0].sampled_reads, 10);
+    assert_eq!(hotness[1].path, infos[1].path);
+    assert_eq!(hotness[1].sampled_reads, 2);
+}
+
+#[test]
+fn test_hot_overlapping_files_are_grouped_first() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        LsmIndexOptions::default()
+            .with_read_sampling(1)
+            .with_clock(clock.clone()),
+    );
+    // Two overlapping files over "a..." and two over "m..."
+    flush_range(&index, &clock, "a", 10);
+    flush_range(&index, &clock, "a", 5);
+    flush_range(&index, &clock, "m", 10);
+    flush_range(&index, &clock, "m", 5);
+
+    for _ in 0..10 {
+        index.get("m002").unwrap();
+    }
+    index.get("a002").unwrap();
+
+    let groups = index.hot_compaction_groups(2);
+    assert_eq!(groups.len(), 2);
+    assert!(groups[0]
+        .iter()
+        .all(|info| info.min_key.as_deref() == Some("m000")));
+    assert!(groups[1]
+        .iter()
+        .all(|info| info.min_key.as_deref() == Some("a000")));
+    // Files are listed oldest first, ready for compaction
+    assert!(groups[0][0].created_at_secs < groups[0][1].created_at_secs);
+}
+
+#[test]
+fn test_overlap_chains_into_one_group() {
+    let sstables = vec![
+        info("1", 1, "a", "d"),
+        info("2", 2, "c", "g"),
+        info("3", 3, "f", "h"),
+        info("4", 4, "x", "z"),
+    ];
+
+    let groups = SSTableCompaction::identify_hot_compaction_groups(&sstables, &[0, 0, 0, 9], 1);
+    assert_eq!(groups, vec![vec![3], vec![0, 1, 2]]);
+
+    let groups = SSTableCompaction::identify_hot_compaction_groups(&sstables, &[0, 0, 0, 9], 2);
+    assert_eq!(groups, vec![vec![0, 1, 2]]);
+}
@@ -0,0 +1,60 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::TtlSweeper;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempdir;
+
+#[test]
+fn test_empty_index_holds_only_the_wal() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+
+    let usage = index.resource_usage();
+    assert_eq!(usage.open_files, 1);
+    assert_eq!(usage.mmapped_bytes, 0);
+    assert_eq!(usage.cache_bytes, 0);
+    assert_eq!(usage.memtable_bytes, 0);
+    assert_eq!(usage.index_bytes, 0);
+    assert_eq!(usage.background_tasks, 0);
+}
+
+#[test]
+fn test_usage_tracks_memtable_and_flushed_files() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+
+    for i in 0..100 {
+        index
+            .insert(format!("key{:03}", i), vec![0u8; 100])
+            .unwrap();
+    }
+    let before_flush = index.resource_usage();
+    assert!(before_flush.memtable_bytes >= 100 * 100);
+    assert!(before_flush.index_bytes >= 100 * 100);
+
+    index.flush().unwrap();
+    index.insert("more".to_string(), b"v".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    let after_flush = index.resource_usage();
+    assert_eq!(after_flush.open_files, 3);
+    assert!(after_flush.cache_bytes > 0);
+    assert!(after_flush.memtable_bytes < before_flush.memtable_bytes);
+}
+
+#[tokio::test]
+async fn test_sweepers_count_as_background_tasks() {
+    let dir = tempdir().unwrap();
+    let index = Arc::new(open_index(dir.path().to_str().unwrap()));
+
+    let first = TtlSweeper::spawn(index.clone(), Duration::from_millis(20));
+    let second = TtlSweeper::spawn(index.clone(), Duration::from_millis(20));
+    assert_eq!(index.resource_usage().background_tasks, 2);
+
+    first.stop().await;
+    assert_eq!(index.resource_usage().background_tasks, 1);
+    second.stop().await;
+    assert_eq!(index.resource_usage().background_tasks, 0);
+}
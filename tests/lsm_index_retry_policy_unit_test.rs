@@ -0,0 +1,145 @@
+use lsmer::lsm_index::{ErrorClass, LsmIndexError, RetryEvent, RetryObserver, RetryPolicy};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Records each retry and frees space on request if `frees_space` is set
+#[derive(Debug, Default)]
+struct Recorder {
+    retries: Mutex<Vec<(u32, ErrorClass, Duration)>>,
+    frees_space: AtomicBool,
+}
+
+/// Shares a recorder with the policy that reports to it
+#[derive(Debug)]
+struct Observer(Arc<Recorder>);
+
+impl RetryObserver for Observer {
+    fn on_retry(&self, event: &RetryEvent<'_>) {
+        assert_eq!(event.operation, "test");
+        self.0
+            .retries
+            .lock()
+            .unwrap()
+            .push((event.attempt, event.class, event.backoff));
+    }
+
+    fn on_no_space(&self, _operation: &str) -> bool {
+        self.0.frees_space.load(Ordering::SeqCst)
+    }
+}
+
+fn policy(recorder: &Arc<Recorder>) -> RetryPolicy {
+    RetryPolicy::default()
+        .with_max_retries(3)
+        .with_backoff(Duration::from_millis(1), Duration::from_millis(2))
+        .with_observer(Observer(recorder.clone()))
+}
+
+/// An operation failing with `kind` the first `failures` times
+fn failing(kind: io::ErrorKind, failures: u32) -> impl FnMut() -> io::Result<u32> {
+    let calls = AtomicU32::new(0);
+    move || {
+        let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        if call <= failures {
+            Err(io::Error::from(kind))
+        } else {
+            Ok(call)
+        }
+    }
+}
+
+#[test]
+fn test_errors_are_classified() {
+    let class = |kind: io::ErrorKind| ErrorClass::of(&io::Error::from(kind));
+    assert_eq!(class(io::ErrorKind::Interrupted), ErrorClass::Interrupted);
+    assert_eq!(class(io::ErrorKind::TimedOut), ErrorClass::Transient);
+    assert_eq!(class(io::ErrorKind::NotFound), ErrorClass::Permanent);
+    assert_eq!(
+        ErrorClass::of(&io::Error::from_raw_os_error(28)),
+        ErrorClass::NoSpace
+    );
+}
+
+#[test]
+fn test_transient_errors_are_retried_with_backoff() {
+    let recorder = Arc::new(Recorder::default());
+    let result = policy(&recorder).run("test", failing(io::ErrorKind::TimedOut, 3));
+    assert_eq!(result.unwrap(), 4);
+
+    let retries = recorder.retries.lock().unwrap();
+    let backoffs: Vec<Duration> = retries.iter().map(|(_, _, backoff)| *backoff).collect();
+    assert_eq!(
+        backoffs,
+        [1, 2, 2].map(Duration::from_millis).to_vec(),
+        "backoff doubles up to the maximum"
+    );
+    assert!(retries.iter().enumerate().all(
+        |(i, (attempt, class, _))| *attempt == i as u32 + 1 && *class == ErrorClass::Transient
+    ));
+}
+
+#[test]
+fn test_interrupted_calls_retry_without_waiting() {
+    let recorder = Arc::new(Recorder::default());
+    let result = policy(&recorder).run("test", failing(io::ErrorKind::Interrupted, 1));
+    assert_eq!(result.unwrap(), 2);
+    assert_eq!(
+        *recorder.retries.lock().unwrap(),
+        vec![(1, ErrorClass::Interrupted, Duration::ZERO)]
+    );
+}
+
+#[test]
+fn test_exhausted_retries_are_unrecoverable() {
+    let recorder = Arc::new(Recorder::default());
+    let result = policy(&recorder).run("test", failing(io::ErrorKind::TimedOut, 10));
+    match result {
+        Err(LsmIndexError::Unrecoverable {
+            operation,
+            attempts,
+            error,
+        }) => {
+            assert_eq!(operation, "test");
+            assert_eq!(attempts, 4);
+            assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        }
+        other => panic!("expected Unrecoverable, got {:?}", other),
+    }
+    assert_eq!(recorder.retries.lock().unwrap().len(), 3);
+}
+
+#[test]
+fn test_permanent_errors_are_not_retried() {
+    let recorder = Arc::new(Recorder::default());
+    let result = policy(&recorder).run("test", failing(io::ErrorKind::NotFound, 1));
+    assert!(
+        matches!(result, Err(LsmIndexError::IoError(e)) if e.kind() == io::ErrorKind::NotFound)
+    );
+    assert!(recorder.retries.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_full_disk_is_retried_only_once_space_is_freed() {
+    let recorder = Arc::new(Recorder::default());
+    let result = policy(&recorder).run("test", failing(io::ErrorKind::StorageFull, 1));
+    assert!(matches!(
+        result,
+        Err(LsmIndexError::Unrecoverable { attempts: 1, .. })
+    ));
+
+    recorder.frees_space.store(true, Ordering::SeqCst);
+    let result = policy(&recorder).run("test", failing(io::ErrorKind::StorageFull, 1));
+    assert_eq!(result.unwrap(), 2);
+    assert_eq!(recorder.retries.lock().unwrap()[0].1, ErrorClass::NoSpace);
+}
+
+#[test]
+fn test_none_makes_a_single_attempt() {
+    let result = RetryPolicy::none().run("test", failing(io::ErrorKind::TimedOut, 1));
+    assert!(matches!(
+        result,
+        Err(LsmIndexError::Unrecoverable { attempts: 1, .. })
+    ));
+}
@@ -0,0 +1,131 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::{LsmIndexError, WriteOptions};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use tempfile::tempdir;
+
+fn unchanged_since(sequence: u64) -> WriteOptions {
+    WriteOptions::default().with_if_unchanged_since(sequence)
+}
+
+#[test]
+fn test_writes_return_increasing_sequences() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    let opened_at = index.last_sequence();
+
+    let first = index
+        .insert_with_options("a".to_string(), b"1".to_vec(), &WriteOptions::default())
+        .unwrap();
+    let second = index
+        .insert_with_options("b".to_string(), b"2".to_vec(), &WriteOptions::default())
+        .unwrap();
+    let (removed, third) = index
+        .remove_versioned("a", &WriteOptions::default())
+        .unwrap();
+
+    assert!(opened_at < first && first < second && second < third);
+    assert_eq!(removed, Some(b"1".to_vec()));
+    assert_eq!(index.last_sequence(), third);
+    assert_eq!(index.sequence_of("a"), third);
+    assert_eq!(index.sequence_of("b"), second);
+    assert_eq!(index.sequence_of("untouched"), opened_at);
+}
+
+#[test]
+fn test_stale_tokens_are_rejected() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    let token = index
+        .insert_with_options("doc".to_string(), b"v1".to_vec(), &WriteOptions::default())
+        .unwrap();
+
+    // The holder of the current token may write, which moves the token on
+    let newer = index
+        .insert_with_options("doc".to_string(), b"v2".to_vec(), &unchanged_since(token))
+        .unwrap();
+
+    match index.insert_with_options("doc".to_string(), b"v3".to_vec(), &unchanged_since(token)) {
+        Err(LsmIndexError::PreconditionFailed { key, sequence }) => {
+            assert_eq!(key, "doc");
+            assert_eq!(sequence, newer);
+        }
+        other => panic!("expected PreconditionFailed, got {:?}", other),
+    }
+    assert!(matches!(
+        index.remove_with_options("doc", &unchanged_since(token)),
+        Err(LsmIndexError::PreconditionFailed { .. })
+    ));
+    assert_eq!(index.get("doc").unwrap(), Some(b"v2".to_vec()));
+
+    // Writes to other keys do not invalidate the token
+    index.insert("other".to_string(), b"x".to_vec()).unwrap();
+    assert_eq!(
+        index
+            .remove_with_options("doc", &unchanged_since(newer))
+            .unwrap(),
+        Some(b"v2".to_vec())
+    );
+}
+
+#[test]
+fn test_tokens_from_before_a_restart_are_rejected() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let token = {
+        let index = open_index(path);
+        let token = index
+            .insert_with_options("a".to_string(), b"1".to_vec(), &WriteOptions::default())
+            .unwrap();
+        index.flush().unwrap();
+        token
+    };
+
+    let mut index = open_index(path);
+    index.recover().unwrap();
+    assert!(index.sequence_of("a") > token);
+    assert!(matches!(
+        index.insert_with_options("a".to_string(), b"2".to_vec(), &unchanged_since(token)),
+        Err(LsmIndexError::PreconditionFailed { .. })
+    ));
+
+    let fresh = index.sequence_of("a");
+    index
+        .insert_with_options("a".to_string(), b"2".to_vec(), &unchanged_since(fresh))
+        .unwrap();
+}
+
+#[test]
+fn test_only_one_conditional_writer_wins() {
+    let dir = tempdir().unwrap();
+    let index = Arc::new(open_index(dir.path().to_str().unwrap()));
+    let token = index
+        .insert_with_options(
+            "counter".to_string(),
+            b"0".to_vec(),
+            &WriteOptions::default(),
+        )
+        .unwrap();
+
+    let barrier = Arc::new(Barrier::new(8));
+    let writers: Vec<_> = (0..8)
+        .map(|i| {
+            let index = index.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                index
+                    .insert_with_options("counter".to_string(), vec![i], &unchanged_since(token))
+                    .is_ok()
+            })
+        })
+        .collect();
+    let wins = writers
+        .into_iter()
+        .map(|writer| writer.join().unwrap())
+        .filter(|won| *won)
+        .count();
+    assert_eq!(wins, 1);
+}
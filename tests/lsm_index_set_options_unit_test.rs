@@ -0,0 +1,123 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{
+    LsmIndexError, LsmIndexOptions, OptionChange, OptionChangeEvent, OptionsObserver,
+};
+use lsmer::sstable::{Compression, FilterCache, SSTableReader};
+use std::sync::{Arc, Mutex};
+use tempfile::tempdir;
+
+/// Records every event it is told about
+#[derive(Debug, Default, Clone)]
+struct Recorder {
+    events: Arc<Mutex<Vec<(String, String, String)>>>,
+}
+
+impl OptionsObserver for Recorder {
+    fn on_option_changed(&self, event: &OptionChangeEvent<'_>) {
+        self.events.lock().unwrap().push((
+            event.name.to_string(),
+            event.previous.clone(),
+            event.current.clone(),
+        ));
+    }
+}
+
+#[test]
+fn test_changes_take_effect_and_emit_one_event_each() {
+    let dir = tempdir().unwrap();
+    let recorder = Recorder::default();
+    let options = LsmIndexOptions::default().with_options_observer(recorder.clone());
+    let index = open_index_with_options(dir.path().to_str().unwrap(), options);
+
+    index
+        .set_options([
+            OptionChange::MaxValueSize(4),
+            OptionChange::SyncWrites(false),
+        ])
+        .unwrap();
+
+    assert_eq!(index.options().max_value_size, 4);
+    assert!(!index.options().sync_writes);
+    assert!(matches!(
+        index.insert("k".to_string(), b"too long".to_vec()),
+        Err(LsmIndexError::ValueTooLarge { size: 8, max: 4 })
+    ));
+    index.insert("k".to_string(), b"fits".to_vec()).unwrap();
+
+    let events = recorder.events.lock().unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].0, "max_value_size");
+    assert_eq!(events[0].2, "4");
+    assert_eq!(
+        events[1],
+        (
+            "sync_writes".to_string(),
+            "true".to_string(),
+            "false".to_string()
+        )
+    );
+}
+
+#[test]
+fn test_invalid_changes_apply_nothing() {
+    let dir = tempdir().unwrap();
+    let recorder = Recorder::default();
+    let options = LsmIndexOptions::default().with_options_observer(recorder.clone());
+    let index = open_index_with_options(dir.path().to_str().unwrap(), options);
+    let before = index.options().max_value_size;
+
+    assert!(index
+        .set_options([OptionChange::MaxValueSize(16), OptionChange::MaxKeySize(0),])
+        .is_err());
+    assert_eq!(index.options().max_value_size, before);
+
+    // A filter cache budget needs a filter cache
+    assert!(matches!(
+        index.set_options([OptionChange::FilterCacheCapacity(1024)]),
+        Err(LsmIndexError::InvalidOperation(_))
+    ));
+    assert!(recorder.events.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_compression_change_applies_to_later_flushes() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    index.insert("a".to_string(), vec![b'x'; 512]).unwrap();
+    index.flush().unwrap();
+
+    index
+        .set_options([OptionChange::Compression(Compression::zstd())])
+        .unwrap();
+    index.insert("b".to_string(), vec![b'y'; 512]).unwrap();
+    index.flush().unwrap();
+
+    assert_eq!(index.list_sstables().len(), 2);
+    let mut names: Vec<Option<String>> = index
+        .list_sstables()
+        .iter()
+        .map(|info| {
+            let reader = SSTableReader::open(&info.path).unwrap();
+            reader.compression_name().map(str::to_string)
+        })
+        .collect();
+    names.sort();
+    assert_eq!(names, vec![None, Some("zstd".to_string())]);
+    assert_eq!(index.get("a").unwrap(), Some(vec![b'x'; 512]));
+    assert_eq!(index.get("b").unwrap(), Some(vec![b'y'; 512]));
+}
+
+#[test]
+fn test_filter_cache_capacity_resizes_the_shared_cache() {
+    let dir = tempdir().unwrap();
+    let cache = Arc::new(FilterCache::new(1 << 20));
+    let options = LsmIndexOptions::default().with_filter_cache(cache.clone());
+    let index = open_index_with_options(dir.path().to_str().unwrap(), options);
+
+    index
+        .set_options([OptionChange::FilterCacheCapacity(4096)])
+        .unwrap();
+    assert_eq!(cache.capacity_bytes(), 4096);
+}
@@ -0,0 +1,106 @@
+use lsmer::lsm_index::{LsmIndex, LsmIndexError, LsmIndexOptions};
+use lsmer::sstable::{SSTableWriter, MAX_KEY_SIZE, MAX_VALUE_SIZE};
+use std::time::Duration;
+use tempfile::tempdir;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_insert_rejects_oversized_key_and_value() {
+    let test_future = async {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_str().unwrap().to_string();
+
+        let options = LsmIndexOptions::default()
+            .with_max_key_size(16)
+            .with_max_value_size(64);
+        let index =
+            LsmIndex::new_with_options(1024 * 1024, path, None, false, 0.01, options).unwrap();
+
+        // Entries at the limit are accepted
+        index.insert("k".repeat(16), vec![0u8; 64]).unwrap();
+
+        // One byte over the key limit is rejected with a typed error
+        match index.insert("k".repeat(17), vec![1]) {
+            Err(LsmIndexError::KeyTooLarge { size, max }) => {
+                assert_eq!(size, 17);
+                assert_eq!(max, 16);
+            }
+            other => panic!("Expected KeyTooLarge, got {:?}", other),
+        }
+
+        // One byte over the value limit is rejected with a typed error
+        match index.insert("key".to_string(), vec![0u8; 65]) {
+            Err(LsmIndexError::ValueTooLarge { size, max }) => {
+                assert_eq!(size, 65);
+                assert_eq!(max, 64);
+            }
+            other => panic!("Expected ValueTooLarge, got {:?}", other),
+        }
+
+        // Rejected writes must not be visible
+        assert_eq!(index.get("key").unwrap(), None);
+        assert_eq!(index.get(&"k".repeat(17)).unwrap(), None);
+    };
+
+    match timeout(Duration::from_secs(10), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 10 seconds"),
+    }
+}
+
+#[tokio::test]
+async fn test_default_limits_match_format_limits() {
+    let test_future = async {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_str().unwrap().to_string();
+
+        let index = LsmIndex::new(64 * 1024 * 1024, path, None, false, 0.01).unwrap();
+        assert_eq!(index.options().max_key_size, MAX_KEY_SIZE);
+        assert_eq!(index.options().max_value_size, MAX_VALUE_SIZE);
+
+        match index.insert("big".to_string(), vec![0u8; MAX_VALUE_SIZE + 1]) {
+            Err(LsmIndexError::ValueTooLarge { max, .. }) => assert_eq!(max, MAX_VALUE_SIZE),
+            other => panic!("Expected ValueTooLarge, got {:?}", other),
+        }
+    };
+
+    match timeout(Duration::from_secs(10), test_future).await {
+        Ok(_) => (),
+        Err(_) => panic!("Test timed out after 10 seconds"),
+    }
+}
+
+#[test]
+fn test_limits_above_format_limits_are_rejected() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().to_str().unwrap().to_string();
+
+    let options = LsmIndexOptions::default().with_max_key_size(MAX_KEY_SIZE + 1);
+    let result = LsmIndex::new_with_options(1024, path.clone(), None, false, 0.01, options);
+    assert!(result.is_err());
+
+    let options = LsmIndexOptions::default().with_max_value_size(MAX_VALUE_SIZE + 1);
+    let result = LsmIndex::new_with_options(1024, path, None, false, 0.01, options);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_sstable_writer_rejects_oversized_entries() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("limits.sst");
+    let mut writer = SSTableWriter::new(path.to_str().unwrap(), 10, false, 0.01).unwrap();
+
+    let err = writer
+        .write_entry(&"k".repeat(MAX_KEY_SIZE + 1), b"value")
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    let err = writer
+        .write_entry("key", &vec![0u8; MAX_VALUE_SIZE + 1])
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    // Valid entries can still be written afterwards
+    writer.write_entry("key", b"value").unwrap();
+    writer.finalize().unwrap();
+}
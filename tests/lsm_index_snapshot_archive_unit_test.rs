@@ -0,0 +1,129 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{LsmIndex, LsmIndexError, LsmIndexOptions, SNAPSHOT_METADATA_NAME};
+use std::io::ErrorKind;
+use tempfile::tempdir;
+
+/// Names of the members of a tar archive, in order
+fn member_names(archive: &[u8]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut offset = 0;
+    while archive[offset..offset + 512].iter().any(|&byte| byte != 0) {
+        let header = &archive[offset..offset + 512];
+        assert_eq!(&header[257..263], b"ustar\0");
+        let name_len = header.iter().position(|&byte| byte == 0).unwrap();
+        names.push(String::from_utf8(header[..name_len].to_vec()).unwrap());
+        let size_field = std::str::from_utf8(&header[124..135]).unwrap();
+        let size = usize::from_str_radix(size_field, 8).unwrap();
+        offset += 512 + size.div_ceil(512) * 512;
+    }
+    names
+}
+
+#[test]
+fn test_snapshot_round_trips_through_an_archive() {
+    let source_dir = tempdir().unwrap();
+    let target = tempdir().unwrap();
+    let target_dir = target.path().join("restored");
+    let target_dir = target_dir.to_str().unwrap();
+
+    let index = open_index_with_options(source_dir.path().to_str().unwrap(), Default::default());
+    index.insert("a".to_string(), b"flushed".to_vec()).unwrap();
+    index.insert("b".to_string(), b"gone".to_vec()).unwrap();
+    index.flush().unwrap();
+    index.remove("b").unwrap();
+    index.insert("c".to_string(), b"memtable".to_vec()).unwrap();
+
+    let mut archive = Vec::new();
+    let written = index.export_snapshot(&mut archive).unwrap();
+    assert_eq!(written, archive.len() as u64);
+    assert_eq!(archive.len() % 512, 0);
+
+    let names = member_names(&archive);
+    assert_eq!(names[0], SNAPSHOT_METADATA_NAME);
+    assert_eq!(names[1], "MANIFEST");
+    assert_eq!(names.len(), 2 + index.list_sstables().len());
+
+    LsmIndex::import_snapshot(archive.as_slice(), target_dir).unwrap();
+    let mut restored = open_index_with_options(target_dir, Default::default());
+    restored.recover().unwrap();
+    assert_eq!(restored.get("a").unwrap(), Some(b"flushed".to_vec()));
+    assert_eq!(restored.get("b").unwrap(), None);
+    assert_eq!(restored.get("c").unwrap(), Some(b"memtable".to_vec()));
+    for info in restored.list_sstables() {
+        assert!(info.path.starts_with(target_dir));
+    }
+
+    // Later writes to the source do not reach the restored copy
+    index.insert("d".to_string(), b"later".to_vec()).unwrap();
+    assert_eq!(restored.get("d").unwrap(), None);
+}
+
+#[test]
+fn test_files_from_data_directories_land_in_the_target() {
+    let source_dir = tempdir().unwrap();
+    let disk = tempdir().unwrap();
+    let target = tempdir().unwrap();
+    let target_dir = target.path().to_str().unwrap();
+
+    let options = LsmIndexOptions::default().with_data_directories([disk.path().to_str().unwrap()]);
+    let index = open_index_with_options(source_dir.path().to_str().unwrap(), options);
+    index.insert("k".to_string(), b"v".to_vec()).unwrap();
+
+    let mut archive = Vec::new();
+    index.export_snapshot(&mut archive).unwrap();
+    LsmIndex::import_snapshot(archive.as_slice(), target_dir).unwrap();
+
+    let mut restored = open_index_with_options(target_dir, Default::default());
+    restored.recover().unwrap();
+    assert_eq!(restored.get("k").unwrap(), Some(b"v".to_vec()));
+    assert_eq!(restored.list_sstables().len(), 1);
+    assert!(restored.list_sstables()[0].path.starts_with(target_dir));
+}
+
+#[test]
+fn test_import_refuses_an_existing_database() {
+    let source_dir = tempdir().unwrap();
+    let index = open_index_with_options(source_dir.path().to_str().unwrap(), Default::default());
+    index.insert("k".to_string(), b"v".to_vec()).unwrap();
+    let mut archive = Vec::new();
+    index.export_snapshot(&mut archive).unwrap();
+
+    assert!(matches!(
+        LsmIndex::import_snapshot(archive.as_slice(), source_dir.path().to_str().unwrap()),
+        Err(LsmIndexError::InvalidOperation(_))
+    ));
+}
+
+#[test]
+fn test_import_rejects_members_outside_the_target() {
+    let source_dir = tempdir().unwrap();
+    let target = tempdir().unwrap();
+    let index = open_index_with_options(source_dir.path().to_str().unwrap(), Default::default());
+    index.insert("k".to_string(), b"v".to_vec()).unwrap();
+    let mut archive = Vec::new();
+    index.export_snapshot(&mut archive).unwrap();
+
+    // Rename the manifest member to escape the target, fixing up the checksum
+    let header = &mut archive[512..1024];
+    header[..11].copy_from_slice(b"../MANIFEST");
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+    let dir = target.path().join("restored");
+    match LsmIndex::import_snapshot(archive.as_slice(), dir.to_str().unwrap()) {
+        Err(LsmIndexError::IoError(e)) => assert_eq!(e.kind(), ErrorKind::InvalidData),
+        other => panic!("expected InvalidData, got {:?}", other.err()),
+    }
+    assert!(!target.path().join("MANIFEST").exists());
+}
+
+#[test]
+fn test_import_rejects_archives_that_are_not_snapshots() {
+    let target = tempdir().unwrap();
+    let dir = target.path().join("restored");
+    let garbage = vec![7u8; 1024];
+    assert!(LsmIndex::import_snapshot(garbage.as_slice(), dir.to_str().unwrap()).is_err());
+}
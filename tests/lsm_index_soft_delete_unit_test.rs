@@ -0,0 +1,164 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::clock::MockClock;
+use lsmer::lsm_index::{LsmIndexError, LsmIndexOptions};
+use lsmer::sstable::{CompactionOptions, SSTableCompaction, SSTableReader};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempdir;
+
+fn index_options(clock: &MockClock, retention: Option<Duration>) -> LsmIndexOptions {
+    let mut options = LsmIndexOptions::default().with_clock(clock.clone());
+    if let Some(retention) = retention {
+        options = options.with_soft_delete_retention(retention);
+    }
+    options
+}
+
+const START_MS: u64 = 1_700_000_000_000;
+const HOUR: Duration = Duration::from_secs(3600);
+
+#[test]
+fn test_undelete_restores_removed_value() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(&clock, Some(HOUR)),
+    );
+
+    index.insert("key".to_string(), b"value".to_vec()).unwrap();
+    assert_eq!(index.remove("key").unwrap(), Some(b"value".to_vec()));
+    assert_eq!(index.get("key").unwrap(), None);
+    assert_eq!(index.soft_deleted_keys(), vec!["key".to_string()]);
+
+    assert!(index.undelete("key").unwrap());
+    assert_eq!(index.get("key").unwrap(), Some(b"value".to_vec()));
+    assert!(index.soft_deleted_keys().is_empty());
+
+    // Nothing left to undelete
+    assert!(!index.undelete("key").unwrap());
+    assert!(!index.undelete("never-written").unwrap());
+}
+
+#[test]
+fn test_new_write_supersedes_soft_delete() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(&clock, Some(HOUR)),
+    );
+
+    index.insert("key".to_string(), b"old".to_vec()).unwrap();
+    index.remove("key").unwrap();
+    index.insert("key".to_string(), b"new".to_vec()).unwrap();
+
+    assert!(!index.undelete("key").unwrap());
+    assert_eq!(index.get("key").unwrap(), Some(b"new".to_vec()));
+}
+
+#[test]
+fn test_undelete_requires_soft_deletes() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(&clock, None));
+
+    index.insert("key".to_string(), b"value".to_vec()).unwrap();
+    index.remove("key").unwrap();
+    assert!(matches!(
+        index.undelete("key"),
+        Err(LsmIndexError::InvalidOperation(_))
+    ));
+    assert!(index.soft_deleted_keys().is_empty());
+}
+
+#[test]
+fn test_expired_deletions_cannot_be_undone() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(&clock, Some(Duration::from_millis(50))),
+    );
+
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    index.insert("b".to_string(), b"2".to_vec()).unwrap();
+    index.remove("a").unwrap();
+    index.remove("b").unwrap();
+    clock.advance(Duration::from_millis(100));
+
+    assert!(index.soft_deleted_keys().is_empty());
+    assert!(!index.undelete("a").unwrap());
+    assert_eq!(index.purge_expired_deletions(), 1);
+    assert_eq!(index.get("b").unwrap(), None);
+}
+
+#[test]
+fn test_soft_deletes_survive_flush_and_recovery() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let clock = MockClock::new(START_MS);
+
+    {
+        let index = open_index_with_options(path, index_options(&clock, Some(HOUR)));
+        index.insert("kept".to_string(), b"1".to_vec()).unwrap();
+        index.insert("deleted".to_string(), b"2".to_vec()).unwrap();
+        index.flush().unwrap();
+        index.remove("deleted").unwrap();
+        index.flush().unwrap();
+    }
+
+    let mut index = open_index_with_options(path, index_options(&clock, Some(HOUR)));
+    index.recover().unwrap();
+    assert_eq!(index.get("kept").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(index.get("deleted").unwrap(), None);
+    assert_eq!(index.soft_deleted_keys(), vec!["deleted".to_string()]);
+
+    assert!(index.undelete("deleted").unwrap());
+    assert_eq!(index.get("deleted").unwrap(), Some(b"2".to_vec()));
+}
+
+#[test]
+fn test_compaction_applies_and_purges_tombstones() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(&clock, Some(HOUR)),
+    );
+    index.insert("kept".to_string(), b"1".to_vec()).unwrap();
+    index.insert("deleted".to_string(), b"2".to_vec()).unwrap();
+    index.flush().unwrap();
+    index.remove("deleted").unwrap();
+    index.flush().unwrap();
+
+    let inputs: Vec<String> = index
+        .list_sstables()
+        .into_iter()
+        .map(|info| info.path)
+        .collect();
+    assert_eq!(inputs.len(), 2);
+
+    // Tombstones within their retention period are carried over
+    let kept = dir.path().join("kept.db");
+    let kept = kept.to_str().unwrap();
+    let options = CompactionOptions::default().with_clock(Arc::new(clock.clone()));
+    SSTableCompaction::compact_sstables_with_options(&inputs, kept, &options).unwrap();
+    let reader = SSTableReader::open(kept).unwrap();
+    assert!(reader.tombstones().contains_key("deleted"));
+    let mut reader = SSTableReader::open(kept).unwrap();
+    assert_eq!(reader.get("deleted").unwrap(), None);
+    assert_eq!(reader.get("kept").unwrap(), Some(b"1".to_vec()));
+
+    // Expired tombstones are dropped, but still hide the older value
+    let purged = dir.path().join("purged.db");
+    let purged = purged.to_str().unwrap();
+    let options = options.with_tombstone_retention(Duration::ZERO);
+    SSTableCompaction::compact_sstables_with_options(&inputs, purged, &options).unwrap();
+    let mut reader = SSTableReader::open(purged).unwrap();
+    assert!(reader.tombstones().is_empty());
+    assert_eq!(reader.get("deleted").unwrap(), None);
+    assert_eq!(reader.entry_count(), 1);
+}
@@ -0,0 +1,71 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::LsmIndex;
+use std::path::Path;
+use tempfile::tempdir;
+
+/// Removed index entries are reclaimed lazily, so give the skip map's
+/// garbage collection a chance to run before giving up on the file going away
+fn wait_for_deletion(index: &LsmIndex, path: &str) -> bool {
+    for i in 0..10_000 {
+        if !Path::new(path).exists() {
+            return true;
+        }
+        let key = format!("__churn{}", i % 16);
+        index.insert(key.clone(), vec![0]).unwrap();
+        index.remove(&key).unwrap();
+    }
+    !Path::new(path).exists()
+}
+
+#[test]
+fn test_retired_file_stays_readable_while_referenced() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+
+    for i in 0..10 {
+        index
+            .insert(format!("key{}", i), format!("value{}", i).into_bytes())
+            .unwrap();
+    }
+    index.flush().unwrap();
+
+    let path = index.list_sstables()[0].path.clone();
+    assert_eq!(index.sstable_references(&path), 10);
+
+    assert!(index.retire_sstable(&path).unwrap());
+    assert!(index.list_sstables().is_empty());
+    assert_eq!(index.sstable_references(&path), 0);
+
+    // Entries still point into the file, so it must not be deleted yet
+    assert!(Path::new(&path).exists());
+    let value = index.get_with_checksum("key3").unwrap().unwrap();
+    assert_eq!(value.value, b"value3".to_vec());
+    assert!(value.verified);
+
+    // Once nothing references the file any more it is deleted
+    index.clear().unwrap();
+    assert!(wait_for_deletion(&index, &path));
+}
+
+#[test]
+fn test_retiring_unknown_file_is_a_no_op() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+
+    assert!(!index.retire_sstable("missing.db").unwrap());
+}
+
+#[test]
+fn test_retired_file_is_not_retired_twice() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    index.flush().unwrap();
+    let path = index.list_sstables()[0].path.clone();
+
+    assert!(index.retire_sstable(&path).unwrap());
+    assert!(!index.retire_sstable(&path).unwrap());
+}
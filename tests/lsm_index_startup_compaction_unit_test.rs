@@ -0,0 +1,115 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use std::fs;
+use tempfile::tempdir;
+
+const TINY_BYTES: u64 = 64 * 1024;
+
+/// Flush one small file per key, as a crash-restart loop would
+fn flush_each(index: &LsmIndex, keys: &[&str], value: &[u8]) {
+    for key in keys {
+        index.insert(key.to_string(), value.to_vec()).unwrap();
+        index.flush().unwrap();
+    }
+}
+
+#[test]
+fn test_recover_merges_tiny_files() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    {
+        let index = open_index_with_options(path, LsmIndexOptions::default());
+        flush_each(&index, &["a", "b", "c", "a"], b"old");
+        index.remove("b").unwrap();
+        index.flush().unwrap();
+        assert_eq!(index.list_sstables().len(), 5);
+    }
+
+    let options = LsmIndexOptions::default().with_startup_compaction(TINY_BYTES);
+    let mut index = open_index_with_options(path, options);
+    index.recover().unwrap();
+
+    assert_eq!(index.list_sstables().len(), 1);
+    assert_eq!(index.get("a").unwrap(), Some(b"old".to_vec()));
+    assert_eq!(index.get("b").unwrap(), None);
+    assert_eq!(index.get("c").unwrap(), Some(b"old".to_vec()));
+}
+
+#[test]
+fn test_recover_leaves_files_without_the_option() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    {
+        let index = open_index_with_options(path, LsmIndexOptions::default());
+        flush_each(&index, &["a", "b", "c"], b"value");
+    }
+
+    let mut index = open_index_with_options(path, LsmIndexOptions::default());
+    index.recover().unwrap();
+    assert_eq!(index.list_sstables().len(), 3);
+}
+
+#[test]
+fn test_only_the_newest_run_of_tiny_files_is_merged() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let large = vec![7u8; 4096];
+
+    {
+        let index = open_index_with_options(path, LsmIndexOptions::default());
+        flush_each(&index, &["a"], b"tiny");
+        for i in 0..32 {
+            index.insert(format!("big{:02}", i), large.clone()).unwrap();
+        }
+        index
+            .insert("a".to_string(), b"large file".to_vec())
+            .unwrap();
+        index.flush().unwrap();
+        flush_each(&index, &["x", "y"], b"tiny");
+    }
+
+    let threshold = 8 * 1024;
+    let sizes_before: Vec<u64> = {
+        let mut index = open_index_with_options(path, LsmIndexOptions::default());
+        index.recover().unwrap();
+        index
+            .list_sstables()
+            .iter()
+            .map(|info| fs::metadata(&info.path).unwrap().len())
+            .collect()
+    };
+    assert_eq!(sizes_before.len(), 4);
+    assert_eq!(
+        sizes_before
+            .iter()
+            .filter(|&&size| size < threshold)
+            .count(),
+        3
+    );
+
+    let options = LsmIndexOptions::default().with_startup_compaction(threshold);
+    let mut index = open_index_with_options(path, options);
+    index.recover().unwrap();
+
+    // The oldest tiny file sits behind the large one and is left alone
+    assert_eq!(index.list_sstables().len(), 3);
+    assert_eq!(index.get("a").unwrap(), Some(b"large file".to_vec()));
+    assert_eq!(index.get("x").unwrap(), Some(b"tiny".to_vec()));
+    assert_eq!(index.get("y").unwrap(), Some(b"tiny".to_vec()));
+    assert_eq!(index.get("big00").unwrap(), Some(large));
+}
+
+#[test]
+fn test_compact_tiny_files_needs_two_files() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    flush_each(&index, &["a"], b"value");
+
+    let summary = index.compact_tiny_files(TINY_BYTES).unwrap();
+    assert!(summary.files_written.is_empty());
+    assert_eq!(index.list_sstables().len(), 1);
+}
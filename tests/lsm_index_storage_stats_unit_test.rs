@@ -0,0 +1,107 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::{Compression, SSTableReader, ZstdOptions};
+use tempfile::tempdir;
+
+fn insert_similar(index: &LsmIndex, range: std::ops::Range<u32>) {
+    for i in range {
+        let value = format!(r#"{{"id":{},"status":"active","plan":"standard"}}"#, i);
+        index
+            .insert(format!("key{:05}", i), value.into_bytes())
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_properties_record_raw_and_data_bytes() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    index.insert("a".to_string(), b"12345".to_vec()).unwrap();
+    index.insert("bb".to_string(), b"678".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    let info = &index.list_sstables()[0];
+    let reader = SSTableReader::open(&info.path).unwrap();
+    assert_eq!(reader.raw_bytes(), Some(11));
+    // Each entry adds two length prefixes and a checksum
+    assert_eq!(reader.data_bytes(), Some(11 + 2 * 12));
+    assert_eq!(info.raw_bytes, 11);
+    assert_eq!(info.data_bytes, 11 + 2 * 12);
+}
+
+#[test]
+fn test_level_storage_stats_sum_files() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    assert!(index.level_storage_stats().is_empty());
+
+    insert_similar(&index, 0..100);
+    index.flush().unwrap();
+    insert_similar(&index, 100..150);
+    index.flush().unwrap();
+
+    let infos = index.list_sstables();
+    let levels = index.level_storage_stats();
+    assert_eq!(levels.len(), 1);
+    let level = &levels[0];
+    assert_eq!(level.level, 0);
+    assert_eq!(level.file_count, 2);
+    assert_eq!(level.entry_count, 150);
+    assert_eq!(
+        level.raw_bytes,
+        infos.iter().map(|i| i.raw_bytes).sum::<u64>()
+    );
+    assert_eq!(
+        level.data_bytes,
+        infos.iter().map(|i| i.data_bytes).sum::<u64>()
+    );
+    assert_eq!(
+        level.size_bytes,
+        infos.iter().map(|i| i.size_bytes).sum::<u64>()
+    );
+    assert!(level.compression_ratio() < 1.0);
+}
+
+#[test]
+fn test_compression_shows_in_ratio() {
+    let dir = tempdir().unwrap();
+    let options = LsmIndexOptions::default().with_compression(Compression::Zstd(
+        ZstdOptions::default().with_dictionary(4 * 1024, 64 * 1024),
+    ));
+    let index = open_index_with_options(dir.path().to_str().unwrap(), options);
+    insert_similar(&index, 0..2000);
+    index.flush().unwrap();
+
+    let level = &index.level_storage_stats()[0];
+    assert!(
+        level.compression_ratio() > 1.0,
+        "compressed level should hold more raw bytes than it stores: {}",
+        level.compression_ratio()
+    );
+    assert!(level.raw_bytes > level.data_bytes);
+}
+
+#[test]
+fn test_space_amplification_grows_with_overwrites() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    assert_eq!(index.space_amplification_estimate(), None);
+
+    insert_similar(&index, 0..500);
+    index.flush().unwrap();
+    let initial = index.space_amplification_estimate().unwrap();
+    assert!(initial >= 1.0);
+
+    // Rewriting every key leaves the first file's copies dead on disk
+    insert_similar(&index, 0..500);
+    index.flush().unwrap();
+    let rewritten = index.space_amplification_estimate().unwrap();
+    assert!(
+        rewritten > initial * 1.8,
+        "duplicate data should roughly double amplification: {} vs {}",
+        rewritten,
+        initial
+    );
+}
@@ -0,0 +1,174 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use lsmer::lsm_index::{
+    LsmIndexOptions, MAX_TRACE_IDS, ReadOptions, WriteBatchWithIndex, WriteOptions,
+};
+use std::sync::Mutex;
+use std::time::Duration;
+use tempfile::tempdir;
+
+/// Logger keeping the level and text of every record it is given
+struct CaptureLogger {
+    records: Mutex<Vec<(Level, String)>>,
+}
+
+impl Log for CaptureLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.records
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CaptureLogger = CaptureLogger {
+    records: Mutex::new(Vec::new()),
+};
+
+fn traced(trace_id: &str) -> WriteOptions {
+    WriteOptions::default().with_trace_id(trace_id)
+}
+
+#[test]
+fn test_flush_carries_the_trace_ids_of_its_writes() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    index
+        .insert_with_options("a".to_string(), b"1".to_vec(), &traced("req-1"))
+        .unwrap();
+    index
+        .insert_with_options("b".to_string(), b"2".to_vec(), &traced("req-2"))
+        .unwrap();
+    index
+        .insert_with_options("c".to_string(), b"3".to_vec(), &traced("req-1"))
+        .unwrap();
+    index.insert("d".to_string(), b"4".to_vec()).unwrap();
+
+    let summary = index.flush_with_summary().unwrap();
+    assert_eq!(summary.trace_ids, vec!["req-1", "req-2"]);
+    assert_eq!(
+        index.file_trace_ids(&summary.files_written[0]),
+        summary.trace_ids
+    );
+
+    // The next flush only carries writes made since
+    index.remove_with_options("a", &traced("req-3")).unwrap();
+    let mut batch = WriteBatchWithIndex::new();
+    batch.put("e", b"5".to_vec());
+    index.write_with_options(batch, &traced("req-4")).unwrap();
+    assert_eq!(
+        index.flush_with_summary().unwrap().trace_ids,
+        vec!["req-3", "req-4"]
+    );
+
+    index.insert("f".to_string(), b"6".to_vec()).unwrap();
+    assert!(index.flush_with_summary().unwrap().trace_ids.is_empty());
+}
+
+#[test]
+fn test_compaction_carries_the_trace_ids_of_its_inputs() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    let mut flushed = Vec::new();
+    for (key, trace_id) in [("a", "req-1"), ("b", "req-2"), ("a", "req-1")] {
+        index
+            .insert_with_options(key.to_string(), b"v".to_vec(), &traced(trace_id))
+            .unwrap();
+        flushed.push(index.flush_with_summary().unwrap().files_written[0].clone());
+    }
+
+    let summary = index.compact_range(..).unwrap();
+    assert_eq!(summary.trace_ids, vec!["req-1", "req-2"]);
+    assert_eq!(
+        index.file_trace_ids(&summary.files_written[0]),
+        summary.trace_ids
+    );
+    for path in &flushed {
+        assert!(index.file_trace_ids(path).is_empty());
+    }
+}
+
+#[test]
+fn test_trace_ids_are_capped_per_file() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    for i in 0..MAX_TRACE_IDS + 10 {
+        index
+            .insert_with_options(
+                format!("key{}", i),
+                b"v".to_vec(),
+                &traced(&format!("req-{}", i)),
+            )
+            .unwrap();
+    }
+    let trace_ids = index.flush_with_summary().unwrap().trace_ids;
+    assert_eq!(trace_ids.len(), MAX_TRACE_IDS);
+    assert_eq!(trace_ids[0], "req-0");
+}
+
+// The logger is global, so one test covers every message
+#[test]
+fn test_events_and_slow_logs_name_trace_ids() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let dir = tempdir().unwrap();
+    let options = LsmIndexOptions::default().with_slow_op_threshold(Duration::ZERO);
+    let index = open_index_with_options(dir.path().to_str().unwrap(), options);
+    index
+        .insert_with_options("a".to_string(), b"1".to_vec(), &traced("req-1"))
+        .unwrap();
+    index
+        .get_with_options("a", &ReadOptions::default().with_trace_id("req-2"))
+        .unwrap();
+    index.flush().unwrap();
+    index
+        .insert_with_options("b".to_string(), b"2".to_vec(), &traced("req-3"))
+        .unwrap();
+    index.flush().unwrap();
+    index.compact_range(..).unwrap();
+
+    let records = std::mem::take(&mut *LOGGER.records.lock().unwrap());
+    let logged = |level: Level, prefix: &str, suffix: &str| {
+        records
+            .iter()
+            .any(|(l, text)| *l == level && text.starts_with(prefix) && text.ends_with(suffix))
+    };
+    assert!(logged(Level::Warn, "Slow write took", "[trace IDs: req-1]"));
+    assert!(logged(Level::Warn, "Slow get took", "[trace IDs: req-2]"));
+    assert!(logged(Level::Warn, "Slow flush took", "[trace IDs: req-1]"));
+    assert!(logged(
+        Level::Warn,
+        "Slow compaction took",
+        "[trace IDs: req-1, req-3]"
+    ));
+    assert!(logged(
+        Level::Debug,
+        "Flushed 1 entries to",
+        "[trace IDs: req-3]"
+    ));
+    assert!(logged(
+        Level::Debug,
+        "Compacted 2 files into",
+        "[trace IDs: req-1, req-3]"
+    ));
+
+    // Without a threshold nothing is slow
+    let quiet = open_index_with_options(
+        dir.path().join("quiet").to_str().unwrap(),
+        LsmIndexOptions::default(),
+    );
+    quiet
+        .insert_with_options("a".to_string(), b"1".to_vec(), &traced("req-4"))
+        .unwrap();
+    let records = std::mem::take(&mut *LOGGER.records.lock().unwrap());
+    assert!(records.iter().all(|(level, _)| *level != Level::Warn));
+}
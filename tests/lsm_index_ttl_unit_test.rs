@@ -0,0 +1,231 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::clock::MockClock;
+use lsmer::lsm_index::{LsmIndexOptions, TtlSweeper};
+use lsmer::sstable::{CompactionOptions, SSTableCompaction, SSTableReader};
+use lsmer::wal::durability::Operation;
+use lsmer::wal::{RecordType, WriteAheadLog, WAL_HEADER_SIZE};
+use std::io::{Seek, SeekFrom};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempdir;
+
+fn index_options(clock: &MockClock) -> LsmIndexOptions {
+    LsmIndexOptions::default().with_clock(clock.clone())
+}
+
+const START_MS: u64 = 1_700_000_000_000;
+const SHORT: Duration = Duration::from_millis(50);
+const HOUR: Duration = Duration::from_secs(3600);
+
+#[test]
+fn test_expired_keys_read_as_missing() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(&clock));
+
+    index
+        .insert_with_ttl("short".to_string(), b"1".to_vec(), SHORT)
+        .unwrap();
+    index
+        .insert_with_ttl("long".to_string(), b"2".to_vec(), HOUR)
+        .unwrap();
+    assert_eq!(index.get("short").unwrap(), Some(b"1".to_vec()));
+
+    clock.advance(SHORT * 2);
+    assert_eq!(index.get("short").unwrap(), None);
+    assert!(!index.contains_key("short").unwrap());
+    assert_eq!(index.get("long").unwrap(), Some(b"2".to_vec()));
+
+    // A plain insert clears the time to live
+    index.insert("short".to_string(), b"3".to_vec()).unwrap();
+    assert_eq!(index.get("short").unwrap(), Some(b"3".to_vec()));
+}
+
+#[test]
+fn test_sweep_deletes_expired_keys() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(&clock));
+
+    index
+        .insert_with_ttl("a".to_string(), b"1".to_vec(), SHORT)
+        .unwrap();
+    index
+        .insert_with_ttl("b".to_string(), b"2".to_vec(), SHORT)
+        .unwrap();
+    index.insert("c".to_string(), b"3".to_vec()).unwrap();
+    clock.advance(SHORT * 2);
+
+    assert_eq!(index.sweep_expired().unwrap(), 2);
+    assert_eq!(index.sweep_expired().unwrap(), 0);
+    assert_eq!(index.get("c").unwrap(), Some(b"3".to_vec()));
+}
+
+#[test]
+fn test_sweep_logs_one_batch() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(path, index_options(&clock));
+
+    for key in ["a", "b", "c"] {
+        index
+            .insert_with_ttl(key.to_string(), b"1".to_vec(), SHORT)
+            .unwrap();
+    }
+    index
+        .insert_with_ttl("later".to_string(), b"2".to_vec(), HOUR)
+        .unwrap();
+    // Overwriting a key without a TTL keeps the sweep from deleting it
+    index.insert("c".to_string(), b"3".to_vec()).unwrap();
+    clock.advance(SHORT * 2);
+
+    assert_eq!(index.sweep_expired().unwrap(), 2);
+    assert_eq!(index.get("c").unwrap(), Some(b"3".to_vec()));
+    assert_eq!(index.get("later").unwrap(), Some(b"2".to_vec()));
+    drop(index);
+
+    // The five inserts, then the sweep's removals as one batch
+    let wal_path = format!("{}/wal/wal.log", path);
+    let mut wal = WriteAheadLog::new(&wal_path).unwrap();
+    wal.file.seek(SeekFrom::Start(WAL_HEADER_SIZE)).unwrap();
+    let mut records = Vec::new();
+    while let Ok(Some(record)) = wal.read_next_record() {
+        records.push(record);
+    }
+    let types: Vec<RecordType> = records.iter().map(|r| r.record_type).collect();
+    assert_eq!(types[..5], [RecordType::Insert; 5]);
+    assert_eq!(types[5..], [RecordType::Batch]);
+    match Operation::from_record(records[5].clone()).unwrap() {
+        Operation::Batch { operations } => assert_eq!(operations.len(), 2),
+        other => panic!("expected a batch, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_flushed_files_record_expiry_range() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(&clock));
+
+    index
+        .insert_with_ttl("a".to_string(), b"1".to_vec(), SHORT)
+        .unwrap();
+    index
+        .insert_with_ttl("b".to_string(), b"2".to_vec(), HOUR)
+        .unwrap();
+    index.insert("c".to_string(), b"3".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    let path = index.list_sstables()[0].path.clone();
+    let reader = SSTableReader::open(&path).unwrap();
+    let min = reader.min_expiry_ms().unwrap();
+    let max = reader.max_expiry_ms().unwrap();
+    assert_eq!(reader.expires_at("a"), Some(min));
+    assert_eq!(reader.expires_at("b"), Some(max));
+    assert_eq!(reader.expires_at("c"), None);
+
+    // Expired keys in flushed files are found through the file's expiries
+    clock.advance(SHORT * 2);
+    assert_eq!(index.sweep_expired().unwrap(), 1);
+    assert_eq!(index.get("a").unwrap(), None);
+    assert_eq!(index.get("b").unwrap(), Some(b"2".to_vec()));
+}
+
+#[test]
+fn test_files_without_expiring_entries_have_no_expiry_range() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(&clock));
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    let path = index.list_sstables()[0].path.clone();
+    let reader = SSTableReader::open(&path).unwrap();
+    assert_eq!(reader.min_expiry_ms(), None);
+    assert_eq!(reader.max_expiry_ms(), None);
+}
+
+#[test]
+fn test_expired_entry_hides_older_value_after_recovery() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let clock = MockClock::new(START_MS);
+
+    {
+        let index = open_index_with_options(path, index_options(&clock));
+        index.insert("key".to_string(), b"old".to_vec()).unwrap();
+        index.flush().unwrap();
+        index
+            .insert_with_ttl("key".to_string(), b"new".to_vec(), SHORT)
+            .unwrap();
+        index.flush().unwrap();
+    }
+    clock.advance(SHORT * 2);
+
+    let mut index = open_index_with_options(path, index_options(&clock));
+    index.recover().unwrap();
+    assert_eq!(index.get("key").unwrap(), None);
+}
+
+#[test]
+fn test_compaction_turns_expired_entries_into_tombstones() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(&clock));
+    index
+        .insert_with_ttl("expired".to_string(), b"1".to_vec(), SHORT)
+        .unwrap();
+    index
+        .insert_with_ttl("live".to_string(), b"2".to_vec(), HOUR)
+        .unwrap();
+    index.flush().unwrap();
+    clock.advance(SHORT * 2);
+
+    let input = index.list_sstables()[0].path.clone();
+    let output = dir.path().join("compacted.db");
+    let output = output.to_str().unwrap();
+    let options = CompactionOptions::default().with_clock(Arc::new(clock.clone()));
+    SSTableCompaction::compact_sstables_with_options(
+        std::slice::from_ref(&input),
+        output,
+        &options,
+    )
+    .unwrap();
+
+    let mut reader = SSTableReader::open(output).unwrap();
+    assert_eq!(reader.entry_count(), 1);
+    assert_eq!(reader.tombstones()["expired"].value, None);
+    assert_eq!(
+        reader.expires_at("live"),
+        SSTableReader::open(&input).unwrap().expires_at("live")
+    );
+    assert_eq!(reader.get("live").unwrap(), Some(b"2".to_vec()));
+}
+
+#[tokio::test]
+async fn test_background_sweeper_deletes_expired_keys() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let index = Arc::new(open_index_with_options(
+        dir.path().to_str().unwrap(),
+        index_options(&clock),
+    ));
+    index
+        .insert_with_ttl(
+            "key".to_string(),
+            b"value".to_vec(),
+            Duration::from_millis(10),
+        )
+        .unwrap();
+    clock.advance(SHORT);
+
+    let sweeper = TtlSweeper::spawn(index.clone(), Duration::from_millis(20));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    sweeper.stop().await;
+
+    // The sweeper already deleted the key, leaving nothing to sweep
+    assert_eq!(index.sweep_expired().unwrap(), 0);
+}
@@ -0,0 +1,170 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions, VALUE_LOG_EXTENSION};
+use std::fs;
+use tempfile::tempdir;
+
+const LARGE: usize = 8 * 1024;
+const THRESHOLD: usize = 1024;
+
+fn index_options() -> LsmIndexOptions {
+    LsmIndexOptions::default().with_value_separation(THRESHOLD)
+}
+
+fn large(i: usize) -> Vec<u8> {
+    vec![(i % 251) as u8; LARGE]
+}
+
+/// Sizes of the value log segments and of the SSTables in `path`
+fn file_sizes(path: &str) -> (Vec<u64>, Vec<u64>) {
+    let mut segments = Vec::new();
+    let mut sstables = Vec::new();
+    for entry in fs::read_dir(path).unwrap() {
+        let path = entry.unwrap().path();
+        let size = fs::metadata(&path).unwrap().len();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(VALUE_LOG_EXTENSION) => segments.push(size),
+            Some("db") => sstables.push(size),
+            _ => {}
+        }
+    }
+    (segments, sstables)
+}
+
+#[test]
+fn test_large_values_are_separated() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    {
+        let index = open_index_with_options(path, index_options());
+        for i in 0..20 {
+            index.insert(format!("large{:02}", i), large(i)).unwrap();
+            index
+                .insert(format!("small{:02}", i), vec![i as u8; 16])
+                .unwrap();
+        }
+        index.flush().unwrap();
+
+        let (segments, sstables) = file_sizes(path);
+        assert_eq!(segments.len(), 1);
+        assert!(segments[0] > 20 * LARGE as u64);
+        assert!(sstables.iter().sum::<u64>() < 4 * LARGE as u64);
+
+        assert_eq!(index.get("large07").unwrap(), Some(large(7)));
+        assert_eq!(index.get("small07").unwrap(), Some(vec![7; 16]));
+        assert_eq!(index.get_flushed("large08").unwrap(), Some(large(8)));
+        let checksummed = index.get_with_checksum("large09").unwrap().unwrap();
+        assert_eq!(checksummed.value, large(9));
+        assert!(checksummed.verified);
+    }
+
+    let mut index = open_index_with_options(path, index_options());
+    index.recover().unwrap();
+    assert_eq!(index.get("large19").unwrap(), Some(large(19)));
+    let scanned = index
+        .range("large03".to_string().."large05".to_string())
+        .unwrap();
+    assert_eq!(
+        scanned,
+        vec![
+            ("large03".to_string(), large(3)),
+            ("large04".to_string(), large(4))
+        ]
+    );
+
+    // Compaction carries the pointers without touching the values
+    let (segments_before, _) = file_sizes(path);
+    index.compact_range(..).unwrap();
+    assert_eq!(file_sizes(path).0, segments_before);
+    assert_eq!(index.get("large11").unwrap(), Some(large(11)));
+}
+
+#[test]
+fn test_garbage_collection_reclaims_segments() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = open_index_with_options(path, index_options());
+    for i in 0..10 {
+        index.insert(format!("key{:02}", i), large(i)).unwrap();
+    }
+    index.flush().unwrap();
+
+    // A segment with few dead values is left alone
+    index.remove("key00").unwrap();
+    index.flush().unwrap();
+    let summary = index.collect_value_log_garbage(0.5).unwrap();
+    assert_eq!(summary.segments_deleted, 0);
+    assert_eq!(summary.values_relocated, 0);
+
+    // Once most values are dead the live ones move to a new segment
+    for i in 1..8 {
+        index
+            .insert(format!("key{:02}", i), vec![i as u8; 16])
+            .unwrap();
+    }
+    index.flush().unwrap();
+    let summary = index.collect_value_log_garbage(0.5).unwrap();
+    assert_eq!(summary.values_relocated, 2);
+    assert_eq!(file_sizes(path).0.len(), 2);
+
+    // The old segment goes once compaction drops the pointers into it
+    index.compact_range(..).unwrap();
+    let summary = index.collect_value_log_garbage(0.5).unwrap();
+    assert_eq!(summary.segments_deleted, 1);
+    assert!(summary.bytes_reclaimed > 10 * LARGE as u64);
+    let (segments, _) = file_sizes(path);
+    assert_eq!(segments.len(), 1);
+    assert!(segments[0] < 3 * LARGE as u64);
+
+    assert_eq!(index.get("key00").unwrap(), None);
+    assert_eq!(index.get("key03").unwrap(), Some(vec![3; 16]));
+    assert_eq!(index.get("key08").unwrap(), Some(large(8)));
+    assert_eq!(index.get("key09").unwrap(), Some(large(9)));
+}
+
+#[test]
+fn test_garbage_collection_keeps_segments_of_pinned_files() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = open_index_with_options(path, index_options());
+    for i in 0..4 {
+        index.insert(format!("key{:02}", i), large(i)).unwrap();
+    }
+    index.flush().unwrap();
+
+    // Retired, but still pinned by the entries pointing into it
+    let file = index.list_sstables().pop().unwrap();
+    assert!(index.retire_sstable(&file.path).unwrap());
+    assert!(fs::metadata(&file.path).is_ok());
+
+    let summary = index.collect_value_log_garbage(0.5).unwrap();
+    assert_eq!(summary.segments_deleted, 0);
+    assert_eq!(file_sizes(path).0.len(), 1);
+    assert_eq!(index.get("key02").unwrap(), Some(large(2)));
+}
+
+#[test]
+fn test_fork_and_snapshot_carry_segments() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("source");
+    let path = path.to_str().unwrap();
+    let index = open_index_with_options(path, index_options());
+    for i in 0..5 {
+        index.insert(format!("key{}", i), large(i)).unwrap();
+    }
+
+    let forked_path = dir.path().join("fork");
+    let fork = index.fork(forked_path.to_str().unwrap()).unwrap();
+    assert_eq!(fork.get("key2").unwrap(), Some(large(2)));
+
+    let mut archive = Vec::new();
+    index.export_snapshot(&mut archive).unwrap();
+    let imported = dir.path().join("imported");
+    let imported = imported.to_str().unwrap();
+    LsmIndex::import_snapshot(archive.as_slice(), imported).unwrap();
+    assert!(!file_sizes(imported).0.is_empty());
+    let mut restored = open_index_with_options(imported, index_options());
+    restored.recover().unwrap();
+    assert_eq!(restored.get("key4").unwrap(), Some(large(4)));
+}
@@ -0,0 +1,149 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::{LsmIndexError, WriteOptions};
+use std::collections::HashMap;
+use std::fs;
+use std::time::Duration;
+use tempfile::tempdir;
+
+fn wal_len(path: &str) -> u64 {
+    fs::metadata(format!("{}/wal/wal.log", path)).unwrap().len()
+}
+
+#[test]
+fn test_default_writes_are_logged() {
+    let options = WriteOptions::default();
+    assert!(!options.disable_wal);
+    assert!(options.sync);
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = open_index(path);
+
+    let before = wal_len(path);
+    index.insert("logged".to_string(), b"v".to_vec()).unwrap();
+    let after_insert = wal_len(path);
+    assert!(after_insert > before);
+    index.remove("logged").unwrap();
+    assert!(wal_len(path) > after_insert);
+}
+
+#[test]
+fn test_disabled_wal_skips_the_log() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = open_index(path);
+    let skip_wal = WriteOptions::default().with_disable_wal(true);
+
+    let before = wal_len(path);
+    index
+        .insert_with_options("bulk".to_string(), b"v".to_vec(), &skip_wal)
+        .unwrap();
+    assert_eq!(wal_len(path), before);
+
+    // Unlogged writes are still visible and removable in memory
+    assert_eq!(index.get("bulk").unwrap(), Some(b"v".to_vec()));
+    assert_eq!(
+        index.remove_with_options("bulk", &skip_wal).unwrap(),
+        Some(b"v".to_vec())
+    );
+    assert_eq!(index.get("bulk").unwrap(), None);
+    assert_eq!(wal_len(path), before);
+}
+
+#[test]
+fn test_unlogged_writes_survive_a_flush() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let skip_wal = WriteOptions::default().with_disable_wal(true);
+    {
+        let index = open_index(path);
+        for i in 0..100 {
+            index
+                .insert_with_options(format!("key{}", i), b"v".to_vec(), &skip_wal)
+                .unwrap();
+        }
+        index.remove_with_options("key7", &skip_wal).unwrap();
+        index.flush().unwrap();
+    }
+
+    let mut index = open_index(path);
+    index.recover().unwrap();
+    assert_eq!(index.get("key42").unwrap(), Some(b"v".to_vec()));
+    assert_eq!(index.get("key7").unwrap(), None);
+}
+
+#[test]
+fn test_unsynced_writes_are_logged() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = open_index(path);
+    let no_sync = WriteOptions::default().with_sync(false);
+
+    let before = wal_len(path);
+    index
+        .insert_with_options("a".to_string(), b"1".to_vec(), &no_sync)
+        .unwrap();
+    index.remove_with_options("a", &no_sync).unwrap();
+    index.sync_wal().unwrap();
+    assert!(wal_len(path) > before);
+    assert_eq!(index.get("a").unwrap(), None);
+}
+
+#[test]
+fn test_every_write_api_can_skip_the_log() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = open_index(path);
+    let skip_wal = WriteOptions::default().with_disable_wal(true);
+
+    let before = wal_len(path);
+    index
+        .insert_bytes_with_options(b"bytes", b"v".to_vec(), &skip_wal)
+        .unwrap();
+    index
+        .insert_with_ttl_and_options(
+            "ttl".to_string(),
+            b"v".to_vec(),
+            Duration::from_secs(60),
+            &skip_wal,
+        )
+        .unwrap();
+    let columns = HashMap::from([("name".to_string(), b"a".to_vec())]);
+    index
+        .put_columns_with_options("row".to_string(), columns, &skip_wal)
+        .unwrap();
+    let updates = HashMap::from([("age".to_string(), Some(b"7".to_vec()))]);
+    index
+        .merge_columns_with_options("row".to_string(), updates, &skip_wal)
+        .unwrap();
+    assert_eq!(index.get_columns("row").unwrap().unwrap().len(), 2);
+    index
+        .delete_range_with_options("bytes", "bytez", &skip_wal)
+        .unwrap();
+    assert_eq!(index.get("bytes").unwrap(), None);
+    assert_eq!(index.get("ttl").unwrap(), Some(b"v".to_vec()));
+    index.clear_with_options(&skip_wal).unwrap();
+    assert_eq!(index.get("ttl").unwrap(), None);
+    assert_eq!(wal_len(path), before);
+}
+
+#[test]
+fn test_keyless_writes_refuse_preconditions() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = open_index(path);
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    let conditional = WriteOptions::default().with_if_unchanged_since(index.sequence_of("a"));
+
+    assert!(matches!(
+        index.delete_range_with_options("a", "b", &conditional),
+        Err(LsmIndexError::InvalidOperation(_))
+    ));
+    assert!(matches!(
+        index.clear_with_options(&conditional),
+        Err(LsmIndexError::InvalidOperation(_))
+    ));
+    assert_eq!(index.get("a").unwrap(), Some(b"1".to_vec()));
+}
@@ -0,0 +1,100 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::LsmIndexOptions;
+use lsmer::sstable::{CompactionOptions, SSTableCompaction, SSTableReader};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tempfile::tempdir;
+
+fn index_options(track_write_times: bool) -> LsmIndexOptions {
+    LsmIndexOptions::default().with_write_times(track_write_times)
+}
+
+#[test]
+fn test_write_time_is_recorded_and_updated() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(true));
+
+    let before = SystemTime::now();
+    index.insert("key".to_string(), b"one".to_vec()).unwrap();
+    let first = index.get_with_metadata("key").unwrap().unwrap();
+    assert_eq!(first.value, b"one".to_vec());
+    let first_written = first.written_at.unwrap();
+    assert!(first_written + Duration::from_millis(1) >= before);
+    assert!(first_written <= SystemTime::now());
+
+    thread::sleep(Duration::from_millis(20));
+    index.insert("key".to_string(), b"two".to_vec()).unwrap();
+    let second = index.get_with_metadata("key").unwrap().unwrap();
+    assert_eq!(second.value, b"two".to_vec());
+    assert!(second.written_at.unwrap() > first_written);
+
+    assert!(index.get_with_metadata("missing").unwrap().is_none());
+}
+
+#[test]
+fn test_write_times_are_off_by_default() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(false));
+
+    index.insert("key".to_string(), b"value".to_vec()).unwrap();
+    let value = index.get_with_metadata("key").unwrap().unwrap();
+    assert_eq!(value.value, b"value".to_vec());
+    assert_eq!(value.written_at, None);
+}
+
+#[test]
+fn test_write_times_survive_flush_and_recovery() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    let written_at = {
+        let index = open_index_with_options(path, index_options(true));
+        index.insert("key".to_string(), b"value".to_vec()).unwrap();
+        let written_at = index.get_with_metadata("key").unwrap().unwrap().written_at;
+        index.flush().unwrap();
+        assert_eq!(
+            index.get_with_metadata("key").unwrap().unwrap().written_at,
+            written_at
+        );
+        written_at
+    };
+
+    let mut index = open_index_with_options(path, index_options(true));
+    index.recover().unwrap();
+    let value = index.get_with_metadata("key").unwrap().unwrap();
+    assert_eq!(value.value, b"value".to_vec());
+    assert_eq!(value.written_at, written_at);
+}
+
+#[test]
+fn test_compaction_keeps_write_times() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), index_options(true));
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    index.insert("b".to_string(), b"2".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    let input = index.list_sstables()[0].path.clone();
+    let expected = SSTableReader::open(&input).unwrap().write_time("b");
+    assert!(expected.is_some());
+
+    let output = dir.path().join("compacted.db");
+    let output = output.to_str().unwrap();
+    SSTableCompaction::compact_sstables_with_options(
+        &[input],
+        output,
+        &CompactionOptions::default(),
+    )
+    .unwrap();
+
+    let reader = SSTableReader::open(output).unwrap();
+    assert_eq!(reader.write_time("b"), expected);
+    let written_ms = expected.unwrap();
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    assert!(written_ms <= now_ms);
+}
@@ -0,0 +1,164 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::LsmIndexOptions;
+use lsmer::sstable::{
+    CompactionOptions, SSTableCompaction, SSTableReader, SSTableWriter, Tombstone,
+};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tempfile::tempdir;
+
+#[test]
+fn test_min_active_sequence_follows_open_snapshots() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    assert_eq!(index.min_active_sequence(), None);
+    assert_eq!(index.min_active_snapshot(), None);
+
+    let first = index.acquire_snapshot();
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    let second = index.acquire_snapshot();
+    assert!(second.sequence() > first.sequence());
+    assert_eq!(index.active_snapshots(), 2);
+    assert_eq!(index.min_active_sequence(), Some(first.sequence()));
+    assert_eq!(index.min_active_snapshot(), Some(first.at_ms()));
+
+    // Releasing the oldest moves the minimum forward
+    drop(first);
+    assert_eq!(index.min_active_sequence(), Some(second.sequence()));
+    drop(second);
+    assert_eq!(index.min_active_sequence(), None);
+    assert_eq!(index.active_snapshots(), 0);
+}
+
+#[test]
+fn test_open_cursor_counts_as_snapshot() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(dir.path().to_str().unwrap(), LsmIndexOptions::default());
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+
+    let sequence = index.last_sequence();
+    let cursor = index.cursor();
+    assert_eq!(index.min_active_sequence(), Some(sequence));
+    index.insert("b".to_string(), b"2".to_vec()).unwrap();
+    assert_eq!(index.min_active_sequence(), Some(sequence));
+
+    drop(cursor);
+    assert_eq!(index.min_active_sequence(), None);
+}
+
+#[test]
+fn test_snapshots_acquired_concurrently_are_all_released() {
+    let dir = tempdir().unwrap();
+    let index = Arc::new(open_index_with_options(
+        dir.path().to_str().unwrap(),
+        LsmIndexOptions::default(),
+    ));
+
+    let handles: Vec<_> = (0..8)
+        .map(|t| {
+            let index = index.clone();
+            thread::spawn(move || {
+                for i in 0..200 {
+                    let snapshot = index.acquire_snapshot();
+                    index.insert(format!("{}-{}", t, i), vec![0]).unwrap();
+                    assert!(index.min_active_sequence().unwrap() <= snapshot.sequence());
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert_eq!(index.active_snapshots(), 0);
+}
+
+#[test]
+fn test_snapshot_read_options_hide_later_writes() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        LsmIndexOptions::default().with_write_times(true),
+    );
+    index.insert("before".to_string(), b"1".to_vec()).unwrap();
+    thread::sleep(Duration::from_millis(5));
+    let snapshot = index.acquire_snapshot();
+    thread::sleep(Duration::from_millis(5));
+    index.insert("after".to_string(), b"2".to_vec()).unwrap();
+
+    let read_options = snapshot.read_options();
+    assert_eq!(
+        index.get_with_options("before", &read_options).unwrap(),
+        Some(b"1".to_vec())
+    );
+    assert_eq!(
+        index.get_with_options("after", &read_options).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn test_compaction_keeps_tombstones_an_open_snapshot_needs() {
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("input.db");
+    let input = input.to_str().unwrap().to_string();
+    let mut writer = SSTableWriter::new(&input, 1, false, 0.0).unwrap();
+    writer.write_entry("live", b"1").unwrap();
+    writer.write_tombstone(
+        "old",
+        Tombstone {
+            deleted_at_ms: 1_000,
+            value: None,
+        },
+    );
+    writer.write_tombstone(
+        "recent",
+        Tombstone {
+            deleted_at_ms: 5_000,
+            value: None,
+        },
+    );
+    writer.finalize().unwrap();
+
+    // Without a snapshot, both tombstones are past retention
+    let output = dir.path().join("plain.db");
+    let output = output.to_str().unwrap();
+    let options = CompactionOptions::default().with_tombstone_retention(Duration::ZERO);
+    SSTableCompaction::compact_sstables_with_options(
+        std::slice::from_ref(&input),
+        output,
+        &options,
+    )
+    .unwrap();
+    assert!(SSTableReader::open(output).unwrap().tombstones().is_empty());
+
+    // A snapshot taken between the deletions keeps the later one
+    let output = dir.path().join("snapshot.db");
+    let output = output.to_str().unwrap();
+    let options = options.with_oldest_snapshot(3_000);
+    SSTableCompaction::compact_sstables_with_options(&[input], output, &options).unwrap();
+    let reader = SSTableReader::open(output).unwrap();
+    let tombstones = reader.tombstones();
+    assert!(!tombstones.contains_key("old"));
+    assert!(tombstones.contains_key("recent"));
+}
+
+#[test]
+fn test_purge_keeps_soft_deletes_an_open_snapshot_needs() {
+    let dir = tempdir().unwrap();
+    let index = open_index_with_options(
+        dir.path().to_str().unwrap(),
+        LsmIndexOptions::default().with_soft_delete_retention(Duration::ZERO),
+    );
+    index.insert("key".to_string(), b"value".to_vec()).unwrap();
+
+    let snapshot = index.acquire_snapshot();
+    thread::sleep(Duration::from_millis(5));
+    index.remove("key").unwrap();
+    assert_eq!(index.purge_expired_deletions(), 0);
+
+    drop(snapshot);
+    assert_eq!(index.purge_expired_deletions(), 1);
+}
@@ -0,0 +1,141 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::lsm_index::{
+    BatchEntry, LsmIndex, LsmIndexError, LsmIndexOptions, WriteBatchWithIndex, WriteOptions,
+};
+use lsmer::wal::durability::Operation;
+use lsmer::wal::{RecordType, WriteAheadLog, WAL_HEADER_SIZE};
+use std::io::{Seek, SeekFrom};
+use tempfile::tempdir;
+
+#[test]
+fn test_batch_reads_layer_over_index() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    index.insert("kept".to_string(), b"db".to_vec()).unwrap();
+    index
+        .insert("changed".to_string(), b"old".to_vec())
+        .unwrap();
+    index
+        .insert("deleted".to_string(), b"gone".to_vec())
+        .unwrap();
+
+    let mut batch = WriteBatchWithIndex::new();
+    batch.put("changed", b"new".to_vec());
+    batch.delete("deleted");
+    batch.put("added", b"1".to_vec());
+
+    assert_eq!(
+        batch.get_from_batch("changed"),
+        Some(BatchEntry::Put(b"new"))
+    );
+    assert_eq!(batch.get_from_batch("deleted"), Some(BatchEntry::Delete));
+    assert_eq!(batch.get_from_batch("kept"), None);
+
+    let read = |key: &str| batch.get_from_batch_and_db(&index, key).unwrap();
+    assert_eq!(read("kept"), Some(b"db".to_vec()));
+    assert_eq!(read("changed"), Some(b"new".to_vec()));
+    assert_eq!(read("deleted"), None);
+    assert_eq!(read("added"), Some(b"1".to_vec()));
+
+    // Nothing reaches the index until the batch is written
+    assert_eq!(index.get("changed").unwrap(), Some(b"old".to_vec()));
+    assert_eq!(index.get("added").unwrap(), None);
+}
+
+#[test]
+fn test_later_change_replaces_earlier_one() {
+    let mut batch = WriteBatchWithIndex::new();
+    batch.put("key", b"1".to_vec());
+    batch.delete("key");
+    assert_eq!(batch.get_from_batch("key"), Some(BatchEntry::Delete));
+    batch.put("key", b"2".to_vec());
+    assert_eq!(batch.len(), 1);
+
+    let entries: Vec<_> = batch.iter().collect();
+    assert_eq!(entries, vec![("key", BatchEntry::Put(b"2"))]);
+
+    batch.clear();
+    assert!(batch.is_empty());
+}
+
+#[test]
+fn test_write_applies_batch_as_one_wal_record() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = open_index(path);
+    index
+        .insert("deleted".to_string(), b"gone".to_vec())
+        .unwrap();
+
+    let mut batch = WriteBatchWithIndex::new();
+    batch.put("a", b"1".to_vec());
+    batch.put("b", b"2".to_vec());
+    batch.delete("deleted");
+    let sequence = index.write(batch).unwrap();
+
+    assert_eq!(sequence, index.last_sequence());
+    assert_eq!(index.get("a").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(index.get("b").unwrap(), Some(b"2".to_vec()));
+    assert_eq!(index.get("deleted").unwrap(), None);
+    drop(index);
+
+    // The insert before the batch, then the batch
+    let wal_path = format!("{}/wal/wal.log", path);
+    let mut wal = WriteAheadLog::new(&wal_path).unwrap();
+    wal.file.seek(SeekFrom::Start(WAL_HEADER_SIZE)).unwrap();
+    let mut records = Vec::new();
+    while let Ok(Some(record)) = wal.read_next_record() {
+        records.push(record);
+    }
+    let types: Vec<RecordType> = records.iter().map(|r| r.record_type).collect();
+    assert_eq!(types, vec![RecordType::Insert, RecordType::Batch]);
+    match Operation::from_record(records[1].clone()).unwrap() {
+        Operation::Batch { operations } => assert_eq!(operations.len(), 3),
+        other => panic!("expected a batch, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_failed_precondition_writes_nothing() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    let token = index.last_sequence();
+    index
+        .insert("raced".to_string(), b"other".to_vec())
+        .unwrap();
+
+    let mut batch = WriteBatchWithIndex::new();
+    batch.put("fresh", b"1".to_vec());
+    batch.put("raced", b"mine".to_vec());
+    let options = WriteOptions::default().with_if_unchanged_since(token);
+    assert!(matches!(
+        index.write_with_options(batch, &options),
+        Err(LsmIndexError::PreconditionFailed { .. })
+    ));
+    assert_eq!(index.get("fresh").unwrap(), None);
+    assert_eq!(index.get("raced").unwrap(), Some(b"other".to_vec()));
+}
+
+#[test]
+fn test_batch_removal_keeps_value_for_undelete() {
+    let dir = tempdir().unwrap();
+    let index = LsmIndex::new_with_options(
+        4 * 1024 * 1024,
+        dir.path().to_str().unwrap().to_string(),
+        None,
+        false,
+        0.01,
+        LsmIndexOptions::default().with_soft_delete_retention(std::time::Duration::from_secs(60)),
+    )
+    .unwrap();
+    index.insert("key".to_string(), b"value".to_vec()).unwrap();
+
+    let mut batch = WriteBatchWithIndex::new();
+    batch.delete("key");
+    index.write(batch).unwrap();
+    assert_eq!(index.get("key").unwrap(), None);
+    assert!(index.undelete("key").unwrap());
+    assert_eq!(index.get("key").unwrap(), Some(b"value".to_vec()));
+}
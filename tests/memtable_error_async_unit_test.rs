@@ -11,7 +11,7 @@ async fn test_memtable_error_display() {
         let errors = vec![
             MemtableError::CapacityExceeded,
             MemtableError::KeyNotFound,
-            MemtableError::WalError(io::Error::new(io::ErrorKind::Other, "WAL error")),
+            MemtableError::WalError(io::Error::other("WAL error")),
             MemtableError::IoError(io::Error::new(io::ErrorKind::NotFound, "IO error")),
             MemtableError::LockError,
         ];
@@ -53,7 +53,7 @@ async fn test_memtable_error_display() {
 async fn test_memtable_error_conversions() {
     let test_future = async {
         // Test From<WalError> for MemtableError
-        let wal_io_error = WalError::IoError(io::Error::new(io::ErrorKind::Other, "WAL IO error"));
+        let wal_io_error = WalError::IoError(io::Error::other("WAL IO error"));
         let wal_invalid_record = WalError::InvalidRecord;
         let wal_checkpoint_not_found = WalError::CheckpointNotFound;
 
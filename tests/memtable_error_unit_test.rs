@@ -13,7 +13,7 @@ async fn test_memtable_error_variants_display() {
             MemtableError::CapacityExceeded,
             MemtableError::KeyNotFound,
             MemtableError::WalError(io::Error::new(io::ErrorKind::NotFound, "test")),
-            MemtableError::IoError(io::Error::new(io::ErrorKind::Other, "io test")),
+            MemtableError::IoError(io::Error::other("io test")),
             MemtableError::LockError,
         ];
 
@@ -54,7 +54,7 @@ async fn test_memtable_error_source() {
         assert!(wal_err.source().is_some());
 
         // IoError has a source
-        let io_err = MemtableError::IoError(io::Error::new(io::ErrorKind::Other, "io error"));
+        let io_err = MemtableError::IoError(io::Error::other("io error"));
         assert!(io_err.source().is_some());
 
         // Other variants should have no source
@@ -73,7 +73,7 @@ async fn test_memtable_error_source() {
 async fn test_memtable_error_from_io_error() {
     let test_future = async {
         // Test From<io::Error> implementation
-        let io_error = io::Error::new(io::ErrorKind::Other, "test io error");
+        let io_error = io::Error::other("test io error");
         let memtable_error = MemtableError::from(io_error);
 
         match memtable_error {
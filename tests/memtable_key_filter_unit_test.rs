@@ -0,0 +1,75 @@
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::memtable::{KeyFilterOptions, Memtable, StringMemtable};
+use tempfile::tempdir;
+
+const FILTER: KeyFilterOptions = KeyFilterOptions {
+    expected_keys: 1000,
+    false_positive_rate: 0.01,
+};
+
+#[test]
+fn test_absent_keys_skip_the_map() {
+    let memtable = StringMemtable::new(1024 * 1024).with_key_filter(FILTER);
+    assert!(memtable.has_key_filter());
+    for i in 0..100 {
+        memtable.insert(format!("key{}", i), vec![1]).unwrap();
+    }
+    let inserts = memtable.filter_negatives();
+
+    for i in 0..100 {
+        assert_eq!(memtable.get(&format!("key{}", i)).unwrap(), Some(vec![1]));
+    }
+    assert_eq!(memtable.filter_negatives(), inserts);
+
+    for i in 0..1000 {
+        assert_eq!(memtable.get(&format!("missing{}", i)).unwrap(), None);
+    }
+    assert!(memtable.filter_negatives() - inserts > 950);
+}
+
+#[test]
+fn test_filter_is_emptied_with_the_memtable() {
+    let memtable = StringMemtable::new(1024 * 1024).with_key_filter(FILTER);
+    memtable.insert("a".to_string(), vec![1]).unwrap();
+    memtable.clear().unwrap();
+
+    let before = memtable.filter_negatives();
+    assert_eq!(memtable.get(&"a".to_string()).unwrap(), None);
+    assert_eq!(memtable.filter_negatives(), before + 1);
+
+    memtable.insert("a".to_string(), vec![2]).unwrap();
+    assert_eq!(memtable.get(&"a".to_string()).unwrap(), Some(vec![2]));
+}
+
+#[test]
+fn test_removed_keys_are_not_returned() {
+    let memtable = StringMemtable::new(1024 * 1024).with_key_filter(FILTER);
+    memtable.insert("a".to_string(), vec![1]).unwrap();
+    memtable.remove(&"a".to_string()).unwrap();
+    assert_eq!(memtable.get(&"a".to_string()).unwrap(), None);
+}
+
+#[test]
+fn test_index_memtable_filter_survives_flushes() {
+    let dir = tempdir().unwrap();
+    let options = LsmIndexOptions::default().with_memtable_filter(1000, 0.01);
+    let index = LsmIndex::new_with_options(
+        4 * 1024 * 1024,
+        dir.path().to_str().unwrap().to_string(),
+        None,
+        true,
+        0.01,
+        options,
+    )
+    .unwrap();
+
+    index.insert("flushed".to_string(), b"1".to_vec()).unwrap();
+    index.flush().unwrap();
+    index.insert("active".to_string(), b"2".to_vec()).unwrap();
+
+    let before = index.memtable_filter_negatives();
+    assert_eq!(index.get("flushed").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(index.get("active").unwrap(), Some(b"2".to_vec()));
+    assert_eq!(index.get("missing").unwrap(), None);
+    assert!(index.memtable_filter_negatives() > before);
+}
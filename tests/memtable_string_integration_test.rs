@@ -273,7 +273,7 @@ async fn test_string_memtable_generate_timestamp() {
         let sstable_path = memtable.flush_to_sstable(test_dir).unwrap();
 
         // Verify timestamp is in filename
-        let filename = sstable_path.split('/').last().unwrap();
+        let filename = sstable_path.split('/').next_back().unwrap();
         assert!(filename.starts_with("sstable_"));
 
         // Extract timestamp and verify it's a valid number
@@ -316,18 +316,21 @@ async fn test_compaction_groups() {
                 size_bytes: 100,
                 entry_count: 10,
                 has_bloom_filter: false,
+                ..Default::default()
             },
             lsmer::sstable::SSTableInfo {
                 path: "path2".to_string(),
                 size_bytes: 200,
                 entry_count: 20,
                 has_bloom_filter: false,
+                ..Default::default()
             },
             lsmer::sstable::SSTableInfo {
                 path: "path3".to_string(),
                 size_bytes: 150,
                 entry_count: 15,
                 has_bloom_filter: false,
+                ..Default::default()
             },
         ];
 
@@ -360,30 +363,35 @@ async fn test_compaction_groups() {
                 size_bytes: 100,
                 entry_count: 10,
                 has_bloom_filter: false,
+                ..Default::default()
             },
             lsmer::sstable::SSTableInfo {
                 path: "path2".to_string(),
                 size_bytes: 110,
                 entry_count: 11,
                 has_bloom_filter: false,
+                ..Default::default()
             },
             lsmer::sstable::SSTableInfo {
                 path: "path3".to_string(),
                 size_bytes: 300,
                 entry_count: 30,
                 has_bloom_filter: false,
+                ..Default::default()
             },
             lsmer::sstable::SSTableInfo {
                 path: "path4".to_string(),
                 size_bytes: 320,
                 entry_count: 32,
                 has_bloom_filter: false,
+                ..Default::default()
             },
             lsmer::sstable::SSTableInfo {
                 path: "path5".to_string(),
                 size_bytes: 800,
                 entry_count: 80,
                 has_bloom_filter: false,
+                ..Default::default()
             },
         ];
 
@@ -447,12 +455,14 @@ async fn test_string_memtable_compact_sstables() -> io::Result<()> {
                 size_bytes: file_size1,
                 entry_count: 5,
                 has_bloom_filter: false,
+                ..Default::default()
             },
             lsmer::sstable::SSTableInfo {
                 path: sstable_path2.clone(),
                 size_bytes: file_size2,
                 entry_count: 5,
                 has_bloom_filter: false,
+                ..Default::default()
             },
         ];
 
@@ -70,9 +70,9 @@ async fn test_memtable_capacity_errors() {
             // Check if first insert succeeds or fails
             // Both outcomes are acceptable - some implementations have overhead,
             // so even the first small insert might fail on a tiny memtable
-            if first_result.is_err() {
+            if let Err(err) = first_result {
                 // If first insert fails, test that it's a capacity error
-                match first_result.unwrap_err() {
+                match err {
                     MemtableError::CapacityExceeded => {
                         // Test passed - we got the expected error type
                     }
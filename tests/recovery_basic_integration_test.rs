@@ -7,10 +7,10 @@ use tokio::time::timeout;
 // Helper function to set up test directory
 fn setup_test_dir(dir_name: &str) -> io::Result<()> {
     let test_dir = format!("target/{}", dir_name);
-    if let Err(e) = fs::create_dir_all(&test_dir) {
-        if e.kind() != ErrorKind::AlreadyExists {
-            return Err(e);
-        }
+    if let Err(e) = fs::create_dir_all(&test_dir)
+        && e.kind() != ErrorKind::AlreadyExists
+    {
+        return Err(e);
     }
     Ok(())
 }
@@ -18,10 +18,10 @@ fn setup_test_dir(dir_name: &str) -> io::Result<()> {
 // Helper function to clean test directory
 fn clean_test_dir(dir_name: &str) -> io::Result<()> {
     let test_dir = format!("target/{}", dir_name);
-    if let Err(e) = fs::remove_dir_all(&test_dir) {
-        if e.kind() != ErrorKind::NotFound {
-            return Err(e);
-        }
+    if let Err(e) = fs::remove_dir_all(&test_dir)
+        && e.kind() != ErrorKind::NotFound
+    {
+        return Err(e);
     }
     setup_test_dir(dir_name)
 }
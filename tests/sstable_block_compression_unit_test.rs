@@ -0,0 +1,174 @@
+mod helpers;
+
+use helpers::open_index_with_options;
+use lsmer::lsm_index::LsmIndexOptions;
+use lsmer::sstable::{CompressionType, SSTableReader, SSTableWriter};
+use std::fs;
+use std::io;
+use tempfile::tempdir;
+
+const CODECS: [CompressionType; 3] = [
+    CompressionType::Lz4,
+    CompressionType::Snappy,
+    CompressionType::Zstd,
+];
+
+/// Text values that repeat each other from one entry to the next
+fn text_entries(count: usize) -> Vec<(String, Vec<u8>)> {
+    (0..count)
+        .map(|i| {
+            let value = format!(
+                "Order {} shipped to warehouse {} on schedule; the customer was notified by email.",
+                i,
+                i % 7
+            );
+            (format!("order:{:05}", i), value.into_bytes())
+        })
+        .collect()
+}
+
+/// Write `entries` with blocks compressed by `compression_type`, returning
+/// the offset each entry was written at
+fn write_sstable(
+    path: &str,
+    entries: &[(String, Vec<u8>)],
+    compression_type: CompressionType,
+) -> io::Result<Vec<u64>> {
+    let mut writer =
+        SSTableWriter::new_with_options(path, entries.len(), true, 0.01, false, compression_type)?;
+    writer.set_block_size(4 * 1024)?;
+    let mut offsets = Vec::with_capacity(entries.len());
+    for (key, value) in entries {
+        offsets.push(writer.offset()?);
+        writer.write_entry(key, value)?;
+    }
+    writer.finalize()?;
+    Ok(offsets)
+}
+
+#[test]
+fn test_compressed_blocks_round_trip() -> io::Result<()> {
+    let dir = tempdir()?;
+    let entries = text_entries(2000);
+    let plain = dir.path().join("plain.sst");
+    let plain_offsets = write_sstable(plain.to_str().unwrap(), &entries, CompressionType::None)?;
+    let plain_size = fs::metadata(&plain)?.len();
+
+    for compression_type in CODECS {
+        let path = dir.path().join(format!("{:?}.sst", compression_type));
+        let path = path.to_str().unwrap();
+        let offsets = write_sstable(path, &entries, compression_type)?;
+        assert_eq!(offsets, plain_offsets);
+
+        let size = fs::metadata(path)?.len();
+        assert!(
+            size * 2 < plain_size,
+            "{:?} should halve the file: {} vs {}",
+            compression_type,
+            size,
+            plain_size
+        );
+
+        let mut reader = SSTableReader::open(path)?;
+        assert_eq!(reader.compression_type(), compression_type);
+        assert_eq!(reader.get("order:01234")?, Some(entries[1234].1.clone()));
+        assert_eq!(reader.get("order:99999")?, None);
+        assert!(reader.data_bytes().unwrap() * 2 < reader.raw_bytes().unwrap());
+
+        let scanned: Vec<_> = reader.into_entries()?.collect::<io::Result<_>>()?;
+        assert_eq!(scanned, entries);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_hash_index_and_restart_points_over_compressed_blocks() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("indexed.sst");
+    let path = path.to_str().unwrap();
+    let entries = text_entries(500);
+
+    let mut writer = SSTableWriter::new(path, entries.len(), true, 0.01)?;
+    writer.set_compression_type(CompressionType::Lz4)?;
+    writer.set_block_size(2 * 1024)?;
+    writer.set_restart_interval(8)?;
+    writer.set_hash_index()?;
+    for (key, value) in &entries {
+        writer.write_entry(key, value)?;
+    }
+    writer.finalize()?;
+
+    let mut reader = SSTableReader::open(path)?;
+    for i in [0, 7, 8, 255, 499] {
+        assert_eq!(reader.get(&entries[i].0)?, Some(entries[i].1.clone()));
+    }
+    let scanned: Vec<_> = reader.into_entries()?.collect::<io::Result<_>>()?;
+    assert_eq!(scanned, entries);
+    Ok(())
+}
+
+#[test]
+fn test_damaged_block_is_detected() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("damaged.sst");
+    let path = path.to_str().unwrap();
+    let entries = text_entries(200);
+    write_sstable(path, &entries, CompressionType::Zstd)?;
+
+    // The first block starts right after the header
+    let mut bytes = fs::read(path)?;
+    let offset = lsmer::sstable::header_size(lsmer::sstable::VERSION) + 8;
+    bytes[offset] ^= 0xFF;
+    fs::write(path, &bytes)?;
+
+    let mut reader = SSTableReader::open(path)?;
+    let err = reader.get(&entries[0].0).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    Ok(())
+}
+
+#[test]
+fn test_index_reads_compressed_sstables() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let options = || LsmIndexOptions::default().with_block_compression(CompressionType::Snappy);
+    let entries = text_entries(300);
+    {
+        let index = open_index_with_options(path, options());
+        for (key, value) in &entries[..150] {
+            index.insert(key.clone(), value.clone()).unwrap();
+        }
+        index.flush().unwrap();
+        for (key, value) in &entries[150..] {
+            index.insert(key.clone(), value.clone()).unwrap();
+        }
+        index.flush().unwrap();
+
+        assert_eq!(
+            index.get("order:00010").unwrap(),
+            Some(entries[10].1.clone())
+        );
+        let checksummed = index.get_with_checksum("order:00200").unwrap().unwrap();
+        assert_eq!(checksummed.value, entries[200].1);
+        assert!(checksummed.verified);
+    }
+
+    let mut index = open_index_with_options(path, options());
+    index.recover().unwrap();
+    assert_eq!(
+        index.get("order:00299").unwrap(),
+        Some(entries[299].1.clone())
+    );
+
+    index.compact_range(..).unwrap();
+    let scanned = index
+        .range("order:00100".to_string().."order:00103".to_string())
+        .unwrap();
+    assert_eq!(scanned, entries[100..103].to_vec());
+    let files = index.list_sstables();
+    assert!(!files.is_empty());
+    for file in files {
+        let reader = SSTableReader::open(&file.path).unwrap();
+        assert_eq!(reader.compression_type(), CompressionType::Snappy);
+    }
+}
@@ -0,0 +1,168 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::{CompactionOptions, SSTableCompaction, SSTableReader, SSTableWriter};
+use std::fs::OpenOptions;
+use std::io::{self, Seek, SeekFrom, Write};
+use tempfile::tempdir;
+
+const VALUE: [u8; 200] = [7u8; 200];
+
+/// Keys are even numbers, so odd ones fall inside blocks without being in them
+fn key(i: usize) -> String {
+    format!("key{:05}", i * 2)
+}
+
+fn absent_key(i: usize) -> String {
+    format!("key{:05}", i * 2 + 1)
+}
+
+/// Write `count` keys with values large enough to span many data blocks,
+/// giving each block a filter at `block_filter_fpr` if given
+fn write_keys(path: &str, count: usize, block_filter_fpr: Option<f64>) -> io::Result<()> {
+    write_table(
+        path,
+        count,
+        false,
+        (0..count).map(|i| (key(i), VALUE)),
+        |writer| match block_filter_fpr {
+            Some(rate) => writer.set_block_filters(rate),
+            None => Ok(()),
+        },
+    )?;
+    Ok(())
+}
+
+#[test]
+fn test_block_filters_rule_out_keys_absent_from_their_block() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_keys(path, 2000, Some(0.01))?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert!(reader.has_block_filters());
+    assert!(reader.block_index().len() > 2, "expected several blocks");
+
+    for i in 0..2000 {
+        assert!(reader.block_may_contain(&key(i)));
+    }
+    let passed = (0..2000)
+        .filter(|&i| reader.block_may_contain(&absent_key(i)))
+        .count();
+    assert!(
+        passed < 100,
+        "{} absent keys passed their block filter",
+        passed
+    );
+    // Keys outside every block are ruled out without a filter
+    assert!(!reader.block_may_contain("aaa"));
+    assert!(!reader.block_may_contain("zzz"));
+
+    assert_eq!(reader.get(&key(0))?, Some(VALUE.to_vec()));
+    assert_eq!(reader.get(&key(1999))?, Some(VALUE.to_vec()));
+    assert_eq!(reader.get(&absent_key(1000))?, None);
+    Ok(())
+}
+
+#[test]
+fn test_files_without_block_filters_rule_nothing_out() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_keys(path, 2000, None)?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert!(!reader.has_block_filters());
+    assert!(reader.block_may_contain(&absent_key(10)));
+    assert_eq!(reader.get(&absent_key(10))?, None);
+    assert_eq!(reader.get(&key(10))?, Some(VALUE.to_vec()));
+    Ok(())
+}
+
+#[test]
+fn test_lookup_skips_a_block_its_filter_rules_out() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_keys(path, 2000, Some(0.01))?;
+
+    // Corrupt the first block, so reading it fails its checksum
+    let first_block = SSTableReader::open(path)?.block_index()[0].clone();
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(first_block.offset + 20))?;
+    file.write_all(&[0xFF; 16])?;
+    drop(file);
+
+    let mut reader = SSTableReader::open(path)?;
+    let ruled_out = (0..first_block.entry_count as usize - 1)
+        .map(absent_key)
+        .find(|key| !reader.block_may_contain(key))
+        .expect("some absent key should fail its block filter");
+    assert_eq!(reader.get(&ruled_out)?, None);
+    assert!(reader.get(&key(0)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_block_filters_must_be_set_before_entries() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let mut writer = SSTableWriter::new(path.to_str().unwrap(), 10, true, 0.01)?;
+    writer.write_entry("a", b"1")?;
+    let err = writer.set_block_filters(0.01).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    Ok(())
+}
+
+#[test]
+fn test_compaction_gives_output_blocks_filters() -> io::Result<()> {
+    let dir = tempdir()?;
+    let input = dir.path().join("input.db");
+    let input = input.to_str().unwrap().to_string();
+    let output = dir.path().join("output.db");
+    let output = output.to_str().unwrap();
+    write_keys(&input, 2000, None)?;
+
+    let options = CompactionOptions::default().with_block_filters(0.01);
+    SSTableCompaction::compact_sstables_with_options(&[input], output, &options)?;
+
+    let mut reader = SSTableReader::open(output)?;
+    assert!(reader.has_block_filters());
+    assert_eq!(reader.get(&key(1500))?, Some(VALUE.to_vec()));
+    Ok(())
+}
+
+#[test]
+fn test_index_writes_block_filters_when_configured() {
+    let dir = tempdir().unwrap();
+    let index = LsmIndex::new_with_options(
+        4 * 1024 * 1024,
+        dir.path().to_str().unwrap().to_string(),
+        None,
+        true,
+        0.01,
+        LsmIndexOptions::default().with_block_filters(0.01),
+    )
+    .unwrap();
+    for i in 0..2000 {
+        index.insert(key(i), VALUE.to_vec()).unwrap();
+    }
+    index.flush().unwrap();
+
+    let path = index.list_sstables()[0].path.clone();
+    assert!(SSTableReader::open(&path).unwrap().has_block_filters());
+    assert_eq!(index.get_flushed(&key(42)).unwrap(), Some(VALUE.to_vec()));
+    assert_eq!(index.get_flushed(&absent_key(42)).unwrap(), None);
+}
+
+#[test]
+fn test_block_filter_rate_is_validated() {
+    let options = LsmIndexOptions::default().with_block_filters(1.5);
+    assert!(options.validate().is_err());
+    assert!(LsmIndexOptions::default()
+        .with_block_filters(0.01)
+        .validate()
+        .is_ok());
+}
@@ -0,0 +1,195 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions, OptionChange};
+use lsmer::sstable::{
+    properties, CompactionOptions, SSTableCompaction, SSTableReader, SSTableWriter, DATA_BLOCK_SIZE,
+};
+use std::fs::OpenOptions;
+use std::io::{self, Seek, SeekFrom, Write};
+use tempfile::tempdir;
+
+const VALUE: [u8; 100] = [3u8; 100];
+
+fn key(i: usize) -> String {
+    format!("key{:05}", i)
+}
+
+fn entries(count: usize) -> impl Iterator<Item = (String, [u8; 100])> {
+    (0..count).map(|i| (key(i), VALUE))
+}
+
+#[test]
+fn test_block_size_sets_how_many_blocks_are_written() -> io::Result<()> {
+    let dir = tempdir()?;
+    let small = dir.path().join("small.db");
+    let default = dir.path().join("default.db");
+    write_table(
+        small.to_str().unwrap(),
+        2000,
+        true,
+        entries(2000),
+        |writer| writer.set_block_size(4 * 1024),
+    )?;
+    write_table(default.to_str().unwrap(), 2000, true, entries(2000), |_| {
+        Ok(())
+    })?;
+
+    let small = SSTableReader::open(small.to_str().unwrap())?;
+    let default = SSTableReader::open(default.to_str().unwrap())?;
+    assert!(small.block_index().len() > 4 * default.block_index().len());
+    assert_eq!(small.data_block_size(), Some(4 * 1024));
+    assert_eq!(default.data_block_size(), Some(DATA_BLOCK_SIZE as u64));
+    assert_eq!(default.restart_interval(), None);
+    assert_eq!(default.index_block_size(), None);
+    Ok(())
+}
+
+#[test]
+fn test_index_block_size_partitions_by_handle_bytes() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 5000, true, entries(5000), |writer| {
+        writer.set_block_size(2 * 1024)?;
+        writer.set_index_block_size(256, 0.01)
+    })?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert!(reader.has_partitioned_index());
+    assert_eq!(reader.index_block_size(), Some(256));
+    assert_eq!(
+        reader.properties().get(properties::PROP_INDEX_BLOCK_SIZE),
+        Some("256")
+    );
+    let spans = reader.block_index();
+    assert!(spans.len() > 2);
+    assert_eq!(reader.get(&key(4321))?, Some(VALUE.to_vec()));
+    assert_eq!(reader.get("key04321x")?, None);
+    Ok(())
+}
+
+#[test]
+fn test_restart_points_narrow_lookups_within_a_block() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 2000, true, entries(2000), |writer| {
+        writer.set_restart_interval(16)
+    })?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(reader.restart_interval(), Some(16));
+    for i in (0..2000).step_by(7) {
+        assert_eq!(reader.get(&key(i))?, Some(VALUE.to_vec()));
+    }
+    assert_eq!(reader.get(&key(1999))?, Some(VALUE.to_vec()));
+    assert_eq!(reader.get("key00100x")?, None);
+
+    // Corrupt the first entry; a lookup further into the block starts at a
+    // later restart point and never reads it
+    let first_block = reader.block_index()[0].clone();
+    assert!(first_block.entry_count > 32);
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(first_block.offset + 20))?;
+    file.write_all(&[0xFF; 16])?;
+    drop(file);
+
+    let mut reader = SSTableReader::open(path)?;
+    assert!(reader.get(&key(0)).is_err());
+    assert_eq!(reader.get(&key(40))?, Some(VALUE.to_vec()));
+    Ok(())
+}
+
+#[test]
+fn test_tuning_knobs_are_validated() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let mut writer = SSTableWriter::new(path.to_str().unwrap(), 10, true, 0.01)?;
+    assert!(writer.set_block_size(0).is_err());
+    assert!(writer.set_restart_interval(0).is_err());
+    writer.write_entry("a", b"1")?;
+    assert!(writer.set_block_size(1024).is_err());
+    assert!(writer.set_restart_interval(4).is_err());
+
+    assert!(LsmIndexOptions::default()
+        .with_block_size(0)
+        .validate()
+        .is_err());
+    assert!(LsmIndexOptions::default()
+        .with_restart_interval(0)
+        .validate()
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_compaction_applies_tuning_knobs() -> io::Result<()> {
+    let dir = tempdir()?;
+    let input = dir.path().join("input.db");
+    let input = input.to_str().unwrap().to_string();
+    let output = dir.path().join("output.db");
+    let output = output.to_str().unwrap();
+    write_table(&input, 2000, true, entries(2000), |_| Ok(()))?;
+
+    let options = CompactionOptions::default()
+        .with_block_size(8 * 1024)
+        .with_index_block_size(512)
+        .with_restart_interval(8);
+    SSTableCompaction::compact_sstables_with_options(&[input], output, &options)?;
+
+    let mut reader = SSTableReader::open(output)?;
+    assert_eq!(reader.data_block_size(), Some(8 * 1024));
+    assert_eq!(reader.index_block_size(), Some(512));
+    assert_eq!(reader.restart_interval(), Some(8));
+    assert!(reader.has_partitioned_index());
+    assert_eq!(reader.get(&key(1234))?, Some(VALUE.to_vec()));
+    Ok(())
+}
+
+#[test]
+fn test_index_records_tuning_knobs_in_flushed_files() {
+    let dir = tempdir().unwrap();
+    let index = LsmIndex::new_with_options(
+        4 * 1024 * 1024,
+        dir.path().to_str().unwrap().to_string(),
+        None,
+        true,
+        0.01,
+        LsmIndexOptions::default()
+            .with_block_size(4 * 1024)
+            .with_index_block_size(1024)
+            .with_restart_interval(4),
+    )
+    .unwrap();
+    for i in 0..1000 {
+        index.insert(key(i), VALUE.to_vec()).unwrap();
+    }
+    index.flush().unwrap();
+
+    let path = index.list_sstables()[0].path.clone();
+    let reader = SSTableReader::open(&path).unwrap();
+    assert_eq!(reader.data_block_size(), Some(4 * 1024));
+    assert_eq!(reader.index_block_size(), Some(1024));
+    assert_eq!(reader.restart_interval(), Some(4));
+    assert_eq!(index.get_flushed(&key(500)).unwrap(), Some(VALUE.to_vec()));
+
+    // Retuned options apply to the next file
+    index
+        .set_options([
+            OptionChange::BlockSize(16 * 1024),
+            OptionChange::RestartInterval(None),
+        ])
+        .unwrap();
+    index.insert(key(1000), VALUE.to_vec()).unwrap();
+    index.flush().unwrap();
+    let newest = index
+        .list_sstables()
+        .into_iter()
+        .map(|info| info.path)
+        .find(|newest| *newest != path)
+        .unwrap();
+    let reader = SSTableReader::open(&newest).unwrap();
+    assert_eq!(reader.data_block_size(), Some(16 * 1024));
+    assert_eq!(reader.restart_interval(), None);
+}
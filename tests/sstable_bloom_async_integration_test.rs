@@ -159,6 +159,8 @@ async fn test_bloom_filter_false_positive_rate() {
         for i in 0..num_entries {
             test_data.push((format!("key_{}", i), vec![i as u8]));
         }
+        // Writers take keys in sorted order
+        test_data.sort();
 
         // Create an SSTable with Bloom filter
         {
@@ -230,7 +232,7 @@ async fn test_sstable_compaction_with_bloom_filter() {
         {
             let mut writer = SSTableWriter::new(&sstable1_path, 10, true, 0.01).unwrap();
             for i in 0..10 {
-                let key = format!("key_{}", i);
+                let key = format!("key_{:02}", i);
                 let value = vec![i as u8];
                 writer.write_entry(&key, &value).unwrap();
             }
@@ -241,7 +243,7 @@ async fn test_sstable_compaction_with_bloom_filter() {
         {
             let mut writer = SSTableWriter::new(&sstable2_path, 10, true, 0.01).unwrap();
             for i in 5..15 {
-                let key = format!("key_{}", i);
+                let key = format!("key_{:02}", i);
                 let value = vec![i as u8 + 100]; // Different values for same keys
                 writer.write_entry(&key, &value).unwrap();
             }
@@ -285,7 +287,7 @@ async fn test_sstable_compaction_with_bloom_filter() {
 
         // Test all keys are present with correct values
         for i in 0..15 {
-            let key = format!("key_{}", i);
+            let key = format!("key_{:02}", i);
             let expected_value = if i < 5 {
                 // Keys 0-4 only in first SSTable
                 vec![i as u8]
@@ -0,0 +1,191 @@
+use lsmer::sstable::{
+    properties, Compression, DataBlockBuilder, SSTableReader, SSTableWriter, ZstdOptions,
+};
+use std::io;
+use std::thread;
+use tempfile::tempdir;
+
+fn entries(count: usize) -> Vec<(String, Vec<u8>)> {
+    (0..count)
+        .map(|i| {
+            let value = format!(r#"{{"id":{},"status":"active"}}"#, i);
+            (format!("key{:05}", i), value.into_bytes())
+        })
+        .collect()
+}
+
+fn write_sequential(
+    path: &str,
+    entries: &[(String, Vec<u8>)],
+    compression: Compression,
+    dictionary: Option<Vec<u8>>,
+) -> io::Result<()> {
+    let mut writer = SSTableWriter::new(path, entries.len(), true, 0.01)?;
+    writer.set_compression(compression, dictionary)?;
+    for (i, (key, value)) in entries.iter().enumerate() {
+        writer.write_entry_with_metadata(key, value, Some(i as u64), None)?;
+    }
+    writer.finalize()
+}
+
+/// Build one block per chunk on its own thread, then append them in order
+fn write_parallel(
+    path: &str,
+    entries: &[(String, Vec<u8>)],
+    compression: Compression,
+    dictionary: Option<Vec<u8>>,
+    chunks: usize,
+) -> io::Result<()> {
+    let mut writer = SSTableWriter::new(path, entries.len(), true, 0.01)?;
+    writer.set_compression(compression, dictionary)?;
+
+    let chunk_len = entries.len().div_ceil(chunks);
+    let blocks = thread::scope(|scope| {
+        let handles: Vec<_> = entries
+            .chunks(chunk_len)
+            .enumerate()
+            .map(|(chunk, range)| {
+                let mut builder = writer.data_block_builder().unwrap();
+                scope.spawn(move || -> io::Result<_> {
+                    for (i, (key, value)) in range.iter().enumerate() {
+                        let written_at = (chunk * chunk_len + i) as u64;
+                        builder.add(key, value, Some(written_at), None)?;
+                    }
+                    Ok(builder.finish())
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<io::Result<Vec<_>>>()
+    })?;
+
+    for block in blocks {
+        writer.append_block(block)?;
+    }
+    writer.finalize()
+}
+
+#[test]
+fn test_parallel_blocks_match_sequential_writes() -> io::Result<()> {
+    let dir = tempdir()?;
+    let entries = entries(5000);
+    let options = ZstdOptions::default().with_dictionary(4 * 1024, 64 * 1024);
+    let dictionary = options.train_dictionary(entries.iter().map(|(_, v)| v.as_slice()));
+
+    for compression in [Compression::None, Compression::Zstd(options)] {
+        let sequential = dir.path().join("sequential.sst");
+        let parallel = dir.path().join("parallel.sst");
+        let sequential = sequential.to_str().unwrap();
+        let parallel = parallel.to_str().unwrap();
+        write_sequential(sequential, &entries, compression, dictionary.clone())?;
+        write_parallel(parallel, &entries, compression, dictionary.clone(), 4)?;
+
+        let a = SSTableReader::open(sequential)?;
+        let mut b = SSTableReader::open(parallel)?;
+        assert_eq!(a.content_digest(), b.content_digest());
+        assert_eq!(a.properties(), b.properties());
+        assert_eq!(a.write_time("key04321"), Some(4321));
+        assert_eq!(b.write_time("key04321"), Some(4321));
+        assert_eq!(b.get("key01234")?, Some(entries[1234].1.clone()));
+        assert!(b.may_contain("key04999"));
+
+        let scanned: Vec<_> = b.into_entries()?.collect::<io::Result<_>>()?;
+        assert_eq!(scanned, entries);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_blocks_interleave_with_written_entries() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("mixed.sst");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 3, true, 0.01)?;
+    writer.write_entry("a", b"1")?;
+    let mut builder = writer.data_block_builder()?;
+    builder.add("b", b"2", None, None)?;
+    writer.append_block(builder.finish())?;
+    writer.write_entry("c", b"3")?;
+    writer.finalize()?;
+
+    let reader = SSTableReader::open(path)?;
+    assert_eq!(
+        reader.properties().get(properties::PROP_KEYS_SORTED),
+        Some("true")
+    );
+    let keys: Vec<_> = reader
+        .into_entries()?
+        .map(|entry| entry.map(|(key, _)| key))
+        .collect::<io::Result<_>>()?;
+    assert_eq!(keys, vec!["a", "b", "c"]);
+    Ok(())
+}
+
+#[test]
+fn test_out_of_order_blocks_clear_sorted_flag() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("unsorted.sst");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 4, true, 0.01)?;
+    writer.allow_unsorted_keys()?;
+    let mut high = writer.data_block_builder()?;
+    high.add("m", b"1", None, None)?;
+    high.add("n", b"2", None, None)?;
+    let mut low = writer.data_block_builder()?;
+    low.add("a", b"3", None, None)?;
+    writer.append_block(high.finish())?;
+    writer.append_block(low.finish())?;
+    writer.finalize()?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(
+        reader.properties().get(properties::PROP_KEYS_SORTED),
+        Some("false")
+    );
+    assert_eq!(reader.get("a")?, Some(b"3".to_vec()));
+    Ok(())
+}
+
+#[test]
+fn test_block_with_other_compression_is_rejected() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("mismatch.sst");
+    let mut writer = SSTableWriter::new(path.to_str().unwrap(), 1, false, 0.01)?;
+    writer.set_compression(Compression::zstd(), None)?;
+
+    let mut plain = DataBlockBuilder::new();
+    plain.add("key", b"value", None, None)?;
+    let err = writer.append_block(plain.finish()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let mut other_dict = DataBlockBuilder::with_compression(Compression::zstd(), Some(b"dict"))?;
+    other_dict.add("key", b"value", None, None)?;
+    assert!(writer.append_block(other_dict.finish()).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_block_builder_reports_contents() -> io::Result<()> {
+    let mut builder = DataBlockBuilder::new();
+    builder.add("a", b"12345", None, None)?;
+    builder.add("b", b"6", None, None)?;
+    assert_eq!(builder.entry_count(), 2);
+    // Each entry adds two length prefixes and a checksum
+    assert_eq!(builder.encoded_len(), 8 + 2 * 12);
+
+    let block = builder.finish();
+    assert_eq!(block.entry_count(), 2);
+    assert_eq!(block.first_key(), Some("a"));
+    assert_eq!(block.last_key(), Some("b"));
+    assert!(block.keys_sorted());
+    assert_eq!(builder.entry_count(), 0);
+
+    assert!(DataBlockBuilder::new()
+        .add(&"k".repeat(2 * 1024 * 1024), b"", None, None)
+        .is_err());
+    Ok(())
+}
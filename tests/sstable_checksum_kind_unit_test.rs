@@ -0,0 +1,154 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::{ChecksumKind, SSTableReader, SSTableWriter, entry_checksum};
+use std::fs;
+use tempfile::tempdir;
+
+const KINDS: [ChecksumKind; 5] = [
+    ChecksumKind::Crc32,
+    ChecksumKind::Crc32c,
+    ChecksumKind::Xxh3,
+    ChecksumKind::XxHash64,
+    ChecksumKind::None,
+];
+
+/// Kinds that store a real checksum
+const VERIFYING_KINDS: [ChecksumKind; 4] = [
+    ChecksumKind::Crc32,
+    ChecksumKind::Crc32c,
+    ChecksumKind::Xxh3,
+    ChecksumKind::XxHash64,
+];
+
+/// Flip a byte of the value of the entry at `offset` in a table from
+/// `write_checksummed`
+fn damage_value(path: &str, offset: u64) {
+    let mut bytes = fs::read(path).unwrap();
+    bytes[offset as usize + 4 + 5 + 4 + 1] ^= 0xff;
+    fs::write(path, bytes).unwrap();
+}
+
+/// Write 50 entries checksummed with `kind`, returning the offset of each
+fn write_checksummed(path: &str, kind: ChecksumKind) -> Vec<u64> {
+    let entries = (0..50).map(|i| (format!("key{:02}", i), format!("value{}", i)));
+    write_table(path, 50, true, entries, |writer| {
+        writer.set_checksum_kind(kind)
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_each_kind_is_recorded_and_verified() {
+    let dir = tempdir().unwrap();
+    for kind in KINDS {
+        let path = dir.path().join(format!("{:?}.sst", kind));
+        let path = path.to_str().unwrap();
+        write_checksummed(path, kind);
+
+        let mut reader = SSTableReader::open(path).unwrap();
+        assert_eq!(reader.checksum_kind(), kind);
+        assert_eq!(reader.get("key07").unwrap(), Some(b"value7".to_vec()));
+        let report = reader.verify_all().unwrap();
+        assert!(report.is_intact(), "{:?}: {:?}", kind, report);
+        assert_eq!(report.entries_checked, 50);
+    }
+}
+
+#[test]
+fn test_damage_is_detected_with_each_kind() {
+    let dir = tempdir().unwrap();
+    for kind in VERIFYING_KINDS {
+        let path = dir.path().join(format!("{:?}.sst", kind));
+        let path = path.to_str().unwrap();
+        let offsets = write_checksummed(path, kind);
+        damage_value(path, offsets[20]);
+
+        let report = SSTableReader::open(path).unwrap().verify_all().unwrap();
+        assert_eq!(report.first_corrupt_offset, Some(offsets[20]), "{:?}", kind);
+    }
+}
+
+#[test]
+fn test_no_checksum_reads_back_damage_unnoticed() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("unchecked.sst");
+    let path = path.to_str().unwrap();
+    let offsets = write_checksummed(path, ChecksumKind::None);
+    damage_value(path, offsets[20]);
+
+    let mut reader = SSTableReader::open(path).unwrap();
+    assert_eq!(reader.checksum_kind(), ChecksumKind::None);
+    let report = reader.verify_all().unwrap();
+    assert!(report.is_intact(), "{:?}", report);
+    assert_ne!(reader.get("key20").unwrap(), Some(b"value20".to_vec()));
+    assert_eq!(reader.get("key21").unwrap(), Some(b"value21".to_vec()));
+}
+
+#[test]
+fn test_kinds_compute_different_checksums() {
+    let crc32 = ChecksumKind::Crc32.entry_checksum("key", b"value");
+    assert_eq!(crc32, entry_checksum("key", b"value"));
+    assert_ne!(ChecksumKind::Crc32c.entry_checksum("key", b"value"), crc32);
+    assert_ne!(ChecksumKind::Xxh3.entry_checksum("key", b"value"), crc32);
+    assert_ne!(ChecksumKind::XxHash64.entry_checksum("key", b"value"), crc32);
+    assert_eq!(ChecksumKind::None.entry_checksum("key", b"value"), 0);
+
+    for kind in KINDS {
+        assert_eq!(ChecksumKind::from_id(kind.id()).unwrap(), kind);
+    }
+    assert!(ChecksumKind::from_id(200).is_err());
+}
+
+#[test]
+fn test_kind_must_be_set_before_entries_and_match_appended_blocks() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("table.sst");
+    let mut writer = SSTableWriter::new(path.to_str().unwrap(), 10, false, 0.0).unwrap();
+    writer.set_checksum_kind(ChecksumKind::Xxh3).unwrap();
+
+    // Builders made by the writer share its kind
+    let mut builder = writer.data_block_builder().unwrap();
+    builder.add("a", b"1", None, None).unwrap();
+    writer.append_block(builder.finish()).unwrap();
+    assert!(writer.set_checksum_kind(ChecksumKind::Crc32).is_err());
+
+    let mut builder = writer.data_block_builder().unwrap();
+    builder.set_checksum_kind(ChecksumKind::Crc32c);
+    builder.add("b", b"2", None, None).unwrap();
+    assert!(writer.append_block(builder.finish()).is_err());
+}
+
+#[test]
+fn test_index_flushes_with_its_checksum_kind() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap().to_string();
+    let options = || LsmIndexOptions::default().with_checksum_kind(ChecksumKind::Crc32c);
+
+    {
+        let index =
+            LsmIndex::new_with_options(1024 * 1024, path.clone(), None, true, 0.01, options())
+                .unwrap();
+        index
+            .insert("alpha".to_string(), b"first".to_vec())
+            .unwrap();
+        index.flush().unwrap();
+    }
+
+    let mut index =
+        LsmIndex::new_with_options(1024 * 1024, path, None, true, 0.01, options()).unwrap();
+    index.recover().unwrap();
+    let sstables = index.list_sstables();
+    assert_eq!(sstables.len(), 1);
+    let reader = SSTableReader::open(&sstables[0].path).unwrap();
+    assert_eq!(reader.checksum_kind(), ChecksumKind::Crc32c);
+
+    let value = index.get_with_checksum("alpha").unwrap().unwrap();
+    assert_eq!(value.value, b"first".to_vec());
+    assert!(value.verified);
+    assert_eq!(
+        value.checksum,
+        ChecksumKind::Crc32c.entry_checksum("alpha", b"first")
+    );
+}
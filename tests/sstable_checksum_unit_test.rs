@@ -142,13 +142,13 @@ async fn test_key_length_validation() {
         println!("File size: {} bytes", file_size);
 
         // Now modify the key length at the exact offset where first key would be
-        // SSTable header size is defined in src/sstable/mod.rs as HEADER_SIZE
+        // SSTable header size is given by src/sstable/mod.rs's header_size
         let mut data = fs::read(&path).unwrap();
 
         // The header is followed immediately by entries
         // Each entry has: key_len (4 bytes) + key + value_len (4 bytes) + value + checksum (4 bytes)
         // So we need to modify the first 4 bytes after the header
-        let header_size = 49; // Based on constants in src/sstable/mod.rs
+        let header_size = lsmer::sstable::header_size(lsmer::sstable::VERSION);
 
         if data.len() >= header_size + 4 {
             // Corrupt the key length field - set it to a impossibly large value
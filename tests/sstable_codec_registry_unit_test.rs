@@ -0,0 +1,225 @@
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::codec::{Lz4Codec, SnappyCodec};
+use lsmer::sstable::{
+    Codec, CodecRegistry, CompactionOptions, Compression, SSTableCompaction, SSTableReader,
+    SSTableWriter,
+};
+use std::fs;
+use std::io;
+use tempfile::tempdir;
+
+/// Stores each value reversed, as a stand-in for a user's own codec
+#[derive(Debug)]
+struct ReverseCodec(&'static str);
+
+impl Codec for ReverseCodec {
+    fn name(&self) -> &str {
+        self.0
+    }
+
+    fn compress(&self, value: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(value.iter().rev().copied().collect())
+    }
+
+    fn decompress(&self, stored: &[u8], _max_len: usize) -> io::Result<Vec<u8>> {
+        Ok(stored.iter().rev().copied().collect())
+    }
+}
+
+fn entries(count: usize) -> Vec<(String, Vec<u8>)> {
+    (0..count)
+        .map(|i| {
+            let value = format!("value-{:05}-{}", i, "abcdefgh".repeat(8));
+            (format!("key:{:05}", i), value.into_bytes())
+        })
+        .collect()
+}
+
+fn write_sstable(
+    path: &str,
+    entries: &[(String, Vec<u8>)],
+    compression: Compression,
+) -> io::Result<()> {
+    let mut writer = SSTableWriter::new(path, entries.len(), true, 0.01)?;
+    writer.set_compression(compression, None)?;
+    for (key, value) in entries {
+        writer.write_entry(key, value)?;
+    }
+    writer.finalize()
+}
+
+fn assert_round_trip(compression: Compression, name: &str) -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("codec.sst");
+    let path = path.to_str().unwrap();
+    let entries = entries(200);
+
+    write_sstable(path, &entries, compression)?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(reader.compression_name(), Some(name));
+    assert!(reader.value_decoder().is_compressed());
+    assert_eq!(reader.get("key:00042")?, Some(entries[42].1.clone()));
+    assert_eq!(reader.get("key:99999")?, None);
+
+    let scanned: Vec<_> = reader.into_entries()?.collect::<io::Result<_>>()?;
+    assert_eq!(scanned, entries);
+    Ok(())
+}
+
+#[test]
+fn test_builtin_codecs_round_trip() -> io::Result<()> {
+    assert_round_trip(Compression::Lz4, "lz4")?;
+    assert_round_trip(Compression::Snappy, "snappy")?;
+    assert_round_trip(Compression::zstd(), "zstd")
+}
+
+#[test]
+fn test_builtin_codecs_shrink_repetitive_values() -> io::Result<()> {
+    let dir = tempdir()?;
+    let entries = entries(500);
+    let plain = dir.path().join("plain.sst");
+    write_sstable(plain.to_str().unwrap(), &entries, Compression::None)?;
+    let plain_size = fs::metadata(&plain)?.len();
+
+    for (compression, file) in [
+        (Compression::Lz4, "lz4.sst"),
+        (Compression::Snappy, "snappy.sst"),
+    ] {
+        let path = dir.path().join(file);
+        write_sstable(path.to_str().unwrap(), &entries, compression)?;
+        assert!(fs::metadata(&path)?.len() < plain_size);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_global_registry_holds_compiled_in_codecs() {
+    let names = CodecRegistry::global().names();
+    assert!(names.contains(&"lz4".to_string()));
+    assert!(names.contains(&"snappy".to_string()));
+    assert!(CodecRegistry::new().names().is_empty());
+}
+
+#[test]
+fn test_custom_codec_round_trip() -> io::Result<()> {
+    CodecRegistry::global().register(ReverseCodec("test-reverse"));
+    assert_round_trip(Compression::Custom("test-reverse"), "test-reverse")
+}
+
+#[test]
+fn test_unregistered_codec_is_unsupported_not_corrupt() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("custom.sst");
+    let path = path.to_str().unwrap();
+
+    CodecRegistry::global().register(ReverseCodec("test-removed"));
+    write_sstable(path, &entries(10), Compression::Custom("test-removed"))?;
+    assert!(CodecRegistry::global().remove("test-removed").is_some());
+
+    let err = SSTableReader::open(path).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    assert!(err.to_string().contains("'test-removed' is not registered"));
+
+    // Writers refuse the codec up front too
+    let other = dir.path().join("other.sst");
+    let err = write_sstable(
+        other.to_str().unwrap(),
+        &entries(1),
+        Compression::Custom("test-removed"),
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    Ok(())
+}
+
+#[test]
+fn test_registry_lookup() {
+    let registry = CodecRegistry::new();
+    let err = registry.get("lz4").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+
+    assert!(registry.register(Lz4Codec).is_none());
+    assert!(registry.register(Lz4Codec).is_some());
+    registry.register(SnappyCodec);
+    assert_eq!(
+        registry.names(),
+        vec!["lz4".to_string(), "snappy".to_string()]
+    );
+    assert_eq!(registry.get("lz4").unwrap().name(), "lz4");
+}
+
+#[test]
+fn test_damaged_values_are_invalid_data() {
+    for codec in [
+        Box::new(Lz4Codec) as Box<dyn Codec>,
+        Box::new(SnappyCodec) as Box<dyn Codec>,
+    ] {
+        let stored = codec.compress(&b"hello world ".repeat(20)).unwrap();
+
+        // A length beyond the limit is refused before decompressing
+        let err = codec.decompress(&stored, 10).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", codec.name());
+
+        let err = codec
+            .decompress(&stored[..stored.len() / 2], 1024)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", codec.name());
+    }
+}
+
+#[test]
+fn test_compaction_output_uses_codec() -> io::Result<()> {
+    let dir = tempdir()?;
+    let input = dir.path().join("input.sst");
+    let output = dir.path().join("output.sst");
+    let entries = entries(100);
+    write_sstable(input.to_str().unwrap(), &entries, Compression::Snappy)?;
+
+    SSTableCompaction::compact_sstables_with_options(
+        &[input.to_str().unwrap().to_string()],
+        output.to_str().unwrap(),
+        &CompactionOptions::default().with_compression(Compression::Lz4),
+    )?;
+
+    let reader = SSTableReader::open(output.to_str().unwrap())?;
+    assert_eq!(reader.compression_name(), Some("lz4"));
+    let scanned: Vec<_> = reader.into_entries()?.collect::<io::Result<_>>()?;
+    assert_eq!(scanned, entries);
+    Ok(())
+}
+
+#[test]
+fn test_index_flushes_and_recovers_with_codec() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap().to_string();
+    let options = LsmIndexOptions::default().with_compression(Compression::Lz4);
+    let entries = entries(100);
+
+    {
+        let index = LsmIndex::new_with_options(
+            4 * 1024 * 1024,
+            path.clone(),
+            None,
+            true,
+            0.01,
+            options.clone(),
+        )
+        .unwrap();
+        for (key, value) in &entries {
+            index.insert(key.clone(), value.clone()).unwrap();
+        }
+        index.flush().unwrap();
+        assert_eq!(
+            index.get_flushed("key:00007").unwrap(),
+            Some(entries[7].1.clone())
+        );
+    }
+
+    let mut index =
+        LsmIndex::new_with_options(4 * 1024 * 1024, path, None, true, 0.01, options).unwrap();
+    index.recover().unwrap();
+    for (key, value) in entries.iter().step_by(10) {
+        assert_eq!(index.get_flushed(key).unwrap(), Some(value.clone()));
+    }
+}
@@ -0,0 +1,99 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::sstable::compaction_report::report_path;
+use lsmer::sstable::{CompactionOptions, SSTableCompaction, Tombstone};
+use std::fs;
+use std::io;
+use std::path::Path;
+use tempfile::tempdir;
+
+fn write_input(path: &Path, entries: &[(&str, &[u8])], tombstones: &[&str]) -> io::Result<String> {
+    let path = path.to_str().unwrap().to_string();
+    write_table(&path, entries.len(), true, entries, |writer| {
+        for key in tombstones {
+            writer.write_tombstone(
+                key,
+                Tombstone {
+                    deleted_at_ms: 1,
+                    value: None,
+                },
+            );
+        }
+        Ok(())
+    })?;
+    Ok(path)
+}
+
+#[test]
+fn test_debug_dump_records_every_decision() -> io::Result<()> {
+    let dir = tempdir()?;
+    let older = write_input(
+        &dir.path().join("older.db"),
+        &[("a", b"1"), ("b", b"1"), ("c", b"1")],
+        &[],
+    )?;
+    let newer = write_input(&dir.path().join("newer.db"), &[("b", b"2")], &["c"])?;
+    let output = dir.path().join("out.db");
+    let output = output.to_str().unwrap();
+
+    SSTableCompaction::compact_sstables_with_options(
+        &[older.clone(), newer.clone()],
+        output,
+        &CompactionOptions::default().with_debug_dump(true),
+    )?;
+
+    let report = fs::read_to_string(report_path(output))?;
+    assert!(report.contains("\"status\": \"ok\""));
+    assert!(report.contains("\"error\": null"));
+    assert!(report.contains("\"merge_strategy\": \"sorted\""));
+    assert!(report.contains("\"all_decisions_recorded\": true"));
+    assert!(report.contains(&format!("\"path\": \"{}\"", older)));
+    assert!(report.contains("\"first_key\": \"a\", \"last_key\": \"c\""));
+    assert!(report.contains(
+        "\"decision_counts\": {\"kept\": 2, \"shadowed\": 1, \"deleted_later\": 1, \
+         \"expired\": 0, \"tombstone_carried\": 1, \"tombstone_dropped\": 0}"
+    ));
+    assert!(report.contains("{\"key\": \"b\", \"input\": 0, \"decision\": \"shadowed\"}"));
+    assert!(report.contains("{\"key\": \"c\", \"input\": 0, \"decision\": \"deleted_later\"}"));
+    Ok(())
+}
+
+#[test]
+fn test_successful_compactions_write_no_report_by_default() -> io::Result<()> {
+    let dir = tempdir()?;
+    let input = write_input(&dir.path().join("in.db"), &[("a", b"1")], &[])?;
+    let output = dir.path().join("out.db");
+    let output = output.to_str().unwrap();
+
+    SSTableCompaction::compact_sstables_with_options(
+        &[input],
+        output,
+        &CompactionOptions::default(),
+    )?;
+    assert!(!Path::new(&report_path(output)).exists());
+    Ok(())
+}
+
+#[test]
+fn test_failed_compactions_write_a_report() -> io::Result<()> {
+    let dir = tempdir()?;
+    let input = write_input(&dir.path().join("in.db"), &[("a", b"1")], &[])?;
+    let missing = dir.path().join("missing.db").to_str().unwrap().to_string();
+    let output = dir.path().join("out.db");
+    let output = output.to_str().unwrap();
+
+    let result = SSTableCompaction::compact_sstables_with_options(
+        &[input, missing.clone()],
+        output,
+        &CompactionOptions::default(),
+    );
+    assert!(result.is_err());
+
+    let report = fs::read_to_string(report_path(output))?;
+    assert!(report.contains("\"status\": \"failed\""));
+    assert!(!report.contains("\"error\": null"));
+    assert!(report.contains("\"all_decisions_recorded\": false"));
+    assert!(report.contains(&format!("\"path\": \"{}\", \"opened\": false", missing)));
+    Ok(())
+}
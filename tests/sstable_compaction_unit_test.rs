@@ -0,0 +1,219 @@
+use lsmer::sstable::{
+    CompactionOptions, CompressionType, SSTableCompaction, SSTableReader, SSTableWriter,
+};
+use std::io;
+use tempfile::tempdir;
+
+fn write_sstable(
+    path: &str,
+    entries: &[(String, Vec<u8>)],
+    expected_entries: usize,
+    use_partitioned_bloom: bool,
+) -> io::Result<()> {
+    let mut writer = SSTableWriter::new_with_options(
+        path,
+        expected_entries,
+        true,
+        0.01,
+        use_partitioned_bloom,
+        CompressionType::None,
+    )?;
+    // Some tests merge deliberately unsorted inputs
+    writer.allow_unsorted_keys()?;
+    for (key, value) in entries {
+        writer.write_entry(key, value)?;
+    }
+    writer.finalize()
+}
+
+fn keyed(range: std::ops::Range<u32>, tag: u8) -> Vec<(String, Vec<u8>)> {
+    range
+        .map(|i| (format!("key{:04}", i), vec![tag, i as u8]))
+        .collect()
+}
+
+fn read_all(path: &str) -> io::Result<Vec<(String, Vec<u8>)>> {
+    SSTableReader::open(path)?.into_entries()?.collect()
+}
+
+#[test]
+fn test_writer_records_key_order() -> io::Result<()> {
+    let dir = tempdir()?;
+    let sorted = dir.path().join("sorted.sst");
+    let unsorted = dir.path().join("unsorted.sst");
+
+    write_sstable(sorted.to_str().unwrap(), &keyed(0..10, 1), 10, false)?;
+    let mut entries = keyed(0..10, 1);
+    entries.swap(2, 7);
+    write_sstable(unsorted.to_str().unwrap(), &entries, 10, false)?;
+
+    assert!(SSTableReader::open(sorted.to_str().unwrap())?.keys_sorted());
+    assert!(!SSTableReader::open(unsorted.to_str().unwrap())?.keys_sorted());
+    Ok(())
+}
+
+#[test]
+fn test_streaming_merge_later_input_wins() -> io::Result<()> {
+    let dir = tempdir()?;
+    let a = dir.path().join("a.sst").to_str().unwrap().to_string();
+    let b = dir.path().join("b.sst").to_str().unwrap().to_string();
+    let c = dir.path().join("c.sst").to_str().unwrap().to_string();
+    let out = dir.path().join("out.sst").to_str().unwrap().to_string();
+
+    write_sstable(&a, &keyed(0..100, 1), 100, false)?;
+    write_sstable(&b, &keyed(50..150, 2), 100, false)?;
+    write_sstable(&c, &keyed(90..110, 3), 100, false)?;
+
+    SSTableCompaction::compact_sstables_with_options(
+        &[a, b, c],
+        &out,
+        &CompactionOptions::default(),
+    )?;
+
+    let entries = read_all(&out)?;
+    assert_eq!(entries.len(), 150);
+    assert!(entries.windows(2).all(|w| w[0].0 < w[1].0));
+    for (key, value) in &entries {
+        let i: u32 = key[3..].parse().unwrap();
+        let expected_tag = if (90..110).contains(&i) {
+            3
+        } else if i >= 50 {
+            2
+        } else {
+            1
+        };
+        assert_eq!(value[0], expected_tag, "wrong winner for {}", key);
+    }
+
+    let mut reader = SSTableReader::open(&out)?;
+    assert!(reader.keys_sorted());
+    assert!(reader.bloom_filter().is_some());
+    assert_eq!(reader.get("key0100")?, Some(vec![3, 100]));
+    Ok(())
+}
+
+#[test]
+fn test_compatible_input_filters_are_merged() -> io::Result<()> {
+    let dir = tempdir()?;
+    let a = dir.path().join("a.sst").to_str().unwrap().to_string();
+    let b = dir.path().join("b.sst").to_str().unwrap().to_string();
+    let out = dir.path().join("out.sst").to_str().unwrap().to_string();
+
+    // Both filters are sized for far more keys than they hold, so their
+    // union still meets the target rate for the combined output
+    write_sstable(&a, &keyed(0..50, 1), 1000, false)?;
+    write_sstable(&b, &keyed(50..100, 2), 1000, false)?;
+
+    let input_a = SSTableReader::open(&a)?;
+    let input_b = SSTableReader::open(&b)?;
+    let mut expected = input_a.bloom_filter().unwrap().clone();
+    expected.merge(input_b.bloom_filter().unwrap()).unwrap();
+
+    SSTableCompaction::compact_sstables_with_options(&[a, b], &out, &CompactionOptions::default())?;
+
+    let reader = SSTableReader::open(&out)?;
+    let merged = reader.bloom_filter().unwrap();
+    assert_eq!(merged.size_bits(), expected.size_bits());
+    assert_eq!(merged.get_bits(), expected.get_bits());
+    for i in 0..100 {
+        assert!(reader.may_contain(&format!("key{:04}", i)));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_overfull_input_filters_are_rebuilt() -> io::Result<()> {
+    let dir = tempdir()?;
+    let a = dir.path().join("a.sst").to_str().unwrap().to_string();
+    let b = dir.path().join("b.sst").to_str().unwrap().to_string();
+    let out = dir.path().join("out.sst").to_str().unwrap().to_string();
+
+    write_sstable(&a, &keyed(0..500, 1), 500, false)?;
+    write_sstable(&b, &keyed(500..1000, 2), 500, false)?;
+    let input_bits = SSTableReader::open(&a)?.bloom_filter().unwrap().size_bits();
+
+    SSTableCompaction::compact_sstables_with_options(&[a, b], &out, &CompactionOptions::default())?;
+
+    let reader = SSTableReader::open(&out)?;
+    let rebuilt = reader.bloom_filter().unwrap();
+    assert!(rebuilt.size_bits() > input_bits);
+    for i in 0..1000 {
+        assert!(reader.may_contain(&format!("key{:04}", i)));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_partitioned_choice_carried_through() -> io::Result<()> {
+    let dir = tempdir()?;
+    let a = dir.path().join("a.sst").to_str().unwrap().to_string();
+    let b = dir.path().join("b.sst").to_str().unwrap().to_string();
+    let standard_out = dir
+        .path()
+        .join("standard.sst")
+        .to_str()
+        .unwrap()
+        .to_string();
+    let partitioned_out = dir
+        .path()
+        .join("partitioned.sst")
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    write_sstable(&a, &keyed(0..200, 1), 200, true)?;
+    write_sstable(&b, &keyed(100..300, 2), 200, false)?;
+
+    SSTableCompaction::compact_sstables_with_options(
+        &[a.clone(), b.clone()],
+        &partitioned_out,
+        &CompactionOptions::default().with_partitioned_bloom(true),
+    )?;
+    SSTableCompaction::compact_sstables_with_options(
+        &[a, b],
+        &standard_out,
+        &CompactionOptions::default().with_partitioned_bloom(false),
+    )?;
+
+    let partitioned = SSTableReader::open(&partitioned_out)?;
+    assert!(partitioned.partitioned_bloom_filter().is_some());
+    assert!(partitioned.bloom_filter().is_none());
+
+    let standard = SSTableReader::open(&standard_out)?;
+    assert!(standard.bloom_filter().is_some());
+    assert!(standard.partitioned_bloom_filter().is_none());
+
+    for i in 0..300 {
+        let key = format!("key{:04}", i);
+        assert!(partitioned.may_contain(&key));
+        assert!(standard.may_contain(&key));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_unsorted_inputs_are_buffered() -> io::Result<()> {
+    let dir = tempdir()?;
+    let a = dir.path().join("a.sst").to_str().unwrap().to_string();
+    let b = dir.path().join("b.sst").to_str().unwrap().to_string();
+    let out = dir.path().join("out.sst").to_str().unwrap().to_string();
+
+    let mut entries = keyed(0..20, 1);
+    entries.reverse();
+    write_sstable(&a, &entries, 20, false)?;
+    write_sstable(&b, &keyed(10..30, 2), 20, false)?;
+
+    SSTableCompaction::compact_sstables_with_options(
+        &[a, b],
+        &out,
+        &CompactionOptions::default().with_bloom_filter(false),
+    )?;
+
+    let entries = read_all(&out)?;
+    assert_eq!(entries.len(), 30);
+    assert!(entries.windows(2).all(|w| w[0].0 < w[1].0));
+    assert_eq!(entries[5].1, vec![1, 5]);
+    assert_eq!(entries[15].1, vec![2, 15]);
+    assert!(!SSTableReader::open(&out)?.has_bloom_filter());
+    Ok(())
+}
@@ -1,6 +1,9 @@
-use lsmer::sstable::{SSTableCompaction, SSTableInfo, SSTableReader, SSTableWriter, VERSION};
-use std::fs::{File, OpenOptions};
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use lsmer::sstable::{
+    CHECKSUM_KIND_VERSION, FOOTER_SIZE, FOOTER_VERSION, Footer, SSTableCompaction, SSTableInfo, SSTableReader,
+    SSTableWriter, VERSION, header_size,
+};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
 use std::path::Path;
 use std::time::Duration;
 use tempfile::tempdir;
@@ -71,22 +74,48 @@ async fn create_custom_version_sstable(path: &str, version: u32) -> io::Result<(
         writer.write_entry("testkey", &[1, 2, 3])?;
         writer.finalize()?;
     }
+    let bytes = fs::read(path)?;
+    let data = &bytes[header_size(VERSION)..bytes.len() - FOOTER_SIZE];
+
+    // Files with a footer keep it, under a header with the new version and
+    // neither a cipher nor a compression type
+    let mut header = bytes[..8].to_vec();
+    header.extend_from_slice(&version.to_le_bytes());
+    if version >= FOOTER_VERSION {
+        header.extend_from_slice(&[0, 0]);
+        if version >= CHECKSUM_KIND_VERSION {
+            header.push(bytes[14]);
+        }
+        let checksum = crc32fast::hash(&header);
+        header.extend_from_slice(&checksum.to_le_bytes());
+        header.extend_from_slice(&bytes[header_size(VERSION)..]);
+        return fs::write(path, header);
+    }
 
-    // Now modify the version field
-    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
-    file.seek(SeekFrom::Start(8))?; // Version is at offset 8
-    file.write_all(&version.to_le_bytes())?;
-
-    // Recalculate header checksum
-    let mut header_data = vec![0u8; 8 + 4 + 8 + 8 + 8 + 8 + 1]; // Size of header without checksum
-    file.seek(SeekFrom::Start(0))?;
-    file.read_exact(&mut header_data)?;
-
-    let checksum = crc32fast::hash(&header_data);
-    file.write_all(&checksum.to_le_bytes())?;
-
-    file.flush()?;
-    Ok(())
+    // Older versions keep the footer's fields in the header, with offsets
+    // moved by the change in header size
+    let footer = Footer::decode(&bytes[bytes.len() - FOOTER_SIZE..])?;
+    let shift = |offset: u64| offset + header_size(version) as u64 - header_size(VERSION) as u64;
+    header.extend_from_slice(&footer.entry_count.to_le_bytes());
+    header.extend_from_slice(&shift(footer.index_offset).to_le_bytes());
+    if version >= 3 {
+        header.extend_from_slice(&shift(footer.bloom_offset).to_le_bytes());
+        header.extend_from_slice(&footer.bloom_size.to_le_bytes());
+        header.push(footer.has_bloom_filter as u8);
+        if version >= 5 {
+            header.extend_from_slice(&footer.file_number.to_le_bytes());
+        }
+        if version >= 6 {
+            header.push(0);
+        }
+        if version >= 7 {
+            header.push(0);
+        }
+        let checksum = crc32fast::hash(&header);
+        header.extend_from_slice(&checksum.to_le_bytes());
+    }
+    header.extend_from_slice(data);
+    fs::write(path, header)
 }
 
 #[tokio::test]
@@ -99,30 +128,35 @@ async fn test_compaction_group_identification() {
                 size_bytes: 100,
                 entry_count: 10,
                 has_bloom_filter: false,
+                ..Default::default()
             },
             SSTableInfo {
                 path: "path2.sst".to_string(),
                 size_bytes: 110,
                 entry_count: 11,
                 has_bloom_filter: false,
+                ..Default::default()
             },
             SSTableInfo {
                 path: "path3.sst".to_string(),
                 size_bytes: 200,
                 entry_count: 20,
                 has_bloom_filter: false,
+                ..Default::default()
             },
             SSTableInfo {
                 path: "path4.sst".to_string(),
                 size_bytes: 1000,
                 entry_count: 100,
                 has_bloom_filter: false,
+                ..Default::default()
             },
             SSTableInfo {
                 path: "path5.sst".to_string(),
                 size_bytes: 1100,
                 entry_count: 110,
                 has_bloom_filter: false,
+                ..Default::default()
             },
         ];
 
@@ -212,7 +246,7 @@ async fn test_compaction_edge_cases() {
 
         // Compact a single SSTable
         let result = SSTableCompaction::compact_sstables(
-            &[single_sstable.clone()],
+            std::slice::from_ref(&single_sstable),
             &single_output,
             false,
             false,
@@ -345,9 +379,8 @@ async fn test_corrupted_sstable_handling() {
 
             // In most cases, opening should fail, but we're testing graceful handling
             // rather than specific error types
-            if result.is_ok() {
+            if let Ok(mut reader) = result {
                 // If it somehow opens, try to read from it to ensure it's handled safely
-                let mut reader = result.unwrap();
                 let _ = reader.get("key0"); // This might fail but shouldn't panic
             }
         }
@@ -431,6 +464,8 @@ async fn test_larger_data_handling() {
 
         // Create moderately large keys and values (not too large to cause memory issues)
         let mut writer = SSTableWriter::new(&large_path, 10, false, 0.0)?;
+        // Keys of varying length are not written in sorted order
+        writer.allow_unsorted_keys()?;
 
         // Use a larger but reasonable number of entries
         for i in 0..100 {
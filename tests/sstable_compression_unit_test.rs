@@ -0,0 +1,245 @@
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::{
+    looks_incompressible, CompactionOptions, Compression, SSTableCompaction, SSTableReader,
+    SSTableWriter, ZstdOptions,
+};
+use std::fs;
+use std::io;
+use tempfile::tempdir;
+
+/// Many small JSON-like values that share most of their bytes
+fn similar_values(count: usize) -> Vec<(String, Vec<u8>)> {
+    (0..count)
+        .map(|i| {
+            let value = format!(
+                r#"{{"user_id":{},"status":"active","region":"eu-west-{}","plan":"standard"}}"#,
+                i,
+                i % 3
+            );
+            (format!("user:{:05}", i), value.into_bytes())
+        })
+        .collect()
+}
+
+fn write_sstable(
+    path: &str,
+    entries: &[(String, Vec<u8>)],
+    compression: Compression,
+    dictionary: Option<Vec<u8>>,
+) -> io::Result<()> {
+    let mut writer = SSTableWriter::new(path, entries.len(), true, 0.01)?;
+    writer.set_compression(compression, dictionary)?;
+    for (key, value) in entries {
+        writer.write_entry(key, value)?;
+    }
+    writer.finalize()
+}
+
+/// Bytes from a xorshift generator, which no codec can shrink
+fn noise(len: usize, mut state: u64) -> Vec<u8> {
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn dictionary_options() -> ZstdOptions {
+    ZstdOptions::default().with_dictionary(4 * 1024, 64 * 1024)
+}
+
+#[test]
+fn test_compressed_values_round_trip() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("zstd.sst");
+    let path = path.to_str().unwrap();
+    let entries = similar_values(200);
+
+    write_sstable(path, &entries, Compression::zstd(), None)?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(reader.compression_name(), Some("zstd"));
+    assert!(reader.value_decoder().is_compressed());
+    assert_eq!(reader.get("user:00042")?, Some(entries[42].1.clone()));
+    assert_eq!(reader.get("user:99999")?, None);
+
+    let scanned: Vec<_> = reader.into_entries()?.collect::<io::Result<_>>()?;
+    assert_eq!(scanned, entries);
+    Ok(())
+}
+
+#[test]
+fn test_dictionary_shrinks_tiny_similar_values() -> io::Result<()> {
+    let dir = tempdir()?;
+    let plain = dir.path().join("plain.sst");
+    let no_dict = dir.path().join("no_dict.sst");
+    let with_dict = dir.path().join("with_dict.sst");
+    let entries = similar_values(2000);
+
+    let options = dictionary_options();
+    let dictionary = options
+        .train_dictionary(entries.iter().map(|(_, value)| value.as_slice()))
+        .expect("dictionary should train from similar values");
+
+    write_sstable(plain.to_str().unwrap(), &entries, Compression::None, None)?;
+    write_sstable(
+        no_dict.to_str().unwrap(),
+        &entries,
+        Compression::zstd(),
+        None,
+    )?;
+    write_sstable(
+        with_dict.to_str().unwrap(),
+        &entries,
+        Compression::Zstd(options),
+        Some(dictionary),
+    )?;
+
+    // Keys and entry framing are stored uncompressed, so the whole file
+    // shrinks by less than its values do
+    let plain_size = fs::metadata(&plain)?.len();
+    let no_dict_size = fs::metadata(&no_dict)?.len();
+    let dict_size = fs::metadata(&with_dict)?.len();
+    assert!(
+        dict_size * 3 < plain_size * 2,
+        "dictionary compression should shrink the file by a third: {} vs {}",
+        dict_size,
+        plain_size
+    );
+    assert!(
+        dict_size < no_dict_size,
+        "dictionary should beat plain Zstd: {} vs {}",
+        dict_size,
+        no_dict_size
+    );
+
+    let mut reader = SSTableReader::open(with_dict.to_str().unwrap())?;
+    assert_eq!(reader.get("user:01234")?, Some(entries[1234].1.clone()));
+    Ok(())
+}
+
+#[test]
+fn test_dictionary_training_needs_samples() {
+    let options = dictionary_options();
+    assert!(options.train_dictionary(std::iter::empty()).is_none());
+    assert!(ZstdOptions::default()
+        .train_dictionary(similar_values(100).iter().map(|(_, v)| v.as_slice()))
+        .is_none());
+}
+
+#[test]
+fn test_compression_must_be_set_before_entries() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("late.sst");
+    let mut writer = SSTableWriter::new(path.to_str().unwrap(), 1, false, 0.01)?;
+    writer.write_entry("key", b"value")?;
+    assert!(writer.set_compression(Compression::zstd(), None).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_compaction_trains_dictionary_for_output() -> io::Result<()> {
+    let dir = tempdir()?;
+    let a = dir.path().join("a.sst").to_str().unwrap().to_string();
+    let b = dir.path().join("b.sst").to_str().unwrap().to_string();
+    let out = dir.path().join("out.sst").to_str().unwrap().to_string();
+    let entries = similar_values(2000);
+
+    write_sstable(&a, &entries[..1000], Compression::None, None)?;
+    write_sstable(&b, &entries[1000..], Compression::zstd(), None)?;
+
+    SSTableCompaction::compact_sstables_with_options(
+        &[a.clone(), b],
+        &out,
+        &CompactionOptions::default().with_compression(Compression::Zstd(dictionary_options())),
+    )?;
+
+    // The output holds twice the entries of the uncompressed input `a`
+    assert!(fs::metadata(&out)?.len() * 3 < fs::metadata(&a)?.len() * 4);
+    let mut reader = SSTableReader::open(&out)?;
+    assert_eq!(reader.compression_name(), Some("zstd"));
+    assert_eq!(reader.get("user:01500")?, Some(entries[1500].1.clone()));
+    let scanned: Vec<_> = reader.into_entries()?.collect::<io::Result<_>>()?;
+    assert_eq!(scanned, entries);
+    Ok(())
+}
+
+#[test]
+fn test_index_flushes_and_recovers_compressed_values() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap().to_string();
+    let options =
+        LsmIndexOptions::default().with_compression(Compression::Zstd(dictionary_options()));
+    let entries = similar_values(500);
+
+    {
+        let index = LsmIndex::new_with_options(
+            4 * 1024 * 1024,
+            path.clone(),
+            None,
+            true,
+            0.01,
+            options.clone(),
+        )
+        .unwrap();
+        for (key, value) in &entries {
+            index.insert(key.clone(), value.clone()).unwrap();
+        }
+        index.flush().unwrap();
+
+        assert_eq!(index.get("user:00007").unwrap(), Some(entries[7].1.clone()));
+        let checksummed = index.get_with_checksum("user:00007").unwrap().unwrap();
+        assert!(checksummed.verified);
+        assert_eq!(checksummed.value, entries[7].1);
+    }
+
+    let mut index =
+        LsmIndex::new_with_options(4 * 1024 * 1024, path, None, true, 0.01, options).unwrap();
+    index.recover().unwrap();
+    for (key, value) in entries.iter().step_by(50) {
+        assert_eq!(index.get(key).unwrap(), Some(value.clone()));
+        assert_eq!(index.get_flushed(key).unwrap(), Some(value.clone()));
+    }
+}
+
+#[test]
+fn test_incompressible_values_are_detected() {
+    assert!(looks_incompressible(&[0x1f, 0x8b, 0x08, 0x00]));
+    assert!(looks_incompressible(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]));
+    assert!(looks_incompressible(&noise(4096, 7)));
+    assert!(!looks_incompressible(&vec![b'a'; 4096]));
+    assert!(!looks_incompressible(&similar_values(1)[0].1));
+    // Too short to trust the sample's entropy
+    assert!(!looks_incompressible(&noise(100, 7)));
+}
+
+#[test]
+fn test_incompressible_values_are_stored_as_written() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("mixed.sst");
+    let path = path.to_str().unwrap();
+    let mut gzipped = vec![0x1f, 0x8b, 0x08, 0x00];
+    gzipped.extend(vec![b'x'; 2000]);
+    let entries = vec![
+        ("a".to_string(), vec![b'a'; 2000]),
+        ("b".to_string(), noise(8192, 3)),
+        ("c".to_string(), gzipped),
+        ("d".to_string(), b"tiny".to_vec()),
+    ];
+
+    write_sstable(path, &entries, Compression::zstd(), None)?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(reader.properties().get("lsmer.value_flags"), Some("1"));
+    // The noise and the gzip blob are skipped, and the tiny value would grow
+    assert_eq!(reader.uncompressed_value_count(), Some(3));
+    for (key, value) in &entries {
+        assert_eq!(reader.get(key)?.as_ref(), Some(value));
+    }
+    let scanned: Vec<_> = reader.into_entries()?.collect::<io::Result<_>>()?;
+    assert_eq!(scanned, entries);
+    Ok(())
+}
@@ -0,0 +1,188 @@
+mod helpers;
+
+use helpers::open_index;
+use lsmer::sstable::{
+    RangeTombstone, SSTableCompaction, SSTableInfo, SSTableReader, SSTableWriter, Tombstone,
+};
+use std::io;
+use tempfile::tempdir;
+
+fn info(path: &str, created_at_secs: u64, keys: (&str, &str), counts: (u64, u64)) -> SSTableInfo {
+    SSTableInfo {
+        path: path.to_string(),
+        size_bytes: 1000,
+        entry_count: counts.0,
+        tombstone_count: counts.1,
+        created_at_secs,
+        min_key: Some(keys.0.to_string()),
+        max_key: Some(keys.1.to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_writer_records_tombstone_count() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("t.sst");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 2, true, 0.01)?;
+    writer.write_entry("a", b"1")?;
+    for key in ["b", "c", "d"] {
+        writer.write_tombstone(
+            key,
+            Tombstone {
+                deleted_at_ms: 1,
+                value: None,
+            },
+        );
+    }
+    writer.finalize()?;
+
+    let reader = SSTableReader::open(path)?;
+    assert_eq!(reader.tombstone_count(), 3);
+    assert_eq!(
+        reader
+            .properties()
+            .get_u64(lsmer::sstable::properties::PROP_NUM_TOMBSTONES),
+        Some(3)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_removals_are_flushed_as_tombstones() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let index = open_index(path);
+
+    for i in 0..10 {
+        index.insert(format!("key{}", i), b"v".to_vec()).unwrap();
+    }
+    index.flush().unwrap();
+
+    for i in 0..8 {
+        index.remove(&format!("key{}", i)).unwrap();
+    }
+    index.insert("key9".to_string(), b"w".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    let infos = index.list_sstables();
+    assert_eq!(infos.len(), 2);
+    let newest = &infos[1];
+    assert_eq!(newest.tombstone_count, 8);
+    assert_eq!(newest.entry_count, 1);
+    assert!((newest.deletion_ratio() - 8.0 / 9.0).abs() < 1e-9);
+    assert_eq!(infos[0].deletion_ratio(), 0.0);
+
+    // Flushed removals hide the older file's values, also after a restart
+    assert_eq!(index.get_flushed("key3").unwrap(), None);
+    assert_eq!(index.get_flushed("key8").unwrap(), Some(b"v".to_vec()));
+    drop(index);
+    let mut index = open_index(path);
+    index.recover().unwrap();
+    assert_eq!(index.get("key3").unwrap(), None);
+    assert_eq!(index.get("key9").unwrap(), Some(b"w".to_vec()));
+    assert_eq!(index.list_sstables()[1].tombstone_count, 8);
+}
+
+#[test]
+fn test_deletion_heavy_files_ranked_by_ratio() {
+    let sstables = vec![
+        info("a", 1, ("a", "m"), (100, 0)),
+        info("b", 2, ("a", "m"), (10, 90)),
+        info("c", 3, ("a", "m"), (50, 50)),
+        info("d", 4, ("a", "m"), (0, 0)),
+    ];
+    assert_eq!(
+        SSTableCompaction::identify_deletion_heavy(&sstables, 0.3),
+        vec![1, 2]
+    );
+    assert!(SSTableCompaction::identify_deletion_heavy(&sstables, 0.95).is_empty());
+}
+
+#[test]
+fn test_deletion_heavy_files_are_compacted_first() {
+    let sstables = vec![
+        info("old_overlapping", 1, ("a", "f"), (100, 0)),
+        info("old_disjoint", 2, ("x", "z"), (100, 0)),
+        info("heavy", 3, ("c", "h"), (20, 80)),
+        info("newer", 4, ("a", "z"), (100, 0)),
+        info("newest", 5, ("a", "z"), (100, 0)),
+    ];
+
+    let groups =
+        SSTableCompaction::identify_compaction_groups_with_deletions(&sstables, 1.5, 2, 0.5);
+    // The heavy file goes first, together with the older file its tombstones
+    // can shadow; size-tiering groups the rest
+    assert_eq!(groups[0], vec![0, 2]);
+    assert_eq!(groups.len(), 2);
+    let mut rest = groups[1].clone();
+    rest.sort();
+    assert_eq!(rest, vec![1, 3, 4]);
+
+    // Without deletion-heavy files the grouping matches size-tiering alone
+    assert_eq!(
+        SSTableCompaction::identify_compaction_groups_with_deletions(&sstables, 1.5, 2, 0.9),
+        SSTableCompaction::identify_compaction_groups(&sstables, 1.5, 2)
+    );
+}
+
+#[test]
+fn test_writer_records_range_deletion_count() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("t.sst");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 1, true, 0.01)?;
+    writer.write_entry("a", b"1")?;
+    writer.write_range_tombstone(RangeTombstone::new("b", "d", 1));
+    writer.write_range_tombstone(RangeTombstone::new("x", "z", 2));
+    writer.finalize()?;
+
+    let reader = SSTableReader::open(path)?;
+    assert_eq!(reader.range_tombstone_count(), 2);
+    assert_eq!(reader.tombstone_count(), 0);
+    Ok(())
+}
+
+#[test]
+fn test_live_keys_are_estimated_net_of_tombstones() {
+    let sstables = vec![
+        info("old", 1, ("a", "z"), (100, 0)),
+        info("new", 2, ("a", "m"), (10, 30)),
+        info("clean", 3, ("n", "z"), (50, 0)),
+    ];
+    assert_eq!(SSTableCompaction::estimate_num_keys(&sstables), 130);
+    assert_eq!(SSTableCompaction::estimate_num_keys(&sstables[1..2]), 0);
+    assert_eq!(SSTableCompaction::estimate_num_keys(&[]), 0);
+
+    // Compacting the deletions with the data they hide reclaims the most
+    let with_deletes = SSTableCompaction::compaction_benefit(&sstables, &[0, 1]);
+    let without = SSTableCompaction::compaction_benefit(&sstables, &[0, 2]);
+    assert_eq!(without, 0.0);
+    assert!((with_deletes - 60.0 / 140.0).abs() < 1e-9);
+    assert_eq!(SSTableCompaction::compaction_benefit(&sstables, &[]), 0.0);
+}
+
+#[test]
+fn test_index_estimates_keys_from_file_counts() {
+    let dir = tempdir().unwrap();
+    let index = open_index(dir.path().to_str().unwrap());
+    for i in 0..10 {
+        index
+            .insert(format!("key{}", i), b"value".to_vec())
+            .unwrap();
+    }
+    index.flush().unwrap();
+    for i in 0..4 {
+        index.remove(&format!("key{}", i)).unwrap();
+    }
+    index.flush().unwrap();
+    assert_eq!(index.estimate_num_keys().unwrap(), 6);
+
+    index
+        .insert("fresh".to_string(), b"value".to_vec())
+        .unwrap();
+    assert_eq!(index.estimate_num_keys().unwrap(), 7);
+}
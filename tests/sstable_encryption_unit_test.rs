@@ -0,0 +1,215 @@
+mod helpers;
+
+use helpers::{open_index_with_options, try_open_index_with_options};
+use lsmer::lsm_index::LsmIndexOptions;
+use lsmer::sstable::encryption::{self, KeyProvider};
+use lsmer::sstable::{CompressionType, SSTableReader, SSTableWriter, StaticKeyProvider};
+use std::fs;
+use std::io;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+fn provider() -> Arc<StaticKeyProvider> {
+    Arc::new(StaticKeyProvider::new("initial", [1; encryption::KEY_SIZE]))
+}
+
+fn entries(range: std::ops::Range<usize>) -> Vec<(String, Vec<u8>)> {
+    range
+        .map(|i| {
+            let value = format!("Shipment {} left the warehouse on schedule.", i);
+            (format!("order:{:05}", i), value.into_bytes())
+        })
+        .collect()
+}
+
+fn write_encrypted(
+    path: &str,
+    entries: &[(String, Vec<u8>)],
+    provider: &StaticKeyProvider,
+) -> io::Result<String> {
+    let mut writer = SSTableWriter::new(path, entries.len(), true, 0.01)?;
+    writer.set_encryption(provider)?;
+    let key_id = writer.encryption_key_id().unwrap().to_string();
+    for (key, value) in entries {
+        writer.write_entry(key, value)?;
+    }
+    writer.finalize()?;
+    Ok(key_id)
+}
+
+#[test]
+fn test_encrypted_blocks_round_trip() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("encrypted.sst");
+    let path = path.to_str().unwrap();
+    let entries = entries(0..5000);
+    let provider = provider();
+    let key_id = write_encrypted(path, &entries, &provider)?;
+
+    let bytes = fs::read(path)?;
+    assert!(!bytes.windows(9).any(|window| window == b"warehouse"));
+
+    let mut reader = SSTableReader::open_with_key_provider(path, provider.as_ref())?;
+    assert_eq!(reader.encryption_key_id(), Some(key_id.as_str()));
+    assert_eq!(reader.get("order:03210")?, Some(entries[3210].1.clone()));
+    assert_eq!(reader.get("order:99999")?, None);
+
+    let scanned: Vec<_> = reader.into_entries()?.collect::<io::Result<_>>()?;
+    assert_eq!(scanned, entries);
+    Ok(())
+}
+
+#[test]
+fn test_compressed_blocks_are_encrypted() -> io::Result<()> {
+    let provider = provider();
+    let dir = tempdir()?;
+    let path = dir.path().join("compressed.sst");
+    let path = path.to_str().unwrap();
+    let entries = entries(0..2000);
+
+    let mut writer = SSTableWriter::new(path, entries.len(), true, 0.01)?;
+    writer.set_compression_type(CompressionType::Lz4)?;
+    writer.set_encryption(provider.as_ref())?;
+    for (key, value) in &entries {
+        writer.write_entry(key, value)?;
+    }
+    writer.finalize()?;
+
+    let bytes = fs::read(path)?;
+    assert!(!bytes.windows(9).any(|window| window == b"warehouse"));
+
+    let mut reader = SSTableReader::open_with_key_provider(path, provider.as_ref())?;
+    assert_eq!(reader.compression_type(), CompressionType::Lz4);
+    assert!(reader.encryption_key_id().is_some());
+    assert!(reader.data_bytes().unwrap() < reader.raw_bytes().unwrap());
+    assert_eq!(reader.get("order:01999")?, Some(entries[1999].1.clone()));
+
+    let scanned: Vec<_> = reader.into_entries()?.collect::<io::Result<_>>()?;
+    assert_eq!(scanned, entries);
+    Ok(())
+}
+
+#[test]
+fn test_tampered_block_is_rejected() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("tampered.sst");
+    let path = path.to_str().unwrap();
+    let entries = entries(0..100);
+    let provider = provider();
+    write_encrypted(path, &entries, &provider)?;
+
+    // The first block's ciphertext starts after the header and its nonce
+    let mut bytes = fs::read(path)?;
+    let offset = lsmer::sstable::header_size(lsmer::sstable::VERSION) + 16;
+    bytes[offset] ^= 0xFF;
+    fs::write(path, &bytes)?;
+
+    let mut reader = SSTableReader::open_with_key_provider(path, provider.as_ref())?;
+    let err = reader.get(&entries[0].0).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    Ok(())
+}
+
+#[test]
+fn test_encrypted_file_needs_a_key_provider() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("sealed.sst");
+    let path = path.to_str().unwrap();
+    write_encrypted(path, &entries(0..10), &provider())?;
+
+    let err = SSTableReader::open(path).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+
+    // A provider without the file's key cannot read it either
+    let other = StaticKeyProvider::new("other", [9; encryption::KEY_SIZE]);
+    let err = SSTableReader::open_with_key_provider(path, &other).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    Ok(())
+}
+
+#[test]
+fn test_value_separation_is_refused_with_encryption() {
+    let dir = tempdir().unwrap();
+    let options = LsmIndexOptions::default()
+        .with_encryption(provider())
+        .with_value_separation(1024);
+    let err = options.validate().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(try_open_index_with_options(dir.path().to_str().unwrap(), options).is_err());
+}
+
+#[test]
+fn test_rotated_keys_are_rewritten_by_compaction() {
+    let provider = provider();
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let options = || LsmIndexOptions::default().with_encryption(provider.clone());
+    let key_ids = |index: &lsmer::lsm_index::LsmIndex| -> Vec<Option<String>> {
+        index
+            .list_sstables()
+            .iter()
+            .map(|file| {
+                let reader =
+                    SSTableReader::open_with_key_provider(&file.path, provider.as_ref()).unwrap();
+                reader.encryption_key_id().map(str::to_string)
+            })
+            .collect()
+    };
+    let old_entries = entries(0..100);
+    let new_entries = entries(100..200);
+
+    provider.rotate("rotation-old", [2; encryption::KEY_SIZE]);
+    {
+        let index = open_index_with_options(path, options());
+        for (key, value) in &old_entries {
+            index.insert(key.clone(), value.clone()).unwrap();
+        }
+        index.flush().unwrap();
+        assert_eq!(key_ids(&index), vec![Some("rotation-old".to_string())]);
+
+        // New files take the new key; the old file keeps its own
+        provider.rotate("rotation-new", [3; encryption::KEY_SIZE]);
+        assert_eq!(provider.current_key_id().unwrap(), "rotation-new");
+        for (key, value) in &new_entries {
+            index.insert(key.clone(), value.clone()).unwrap();
+        }
+        index.flush().unwrap();
+        let mut written = key_ids(&index);
+        written.sort();
+        assert_eq!(
+            written,
+            vec![
+                Some("rotation-new".to_string()),
+                Some("rotation-old".to_string())
+            ]
+        );
+    }
+
+    let mut index = open_index_with_options(path, options());
+    index.recover().unwrap();
+    assert_eq!(
+        index.get("order:00042").unwrap(),
+        Some(old_entries[42].1.clone())
+    );
+    assert_eq!(
+        index.get("order:00142").unwrap(),
+        Some(new_entries[42].1.clone())
+    );
+
+    // Compaction rewrites the old file's entries under the current key
+    index.compact_range(..).unwrap();
+    let rewritten = key_ids(&index);
+    assert!(!rewritten.is_empty());
+    assert!(
+        rewritten
+            .iter()
+            .all(|key_id| key_id.as_deref() == Some("rotation-new"))
+    );
+    let scanned = index
+        .range("order:00098".to_string().."order:00102".to_string())
+        .unwrap();
+    assert_eq!(
+        scanned,
+        [&old_entries[98..], &new_entries[..2]].concat().to_vec()
+    );
+}
@@ -0,0 +1,108 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::{FilterCache, SSTableReader};
+use std::io;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+fn key(i: usize) -> String {
+    format!("key{:05}", i)
+}
+
+fn entries(count: usize) -> impl Iterator<Item = (String, &'static [u8])> {
+    (0..count).map(|i| (key(i), b"value".as_slice()))
+}
+
+#[test]
+fn test_filter_is_loaded_on_first_use() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 1000, true, entries(1000), |_| Ok(()))?;
+    let cache = Arc::new(FilterCache::new(1024 * 1024));
+
+    let mut reader = SSTableReader::open_with_filter_cache(path, cache.clone())?;
+    assert!(reader.has_bloom_filter());
+    assert!(reader.bloom_filter().is_none());
+    assert_eq!(cache.stats().resident_filters, 0);
+
+    assert!(reader.may_contain(&key(10)));
+    assert!(!reader.may_contain("absent"));
+    assert_eq!(reader.get(&key(999))?, Some(b"value".to_vec()));
+    let stats = cache.stats();
+    assert_eq!(stats.misses, 1);
+    assert_eq!(stats.hits, 2);
+    assert_eq!(stats.resident_filters, 1);
+    assert!(stats.resident_bytes > 0);
+
+    // The filter is released along with its reader
+    drop(reader);
+    assert_eq!(cache.stats().resident_filters, 0);
+    Ok(())
+}
+
+#[test]
+fn test_evicted_filters_are_reloaded() -> io::Result<()> {
+    let dir = tempdir()?;
+    let first = dir.path().join("first.db");
+    let second = dir.path().join("second.db");
+    write_table(first.to_str().unwrap(), 1000, true, entries(1000), |_| {
+        Ok(())
+    })?;
+    write_table(second.to_str().unwrap(), 1000, true, entries(1000), |_| {
+        Ok(())
+    })?;
+
+    // Room for one filter only
+    let filter_bytes = SSTableReader::open(first.to_str().unwrap())?
+        .bloom_filter()
+        .unwrap()
+        .get_bits()
+        .len();
+    let cache = Arc::new(FilterCache::new(filter_bytes + filter_bytes / 2));
+    let first = SSTableReader::open_with_filter_cache(first.to_str().unwrap(), cache.clone())?;
+    let second = SSTableReader::open_with_filter_cache(second.to_str().unwrap(), cache.clone())?;
+
+    assert!(first.may_contain(&key(1)));
+    assert!(second.may_contain(&key(1)));
+    assert_eq!(cache.stats().evictions, 1);
+    assert_eq!(cache.stats().resident_filters, 1);
+
+    // Reading the first file's filter back still rules keys out
+    assert!(!first.may_contain("absent"));
+    assert_eq!(cache.stats().misses, 3);
+    assert!(cache.stats().resident_bytes <= cache.capacity_bytes());
+
+    // Shrinking the budget gives the memory back
+    cache.set_capacity(0);
+    assert_eq!(cache.stats().resident_filters, 0);
+    assert!(first.may_contain(&key(1)));
+    assert!(!second.may_contain("absent"));
+    assert_eq!(cache.stats().resident_bytes, 0);
+    Ok(())
+}
+
+#[test]
+fn test_index_readers_use_the_filter_cache() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let cache = Arc::new(FilterCache::new(1024 * 1024));
+    let options = LsmIndexOptions::default().with_filter_cache(cache.clone());
+    let index =
+        LsmIndex::new_with_options(4 * 1024 * 1024, path.to_string(), None, true, 0.01, options)
+            .unwrap();
+
+    for i in 0..100 {
+        index.insert(key(i), b"value".to_vec()).unwrap();
+    }
+    index.flush().unwrap();
+    assert_eq!(cache.stats().resident_filters, 0);
+
+    // A key inside the file's range reaches its filter
+    assert_eq!(index.get_flushed("key00050x").unwrap(), None);
+    assert_eq!(cache.stats().misses, 1);
+    assert_eq!(cache.stats().resident_filters, 1);
+    assert!(index.resource_usage().cache_bytes >= cache.stats().resident_bytes as u64);
+}
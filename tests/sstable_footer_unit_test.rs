@@ -0,0 +1,109 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::sstable::{
+    FOOTER_SIZE, Footer, MAGIC, SSTableReader, SSTableWriter, VERSION, header_size,
+    is_valid_header,
+};
+use std::fs::{self, File};
+use std::io;
+use tempfile::tempdir;
+
+fn entries(count: usize) -> impl Iterator<Item = (String, String)> {
+    (0..count).map(|i| (format!("key{:04}", i), format!("value{}", i)))
+}
+
+fn numbered(writer: &mut SSTableWriter) -> io::Result<()> {
+    writer.set_file_number(42);
+    Ok(())
+}
+
+#[test]
+fn test_header_holds_no_offsets() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+    write_table(path, 100, true, entries(100), numbered)?;
+
+    // The header is the same whatever the file holds, so it can be written
+    // up front and never revisited
+    let bytes = fs::read(path)?;
+    let header = &bytes[..header_size(VERSION)];
+    assert!(is_valid_header(header));
+    assert_eq!(&header[..8], &MAGIC.to_le_bytes());
+    assert_eq!(&header[8..12], &VERSION.to_le_bytes());
+    // Neither a cipher nor a block compression type
+    assert_eq!(&header[12..14], &[0, 0]);
+
+    let empty = dir.path().join("empty.sst");
+    let empty = empty.to_str().unwrap();
+    write_table(empty, 0, true, entries(0), numbered)?;
+    assert_eq!(&fs::read(empty)?[..header_size(VERSION)], header);
+    Ok(())
+}
+
+#[test]
+fn test_footer_records_what_the_reader_sees() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+    write_table(path, 100, true, entries(100), numbered)?;
+
+    let footer = Footer::read_from(&mut File::open(path)?)?;
+    assert_eq!(footer.entry_count, 100);
+    assert_eq!(footer.file_number, 42);
+    assert!(footer.has_bloom_filter);
+    assert!(footer.bloom_size > 0);
+    assert!(footer.index_offset < footer.bloom_offset);
+    assert!(footer.bloom_offset + footer.bloom_size <= footer.checksums_offset);
+    assert!(footer.checksums_offset <= fs::metadata(path)?.len() - FOOTER_SIZE as u64);
+
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(reader.version(), VERSION);
+    assert_eq!(reader.entry_count(), 100);
+    assert_eq!(reader.file_number(), Some(42));
+    assert_eq!(reader.get("key0042")?, Some(b"value42".to_vec()));
+    Ok(())
+}
+
+#[test]
+fn test_footer_round_trips() {
+    let footer = Footer {
+        entry_count: 7,
+        index_offset: 100,
+        bloom_offset: 200,
+        bloom_size: 30,
+        has_bloom_filter: true,
+        file_number: 9,
+        checksums_offset: 230,
+    };
+    let encoded = footer.encode();
+    assert_eq!(encoded.len(), FOOTER_SIZE);
+    assert_eq!(Footer::decode(&encoded).unwrap(), footer);
+}
+
+#[test]
+fn test_damaged_footer_is_rejected() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+    write_table(path, 10, true, entries(10), numbered)?;
+    let bytes = fs::read(path)?;
+
+    // Any damaged byte fails the footer's checksum or magic number
+    for at in [0, 8, 32, FOOTER_SIZE - 10, FOOTER_SIZE - 1] {
+        let mut damaged = bytes.clone();
+        let len = damaged.len();
+        damaged[len - FOOTER_SIZE + at] ^= 0x01;
+        fs::write(path, &damaged)?;
+        let err = SSTableReader::open(path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "byte {}", at);
+    }
+
+    // As does a file cut short
+    fs::write(path, &bytes[..bytes.len() - 1])?;
+    assert!(SSTableReader::open(path).is_err());
+    fs::write(path, &bytes[..header_size(VERSION)])?;
+    assert!(SSTableReader::open(path).is_err());
+    Ok(())
+}
@@ -0,0 +1,152 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::sstable::{Compression, CompressionType, SSTableReader, SSTableWriter};
+use std::fs;
+use std::io;
+use tempfile::tempdir;
+
+const VALUE_SIZE: usize = 64 * 1024;
+
+/// Sets up a writer before any entry is written
+type Setup = fn(&mut SSTableWriter) -> io::Result<()>;
+
+/// Bytes with no redundancy for compression to remove
+fn noise(seed: u64) -> Vec<u8> {
+    let mut state = seed | 1;
+    (0..VALUE_SIZE)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn entries() -> Vec<(String, Vec<u8>)> {
+    (0..40)
+        .map(|i| (format!("key{:03}", i), noise(i)))
+        .collect()
+}
+
+#[test]
+fn test_get_ref_matches_get() -> io::Result<()> {
+    let dir = tempdir()?;
+    let entries = entries();
+    let setups: [(&str, Setup); 4] = [
+        ("plain", |_| Ok(())),
+        ("strict", |writer| writer.set_strict_key_order()),
+        ("hash", |writer| writer.set_hash_index()),
+        ("prefix", |writer| {
+            writer.set_restart_interval(4)?;
+            writer.set_key_prefix_compression()
+        }),
+    ];
+    for (name, setup) in setups {
+        let path = dir.path().join(format!("{}.sst", name));
+        let path = path.to_str().unwrap();
+        write_table(path, entries.len(), true, &entries, setup)?;
+
+        let mut reader = SSTableReader::open(path)?;
+        assert_eq!(reader.mapped_bytes(), 0);
+        for (key, value) in &entries {
+            let value_ref = reader.get_ref(key)?.unwrap();
+            assert!(value_ref.is_mapped(), "{}", name);
+            assert_eq!(&*value_ref, value.as_slice(), "{}", name);
+            assert_eq!(reader.get(key)?.as_ref(), Some(value));
+        }
+        assert_eq!(reader.get_ref("key999")?, None);
+        assert_eq!(reader.get_ref("absent")?, None);
+        assert_eq!(reader.mapped_bytes() as u64, fs::metadata(path)?.len());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_get_ref_outlives_reader() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+    let entries = entries();
+    write_table(path, entries.len(), true, &entries, |_| Ok(()))?;
+
+    let mut reader = SSTableReader::open(path)?;
+    let value_ref = reader.get_ref("key007")?.unwrap();
+    let copy = value_ref.clone();
+    drop(reader);
+    fs::remove_file(path)?;
+    assert_eq!(&*copy, entries[7].1.as_slice());
+    assert_eq!(Vec::from(value_ref), entries[7].1);
+    Ok(())
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_get_ref_decodes_compressed_values() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+    let entries = vec![
+        ("compressible".to_string(), vec![b'a'; VALUE_SIZE]),
+        ("incompressible".to_string(), noise(3)),
+    ];
+    write_table(path, entries.len(), true, &entries, |writer| {
+        writer.set_compression(Compression::zstd(), None)
+    })?;
+
+    // Values stored as written are still borrowed from the map
+    let mut reader = SSTableReader::open(path)?;
+    let compressed = reader.get_ref("compressible")?.unwrap();
+    assert!(!compressed.is_mapped());
+    assert_eq!(*compressed, *entries[0].1);
+    let raw = reader.get_ref("incompressible")?.unwrap();
+    assert!(raw.is_mapped());
+    assert_eq!(*raw, *entries[1].1);
+    Ok(())
+}
+
+#[test]
+fn test_get_ref_reads_compressed_blocks() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+    let entries = entries();
+    write_table(path, entries.len(), true, &entries, |writer| {
+        writer.set_compression_type(CompressionType::Lz4)
+    })?;
+
+    // Entry offsets do not match the stored bytes, so nothing is mapped
+    let mut reader = SSTableReader::open(path)?;
+    for (key, value) in &entries {
+        let value_ref = reader.get_ref(key)?.unwrap();
+        assert!(!value_ref.is_mapped());
+        assert_eq!(&*value_ref, value.as_slice());
+    }
+    assert_eq!(reader.mapped_bytes(), 0);
+    Ok(())
+}
+
+#[test]
+fn test_get_ref_verifies_checksums() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+    let entries = entries();
+    write_table(path, entries.len(), true, &entries, |_| Ok(()))?;
+
+    // Flip a byte in the middle of the first value
+    let mut bytes = fs::read(path)?;
+    let offset = bytes
+        .windows(VALUE_SIZE / 2)
+        .position(|window| window == &entries[0].1[..VALUE_SIZE / 2])
+        .unwrap()
+        + VALUE_SIZE / 4;
+    bytes[offset] ^= 0xff;
+    fs::write(path, bytes)?;
+
+    let mut reader = SSTableReader::open(path)?;
+    let err = reader.get_ref("key000").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    Ok(())
+}
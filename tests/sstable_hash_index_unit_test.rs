@@ -0,0 +1,182 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::{
+    CompactionOptions, Compression, SSTableCompaction, SSTableReader, SSTableWriter,
+};
+use std::io;
+use tempfile::tempdir;
+
+fn key(i: usize) -> String {
+    format!("key{:05}", i)
+}
+
+fn value(i: usize) -> Vec<u8> {
+    format!("value-{}-{}", i, "x".repeat(100)).into_bytes()
+}
+
+fn entry(i: usize) -> (String, Vec<u8>) {
+    (key(i), value(i))
+}
+
+#[test]
+fn test_hash_index_serves_point_lookups() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 3000, true, (0..3000).map(entry), |writer| {
+        writer.set_hash_index()
+    })?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert!(reader.has_hash_index());
+    // The hash index replaces the block index
+    assert!(reader.block_index().is_empty());
+    for i in 0..3000 {
+        assert_eq!(reader.get(&key(i))?, Some(value(i)));
+    }
+    assert_eq!(reader.get("key01234x")?, None);
+    assert_eq!(reader.get("")?, None);
+    Ok(())
+}
+
+#[test]
+fn test_hash_index_handles_unsorted_keys() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 1000, true, (0..1000).rev().map(entry), |writer| {
+        writer.allow_unsorted_keys()?;
+        writer.set_hash_index()
+    })?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert!(!reader.keys_sorted());
+    for i in (0..1000).step_by(37) {
+        assert_eq!(reader.get(&key(i))?, Some(value(i)));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_hash_index_finds_the_first_of_duplicate_keys() -> io::Result<()> {
+    let dir = tempdir()?;
+    let hashed = dir.path().join("hashed.db");
+    let scanned = dir.path().join("scanned.db");
+    for (path, hash_index) in [(&hashed, true), (&scanned, false)] {
+        let mut writer = SSTableWriter::new(path.to_str().unwrap(), 3, true, 0.01)?;
+        writer.allow_unsorted_keys()?;
+        if hash_index {
+            writer.set_hash_index()?;
+        }
+        writer.write_entry("b", b"first")?;
+        writer.write_entry("a", b"other")?;
+        writer.write_entry("b", b"second")?;
+        writer.finalize()?;
+    }
+
+    let hashed = SSTableReader::open(hashed.to_str().unwrap())?.get("b")?;
+    let scanned = SSTableReader::open(scanned.to_str().unwrap())?.get("b")?;
+    assert_eq!(hashed, Some(b"first".to_vec()));
+    assert_eq!(hashed, scanned);
+    Ok(())
+}
+
+#[test]
+fn test_hash_index_works_with_compression_and_range_scans() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    let mut writer = SSTableWriter::new(path, 500, true, 0.01)?;
+    writer.set_hash_index()?;
+    writer.set_compression(Compression::zstd(), None)?;
+    for i in 0..500 {
+        writer.write_entry(&key(i), &value(i))?;
+    }
+    writer.finalize()?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(reader.get(&key(321))?, Some(value(321)));
+
+    // Range scans still work, reading the file from the start
+    let keys: Vec<String> = reader
+        .into_range_entries(Some(&key(100)), Some(&key(105)))?
+        .map(|entry| entry.map(|(key, _)| key))
+        .collect::<io::Result<_>>()?;
+    assert_eq!(keys, (100..105).map(key).collect::<Vec<_>>());
+    Ok(())
+}
+
+#[test]
+fn test_hash_index_must_be_set_before_entries_and_not_partitioned() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 10, true, 0.01)?;
+    writer.write_entry("a", b"1")?;
+    assert_eq!(
+        writer.set_hash_index().unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+
+    let mut writer = SSTableWriter::new(path, 10, true, 0.01)?;
+    writer.set_partitioned_index(4, 0.01)?;
+    assert_eq!(
+        writer.set_hash_index().unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    Ok(())
+}
+
+#[test]
+fn test_compaction_can_hash_index_its_output() -> io::Result<()> {
+    let dir = tempdir()?;
+    let input = dir.path().join("input.db");
+    let input = input.to_str().unwrap().to_string();
+    let output = dir.path().join("output.db");
+    let output = output.to_str().unwrap();
+    write_table(&input, 1000, true, (0..1000).map(entry), |_| Ok(()))?;
+
+    let options = CompactionOptions::default()
+        .with_hash_index(true)
+        .with_partitioned_index(2);
+    SSTableCompaction::compact_sstables_with_options(&[input], output, &options)?;
+
+    let mut reader = SSTableReader::open(output)?;
+    assert!(reader.has_hash_index());
+    assert!(!reader.has_partitioned_index());
+    assert_eq!(reader.get(&key(777))?, Some(value(777)));
+    Ok(())
+}
+
+#[test]
+fn test_index_hashes_files_at_configured_levels() {
+    let dir = tempdir().unwrap();
+    let index = LsmIndex::new_with_options(
+        4 * 1024 * 1024,
+        dir.path().to_str().unwrap().to_string(),
+        None,
+        true,
+        0.01,
+        LsmIndexOptions::default().with_hash_index_levels([0]),
+    )
+    .unwrap();
+    for i in 0..500 {
+        index.insert(key(i), value(i)).unwrap();
+    }
+    index.flush().unwrap();
+
+    let path = index.list_sstables()[0].path.clone();
+    assert!(SSTableReader::open(&path).unwrap().has_hash_index());
+    assert_eq!(index.get_flushed(&key(42)).unwrap(), Some(value(42)));
+    assert_eq!(index.get_flushed("missing").unwrap(), None);
+    let range: Vec<String> = index
+        .range(key(10)..key(13))
+        .unwrap()
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(range, vec![key(10), key(11), key(12)]);
+}
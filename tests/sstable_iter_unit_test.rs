@@ -0,0 +1,144 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::sstable::compaction_report::report_path;
+use lsmer::sstable::{CompactionOptions, SSTableCompaction, SSTableReader, SSTableWriter};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use tempfile::tempdir;
+
+fn key(i: usize) -> String {
+    format!("key{:05}", i)
+}
+
+fn value(i: usize) -> Vec<u8> {
+    format!("value-{}", i).into_bytes()
+}
+
+fn entry(i: usize) -> (String, Vec<u8>) {
+    (key(i), value(i))
+}
+
+fn expected(range: std::ops::Range<usize>) -> Vec<(String, Vec<u8>)> {
+    range.map(|i| (key(i), value(i))).collect()
+}
+
+#[test]
+fn test_iter_walks_sorted_files_without_consuming_the_reader() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 500, true, (0..500).map(entry), |_| Ok(()))?;
+
+    let mut reader = SSTableReader::open(path)?;
+    let mut entries = reader.iter()?;
+    let first = entries.next().unwrap()?;
+    assert_eq!(first, (key(0), value(0)));
+
+    // Lookups in between do not disturb the scan
+    assert_eq!(reader.get(&key(400))?, Some(value(400)));
+    let rest: Vec<_> = entries.collect::<io::Result<_>>()?;
+    assert_eq!(rest, expected(1..500));
+
+    assert_eq!(reader.get(&key(7))?, Some(value(7)));
+    assert_eq!(reader.iter()?.count(), 500);
+    Ok(())
+}
+
+#[test]
+fn test_iter_orders_unsorted_files_by_key() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(
+        path,
+        300,
+        true,
+        (0..300).map(|i| (i * 7) % 300).map(entry),
+        SSTableWriter::allow_unsorted_keys,
+    )?;
+
+    let reader = SSTableReader::open(path)?;
+    assert!(!reader.keys_sorted());
+    let entries: Vec<_> = reader.iter()?.collect::<io::Result<_>>()?;
+    assert_eq!(entries, expected(0..300));
+    Ok(())
+}
+
+#[test]
+fn test_iter_refuses_unsorted_files_without_a_key_index() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    let mut writer = SSTableWriter::new(path, 3, false, 0.01)?;
+    writer.allow_unsorted_keys()?;
+    writer.set_hash_index()?;
+    writer.write_entry("b", b"1")?;
+    writer.write_entry("a", b"2")?;
+    writer.finalize()?;
+
+    let reader = SSTableReader::open(path)?;
+    let err = reader.iter().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(reader.into_entries()?.count(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_iter_verifies_entry_checksums() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 10, true, (0..10).map(entry), |_| Ok(()))?;
+
+    let reader = SSTableReader::open(path)?;
+    let offset = reader.data_offset() + 4 + key(0).len() as u64 + 4;
+    drop(reader);
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(b"X")?;
+    drop(file);
+
+    let reader = SSTableReader::open(path)?;
+    let mut entries = reader.iter()?;
+    let err = entries.next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(entries.next().is_none());
+    Ok(())
+}
+
+#[test]
+fn test_unsorted_inputs_with_a_key_index_are_streamed() -> io::Result<()> {
+    let dir = tempdir()?;
+    let a = dir.path().join("a.db").to_str().unwrap().to_string();
+    let b = dir.path().join("b.db").to_str().unwrap().to_string();
+    let out = dir.path().join("out.db").to_str().unwrap().to_string();
+    write_table(
+        &a,
+        100,
+        true,
+        (0..100).rev().map(entry),
+        SSTableWriter::allow_unsorted_keys,
+    )?;
+    write_table(
+        &b,
+        100,
+        true,
+        (50..150).map(|i| 50 + (i * 13) % 100).map(entry),
+        SSTableWriter::allow_unsorted_keys,
+    )?;
+
+    SSTableCompaction::compact_sstables_with_options(
+        &[a, b],
+        &out,
+        &CompactionOptions::default().with_debug_dump(true),
+    )?;
+    let report = fs::read_to_string(report_path(&out))?;
+    assert!(report.contains("\"merge_strategy\": \"sorted\""));
+
+    let entries: Vec<_> = SSTableReader::open(&out)?
+        .iter()?
+        .collect::<io::Result<_>>()?;
+    assert_eq!(entries, expected(0..150));
+    Ok(())
+}
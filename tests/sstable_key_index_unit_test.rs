@@ -0,0 +1,164 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::sstable::{
+    CHECKSUMMED_VERSION, FOOTER_SIZE, Footer, HEADER_MAGIC_SIZE, SSTableReader, SSTableWriter,
+    VERSION, header_size,
+};
+use std::fs;
+use std::io;
+use tempfile::tempdir;
+
+fn key(i: usize) -> String {
+    format!("key{:05}", i)
+}
+
+fn value(i: usize) -> Vec<u8> {
+    format!("value-{}", i).into_bytes()
+}
+
+/// Keys 0..count in a scrambled order
+fn scrambled(count: usize) -> impl Iterator<Item = usize> {
+    (0..count).map(move |i| (i * 7919) % count)
+}
+
+fn entry(i: usize) -> (String, Vec<u8>) {
+    (key(i), value(i))
+}
+
+/// Rewrite a current file as version 3, which keeps the entry count and
+/// offsets in its header rather than a footer, and whose reader ignores the
+/// meta section
+fn downgrade_to_v3(path: &str) {
+    let bytes = fs::read(path).unwrap();
+    let footer = Footer::decode(&bytes[bytes.len() - FOOTER_SIZE..]).unwrap();
+    // Offsets move with the longer header
+    let shift = |offset: u64| {
+        offset + header_size(CHECKSUMMED_VERSION) as u64 - header_size(VERSION) as u64
+    };
+
+    let mut header = bytes[..HEADER_MAGIC_SIZE].to_vec();
+    header.extend_from_slice(&CHECKSUMMED_VERSION.to_le_bytes());
+    header.extend_from_slice(&footer.entry_count.to_le_bytes());
+    header.extend_from_slice(&shift(footer.index_offset).to_le_bytes());
+    header.extend_from_slice(&shift(footer.bloom_offset).to_le_bytes());
+    header.extend_from_slice(&footer.bloom_size.to_le_bytes());
+    header.push(footer.has_bloom_filter as u8);
+    let checksum = crc32fast::hash(&header);
+    header.extend_from_slice(&checksum.to_le_bytes());
+    header.extend_from_slice(&bytes[header_size(VERSION)..bytes.len() - FOOTER_SIZE]);
+    fs::write(path, header).unwrap();
+}
+
+#[test]
+fn test_unsorted_keys_are_looked_up_through_the_key_index() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(
+        path,
+        2000,
+        true,
+        scrambled(2000).map(entry),
+        SSTableWriter::allow_unsorted_keys,
+    )?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert!(!reader.keys_sorted());
+    assert!(reader.has_key_index());
+    for i in 0..2000 {
+        assert_eq!(reader.get(&key(i))?, Some(value(i)));
+    }
+    assert_eq!(reader.get("key99999")?, None);
+    assert_eq!(reader.get("")?, None);
+    Ok(())
+}
+
+#[test]
+fn test_key_index_returns_the_first_entry_of_a_repeated_key() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    let mut writer = SSTableWriter::new(path, 4, false, 0.01)?;
+    writer.allow_unsorted_keys()?;
+    writer.write_entry("b", b"first")?;
+    writer.write_entry("a", b"other")?;
+    writer.write_entry("b", b"second")?;
+    writer.finalize()?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert!(reader.has_key_index());
+    assert_eq!(reader.get("b")?, Some(b"first".to_vec()));
+    assert_eq!(reader.get("a")?, Some(b"other".to_vec()));
+    Ok(())
+}
+
+#[test]
+fn test_sorted_and_hashed_files_have_no_key_index() -> io::Result<()> {
+    let dir = tempdir()?;
+    let sorted = dir.path().join("sorted.db");
+    let sorted = sorted.to_str().unwrap();
+    write_table(sorted, 500, true, (0..500).map(entry), |_| Ok(()))?;
+    assert!(!SSTableReader::open(sorted)?.has_key_index());
+
+    let hashed = dir.path().join("hashed.db");
+    let hashed = hashed.to_str().unwrap();
+    let mut writer = SSTableWriter::new(hashed, 500, true, 0.01)?;
+    writer.allow_unsorted_keys()?;
+    writer.set_hash_index()?;
+    for i in scrambled(500) {
+        writer.write_entry(&key(i), &value(i))?;
+    }
+    writer.finalize()?;
+    let mut reader = SSTableReader::open(hashed)?;
+    assert!(reader.has_hash_index());
+    assert!(!reader.has_key_index());
+    assert_eq!(reader.get(&key(123))?, Some(value(123)));
+    Ok(())
+}
+
+#[test]
+fn test_key_index_is_counted_in_memory_usage() -> io::Result<()> {
+    let dir = tempdir()?;
+    let sorted = dir.path().join("sorted.db");
+    let sorted = sorted.to_str().unwrap();
+    write_table(sorted, 1000, true, (0..1000).map(entry), |_| Ok(()))?;
+    let unsorted = dir.path().join("unsorted.db");
+    let unsorted = unsorted.to_str().unwrap();
+    write_table(
+        unsorted,
+        1000,
+        true,
+        scrambled(1000).map(entry),
+        SSTableWriter::allow_unsorted_keys,
+    )?;
+
+    let sorted = SSTableReader::open(sorted)?.memory_usage();
+    let unsorted = SSTableReader::open(unsorted)?.memory_usage();
+    assert!(unsorted >= sorted + 1000 * (key(0).len() + 8));
+    Ok(())
+}
+
+#[test]
+fn test_version_3_files_without_an_index_are_scanned() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(
+        path,
+        300,
+        true,
+        scrambled(300).map(entry),
+        SSTableWriter::allow_unsorted_keys,
+    )?;
+    downgrade_to_v3(path);
+
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(reader.version(), CHECKSUMMED_VERSION);
+    assert!(!reader.has_key_index());
+    for i in 0..300 {
+        assert_eq!(reader.get(&key(i))?, Some(value(i)));
+    }
+    assert_eq!(reader.get("key99999")?, None);
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use lsmer::sstable::{SSTableReader, SSTableWriter};
+use std::io;
+use tempfile::tempdir;
+
+fn strict_writer(path: &str) -> io::Result<SSTableWriter> {
+    // Strict key order is the default
+    SSTableWriter::new(path, 10, true, 0.01)
+}
+
+#[test]
+fn test_strict_writer_accepts_ascending_keys() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    let mut writer = strict_writer(path)?;
+    for key in ["a", "b", "ba", "c"] {
+        writer.write_entry(key, key.as_bytes())?;
+    }
+    writer.finalize()?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert!(reader.keys_sorted());
+    assert_eq!(reader.get("ba")?, Some(b"ba".to_vec()));
+    Ok(())
+}
+
+#[test]
+fn test_strict_writer_refuses_out_of_order_and_repeated_keys() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    let mut writer = strict_writer(path)?;
+    writer.write_entry("b", b"1")?;
+
+    let err = writer.write_entry("a", b"2").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    let err = writer.write_entry("b", b"3").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    // A refused entry leaves the writer usable
+    writer.write_entry("c", b"4")?;
+    writer.finalize()?;
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(reader.entry_count(), 2);
+    assert_eq!(reader.get("a")?, None);
+    assert_eq!(reader.get("b")?, Some(b"1".to_vec()));
+    Ok(())
+}
+
+#[test]
+fn test_strict_order_spans_data_blocks() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    let mut writer = strict_writer(path)?;
+    writer.set_block_size(64)?;
+    for i in 0..20 {
+        writer.write_entry(&format!("key{:03}", i), &[0u8; 32])?;
+    }
+    let err = writer.write_entry("key005", b"late").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    Ok(())
+}
+
+#[test]
+fn test_strict_order_checks_appended_blocks() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    let mut writer = strict_writer(path)?;
+    writer.write_entry("m", b"1")?;
+
+    let mut before = writer.data_block_builder()?;
+    before.add("a", b"2", None, None)?;
+    let err = writer.append_block(before.finish()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let mut unsorted = writer.data_block_builder()?;
+    unsorted.add("z", b"3", None, None)?;
+    unsorted.add("x", b"4", None, None)?;
+    let err = writer.append_block(unsorted.finish()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let mut after = writer.data_block_builder()?;
+    after.add("n", b"5", None, None)?;
+    after.add("o", b"6", None, None)?;
+    writer.append_block(after.finish())?;
+    writer.finalize()?;
+    assert_eq!(SSTableReader::open(path)?.entry_count(), 3);
+    Ok(())
+}
+
+#[test]
+fn test_default_writer_refuses_out_of_order_keys() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let mut writer = SSTableWriter::new(path.to_str().unwrap(), 10, false, 0.01)?;
+    writer.write_entry("b", b"1")?;
+    let err = writer.write_entry("a", b"2").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    writer.finalize()?;
+    assert!(SSTableReader::open(path.to_str().unwrap())?.keys_sorted());
+    Ok(())
+}
+
+#[test]
+fn test_unsorted_keys_must_be_allowed_before_writing() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let mut writer = SSTableWriter::new(path.to_str().unwrap(), 10, false, 0.01)?;
+    writer.allow_unsorted_keys()?;
+    writer.write_entry("b", b"1")?;
+    assert!(writer.allow_unsorted_keys().is_err());
+    assert!(writer.set_strict_key_order().is_err());
+
+    // Writers that allow unsorted keys take them in any order
+    writer.write_entry("a", b"2")?;
+    writer.finalize()?;
+    assert!(!SSTableReader::open(path.to_str().unwrap())?.keys_sorted());
+    Ok(())
+}
+
+#[test]
+fn test_unsorted_keys_refuse_prefix_compression() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let mut writer = SSTableWriter::new(path.to_str().unwrap(), 10, false, 0.01)?;
+    writer.set_key_prefix_compression()?;
+    assert!(writer.allow_unsorted_keys().is_err());
+    Ok(())
+}
@@ -0,0 +1,226 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::{
+    CompactionOptions, DEFAULT_PREFIX_RESTART_INTERVAL, DataBlockBuilder, SSTableCompaction,
+    SSTableReader, SSTableWriter,
+};
+use std::fs;
+use tempfile::tempdir;
+
+/// Keys shaped like a tenant ID followed by a timestamp, sharing long prefixes
+fn tenant_keys() -> Vec<String> {
+    let mut keys = Vec::new();
+    for tenant in 0..4 {
+        for event in 0..250 {
+            keys.push(format!(
+                "tenant-7f3a9c2e-{:04}/events/2024-05-01T12:00:{:06}",
+                tenant, event
+            ));
+        }
+    }
+    keys
+}
+
+fn write_keys(path: &str, keys: &[String], prefix_compression: bool, hash_index: bool) {
+    let entries = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| (key, format!("v{}", i)));
+    write_table(path, keys.len(), true, entries, |writer| {
+        if prefix_compression {
+            writer.set_key_prefix_compression()?;
+        }
+        if hash_index {
+            writer.set_hash_index()?;
+        }
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_prefix_compressed_keys_read_back_and_shrink_the_file() {
+    let dir = tempdir().unwrap();
+    let keys = tenant_keys();
+    let plain = dir.path().join("plain.sst");
+    let compressed = dir.path().join("compressed.sst");
+    write_keys(plain.to_str().unwrap(), &keys, false, false);
+    write_keys(compressed.to_str().unwrap(), &keys, true, false);
+
+    let plain_size = fs::metadata(&plain).unwrap().len();
+    let compressed_size = fs::metadata(&compressed).unwrap().len();
+    assert!(
+        compressed_size * 10 < plain_size * 7,
+        "{} bytes compressed vs {} plain",
+        compressed_size,
+        plain_size
+    );
+
+    let mut reader = SSTableReader::open(compressed.to_str().unwrap()).unwrap();
+    assert!(reader.has_prefix_compressed_keys());
+    assert_eq!(
+        reader.restart_interval(),
+        Some(DEFAULT_PREFIX_RESTART_INTERVAL)
+    );
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(
+            reader.get(key).unwrap(),
+            Some(format!("v{}", i).into_bytes())
+        );
+    }
+    assert_eq!(
+        reader.get("tenant-7f3a9c2e-0001/events/missing").unwrap(),
+        None
+    );
+
+    let scanned: Vec<String> = reader
+        .iter()
+        .unwrap()
+        .map(|entry| entry.unwrap().0)
+        .collect();
+    assert_eq!(scanned, keys);
+    let report = reader.verify_all().unwrap();
+    assert!(report.is_intact(), "{:?}", report);
+    assert_eq!(report.entries_checked, keys.len() as u64);
+
+    // A bounded scan starts part way through the file
+    let lower = &keys[500];
+    let upper = &keys[540];
+    let ranged: Vec<String> = reader
+        .into_range_entries(Some(lower), Some(upper))
+        .unwrap()
+        .map(|entry| entry.unwrap().0)
+        .collect();
+    assert_eq!(ranged, keys[500..540]);
+}
+
+#[test]
+fn test_hash_index_lookups_rebuild_keys_between_restart_points() {
+    let dir = tempdir().unwrap();
+    let keys = tenant_keys();
+    let path = dir.path().join("hashed.sst");
+    write_keys(path.to_str().unwrap(), &keys, true, true);
+
+    let mut reader = SSTableReader::open(path.to_str().unwrap()).unwrap();
+    assert!(reader.has_hash_index());
+    for (i, key) in keys.iter().enumerate().step_by(7) {
+        assert_eq!(
+            reader.get(key).unwrap(),
+            Some(format!("v{}", i).into_bytes())
+        );
+    }
+}
+
+#[test]
+fn test_prefix_compression_needs_ascending_keys_and_matching_blocks() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("table.sst");
+    let mut writer = SSTableWriter::new(path.to_str().unwrap(), 10, false, 0.0).unwrap();
+    writer.set_key_prefix_compression().unwrap();
+    writer.set_restart_interval(4).unwrap();
+    writer.write_entry("b", b"1").unwrap();
+    assert!(writer.write_entry("a", b"2").is_err());
+    assert!(writer.set_key_prefix_compression().is_err());
+
+    // Blocks from the writer's own builders are compressed the same way
+    let mut builder = writer.data_block_builder().unwrap();
+    for key in ["c1", "c2", "c3", "c4", "c5"] {
+        builder.add(key, b"v", None, None).unwrap();
+    }
+    writer.append_block(builder.finish()).unwrap();
+
+    let mut plain = DataBlockBuilder::new();
+    plain.add("d", b"v", None, None).unwrap();
+    assert!(writer.append_block(plain.finish()).is_err());
+    writer.finalize().unwrap();
+
+    let mut reader = SSTableReader::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(reader.restart_interval(), Some(4));
+    assert_eq!(reader.get("c5").unwrap(), Some(b"v".to_vec()));
+    assert_eq!(reader.get("b").unwrap(), Some(b"1".to_vec()));
+}
+
+#[test]
+fn test_compaction_output_keeps_keys_prefix_compressed() {
+    let dir = tempdir().unwrap();
+    let keys = tenant_keys();
+    let first = dir.path().join("first.sst");
+    let second = dir.path().join("second.sst");
+    let output = dir.path().join("output.sst");
+    write_keys(first.to_str().unwrap(), &keys[..600], true, false);
+    write_keys(second.to_str().unwrap(), &keys[400..], false, false);
+
+    SSTableCompaction::compact_sstables_with_options(
+        &[
+            first.to_str().unwrap().to_string(),
+            second.to_str().unwrap().to_string(),
+        ],
+        output.to_str().unwrap(),
+        &CompactionOptions::default().with_key_prefix_compression(true),
+    )
+    .unwrap();
+
+    let reader = SSTableReader::open(output.to_str().unwrap()).unwrap();
+    assert!(reader.has_prefix_compressed_keys());
+    let scanned: Vec<String> = reader
+        .iter()
+        .unwrap()
+        .map(|entry| entry.unwrap().0)
+        .collect();
+    assert_eq!(scanned, keys);
+}
+
+#[test]
+fn test_index_flushes_recovers_and_compacts_prefix_compressed_files() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap().to_string();
+    let options = || {
+        LsmIndexOptions::default()
+            .with_key_prefix_compression(true)
+            .with_restart_interval(8)
+    };
+    let open = || {
+        LsmIndex::new_with_options(4 * 1024 * 1024, path.clone(), None, true, 0.01, options())
+            .unwrap()
+    };
+    let keys = tenant_keys();
+
+    let index = open();
+    for (i, key) in keys.iter().enumerate() {
+        index
+            .insert(key.clone(), format!("v{}", i).into_bytes())
+            .unwrap();
+        if i % 300 == 299 {
+            index.flush().unwrap();
+        }
+    }
+    index.flush().unwrap();
+    assert_eq!(index.get(&keys[13]).unwrap(), Some(b"v13".to_vec()));
+    drop(index);
+
+    let mut index = open();
+    index.recover().unwrap();
+    for (i, key) in keys.iter().enumerate().step_by(11) {
+        assert_eq!(
+            index.get(key).unwrap(),
+            Some(format!("v{}", i).into_bytes())
+        );
+    }
+
+    index.compact_range(..).unwrap();
+    for (i, key) in keys.iter().enumerate().step_by(13) {
+        assert_eq!(
+            index.get(key).unwrap(),
+            Some(format!("v{}", i).into_bytes())
+        );
+    }
+    for file in index.list_sstables() {
+        assert!(
+            SSTableReader::open(&file.path)
+                .unwrap()
+                .has_prefix_compressed_keys()
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use lsmer::sstable::{SSTableReader, SSTableWriter, Tombstone};
+use std::io;
+use tempfile::tempdir;
+
+#[test]
+fn test_key_range_covers_entries_and_tombstones() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    let mut writer = SSTableWriter::new(path, 3, true, 0.01)?;
+    // Unsorted on purpose: the range does not depend on write order
+    writer.allow_unsorted_keys()?;
+    writer.write_entry("m", b"1")?;
+    writer.write_entry("c", b"2")?;
+    writer.write_entry("k", b"3")?;
+    writer.write_tombstone(
+        "x",
+        Tombstone {
+            deleted_at_ms: 1,
+            value: None,
+        },
+    );
+    writer.finalize()?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(reader.min_key(), Some("c"));
+    assert_eq!(reader.max_key(), Some("x"));
+    assert!(reader.key_range_contains("c"));
+    assert!(reader.key_range_contains("p"));
+    assert!(!reader.key_range_contains("a"));
+    assert!(!reader.key_range_contains("z"));
+    assert!(reader.key_range_overlaps(Some("a"), Some("d")));
+    assert!(!reader.key_range_overlaps(Some("a"), Some("c")));
+    assert!(!reader.key_range_overlaps(Some("y"), None));
+    assert_eq!(reader.get("k")?, Some(b"3".to_vec()));
+    assert_eq!(reader.get("a")?, None);
+
+    let outside: Vec<_> = reader
+        .into_range_entries(Some("y"), None)?
+        .collect::<io::Result<_>>()?;
+    assert!(outside.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_empty_files_have_no_key_range() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("empty.db");
+    let path = path.to_str().unwrap();
+    SSTableWriter::new(path, 1, false, 0.01)?.finalize()?;
+
+    let reader = SSTableReader::open(path)?;
+    assert_eq!(reader.min_key(), None);
+    assert_eq!(reader.max_key(), None);
+    assert!(!reader.key_range_contains("a"));
+    assert!(!reader.key_range_overlaps(None, None));
+    Ok(())
+}
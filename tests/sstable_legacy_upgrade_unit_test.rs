@@ -0,0 +1,166 @@
+use lsmer::memtable::{Memtable, SSTableWriter as _, StringMemtable};
+use lsmer::sstable::upgrade::{format_version, upgrade_directory, upgrade_sstable};
+use lsmer::sstable::{SSTableReader, SSTableWriter, LEGACY_VERSION, VERSION};
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+/// Flush a memtable through the legacy layout, returning the file's path
+fn write_legacy(dir: &Path, entries: &[(&str, &[u8])]) -> String {
+    let memtable = StringMemtable::new(1024 * 1024);
+    for (key, value) in entries {
+        memtable.insert(key.to_string(), value.to_vec()).unwrap();
+    }
+    memtable.flush_to_sstable(dir.to_str().unwrap()).unwrap()
+}
+
+/// Rewrite the version field of a file's header
+fn set_version(path: &str, version: u32) {
+    let mut bytes = fs::read(path).unwrap();
+    bytes[8..12].copy_from_slice(&version.to_le_bytes());
+    fs::write(path, bytes).unwrap();
+}
+
+const ENTRIES: &[(&str, &[u8])] = &[
+    ("apple", b"red"),
+    ("banana", b"yellow"),
+    ("cherry", b""),
+    ("damson", b"purple"),
+];
+
+fn scan(path: &str) -> Vec<(String, Vec<u8>)> {
+    SSTableReader::open(path)
+        .unwrap()
+        .into_entries()
+        .unwrap()
+        .collect::<std::io::Result<_>>()
+        .unwrap()
+}
+
+fn expected() -> Vec<(String, Vec<u8>)> {
+    ENTRIES
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_vec()))
+        .collect()
+}
+
+#[test]
+fn test_reads_legacy_versions() {
+    for version in [LEGACY_VERSION, 2] {
+        let dir = tempdir().unwrap();
+        let path = write_legacy(dir.path(), ENTRIES);
+        set_version(&path, version);
+
+        let mut reader = SSTableReader::open(&path).unwrap();
+        assert_eq!(reader.version(), version);
+        assert_eq!(reader.entry_count(), ENTRIES.len() as u64);
+        assert!(!reader.has_bloom_filter());
+        assert_eq!(reader.file_number(), None);
+        for (key, value) in ENTRIES {
+            assert_eq!(reader.get(key).unwrap().as_deref(), Some(*value));
+        }
+        assert_eq!(reader.get("elderberry").unwrap(), None);
+
+        assert_eq!(scan(&path), expected());
+    }
+}
+
+#[test]
+fn test_rejects_version_zero() {
+    let dir = tempdir().unwrap();
+    let path = write_legacy(dir.path(), ENTRIES);
+    set_version(&path, 0);
+    assert!(SSTableReader::open(&path).is_err());
+}
+
+#[test]
+fn test_rejects_legacy_index_offset_past_end() {
+    let dir = tempdir().unwrap();
+    let path = write_legacy(dir.path(), ENTRIES);
+    let mut bytes = fs::read(&path).unwrap();
+    let len = bytes.len() as u64;
+    bytes[20..28].copy_from_slice(&(len + 1).to_le_bytes());
+    fs::write(&path, bytes).unwrap();
+    assert!(SSTableReader::open(&path).is_err());
+}
+
+#[test]
+fn test_legacy_index_serves_lookups_and_iteration() {
+    let dir = tempdir().unwrap();
+    let path = write_legacy(dir.path(), ENTRIES);
+
+    let reader = SSTableReader::open(&path).unwrap();
+    assert!(reader.has_key_index());
+    let iterated: Vec<_> = reader
+        .iter()
+        .unwrap()
+        .collect::<std::io::Result<_>>()
+        .unwrap();
+    assert_eq!(iterated, expected());
+
+    // An index that lost its last key, or points past the entries, is
+    // ignored and the file scanned
+    let mut lost_key = fs::read(&path).unwrap();
+    let last_key = ENTRIES.last().unwrap().0;
+    lost_key.truncate(lost_key.len() - (4 + last_key.len() + 8));
+    let mut past_end = fs::read(&path).unwrap();
+    let end = past_end.len();
+    past_end[end - 8..].copy_from_slice(&u64::MAX.to_le_bytes());
+    for bytes in [lost_key, past_end] {
+        fs::write(&path, bytes).unwrap();
+        let mut reader = SSTableReader::open(&path).unwrap();
+        assert!(!reader.has_key_index());
+        assert_eq!(reader.get("banana").unwrap(), Some(b"yellow".to_vec()));
+        assert_eq!(scan(&path), expected());
+    }
+}
+
+#[test]
+fn test_upgrade_rewrites_legacy_file() {
+    let dir = tempdir().unwrap();
+    let path = write_legacy(dir.path(), ENTRIES);
+    assert_eq!(
+        format_version(Path::new(&path)).unwrap(),
+        Some(LEGACY_VERSION)
+    );
+
+    assert!(upgrade_sstable(Path::new(&path)).unwrap());
+    assert_eq!(format_version(Path::new(&path)).unwrap(), Some(VERSION));
+    assert!(!Path::new(&format!("{}.upgrade", path)).exists());
+
+    let mut reader = SSTableReader::open(&path).unwrap();
+    assert!(reader.has_bloom_filter());
+    for (key, value) in ENTRIES {
+        assert!(reader.may_contain(key));
+        assert_eq!(reader.get(key).unwrap().as_deref(), Some(*value));
+    }
+    assert_eq!(scan(&path), expected());
+
+    // A current file is left alone
+    let modified = fs::metadata(&path).unwrap().modified().unwrap();
+    assert!(!upgrade_sstable(Path::new(&path)).unwrap());
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+}
+
+#[test]
+fn test_upgrade_directory() {
+    let dir = tempdir().unwrap();
+    let legacy = write_legacy(dir.path(), ENTRIES);
+
+    let current = dir.path().join("sstable_current.sst");
+    let mut writer = SSTableWriter::new(current.to_str().unwrap(), 1, true, 0.01).unwrap();
+    writer.write_entry("key", b"value").unwrap();
+    writer.finalize().unwrap();
+    fs::write(dir.path().join("notes.db"), b"not an sstable").unwrap();
+    fs::write(dir.path().join("MANIFEST"), b"manifest").unwrap();
+
+    let upgraded = upgrade_directory(dir.path()).unwrap();
+    assert_eq!(upgraded, vec![legacy.clone()]);
+    assert_eq!(scan(&legacy), expected());
+    assert_eq!(
+        fs::read(dir.path().join("notes.db")).unwrap(),
+        b"not an sstable"
+    );
+
+    assert!(upgrade_directory(dir.path()).unwrap().is_empty());
+}
@@ -0,0 +1,200 @@
+mod helpers;
+
+use helpers::{start_table, write_table};
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::{
+    Compression, FixedPrefixExtractor, RangeTombstone, SSTableReader, SSTableRecord, SSTableWriter,
+    Tombstone,
+};
+use std::io;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+fn entries(count: usize) -> Vec<(String, Vec<u8>)> {
+    (0..count)
+        .map(|i| {
+            (
+                format!("key{:05}", i),
+                format!("value-{}-{}", i, "x".repeat(i % 50)).into_bytes(),
+            )
+        })
+        .collect()
+}
+
+fn records(entries: &[(String, Vec<u8>)]) -> impl Iterator<Item = io::Result<SSTableRecord>> + '_ {
+    entries.iter().enumerate().map(|(i, (key, value))| {
+        Ok(SSTableRecord::Put {
+            key: key.clone(),
+            value: value.clone(),
+            written_at_ms: Some(i as u64),
+            expires_at_ms: None,
+            sequence: Some(i as u64 + 1),
+        })
+    })
+}
+
+type Configure = dyn Fn(&mut SSTableWriter) -> io::Result<()>;
+
+/// Write `entries` with each configuration applied, through `write_all` or
+/// `build_from_sorted_iter`
+fn write_entries(
+    path: &str,
+    entries: &[(String, Vec<u8>)],
+    configure: &Configure,
+    parallel: bool,
+) -> io::Result<()> {
+    let setup = |writer: &mut SSTableWriter| {
+        writer.set_block_size(512)?;
+        configure(writer)
+    };
+    if parallel {
+        let no_entries = std::iter::empty::<SSTableRecord>();
+        let (mut writer, _) = start_table(path, entries.len(), true, no_entries, setup)?;
+        writer.build_from_sorted_iter(records(entries))?;
+        writer.finalize()
+    } else {
+        write_table(path, entries.len(), true, records(entries), setup)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parallel_build_matches_write_all() -> io::Result<()> {
+    let dir = tempdir()?;
+    let entries = entries(5000);
+    let configurations: [&Configure; 5] = [
+        &|_| Ok(()),
+        &|writer| writer.set_compression(Compression::zstd(), None),
+        &|writer| {
+            writer.set_key_prefix_compression()?;
+            writer.set_hash_index()
+        },
+        &|writer| writer.set_block_filters(0.01),
+        &|writer| writer.set_partitioned_index(4, 0.01),
+    ];
+
+    for (i, configure) in configurations.iter().enumerate() {
+        let sequential = dir.path().join(format!("sequential{}.sst", i));
+        let parallel = dir.path().join(format!("parallel{}.sst", i));
+        let sequential = sequential.to_str().unwrap();
+        let parallel = parallel.to_str().unwrap();
+        write_entries(sequential, &entries, configure, false)?;
+        write_entries(parallel, &entries, configure, true)?;
+
+        let a = SSTableReader::open(sequential)?;
+        let mut b = SSTableReader::open(parallel)?;
+        assert_eq!(
+            a.content_digest(),
+            b.content_digest(),
+            "configuration {}",
+            i
+        );
+        assert_eq!(b.entry_count(), entries.len() as u64);
+        assert!(b.verify_all()?.is_intact());
+        assert_eq!(b.write_time("key04321"), Some(4321));
+        for (key, value) in entries.iter().step_by(97) {
+            assert!(b.may_contain(key));
+            assert_eq!(b.get(key)?.as_ref(), Some(value), "configuration {}", i);
+        }
+        assert_eq!(b.get("key99999")?, None);
+
+        let scanned: Vec<_> = b.into_entries()?.collect::<io::Result<_>>()?;
+        assert_eq!(scanned, entries);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parallel_build_records_tombstones_and_prefixes() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+    let extractor = FixedPrefixExtractor::new(5);
+
+    let mut writer = SSTableWriter::new(path, 100, true, 0.01)?;
+    writer.set_prefix_extractor(Arc::new(extractor));
+    writer.write_entry("apple", b"first")?;
+    let entries = entries(100);
+    let deleted = SSTableRecord::Delete {
+        key: "key00050x".to_string(),
+        tombstone: Tombstone {
+            deleted_at_ms: 7,
+            value: None,
+        },
+    };
+    let range = SSTableRecord::DeleteRange(RangeTombstone::new("m", "n", 9));
+    writer.build_from_sorted_iter(records(&entries).chain([Ok(deleted), Ok(range)]))?;
+    writer.finalize()?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(reader.entry_count(), 101);
+    assert_eq!(reader.get("apple")?, Some(b"first".to_vec()));
+    assert_eq!(reader.get("key00099")?, Some(entries[99].1.clone()));
+    assert!(reader.tombstones().contains_key("key00050x"));
+    assert!(reader.range_tombstones().covers("m5"));
+    assert!(reader.may_contain_prefix(&extractor, "key00"));
+    assert!(reader.may_contain_prefix(&extractor, "apple"));
+    Ok(())
+}
+
+#[test]
+fn test_parallel_build_refuses_unsorted_keys() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 10, true, 0.01)?;
+    let unsorted = [SSTableRecord::put("b", "1"), SSTableRecord::put("a", "2")];
+    let error = writer
+        .build_from_sorted_iter(unsorted.into_iter().map(Ok))
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+    // Keys must also follow those already written
+    let mut writer = SSTableWriter::new(path, 10, true, 0.01)?;
+    writer.write_entry("m", b"v")?;
+    let behind = [SSTableRecord::put("m", "again")];
+    assert!(
+        writer
+            .build_from_sorted_iter(behind.into_iter().map(Ok))
+            .is_err()
+    );
+
+    // Errors from the iterator stop the build
+    let mut writer = SSTableWriter::new(path, 10, true, 0.01)?;
+    let failing = [
+        Ok(SSTableRecord::put("a", "1")),
+        Err(io::Error::other("source failed")),
+    ];
+    assert_eq!(
+        writer.build_from_sorted_iter(failing).unwrap_err().kind(),
+        io::ErrorKind::Other
+    );
+    Ok(())
+}
+
+#[test]
+fn test_index_flush_builds_files_in_parallel() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap().to_string();
+    let options = LsmIndexOptions::default().with_block_size(256);
+    let index =
+        LsmIndex::new_with_options(4 * 1024 * 1024, path, None, true, 0.01, options).unwrap();
+    let entries = entries(2000);
+    for (key, value) in &entries {
+        index.insert(key.clone(), value.clone()).unwrap();
+    }
+    index.remove("key00007").unwrap();
+    index.flush().unwrap();
+
+    let files = index.list_sstables();
+    assert_eq!(files.len(), 1);
+    let reader = SSTableReader::open(&files[0].path).unwrap();
+    assert!(reader.verify_all().unwrap().is_intact());
+    assert!(reader.block_index().len() > 1);
+    assert_eq!(
+        index.get("key01999").unwrap(),
+        Some(entries[1999].1.clone())
+    );
+    assert_eq!(index.get("key00007").unwrap(), None);
+}
@@ -1,4 +1,4 @@
-use lsmer::sstable::{SSTableReader, SSTableWriter};
+use lsmer::sstable::{CompressionType, SSTableReader, SSTableWriter};
 use std::time::Instant;
 use tempfile::tempdir;
 
@@ -10,7 +10,7 @@ fn test_sstable_with_partitioned_bloom_filter() {
 
     // Create test data
     let test_data: Vec<(String, Vec<u8>)> = (0..1000)
-        .map(|i| (format!("key-{}", i), format!("value-{}", i).into_bytes()))
+        .map(|i| (format!("key-{:05}", i), format!("value-{}", i).into_bytes()))
         .collect();
 
     // Create SSTable writer with partitioned bloom filter
@@ -27,6 +27,7 @@ fn test_sstable_with_partitioned_bloom_filter() {
         true,            // Use bloom filter
         0.01,            // 1% false positive rate
         true,            // Use partitioned bloom
+        CompressionType::None,
     );
 
     assert!(writer_result.is_ok(), "Failed to create SSTable writer");
@@ -61,7 +62,7 @@ fn test_sstable_with_partitioned_bloom_filter() {
 
     // Check some keys that don't exist
     for i in 2000..2010 {
-        let key = format!("key-{}", i);
+        let key = format!("key-{:05}", i);
         // Some false positives are possible, but most should return false
         if reader.may_contain(&key) {
             println!("False positive for key: {}", key);
@@ -71,7 +72,7 @@ fn test_sstable_with_partitioned_bloom_filter() {
     // Test batch lookups
     let existing_keys: Vec<String> = test_data.iter().take(100).map(|(k, _)| k.clone()).collect();
 
-    let non_existing_keys: Vec<String> = (2000..2100).map(|i| format!("key-{}", i)).collect();
+    let non_existing_keys: Vec<String> = (2000..2100).map(|i| format!("key-{:05}", i)).collect();
 
     // Combine them to measure false positive rate
     let mixed_keys: Vec<String> = existing_keys
@@ -113,11 +114,11 @@ fn test_performance_comparison() {
 
     // Create test data
     let test_data: Vec<(String, Vec<u8>)> = (0..5000)
-        .map(|i| (format!("key-{}", i), format!("value-{}", i).into_bytes()))
+        .map(|i| (format!("key-{:05}", i), format!("value-{}", i).into_bytes()))
         .collect();
 
     // Create lookup batch
-    let lookup_keys: Vec<String> = (0..1000).map(|i| format!("key-{}", i)).collect();
+    let lookup_keys: Vec<String> = (0..1000).map(|i| format!("key-{:05}", i)).collect();
 
     // 1. Create standard bloom filter SSTable
     let standard_path = format!("{}/standard_bloom", temp_path);
@@ -137,6 +138,7 @@ fn test_performance_comparison() {
         true, // Use bloom filter
         0.01, // 1% false positive rate
         true, // Use partitioned bloom
+        CompressionType::None,
     )
     .unwrap();
 
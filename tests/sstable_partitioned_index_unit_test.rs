@@ -0,0 +1,149 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::sstable::{CompactionOptions, SSTableCompaction, SSTableReader, SSTableWriter};
+use std::io;
+use tempfile::tempdir;
+
+const VALUE: [u8; 200] = [7u8; 200];
+
+fn key(i: usize) -> String {
+    format!("key{:05}", i)
+}
+
+/// Keys with values large enough to span many data blocks
+fn entry(i: usize) -> (String, [u8; 200]) {
+    (key(i), VALUE)
+}
+
+#[test]
+fn test_partitioned_index_loads_partitions_on_demand() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 5000, true, (0..5000).map(entry), |writer| {
+        writer.set_partitioned_index(2, 0.01)
+    })?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert!(reader.has_partitioned_index());
+    assert!(reader.has_bloom_filter());
+    assert!(reader.bloom_filter().is_none());
+    assert_eq!(reader.resident_partitions(), 0);
+
+    // The top level spans every key
+    let spans = reader.block_index();
+    assert!(spans.len() > 2, "expected several partitions");
+    assert_eq!(spans[0].first_key, key(0));
+    assert_eq!(spans.last().unwrap().last_key, key(4999));
+    assert_eq!(spans.iter().map(|span| span.entry_count).sum::<u64>(), 5000);
+
+    let before = reader.memory_usage();
+    assert_eq!(reader.get(&key(1234))?, Some(VALUE.to_vec()));
+    assert_eq!(reader.resident_partitions(), 1);
+    assert!(reader.memory_usage() > before);
+
+    assert_eq!(reader.get(&key(0))?, Some(VALUE.to_vec()));
+    assert_eq!(reader.get(&key(4999))?, Some(VALUE.to_vec()));
+    assert_eq!(reader.get("key01234x")?, None);
+    assert_eq!(reader.get("zzz")?, None);
+    assert!(!reader.may_contain("aaa"));
+    assert!(reader.may_contain(&key(2500)));
+    Ok(())
+}
+
+#[test]
+fn test_resident_partitions_are_bounded() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 5000, true, (0..5000).map(entry), |writer| {
+        writer.set_partitioned_index(1, 0.01)
+    })?;
+
+    let reader = SSTableReader::open(path)?;
+    let partitions = reader.block_index().len();
+    for i in 0..5000 {
+        assert!(reader.may_contain(&key(i)));
+    }
+    assert!(reader.resident_partitions() < partitions);
+    assert!(reader.resident_partitions() > 0);
+    Ok(())
+}
+
+#[test]
+fn test_partitioned_index_keeps_range_scans() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 3000, true, (0..3000).map(entry), |writer| {
+        writer.set_partitioned_index(2, 0.01)
+    })?;
+
+    let keys: Vec<String> = SSTableReader::open(path)?
+        .into_range_entries(Some(&key(1000)), Some(&key(1010)))?
+        .map(|entry| entry.map(|(key, _)| key))
+        .collect::<io::Result<_>>()?;
+    assert_eq!(keys, (1000..1010).map(key).collect::<Vec<_>>());
+    Ok(())
+}
+
+#[test]
+fn test_unsorted_keys_fall_back_to_a_whole_file_filter() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 2000, true, (0..2000).rev().map(entry), |writer| {
+        writer.allow_unsorted_keys()?;
+        writer.set_partitioned_index(2, 0.01)
+    })?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert!(!reader.has_partitioned_index());
+    assert!(reader.bloom_filter().is_some());
+    assert_eq!(reader.get(&key(42))?, Some(VALUE.to_vec()));
+    Ok(())
+}
+
+#[test]
+fn test_compaction_writes_a_partitioned_index() -> io::Result<()> {
+    let dir = tempdir()?;
+    let first = dir.path().join("first.db").to_str().unwrap().to_string();
+    let second = dir.path().join("second.db").to_str().unwrap().to_string();
+    write_table(&first, 2000, true, (0..4000).step_by(2).map(entry), |_| {
+        Ok(())
+    })?;
+    write_table(&second, 2000, true, (1..4000).step_by(2).map(entry), |_| {
+        Ok(())
+    })?;
+    let output = dir.path().join("out.db");
+    let output = output.to_str().unwrap();
+
+    SSTableCompaction::compact_sstables_with_options(
+        &[first, second],
+        output,
+        &CompactionOptions::default().with_partitioned_index(4),
+    )?;
+
+    let mut reader = SSTableReader::open(output)?;
+    assert!(reader.has_partitioned_index());
+    assert_eq!(reader.entry_count(), 4000);
+    for i in [0, 1, 1999, 3998, 3999] {
+        assert_eq!(reader.get(&key(i))?, Some(VALUE.to_vec()));
+    }
+    assert_eq!(reader.get(&key(4000))?, None);
+    Ok(())
+}
+
+#[test]
+fn test_partitioning_after_writing_is_rejected() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let mut writer = SSTableWriter::new(path.to_str().unwrap(), 10, true, 0.01)?;
+    writer.write_entry("a", b"1")?;
+    assert_eq!(
+        writer.set_partitioned_index(2, 0.01).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    Ok(())
+}
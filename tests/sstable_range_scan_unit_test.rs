@@ -0,0 +1,171 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::lsm_index::{LsmIndex, ReadOptions};
+use lsmer::sstable::{SSTableReader, SSTableWriter};
+use std::fs;
+use std::io;
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+use tempfile::tempdir;
+
+const VALUE: [u8; 200] = [7u8; 200];
+
+fn key(i: usize) -> String {
+    format!("key{:05}", i)
+}
+
+/// Keys with values large enough to span several data blocks
+fn entry(i: usize) -> (String, [u8; 200]) {
+    (key(i), VALUE)
+}
+
+fn scan(path: &str, lower: Option<&str>, upper: Option<&str>) -> io::Result<Vec<String>> {
+    SSTableReader::open(path)?
+        .into_range_entries(lower, upper)?
+        .map(|entry| entry.map(|(key, _)| key))
+        .collect()
+}
+
+#[test]
+fn test_blocks_are_recorded_in_order() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 2000, false, (0..2000).map(entry), |_| Ok(()))?;
+
+    let reader = SSTableReader::open(path)?;
+    let blocks = reader.block_index();
+    assert!(
+        blocks.len() > 2,
+        "expected several blocks, got {}",
+        blocks.len()
+    );
+    assert_eq!(blocks[0].first_key, key(0));
+    assert_eq!(blocks.last().unwrap().last_key, key(1999));
+    assert_eq!(
+        blocks.iter().map(|block| block.entry_count).sum::<u64>(),
+        2000
+    );
+    assert!(blocks
+        .windows(2)
+        .all(|pair| pair[0].last_key < pair[1].first_key));
+    Ok(())
+}
+
+#[test]
+fn test_bounded_scan_returns_keys_in_bounds() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 2000, false, (0..2000).map(entry), |_| Ok(()))?;
+
+    let keys = scan(path, Some(&key(500)), Some(&key(1500)))?;
+    assert_eq!(keys, (500..1500).map(key).collect::<Vec<_>>());
+    assert_eq!(
+        scan(path, None, Some(&key(3)))?,
+        vec![key(0), key(1), key(2)]
+    );
+    assert_eq!(
+        scan(path, Some(&key(1998)), None)?,
+        vec![key(1998), key(1999)]
+    );
+    assert!(scan(path, Some("zzz"), None)?.is_empty());
+    assert_eq!(scan(path, None, None)?.len(), 2000);
+    Ok(())
+}
+
+#[test]
+fn test_bounded_scan_skips_blocks_outside_bounds() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(path, 2000, false, (0..2000).map(entry), |_| Ok(()))?;
+
+    let blocks = SSTableReader::open(path)?.block_index().to_vec();
+    let first = &blocks[0];
+    let last = blocks.last().unwrap();
+
+    // Corrupt a value in the first and last blocks
+    let mut bytes = fs::read(path)?;
+    for offset in [first.offset, last.offset] {
+        bytes[offset as usize + 4 + key(0).len() + 4] ^= 0xFF;
+    }
+    fs::write(path, bytes)?;
+
+    // Scans that stay clear of those blocks never read them
+    let lower = blocks[1].first_key.clone();
+    let upper = last.first_key.clone();
+    let keys = scan(path, Some(&lower), Some(&upper))?;
+    assert_eq!(keys.first(), Some(&lower));
+    assert!(keys.iter().all(|key| *key < upper));
+
+    // A full scan hits the corruption
+    assert!(scan(path, None, None).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_unsorted_tables_are_filtered() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_table(
+        path,
+        100,
+        false,
+        (0..100).rev().map(entry),
+        SSTableWriter::allow_unsorted_keys,
+    )?;
+
+    let reader = SSTableReader::open(path)?;
+    assert!(!reader.keys_sorted());
+    let mut keys = scan(path, Some(&key(10)), Some(&key(20)))?;
+    keys.sort();
+    assert_eq!(keys, (10..20).map(key).collect::<Vec<_>>());
+    Ok(())
+}
+
+#[test]
+fn test_index_range_is_narrowed_by_bounds() {
+    let dir = tempdir().unwrap();
+    let index = LsmIndex::new(
+        4 * 1024 * 1024,
+        dir.path().to_str().unwrap().to_string(),
+        None,
+        false,
+        0.01,
+    )
+    .unwrap();
+    for i in 0..10 {
+        index.insert(key(i), b"v".to_vec()).unwrap();
+    }
+
+    let range_keys = |range: (Bound<String>, Bound<String>), options: &ReadOptions| {
+        index
+            .range_with_options(range, options)
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>()
+    };
+
+    let options = ReadOptions::default()
+        .with_lower_bound(key(3))
+        .with_upper_bound(key(6));
+    assert_eq!(
+        range_keys((Unbounded, Unbounded), &options),
+        vec![key(3), key(4), key(5)]
+    );
+    assert_eq!(
+        range_keys((Excluded(key(3)), Included(key(8))), &options),
+        vec![key(4), key(5)]
+    );
+    assert_eq!(
+        range_keys((Included(key(1)), Included(key(4))), &options),
+        vec![key(3), key(4)]
+    );
+
+    // Bounds that don't overlap the range yield nothing
+    let disjoint = ReadOptions::default().with_lower_bound(key(8));
+    assert!(range_keys((Unbounded, Excluded(key(2))), &disjoint).is_empty());
+}
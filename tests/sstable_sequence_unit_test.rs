@@ -0,0 +1,90 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::lsm_index::LsmIndex;
+use lsmer::sstable::{SSTableCompaction, SSTableReader, SSTableRecord, SSTableWriter};
+use std::io;
+use tempfile::tempdir;
+
+fn write_sequenced(path: &str, entries: &[(&str, &str, u64)]) -> io::Result<()> {
+    let records = entries
+        .iter()
+        .map(|(key, value, sequence)| SSTableRecord::Put {
+            key: key.to_string(),
+            value: value.as_bytes().to_vec(),
+            written_at_ms: None,
+            expires_at_ms: None,
+            sequence: Some(*sequence),
+        });
+    write_table(path, entries.len(), false, records, |_| Ok(()))?;
+    Ok(())
+}
+
+#[test]
+fn test_sequences_round_trip() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    write_sequenced(path, &[("a", "1", 3), ("b", "2", 7)])?;
+
+    let reader = SSTableReader::open(path)?;
+    assert_eq!(reader.sequence("a"), Some(3));
+    assert_eq!(reader.sequence("b"), Some(7));
+    assert_eq!(reader.sequence("c"), None);
+    assert_eq!(reader.max_sequence(), Some(7));
+    Ok(())
+}
+
+#[test]
+fn test_tables_without_sequences_report_zero() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    let mut writer = SSTableWriter::new(path, 1, false, 0.01)?;
+    writer.write_entry("a", b"1")?;
+    writer.finalize()?;
+
+    let reader = SSTableReader::open(path)?;
+    assert_eq!(reader.sequence("a"), None);
+    assert_eq!(reader.max_sequence(), None);
+    Ok(())
+}
+
+#[test]
+fn test_compaction_keeps_the_highest_sequence_regardless_of_input_order() -> io::Result<()> {
+    let dir = tempdir()?;
+    let newer = dir.path().join("newer.db").to_str().unwrap().to_string();
+    let older = dir.path().join("older.db").to_str().unwrap().to_string();
+    let output = dir.path().join("out.db").to_str().unwrap().to_string();
+    write_sequenced(&newer, &[("a", "new", 10), ("b", "only-new", 11)])?;
+    write_sequenced(&older, &[("a", "old", 2), ("c", "only-old", 3)])?;
+
+    SSTableCompaction::compact_sstables(&[newer, older], &output, false, false, 0.01)?;
+
+    let mut reader = SSTableReader::open(&output)?;
+    assert_eq!(reader.get("a")?, Some(b"new".to_vec()));
+    assert_eq!(reader.get("b")?, Some(b"only-new".to_vec()));
+    assert_eq!(reader.get("c")?, Some(b"only-old".to_vec()));
+    assert_eq!(reader.sequence("a"), Some(10));
+    assert_eq!(reader.sequence("c"), Some(3));
+    assert_eq!(reader.max_sequence(), Some(11));
+    Ok(())
+}
+
+#[test]
+fn test_flushed_sstables_report_their_max_sequence() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap().to_string();
+    let index = LsmIndex::new(4 * 1024 * 1024, path.clone(), None, true, 0.01).unwrap();
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    index.insert("b".to_string(), b"2".to_vec()).unwrap();
+    index.flush().unwrap();
+
+    let infos = index.list_sstables();
+    assert_eq!(infos.len(), 1);
+    assert!(infos[0].max_sequence >= 2);
+    drop(index);
+
+    let reopened = LsmIndex::new(4 * 1024 * 1024, path, None, true, 0.01).unwrap();
+    assert_eq!(reopened.list_sstables()[0].max_sequence, infos[0].max_sequence);
+}
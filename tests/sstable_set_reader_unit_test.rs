@@ -0,0 +1,119 @@
+mod helpers;
+
+use helpers::start_table;
+use lsmer::sstable::{RangeTombstone, SSTableSetReader, SSTableWriter, Tombstone};
+use lsmer::MockClock;
+use std::io;
+use std::sync::Arc;
+use tempfile::{tempdir, TempDir};
+
+fn table_path(dir: &TempDir, name: &str) -> String {
+    dir.path().join(name).to_str().unwrap().to_string()
+}
+
+fn start_writing(path: &str, entries: &[(&str, &str)]) -> io::Result<SSTableWriter> {
+    let (writer, _) = start_table(path, entries.len().max(1), true, entries, |_| Ok(()))?;
+    Ok(writer)
+}
+
+#[test]
+fn test_later_files_shadow_earlier_ones() -> io::Result<()> {
+    let dir = tempdir()?;
+    let older = table_path(&dir, "older.db");
+    let newer = table_path(&dir, "newer.db");
+    start_writing(&older, &[("a", "old"), ("b", "kept"), ("c", "deleted")])?.finalize()?;
+    let mut writer = start_writing(&newer, &[("a", "new"), ("d", "added")])?;
+    writer.write_tombstone(
+        "c",
+        Tombstone {
+            deleted_at_ms: 1,
+            value: None,
+        },
+    );
+    writer.finalize()?;
+
+    let mut set = SSTableSetReader::open(&[older, newer])?;
+    assert_eq!(set.get("a")?, Some(b"new".to_vec()));
+    assert_eq!(set.get("b")?, Some(b"kept".to_vec()));
+    assert_eq!(set.get("c")?, None);
+    assert_eq!(set.get("missing")?, None);
+    assert_eq!(set.entry_count(), 5);
+
+    let all = set.scan(None, None)?;
+    assert_eq!(
+        all,
+        vec![
+            ("a".to_string(), b"new".to_vec()),
+            ("b".to_string(), b"kept".to_vec()),
+            ("d".to_string(), b"added".to_vec()),
+        ]
+    );
+    let bounded = set.scan(Some("b"), Some("d"))?;
+    assert_eq!(bounded, vec![("b".to_string(), b"kept".to_vec())]);
+    Ok(())
+}
+
+#[test]
+fn test_range_tombstones_hide_older_files_only() -> io::Result<()> {
+    let dir = tempdir()?;
+    let older = table_path(&dir, "older.db");
+    let newer = table_path(&dir, "newer.db");
+    start_writing(&older, &[("k1", "old"), ("k5", "old")])?.finalize()?;
+    let mut writer = start_writing(&newer, &[("k2", "own")])?;
+    writer.write_range_tombstone(RangeTombstone::new("k0", "k3", 1));
+    writer.finalize()?;
+
+    let mut set = SSTableSetReader::open(&[older, newer])?;
+    assert_eq!(set.get("k1")?, None);
+    assert_eq!(set.get("k2")?, Some(b"own".to_vec()));
+    assert_eq!(set.get("k5")?, Some(b"old".to_vec()));
+    assert_eq!(
+        set.scan(None, None)?,
+        vec![
+            ("k2".to_string(), b"own".to_vec()),
+            ("k5".to_string(), b"old".to_vec()),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_higher_sequences_win_over_file_order() -> io::Result<()> {
+    let dir = tempdir()?;
+    let newer = table_path(&dir, "newer.db");
+    let older = table_path(&dir, "older.db");
+    let mut writer = SSTableWriter::new(&newer, 1, false, 0.01)?;
+    writer.write_entry_with_sequence("a", b"new", 9)?;
+    writer.finalize()?;
+    let mut writer = SSTableWriter::new(&older, 1, false, 0.01)?;
+    writer.write_entry_with_sequence("a", b"old", 4)?;
+    writer.finalize()?;
+
+    let mut set = SSTableSetReader::open(&[newer, older])?;
+    assert_eq!(set.get("a")?, Some(b"new".to_vec()));
+    assert_eq!(
+        set.scan(None, None)?,
+        vec![("a".to_string(), b"new".to_vec())]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_expired_entries_hide_older_values() -> io::Result<()> {
+    let dir = tempdir()?;
+    let older = table_path(&dir, "older.db");
+    let newer = table_path(&dir, "newer.db");
+    start_writing(&older, &[("a", "old")])?.finalize()?;
+    let mut writer = SSTableWriter::new(&newer, 1, false, 0.01)?;
+    writer.write_entry_with_metadata("a", b"short-lived", Some(1), Some(1_000))?;
+    writer.finalize()?;
+
+    let clock = Arc::new(MockClock::new(500));
+    let mut set = SSTableSetReader::open(&[older, newer])?.with_clock(clock.clone());
+    assert_eq!(set.get("a")?, Some(b"short-lived".to_vec()));
+
+    clock.set(1_000);
+    assert_eq!(set.get("a")?, None);
+    assert!(set.scan(None, None)?.is_empty());
+    Ok(())
+}
@@ -0,0 +1,153 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::clock::MockClock;
+use lsmer::lsm_index::{LsmIndex, LsmIndexOptions};
+use lsmer::sstable::properties::{PROP_CHECKPOINT_ID, USER_PROPERTY_PREFIX};
+use lsmer::sstable::{CompactionOptions, SSTableCompaction, SSTableReader, SSTableWriter};
+use std::io;
+use std::sync::Arc;
+use tempfile::tempdir;
+
+const START_MS: u64 = 1_700_000_000_000;
+
+/// Write a one-entry file with flush number `flush_number` and the given
+/// user properties
+fn write_with_properties(
+    path: &str,
+    flush_number: u64,
+    user_properties: &[(&str, &str)],
+) -> io::Result<()> {
+    let entries = [(format!("key{}", flush_number), b"v")];
+    write_table(path, 1, false, entries, |writer| {
+        writer.set_flush_number(flush_number);
+        for (name, value) in user_properties {
+            writer.set_user_property(name, value)?;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[test]
+fn test_writer_records_properties() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 1, false, 0.0)?;
+    writer.set_created_at(1_700_000_000);
+    writer.set_level(2);
+    writer.set_checkpoint_id(17);
+    writer.set_user_property("schema_version", "3")?;
+    writer.set_user_property("owner", "billing")?;
+    writer.set_user_property("schema_version", "4")?;
+    assert_eq!(
+        writer.set_user_property("", "x").unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    writer.write_entry("a", b"1")?;
+    writer.finalize()?;
+
+    let reader = SSTableReader::open(path)?;
+    assert_eq!(reader.created_at_secs(), Some(1_700_000_000));
+    assert_eq!(reader.level(), Some(2));
+    assert_eq!(reader.checkpoint_id(), Some(17));
+    assert_eq!(reader.user_property("schema_version"), Some("4"));
+    assert_eq!(reader.user_property("missing"), None);
+    let user: Vec<_> = reader.properties().user_properties().collect();
+    assert_eq!(user, vec![("owner", "billing"), ("schema_version", "4")]);
+    assert_eq!(
+        reader
+            .properties()
+            .get(&format!("{}owner", USER_PROPERTY_PREFIX)),
+        Some("billing")
+    );
+
+    // Files written without them record none
+    let bare = dir.path().join("bare.sst");
+    write_with_properties(bare.to_str().unwrap(), 1, &[])?;
+    let reader = SSTableReader::open(bare.to_str().unwrap())?;
+    assert_eq!(reader.created_at_secs(), None);
+    assert_eq!(reader.level(), None);
+    assert_eq!(reader.checkpoint_id(), None);
+    assert_eq!(reader.properties().user_properties().count(), 0);
+    Ok(())
+}
+
+#[test]
+fn test_compaction_carries_user_properties_newest_first() -> io::Result<()> {
+    let dir = tempdir()?;
+    let mut inputs = Vec::new();
+    for (flush_number, properties) in [
+        (5, vec![("schema_version", "2"), ("owner", "billing")]),
+        (3, vec![("schema_version", "1"), ("region", "eu")]),
+    ] {
+        let path = dir.path().join(format!("{}.sst", flush_number));
+        let path = path.to_str().unwrap().to_string();
+        write_with_properties(&path, flush_number, &properties)?;
+        inputs.push(path);
+    }
+
+    let output = dir.path().join("output.sst");
+    let output = output.to_str().unwrap();
+    let options = CompactionOptions::default()
+        .with_clock(Arc::new(MockClock::new(START_MS)))
+        .with_level(1)
+        .with_user_property("owner", "ledger");
+    SSTableCompaction::compact_sstables_with_options(&inputs, output, &options)?;
+
+    let reader = SSTableReader::open(output)?;
+    let user: Vec<_> = reader.properties().user_properties().collect();
+    assert_eq!(
+        user,
+        vec![
+            ("owner", "ledger"),
+            ("region", "eu"),
+            ("schema_version", "2")
+        ]
+    );
+    assert_eq!(reader.level(), Some(1));
+    assert_eq!(reader.created_at_secs(), Some(START_MS / 1000));
+    Ok(())
+}
+
+#[test]
+fn test_index_stamps_flushed_and_compacted_files() {
+    let dir = tempdir().unwrap();
+    let clock = MockClock::new(START_MS);
+    let options = LsmIndexOptions::default()
+        .with_clock(clock.clone())
+        .with_user_property("schema_version", "7");
+    let index = LsmIndex::new_with_options(
+        4 * 1024 * 1024,
+        dir.path().to_str().unwrap().to_string(),
+        None,
+        false,
+        0.01,
+        options,
+    )
+    .unwrap();
+
+    for key in ["a", "b"] {
+        index.insert(key.to_string(), b"v".to_vec()).unwrap();
+        index.flush().unwrap();
+    }
+    let files = index.list_sstables();
+    for file in &files {
+        let reader = SSTableReader::open(&file.path).unwrap();
+        assert_eq!(reader.user_property("schema_version"), Some("7"));
+        assert_eq!(reader.created_at_secs(), Some(file.created_at_secs));
+        assert_eq!(reader.level(), Some(0));
+        assert!(reader.properties().get(PROP_CHECKPOINT_ID).is_some());
+    }
+    let first = SSTableReader::open(&files[0].path).unwrap();
+    let second = SSTableReader::open(&files[1].path).unwrap();
+    assert!(first.checkpoint_id() < second.checkpoint_id());
+
+    let summary = index.compact_range(..).unwrap();
+    let reader = SSTableReader::open(&summary.files_written[0]).unwrap();
+    assert_eq!(reader.user_property("schema_version"), Some("7"));
+    assert_eq!(reader.level(), Some(index.list_sstables()[0].level));
+    assert_eq!(reader.checkpoint_id(), None);
+}
@@ -0,0 +1,99 @@
+mod helpers;
+
+use helpers::write_table;
+use lsmer::sstable::{Compression, SSTableReader};
+use lsmer::wal::durability::{DurabilityError, DurabilityManager};
+use std::fs;
+use tempfile::tempdir;
+
+/// Write 100 entries to `path`, returning the offset of each
+fn write_entries(path: &str, compression: Option<Compression>) -> Vec<u64> {
+    let entries = (0..100).map(|i| (format!("key{:03}", i), format!("value{}", i).repeat(4)));
+    write_table(path, 100, true, entries, |writer| {
+        if let Some(compression) = compression {
+            writer.set_compression(compression, None)?;
+        }
+        writer.set_block_size(256)
+    })
+    .unwrap()
+}
+
+fn flip_byte(path: &str, offset: u64) {
+    let mut bytes = fs::read(path).unwrap();
+    bytes[offset as usize] ^= 0xff;
+    fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn test_intact_table_verifies() {
+    let dir = tempdir().unwrap();
+    for (name, compression) in [("plain.sst", None), ("zstd.sst", Some(Compression::zstd()))] {
+        let path = dir.path().join(name);
+        let path = path.to_str().unwrap();
+        write_entries(path, compression);
+
+        let report = SSTableReader::open(path).unwrap().verify_all().unwrap();
+        assert!(report.is_intact(), "{:?}", report);
+        assert_eq!(report.entries_checked, 100);
+        assert_eq!(report.problem, None);
+    }
+}
+
+#[test]
+fn test_reports_first_corrupt_entry() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+    let offsets = write_entries(path, None);
+
+    // Damage the value of entry 40, past its key and two length fields
+    flip_byte(path, offsets[40] + 4 + 6 + 4 + 1);
+    // and a later entry, which is never reached
+    flip_byte(path, offsets[70] + 4 + 6 + 4 + 1);
+
+    let report = SSTableReader::open(path).unwrap().verify_all().unwrap();
+    assert!(!report.is_intact());
+    assert_eq!(report.entries_checked, 40);
+    assert_eq!(report.first_corrupt_offset, Some(offsets[40]));
+    assert!(report.problem.is_some());
+}
+
+#[test]
+fn test_reports_damaged_checksum_table() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("table.sst");
+    let path = path.to_str().unwrap();
+    write_entries(path, None);
+
+    // The checksum table ends just before the footer
+    let table_end = fs::metadata(path).unwrap().len() - lsmer::sstable::FOOTER_SIZE as u64;
+    flip_byte(path, table_end - 4 * 3);
+
+    let report = SSTableReader::open(path).unwrap().verify_all().unwrap();
+    assert_eq!(report.entries_checked, 97);
+    assert_eq!(
+        report.problem.as_deref(),
+        Some("Entry does not match the checksum table")
+    );
+}
+
+#[test]
+fn test_durability_manager_checks_every_entry() {
+    let dir = tempdir().unwrap();
+    let sstable_dir = dir.path().join("sst");
+    let manager = DurabilityManager::new(
+        dir.path().join("wal").to_str().unwrap(),
+        sstable_dir.to_str().unwrap(),
+    )
+    .unwrap();
+    let path = sstable_dir.join("table.sst");
+    let path = path.to_str().unwrap();
+    let offsets = write_entries(path, None);
+    assert!(manager.verify_sstable_data_integrity(path).unwrap());
+
+    flip_byte(path, offsets[99] + 4 + 6 + 4);
+    assert!(matches!(
+        manager.verify_sstable_data_integrity(path),
+        Err(DurabilityError::DataCorruption(_))
+    ));
+}
@@ -0,0 +1,73 @@
+use lsmer::sstable::{RangeTombstone, SSTableReader, SSTableRecord, SSTableWriter, Tombstone};
+use std::io;
+use tempfile::tempdir;
+
+fn key(i: usize) -> String {
+    format!("key{:05}", i)
+}
+
+#[test]
+fn test_write_all_writes_entries_and_deletions() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    let mut writer = SSTableWriter::new(path, 1000, true, 0.01)?;
+    writer.set_block_size(256)?;
+    let puts = (0..1000).map(|i| Ok(SSTableRecord::put(key(i), i.to_string())));
+    let deletions = [
+        SSTableRecord::Put {
+            key: "timed".to_string(),
+            value: b"v".to_vec(),
+            written_at_ms: Some(5),
+            expires_at_ms: Some(9_999_999_999_999),
+            sequence: None,
+        },
+        SSTableRecord::Delete {
+            key: "gone".to_string(),
+            tombstone: Tombstone {
+                deleted_at_ms: 7,
+                value: None,
+            },
+        },
+        SSTableRecord::DeleteRange(RangeTombstone {
+            start: "x".to_string(),
+            end: "y".to_string(),
+            deleted_at_ms: 8,
+        }),
+    ];
+    writer.write_all(puts.chain(deletions.into_iter().map(Ok)))?;
+    writer.finalize()?;
+
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(reader.entry_count(), 1001);
+    assert!(reader.has_bloom_filter());
+    assert!(reader.keys_sorted());
+    assert_eq!(reader.get(&key(500))?, Some(b"500".to_vec()));
+    assert_eq!(reader.get("timed")?, Some(b"v".to_vec()));
+    assert_eq!(reader.write_times().get("timed"), Some(&5));
+    assert_eq!(reader.tombstones()["gone"].deleted_at_ms, 7);
+    assert_eq!(reader.range_tombstone_count(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_write_all_stops_at_the_first_error() -> io::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("table.db");
+    let path = path.to_str().unwrap();
+    let mut writer = SSTableWriter::new(path, 10, false, 0.01)?;
+    let records = vec![
+        Ok(SSTableRecord::put("a", "1")),
+        Err(io::Error::other("source failed")),
+        Ok(SSTableRecord::put("b", "2")),
+    ];
+    let err = writer.write_all(records).unwrap_err();
+    assert_eq!(err.to_string(), "source failed");
+
+    // Records before the error were written
+    writer.finalize()?;
+    let mut reader = SSTableReader::open(path)?;
+    assert_eq!(reader.entry_count(), 1);
+    assert_eq!(reader.get("a")?, Some(b"1".to_vec()));
+    Ok(())
+}
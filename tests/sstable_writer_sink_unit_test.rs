@@ -0,0 +1,72 @@
+use lsmer::sstable::{SSTableReader, SSTableWriter};
+use std::io::{self, Write};
+use tempfile::tempdir;
+
+fn write_entries<W: Write>(writer: &mut SSTableWriter<W>) {
+    for i in 0..500 {
+        writer
+            .write_entry(&format!("key{:04}", i), format!("value{}", i).as_bytes())
+            .unwrap();
+    }
+}
+
+/// Sink that only counts what passes through it, standing in for an upload
+struct CountingSink<W> {
+    inner: W,
+    bytes: usize,
+    flushes: usize,
+}
+
+impl<W: Write> Write for CountingSink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes += 1;
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn test_buffer_matches_file() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("file.sst");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 500, true, 0.01).unwrap();
+    write_entries(&mut writer);
+    writer.finalize().unwrap();
+
+    let mut writer = SSTableWriter::from_writer(Vec::new(), 500, true, 0.01, false).unwrap();
+    write_entries(&mut writer);
+    let bytes = writer.finish().unwrap();
+    assert_eq!(bytes, std::fs::read(path).unwrap());
+}
+
+#[test]
+fn test_streamed_table_reads_back() {
+    let dir = tempdir().unwrap();
+    let sink = CountingSink {
+        inner: Vec::new(),
+        bytes: 0,
+        flushes: 0,
+    };
+
+    let mut writer = SSTableWriter::from_writer(sink, 500, true, 0.01, false).unwrap();
+    writer.set_block_size(512).unwrap();
+    write_entries(&mut writer);
+    assert!(writer.offset().unwrap() > 0);
+    let sink = writer.finish().unwrap();
+    assert_eq!(sink.bytes, sink.inner.len());
+    assert_eq!(sink.flushes, 1);
+
+    let path = dir.path().join("streamed.sst");
+    std::fs::write(&path, &sink.inner).unwrap();
+    let mut reader = SSTableReader::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(reader.entry_count(), 500);
+    assert_eq!(reader.get("key0123").unwrap(), Some(b"value123".to_vec()));
+    assert_eq!(reader.get("missing").unwrap(), None);
+}
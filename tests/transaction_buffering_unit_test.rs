@@ -0,0 +1,192 @@
+use lsmer::memtable::{Memtable, StringMemtable};
+use lsmer::wal::durability::{DurabilityManager, KeyValuePair, Operation, RecoveryPhase};
+use std::fs;
+use tempfile::{tempdir, TempDir};
+
+struct Paths {
+    _dir: TempDir,
+    wal: String,
+    sstables: String,
+}
+
+fn paths() -> Paths {
+    let dir = tempdir().unwrap();
+    let wal = dir.path().join("wal.log").to_str().unwrap().to_string();
+    let sstables = dir.path().join("sstables").to_str().unwrap().to_string();
+    Paths {
+        _dir: dir,
+        wal,
+        sstables,
+    }
+}
+
+fn insert(key: &str) -> Operation {
+    Operation::Insert {
+        key: key.to_string(),
+        value: key.as_bytes().to_vec(),
+    }
+}
+
+fn has(memtable: &StringMemtable, key: &str) -> bool {
+    memtable.get(&key.to_string()).unwrap().is_some()
+}
+
+#[test]
+fn test_operations_are_logged_at_commit() {
+    let paths = paths();
+    let mut manager = DurabilityManager::new(&paths.wal, &paths.sstables).unwrap();
+    let tx_id = manager.begin_transaction().unwrap();
+
+    let before = fs::metadata(&paths.wal).unwrap().len();
+    manager.add_to_transaction(tx_id, insert("a")).unwrap();
+    manager.add_to_transaction(tx_id, insert("b")).unwrap();
+    assert_eq!(fs::metadata(&paths.wal).unwrap().len(), before);
+
+    manager.commit_transaction(tx_id).unwrap();
+    assert!(fs::metadata(&paths.wal).unwrap().len() > before);
+}
+
+#[test]
+fn test_commit_and_apply_stages_until_commit() {
+    let paths = paths();
+    let mut manager = DurabilityManager::new(&paths.wal, &paths.sstables).unwrap();
+    let mut memtable = StringMemtable::new(1024 * 1024);
+
+    let tx_id = manager.begin_transaction().unwrap();
+    manager.add_to_transaction(tx_id, insert("a")).unwrap();
+    manager
+        .add_to_transaction(
+            tx_id,
+            Operation::Remove {
+                key: "old".to_string(),
+            },
+        )
+        .unwrap();
+    memtable.insert("old".to_string(), vec![1]).unwrap();
+    assert!(!has(&memtable, "a"));
+
+    manager.commit_and_apply(tx_id, &mut memtable).unwrap();
+    assert!(has(&memtable, "a"));
+    assert!(!has(&memtable, "old"));
+}
+
+#[test]
+fn test_replay_honours_transaction_outcomes() {
+    let paths = paths();
+    {
+        let mut manager = DurabilityManager::new(&paths.wal, &paths.sstables).unwrap();
+
+        let committed = manager.begin_transaction().unwrap();
+        manager
+            .add_to_transaction(committed, insert("committed"))
+            .unwrap();
+        manager.commit_transaction(committed).unwrap();
+
+        let aborted = manager.begin_transaction().unwrap();
+        manager
+            .add_to_transaction(aborted, insert("aborted"))
+            .unwrap();
+        manager.abort_transaction(aborted).unwrap();
+
+        let prepared_aborted = manager.begin_transaction().unwrap();
+        manager
+            .add_to_transaction(prepared_aborted, insert("prepared_aborted"))
+            .unwrap();
+        manager.prepare_transaction(prepared_aborted).unwrap();
+        manager.abort_transaction(prepared_aborted).unwrap();
+
+        let prepared_committed = manager.begin_transaction().unwrap();
+        manager
+            .add_to_transaction(prepared_committed, insert("prepared_committed"))
+            .unwrap();
+        manager.prepare_transaction(prepared_committed).unwrap();
+        manager.commit_transaction(prepared_committed).unwrap();
+
+        // Prepared when the process stopped, so its outcome is unknown
+        let in_doubt = manager.begin_transaction().unwrap();
+        manager
+            .add_to_transaction(in_doubt, insert("in_doubt"))
+            .unwrap();
+        manager.prepare_transaction(in_doubt).unwrap();
+
+        // Never committed, so nothing of it was logged
+        let open = manager.begin_transaction().unwrap();
+        manager.add_to_transaction(open, insert("open")).unwrap();
+    }
+
+    let mut manager = DurabilityManager::new(&paths.wal, &paths.sstables).unwrap();
+    let memtable = manager.recover_from_crash().unwrap();
+    assert!(has(&memtable, "committed"));
+    assert!(has(&memtable, "prepared_committed"));
+    for key in ["aborted", "prepared_aborted", "in_doubt", "open"] {
+        assert!(!has(&memtable, key), "{} should not be replayed", key);
+    }
+}
+
+#[test]
+fn test_prepared_transactions_survive_a_checkpoint() {
+    let paths = paths();
+    {
+        let mut manager = DurabilityManager::new(&paths.wal, &paths.sstables).unwrap();
+
+        // Prepared before the checkpoint starts, so ahead of where replay
+        // starts
+        let before = manager.begin_transaction().unwrap();
+        manager
+            .add_to_transaction(before, insert("before"))
+            .unwrap();
+        manager.prepare_transaction(before).unwrap();
+
+        // Prepared while the checkpoint is written, so behind the point
+        // the WAL is truncated at
+        let checkpoint_id = manager.begin_checkpoint().unwrap();
+        let during = manager.begin_transaction().unwrap();
+        manager
+            .add_to_transaction(during, insert("during"))
+            .unwrap();
+        manager.prepare_transaction(during).unwrap();
+
+        let flushed = [KeyValuePair {
+            key: "flushed".to_string(),
+            value: b"flushed".to_vec(),
+        }];
+        let sstable_path = manager
+            .write_sstable_atomically(&flushed, checkpoint_id)
+            .unwrap();
+        manager.end_checkpoint(checkpoint_id).unwrap();
+        manager
+            .register_durable_checkpoint(checkpoint_id, &sstable_path)
+            .unwrap();
+
+        manager.commit_transaction(before).unwrap();
+        manager.commit_transaction(during).unwrap();
+    }
+
+    let mut manager = DurabilityManager::new(&paths.wal, &paths.sstables).unwrap();
+    let memtable = manager.recover_from_crash().unwrap();
+    for key in ["flushed", "before", "during"] {
+        assert!(has(&memtable, key), "{} should be recovered", key);
+    }
+}
+
+#[test]
+fn test_commit_without_prepare_fails_to_replay() {
+    let paths = paths();
+    {
+        let mut manager = DurabilityManager::new(&paths.wal, &paths.sstables).unwrap();
+        manager
+            .log_operation(Operation::TransactionCommit { id: 42 })
+            .unwrap();
+    }
+
+    let mut manager = DurabilityManager::new(&paths.wal, &paths.sstables).unwrap();
+    let mut failed = 0;
+    manager
+        .recover_from_crash_with_progress(|progress| {
+            if progress.phase == RecoveryPhase::ReplayingWal {
+                failed = progress.records_failed;
+            }
+        })
+        .unwrap();
+    assert_eq!(failed, 1);
+}
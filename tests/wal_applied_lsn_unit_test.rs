@@ -0,0 +1,117 @@
+use lsmer::lsm_index::LsmIndex;
+use lsmer::memtable::Memtable;
+use lsmer::sstable::{SSTableCompaction, SSTableReader, SSTableWriter};
+use lsmer::wal::durability::{DurabilityManager, Operation, RecoveryPhase};
+use lsmer::wal::WAL_HEADER_SIZE;
+use tempfile::tempdir;
+
+fn insert(key: &str) -> Operation {
+    Operation::Insert {
+        key: key.to_string(),
+        value: key.as_bytes().to_vec(),
+    }
+}
+
+#[test]
+fn test_applied_lsn_round_trips_through_properties() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lsn.db");
+    let path = path.to_str().unwrap();
+
+    let mut writer = SSTableWriter::new(path, 1, false, 0.0).unwrap();
+    writer.set_applied_lsn(4096);
+    writer.write_entry("a", b"1").unwrap();
+    writer.finalize().unwrap();
+    assert_eq!(SSTableReader::open(path).unwrap().applied_lsn(), Some(4096));
+
+    let plain = dir.path().join("plain.db");
+    let plain = plain.to_str().unwrap();
+    let mut writer = SSTableWriter::new(plain, 1, false, 0.0).unwrap();
+    writer.write_entry("b", b"2").unwrap();
+    writer.finalize().unwrap();
+    assert_eq!(SSTableReader::open(plain).unwrap().applied_lsn(), None);
+
+    // Compaction output reflects the newest of its inputs
+    let output = dir.path().join("merged.db");
+    let output = output.to_str().unwrap();
+    SSTableCompaction::compact_sstables(
+        &[path.to_string(), plain.to_string()],
+        output,
+        false,
+        false,
+        0.01,
+    )
+    .unwrap();
+    assert_eq!(
+        SSTableReader::open(output).unwrap().applied_lsn(),
+        Some(4096)
+    );
+}
+
+#[test]
+fn test_recovery_replays_only_past_the_applied_lsn() {
+    let dir = tempdir().unwrap();
+    let sstable_dir = dir.path().join("sstables");
+    let sstable_dir = sstable_dir.to_str().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let wal_path = wal_path.to_str().unwrap();
+
+    {
+        let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+        for key in ["k1", "k2", "k3"] {
+            manager.log_operation(insert(key)).unwrap();
+        }
+        // Writes the recovered state to a checkpoint SSTable
+        manager.recover_from_crash().unwrap();
+        manager.log_operation(insert("k4")).unwrap();
+    }
+
+    let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+    let checkpoint = manager.find_latest_complete_sstable().unwrap().unwrap();
+    let applied_lsn = SSTableReader::open(checkpoint.to_str().unwrap())
+        .unwrap()
+        .applied_lsn()
+        .unwrap();
+    assert!(applied_lsn > WAL_HEADER_SIZE);
+
+    let mut replayed = 0;
+    let memtable = manager
+        .recover_from_crash_with_progress(|progress| {
+            if progress.phase == RecoveryPhase::ReplayingWal {
+                replayed = progress.records_applied;
+            }
+        })
+        .unwrap();
+
+    // Only the write logged after the checkpoint is replayed
+    assert_eq!(replayed, 1);
+    for key in ["k1", "k2", "k3", "k4"] {
+        assert_eq!(
+            memtable.get(&key.to_string()).unwrap(),
+            Some(key.as_bytes().to_vec())
+        );
+    }
+}
+
+#[test]
+fn test_flush_records_applied_lsn_in_manifest() {
+    let dir = tempdir().unwrap();
+    let index = LsmIndex::new(
+        1024 * 1024,
+        dir.path().to_str().unwrap().to_string(),
+        None,
+        false,
+        0.01,
+    )
+    .unwrap();
+    assert_eq!(index.applied_lsn(), 0);
+
+    index.insert("a".to_string(), b"1".to_vec()).unwrap();
+    index.flush().unwrap();
+    let first = index.applied_lsn();
+    assert!(first > WAL_HEADER_SIZE);
+
+    index.insert("b".to_string(), b"2".to_vec()).unwrap();
+    index.flush().unwrap();
+    assert!(index.applied_lsn() > first);
+}
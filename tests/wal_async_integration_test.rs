@@ -1,4 +1,6 @@
-use lsmer::wal::{RecordType, WalError, WalRecord, WriteAheadLog, WAL_MAGIC, WAL_VERSION};
+use lsmer::wal::{
+    RecordType, WalError, WalRecord, WriteAheadLog, WAL_HEADER_SIZE, WAL_MAGIC, WAL_VERSION,
+};
 use std::fs;
 use std::io::{Read, Seek, SeekFrom};
 use std::time::Duration;
@@ -10,8 +12,9 @@ fn read_all_wal_records(wal: &mut WriteAheadLog) -> Result<Vec<WalRecord>, WalEr
     // Seek to beginning of file
     wal.file.seek(SeekFrom::Start(0))?;
 
-    // Skip the WAL header (8 bytes for magic + 4 bytes for version)
-    let mut header = [0u8; 12];
+    // Skip the WAL header (8 bytes for magic, 4 bytes for version and 1 byte
+    // for the checksum kind)
+    let mut header = [0u8; WAL_HEADER_SIZE as usize];
     if let Err(e) = wal.file.read_exact(&mut header) {
         return Err(WalError::IoError(e));
     }
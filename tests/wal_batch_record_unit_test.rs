@@ -0,0 +1,120 @@
+use lsmer::memtable::Memtable;
+use lsmer::wal::durability::{DurabilityManager, Operation};
+use lsmer::wal::RecordType;
+use std::fs::OpenOptions;
+use tempfile::tempdir;
+
+fn batch(keys: &[&str]) -> Vec<Operation> {
+    keys.iter()
+        .map(|key| Operation::Insert {
+            key: key.to_string(),
+            value: format!("value-{}", key).into_bytes(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_batch_record_round_trip() {
+    let mut operations = batch(&["a", "b"]);
+    operations.push(Operation::Remove {
+        key: "a".to_string(),
+    });
+    operations.push(Operation::Insert {
+        key: "empty".to_string(),
+        value: Vec::new(),
+    });
+    operations.push(Operation::Clear);
+
+    let record = Operation::Batch {
+        operations: operations.clone(),
+    }
+    .into_record();
+    assert_eq!(record.record_type, RecordType::Batch);
+    assert_eq!(RecordType::from_u8(10), RecordType::Batch);
+
+    match Operation::from_record(record).unwrap() {
+        Operation::Batch {
+            operations: decoded,
+        } => {
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", operations));
+        }
+        other => panic!("Expected a batch, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_damaged_batch_record_is_rejected() {
+    let mut record = Operation::Batch {
+        operations: batch(&["a", "b", "c"]),
+    }
+    .into_record();
+    record.data.truncate(record.data.len() - 3);
+    record.data[0] = 200;
+    assert!(Operation::from_record(record).is_err());
+}
+
+#[test]
+fn test_replay_applies_whole_batch() {
+    let dir = tempdir().unwrap();
+    let sstable_dir = dir.path().join("sstables");
+    let sstable_dir = sstable_dir.to_str().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let wal_path = wal_path.to_str().unwrap();
+
+    {
+        let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+        let mut operations = batch(&["k1", "k2", "k3"]);
+        operations.push(Operation::Remove {
+            key: "k2".to_string(),
+        });
+        manager.execute_batch(operations).unwrap();
+    }
+
+    let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+    let memtable = manager.recover_from_crash().unwrap();
+    assert_eq!(
+        memtable.get(&"k1".to_string()).unwrap(),
+        Some(b"value-k1".to_vec())
+    );
+    assert_eq!(memtable.get(&"k2".to_string()).unwrap(), None);
+    assert_eq!(
+        memtable.get(&"k3".to_string()).unwrap(),
+        Some(b"value-k3".to_vec())
+    );
+}
+
+#[test]
+fn test_torn_batch_applies_nothing() {
+    let dir = tempdir().unwrap();
+    let sstable_dir = dir.path().join("sstables");
+    let sstable_dir = sstable_dir.to_str().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let wal_path = wal_path.to_str().unwrap();
+
+    {
+        let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+        manager
+            .log_operation(Operation::Insert {
+                key: "before".to_string(),
+                value: b"kept".to_vec(),
+            })
+            .unwrap();
+        manager.execute_batch(batch(&["k1", "k2", "k3"])).unwrap();
+    }
+
+    // Cut the batch record short, as a crash part way through writing it would
+    let file = OpenOptions::new().write(true).open(wal_path).unwrap();
+    let len = file.metadata().unwrap().len();
+    file.set_len(len - 6).unwrap();
+    drop(file);
+
+    let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+    let memtable = manager.recover_from_crash().unwrap();
+    assert_eq!(
+        memtable.get(&"before".to_string()).unwrap(),
+        Some(b"kept".to_vec())
+    );
+    for key in ["k1", "k2", "k3"] {
+        assert_eq!(memtable.get(&key.to_string()).unwrap(), None);
+    }
+}
@@ -0,0 +1,134 @@
+use lsmer::wal::durability::{DurabilityManager, Operation, RecoveryPhase};
+use std::fs;
+use tempfile::tempdir;
+
+const CHUNK_BYTES: usize = 16 * 1024;
+
+fn value(i: usize) -> Vec<u8> {
+    vec![(i % 251) as u8; 1024]
+}
+
+/// Log a mix of writes whose values add up to many chunks
+fn log_operations(manager: &mut DurabilityManager, round: usize) {
+    for i in 0..300 {
+        manager
+            .log_operation_with_sync(
+                Operation::Insert {
+                    key: format!("key{:04}", i),
+                    value: value(i + round),
+                },
+                false,
+            )
+            .unwrap();
+    }
+    manager
+        .log_operation_with_sync(
+            Operation::Remove {
+                key: "key0005".to_string(),
+            },
+            false,
+        )
+        .unwrap();
+    manager
+        .log_operation_with_sync(
+            Operation::DeleteRange {
+                start: format!("key{:04}", 100 + round),
+                end: format!("key{:04}", 120 + round),
+            },
+            false,
+        )
+        .unwrap();
+    manager
+        .log_operation_with_sync(
+            Operation::Insert {
+                key: "key0110".to_string(),
+                value: b"rewritten".to_vec(),
+            },
+            false,
+        )
+        .unwrap();
+    manager.sync().unwrap();
+}
+
+#[test]
+fn test_bounded_recovery_matches_full_recovery() {
+    let full_dir = tempdir().unwrap();
+    let bounded_dir = tempdir().unwrap();
+    let mut managers: Vec<(String, String)> = Vec::new();
+    for dir in [&full_dir, &bounded_dir] {
+        let sstable_dir = dir.path().to_str().unwrap().to_string();
+        let wal_path = dir.path().join("wal.log").to_str().unwrap().to_string();
+        managers.push((wal_path, sstable_dir));
+    }
+
+    // Two rounds: the second starts from the first's recovery checkpoint
+    for round in 0..2 {
+        for (wal_path, sstable_dir) in &managers {
+            let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+            log_operations(&mut manager, round);
+        }
+
+        let (wal_path, sstable_dir) = &managers[0];
+        let memtable = DurabilityManager::new(wal_path, sstable_dir)
+            .unwrap()
+            .recover_from_crash()
+            .unwrap();
+
+        let (wal_path, sstable_dir) = &managers[1];
+        let mut phases = Vec::new();
+        let recovered = DurabilityManager::new(wal_path, sstable_dir)
+            .unwrap()
+            .recover_from_crash_bounded_with_progress(CHUNK_BYTES, |progress| {
+                phases.push(progress.phase)
+            })
+            .unwrap();
+        assert_eq!(phases.last(), Some(&RecoveryPhase::Complete));
+
+        let expected = memtable.iter().unwrap();
+        assert_eq!(recovered.len(), expected.len());
+        assert!(
+            recovered
+                .entries
+                .keys()
+                .eq(expected.iter().map(|(key, _)| key))
+        );
+        for (key, value) in &expected {
+            assert_eq!(recovered.value(key).unwrap().as_ref(), Some(value));
+        }
+        assert_eq!(recovered.value("key0005").unwrap(), None);
+        assert_eq!(
+            recovered.value("key0110").unwrap(),
+            Some(b"rewritten".to_vec())
+        );
+
+        // Every reference points into the checkpoint's SSTable, and the
+        // spills are gone
+        let sstable_path = recovered.sstable_path.as_ref().unwrap();
+        assert!(
+            recovered
+                .entries
+                .values()
+                .all(|reference| reference.file_path == sstable_path.to_string_lossy())
+        );
+        let leftovers = fs::read_dir(sstable_dir)
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with("recovery_spill_")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+}
+
+#[test]
+fn test_bounded_recovery_of_an_empty_log() {
+    let dir = tempdir().unwrap();
+    let sstable_dir = dir.path().to_str().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let mut manager = DurabilityManager::new(wal_path.to_str().unwrap(), sstable_dir).unwrap();
+
+    let recovered = manager.recover_from_crash_bounded(CHUNK_BYTES).unwrap();
+    assert!(recovered.is_empty());
+    assert!(recovered.sstable_path.is_none());
+}
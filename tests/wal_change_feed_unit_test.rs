@@ -0,0 +1,175 @@
+use lsmer::wal::change_feed::{ChangeFeed, OperationId};
+use lsmer::wal::durability::{DurabilityError, DurabilityManager, Operation};
+use std::fs::OpenOptions;
+use tempfile::tempdir;
+
+fn insert(key: &str) -> Operation {
+    Operation::Insert {
+        key: key.to_string(),
+        value: key.as_bytes().to_vec(),
+    }
+}
+
+fn keys(operations: &[Operation]) -> Vec<String> {
+    operations
+        .iter()
+        .map(|operation| match operation {
+            Operation::Insert { key, .. } | Operation::Remove { key } => key.clone(),
+            other => panic!("unexpected operation {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn test_changes_carry_lsn_and_transaction() {
+    let dir = tempdir().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let wal_path = wal_path.to_str().unwrap();
+    let mut manager =
+        DurabilityManager::new(wal_path, dir.path().join("sst").to_str().unwrap()).unwrap();
+
+    manager.insert("a".to_string(), b"1".to_vec()).unwrap();
+    manager
+        .execute_batch(vec![insert("b"), insert("c")])
+        .unwrap();
+    manager.remove("a").unwrap();
+
+    let mut feed = ChangeFeed::open(wal_path, "indexer").unwrap();
+    let changes = feed.poll().unwrap();
+    assert_eq!(changes.len(), 3);
+    assert_eq!(keys(&changes[0].operations), vec!["a"]);
+    assert_ne!(changes[0].id.tx_id, 0);
+    assert_eq!(keys(&changes[1].operations), vec!["b", "c"]);
+    assert_eq!(changes[1].id.tx_id, 0);
+    assert_eq!(keys(&changes[2].operations), vec!["a"]);
+    assert!(changes[2].id.tx_id > changes[0].id.tx_id);
+    assert!(
+        changes
+            .windows(2)
+            .all(|pair| pair[0].id.lsn < pair[1].id.lsn)
+    );
+
+    // Nothing new until more is logged
+    assert!(feed.poll().unwrap().is_empty());
+    manager.insert("d".to_string(), b"4".to_vec()).unwrap();
+    let changes = feed.poll().unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(keys(&changes[0].operations), vec!["d"]);
+}
+
+#[test]
+fn test_resume_after_ack_without_duplicates() {
+    let dir = tempdir().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let wal_path = wal_path.to_str().unwrap();
+    let mut manager =
+        DurabilityManager::new(wal_path, dir.path().join("sst").to_str().unwrap()).unwrap();
+    for key in ["a", "b", "c"] {
+        manager.insert(key.to_string(), b"v".to_vec()).unwrap();
+    }
+
+    let mut feed = ChangeFeed::open(wal_path, "indexer").unwrap();
+    assert_eq!(feed.acked(), None);
+    let changes = feed.poll().unwrap();
+    feed.ack(changes[1].id).unwrap();
+    // Acknowledging an earlier change leaves the acknowledgement where it is
+    feed.ack(changes[0].id).unwrap();
+    assert_eq!(feed.acked(), Some(changes[1].id));
+    drop(feed);
+
+    // The consumer crashed after acknowledging "b"
+    let mut feed = ChangeFeed::open(wal_path, "indexer").unwrap();
+    assert_eq!(feed.acked(), Some(changes[1].id));
+    let resumed = feed.poll().unwrap();
+    assert_eq!(resumed, vec![changes[2].clone()]);
+
+    // Other consumers keep their own position
+    let mut other = ChangeFeed::open(wal_path, "archiver").unwrap();
+    assert_eq!(other.poll().unwrap().len(), 3);
+}
+
+#[test]
+fn test_prepared_transaction_surfaces_at_commit() {
+    let dir = tempdir().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let wal_path = wal_path.to_str().unwrap();
+    let mut manager =
+        DurabilityManager::new(wal_path, dir.path().join("sst").to_str().unwrap()).unwrap();
+
+    let committed = manager.begin_transaction().unwrap();
+    manager.add_to_transaction(committed, insert("x")).unwrap();
+    manager.prepare_transaction(committed).unwrap();
+    let aborted = manager.begin_transaction().unwrap();
+    manager.add_to_transaction(aborted, insert("y")).unwrap();
+    manager.prepare_transaction(aborted).unwrap();
+    manager.insert("z".to_string(), b"1".to_vec()).unwrap();
+
+    let mut feed = ChangeFeed::open(wal_path, "indexer").unwrap();
+    let changes = feed.poll().unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(keys(&changes[0].operations), vec!["z"]);
+    feed.ack(changes[0].id).unwrap();
+    drop(feed);
+
+    // The prepare was read before the acknowledgement, the commit after it
+    manager.commit_transaction(committed).unwrap();
+    manager.abort_transaction(aborted).unwrap();
+    let mut feed = ChangeFeed::open(wal_path, "indexer").unwrap();
+    let changes = feed.poll().unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(keys(&changes[0].operations), vec!["x"]);
+    assert_eq!(changes[0].id.tx_id, committed);
+}
+
+#[test]
+fn test_truncated_log_is_reported() {
+    let dir = tempdir().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let wal_path = wal_path.to_str().unwrap();
+    let sst_dir = dir.path().join("sst");
+    let mut manager = DurabilityManager::new(wal_path, sst_dir.to_str().unwrap()).unwrap();
+    manager.insert("a".to_string(), b"1".to_vec()).unwrap();
+    let checkpoint_start = std::fs::metadata(wal_path).unwrap().len();
+    manager.insert("b".to_string(), b"2".to_vec()).unwrap();
+
+    let mut feed = ChangeFeed::open(wal_path, "indexer").unwrap();
+    let changes = feed.poll().unwrap();
+    feed.ack(changes[1].id).unwrap();
+
+    // Cut the log back and log something else over the same offsets
+    OpenOptions::new()
+        .write(true)
+        .open(wal_path)
+        .unwrap()
+        .set_len(checkpoint_start)
+        .unwrap();
+    manager.insert("c".to_string(), b"33".to_vec()).unwrap();
+
+    assert!(matches!(
+        feed.poll(),
+        Err(DurabilityError::RecoveryFailed(_))
+    ));
+    assert!(matches!(
+        ChangeFeed::open(wal_path, "indexer"),
+        Err(DurabilityError::RecoveryFailed(_))
+    ));
+}
+
+#[test]
+fn test_rejects_bad_consumer_and_unknown_ack() {
+    let dir = tempdir().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let wal_path = wal_path.to_str().unwrap();
+    DurabilityManager::new(wal_path, dir.path().join("sst").to_str().unwrap()).unwrap();
+
+    for name in ["", "..", "a/b"] {
+        assert!(matches!(
+            ChangeFeed::open(wal_path, name),
+            Err(DurabilityError::IoError(_))
+        ));
+    }
+
+    let mut feed = ChangeFeed::open(wal_path, "indexer").unwrap();
+    assert!(feed.ack(OperationId { lsn: 99, tx_id: 1 }).is_err());
+    assert_eq!(feed.acked(), None);
+}
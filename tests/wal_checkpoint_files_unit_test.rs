@@ -0,0 +1,133 @@
+use lsmer::memtable::Memtable;
+use lsmer::wal::durability::{CheckpointFile, DurabilityManager, KeyValuePair, Operation};
+use lsmer::wal::{RecordType, WalRecord};
+use tempfile::tempdir;
+
+fn checkpoint_file(path: &str, entry_count: u64) -> CheckpointFile {
+    CheckpointFile {
+        path: path.to_string(),
+        entry_count,
+        min_key: Some("a".to_string()),
+        max_key: None,
+    }
+}
+
+fn pairs(entries: &[(&str, &[u8])]) -> Vec<KeyValuePair> {
+    entries
+        .iter()
+        .map(|(key, value)| KeyValuePair {
+            key: key.to_string(),
+            value: value.to_vec(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_checkpoint_end_records_round_trip_files() {
+    let operation = Operation::CheckpointEnd {
+        id: 7,
+        files: vec![checkpoint_file("dir/one.db", 3), checkpoint_file("", 0)],
+    };
+    let record = operation.clone().into_record();
+    assert_eq!(record.record_type, RecordType::CheckpointEnd);
+    assert_eq!(Operation::from_record(record).unwrap(), operation);
+}
+
+#[test]
+fn test_legacy_checkpoint_end_records_have_no_files() {
+    let record = WalRecord::new(RecordType::CheckpointEnd, 7u64.to_be_bytes().to_vec());
+    assert_eq!(
+        Operation::from_record(record).unwrap(),
+        Operation::CheckpointEnd {
+            id: 7,
+            files: Vec::new()
+        }
+    );
+}
+
+#[test]
+fn test_malformed_checkpoint_files_are_rejected() {
+    let operation = Operation::CheckpointEnd {
+        id: 7,
+        files: vec![checkpoint_file("one.db", 3)],
+    };
+
+    let mut record = operation.clone().into_record();
+    record.data[8] = 99;
+    assert!(Operation::from_record(record).is_err());
+
+    let mut record = operation.clone().into_record();
+    record.data.pop();
+    assert!(Operation::from_record(record).is_err());
+
+    let mut record = operation.clone().into_record();
+    record.data.push(0);
+    assert!(Operation::from_record(record).is_err());
+
+    let mut record = operation.into_record();
+    record.data[9..13].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(Operation::from_record(record).is_err());
+}
+
+#[test]
+fn test_described_files_verify_until_they_change() {
+    let dir = tempdir().unwrap();
+    let sstable_dir = dir.path().join("sstables");
+    let wal_path = dir.path().join("wal.log");
+    let manager =
+        DurabilityManager::new(wal_path.to_str().unwrap(), sstable_dir.to_str().unwrap()).unwrap();
+
+    let path = manager
+        .write_sstable_atomically(&pairs(&[("b", b"2"), ("a", b"1"), ("c", b"3")]), 1)
+        .unwrap();
+    let file = manager.describe_checkpoint_file(&path).unwrap();
+    assert_eq!(file.entry_count, 3);
+    assert_eq!(file.min_key.as_deref(), Some("a"));
+    assert_eq!(file.max_key.as_deref(), Some("c"));
+    assert!(manager.verify_checkpoint_file(&file));
+
+    let mut claimed = file.clone();
+    claimed.entry_count += 1;
+    assert!(!manager.verify_checkpoint_file(&claimed));
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(!manager.verify_checkpoint_file(&file));
+}
+
+#[test]
+fn test_recovery_passes_over_files_that_do_not_match_their_checkpoint() {
+    let dir = tempdir().unwrap();
+    let sstable_dir = dir.path().join("sstables");
+    let sstable_dir = sstable_dir.to_str().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let wal_path = wal_path.to_str().unwrap();
+
+    {
+        let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+
+        let first = manager.begin_checkpoint().unwrap();
+        let path = manager
+            .write_sstable_atomically(&pairs(&[("a", b"old")]), first)
+            .unwrap();
+        let file = manager.describe_checkpoint_file(&path).unwrap();
+        manager.end_checkpoint_with_files(first, vec![file]).unwrap();
+
+        // The second checkpoint's record says more was written than the
+        // file holds, as if the file were left incomplete
+        let second = manager.begin_checkpoint().unwrap();
+        let path = manager
+            .write_sstable_atomically(&pairs(&[("a", b"new"), ("b", b"new")]), second)
+            .unwrap();
+        let mut file = manager.describe_checkpoint_file(&path).unwrap();
+        file.entry_count += 1;
+        manager.end_checkpoint_with_files(second, vec![file]).unwrap();
+    }
+
+    let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+    let memtable = manager.recover_from_crash().unwrap();
+    assert_eq!(
+        memtable.get(&"a".to_string()).unwrap(),
+        Some(b"old".to_vec())
+    );
+    assert_eq!(memtable.get(&"b".to_string()).unwrap(), None);
+}
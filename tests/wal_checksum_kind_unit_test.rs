@@ -0,0 +1,153 @@
+use lsmer::memtable::Memtable;
+use lsmer::sstable::ChecksumKind;
+use lsmer::wal::durability::{DurabilityManager, Operation};
+use lsmer::wal::{
+    RecordType, WAL_CHECKSUM_KIND_VERSION, WAL_HEADER_SIZE, WAL_MAGIC, WAL_V1_HEADER_SIZE,
+    WalRecord, WriteAheadLog,
+};
+use std::fs;
+use std::io::{Seek, SeekFrom};
+use tempfile::tempdir;
+
+const KINDS: [ChecksumKind; 5] = [
+    ChecksumKind::Crc32,
+    ChecksumKind::Crc32c,
+    ChecksumKind::Xxh3,
+    ChecksumKind::XxHash64,
+    ChecksumKind::None,
+];
+
+fn record(i: u32) -> WalRecord {
+    WalRecord::new(RecordType::Insert, format!("record{}", i).into_bytes())
+}
+
+/// Every record from the start of the log
+fn read_all(wal: &mut WriteAheadLog) -> Vec<Vec<u8>> {
+    wal.file.seek(SeekFrom::Start(wal.data_start())).unwrap();
+    let mut records = Vec::new();
+    while let Some(record) = wal.read_next_record().unwrap() {
+        records.push(record.data);
+    }
+    records
+}
+
+#[test]
+fn test_each_kind_is_recorded_in_the_header_and_kept_on_reopen() {
+    let dir = tempdir().unwrap();
+    for kind in KINDS {
+        let path = dir.path().join(format!("{:?}.log", kind));
+        let path = path.to_str().unwrap();
+        {
+            let mut wal = WriteAheadLog::new_with_checksum_kind(path, kind).unwrap();
+            for i in 0..3 {
+                wal.append_and_sync(record(i)).unwrap();
+            }
+        }
+
+        let bytes = fs::read(path).unwrap();
+        assert_eq!(bytes[..8], WAL_MAGIC.to_le_bytes());
+        assert_eq!(
+            u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            WAL_CHECKSUM_KIND_VERSION
+        );
+        assert_eq!(bytes[12], kind.id());
+
+        // Reopening with another kind keeps the recorded one
+        let mut wal = WriteAheadLog::new(path).unwrap();
+        assert_eq!(wal.checksum_kind(), kind);
+        assert_eq!(wal.data_start(), WAL_HEADER_SIZE);
+        wal.append_and_sync(record(3)).unwrap();
+        let expected: Vec<Vec<u8>> = (0..4).map(|i| record(i).data).collect();
+        assert_eq!(read_all(&mut wal), expected, "{:?}", kind);
+    }
+}
+
+#[test]
+fn test_version_1_logs_are_read_as_crc32() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("v1.log");
+    let path = path.to_str().unwrap();
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&WAL_MAGIC.to_le_bytes());
+    bytes.extend_from_slice(&1u32.to_le_bytes());
+    bytes.extend_from_slice(&record(0).serialize().unwrap());
+    fs::write(path, bytes).unwrap();
+
+    let mut wal = WriteAheadLog::new_with_checksum_kind(path, ChecksumKind::Xxh3).unwrap();
+    assert_eq!(wal.checksum_kind(), ChecksumKind::Crc32);
+    assert_eq!(wal.data_start(), WAL_V1_HEADER_SIZE);
+    wal.append_and_sync(record(1)).unwrap();
+    assert_eq!(read_all(&mut wal), vec![record(0).data, record(1).data]);
+}
+
+#[test]
+fn test_damage_is_detected_unless_records_are_unchecked() {
+    let dir = tempdir().unwrap();
+    for kind in KINDS {
+        let path = dir.path().join(format!("{:?}.log", kind));
+        let path = path.to_str().unwrap();
+        let mut wal = WriteAheadLog::new_with_checksum_kind(path, kind).unwrap();
+        wal.append_and_sync(record(0)).unwrap();
+        drop(wal);
+
+        // Flip a byte of the record's data, past its type and length
+        let mut bytes = fs::read(path).unwrap();
+        bytes[WAL_HEADER_SIZE as usize + 5 + 2] ^= 0xff;
+        fs::write(path, bytes).unwrap();
+
+        let mut wal = WriteAheadLog::new(path).unwrap();
+        wal.file.seek(SeekFrom::Start(wal.data_start())).unwrap();
+        let read = wal.read_next_record();
+        if kind == ChecksumKind::None {
+            assert_ne!(read.unwrap().unwrap().data, record(0).data);
+        } else {
+            assert!(read.is_err(), "{:?}", kind);
+        }
+    }
+}
+
+#[test]
+fn test_manager_logs_and_recovers_with_its_kind() {
+    let dir = tempdir().unwrap();
+    let sstable_dir = dir.path().join("sstables");
+    let sstable_dir = sstable_dir.to_str().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let wal_path = wal_path.to_str().unwrap();
+
+    {
+        let mut manager = DurabilityManager::new_with_checksum_kind(
+            wal_path,
+            sstable_dir,
+            ChecksumKind::XxHash64,
+        )
+        .unwrap();
+        manager
+            .log_operation(Operation::Insert {
+                key: "k1".to_string(),
+                value: b"v1".to_vec(),
+            })
+            .unwrap();
+    }
+
+    let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+    let memtable = manager.recover_from_crash().unwrap();
+    assert_eq!(
+        memtable.get(&"k1".to_string()).unwrap(),
+        Some(b"v1".to_vec())
+    );
+    assert_eq!(
+        WriteAheadLog::new(wal_path).unwrap().checksum_kind(),
+        ChecksumKind::XxHash64
+    );
+}
+
+#[test]
+fn test_record_serialization_round_trips_with_each_kind() {
+    for kind in KINDS {
+        let bytes = record(7).serialize_with(kind).unwrap();
+        let decoded = WalRecord::deserialize_with(&bytes, kind).unwrap();
+        assert_eq!(decoded.data, record(7).data);
+    }
+    let bytes = record(7).serialize_with(ChecksumKind::Crc32c).unwrap();
+    assert!(WalRecord::deserialize_with(&bytes, ChecksumKind::Crc32).is_err());
+}
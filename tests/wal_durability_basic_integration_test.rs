@@ -156,15 +156,19 @@ async fn test_operation_conversion() {
         }
 
         // CheckpointEnd operation
-        let checkpoint_end_op = Operation::CheckpointEnd { id: 42 };
+        let checkpoint_end_op = Operation::CheckpointEnd {
+            id: 42,
+            files: Vec::new(),
+        };
         let record = checkpoint_end_op.into_record();
         assert_eq!(record.record_type, RecordType::CheckpointEnd);
 
         // Convert back
         let recovered_op = Operation::from_record(record).unwrap();
         match recovered_op {
-            Operation::CheckpointEnd { id } => {
+            Operation::CheckpointEnd { id, files } => {
                 assert_eq!(id, 42);
+                assert!(files.is_empty());
             }
             _ => panic!("Wrong operation type"),
         }
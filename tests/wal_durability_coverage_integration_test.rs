@@ -196,9 +196,9 @@ async fn test_extract_checkpoint_id() {
         for path in paths_to_try {
             println!("Testing path: {:?}", path);
             let result = durability_manager.extract_checkpoint_id(&path);
-            if result.is_ok() {
+            if let Ok(extracted_id) = result {
                 println!("Path format accepted: {:?}", path);
-                assert_eq!(result.unwrap(), checkpoint_id);
+                assert_eq!(extracted_id, checkpoint_id);
                 // If we found a working format, we're done
                 break;
             } else {
@@ -270,16 +270,14 @@ async fn test_find_sstables_and_latest() {
 
         // Now test find_sstables
         let result = durability_manager.find_sstables();
-        if result.is_ok() {
-            let found_sstables = result.unwrap();
+        if let Ok(found_sstables) = result {
             // The implementation might filter differently, but we should find some files
             println!("Found {} sstable files", found_sstables.len());
         }
 
         // Test find_latest_complete_sstable
         let result = durability_manager.find_latest_complete_sstable();
-        if result.is_ok() {
-            let latest = result.unwrap();
+        if let Ok(latest) = result {
             println!("Latest sstable: {:?}", latest);
         }
     };
@@ -0,0 +1,157 @@
+use lsmer::wal::durability::{
+    DurabilityError, DurabilityLevel, DurabilityManager, Operation, WalReplicator,
+};
+use lsmer::wal::{RecordType, WriteAheadLog, WAL_HEADER_SIZE};
+use std::fs;
+use std::io::{self, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+use tempfile::{tempdir, TempDir};
+
+fn manager() -> (TempDir, String, DurabilityManager) {
+    let dir = tempdir().unwrap();
+    let wal_path = dir.path().join("wal.log").to_str().unwrap().to_string();
+    let sstable_dir = dir.path().join("sstables");
+    let manager = DurabilityManager::new(&wal_path, sstable_dir.to_str().unwrap()).unwrap();
+    (dir, wal_path, manager)
+}
+
+fn wal_len(wal_path: &str) -> u64 {
+    fs::metadata(wal_path).unwrap().len()
+}
+
+fn record_types(wal_path: &str) -> Vec<RecordType> {
+    let mut wal = WriteAheadLog::new(wal_path).unwrap();
+    wal.file.seek(SeekFrom::Start(WAL_HEADER_SIZE)).unwrap();
+    let mut types = Vec::new();
+    while let Ok(Some(record)) = wal.read_next_record() {
+        types.push(record.record_type);
+    }
+    types
+}
+
+#[derive(Debug, Default)]
+struct RecordingReplicator {
+    shipped: Arc<Mutex<Vec<u64>>>,
+}
+
+impl WalReplicator for RecordingReplicator {
+    fn replicate(&self, _wal_path: &str, lsn: u64) -> io::Result<()> {
+        self.shipped.lock().unwrap().push(lsn);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_levels_are_ordered() {
+    assert!(DurabilityLevel::Memory < DurabilityLevel::WalWritten);
+    assert!(DurabilityLevel::WalWritten < DurabilityLevel::WalSynced);
+    assert!(DurabilityLevel::WalSynced < DurabilityLevel::Replicated);
+}
+
+#[test]
+fn test_memory_commit_is_written_by_the_next_record() {
+    let (_dir, wal_path, mut manager) = manager();
+    let tx_id = manager.begin_transaction().unwrap();
+    manager
+        .add_to_transaction(
+            tx_id,
+            Operation::Insert {
+                key: "k".to_string(),
+                value: b"v".to_vec(),
+            },
+        )
+        .unwrap();
+
+    let before = wal_len(&wal_path);
+    manager
+        .commit_with_durability(tx_id, DurabilityLevel::Memory)
+        .unwrap();
+    assert_eq!(wal_len(&wal_path), before);
+
+    // A later record written out carries the buffered commit ahead of it
+    manager
+        .log_operation_with_durability(Operation::Clear, DurabilityLevel::WalWritten)
+        .unwrap();
+    assert_eq!(
+        record_types(&wal_path),
+        vec![
+            RecordType::TransactionBegin,
+            RecordType::Batch,
+            RecordType::Clear,
+        ]
+    );
+}
+
+#[test]
+fn test_sync_writes_out_memory_records() {
+    let (_dir, wal_path, mut manager) = manager();
+    manager
+        .log_operation_with_durability(Operation::Clear, DurabilityLevel::Memory)
+        .unwrap();
+    assert!(record_types(&wal_path).is_empty());
+
+    manager.sync().unwrap();
+    assert_eq!(record_types(&wal_path), vec![RecordType::Clear]);
+}
+
+#[test]
+fn test_replicated_commit_waits_on_the_replicator() {
+    let (_dir, wal_path, manager) = manager();
+    let replicator = RecordingReplicator::default();
+    let shipped = replicator.shipped.clone();
+    let mut manager = manager.with_replicator(replicator);
+
+    let tx_id = manager.begin_transaction().unwrap();
+    manager
+        .commit_with_durability(tx_id, DurabilityLevel::Replicated)
+        .unwrap();
+    assert_eq!(*shipped.lock().unwrap(), vec![wal_len(&wal_path)]);
+}
+
+#[derive(Debug)]
+struct FailingReplicator;
+
+impl WalReplicator for FailingReplicator {
+    fn replicate(&self, _wal_path: &str, _lsn: u64) -> io::Result<()> {
+        Err(io::Error::other("replica unreachable"))
+    }
+}
+
+#[test]
+fn test_replicated_commit_needs_a_replicator() {
+    let (_dir, wal_path, mut manager) = manager();
+    let tx_id = manager.begin_transaction().unwrap();
+    let before = fs::read(&wal_path).unwrap();
+    assert!(matches!(
+        manager.commit_with_durability(tx_id, DurabilityLevel::Replicated),
+        Err(DurabilityError::ReplicatorNotConfigured)
+    ));
+    assert!(matches!(
+        manager.log_operation_with_durability(Operation::Clear, DurabilityLevel::Replicated),
+        Err(DurabilityError::ReplicatorNotConfigured)
+    ));
+    assert_eq!(fs::read(&wal_path).unwrap(), before);
+
+    // Nothing was logged, so the transaction can still commit
+    manager.commit_transaction(tx_id).unwrap();
+}
+
+#[test]
+fn test_commit_stays_committed_when_replication_fails() {
+    let (_dir, wal_path, manager) = manager();
+    let mut manager = manager.with_replicator(FailingReplicator);
+    let tx_id = manager.begin_transaction().unwrap();
+    assert!(
+        manager
+            .commit_with_durability(tx_id, DurabilityLevel::Replicated)
+            .is_err()
+    );
+    assert_eq!(
+        record_types(&wal_path),
+        vec![RecordType::TransactionBegin, RecordType::Batch]
+    );
+    assert!(matches!(
+        manager.commit_transaction(tx_id),
+        Err(DurabilityError::TransactionAlreadyCommitted(id)) if id == tx_id
+    ));
+}
@@ -0,0 +1,85 @@
+use lsmer::memtable::Memtable;
+use lsmer::wal::durability::{DurabilityManager, Operation};
+use lsmer::wal::{RecordType, WalRecord, WriteAheadLog};
+use tempfile::tempdir;
+
+fn insert(key: &str, value: &[u8]) -> Operation {
+    Operation::Insert {
+        key: key.to_string(),
+        value: value.to_vec(),
+    }
+}
+
+/// An Insert record as logs written before payloads were versioned hold it
+fn legacy_insert(key: &str, value: &[u8]) -> WalRecord {
+    let mut data = key.as_bytes().to_vec();
+    data.push(0);
+    data.extend_from_slice(value);
+    WalRecord::new(RecordType::Insert, data)
+}
+
+#[test]
+fn test_insert_records_round_trip_any_bytes() {
+    for (key, value) in [
+        ("key", &b"value"[..]),
+        ("nul\0in\0key", b"v"),
+        ("k", b"nul\0in\0value"),
+        ("", b""),
+        ("\u{00ff}unicode", &[0xff, 0x00, 0xff]),
+    ] {
+        let record = insert(key, value).into_record();
+        assert_eq!(record.record_type, RecordType::Insert);
+        assert_eq!(Operation::from_record(record).unwrap(), insert(key, value));
+    }
+}
+
+#[test]
+fn test_legacy_insert_records_still_decode() {
+    for (key, value) in [("key", &b"value"[..]), ("", b"empty key"), ("k", b"")] {
+        let operation = Operation::from_record(legacy_insert(key, value)).unwrap();
+        assert_eq!(operation, insert(key, value));
+    }
+}
+
+#[test]
+fn test_malformed_insert_records_are_rejected() {
+    let mut record = insert("key", b"value").into_record();
+    record.data[1] = 99;
+    assert!(Operation::from_record(record).is_err());
+
+    let mut record = insert("key", b"value").into_record();
+    record.data.truncate(5);
+    assert!(Operation::from_record(record).is_err());
+
+    let mut record = insert("key", b"value").into_record();
+    record.data[2..6].copy_from_slice(&1000u32.to_le_bytes());
+    assert!(Operation::from_record(record).is_err());
+}
+
+#[test]
+fn test_replay_reads_legacy_and_versioned_inserts() {
+    let dir = tempdir().unwrap();
+    let sstable_dir = dir.path().join("sstables");
+    let sstable_dir = sstable_dir.to_str().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let wal_path = wal_path.to_str().unwrap();
+
+    {
+        let mut wal = WriteAheadLog::new(wal_path).unwrap();
+        wal.append_and_sync(legacy_insert("old", b"legacy"))
+            .unwrap();
+        wal.append_and_sync(insert("new\0key", b"versioned").into_record())
+            .unwrap();
+    }
+
+    let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+    let memtable = manager.recover_from_crash().unwrap();
+    assert_eq!(
+        memtable.get(&"old".to_string()).unwrap(),
+        Some(b"legacy".to_vec())
+    );
+    assert_eq!(
+        memtable.get(&"new\0key".to_string()).unwrap(),
+        Some(b"versioned".to_vec())
+    );
+}
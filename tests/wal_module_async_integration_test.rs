@@ -84,12 +84,12 @@ async fn test_wal_error_handling() {
         let mut wal = WriteAheadLog::new(corrupt_path.to_str().unwrap()).unwrap();
 
         // Try to read a record - should fail with an I/O error when trying to read from the corrupted file
-        wal.file.seek(SeekFrom::Start(12)).unwrap(); // Skip header (8 bytes for magic + 4 bytes for version)
+        wal.file.seek(SeekFrom::Start(wal.data_start())).unwrap(); // Skip header
         let result = wal.read_next_record();
 
-        // The actual implementation returns an UnexpectedEof error, not an InvalidRecord error
+        // The record's length field runs past the end of the file, so it is rejected as InvalidData
         match result {
-            Err(WalError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            Err(WalError::IoError(e)) if e.kind() == std::io::ErrorKind::InvalidData => {
                 // This is the expected error
             }
             Ok(_) => {
@@ -217,7 +217,7 @@ async fn test_wal_record_reading() {
         let mut wal = WriteAheadLog::new(wal_path.to_str().unwrap()).unwrap();
 
         // Seek to beginning (after header)
-        wal.file.seek(SeekFrom::Start(12)).unwrap(); // Skip header (8 bytes for magic + 4 bytes for version)
+        wal.file.seek(SeekFrom::Start(wal.data_start())).unwrap(); // Skip header
 
         // Read all records
         let mut records = Vec::new();
@@ -0,0 +1,80 @@
+use lsmer::wal::durability::{
+    DurabilityManager, Operation, RecoveryPhase, RecoveryProgress, RECOVERY_PROGRESS_INTERVAL,
+};
+use tempfile::tempdir;
+
+fn recover_with_reports(wal_path: &str, sstable_dir: &str) -> Vec<RecoveryProgress> {
+    let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+    let mut reports = Vec::new();
+    manager
+        .recover_from_crash_with_progress(|progress| reports.push(progress.clone()))
+        .unwrap();
+    reports
+}
+
+#[test]
+fn test_replay_reports_progress() {
+    let dir = tempdir().unwrap();
+    let sstable_dir = dir.path().to_str().unwrap();
+    let wal_path = dir.path().join("wal.log");
+    let wal_path = wal_path.to_str().unwrap();
+
+    let records = 2 * RECOVERY_PROGRESS_INTERVAL + 10;
+    {
+        let mut manager = DurabilityManager::new(wal_path, sstable_dir).unwrap();
+        for i in 0..records {
+            manager
+                .log_operation_with_sync(
+                    Operation::Insert {
+                        key: format!("key{}", i),
+                        value: b"value".to_vec(),
+                    },
+                    false,
+                )
+                .unwrap();
+        }
+        manager.sync().unwrap();
+    }
+
+    let reports = recover_with_reports(wal_path, sstable_dir);
+    let replay: Vec<&RecoveryProgress> = reports
+        .iter()
+        .filter(|report| report.phase == RecoveryPhase::ReplayingWal)
+        .collect();
+    for r in &reports {
+        println!("{:?}", r);
+    }
+
+    // One report as replay starts, one per interval, and one at the end
+    assert_eq!(replay.len(), 4);
+    assert_eq!(replay[0].segment, wal_path);
+    assert!(replay[0].total_bytes > 0);
+    assert_eq!(replay[0].bytes_replayed, 0);
+    assert!(replay
+        .windows(2)
+        .all(|pair| pair[0].bytes_replayed <= pair[1].bytes_replayed));
+
+    let last = replay.last().unwrap();
+    assert_eq!(last.records_applied + last.records_failed, records);
+    assert_eq!(last.bytes_replayed, last.total_bytes);
+    assert_eq!(reports.last().unwrap().phase, RecoveryPhase::Complete);
+}
+
+#[test]
+fn test_empty_wal_still_reports_phases() {
+    let dir = tempdir().unwrap();
+    let sstable_dir = dir.path().to_str().unwrap();
+    let wal_path = dir.path().join("wal.log");
+
+    let reports = recover_with_reports(wal_path.to_str().unwrap(), sstable_dir);
+    let phases: Vec<RecoveryPhase> = reports.iter().map(|report| report.phase).collect();
+    assert_eq!(
+        phases,
+        vec![
+            RecoveryPhase::ReplayingWal,
+            RecoveryPhase::ReplayingWal,
+            RecoveryPhase::WritingCheckpoint,
+            RecoveryPhase::Complete,
+        ]
+    );
+}